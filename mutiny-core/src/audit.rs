@@ -0,0 +1,143 @@
+use crate::error::MutinyError;
+use crate::storage::MutinyStorage;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The ways other apps can use the wallet
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integration {
+    NostrWalletConnect,
+    /// A receive-only Nostr Wallet Connect credential
+    ReceiveCredential,
+    /// A [crate::pos::PosHandle]
+    PointOfSale,
+    /// The WebLN provider in the wasm bindings
+    WebLn,
+}
+
+/// Something a connected app did with the wallet
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IntegrationAuditRecord {
+    pub timestamp: u64,
+    pub integration: Integration,
+    /// Who made the request, the nostr pubkey of the app when there is one
+    pub client: String,
+    /// What was requested, like `pay_invoice`
+    pub action: String,
+    pub amount_sats: Option<u64>,
+    pub success: bool,
+    /// Why the action failed or was refused
+    pub error: Option<String>,
+}
+
+impl IntegrationAuditRecord {
+    pub fn new(
+        integration: Integration,
+        client: String,
+        action: &str,
+        amount_sats: Option<u64>,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            timestamp: utils::now().as_secs(),
+            integration,
+            client,
+            action: action.to_string(),
+            amount_sats,
+            success: error.is_none(),
+            error,
+        }
+    }
+}
+
+pub trait IntegrationAuditStorage {
+    fn append_integration_audit_record(
+        &self,
+        record: &IntegrationAuditRecord,
+    ) -> Result<(), MutinyError>;
+    /// Returns every audit record, newest first
+    fn get_integration_audit_log(&self) -> Result<Vec<IntegrationAuditRecord>, MutinyError>;
+}
+
+const INTEGRATION_AUDIT_PREFIX: &str = "integration_audit/";
+
+impl<S: MutinyStorage> IntegrationAuditStorage for S {
+    fn append_integration_audit_record(
+        &self,
+        record: &IntegrationAuditRecord,
+    ) -> Result<(), MutinyError> {
+        // records are never updated, the uuid keeps ones from the same second apart
+        let key = format!(
+            "{INTEGRATION_AUDIT_PREFIX}{}_{}",
+            record.timestamp,
+            uuid::Uuid::new_v4()
+        );
+        self.set_data(key, record)
+    }
+
+    fn get_integration_audit_log(&self) -> Result<Vec<IntegrationAuditRecord>, MutinyError> {
+        let map: HashMap<String, IntegrationAuditRecord> =
+            self.scan(INTEGRATION_AUDIT_PREFIX, None)?;
+        let mut records: Vec<IntegrationAuditRecord> = map.into_values().collect();
+        records.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_integration_audit_log() {
+        let test_name = "test_integration_audit_log";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        assert!(storage.get_integration_audit_log().unwrap().is_empty());
+
+        let mut paid = IntegrationAuditRecord::new(
+            Integration::NostrWalletConnect,
+            "client".to_string(),
+            "pay_invoice",
+            Some(1_000),
+            None,
+        );
+        paid.timestamp = 1;
+        let mut refused = IntegrationAuditRecord::new(
+            Integration::NostrWalletConnect,
+            "client".to_string(),
+            "pay_invoice",
+            Some(1_000_000),
+            Some("Invoice amount too high".to_string()),
+        );
+        refused.timestamp = 1;
+        let mut invoice = IntegrationAuditRecord::new(
+            Integration::ReceiveCredential,
+            "donations".to_string(),
+            "make_invoice",
+            Some(21),
+            None,
+        );
+        invoice.timestamp = 2;
+        assert!(paid.success);
+        assert!(!refused.success);
+
+        // records from the same second are all kept
+        storage.append_integration_audit_record(&paid).unwrap();
+        storage.append_integration_audit_record(&refused).unwrap();
+        storage.append_integration_audit_record(&invoice).unwrap();
+
+        let log = storage.get_integration_audit_log().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0], invoice);
+        assert!(log.contains(&paid));
+        assert!(log.contains(&refused));
+    }
+}
@@ -509,6 +509,7 @@ pub async fn process_events_async<
     R: 'static + Deref,
     G: 'static + Deref<Target = NetworkGraph<L>>,
     L: 'static + Deref,
+    PL: 'static + Deref,
     P: 'static + Deref,
     Descriptor: 'static + SocketDescriptor,
     CMH: 'static + Deref,
@@ -522,7 +523,7 @@ pub async fn process_events_async<
     PGS: 'static + Deref<Target = P2PGossipSync<G, UL, L>>,
     RGS: 'static + Deref<Target = RapidGossipSync<G, L>>,
     UMH: 'static + Deref,
-    PM: 'static + Deref<Target = PeerManager<Descriptor, CMH, RMH, OMH, L, UMH, NS>>,
+    PM: 'static + Deref<Target = PeerManager<Descriptor, CMH, RMH, OMH, PL, UMH, NS>>,
     S: 'static + Deref<Target = SC>,
     SC: for<'b> WriteableScore<'b>,
     SleepFuture: core::future::Future<Output = bool> + core::marker::Unpin,
@@ -550,6 +551,7 @@ where
     F::Target: 'static + FeeEstimator,
     R::Target: 'static + Router,
     L::Target: 'static + Logger,
+    PL::Target: 'static + Logger,
     P::Target: 'static + Persist<<SP::Target as SignerProvider>::Signer>,
     CMH::Target: 'static + ChannelMessageHandler,
     OMH::Target: 'static + OnionMessageHandler,
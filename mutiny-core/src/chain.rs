@@ -44,6 +44,11 @@ impl<S: MutinyStorage> Filter for MutinyChain<S> {
 
 impl<S: MutinyStorage> BroadcasterInterface for MutinyChain<S> {
     fn broadcast_transaction(&self, tx: &Transaction) {
+        // keep it around so it is rebroadcast if it falls out of the mempool
+        if let Err(e) = self.wallet.storage.add_broadcast_transaction(tx) {
+            log_warn!(self.logger, "Could not save broadcast transaction: {e}");
+        }
+
         let tx_clone = tx.clone();
         let wallet = self.wallet.clone();
         let logger = self.logger.clone();
@@ -0,0 +1,70 @@
+use crate::error::MutinyError;
+use futures::lock::{Mutex, MutexGuard};
+
+/// The parts of the wallet that can't safely be used by two operations at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Resource {
+    /// Generating new addresses and invoices and labeling them
+    Addresses,
+    /// The chain and routing data that is updated while syncing
+    Sync,
+}
+
+/// Makes sure operations that touch the same [Resource] don't run at the same time.
+///
+/// Operations that can just run one after the other should wait with
+/// [OperationCoordinator::lock]. Operations that would undo each other's work
+/// should use [OperationCoordinator::try_lock] so the caller gets
+/// [MutinyError::Busy] instead.
+#[derive(Default)]
+pub(crate) struct OperationCoordinator {
+    addresses: Mutex<()>,
+    sync: Mutex<()>,
+}
+
+impl OperationCoordinator {
+    fn mutex(&self, resource: Resource) -> &Mutex<()> {
+        match resource {
+            Resource::Addresses => &self.addresses,
+            Resource::Sync => &self.sync,
+        }
+    }
+
+    /// Waits until no other operation is using the resource
+    pub async fn lock(&self, resource: Resource) -> MutexGuard<'_, ()> {
+        self.mutex(resource).lock().await
+    }
+
+    /// Returns [MutinyError::Busy] if another operation is using the resource
+    pub fn try_lock(&self, resource: Resource) -> Result<MutexGuard<'_, ()>, MutinyError> {
+        self.mutex(resource).try_lock().ok_or(MutinyError::Busy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    async fn test_operation_coordinator() {
+        let test_name = "test_operation_coordinator";
+        log!("{}", test_name);
+
+        let coordinator = OperationCoordinator::default();
+
+        let guard = coordinator.lock(Resource::Sync).await;
+        assert!(matches!(
+            coordinator.try_lock(Resource::Sync),
+            Err(MutinyError::Busy)
+        ));
+        // other resources are not affected
+        assert!(coordinator.try_lock(Resource::Addresses).is_ok());
+
+        drop(guard);
+        assert!(coordinator.try_lock(Resource::Sync).is_ok());
+    }
+}
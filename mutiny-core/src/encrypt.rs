@@ -1,9 +1,102 @@
+use crate::error::MutinyError;
 use aes_gcm::aead::Aead;
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use anyhow::anyhow;
+use argon2::Argon2;
 use pbkdf2::password_hash::Output;
 use pbkdf2::password_hash::{PasswordHasher, Salt, SaltString};
 use pbkdf2::{Params, Pbkdf2};
 
+/// Values encrypted with a [Cipher] are prefixed with this so they can be
+/// told apart from values encrypted with the legacy pbkdf2 scheme.
+const CIPHER_PREFIX: &str = "enc2:";
+
+/// An AES-256-GCM key derived from the user's password with argon2id.
+///
+/// The key is derived once and reused for every value, each value
+/// gets its own random nonce.
+#[derive(Clone)]
+pub struct Cipher {
+    /// Kept to decrypt values written with the legacy scheme
+    password: Option<String>,
+    key: [u8; 32],
+}
+
+impl Cipher {
+    pub fn new(password: &str, salt: &[u8]) -> Result<Self, MutinyError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|e| MutinyError::Other(anyhow!("Failed to derive key: {e}")))?;
+
+        Ok(Self {
+            password: Some(password.to_string()),
+            key,
+        })
+    }
+
+    /// Creates a cipher from a key that is already uniformly random,
+    /// such as one derived from the seed.
+    pub fn from_key(key: [u8; 32]) -> Self {
+        Self {
+            password: None,
+            key,
+        }
+    }
+
+    pub fn encrypt(&self, content: &str) -> Result<String, MutinyError> {
+        let mut iv = [0u8; 12];
+        getrandom::getrandom(&mut iv)
+            .map_err(|e| MutinyError::Other(anyhow!("Failed to generate nonce: {e}")))?;
+
+        let cipher = Aes256Gcm::new_from_slice(&self.key)
+            .map_err(|e| MutinyError::Other(anyhow!("Invalid key: {e}")))?;
+        let nonce = Nonce::from_slice(&iv);
+        let mut bytes = cipher
+            .encrypt(nonce, content.as_bytes())
+            .map_err(|e| MutinyError::Other(anyhow!("Failed to encrypt: {e}")))?;
+
+        let mut combined = iv.to_vec();
+        combined.append(&mut bytes);
+        Ok(format!("{CIPHER_PREFIX}{}", base64::encode(combined)))
+    }
+
+    /// Decrypts a value, this also handles values encrypted with the legacy scheme.
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, MutinyError> {
+        let Some(encoded) = encrypted.strip_prefix(CIPHER_PREFIX) else {
+            return match self.password.as_ref() {
+                Some(password) => decrypt(encrypted, password),
+                None => Err(MutinyError::IncorrectPassword),
+            };
+        };
+
+        let buffer = base64::decode(encoded).map_err(|_| MutinyError::IncorrectPassword)?;
+        if buffer.len() < 12 {
+            return Err(MutinyError::IncorrectPassword);
+        }
+        let (iv, data) = buffer.split_at(12);
+
+        let cipher = Aes256Gcm::new_from_slice(&self.key)
+            .map_err(|e| MutinyError::Other(anyhow!("Invalid key: {e}")))?;
+        let nonce = Nonce::from_slice(iv);
+        let decrypted = cipher
+            .decrypt(nonce, data)
+            .map_err(|_| MutinyError::IncorrectPassword)?;
+        String::from_utf8(decrypted).map_err(|_| MutinyError::IncorrectPassword)
+    }
+}
+
+impl core::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
+
+/// Returns if the value was encrypted with a [Cipher]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(CIPHER_PREFIX)
+}
+
 pub fn encrypt(content: &str, password: &str) -> String {
     let mut salt = [0u8; 16];
     getrandom::getrandom(&mut salt).unwrap();
@@ -24,9 +117,11 @@ pub fn encrypt(content: &str, password: &str) -> String {
     base64::encode(combined.as_slice())
 }
 
-pub fn decrypt(encrypted: &str, password: &str) -> String {
-    let buffer = base64::decode(encrypted)
-        .unwrap_or_else(|_| panic!("Error reading ciphertext: {encrypted}"));
+pub fn decrypt(encrypted: &str, password: &str) -> Result<String, MutinyError> {
+    let buffer = base64::decode(encrypted).map_err(|_| MutinyError::IncorrectPassword)?;
+    if buffer.len() < 28 {
+        return Err(MutinyError::IncorrectPassword);
+    }
     let buffer_slice = buffer.as_slice();
     let salt = &buffer_slice[0..16];
     let iv = &buffer_slice[16..28];
@@ -37,8 +132,10 @@ pub fn decrypt(encrypted: &str, password: &str) -> String {
 
     let cipher = Aes256Gcm::new_from_slice(key).unwrap();
     let nonce = Nonce::from_slice(iv);
-    let decrypted = cipher.decrypt(nonce, data).unwrap();
-    String::from_utf8(decrypted).unwrap()
+    let decrypted = cipher
+        .decrypt(nonce, data)
+        .map_err(|_| MutinyError::IncorrectPassword)?;
+    String::from_utf8(decrypted).map_err(|_| MutinyError::IncorrectPassword)
 }
 
 fn derive_key(password: &str, salt: &[u8]) -> Output {
@@ -58,7 +155,8 @@ fn derive_key(password: &str, salt: &[u8]) -> Output {
 
 #[cfg(test)]
 mod tests {
-    use crate::encrypt::{decrypt, encrypt};
+    use crate::encrypt::{decrypt, encrypt, is_encrypted, Cipher};
+    use crate::error::MutinyError;
 
     #[test]
     fn test_encryption() {
@@ -67,8 +165,30 @@ mod tests {
         let encrypted = encrypt(content, password);
         println!("{encrypted}");
 
-        let decrypted = decrypt(&encrypted, password);
+        let decrypted = decrypt(&encrypted, password).unwrap();
         println!("{decrypted}");
         assert_eq!(content, decrypted);
     }
+
+    #[test]
+    fn test_cipher() {
+        let salt = [1u8; 16];
+        let content = "hello world";
+        let cipher = Cipher::new("password", &salt).unwrap();
+
+        let encrypted = cipher.encrypt(content).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), content);
+
+        // can still read values from the legacy scheme
+        let legacy = encrypt(content, "password");
+        assert!(!is_encrypted(&legacy));
+        assert_eq!(cipher.decrypt(&legacy).unwrap(), content);
+
+        let wrong = Cipher::new("wrong password", &salt).unwrap();
+        assert!(matches!(
+            wrong.decrypt(&encrypted),
+            Err(MutinyError::IncorrectPassword)
+        ));
+    }
 }
@@ -29,6 +29,15 @@ pub enum MutinyError {
     /// The invoice or address is on a different network
     #[error("The invoice or address is on a different network.")]
     IncorrectNetwork(Network),
+    /// The wallet's saved data is for a different network than the one configured
+    #[error("The wallet was created for a different network.")]
+    NetworkMismatch(Network),
+    /// The wallet's saved data is for a different account index than the one configured
+    #[error("The wallet was created with a different account index.")]
+    AccountIndexMismatch(u32),
+    /// There are still funds on the current network that would be lost by migrating
+    #[error("Move all funds out of the wallet before changing networks.")]
+    NetworkMigrationNotReady,
     /// Payment of the given invoice has already been initiated.
     #[error("An invoice must not get payed twice.")]
     NonUniquePaymentHash,
@@ -38,6 +47,9 @@ pub enum MutinyError {
     /// The given invoice is invalid.
     #[error("The given invoice is invalid.")]
     InvoiceInvalid,
+    /// The invoice expired before it was paid.
+    #[error("The invoice has expired.")]
+    InvoiceExpired,
     /// Invoice creation failed.
     #[error("Failed to create invoice.")]
     InvoiceCreationFailed,
@@ -56,6 +68,18 @@ pub enum MutinyError {
     /// No route for the given target could be found.
     #[error("Failed to find route.")]
     RoutingFailed,
+    /// No route could be found with fees under the given limit.
+    #[error("Failed to find a route with fees under the limit.")]
+    FeeTooHigh,
+    /// The fee rate is below the minimum fee rate nodes will relay transactions at
+    #[error("The fee rate is below the network minimum.")]
+    FeeRateBelowMinimum,
+    /// An output's amount is so small it costs more to spend than it is worth
+    #[error("The amount is below the dust limit.")]
+    AmountBelowDust,
+    /// A swap with Boltz could not be created or completed.
+    #[error("Failed to complete the swap.")]
+    SwapFailed,
     /// A given peer info could not be parsed.
     #[error("Failed to parse the given peer information.")]
     PeerInfoParseFailed,
@@ -110,6 +134,30 @@ pub enum MutinyError {
     /// Error getting the bitcoin price
     #[error("Failed to get the bitcoin price.")]
     BitcoinPriceError,
+    /// Invalid Arguments were given
+    #[error("Invalid Arguments were given")]
+    InvalidArgumentsError,
+    /// The wallet is watch-only and cannot spend funds
+    #[error("This wallet is watch-only.")]
+    WatchOnly,
+    /// The on-chain wallet is signed for by an external signer
+    #[error("On-chain transactions must be signed by the external signer.")]
+    ExternalSigner,
+    /// The password given could not decrypt the storage
+    #[error("Incorrect password entered.")]
+    IncorrectPassword,
+    /// The storage is encrypted and has not been unlocked
+    #[error("The wallet is locked.")]
+    WalletLocked,
+    /// Failed to read from or write to the remote backup server
+    #[error("Failed to sync with the backup server.")]
+    VssError,
+    /// The storage already has a wallet in it
+    #[error("A wallet already exists in this storage.")]
+    WalletExists,
+    /// Another operation that conflicts with this one is in progress
+    #[error("Another operation is in progress, try again later.")]
+    Busy,
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -145,8 +145,8 @@ use lightning::{log_debug, log_error, log_info, log_trace};
 
 use bitcoin::{BlockHash, Script, Txid};
 
-use esplora_client::r#async::AsyncClient;
-use esplora_client::Builder;
+use crate::esplora_failover::FailoverEsploraClient;
+use std::sync::Arc;
 
 use core::ops::Deref;
 use std::collections::HashSet;
@@ -181,14 +181,6 @@ impl<L: Deref> EsploraSyncClient<L>
 where
     L::Target: Logger,
 {
-    /// Returns a new [`EsploraSyncClient`] object.
-    pub fn new(server_url: String, logger: L) -> Self {
-        let builder = Builder::new(&server_url);
-        let client = builder.build_async().unwrap();
-
-        EsploraSyncClient::from_client(client, logger)
-    }
-
     /// Returns a new [`EsploraSyncClient`] object using the given Esplora client.
     pub fn from_client(client: EsploraClientType, logger: L) -> Self {
         let sync_state = MutexType::new(SyncState::new());
@@ -528,7 +520,7 @@ where
 type MutexType<I> = futures::lock::Mutex<I>;
 
 // The underlying client type.
-type EsploraClientType = AsyncClient;
+type EsploraClientType = Arc<FailoverEsploraClient>;
 
 impl<L: Deref> Filter for EsploraSyncClient<L>
 where
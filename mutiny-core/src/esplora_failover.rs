@@ -0,0 +1,317 @@
+use crate::error::MutinyError;
+use crate::logging::MutinyLogger;
+use crate::utils;
+use bitcoin::{BlockHash, BlockHeader, MerkleBlock, Script, Transaction, Txid};
+use esplora_client::{AsyncClient, BlockStatus, Builder, Error, OutputStatus, Tx, TxStatus};
+use lightning::util::logger::*;
+use lightning::{log_debug, log_warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// How many failures in a row before an endpoint is considered unhealthy
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// How long an unhealthy endpoint is skipped before it is tried again, in seconds
+const UNHEALTHY_RETRY_SECS: u64 = 60;
+
+/// The health of a single esplora server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EsploraEndpointHealth {
+    pub url: String,
+    pub consecutive_failures: u32,
+    pub total_successes: u64,
+    pub total_failures: u64,
+    /// Unix timestamp, in seconds, of the last successful request
+    pub last_success: Option<u64>,
+    /// Unix timestamp, in seconds, of the last failed request
+    pub last_failure: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl EsploraEndpointHealth {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            consecutive_failures: 0,
+            total_successes: 0,
+            total_failures: 0,
+            last_success: None,
+            last_failure: None,
+            last_error: None,
+        }
+    }
+
+    /// Unhealthy endpoints are given another chance after [UNHEALTHY_RETRY_SECS]
+    pub fn is_healthy(&self, now: u64) -> bool {
+        self.consecutive_failures < MAX_CONSECUTIVE_FAILURES
+            || self
+                .last_failure
+                .map_or(true, |t| now >= t + UNHEALTHY_RETRY_SECS)
+    }
+}
+
+struct Endpoint {
+    client: AsyncClient,
+    health: RwLock<EsploraEndpointHealth>,
+}
+
+/// An esplora client that spreads requests over multiple servers.
+///
+/// Requests go to the last server that worked. If it errors or times out, the
+/// request is retried on the next healthy server, unhealthy servers are only
+/// tried when nothing else works.
+pub struct FailoverEsploraClient {
+    endpoints: Vec<Endpoint>,
+    /// Index of the endpoint that is tried first
+    current: AtomicUsize,
+    logger: Arc<MutinyLogger>,
+}
+
+impl FailoverEsploraClient {
+    pub fn new(urls: Vec<String>, logger: Arc<MutinyLogger>) -> Result<Self, MutinyError> {
+        Self::with_proxy(urls, None, logger)
+    }
+
+    /// Like [FailoverEsploraClient::new], but requests go through the given SOCKS5 proxy.
+    /// The proxy is ignored on wasm, where the browser handles connections.
+    pub fn with_proxy(
+        urls: Vec<String>,
+        socks_proxy: Option<&str>,
+        logger: Arc<MutinyLogger>,
+    ) -> Result<Self, MutinyError> {
+        if urls.is_empty() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                let builder = match socks_proxy {
+                    Some(proxy) => Builder::new(&url).proxy(&format!("socks5h://{proxy}")),
+                    None => Builder::new(&url),
+                };
+                let client = builder.build_async()?;
+                Ok(Endpoint {
+                    client,
+                    health: RwLock::new(EsploraEndpointHealth::new(url)),
+                })
+            })
+            .collect::<Result<Vec<_>, MutinyError>>()?;
+
+        Ok(Self {
+            endpoints,
+            current: AtomicUsize::new(0),
+            logger,
+        })
+    }
+
+    /// The client for the server that is currently preferred
+    pub fn client(&self) -> &AsyncClient {
+        &self.endpoints[self.current.load(Ordering::Relaxed)].client
+    }
+
+    /// The url of the server that is currently preferred
+    pub fn url(&self) -> &str {
+        self.client().url()
+    }
+
+    /// Returns the health of every server
+    pub fn health(&self) -> Vec<EsploraEndpointHealth> {
+        self.endpoints
+            .iter()
+            .filter_map(|e| e.health.read().ok().map(|h| h.clone()))
+            .collect()
+    }
+
+    /// The order to try the endpoints in, starting from the current one
+    /// with healthy endpoints before unhealthy ones.
+    fn attempt_order(&self) -> Vec<usize> {
+        let now = utils::now().as_secs();
+        let current = self.current.load(Ordering::Relaxed);
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..self.endpoints.len())
+            .map(|i| (current + i) % self.endpoints.len())
+            .partition(|&i| {
+                self.endpoints[i]
+                    .health
+                    .read()
+                    .map(|h| h.is_healthy(now))
+                    .unwrap_or(true)
+            });
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    fn record_success(&self, index: usize) {
+        if let Ok(mut health) = self.endpoints[index].health.write() {
+            health.consecutive_failures = 0;
+            health.total_successes += 1;
+            health.last_success = Some(utils::now().as_secs());
+        }
+        self.current.store(index, Ordering::Relaxed);
+    }
+
+    fn record_failure(&self, index: usize, error: &Error) {
+        if let Ok(mut health) = self.endpoints[index].health.write() {
+            health.consecutive_failures += 1;
+            health.total_failures += 1;
+            health.last_failure = Some(utils::now().as_secs());
+            health.last_error = Some(error.to_string());
+            log_warn!(
+                self.logger,
+                "Esplora request to {} failed: {error}",
+                health.url
+            );
+        }
+    }
+
+    /// Runs the request on each server until one succeeds. Errors that are not
+    /// the server's fault, like a rejected transaction, are returned right away.
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T, Error>
+    where
+        F: Fn(AsyncClient) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut last_error = None;
+        for index in self.attempt_order() {
+            match request(self.endpoints[index].client.clone()).await {
+                Ok(res) => {
+                    self.record_success(index);
+                    return Ok(res);
+                }
+                Err(e) if is_server_error(&e) => {
+                    self.record_failure(index, &e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        log_debug!(self.logger, "All esplora servers failed");
+        Err(last_error.expect("there is always at least one endpoint"))
+    }
+
+    pub async fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, Error> {
+        self.call(|c| async move { c.get_tx(txid).await }).await
+    }
+
+    pub async fn get_tx_status(&self, txid: &Txid) -> Result<Option<TxStatus>, Error> {
+        self.call(|c| async move { c.get_tx_status(txid).await })
+            .await
+    }
+
+    pub async fn get_output_status(
+        &self,
+        txid: &Txid,
+        index: u64,
+    ) -> Result<Option<OutputStatus>, Error> {
+        self.call(|c| async move { c.get_output_status(txid, index).await })
+            .await
+    }
+
+    pub async fn get_header_by_hash(&self, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
+        self.call(|c| async move { c.get_header_by_hash(block_hash).await })
+            .await
+    }
+
+    pub async fn get_block_status(&self, block_hash: &BlockHash) -> Result<BlockStatus, Error> {
+        self.call(|c| async move { c.get_block_status(block_hash).await })
+            .await
+    }
+
+    pub async fn get_merkle_block(&self, txid: &Txid) -> Result<Option<MerkleBlock>, Error> {
+        self.call(|c| async move { c.get_merkle_block(txid).await })
+            .await
+    }
+
+    pub async fn get_tip_hash(&self) -> Result<BlockHash, Error> {
+        self.call(|c| async move { c.get_tip_hash().await }).await
+    }
+
+    pub async fn get_height(&self) -> Result<u32, Error> {
+        self.call(|c| async move { c.get_height().await }).await
+    }
+
+    pub async fn broadcast(&self, transaction: &Transaction) -> Result<(), Error> {
+        self.call(|c| async move { c.broadcast(transaction).await })
+            .await
+    }
+
+    pub async fn get_fee_estimates(&self) -> Result<HashMap<String, f64>, Error> {
+        self.call(|c| async move { c.get_fee_estimates().await })
+            .await
+    }
+
+    pub async fn scripthash_txs(
+        &self,
+        script: &Script,
+        last_seen: Option<Txid>,
+    ) -> Result<Vec<Tx>, Error> {
+        self.call(|c| async move { c.scripthash_txs(script, last_seen).await })
+            .await
+    }
+}
+
+/// Returns if the error means the server is unreachable or broken,
+/// rather than the request being invalid.
+fn is_server_error(error: &Error) -> bool {
+    match error {
+        Error::Reqwest(_) => true,
+        Error::HttpResponse(status) => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_failover_order() {
+        let test_name = "test_failover_order";
+        log!("{}", test_name);
+
+        let client = FailoverEsploraClient::new(
+            vec![
+                "https://mempool.space/api".to_string(),
+                "https://blockstream.info/api".to_string(),
+            ],
+            Arc::new(MutinyLogger::default()),
+        )
+        .unwrap();
+        assert_eq!(client.url(), "https://mempool.space/api");
+        assert_eq!(client.attempt_order(), vec![0, 1]);
+
+        // the first server keeps failing so it is tried last
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            client.record_failure(0, &Error::HttpResponse(503));
+        }
+        assert_eq!(client.attempt_order(), vec![1, 0]);
+        assert!(!client.health()[0].is_healthy(utils::now().as_secs()));
+
+        // a success makes it the preferred server
+        client.record_success(1);
+        assert_eq!(client.url(), "https://blockstream.info/api");
+        assert_eq!(client.health()[1].total_successes, 1);
+
+        // and an unhealthy server gets another chance after a while
+        let later = utils::now().as_secs() + UNHEALTHY_RETRY_SECS;
+        assert!(client.health()[0].is_healthy(later));
+    }
+
+    #[test]
+    fn test_no_endpoints() {
+        let test_name = "test_no_endpoints";
+        log!("{}", test_name);
+
+        assert!(FailoverEsploraClient::new(vec![], Arc::new(MutinyLogger::default())).is_err());
+        assert!(is_server_error(&Error::HttpResponse(502)));
+        assert!(!is_server_error(&Error::HttpResponse(400)));
+    }
+}
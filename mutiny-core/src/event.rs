@@ -1,18 +1,27 @@
 use crate::fees::MutinyFeeEstimator;
 use crate::keymanager::PhantomKeysManager;
-use crate::ldkstorage::{MutinyNodePersister, PhantomChannelManager};
+use crate::ldkstorage::{ClaimIntent, MutinyNodePersister, PhantomChannelManager};
 use crate::logging::MutinyLogger;
-use crate::nodemanager::ChannelClosure;
+use crate::lspclient::{LspOrderChannel, LspOrderStatus, LspSelection};
+use crate::node::ChainMonitor;
+use crate::nodemanager::{
+    ChannelAcceptancePolicy, ChannelClosure, ChannelClosureKind, ChannelForensics,
+    ForwardedPayment, MonitorBalance,
+};
+use crate::notifier::{EventNotifier, MutinyEvent};
 use crate::onchain::OnChainWallet;
-use crate::redshift::RedshiftStorage;
+use crate::redshift::{RedshiftStatus, RedshiftStorage};
 use crate::storage::MutinyStorage;
-use crate::utils::sleep;
+use crate::utils::{self, sleep};
 use anyhow::anyhow;
-use bitcoin::hashes::hex::ToHex;
+use async_trait::async_trait;
+use bitcoin::hashes::hex::{FromHex, ToHex};
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::secp256k1::Secp256k1;
+use bitcoin::Txid;
 use lightning::chain::keysinterface::SpendableOutputDescriptor;
-use lightning::events::{Event, PaymentPurpose};
+use lightning::events::{ClosureReason, Event, HTLCDestination, PaymentPurpose};
+use lightning::ln::PaymentHash;
 use lightning::{
     chain::chaininterface::{ConfirmationTarget, FeeEstimator},
     log_debug, log_error, log_info, log_warn,
@@ -21,7 +30,8 @@ use lightning::{
 };
 use lightning_invoice::Invoice;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct PaymentInfo {
@@ -38,9 +48,40 @@ pub(crate) struct PaymentInfo {
     pub bolt11: Option<Invoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub payee_pubkey: Option<PublicKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<PaymentFailureReason>,
+    /// The LSP that wrapped the invoice, for invoices we created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsp_selection: Option<LspSelection>,
+    /// The parts an outgoing payment was split into, added as each one arrives
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<PaymentPart>,
     pub last_update: u64,
 }
 
+/// One part of a multi-path payment, and the channel of ours it went out of
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PaymentPart {
+    pub amount_msats: u64,
+    pub fee_msats: u64,
+    pub short_channel_id: u64,
+    /// The funding outpoint of the channel, if it is still open
+    pub channel: Option<String>,
+}
+
+/// Why a lightning payment did not go through
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaymentFailureReason {
+    /// No route to the recipient could be found, or every route tried failed
+    NoRoute,
+    /// The payment was still in flight when we stopped waiting for it
+    Timeout,
+    /// The recipient rejected the payment
+    Rejected,
+    /// The invoice expired before it was paid
+    Expired,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct MillisatAmount(pub Option<u64>);
 
@@ -58,34 +99,63 @@ pub(crate) enum HTLCStatus {
     Failed,
 }
 
+/// An inbound channel a [ChannelAcceptor] is asked to accept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChannelRequest {
+    pub counterparty: PublicKey,
+    pub funding_satoshis: u64,
+    pub push_msat: u64,
+    /// The reserve the peer wants us to always keep in the channel, in sats
+    pub our_reserve_sats: Option<u64>,
+}
+
+/// Decides on inbound channels that pass the [ChannelAcceptancePolicy], like by
+/// prompting the user. Channels we asked for, from our LSPs or with LNURL-channel,
+/// are accepted without asking.
+#[async_trait(?Send)]
+pub trait ChannelAcceptor: Send + Sync {
+    /// Returns true to accept the channel, false to reject it
+    async fn accept_channel(&self, request: ChannelRequest) -> bool;
+}
+
 #[derive(Clone)]
 pub struct EventHandler<S: MutinyStorage> {
     channel_manager: Arc<PhantomChannelManager<S>>,
+    chain_monitor: Arc<ChainMonitor<S>>,
     fee_estimator: Arc<MutinyFeeEstimator<S>>,
     wallet: Arc<OnChainWallet<S>>,
     keys_manager: Arc<PhantomKeysManager<S>>,
     persister: Arc<MutinyNodePersister<S>>,
-    lsp_client_pubkey: Option<PublicKey>,
+    pending_lnurl_channels: Arc<Mutex<HashSet<PublicKey>>>,
+    channel_acceptor: Arc<RwLock<Option<Arc<dyn ChannelAcceptor>>>>,
+    notifier: Arc<EventNotifier>,
     logger: Arc<MutinyLogger>,
 }
 
 impl<S: MutinyStorage> EventHandler<S> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         channel_manager: Arc<PhantomChannelManager<S>>,
+        chain_monitor: Arc<ChainMonitor<S>>,
         fee_estimator: Arc<MutinyFeeEstimator<S>>,
         wallet: Arc<OnChainWallet<S>>,
         keys_manager: Arc<PhantomKeysManager<S>>,
         persister: Arc<MutinyNodePersister<S>>,
-        lsp_client_pubkey: Option<PublicKey>,
+        pending_lnurl_channels: Arc<Mutex<HashSet<PublicKey>>>,
+        channel_acceptor: Arc<RwLock<Option<Arc<dyn ChannelAcceptor>>>>,
+        notifier: Arc<EventNotifier>,
         logger: Arc<MutinyLogger>,
     ) -> Self {
         Self {
             channel_manager,
+            chain_monitor,
             fee_estimator,
             wallet,
             keys_manager,
-            lsp_client_pubkey,
+            pending_lnurl_channels,
+            channel_acceptor,
             persister,
+            notifier,
             logger,
         }
     }
@@ -118,11 +188,21 @@ impl<S: MutinyStorage> EventHandler<S> {
                     ),
                     Some(params) => {
                         log_debug!(self.logger, "Opening channel with params: {params:?}");
-                        let psbt = self.wallet.create_sweep_psbt_to_output(
-                            &params.utxos,
-                            output_script,
-                            channel_value_satoshis,
-                        );
+                        let psbt = if params.utxos.is_empty() {
+                            // 250 weight units per vbyte
+                            let fee_rate = params.sats_per_kw as f32 / 250.0;
+                            self.wallet.create_signed_psbt_to_spk(
+                                output_script,
+                                channel_value_satoshis,
+                                Some(fee_rate),
+                            )
+                        } else {
+                            self.wallet.create_sweep_psbt_to_output(
+                                &params.utxos,
+                                output_script,
+                                channel_value_satoshis,
+                            )
+                        };
 
                         // delete from storage, if it fails, it is fine, just log it.
                         if let Err(e) = self.persister.delete_channel_open_params(user_channel_id) {
@@ -185,19 +265,48 @@ impl<S: MutinyStorage> EventHandler<S> {
                 payment_hash,
                 purpose,
                 amount_msat,
+                via_channel_id,
+                claim_deadline,
                 ..
             } => {
                 log_debug!(self.logger, "EVENT: PaymentReceived received payment from payment hash {} of {amount_msat} millisatoshis to {receiver_node_id:?}", payment_hash.0.to_hex());
 
-                if let Some(payment_preimage) = match purpose {
+                // reject payments that are below the user's configured minimum
+                let min_payment_msat = self
+                    .persister
+                    .storage
+                    .get_inbound_payment_config()
+                    .unwrap_or_default()
+                    .min_payment_msat();
+
+                if let Some(channel_id) = via_channel_id {
+                    self.verify_lsp_order(&payment_hash, channel_id);
+                }
+
+                if amount_msat < min_payment_msat {
+                    log_warn!(self.logger, "EVENT: PaymentReceived rejected payment from payment hash {} of {amount_msat} millisatoshis, below minimum of {min_payment_msat} millisatoshis", payment_hash.0.to_hex());
+                    self.channel_manager.fail_htlc_backwards(&payment_hash);
+                } else if let Some(payment_preimage) = match purpose {
                     PaymentPurpose::InvoicePayment {
                         payment_preimage, ..
                     } => payment_preimage,
                     PaymentPurpose::SpontaneousPayment(preimage) => Some(preimage),
                 } {
+                    // record the claim before starting it so it can be finished
+                    // on the next start if we are shut down in the middle of it
+                    let intent = ClaimIntent {
+                        preimage: payment_preimage.0,
+                        amount_msat,
+                        created_at: crate::utils::now().as_secs(),
+                        claim_deadline,
+                    };
+                    if let Err(e) = self.persister.persist_claim_intent(&payment_hash, &intent) {
+                        log_error!(self.logger, "ERROR: could not persist claim intent: {e}");
+                    }
                     self.channel_manager.claim_funds(payment_preimage);
                 } else {
-                    log_error!(self.logger, "ERROR: No payment preimage found");
+                    // we don't know the preimage of hodl invoices
+                    self.hold_payment(payment_hash, amount_msat);
                 };
             }
             Event::PaymentClaimed {
@@ -224,7 +333,9 @@ impl<S: MutinyStorage> EventHandler<S> {
                         let payment_preimage = payment_preimage.map(|p| p.0);
                         let payment_secret = payment_secret.map(|p| p.0);
                         saved_payment_info.status = HTLCStatus::Succeeded;
-                        saved_payment_info.preimage = payment_preimage;
+                        // hodl invoices get their preimage when they are settled
+                        saved_payment_info.preimage =
+                            payment_preimage.or(saved_payment_info.preimage);
                         saved_payment_info.secret = payment_secret;
                         saved_payment_info.amt_msat = MillisatAmount(Some(amount_msat));
                         saved_payment_info.last_update = crate::utils::now().as_secs();
@@ -252,6 +363,9 @@ impl<S: MutinyStorage> EventHandler<S> {
                             amt_msat: MillisatAmount(Some(amount_msat)),
                             fee_paid_msat: None,
                             payee_pubkey: receiver_node_id,
+                            failure_reason: None,
+                            lsp_selection: None,
+                            parts: vec![],
                             bolt11: None,
                             last_update,
                         };
@@ -268,6 +382,16 @@ impl<S: MutinyStorage> EventHandler<S> {
                         }
                     }
                 }
+
+                // the payment is stored as succeeded, the claim no longer needs to be retried
+                if let Err(e) = self.persister.delete_claim_intent(&payment_hash) {
+                    log_warn!(self.logger, "WARN: could not delete claim intent: {e}");
+                }
+
+                self.notifier.notify(MutinyEvent::PaymentReceived {
+                    payment_hash: payment_hash.0.to_hex(),
+                    amount_msats: amount_msat,
+                });
             }
             Event::PaymentSent {
                 payment_preimage,
@@ -289,6 +413,7 @@ impl<S: MutinyStorage> EventHandler<S> {
                         saved_payment_info.status = HTLCStatus::Succeeded;
                         saved_payment_info.preimage = Some(payment_preimage.0);
                         saved_payment_info.fee_paid_msat = fee_paid_msat;
+                        saved_payment_info.failure_reason = None;
                         saved_payment_info.last_update = crate::utils::now().as_secs();
                         match self.persister.persist_payment_info(
                             &payment_hash,
@@ -310,10 +435,17 @@ impl<S: MutinyStorage> EventHandler<S> {
                         );
                     }
                 }
+
+                self.notifier.notify(MutinyEvent::PaymentSent {
+                    payment_hash: payment_hash.0.to_hex(),
+                    fees_paid_msats: fee_paid_msat,
+                });
             }
             Event::OpenChannelRequest {
                 temporary_channel_id,
                 counterparty_node_id,
+                funding_satoshis,
+                push_msat,
                 ..
             } => {
                 log_debug!(
@@ -335,8 +467,102 @@ impl<S: MutinyStorage> EventHandler<S> {
                     Err(e) => log_debug!(self.logger, "EVENT: OpenChannelRequest error: {e:?}"),
                 };
 
-                if self.lsp_client_pubkey.as_ref() != Some(&counterparty_node_id) {
-                    // did not match the lsp pubkey, normal open
+                // a channel we asked for with LNURL-channel, accepted like any other open
+                let requested = self
+                    .pending_lnurl_channels
+                    .lock()
+                    .map(|mut pending| pending.remove(&counterparty_node_id))
+                    .unwrap_or(false);
+                if requested {
+                    log_info!(
+                        self.logger,
+                        "EVENT: OpenChannelRequest is the LNURL channel we requested"
+                    );
+                }
+
+                // only the LSP we ordered a channel from for an invoice is trusted with zero-conf
+                let from_lsp = self.has_pending_lsp_order(&counterparty_node_id);
+
+                if !requested && !from_lsp {
+                    let mut num_channels = 0;
+                    let mut our_reserve_sats = None;
+                    for channel in self.channel_manager.list_channels() {
+                        if channel.channel_id == temporary_channel_id {
+                            our_reserve_sats = channel.unspendable_punishment_reserve;
+                        } else {
+                            num_channels += 1;
+                        }
+                    }
+                    let request = ChannelRequest {
+                        counterparty: counterparty_node_id,
+                        funding_satoshis,
+                        push_msat,
+                        our_reserve_sats,
+                    };
+
+                    let policy = match self.persister.storage.get_channel_acceptance_policy() {
+                        Ok(policy) => policy.unwrap_or_default(),
+                        Err(e) => {
+                            log_error!(
+                                self.logger,
+                                "Failed to read channel acceptance policy: {e}"
+                            );
+                            ChannelAcceptancePolicy::default()
+                        }
+                    };
+                    if let Err(reason) = policy.check(&request, num_channels) {
+                        log_info!(self.logger, "EVENT: OpenChannelRequest rejected, {reason}");
+                        reject_inbound_channel(
+                            &self.channel_manager,
+                            &temporary_channel_id,
+                            &counterparty_node_id,
+                            &self.logger,
+                        );
+                        return;
+                    }
+
+                    let acceptor = self
+                        .channel_acceptor
+                        .read()
+                        .ok()
+                        .and_then(|acceptor| acceptor.clone());
+                    if let Some(acceptor) = acceptor {
+                        // ask in the background so other events don't wait on the answer
+                        let channel_manager = self.channel_manager.clone();
+                        let logger = self.logger.clone();
+                        utils::spawn(async move {
+                            if acceptor.accept_channel(request).await {
+                                match channel_manager.accept_inbound_channel(
+                                    &temporary_channel_id,
+                                    &counterparty_node_id,
+                                    internal_channel_id,
+                                ) {
+                                    Ok(_) => {
+                                        log_debug!(logger, "EVENT: OpenChannelRequest accepted")
+                                    }
+                                    Err(e) => {
+                                        log_debug!(logger, "EVENT: OpenChannelRequest error: {e:?}")
+                                    }
+                                }
+                            } else {
+                                log_info!(
+                                    logger,
+                                    "EVENT: OpenChannelRequest rejected by the acceptor"
+                                );
+                                reject_inbound_channel(
+                                    &channel_manager,
+                                    &temporary_channel_id,
+                                    &counterparty_node_id,
+                                    &logger,
+                                );
+                            }
+                        });
+                        return;
+                    }
+                }
+
+                if !from_lsp {
+                    // not a channel we ordered from an lsp, normal open
                     let result = self.channel_manager.accept_inbound_channel(
                         &temporary_channel_id,
                         &counterparty_node_id,
@@ -344,7 +570,7 @@ impl<S: MutinyStorage> EventHandler<S> {
                     );
                     log_result(result);
                 } else {
-                    // matched lsp pubkey, accept 0 conf
+                    // the channel we ordered from the lsp, accept 0 conf
                     let result = self
                         .channel_manager
                         .accept_inbound_channel_from_trusted_peer_0conf(
@@ -355,11 +581,79 @@ impl<S: MutinyStorage> EventHandler<S> {
                     log_result(result);
                 }
             }
-            Event::PaymentPathSuccessful { .. } => {
-                log_debug!(self.logger, "EVENT: PaymentPathSuccessful, ignored");
+            Event::PaymentPathSuccessful {
+                payment_id,
+                payment_hash,
+                path,
+            } => {
+                log_debug!(self.logger, "EVENT: PaymentPathSuccessful");
+
+                // invoice payments use the payment hash as their id
+                let payment_hash = payment_hash.unwrap_or(PaymentHash(payment_id.0));
+                let (Some(first_hop), Some(last_hop)) = (path.hops.first(), path.hops.last())
+                else {
+                    return;
+                };
+                let short_channel_id = first_hop.short_channel_id;
+                let channel = self
+                    .channel_manager
+                    .list_channels()
+                    .into_iter()
+                    .find(|c| c.get_outbound_payment_scid() == Some(short_channel_id))
+                    .and_then(|c| c.funding_txo)
+                    .map(|f| f.into_bitcoin_outpoint().to_string());
+                let part = PaymentPart {
+                    amount_msats: last_hop.fee_msat,
+                    // every hop but the last one takes a fee
+                    fee_msats: path.hops[..path.hops.len() - 1]
+                        .iter()
+                        .map(|h| h.fee_msat)
+                        .sum(),
+                    short_channel_id,
+                    channel,
+                };
+
+                if let Some(mut saved_payment_info) =
+                    self.persister
+                        .read_payment_info(&payment_hash, false, &self.logger)
+                {
+                    saved_payment_info.parts.push(part);
+                    if let Err(e) = self.persister.persist_payment_info(
+                        &payment_hash,
+                        &saved_payment_info,
+                        false,
+                    ) {
+                        log_error!(self.logger, "ERROR: could not persist payment info: {e}");
+                    }
+                }
             }
-            Event::PaymentPathFailed { .. } => {
-                log_debug!(self.logger, "EVENT: PaymentPathFailed, ignored");
+            Event::PaymentPathFailed {
+                payment_hash,
+                payment_failed_permanently,
+                ..
+            } => {
+                log_debug!(
+                    self.logger,
+                    "EVENT: PaymentPathFailed: {}, permanently: {payment_failed_permanently}",
+                    payment_hash.0.to_hex()
+                );
+
+                // remember that the recipient rejected it for when the payment fails
+                if payment_failed_permanently {
+                    if let Some(mut saved_payment_info) =
+                        self.persister
+                            .read_payment_info(&payment_hash, false, &self.logger)
+                    {
+                        saved_payment_info.failure_reason = Some(PaymentFailureReason::Rejected);
+                        if let Err(e) = self.persister.persist_payment_info(
+                            &payment_hash,
+                            &saved_payment_info,
+                            false,
+                        ) {
+                            log_error!(self.logger, "ERROR: could not persist payment info: {e}");
+                        }
+                    }
+                }
             }
             Event::ProbeSuccessful { .. } => {
                 log_debug!(self.logger, "EVENT: ProbeSuccessful, ignored");
@@ -379,8 +673,21 @@ impl<S: MutinyStorage> EventHandler<S> {
                     .read_payment_info(&payment_hash, false, &self.logger)
                 {
                     Some(mut saved_payment_info) => {
+                        let now = crate::utils::now();
+                        let expired = saved_payment_info
+                            .bolt11
+                            .as_ref()
+                            .is_some_and(|b| b.would_expire(now));
+                        saved_payment_info.failure_reason = match saved_payment_info.failure_reason
+                        {
+                            Some(PaymentFailureReason::Rejected) => {
+                                Some(PaymentFailureReason::Rejected)
+                            }
+                            _ if expired => Some(PaymentFailureReason::Expired),
+                            _ => Some(PaymentFailureReason::NoRoute),
+                        };
                         saved_payment_info.status = HTLCStatus::Failed;
-                        saved_payment_info.last_update = crate::utils::now().as_secs();
+                        saved_payment_info.last_update = now.as_secs();
                         match self.persister.persist_payment_info(
                             &payment_hash,
                             &saved_payment_info,
@@ -402,8 +709,43 @@ impl<S: MutinyStorage> EventHandler<S> {
                     }
                 }
             }
-            Event::PaymentForwarded { .. } => {
-                log_info!(self.logger, "EVENT: PaymentForwarded somehow...");
+            Event::PaymentForwarded {
+                prev_channel_id,
+                next_channel_id,
+                fee_earned_msat,
+                claim_from_onchain_tx,
+            } => {
+                log_info!(
+                    self.logger,
+                    "EVENT: PaymentForwarded, earned {fee_earned_msat:?} msats"
+                );
+
+                let forward = ForwardedPayment {
+                    inbound_channel_id: prev_channel_id.map(|id| id.to_hex()),
+                    outbound_channel_id: next_channel_id.map(|id| id.to_hex()),
+                    fee_earned_msat,
+                    claimed_on_chain: claim_from_onchain_tx,
+                    timestamp: utils::now().as_secs(),
+                };
+                if let Err(e) = self.persister.persist_forward(&forward) {
+                    log_error!(self.logger, "Failed to persist forwarded payment: {e}");
+                }
+
+                self.notifier
+                    .notify(MutinyEvent::PaymentForwarded { fee_earned_msat });
+            }
+            Event::HTLCHandlingFailed {
+                failed_next_destination: HTLCDestination::FailedPayment { payment_hash },
+                ..
+            } => {
+                log_debug!(
+                    self.logger,
+                    "EVENT: HTLCHandlingFailed, failed back payment {}",
+                    payment_hash.0.to_hex()
+                );
+                if let Err(e) = self.persister.fail_inbound_payment(&payment_hash) {
+                    log_error!(self.logger, "ERROR: could not fail inbound payment: {e}");
+                }
             }
             Event::HTLCHandlingFailed { .. } => {
                 log_debug!(self.logger, "EVENT: HTLCHandlingFailed, ignored");
@@ -453,6 +795,18 @@ impl<S: MutinyStorage> EventHandler<S> {
                     }
                 });
 
+                if matches!(reason, ClosureReason::CounterpartyForceClosed { .. }) {
+                    self.check_lsp_lease(channel_id);
+                }
+
+                self.notifier.notify(MutinyEvent::ChannelClosed {
+                    channel_id: channel_id.to_hex(),
+                    kind: ChannelClosureKind::from(&reason),
+                    reason: reason.to_string(),
+                });
+
+                self.record_channel_forensics(channel_id, node_id, &reason);
+
                 let closure = ChannelClosure::new(user_channel_id, channel_id, node_id, reason);
                 if let Err(e) = self
                     .persister
@@ -480,12 +834,14 @@ impl<S: MutinyStorage> EventHandler<S> {
                     counterparty_node_id.to_hex(),
                     channel_type);
 
+                self.notifier.notify(MutinyEvent::ChannelOpened {
+                    channel_id: channel_id.to_hex(),
+                    counterparty_node_id,
+                });
+
                 // Channel is ready, if it is a redshift channel, should update the status.
-                if let Ok(Some(mut redshift)) = self
-                    .persister
-                    .storage
-                    .get_redshift(&user_channel_id.to_be_bytes())
-                {
+                let redshift_id = user_channel_id.to_be_bytes();
+                if let Ok(Some(_)) = self.persister.storage.get_redshift(&redshift_id) {
                     // get channel
                     if let Some(chan) = self
                         .channel_manager
@@ -494,11 +850,26 @@ impl<S: MutinyStorage> EventHandler<S> {
                         .find(|c| c.channel_id == channel_id)
                     {
                         // update status, unwrap is safe because the channel is ready
-                        redshift.channel_opened(chan.funding_txo.unwrap().into_bitcoin_outpoint());
-
-                        // persist
-                        if let Err(e) = self.persister.storage.persist_redshift(redshift) {
-                            log_error!(self.logger, "Failed to persist redshift: {e}");
+                        let outpoint = chan.funding_txo.unwrap().into_bitcoin_outpoint();
+                        match self
+                            .persister
+                            .storage
+                            .update_redshift(&redshift_id, |r| r.channel_opened(outpoint))
+                        {
+                            // cancelled while the channel was opening, close it now that we can
+                            Ok(redshift) if redshift.status == RedshiftStatus::Cancelled => {
+                                if let Err(e) = self
+                                    .channel_manager
+                                    .close_channel(&channel_id, &counterparty_node_id)
+                                {
+                                    log_error!(
+                                        self.logger,
+                                        "Failed to close cancelled redshift channel: {e:?}"
+                                    );
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => log_error!(self.logger, "Failed to persist redshift: {e}"),
                         }
                     }
                 }
@@ -507,6 +878,7 @@ impl<S: MutinyStorage> EventHandler<S> {
                 channel_id,
                 user_channel_id,
                 counterparty_node_id,
+                funding_txo,
                 ..
             } => {
                 log_debug!(
@@ -515,11 +887,158 @@ impl<S: MutinyStorage> EventHandler<S> {
                     channel_id.to_hex(),
                     user_channel_id,
                     counterparty_node_id.to_hex());
+
+                self.notifier.notify(MutinyEvent::ChannelPending {
+                    channel_id: channel_id.to_hex(),
+                    counterparty_node_id,
+                    funding_txid: funding_txo.txid,
+                });
             }
             Event::HTLCIntercepted { .. } => {}
         }
     }
 
+    /// Holds a payment to a hodl invoice until it is settled or cancelled,
+    /// payments to cancelled or unknown invoices are failed back.
+    fn hold_payment(&self, payment_hash: PaymentHash, amount_msat: u64) {
+        let payment_info = self
+            .persister
+            .read_payment_info(&payment_hash, true, &self.logger);
+        let Some(mut payment_info) = payment_info.filter(|p| p.status != HTLCStatus::Failed) else {
+            log_warn!(
+                self.logger,
+                "EVENT: PaymentReceived failing back payment to unknown or cancelled invoice {}",
+                payment_hash.0.to_hex()
+            );
+            self.channel_manager.fail_htlc_backwards(&payment_hash);
+            return;
+        };
+
+        payment_info.status = HTLCStatus::InFlight;
+        payment_info.amt_msat = MillisatAmount(Some(amount_msat));
+        payment_info.last_update = crate::utils::now().as_secs();
+        if let Err(e) = self
+            .persister
+            .persist_payment_info(&payment_hash, &payment_info, true)
+        {
+            log_error!(self.logger, "ERROR: could not persist held payment: {e}");
+        }
+
+        log_info!(
+            self.logger,
+            "EVENT: PaymentReceived holding payment for hodl invoice {}",
+            payment_hash.0.to_hex()
+        );
+        self.notifier.notify(MutinyEvent::HodlInvoiceAccepted {
+            payment_hash: payment_hash.0.to_hex(),
+            amount_msats: amount_msat,
+        });
+    }
+
+    /// If we ordered a channel from the LSP for one of our invoices that
+    /// hasn't been paid or expired yet.
+    fn has_pending_lsp_order(&self, lsp_pubkey: &PublicKey) -> bool {
+        let orders = match self.persister.list_lsp_orders() {
+            Ok(orders) => orders,
+            Err(e) => {
+                log_error!(self.logger, "ERROR: could not read lsp orders: {e}");
+                return false;
+            }
+        };
+
+        let now = utils::now();
+        orders
+            .into_iter()
+            .filter(|o| o.status == LspOrderStatus::Pending && &o.lsp_pubkey == lsp_pubkey)
+            .any(|order| {
+                let Ok(payment_hash) = FromHex::from_hex(&order.payment_hash) else {
+                    return false;
+                };
+                self.persister
+                    .read_payment_info(&PaymentHash(payment_hash), true, &self.logger)
+                    .is_some_and(|info| {
+                        info.status == HTLCStatus::Pending
+                            && !info.bolt11.is_some_and(|b| b.would_expire(now))
+                    })
+            })
+    }
+
+    /// Checks the channel a payment came in through against the LSP order for
+    /// the invoice, if there was one.
+    fn verify_lsp_order(&self, payment_hash: &PaymentHash, channel_id: [u8; 32]) {
+        let mut order = match self.persister.get_lsp_order(payment_hash) {
+            Ok(Some(order)) if order.status == LspOrderStatus::Pending => order,
+            Ok(_) => return,
+            Err(e) => {
+                log_error!(self.logger, "ERROR: could not read lsp order: {e}");
+                return;
+            }
+        };
+
+        let Some(channel) = self
+            .channel_manager
+            .list_channels_with_counterparty(&order.lsp_pubkey)
+            .into_iter()
+            .find(|c| c.channel_id == channel_id)
+        else {
+            log_warn!(
+                self.logger,
+                "WARN: payment for lsp order {} did not come through an lsp channel",
+                order.payment_hash
+            );
+            return;
+        };
+
+        let delivered = LspOrderChannel {
+            channel_id: channel_id.to_hex(),
+            capacity_sat: channel.channel_value_satoshis,
+            confirmations: channel.confirmations.unwrap_or(0),
+            opened_at_height: self.channel_manager.current_best_block().height(),
+        };
+        for discrepancy in order.channel_delivered(delivered) {
+            log_warn!(
+                self.logger,
+                "WARN: lsp under-delivered order {}: {discrepancy:?}",
+                order.payment_hash
+            );
+        }
+
+        if let Err(e) = self.persister.persist_lsp_order(&order) {
+            log_error!(self.logger, "ERROR: could not persist lsp order: {e}");
+        }
+    }
+
+    /// Checks if a force closed channel was leased from our LSP and the lease had not ended yet.
+    fn check_lsp_lease(&self, channel_id: [u8; 32]) {
+        let channel_id = channel_id.to_hex();
+        let orders = match self.persister.list_lsp_orders() {
+            Ok(orders) => orders,
+            Err(e) => {
+                log_error!(self.logger, "ERROR: could not read lsp orders: {e}");
+                return;
+            }
+        };
+
+        let Some(mut order) = orders
+            .into_iter()
+            .find(|o| o.channel.as_ref().map(|c| &c.channel_id) == Some(&channel_id))
+        else {
+            return;
+        };
+
+        let height = self.channel_manager.current_best_block().height();
+        if let Some(discrepancy) = order.channel_force_closed(height) {
+            log_warn!(
+                self.logger,
+                "WARN: lsp under-delivered order {}: {discrepancy:?}",
+                order.payment_hash
+            );
+            if let Err(e) = self.persister.persist_lsp_order(&order) {
+                log_error!(self.logger, "ERROR: could not persist lsp order: {e}");
+            }
+        }
+    }
+
     // Separate function to handle spendable outputs
     // This is so we can return a result and handle errors
     // without having to use a lot of nested if statements
@@ -563,15 +1082,172 @@ impl<S: MutinyStorage> EventHandler<S> {
             )
             .map_err(|_| anyhow!("Failed to spend spendable outputs"))?;
 
+        let sweep_txid = spending_tx.txid();
         self.wallet.broadcast_transaction(spending_tx).await?;
 
+        let swept_outputs: Vec<(Txid, u64)> = output_descriptors
+            .iter()
+            .map(|d| match d {
+                SpendableOutputDescriptor::StaticOutput { outpoint, output } => {
+                    (outpoint.txid, output.value)
+                }
+                SpendableOutputDescriptor::DelayedPaymentOutput(d) => {
+                    (d.outpoint.txid, d.output.value)
+                }
+                SpendableOutputDescriptor::StaticPaymentOutput(d) => {
+                    (d.outpoint.txid, d.output.value)
+                }
+            })
+            .collect();
+        self.record_sweep(&swept_outputs, sweep_txid);
+
         Ok(())
     }
+
+    /// Saves a [ChannelForensics] record for a channel that just closed. The channel
+    /// is already gone from the channel manager but its monitor is still around.
+    fn record_channel_forensics(
+        &self,
+        channel_id: [u8; 32],
+        node_id: Option<PublicKey>,
+        reason: &ClosureReason,
+    ) {
+        let mut forensics = ChannelForensics {
+            channel_id: channel_id.to_hex(),
+            peer: node_id,
+            funding_txo: None,
+            reason: reason.to_string(),
+            closed_at: crate::utils::now().as_secs(),
+            commitment_txids: vec![],
+            balances_at_close: vec![],
+            sweep_txids: vec![],
+        };
+
+        let funding_txo = self
+            .chain_monitor
+            .list_monitors()
+            .into_iter()
+            .find(|o| o.to_channel_id() == channel_id);
+        if let Some(funding_txo) = funding_txo {
+            if let Ok(monitor) = self.chain_monitor.get_monitor(funding_txo) {
+                forensics.peer = monitor.get_counterparty_node_id().or(node_id);
+                forensics.funding_txo = Some(funding_txo.into_bitcoin_outpoint());
+                forensics.commitment_txids = self.commitment_txids(funding_txo);
+                forensics.balances_at_close = monitor
+                    .get_claimable_balances()
+                    .into_iter()
+                    .map(MonitorBalance::from)
+                    .collect();
+            }
+        }
+
+        if let Err(e) = self.persister.persist_channel_forensics(&forensics) {
+            log_error!(
+                self.logger,
+                "ERROR: could not persist channel forensics: {e}"
+            );
+        }
+    }
+
+    /// The transactions the channel monitor has seen spending from the channel
+    fn commitment_txids(&self, funding_txo: lightning::chain::transaction::OutPoint) -> Vec<Txid> {
+        match self.chain_monitor.get_monitor(funding_txo) {
+            Ok(monitor) => monitor
+                .get_outputs_to_watch()
+                .into_keys()
+                .filter(|txid| *txid != funding_txo.txid)
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Adds a sweep to the forensic records and closures of the closed channels
+    /// it swept outputs from, `swept_outputs` are the txid and value of each output
+    fn record_sweep(&self, swept_outputs: &[(Txid, u64)], sweep_txid: Txid) {
+        let records = match self.persister.list_channel_forensics() {
+            Ok(records) => records,
+            Err(e) => {
+                log_error!(self.logger, "ERROR: could not read channel forensics: {e}");
+                return;
+            }
+        };
+
+        for mut forensics in records {
+            // commitment transactions may only confirm after the close was recorded
+            if let Some(funding_txo) = forensics.funding_txo {
+                let funding_txo = lightning::chain::transaction::OutPoint {
+                    txid: funding_txo.txid,
+                    index: funding_txo.vout as u16,
+                };
+                for txid in self.commitment_txids(funding_txo) {
+                    if !forensics.commitment_txids.contains(&txid) {
+                        forensics.commitment_txids.push(txid);
+                    }
+                }
+            }
+
+            let swept_sats: Option<u64> = swept_outputs
+                .iter()
+                .filter(|(txid, _)| forensics.commitment_txids.contains(txid))
+                .map(|(_, value)| *value)
+                .reduce(|a, b| a + b);
+            if let Some(swept_sats) = swept_sats {
+                if !forensics.sweep_txids.contains(&sweep_txid) {
+                    forensics.sweep_txids.push(sweep_txid);
+                    if let Err(e) = self.persister.persist_channel_forensics(&forensics) {
+                        log_error!(
+                            self.logger,
+                            "ERROR: could not persist channel forensics: {e}"
+                        );
+                    }
+                    self.add_swept_to_closure(&forensics.channel_id, swept_sats);
+                }
+            }
+        }
+    }
+
+    /// Adds what a sweep got back to the closure of the channel it swept from
+    fn add_swept_to_closure(&self, channel_id: &str, swept_sats: u64) {
+        let closures = match self.persister.list_channel_closures() {
+            Ok(closures) => closures,
+            Err(e) => {
+                log_error!(self.logger, "ERROR: could not read channel closures: {e}");
+                return;
+            }
+        };
+
+        let closure = closures
+            .into_iter()
+            .find(|(_, c)| c.channel_id.map(|id| id.to_hex()).as_deref() == Some(channel_id));
+        if let Some((user_channel_id, mut closure)) = closure {
+            closure.swept_sats = Some(closure.swept_sats.unwrap_or(0) + swept_sats);
+            if let Err(e) = self
+                .persister
+                .persist_channel_closure(user_channel_id, closure)
+            {
+                log_error!(self.logger, "Failed to persist channel closure: {e}");
+            }
+        }
+    }
+}
+
+/// Rejects a channel that is waiting to be accepted, nothing was broadcast for it yet
+fn reject_inbound_channel<S: MutinyStorage>(
+    channel_manager: &PhantomChannelManager<S>,
+    temporary_channel_id: &[u8; 32],
+    counterparty_node_id: &PublicKey,
+    logger: &MutinyLogger,
+) {
+    if let Err(e) = channel_manager
+        .force_close_without_broadcasting_txn(temporary_channel_id, counterparty_node_id)
+    {
+        log_error!(logger, "ERROR: could not reject inbound channel: {e:?}");
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::event::{HTLCStatus, MillisatAmount, PaymentInfo};
+    use crate::event::{HTLCStatus, MillisatAmount, PaymentFailureReason, PaymentInfo};
     use crate::utils;
     use bitcoin::secp256k1::PublicKey;
     use std::str::FromStr;
@@ -594,6 +1270,9 @@ mod test {
             fee_paid_msat: None,
             bolt11: None,
             payee_pubkey: Some(pubkey),
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             secret: None,
             last_update: utils::now().as_secs(),
         };
@@ -606,4 +1285,32 @@ mod test {
         let deserialized: PaymentInfo = serde_json::from_value(serialized).unwrap();
         assert_eq!(payment_info, deserialized);
     }
+
+    #[test]
+    fn test_payment_info_failure_reason() {
+        let failed = PaymentInfo {
+            preimage: None,
+            status: HTLCStatus::Failed,
+            amt_msat: MillisatAmount(Some(420)),
+            fee_paid_msat: None,
+            bolt11: None,
+            payee_pubkey: None,
+            failure_reason: Some(PaymentFailureReason::Rejected),
+            lsp_selection: None,
+            parts: vec![],
+            secret: None,
+            last_update: utils::now().as_secs(),
+        };
+
+        let serialized = serde_json::to_value(&failed).unwrap();
+        assert_eq!(serialized["failure_reason"], "Rejected");
+        let deserialized: PaymentInfo = serde_json::from_value(serialized).unwrap();
+        assert_eq!(failed, deserialized);
+
+        // payments saved before failure reasons were tracked have none
+        let mut old = serde_json::to_value(&failed).unwrap();
+        old.as_object_mut().unwrap().remove("failure_reason");
+        let deserialized: PaymentInfo = serde_json::from_value(old).unwrap();
+        assert_eq!(deserialized.failure_reason, None);
+    }
 }
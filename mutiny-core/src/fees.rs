@@ -1,13 +1,14 @@
 use crate::error::MutinyError;
+use crate::esplora_failover::FailoverEsploraClient;
 use crate::logging::MutinyLogger;
 use crate::storage::MutinyStorage;
+use async_trait::async_trait;
 use bdk::FeeRate;
-use esplora_client::AsyncClient;
 use lightning::chain::chaininterface::{
     ConfirmationTarget, FeeEstimator, FEERATE_FLOOR_SATS_PER_KW,
 };
-use lightning::log_trace;
 use lightning::util::logger::Logger;
+use lightning::{log_trace, log_warn};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -20,22 +21,72 @@ pub(crate) const P2WSH_OUTPUT_SIZE: usize = 43;
 #[allow(dead_code)]
 pub(crate) const TAPROOT_OUTPUT_SIZE: usize = 43;
 
+/// The lowest fee rate nodes relay transactions at by default, in sat/vbyte
+pub const MIN_RELAY_FEE_RATE_SATS_PER_VBYTE: f32 = 1.0;
+
+/// The highest fee rate we accept from a fee source, in sat/vbyte, so a bad
+/// source can't make us pay absurd fees, like when force closing channels
+pub const MAX_FEE_RATE_SATS_PER_VBYTE: f64 = 1_000.0;
+const MAX_FEERATE_SATS_PER_KW: u32 = (MAX_FEE_RATE_SATS_PER_VBYTE * 250.0) as u32;
+
+/// A source of fee estimates, like a mempool.space API or a node's `estimatesmartfee`.
+///
+/// When it fails, the esplora server's `fee-estimates` are used instead, and
+/// while we're offline the last estimates we got.
+#[async_trait(?Send)]
+pub trait FeeSource: Send + Sync {
+    /// Fee rates in sat/vbyte, keyed by the number of blocks to confirm in, like
+    /// esplora's `fee-estimates`. Mutiny uses the 3, 6 and 12 block targets.
+    ///
+    /// `client` goes through the wallet's SOCKS5 proxy when one is configured, sources
+    /// that make http requests should use it so they don't leak the user's IP.
+    async fn get_fee_estimates(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<HashMap<String, f64>, MutinyError>;
+}
+
+/// The recommended fees of a mempool.space API, like `https://mempool.space/api`
+pub struct MempoolSpaceFeeSource {
+    url: String,
+}
+
+impl MempoolSpaceFeeSource {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+#[async_trait(?Send)]
+impl FeeSource for MempoolSpaceFeeSource {
+    async fn get_fee_estimates(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<HashMap<String, f64>, MutinyError> {
+        Ok(get_mempool_recommended_fees(client, &self.url).await?)
+    }
+}
+
 #[derive(Clone)]
 pub struct MutinyFeeEstimator<S: MutinyStorage> {
     storage: S,
-    esplora: Arc<AsyncClient>,
+    esplora: Arc<FailoverEsploraClient>,
+    /// Uses the mempool.space API of the esplora server, if it has one, when not set
+    fee_source: Option<Arc<dyn FeeSource>>,
     logger: Arc<MutinyLogger>,
 }
 
 impl<S: MutinyStorage> MutinyFeeEstimator<S> {
     pub fn new(
         storage: S,
-        esplora: Arc<AsyncClient>,
+        esplora: Arc<FailoverEsploraClient>,
+        fee_source: Option<Arc<dyn FeeSource>>,
         logger: Arc<MutinyLogger>,
     ) -> MutinyFeeEstimator<S> {
         MutinyFeeEstimator {
             storage,
             esplora,
+            fee_source,
             logger,
         }
     }
@@ -77,37 +128,68 @@ struct MempoolFees {
     minimum_fee: f64,
 }
 
-impl<S: MutinyStorage> MutinyFeeEstimator<S> {
-    async fn get_mempool_recommended_fees(&self) -> anyhow::Result<HashMap<String, f64>> {
-        let fees = self
-            .esplora
-            .client()
-            .get(&format!("{}/v1/fees/recommended", self.esplora.url()))
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<MempoolFees>()
-            .await?;
-
-        // convert to hashmap of num blocks -> fee rate
-        let mut fee_estimates = HashMap::new();
-        fee_estimates.insert("1".to_string(), fees.fastest_fee);
-        fee_estimates.insert("3".to_string(), fees.half_hour_fee);
-        fee_estimates.insert("6".to_string(), fees.hour_fee);
-        fee_estimates.insert("12".to_string(), fees.economy_fee);
-        fee_estimates.insert("1008".to_string(), fees.minimum_fee);
-
-        Ok(fee_estimates)
-    }
+async fn get_mempool_recommended_fees(
+    client: &reqwest::Client,
+    url: &str,
+) -> anyhow::Result<HashMap<String, f64>> {
+    let fees = client
+        .get(&format!(
+            "{}/v1/fees/recommended",
+            url.trim_end_matches('/')
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<MempoolFees>()
+        .await?;
+
+    // convert to hashmap of num blocks -> fee rate
+    let mut fee_estimates = HashMap::new();
+    fee_estimates.insert("1".to_string(), fees.fastest_fee);
+    fee_estimates.insert("3".to_string(), fees.half_hour_fee);
+    fee_estimates.insert("6".to_string(), fees.hour_fee);
+    fee_estimates.insert("12".to_string(), fees.economy_fee);
+    fee_estimates.insert("1008".to_string(), fees.minimum_fee);
+
+    Ok(fee_estimates)
+}
 
+impl<S: MutinyStorage> MutinyFeeEstimator<S> {
+    /// Saves the estimates of our fee source, or of the esplora server if it fails.
+    /// If both fail the estimates we saved last are kept.
     pub async fn update_fee_estimates(&self) -> Result<(), MutinyError> {
-        // first try mempool.space's API
-        let mempool_fees = self.get_mempool_recommended_fees().await;
+        // first try our fee source, by default mempool.space's API
+        let source_fees = match &self.fee_source {
+            Some(source) => {
+                source
+                    .get_fee_estimates(self.esplora.client().client())
+                    .await
+            }
+            None => {
+                get_mempool_recommended_fees(self.esplora.client().client(), self.esplora.url())
+                    .await
+                    .map_err(MutinyError::from)
+            }
+        }
+        .and_then(|fees| {
+            sanitize_fee_estimates(fees).ok_or_else(|| {
+                MutinyError::Other(anyhow::anyhow!("Fee source gave no valid fee estimates"))
+            })
+        });
 
         // if that fails, fall back to esplora's API
-        let fee_estimates = match mempool_fees {
-            Ok(mempool_fees) => mempool_fees,
-            Err(_) => self.esplora.get_fee_estimates().await?,
+        let fee_estimates = match source_fees {
+            Ok(source_fees) => source_fees,
+            Err(e) => {
+                log_warn!(
+                    self.logger,
+                    "Could not get fee estimates from our fee source, falling back to esplora: {e}"
+                );
+                let esplora_fees = self.esplora.get_fee_estimates().await?;
+                sanitize_fee_estimates(esplora_fees).ok_or_else(|| {
+                    MutinyError::Other(anyhow::anyhow!("Esplora gave no valid fee estimates"))
+                })?
+            }
         };
 
         self.storage.insert_fee_estimates(fee_estimates)?;
@@ -116,6 +198,22 @@ impl<S: MutinyStorage> MutinyFeeEstimator<S> {
     }
 }
 
+/// Drops the estimates that aren't a positive fee rate for a number of blocks and
+/// caps the rest at [MAX_FEE_RATE_SATS_PER_VBYTE]. Returns None if none are left.
+fn sanitize_fee_estimates(estimates: HashMap<String, f64>) -> Option<HashMap<String, f64>> {
+    let estimates: HashMap<String, f64> = estimates
+        .into_iter()
+        .filter(|(blocks, rate)| blocks.parse::<usize>().is_ok() && rate.is_finite() && *rate > 0.0)
+        .map(|(blocks, rate)| (blocks, rate.min(MAX_FEE_RATE_SATS_PER_VBYTE)))
+        .collect();
+
+    if estimates.is_empty() {
+        None
+    } else {
+        Some(estimates)
+    }
+}
+
 impl<S: MutinyStorage> FeeEstimator for MutinyFeeEstimator<S> {
     fn get_est_sat_per_1000_weight(&self, confirmation_target: ConfirmationTarget) -> u32 {
         let num_blocks = num_blocks_from_conf_target(confirmation_target);
@@ -132,8 +230,8 @@ impl<S: MutinyStorage> FeeEstimator for MutinyFeeEstimator<S> {
                         // convert to sats per kw
                         let fee_rate = sats_vbyte * 250.0;
 
-                        // return the fee rate, but make sure it's not lower than the floor
-                        (fee_rate as u32).max(FEERATE_FLOOR_SATS_PER_KW)
+                        // return the fee rate, but make sure it's within our bounds
+                        (fee_rate as u32).clamp(FEERATE_FLOOR_SATS_PER_KW, MAX_FEERATE_SATS_PER_KW)
                     }
                     None => fallback_fee,
                 }
@@ -163,7 +261,6 @@ mod test {
     use super::*;
     use crate::storage::{MemoryStorage, MutinyStorage};
     use crate::test_utils::*;
-    use esplora_client::Builder;
     use std::collections::HashMap;
 
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
@@ -172,14 +269,16 @@ mod test {
 
     async fn create_fee_estimator() -> MutinyFeeEstimator<MemoryStorage> {
         let storage = MemoryStorage::new(None);
+        let logger = Arc::new(MutinyLogger::default());
         let esplora = Arc::new(
-            Builder::new("https://mutinynet.com/api")
-                .build_async()
-                .unwrap(),
+            FailoverEsploraClient::new(
+                vec!["https://mutinynet.com/api".to_string()],
+                logger.clone(),
+            )
+            .unwrap(),
         );
-        let logger = Arc::new(MutinyLogger::default());
 
-        MutinyFeeEstimator::new(storage, esplora, logger)
+        MutinyFeeEstimator::new(storage, esplora, None, logger)
     }
 
     #[test]
@@ -257,6 +356,102 @@ mod test {
         );
     }
 
+    struct TestFeeSource {
+        fee_estimates: Option<HashMap<String, f64>>,
+    }
+
+    #[async_trait(?Send)]
+    impl FeeSource for TestFeeSource {
+        async fn get_fee_estimates(
+            &self,
+            _client: &reqwest::Client,
+        ) -> Result<HashMap<String, f64>, MutinyError> {
+            self.fee_estimates
+                .clone()
+                .ok_or(MutinyError::ConnectionFailed)
+        }
+    }
+
+    #[test]
+    async fn test_custom_fee_source() {
+        let test_name = "test_custom_fee_source";
+        log!("{}", test_name);
+
+        let mut fee_estimates = HashMap::new();
+        fee_estimates.insert("6".to_string(), 20_f64);
+        let source = TestFeeSource {
+            fee_estimates: Some(fee_estimates),
+        };
+        let default_estimator = create_fee_estimator().await;
+        let fee_estimator = MutinyFeeEstimator::new(
+            default_estimator.storage,
+            default_estimator.esplora,
+            Some(Arc::new(source)),
+            default_estimator.logger,
+        );
+        fee_estimator.update_fee_estimates().await.unwrap();
+        assert_eq!(
+            fee_estimator.get_est_sat_per_1000_weight(ConfirmationTarget::Normal),
+            5000
+        );
+
+        // the saved estimates are kept when every source fails
+        let source = TestFeeSource {
+            fee_estimates: None,
+        };
+        let offline = MutinyFeeEstimator::new(
+            fee_estimator.storage.clone(),
+            Arc::new(
+                FailoverEsploraClient::new(
+                    vec!["http://127.0.0.1:1".to_string()],
+                    fee_estimator.logger.clone(),
+                )
+                .unwrap(),
+            ),
+            Some(Arc::new(source)),
+            fee_estimator.logger.clone(),
+        );
+        assert!(offline.update_fee_estimates().await.is_err());
+        assert_eq!(
+            offline.get_est_sat_per_1000_weight(ConfirmationTarget::Normal),
+            5000
+        );
+    }
+
+    #[test]
+    async fn test_fee_rate_bounds() {
+        let test_name = "test_fee_rate_bounds";
+        log!("{}", test_name);
+
+        let mut fee_estimates = HashMap::new();
+        fee_estimates.insert("1".to_string(), 50_000_f64);
+        fee_estimates.insert("3".to_string(), f64::NAN);
+        fee_estimates.insert("6".to_string(), -1_f64);
+        fee_estimates.insert("12".to_string(), 5_f64);
+        fee_estimates.insert("fastest".to_string(), 10_f64);
+        let sanitized = sanitize_fee_estimates(fee_estimates).unwrap();
+        assert_eq!(sanitized.len(), 2);
+        assert_eq!(sanitized.get("1"), Some(&MAX_FEE_RATE_SATS_PER_VBYTE));
+        assert_eq!(sanitized.get("12"), Some(&5_f64));
+
+        let mut fee_estimates = HashMap::new();
+        fee_estimates.insert("6".to_string(), 0_f64);
+        assert!(sanitize_fee_estimates(fee_estimates).is_none());
+
+        // estimates saved before the bounds are capped too
+        let fee_estimator = create_fee_estimator().await;
+        let mut fee_estimates = HashMap::new();
+        fee_estimates.insert("3".to_string(), 50_000_f64);
+        fee_estimator
+            .storage
+            .insert_fee_estimates(fee_estimates)
+            .unwrap();
+        assert_eq!(
+            fee_estimator.get_est_sat_per_1000_weight(ConfirmationTarget::HighPriority),
+            MAX_FEERATE_SATS_PER_KW
+        );
+    }
+
     #[test]
     async fn test_estimate_expected_fee() {
         let test_name = "test_estimate_expected_fee";
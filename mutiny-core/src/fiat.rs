@@ -0,0 +1,130 @@
+use crate::error::MutinyError;
+use crate::nodemanager::MutinyInvoice;
+use crate::storage::MutinyStorage;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::sha256;
+use serde::{Deserialize, Serialize};
+
+/// How long a quoted exchange rate is honored by default, in seconds
+pub const DEFAULT_FIAT_QUOTE_TTL_SECS: u64 = 600;
+
+/// The maximum number of times an unpaid fiat invoice will be re-issued
+/// before we give up on it.
+pub(crate) const MAX_FIAT_INVOICE_REISSUES: usize = 12;
+
+/// The exchange rate that a lightning invoice was priced at
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FiatQuote {
+    /// The amount requested in the fiat currency
+    pub fiat_amount: f64,
+    /// The lowercase currency code, ie "usd"
+    pub currency: String,
+    /// The price of one bitcoin in the fiat currency when quoted
+    pub rate: f32,
+    /// The amount of the invoice in sats
+    pub amount_sats: u64,
+    /// Unix timestamp, in seconds, of when the rate was quoted
+    pub quoted_at: u64,
+    /// How long the rate is honored for, in seconds
+    pub ttl: u64,
+}
+
+impl FiatQuote {
+    pub fn new(fiat_amount: f64, currency: String, rate: f32, quoted_at: u64, ttl: u64) -> Self {
+        Self {
+            fiat_amount,
+            currency,
+            rate,
+            amount_sats: fiat_to_sats(fiat_amount, rate),
+            quoted_at,
+            ttl,
+        }
+    }
+
+    /// Unix timestamp, in seconds, of when the quoted rate is no longer honored
+    pub fn expires_at(&self) -> u64 {
+        self.quoted_at + self.ttl
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at()
+    }
+}
+
+/// A lightning invoice that was priced in a fiat currency
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FiatInvoice {
+    pub invoice: MutinyInvoice,
+    pub quote: FiatQuote,
+}
+
+/// Converts a fiat amount to sats at the given price of one bitcoin
+pub(crate) fn fiat_to_sats(fiat_amount: f64, rate: f32) -> u64 {
+    (fiat_amount / rate as f64 * 100_000_000.0).round() as u64
+}
+
+pub trait FiatQuoteStorage {
+    fn get_fiat_quote(&self, payment_hash: &sha256::Hash)
+        -> Result<Option<FiatQuote>, MutinyError>;
+    fn persist_fiat_quote(
+        &self,
+        payment_hash: &sha256::Hash,
+        quote: FiatQuote,
+    ) -> Result<(), MutinyError>;
+}
+
+const FIAT_QUOTE_KEY_PREFIX: &str = "fiat_quote/";
+
+fn get_fiat_quote_key(payment_hash: &sha256::Hash) -> String {
+    format!("{FIAT_QUOTE_KEY_PREFIX}{}", payment_hash.to_hex())
+}
+
+impl<S: MutinyStorage> FiatQuoteStorage for S {
+    fn get_fiat_quote(
+        &self,
+        payment_hash: &sha256::Hash,
+    ) -> Result<Option<FiatQuote>, MutinyError> {
+        self.get_data(get_fiat_quote_key(payment_hash))
+    }
+
+    fn persist_fiat_quote(
+        &self,
+        payment_hash: &sha256::Hash,
+        quote: FiatQuote,
+    ) -> Result<(), MutinyError> {
+        self.set_data(get_fiat_quote_key(payment_hash), quote)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use bitcoin::hashes::Hash;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_fiat_quote() {
+        let test_name = "test_fiat_quote";
+        log!("{}", test_name);
+
+        // $10 at $25,000 per bitcoin is 40,000 sats
+        let quote = FiatQuote::new(10.0, "usd".to_string(), 25_000.0, 1_000, 600);
+        assert_eq!(quote.amount_sats, 40_000);
+        assert_eq!(quote.expires_at(), 1_600);
+        assert!(!quote.is_expired(1_599));
+        assert!(quote.is_expired(1_600));
+
+        let storage = MemoryStorage::default();
+        let payment_hash = sha256::Hash::hash(&[0; 32]);
+        assert_eq!(storage.get_fiat_quote(&payment_hash).unwrap(), None);
+
+        storage
+            .persist_fiat_quote(&payment_hash, quote.clone())
+            .unwrap();
+        assert_eq!(storage.get_fiat_quote(&payment_hash).unwrap(), Some(quote));
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::Network;
-use lightning::ln::msgs::NodeAnnouncement;
+use lightning::ln::msgs::{NetAddress, NodeAnnouncement};
 use lightning::routing::gossip::NodeId;
 use lightning::routing::scoring::ProbabilisticScoringParameters;
 use lightning::util::logger::Logger;
@@ -15,7 +17,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::error::MutinyError;
 use crate::logging::MutinyLogger;
-use crate::node::{NetworkGraph, ProbScorer, RapidGossipSync};
+use crate::node::{
+    NetworkGraph, ProbScorer, RapidGossipSync, INITIAL_RECONNECTION_DELAY, MAX_RECONNECTION_DELAY,
+    RECONNECTION_DELAY_LIMIT,
+};
 use crate::storage::MutinyStorage;
 use crate::utils;
 
@@ -97,7 +102,7 @@ async fn get_gossip_data(
     Ok(Some(gossip))
 }
 
-fn write_gossip_data(
+pub(crate) fn write_gossip_data(
     storage: &impl MutinyStorage,
     last_sync_timestamp: u32,
     network_graph: &NetworkGraph,
@@ -115,6 +120,7 @@ pub async fn get_gossip_sync(
     storage: &impl MutinyStorage,
     user_rgs_url: Option<String>,
     network: Network,
+    http_client: &Client,
     logger: Arc<MutinyLogger>,
 ) -> Result<(RapidGossipSync, ProbScorer), MutinyError> {
     // if we error out, we just use the default gossip data
@@ -147,47 +153,67 @@ pub async fn get_gossip_sync(
         }
     };
 
-    if let Some(rgs_url) = get_rgs_url(network, user_rgs_url, Some(gossip_data.last_sync_timestamp))
+    if let Err(e) = sync_gossip(
+        storage,
+        user_rgs_url,
+        network,
+        gossip_data.last_sync_timestamp,
+        &gossip_sync,
+        http_client,
+        &logger,
+    )
+    .await
     {
-        log_info!(&logger, "RGS URL: {}", rgs_url);
+        log_warn!(
+            logger,
+            "Failed to fetch updated gossip: {e}, using the gossip data we have"
+        );
+    }
 
-        let now = utils::now().as_secs();
-        let fetch_result = fetch_updated_gossip(
-            rgs_url,
-            now,
-            gossip_data.last_sync_timestamp,
-            &gossip_sync,
-            storage,
-            &logger,
-        )
-        .await;
+    Ok((gossip_sync, prob_scorer))
+}
 
-        if fetch_result.is_err() {
-            log_warn!(
-                logger,
-                "Failed to fetch updated gossip, using default gossip data"
-            );
+/// Fetches what changed in the network graph since `last_sync_timestamp`, trying each
+/// RGS server in order until one works. Returns the timestamp the graph is now synced to.
+pub(crate) async fn sync_gossip(
+    storage: &impl MutinyStorage,
+    user_rgs_url: Option<String>,
+    network: Network,
+    last_sync_timestamp: u32,
+    gossip_sync: &RapidGossipSync,
+    http_client: &Client,
+    logger: &MutinyLogger,
+) -> Result<u32, MutinyError> {
+    let rgs_urls = get_rgs_urls(network, user_rgs_url, Some(last_sync_timestamp));
+    if rgs_urls.is_empty() {
+        return Ok(last_sync_timestamp);
+    }
+
+    for rgs_url in rgs_urls {
+        log_info!(logger, "RGS URL: {}", rgs_url);
+
+        match fetch_updated_gossip(&rgs_url, gossip_sync, storage, http_client, logger).await {
+            Ok(timestamp) => return Ok(timestamp),
+            Err(e) => log_warn!(logger, "Failed to fetch gossip from {rgs_url}: {e}"),
         }
     }
 
-    Ok((gossip_sync, prob_scorer))
+    Err(MutinyError::RapidGossipSyncError)
 }
 
 async fn fetch_updated_gossip(
-    rgs_url: String,
-    now: u64,
-    last_sync_timestamp: u32,
+    rgs_url: &str,
     gossip_sync: &RapidGossipSync,
     storage: &impl MutinyStorage,
+    http_client: &Client,
     logger: &MutinyLogger,
-) -> Result<(), MutinyError> {
-    let http_client = Client::builder()
-        .build()
-        .map_err(|_| MutinyError::RapidGossipSyncError)?;
+) -> Result<u32, MutinyError> {
     let rgs_response = http_client
         .get(rgs_url)
         .send()
         .await
+        .map_err(|_| MutinyError::RapidGossipSyncError)?
+        .error_for_status()
         .map_err(|_| MutinyError::RapidGossipSyncError)?;
 
     let rgs_data = rgs_response
@@ -196,8 +222,49 @@ async fn fetch_updated_gossip(
         .map_err(|_| MutinyError::RapidGossipSyncError)?
         .to_vec();
 
+    apply_rgs_snapshot(&rgs_data, gossip_sync, storage, logger)
+}
+
+/// Saves the network graph of a RGS snapshot before the wallet is started, so apps can
+/// bundle one and a new wallet only downloads what changed since it was made.
+/// Returns the timestamp the saved network graph is synced to.
+///
+/// Does nothing if there already is a saved network graph.
+pub async fn import_gossip_snapshot(
+    storage: &impl MutinyStorage,
+    network: Network,
+    snapshot: &[u8],
+    logger: Arc<MutinyLogger>,
+) -> Result<u32, MutinyError> {
+    if let Some(gossip) = get_gossip_data(storage, logger.clone()).await? {
+        log_debug!(
+            logger,
+            "Network graph already saved, not importing snapshot"
+        );
+        return Ok(gossip.last_sync_timestamp);
+    }
+
+    let network_graph = Arc::new(NetworkGraph::new(network, logger.clone()));
+    let gossip_sync = RapidGossipSync::new(network_graph, logger.clone());
+    apply_rgs_snapshot(snapshot, &gossip_sync, storage, &logger)
+}
+
+/// Applies a RGS snapshot to the network graph and saves it,
+/// returns the timestamp the graph is now synced to.
+///
+/// The snapshot can come from a RGS server or be fetched out of band,
+/// for setups that can't reach a RGS server themselves.
+pub(crate) fn apply_rgs_snapshot(
+    rgs_data: &[u8],
+    gossip_sync: &RapidGossipSync,
+    storage: &impl MutinyStorage,
+    logger: &MutinyLogger,
+) -> Result<u32, MutinyError> {
+    let last_sync_timestamp: u32 = storage.get_data(GOSSIP_SYNC_TIME_KEY)?.unwrap_or(0);
+
+    let now = utils::now().as_secs();
     let new_last_sync_timestamp_result =
-        gossip_sync.update_network_graph_no_std(&rgs_data, Some(now))?;
+        gossip_sync.update_network_graph_no_std(rgs_data, Some(now))?;
 
     log_info!(
         logger,
@@ -214,7 +281,130 @@ async fn fetch_updated_gossip(
         )?;
     }
 
-    Ok(())
+    Ok(new_last_sync_timestamp_result)
+}
+
+/// How big the network graph is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NetworkGraphSize {
+    pub num_channels: usize,
+    pub num_nodes: usize,
+    /// Size of the serialized network graph, roughly what it takes in memory and storage
+    pub size_bytes: u64,
+}
+
+pub(crate) fn network_graph_size(network_graph: &NetworkGraph) -> NetworkGraphSize {
+    let (num_channels, num_nodes) = {
+        let graph = network_graph.read_only();
+        (graph.channels().len(), graph.nodes().len())
+    };
+
+    // counts the bytes without holding the whole serialized graph in memory
+    NetworkGraphSize {
+        num_channels,
+        num_nodes,
+        size_bytes: network_graph.serialized_length() as u64,
+    }
+}
+
+/// Removes channels that have not been updated in two weeks from the network graph.
+/// If the graph is still bigger than `max_size_bytes` the smallest channels are removed
+/// until it fits, channels of the `keep` nodes are never removed.
+/// Nodes left without channels are removed along with them.
+pub(crate) fn prune_network_graph(
+    network_graph: &NetworkGraph,
+    max_size_bytes: Option<u64>,
+    keep: &[NodeId],
+    logger: &MutinyLogger,
+) -> NetworkGraphSize {
+    network_graph.remove_stale_channels_and_tracking_with_time(utils::now().as_secs());
+    let size = network_graph_size(network_graph);
+
+    let max_size_bytes = match max_size_bytes {
+        Some(max) if size.size_bytes > max && size.num_channels > 0 => max,
+        _ => return size,
+    };
+
+    // small channels are the least useful for routing, so they go first
+    let mut channels: Vec<(u64, u64)> = network_graph
+        .read_only()
+        .channels()
+        .unordered_iter()
+        .filter(|(_, info)| !keep.contains(&info.node_one) && !keep.contains(&info.node_two))
+        .map(|(scid, info)| (info.capacity_sats.unwrap_or(0), *scid))
+        .collect();
+    channels.sort_unstable();
+    let mut candidates = channels.into_iter().map(|(_, scid)| scid);
+
+    let mut pruned = size;
+    while pruned.size_bytes > max_size_bytes {
+        // take off what each channel adds to the graph until it should fit, the
+        // nodes removed along with them only make it smaller, then measure again
+        let mut estimate = pruned.size_bytes;
+        let mut removed = 0;
+        while estimate > max_size_bytes {
+            let Some(scid) = candidates.next() else {
+                break;
+            };
+            let channel_bytes = network_graph
+                .read_only()
+                .channel(scid)
+                .map(|info| scid.serialized_length() + info.serialized_length())
+                .unwrap_or(0);
+            network_graph.channel_failed(scid, true);
+            estimate = estimate.saturating_sub(channel_bytes as u64);
+            removed += 1;
+        }
+
+        // nothing left that we are allowed to remove
+        if removed == 0 {
+            break;
+        }
+        pruned = network_graph_size(network_graph);
+    }
+
+    log_info!(
+        logger,
+        "Pruned network graph from {} to {} bytes, {} channels left",
+        size.size_bytes,
+        pruned.size_bytes,
+        pruned.num_channels
+    );
+
+    pruned
+}
+
+/// How our nodes reconnect to a peer after losing the connection
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// If the peer is reconnected to automatically, otherwise only manual connections are made
+    pub always_reconnect: bool,
+    /// Seconds to wait before retrying, doubled after each failed attempt
+    pub initial_backoff_secs: u64,
+    /// The most seconds to wait between attempts, at most a day
+    pub max_backoff_secs: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            always_reconnect: true,
+            initial_backoff_secs: INITIAL_RECONNECTION_DELAY,
+            max_backoff_secs: MAX_RECONNECTION_DELAY,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub(crate) fn validate(&self) -> Result<(), MutinyError> {
+        if self.initial_backoff_secs == 0
+            || self.max_backoff_secs < self.initial_backoff_secs
+            || self.max_backoff_secs > RECONNECTION_DELAY_LIMIT
+        {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -232,6 +422,9 @@ pub struct LnPeerMetadata {
     /// Our nodes' uuids that are connected to this node
     #[serde(default)]
     pub nodes: Vec<String>,
+    /// How to reconnect to this node, the default policy is used if not set
+    #[serde(default)]
+    pub reconnect_policy: Option<ReconnectPolicy>,
 }
 
 impl LnPeerMetadata {
@@ -263,6 +456,13 @@ impl LnPeerMetadata {
         }
     }
 
+    pub(crate) fn with_reconnect_policy(&self, reconnect_policy: Option<ReconnectPolicy>) -> Self {
+        Self {
+            reconnect_policy,
+            ..self.clone()
+        }
+    }
+
     pub(crate) fn merge_opt(&self, other: &Option<LnPeerMetadata>) -> LnPeerMetadata {
         match other {
             Some(other) => self.merge(other),
@@ -295,6 +495,7 @@ impl LnPeerMetadata {
             label: primary.label.or(secondary.label),
             timestamp: primary.timestamp.or(secondary.timestamp),
             nodes,
+            reconnect_policy: primary.reconnect_policy.or(secondary.reconnect_policy),
         }
     }
 }
@@ -308,6 +509,7 @@ impl From<NodeAnnouncement> for LnPeerMetadata {
             label: None,
             timestamp: Some(value.contents.timestamp),
             nodes: vec![],
+            reconnect_policy: None,
         }
     }
 }
@@ -391,6 +593,31 @@ pub(crate) fn set_peer_label(
     Ok(())
 }
 
+pub(crate) fn set_peer_reconnect_policy(
+    storage: &impl MutinyStorage,
+    node_id: &NodeId,
+    policy: Option<ReconnectPolicy>,
+) -> Result<(), MutinyError> {
+    if let Some(policy) = policy.as_ref() {
+        policy.validate()?;
+    }
+    let key = format!("{LN_PEER_METADATA_KEY_PREFIX}{node_id}");
+
+    let current: Option<LnPeerMetadata> = storage.get_data(&key)?;
+
+    let new_info = match current {
+        Some(current) => current.with_reconnect_policy(policy),
+        None => LnPeerMetadata {
+            reconnect_policy: policy,
+            timestamp: Some(utils::now().as_secs() as u32),
+            ..Default::default()
+        },
+    };
+
+    storage.set_data(key, new_info)?;
+    Ok(())
+}
+
 pub(crate) fn delete_peer_info(
     storage: &impl MutinyStorage,
     uuid: &str,
@@ -431,28 +658,113 @@ pub(crate) fn save_ln_peer_info(
     Ok(())
 }
 
-pub(crate) fn get_rgs_url(
+/// Finds the address a node announced in the network graph, as `host:port`.
+/// IPv4 addresses are preferred since they work through every proxy.
+pub(crate) fn find_graph_address(
+    network_graph: &NetworkGraph,
+    pubkey: &PublicKey,
+) -> Option<String> {
+    let graph = network_graph.read_only();
+    let info = graph
+        .node(&NodeId::from_pubkey(pubkey))?
+        .announcement_info
+        .as_ref()?;
+
+    let addresses: Vec<String> = info
+        .addresses()
+        .iter()
+        .filter_map(|addr| match addr {
+            NetAddress::IPv4 { addr, port } => {
+                Some(SocketAddr::from((Ipv4Addr::from(*addr), *port)).to_string())
+            }
+            NetAddress::IPv6 { addr, port } => {
+                Some(SocketAddr::from((Ipv6Addr::from(*addr), *port)).to_string())
+            }
+            _ => None,
+        })
+        .collect();
+
+    pick_address(&addresses)
+}
+
+/// The lightning explorer url with a node's announced addresses, if there is one for the network
+pub(crate) fn explorer_node_url(network: Network, pubkey: &PublicKey) -> Option<String> {
+    let base = match network {
+        Network::Bitcoin => "https://mempool.space/api/v1/lightning/nodes",
+        Network::Testnet => "https://mempool.space/testnet/api/v1/lightning/nodes",
+        Network::Signet => "https://mutinynet.com/api/v1/lightning/nodes",
+        Network::Regtest => return None,
+    };
+    Some(format!("{base}/{pubkey}"))
+}
+
+/// A node as returned by the lightning explorer
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ExplorerNode {
+    /// Comma separated list of the node's addresses
+    #[serde(default)]
+    pub sockets: Option<String>,
+}
+
+impl ExplorerNode {
+    pub(crate) fn address(&self) -> Option<String> {
+        let sockets: Vec<String> = self
+            .sockets
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        pick_address(&sockets)
+    }
+}
+
+/// Picks the address we are most likely to reach, IPv4 first, then IPv6, then anything else
+fn pick_address(addresses: &[String]) -> Option<String> {
+    let parsed = |a: &String| a.parse::<SocketAddr>().ok();
+    addresses
+        .iter()
+        .find(|a| parsed(a).is_some_and(|s| s.is_ipv4()))
+        .or_else(|| addresses.iter().find(|a| parsed(a).is_some()))
+        .or_else(|| addresses.first())
+        .cloned()
+}
+
+/// Returns the RGS snapshot urls to try in order,
+/// the user provided url can be a comma separated list.
+pub(crate) fn get_rgs_urls(
     network: Network,
     user_provided_url: Option<String>,
     last_sync_time: Option<u32>,
-) -> Option<String> {
+) -> Vec<String> {
     let last_sync_time = last_sync_time.unwrap_or(0);
-    if let Some(url) = user_provided_url.filter(|url| !url.is_empty()) {
-        let url = url.strip_suffix('/').unwrap_or(&url);
-        Some(format!("{url}/{last_sync_time}"))
-    } else {
-        match network {
-            Network::Bitcoin => Some(format!(
-                "https://rapidsync.lightningdevkit.org/snapshot/{last_sync_time}"
-            )),
-            Network::Testnet => Some(format!(
-                "https://rapidsync.lightningdevkit.org/testnet/snapshot/{last_sync_time}"
-            )),
-            Network::Signet => Some(format!(
-                "https://rgs.mutinynet.com/snapshot/{last_sync_time}"
-            )),
-            Network::Regtest => None,
-        }
+    let urls: Vec<String> = user_provided_url
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim())
+        .filter(|url| !url.is_empty())
+        .map(|url| {
+            let url = url.strip_suffix('/').unwrap_or(url);
+            format!("{url}/{last_sync_time}")
+        })
+        .collect();
+
+    if !urls.is_empty() {
+        return urls;
+    }
+
+    match network {
+        Network::Bitcoin => vec![format!(
+            "https://rapidsync.lightningdevkit.org/snapshot/{last_sync_time}"
+        )],
+        Network::Testnet => vec![format!(
+            "https://rapidsync.lightningdevkit.org/testnet/snapshot/{last_sync_time}"
+        )],
+        Network::Signet => vec![format!(
+            "https://rgs.mutinynet.com/snapshot/{last_sync_time}"
+        )],
+        Network::Regtest => vec![],
     }
 }
 
@@ -460,6 +772,7 @@ pub(crate) fn get_rgs_url(
 mod test {
     use crate::storage::MemoryStorage;
     use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use lightning::ln::features::ChannelFeatures;
     use uuid::Uuid;
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
 
@@ -486,6 +799,7 @@ mod test {
             label: Some("test label".to_string()),
             timestamp: Some(utils::now().as_secs() as u32),
             nodes: vec![uuid],
+            reconnect_policy: None,
         };
 
         (node_id, data)
@@ -522,9 +836,15 @@ mod test {
         let storage = MemoryStorage::default();
 
         let logger = Arc::new(MutinyLogger::default());
-        let _gossip_sync = get_gossip_sync(&storage, None, Network::Regtest, logger.clone())
-            .await
-            .unwrap();
+        let _gossip_sync = get_gossip_sync(
+            &storage,
+            None,
+            Network::Regtest,
+            &Client::new(),
+            logger.clone(),
+        )
+        .await
+        .unwrap();
 
         let data = get_gossip_data(&storage, logger).await.unwrap();
 
@@ -532,6 +852,139 @@ mod test {
         assert!(data.unwrap().last_sync_timestamp > 0);
     }
 
+    #[test]
+    async fn test_import_gossip_snapshot() {
+        crate::test_utils::log!("test import gossip snapshot");
+        let storage = MemoryStorage::default();
+        let logger = Arc::new(MutinyLogger::default());
+
+        // a bad snapshot is rejected when there is nothing saved yet
+        let result =
+            import_gossip_snapshot(&storage, Network::Regtest, &[1, 2, 3], logger.clone()).await;
+        assert!(result.is_err());
+
+        // an existing network graph is kept
+        let network_graph = NetworkGraph::new(Network::Regtest, logger.clone());
+        write_gossip_data(&storage, 1234, &network_graph).unwrap();
+        let timestamp = import_gossip_snapshot(&storage, Network::Regtest, &[1, 2, 3], logger)
+            .await
+            .unwrap();
+        assert_eq!(timestamp, 1234);
+    }
+
+    #[test]
+    fn test_prune_network_graph() {
+        crate::test_utils::log!("test prune network graph");
+        let logger = Arc::new(MutinyLogger::default());
+        let network_graph = NetworkGraph::new(Network::Regtest, logger.clone());
+        let now = utils::now().as_secs();
+
+        // channels 1 and 2 are with the node we keep, the rest are between others
+        let keep = dummy_node_id();
+        let nodes: Vec<NodeId> = (0..10).map(|_| dummy_node_id()).collect();
+        for scid in 1..=20u64 {
+            let node_one = if scid <= 2 {
+                keep
+            } else {
+                nodes[scid as usize % 10]
+            };
+            let node_two = nodes[(scid as usize + 1) % 10];
+            network_graph
+                .add_channel_from_partial_announcement(
+                    scid,
+                    now,
+                    ChannelFeatures::empty(),
+                    node_one.as_pubkey().unwrap(),
+                    node_two.as_pubkey().unwrap(),
+                )
+                .unwrap();
+        }
+
+        let size = network_graph_size(&network_graph);
+        assert_eq!(size.num_channels, 20);
+
+        // nothing is stale and there is no limit
+        assert_eq!(
+            prune_network_graph(&network_graph, None, &[keep], &logger),
+            size
+        );
+
+        let limit = size.size_bytes / 2;
+        let pruned = prune_network_graph(&network_graph, Some(limit), &[keep], &logger);
+        assert!(pruned.size_bytes <= limit);
+        assert!(pruned.num_channels < 20);
+        assert_eq!(pruned, network_graph_size(&network_graph));
+
+        // all the channels have the same capacity, so the lowest ids went first
+        {
+            let graph = network_graph.read_only();
+            assert!(graph.channel(1).is_some());
+            assert!(graph.channel(2).is_some());
+            assert!(graph.channel(3).is_none());
+            assert!(graph.channel(20).is_some());
+        }
+
+        // it can't get smaller than the channels we keep
+        let pruned = prune_network_graph(&network_graph, Some(1), &[keep], &logger);
+        assert_eq!(pruned.num_channels, 2);
+    }
+
+    #[test]
+    fn test_explorer_node_address() {
+        let node = ExplorerNode {
+            sockets: Some("abc.onion:9735,[2001:db8::1]:9735, 1.2.3.4:9735".to_string()),
+        };
+        assert_eq!(node.address(), Some("1.2.3.4:9735".to_string()));
+
+        let node = ExplorerNode {
+            sockets: Some("abc.onion:9735,[2001:db8::1]:9735".to_string()),
+        };
+        assert_eq!(node.address(), Some("[2001:db8::1]:9735".to_string()));
+
+        let node = ExplorerNode {
+            sockets: Some("abc.onion:9735".to_string()),
+        };
+        assert_eq!(node.address(), Some("abc.onion:9735".to_string()));
+
+        assert_eq!(ExplorerNode { sockets: None }.address(), None);
+        assert_eq!(
+            ExplorerNode {
+                sockets: Some(String::new())
+            }
+            .address(),
+            None
+        );
+
+        let pubkey = dummy_node_id().as_pubkey().unwrap();
+        assert!(explorer_node_url(Network::Bitcoin, &pubkey)
+            .unwrap()
+            .ends_with(&pubkey.to_string()));
+        assert!(explorer_node_url(Network::Regtest, &pubkey).is_none());
+    }
+
+    #[test]
+    fn test_rgs_urls() {
+        let urls = get_rgs_urls(
+            Network::Bitcoin,
+            Some("https://rgs.example.com/, https://backup.example.com".to_string()),
+            Some(10),
+        );
+        assert_eq!(
+            urls,
+            vec![
+                "https://rgs.example.com/10".to_string(),
+                "https://backup.example.com/10".to_string()
+            ]
+        );
+
+        let urls = get_rgs_urls(Network::Signet, Some(String::new()), None);
+        assert_eq!(
+            urls,
+            vec!["https://rgs.mutinynet.com/snapshot/0".to_string()]
+        );
+        assert!(get_rgs_urls(Network::Regtest, None, None).is_empty());
+    }
+
     #[test]
     fn test_peer_info() {
         let storage = MemoryStorage::default();
@@ -575,4 +1028,41 @@ mod test {
         assert!(read.is_some());
         assert_eq!(read.unwrap(), expected);
     }
+
+    #[test]
+    fn test_reconnect_policy() {
+        let storage = MemoryStorage::default();
+
+        let (node_id, data) = dummy_peer_info();
+
+        save_ln_peer_info(&storage, &node_id, &data).unwrap();
+
+        let policy = ReconnectPolicy {
+            always_reconnect: false,
+            ..Default::default()
+        };
+        set_peer_reconnect_policy(&storage, &node_id, Some(policy)).unwrap();
+
+        let read = read_peer_info(&storage, &node_id).unwrap().unwrap();
+        assert_eq!(read.reconnect_policy, Some(policy));
+        assert_eq!(read.label, data.label);
+
+        let bad_policy = ReconnectPolicy {
+            initial_backoff_secs: 10,
+            max_backoff_secs: 5,
+            ..Default::default()
+        };
+        assert!(set_peer_reconnect_policy(&storage, &node_id, Some(bad_policy)).is_err());
+
+        let too_long = ReconnectPolicy {
+            max_backoff_secs: u64::MAX,
+            ..Default::default()
+        };
+        assert!(set_peer_reconnect_policy(&storage, &node_id, Some(too_long)).is_err());
+
+        // going back to the default policy
+        set_peer_reconnect_policy(&storage, &node_id, None).unwrap();
+        let read = read_peer_info(&storage, &node_id).unwrap().unwrap();
+        assert_eq!(read.reconnect_policy, None);
+    }
 }
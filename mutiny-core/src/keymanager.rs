@@ -9,7 +9,7 @@ use bitcoin::bech32::u5;
 use bitcoin::secp256k1::ecdh::SharedSecret;
 use bitcoin::secp256k1::ecdsa::RecoverableSignature;
 use bitcoin::secp256k1::ecdsa::Signature;
-use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, Signing};
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing};
 use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
 use bitcoin::{Script, Transaction, TxOut};
 use lightning::chain::keysinterface::{
@@ -21,12 +21,14 @@ use lightning::ln::msgs::{DecodeError, UnsignedGossipMessage};
 use lightning::ln::script::ShutdownScript;
 use lightning::log_warn;
 use lightning::util::logger::Logger;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub struct PhantomKeysManager<S: MutinyStorage> {
     inner: LdkPhantomKeysManager,
     wallet: Arc<OnChainWallet<S>>,
     logger: Arc<MutinyLogger>,
+    /// Overrides the wallet address for the next cooperative close
+    next_shutdown_script: Mutex<Option<ShutdownScript>>,
 }
 
 impl<S: MutinyStorage> PhantomKeysManager<S> {
@@ -48,9 +50,22 @@ impl<S: MutinyStorage> PhantomKeysManager<S> {
             inner,
             wallet,
             logger,
+            next_shutdown_script: Mutex::new(None),
         }
     }
 
+    /// Sets where the funds of the next channel we close go, instead of a new wallet address.
+    /// LDK asks for the shutdown script while the close is initiated, so this should be
+    /// set right before closing and cleared after.
+    pub(crate) fn set_next_shutdown_script(&self, script: Option<ShutdownScript>) {
+        *self.next_shutdown_script.lock().unwrap() = script;
+    }
+
+    /// Gets the node's secret key, used to sign messages with the node key
+    pub(crate) fn get_node_secret_key(&self) -> SecretKey {
+        self.inner.get_node_secret_key()
+    }
+
     /// See [`KeysManager::spend_spendable_outputs`] for documentation on this method.
     pub fn spend_spendable_outputs<C: Signing>(
         &self,
@@ -165,6 +180,10 @@ impl<S: MutinyStorage> SignerProvider for PhantomKeysManager<S> {
     }
 
     fn get_shutdown_scriptpubkey(&self) -> ShutdownScript {
+        if let Some(script) = self.next_shutdown_script.lock().unwrap().take() {
+            return script;
+        }
+
         let mut wallet = self.wallet.wallet.try_write().unwrap();
         let script = wallet
             .get_address(AddressIndex::New)
@@ -190,12 +209,28 @@ pub fn generate_seed(num_words: u8) -> Result<Mnemonic, MutinyError> {
     Ok(mnemonic)
 }
 
-// A node private key will be derived from `m/0'/X'`, where its node pubkey will
-// be derived from the LDK default being `m/0'/X'/0'`. The PhantomKeysManager shared
-// key secret will be derived from `m/0'`.
+/// The path the lightning keys of an account are derived from, the PhantomKeysManager
+/// shared key. Account 0 uses `m/0'` like wallets from before account indexes,
+/// other accounts use `m/1'/A'` so they never share keys with it.
+pub(crate) fn lightning_derivation_path(account_index: u32) -> Result<DerivationPath, MutinyError> {
+    let path = if account_index == 0 {
+        vec![ChildNumber::from_hardened_idx(0)?]
+    } else {
+        vec![
+            ChildNumber::from_hardened_idx(1)?,
+            ChildNumber::from_hardened_idx(account_index)?,
+        ]
+    };
+    Ok(DerivationPath::from(path))
+}
+
+// A node private key will be derived from `<shared>/X'`, where its node pubkey will
+// be derived from the LDK default being `<shared>/X'/0'`. The PhantomKeysManager shared
+// key secret will be derived from `<shared>`, see [lightning_derivation_path].
 pub(crate) fn create_keys_manager<S: MutinyStorage>(
     wallet: Arc<OnChainWallet<S>>,
     mnemonic: &Mnemonic,
+    account_index: u32,
     child_index: u32,
     logger: Arc<MutinyLogger>,
 ) -> Result<PhantomKeysManager<S>, MutinyError> {
@@ -203,10 +238,7 @@ pub(crate) fn create_keys_manager<S: MutinyStorage>(
 
     let seed = mnemonic.to_seed("");
     let xprivkey = ExtendedPrivKey::new_master(wallet.network, &seed)?;
-    let shared_key = xprivkey.derive_priv(
-        &context,
-        &DerivationPath::from(vec![ChildNumber::from_hardened_idx(0)?]),
-    )?;
+    let shared_key = xprivkey.derive_priv(&context, &lightning_derivation_path(account_index)?)?;
 
     let xpriv = shared_key.derive_priv(
         &context,
@@ -242,13 +274,13 @@ mod tests {
     use crate::{keymanager::pubkey_from_keys_manager, test_utils::*};
 
     use super::create_keys_manager;
+    use crate::esplora_failover::FailoverEsploraClient;
     use crate::fees::MutinyFeeEstimator;
     use crate::logging::MutinyLogger;
     use crate::onchain::OnChainWallet;
     use crate::storage::MemoryStorage;
     use bip39::Mnemonic;
     use bitcoin::Network;
-    use esplora_client::Builder;
     use std::str::FromStr;
     use std::sync::Arc;
 
@@ -258,16 +290,19 @@ mod tests {
         log!("{}", test_name);
 
         let mnemonic = Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").expect("could not generate");
-        let esplora = Arc::new(
-            Builder::new("https://blockstream.info/testnet/api/")
-                .build_async()
-                .unwrap(),
-        );
         let db = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
         let logger = Arc::new(MutinyLogger::default());
+        let esplora = Arc::new(
+            FailoverEsploraClient::new(
+                vec!["https://blockstream.info/testnet/api/".to_string()],
+                logger.clone(),
+            )
+            .unwrap(),
+        );
         let fees = Arc::new(MutinyFeeEstimator::new(
             db.clone(),
             esplora.clone(),
+            None,
             logger.clone(),
         ));
 
@@ -276,6 +311,7 @@ mod tests {
                 &mnemonic,
                 db,
                 Network::Testnet,
+                0,
                 esplora,
                 fees,
                 logger.clone(),
@@ -283,23 +319,28 @@ mod tests {
             .unwrap(),
         );
 
-        let km = create_keys_manager(wallet.clone(), &mnemonic, 1, logger.clone()).unwrap();
+        let km = create_keys_manager(wallet.clone(), &mnemonic, 0, 1, logger.clone()).unwrap();
         let pubkey = pubkey_from_keys_manager(&km);
         assert_eq!(
             "02cae09cf2c8842ace44068a5bf3117a494ebbf69a99e79712483c36f97cdb7b54",
             pubkey.to_string()
         );
 
-        let km = create_keys_manager(wallet.clone(), &mnemonic, 2, logger.clone()).unwrap();
+        let km = create_keys_manager(wallet.clone(), &mnemonic, 0, 2, logger.clone()).unwrap();
         let second_pubkey = pubkey_from_keys_manager(&km);
         assert_eq!(
             "03fcc9eaaf0b84946ea7935e3bc4f2b498893c2f53e5d2994d6877d149601ce553",
             second_pubkey.to_string()
         );
 
-        let km = create_keys_manager(wallet, &mnemonic, 2, logger).unwrap();
+        let km = create_keys_manager(wallet.clone(), &mnemonic, 0, 2, logger.clone()).unwrap();
         let second_pubkey_again = pubkey_from_keys_manager(&km);
 
         assert_eq!(second_pubkey, second_pubkey_again);
+
+        // other accounts get their own nodes from the same seed
+        let km = create_keys_manager(wallet, &mnemonic, 1, 2, logger).unwrap();
+        let other_account_pubkey = pubkey_from_keys_manager(&km);
+        assert_ne!(second_pubkey, other_account_pubkey);
     }
 }
@@ -1,18 +1,20 @@
 use crate::error::MutinyError;
 use crate::nodemanager::NodeManager;
 use crate::storage::MutinyStorage;
-use bitcoin::{Address, XOnlyPublicKey};
+use bitcoin::{Address, Network, Txid, XOnlyPublicKey};
 use lightning_invoice::Invoice;
 use lnurl::lightning_address::LightningAddress;
 use lnurl::lnurl::LnUrl;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 use uuid::Uuid;
 
-const ADDRESS_LABELS_MAP_KEY: &str = "address_labels";
-const INVOICE_LABELS_MAP_KEY: &str = "invoice_labels";
-const LABEL_PREFIX: &str = "label/";
-const CONTACT_PREFIX: &str = "contact/";
+pub(crate) const ADDRESS_LABELS_MAP_KEY: &str = "address_labels";
+pub(crate) const INVOICE_LABELS_MAP_KEY: &str = "invoice_labels";
+pub(crate) const LABEL_PREFIX: &str = "label/";
+pub(crate) const CONTACT_PREFIX: &str = "contact/";
+pub(crate) const MEMO_PREFIX: &str = "memo/";
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash, Default)]
 pub struct LabelItem {
@@ -44,6 +46,157 @@ pub enum TagItem {
     Contact((String, Contact)),
 }
 
+/// BIP329 record types we export and import
+pub(crate) const BIP329_ADDRESS_TYPE: &str = "addr";
+pub(crate) const BIP329_TX_TYPE: &str = "tx";
+
+/// A record of the [BIP329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki)
+/// wallet labels export format, one of these is on each line of an export.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Bip329Label {
+    #[serde(rename = "type")]
+    pub label_type: String,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+impl Bip329Label {
+    /// BIP329 labels are plain text with a single label per record,
+    /// so our labels are written together like they are shown
+    fn new(label_type: &str, reference: String, labels: &[String]) -> Self {
+        Self {
+            label_type: label_type.to_string(),
+            reference,
+            label: Some(join_labels(labels)),
+            origin: None,
+            spendable: None,
+        }
+    }
+
+    /// The record's plain text label, if it has one
+    fn label(&self) -> Option<String> {
+        self.label
+            .as_deref()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+    }
+}
+
+fn join_labels(labels: &[String]) -> String {
+    labels.join(", ")
+}
+
+/// Exports the address and transaction labels in the BIP329 JSONL format.
+/// Invoices have no BIP329 type, so their labels are not exported.
+pub(crate) fn export_bip329(
+    address_labels: &HashMap<String, Vec<String>>,
+    tx_labels: &[(Txid, Vec<String>)],
+) -> Result<String, MutinyError> {
+    let mut records = vec![];
+    for (txid, labels) in tx_labels.iter().filter(|(_, l)| !l.is_empty()) {
+        records.push(Bip329Label::new(BIP329_TX_TYPE, txid.to_string(), labels));
+    }
+
+    let mut addresses: Vec<_> = address_labels
+        .iter()
+        .filter(|(_, l)| !l.is_empty())
+        .collect();
+    addresses.sort();
+    for (address, labels) in addresses {
+        records.push(Bip329Label::new(
+            BIP329_ADDRESS_TYPE,
+            address.clone(),
+            labels,
+        ));
+    }
+
+    let lines = records
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(lines.join("\n"))
+}
+
+/// Imports labels from the BIP329 JSONL format, adding them to the labels we already have.
+/// Each record's label is plain text, so it is imported as a single label.
+/// Transaction labels only become the memo of transactions that don't have one when
+/// `memos` is set, memos are private so they aren't filled in from files by default.
+///
+/// Records of other types or for another network are skipped,
+/// returns the number of records that were imported.
+pub(crate) fn import_bip329<L: LabelStorage>(
+    storage: &L,
+    jsonl: &str,
+    network: Network,
+    memos: bool,
+) -> Result<usize, MutinyError> {
+    let records = jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<Bip329Label>(line)
+                .map_err(|_| MutinyError::InvalidArgumentsError)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut count = 0;
+    for record in records {
+        let Some(label) = record.label() else {
+            continue;
+        };
+
+        match record.label_type.as_str() {
+            BIP329_ADDRESS_TYPE => {
+                let Ok(address) = Address::from_str(&record.reference) else {
+                    continue;
+                };
+                if !address.is_valid_for_network(network) {
+                    continue;
+                }
+                let existing = storage
+                    .get_address_labels()?
+                    .remove(&address.to_string())
+                    .unwrap_or_default();
+                // our own export has the labels written together, don't add them again
+                if join_labels(&existing) == label {
+                    continue;
+                }
+                storage.set_address_labels(address, merge_labels(existing, vec![label]))?;
+            }
+            BIP329_TX_TYPE if memos => {
+                let Ok(txid) = Txid::from_str(&record.reference) else {
+                    continue;
+                };
+                if storage.get_memo(txid.to_string())?.is_some() {
+                    continue;
+                }
+                storage.set_memo(txid.to_string(), Some(label))?;
+            }
+            _ => continue,
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Adds the new labels that aren't already in the existing ones
+fn merge_labels(mut existing: Vec<String>, new: Vec<String>) -> Vec<String> {
+    for label in new {
+        if !existing.contains(&label) {
+            existing.push(label);
+        }
+    }
+    existing
+}
+
 fn get_label_item_key(label: impl AsRef<str>) -> String {
     format!("{}{}", LABEL_PREFIX, label.as_ref())
 }
@@ -52,6 +205,10 @@ fn get_contact_key(label: impl AsRef<str>) -> String {
     format!("{}{}", CONTACT_PREFIX, label.as_ref())
 }
 
+fn get_memo_key(id: impl AsRef<str>) -> String {
+    format!("{}{}", MEMO_PREFIX, id.as_ref())
+}
+
 pub trait LabelStorage {
     /// Get a map of addresses to labels. This can be used to get all the labels for an address
     fn get_address_labels(&self) -> Result<HashMap<String, Vec<String>>, MutinyError>;
@@ -87,6 +244,13 @@ pub trait LabelStorage {
     fn edit_contact(&self, id: impl AsRef<str>, contact: Contact) -> Result<(), MutinyError>;
     /// Gets all the existing tags (labels and contacts)
     fn get_tag_items(&self) -> Result<Vec<TagItem>, MutinyError>;
+    /// Get the private memo of a transaction or payment, the id is its txid or payment hash
+    fn get_memo(&self, id: impl AsRef<str>) -> Result<Option<String>, MutinyError>;
+    /// Get a map of txids and payment hashes to their memos
+    fn get_memos(&self) -> Result<HashMap<String, String>, MutinyError>;
+    /// Set the private memo of a transaction or payment, replacing any existing memo.
+    /// Memos are encrypted when the wallet has a password, setting None or an empty memo removes it.
+    fn set_memo(&self, id: impl AsRef<str>, memo: Option<String>) -> Result<(), MutinyError>;
 }
 
 impl<S: MutinyStorage> LabelStorage for S {
@@ -327,6 +491,27 @@ impl<S: MutinyStorage> LabelStorage for S {
 
         Ok(tag_items)
     }
+
+    fn get_memo(&self, id: impl AsRef<str>) -> Result<Option<String>, MutinyError> {
+        self.get_data(get_memo_key(id))
+    }
+
+    fn get_memos(&self) -> Result<HashMap<String, String>, MutinyError> {
+        let all: HashMap<String, String> = self.scan(MEMO_PREFIX, None)?;
+        // remove the prefix from the keys
+        Ok(all
+            .into_iter()
+            .map(|(key, memo)| (key.replace(MEMO_PREFIX, ""), memo))
+            .collect())
+    }
+
+    fn set_memo(&self, id: impl AsRef<str>, memo: Option<String>) -> Result<(), MutinyError> {
+        let key = get_memo_key(id);
+        match memo.filter(|m| !m.is_empty()) {
+            Some(memo) => self.set_data(key, memo),
+            None => self.delete(&[key]),
+        }
+    }
 }
 
 impl<S: MutinyStorage> LabelStorage for NodeManager<S> {
@@ -385,6 +570,18 @@ impl<S: MutinyStorage> LabelStorage for NodeManager<S> {
     fn get_tag_items(&self) -> Result<Vec<TagItem>, MutinyError> {
         self.storage.get_tag_items()
     }
+
+    fn get_memo(&self, id: impl AsRef<str>) -> Result<Option<String>, MutinyError> {
+        self.storage.get_memo(id)
+    }
+
+    fn get_memos(&self) -> Result<HashMap<String, String>, MutinyError> {
+        self.storage.get_memos()
+    }
+
+    fn set_memo(&self, id: impl AsRef<str>, memo: Option<String>) -> Result<(), MutinyError> {
+        self.storage.set_memo(id, memo)
+    }
 }
 
 #[cfg(test)]
@@ -809,4 +1006,114 @@ mod tests {
 
         assert_eq!(result, expected_tag_items);
     }
+
+    #[test]
+    async fn test_memos() {
+        let test_name = "test_memos";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        let txid = "2e1e1a8c1b3a5b6e9f4c2d7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c";
+        let payment_hash = "6f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0";
+
+        assert_eq!(storage.get_memo(txid).unwrap(), None);
+
+        storage
+            .set_memo(txid, Some("rent for march".to_string()))
+            .unwrap();
+        storage
+            .set_memo(payment_hash, Some("coffee".to_string()))
+            .unwrap();
+        assert_eq!(
+            storage.get_memo(txid).unwrap(),
+            Some("rent for march".to_string())
+        );
+
+        let memos = storage.get_memos().unwrap();
+        assert_eq!(memos.len(), 2);
+        assert_eq!(memos.get(payment_hash), Some(&"coffee".to_string()));
+
+        // an empty memo removes it
+        storage.set_memo(txid, Some(String::new())).unwrap();
+        assert_eq!(storage.get_memo(txid).unwrap(), None);
+        storage.set_memo(payment_hash, None).unwrap();
+        assert!(storage.get_memos().unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_bip329_round_trip() {
+        let test_name = "test_bip329_round_trip";
+        log!("{}", test_name);
+
+        let mut address_labels = create_test_address_labels_map();
+        // an address with several labels, one of them with a comma
+        address_labels.insert(
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string(),
+            vec!["rent, march".to_string(), "home".to_string()],
+        );
+        let txid =
+            Txid::from_str("2e1e1a8c1b3a5b6e9f4c2d7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c")
+                .unwrap();
+        let tx_labels = vec![(txid, vec!["rent".to_string(), "march".to_string()])];
+
+        let export = export_bip329(&address_labels, &tx_labels).unwrap();
+        let lines: Vec<&str> = export.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            format!(r#"{{"type":"tx","ref":"{txid}","label":"rent, march"}}"#)
+        );
+        assert!(lines.contains(
+            &r#"{"type":"addr","ref":"1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2","label":"rent, march, home"}"#
+        ));
+        // only BIP329 types are written
+        assert!(lines
+            .iter()
+            .all(|l| l.contains(r#""type":"tx""#) || l.contains(r#""type":"addr""#)));
+
+        // each label is plain text, so it is imported as a single label,
+        // transaction labels only become memos when asked for
+        let storage = MemoryStorage::default();
+        let count = import_bip329(&storage, &export, Network::Bitcoin, false).unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(
+            storage.get_address_labels().unwrap()["1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"],
+            vec!["rent, march, home".to_string()]
+        );
+        assert_eq!(storage.get_memo(txid.to_string()).unwrap(), None);
+
+        import_bip329(&storage, &export, Network::Bitcoin, true).unwrap();
+        assert_eq!(
+            storage.get_memo(txid.to_string()).unwrap(),
+            Some("rent, march".to_string())
+        );
+
+        // importing our own export doesn't duplicate the labels we already have
+        let storage = MemoryStorage::default();
+        for (address, labels) in address_labels.iter() {
+            let address = Address::from_str(address).unwrap();
+            storage.set_address_labels(address, labels.clone()).unwrap();
+        }
+        import_bip329(&storage, &export, Network::Bitcoin, false).unwrap();
+        assert_eq!(storage.get_address_labels().unwrap(), address_labels);
+
+        // a plain label from another wallet is added as a single label
+        let plain =
+            r#"{"type":"addr","ref":"1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa","label":"savings, old"}"#;
+        import_bip329(&storage, plain, Network::Bitcoin, false).unwrap();
+        assert_eq!(
+            storage.get_address_labels().unwrap()["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"],
+            vec!["test1".to_string(), "savings, old".to_string()]
+        );
+
+        // other types and addresses of other networks are skipped
+        let other = r#"{"type":"xpub","ref":"xpub123","label":"cold storage"}
+{"type":"addr","ref":"tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx","label":"testnet"}"#;
+        assert_eq!(
+            import_bip329(&storage, other, Network::Bitcoin, true).unwrap(),
+            0
+        );
+
+        assert!(import_bip329(&storage, "not json", Network::Bitcoin, false).is_err());
+    }
 }
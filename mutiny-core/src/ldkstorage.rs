@@ -1,18 +1,20 @@
 use crate::chain::MutinyChain;
 use crate::error::{MutinyError, MutinyStorageError};
-use crate::event::PaymentInfo;
+use crate::esplora_failover::FailoverEsploraClient;
+use crate::event::{HTLCStatus, PaymentInfo};
 use crate::fees::MutinyFeeEstimator;
 use crate::gossip::{NETWORK_GRAPH_KEY, PROB_SCORER_KEY};
 use crate::keymanager::PhantomKeysManager;
 use crate::logging::MutinyLogger;
-use crate::node::{default_user_config, ChainMonitor, ProbScorer};
+use crate::lspclient::{LspOrder, LspReliability};
+use crate::node::{user_config, ChainMonitor, ProbScorer};
 use crate::node::{NetworkGraph, Router};
-use crate::nodemanager::ChannelClosure;
+use crate::nodemanager::{ChannelClosure, ChannelForensics, ForwardedPayment};
 use crate::storage::MutinyStorage;
 use crate::utils;
 use anyhow::anyhow;
-use bdk_esplora::esplora_client::AsyncClient;
 use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::BlockHash;
 use bitcoin::Network;
 use futures::{try_join, TryFutureExt};
@@ -26,27 +28,35 @@ use lightning::io::Cursor;
 use lightning::ln::channelmanager::{
     self, ChainParameters, ChannelManager as LdkChannelManager, ChannelManagerReadArgs,
 };
-use lightning::ln::PaymentHash;
+use lightning::ln::{PaymentHash, PaymentPreimage};
 use lightning::util::logger::Logger;
 use lightning::util::persist::Persister;
 use lightning::util::ser::{Readable, ReadableArgs, Writeable};
 use lightning::{chain, log_trace};
 use lightning::{
     chain::chainmonitor::{MonitorUpdateId, Persist},
-    log_error,
+    log_error, log_warn,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io;
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub const CHANNEL_MANAGER_KEY: &str = "manager";
 pub const MONITORS_PREFIX_KEY: &str = "monitors/";
-const PAYMENT_INBOUND_PREFIX_KEY: &str = "payment_inbound/";
-const PAYMENT_OUTBOUND_PREFIX_KEY: &str = "payment_outbound/";
-const CHANNEL_OPENING_PARAMS_PREFIX: &str = "chan_open_params/";
-const CHANNEL_CLOSURE_PREFIX: &str = "channel_closure/";
+pub(crate) const PAYMENT_INBOUND_PREFIX_KEY: &str = "payment_inbound/";
+pub(crate) const PAYMENT_OUTBOUND_PREFIX_KEY: &str = "payment_outbound/";
+pub(crate) const CHANNEL_OPENING_PARAMS_PREFIX: &str = "chan_open_params/";
+pub(crate) const CHANNEL_CLOSURE_PREFIX: &str = "channel_closure/";
 const FAILED_SPENDABLE_OUTPUT_DESCRIPTOR_KEY: &str = "failed_spendable_outputs";
+const LSP_ORDER_PREFIX: &str = "lsp_order/";
+const LSP_RELIABILITY_PREFIX: &str = "lsp_reliability/";
+const CLAIM_INTENT_PREFIX: &str = "claim_intent/";
+/// How long we retry a claim whose HTLC expiry we don't know, in seconds
+const CLAIM_INTENT_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+const CHANNEL_FORENSICS_PREFIX: &str = "channel_forensics/";
+const FORWARD_PREFIX: &str = "forward/";
 
 pub(crate) type PhantomChannelManager<S: MutinyStorage> = LdkChannelManager<
     Arc<ChainMonitor<S>>,
@@ -63,6 +73,9 @@ pub(crate) type PhantomChannelManager<S: MutinyStorage> = LdkChannelManager<
 pub struct MutinyNodePersister<S: MutinyStorage> {
     node_id: String,
     pub(crate) storage: S,
+    /// Monitor updates waiting for the remote backup to confirm them,
+    /// with the storage key and the backup's sequence number of the write
+    pending_monitor_updates: utils::Mutex<Vec<(String, u64, OutPoint, MonitorUpdateId)>>,
     logger: Arc<MutinyLogger>,
 }
 
@@ -77,6 +90,7 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
         MutinyNodePersister {
             node_id,
             storage,
+            pending_monitor_updates: utils::Mutex::new(Vec::new()),
             logger,
         }
     }
@@ -106,6 +120,60 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
             })
     }
 
+    /// Persists a channel monitor. If there is a remote backup the update is only
+    /// completed once the backup has uploaded it, see [Self::take_backed_up_monitor_updates].
+    fn persist_monitor<ChannelSigner: WriteableEcdsaChannelSigner>(
+        &self,
+        funding_txo: OutPoint,
+        monitor: &ChannelMonitor<ChannelSigner>,
+        update_id: MonitorUpdateId,
+    ) -> chain::ChannelMonitorUpdateStatus {
+        let key = format!(
+            "{MONITORS_PREFIX_KEY}{}_{}",
+            funding_txo.txid.to_hex(),
+            funding_txo.index
+        );
+        if self.persist_local_storage(&key, monitor).is_err() {
+            return chain::ChannelMonitorUpdateStatus::PermanentFailure;
+        }
+
+        let key_with_node = self.get_key(&key);
+        let sequence = self
+            .storage
+            .vss()
+            .and_then(|vss| vss.last_queued(&key_with_node));
+        match sequence {
+            Some(sequence) => {
+                self.pending_monitor_updates.lock().unwrap().push((
+                    key_with_node,
+                    sequence,
+                    funding_txo,
+                    update_id,
+                ));
+                chain::ChannelMonitorUpdateStatus::InProgress
+            }
+            None => chain::ChannelMonitorUpdateStatus::Completed,
+        }
+    }
+
+    /// Removes and returns the monitor updates the remote backup has uploaded,
+    /// these need to be passed to [ChainMonitor::channel_monitor_updated].
+    pub(crate) fn take_backed_up_monitor_updates(&self) -> Vec<(OutPoint, MonitorUpdateId)> {
+        let Some(vss) = self.storage.vss() else {
+            return vec![];
+        };
+        let mut pending = self.pending_monitor_updates.lock().unwrap();
+        let (uploaded, waiting) = pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|(key, sequence, _, _)| vss.is_uploaded(key, *sequence));
+        *pending = waiting;
+
+        uploaded
+            .into_iter()
+            .map(|(_, _, funding_txo, update_id)| (funding_txo, update_id))
+            .collect()
+    }
+
     // name this param _key so it is not confused with the key
     // that has the concatenated node_id
     fn read_value(&self, _key: &str) -> Result<Vec<u8>, MutinyError> {
@@ -166,7 +234,7 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
         keys_manager: Arc<PhantomKeysManager<S>>,
         router: Arc<Router>,
         mut channel_monitors: Vec<(BlockHash, ChannelMonitor<InMemorySigner>)>,
-        esplora: Arc<AsyncClient>,
+        esplora: Arc<FailoverEsploraClient>,
     ) -> Result<ReadChannelManager<S>, MutinyError> {
         match self.read_value(CHANNEL_MANAGER_KEY) {
             Ok(kv_value) => {
@@ -183,7 +251,7 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
                     mutiny_chain,
                     router,
                     mutiny_logger,
-                    default_user_config(),
+                    user_config(&self.storage),
                     channel_monitor_mut_references,
                 );
                 let mut readable_kv_value = Cursor::new(kv_value);
@@ -228,7 +296,7 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
                         keys_manager.clone(),
                         keys_manager.clone(),
                         keys_manager,
-                        default_user_config(),
+                        user_config(&self.storage),
                         chain_params,
                     );
 
@@ -266,6 +334,15 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
         deserialized_value.ok().flatten()
     }
 
+    pub(crate) fn delete_payment_info(
+        &self,
+        payment_hash: &PaymentHash,
+        inbound: bool,
+    ) -> Result<(), MutinyError> {
+        let key = self.get_key(payment_key(inbound, payment_hash).as_str());
+        self.storage.delete(&[key])
+    }
+
     pub(crate) fn list_payment_info(
         &self,
         inbound: bool,
@@ -334,6 +411,175 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
             .collect())
     }
 
+    /// Records that we are about to claim a payment, so the claim can be
+    /// retried if we are shut down before it completes.
+    pub(crate) fn persist_claim_intent(
+        &self,
+        payment_hash: &PaymentHash,
+        intent: &ClaimIntent,
+    ) -> Result<(), MutinyError> {
+        let key = self.get_key(&claim_intent_key(payment_hash));
+        self.storage.set_data(key, intent)
+    }
+
+    pub(crate) fn delete_claim_intent(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<(), MutinyError> {
+        let key = self.get_key(&claim_intent_key(payment_hash));
+        self.storage.delete(&[key])
+    }
+
+    pub(crate) fn list_claim_intents(
+        &self,
+    ) -> Result<Vec<(PaymentHash, ClaimIntent)>, MutinyError> {
+        let suffix = format!("_{}", self.node_id);
+        let map: HashMap<String, ClaimIntent> =
+            self.storage.scan(CLAIM_INTENT_PREFIX, Some(&suffix))?;
+
+        Ok(map
+            .into_iter()
+            .map(|(key, value)| {
+                let payment_hash_str = key
+                    .trim_start_matches(CLAIM_INTENT_PREFIX)
+                    .trim_end_matches(&suffix);
+                let hash: [u8; 32] =
+                    FromHex::from_hex(payment_hash_str).expect("key should be a sha256 hash");
+                (PaymentHash(hash), value)
+            })
+            .collect())
+    }
+
+    /// Returns the preimages of claims that were started but never completed.
+    /// Intents for payments that did complete, or whose HTLCs expired at the
+    /// given block height, are cleaned up.
+    pub(crate) fn get_unfinished_claims(
+        &self,
+        best_height: u32,
+    ) -> Result<Vec<PaymentPreimage>, MutinyError> {
+        let now = utils::now().as_secs();
+        let mut preimages = vec![];
+        for (payment_hash, intent) in self.list_claim_intents()? {
+            let claimed = self
+                .read_payment_info(&payment_hash, true, &self.logger)
+                .map_or(false, |p| p.status == HTLCStatus::Succeeded);
+            if claimed {
+                self.delete_claim_intent(&payment_hash)?;
+            } else if intent.is_expired(best_height, now) {
+                log_warn!(
+                    self.logger,
+                    "Dropping expired claim for payment hash: {}",
+                    payment_hash.0.to_hex()
+                );
+                self.delete_claim_intent(&payment_hash)?;
+            } else {
+                preimages.push(PaymentPreimage(intent.preimage));
+            }
+        }
+
+        Ok(preimages)
+    }
+
+    /// Called when a payment to us was failed back, like a held payment that
+    /// timed out or a claim that came too late. The claim is no longer retried
+    /// and a held payment is marked as failed.
+    pub(crate) fn fail_inbound_payment(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<(), MutinyError> {
+        self.delete_claim_intent(payment_hash)?;
+
+        if let Some(mut payment_info) = self.read_payment_info(payment_hash, true, &self.logger) {
+            if payment_info.status == HTLCStatus::InFlight {
+                payment_info.status = HTLCStatus::Failed;
+                payment_info.last_update = utils::now().as_secs();
+                self.persist_payment_info(payment_hash, &payment_info, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn persist_lsp_order(&self, order: &LspOrder) -> Result<(), MutinyError> {
+        let key = self.get_key(&format!("{LSP_ORDER_PREFIX}{}", order.payment_hash));
+        self.storage.set_data(key, order)
+    }
+
+    pub(crate) fn get_lsp_order(
+        &self,
+        payment_hash: &PaymentHash,
+    ) -> Result<Option<LspOrder>, MutinyError> {
+        let key = self.get_key(&format!("{LSP_ORDER_PREFIX}{}", payment_hash.0.to_hex()));
+        self.storage.get_data(key)
+    }
+
+    pub(crate) fn list_lsp_orders(&self) -> Result<Vec<LspOrder>, MutinyError> {
+        let suffix = format!("_{}", self.node_id);
+        let map: HashMap<String, LspOrder> = self.storage.scan(LSP_ORDER_PREFIX, Some(&suffix))?;
+        Ok(map.into_values().collect())
+    }
+
+    pub(crate) fn get_lsp_reliability(
+        &self,
+        lsp_pubkey: &PublicKey,
+    ) -> Result<LspReliability, MutinyError> {
+        let key = self.get_key(&format!("{LSP_RELIABILITY_PREFIX}{lsp_pubkey}"));
+        Ok(self.storage.get_data(key)?.unwrap_or_default())
+    }
+
+    /// Records whether the LSP came through when we asked it to wrap an invoice
+    pub(crate) fn record_lsp_result(
+        &self,
+        lsp_pubkey: &PublicKey,
+        success: bool,
+    ) -> Result<(), MutinyError> {
+        let mut reliability = self.get_lsp_reliability(lsp_pubkey)?;
+        reliability.record(success);
+        let key = self.get_key(&format!("{LSP_RELIABILITY_PREFIX}{lsp_pubkey}"));
+        self.storage.set_data(key, reliability)
+    }
+
+    pub(crate) fn persist_channel_forensics(
+        &self,
+        forensics: &ChannelForensics,
+    ) -> Result<(), MutinyError> {
+        let key = self.get_key(&format!(
+            "{CHANNEL_FORENSICS_PREFIX}{}",
+            forensics.channel_id
+        ));
+        self.storage.set_data(key, forensics)
+    }
+
+    pub(crate) fn get_channel_forensics(
+        &self,
+        channel_id: &[u8; 32],
+    ) -> Result<Option<ChannelForensics>, MutinyError> {
+        let key = self.get_key(&format!(
+            "{CHANNEL_FORENSICS_PREFIX}{}",
+            channel_id.to_hex()
+        ));
+        self.storage.get_data(key)
+    }
+
+    pub(crate) fn list_channel_forensics(&self) -> Result<Vec<ChannelForensics>, MutinyError> {
+        let suffix = format!("_{}", self.node_id);
+        let map: HashMap<String, ChannelForensics> =
+            self.storage.scan(CHANNEL_FORENSICS_PREFIX, Some(&suffix))?;
+        Ok(map.into_values().collect())
+    }
+
+    pub(crate) fn persist_forward(&self, forward: &ForwardedPayment) -> Result<(), MutinyError> {
+        let key = self.get_key(&format!("{FORWARD_PREFIX}{}", Uuid::new_v4()));
+        self.storage.set_data(key, forward)
+    }
+
+    pub(crate) fn list_forwards(&self) -> Result<Vec<ForwardedPayment>, MutinyError> {
+        let suffix = format!("_{}", self.node_id);
+        let map: HashMap<String, ForwardedPayment> =
+            self.storage.scan(FORWARD_PREFIX, Some(&suffix))?;
+        Ok(map.into_values().collect())
+    }
+
     /// Persists the failed spendable outputs to storage.
     /// Previously failed spendable outputs are not overwritten.
     ///
@@ -415,10 +661,38 @@ impl<S: MutinyStorage> MutinyNodePersister<S> {
     }
 }
 
+/// A payment we have started claiming but have not seen complete yet
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub(crate) struct ClaimIntent {
+    pub preimage: [u8; 32],
+    pub amount_msat: u64,
+    pub created_at: u64,
+    /// The block height the payment has to be claimed by, if we know it
+    #[serde(default)]
+    pub claim_deadline: Option<u32>,
+}
+
+impl ClaimIntent {
+    /// Once the HTLCs expire the payment is failed back, so the claim can't complete.
+    /// Without a deadline the claim is given up on after [CLAIM_INTENT_MAX_AGE_SECS].
+    pub fn is_expired(&self, best_height: u32, now: u64) -> bool {
+        match self.claim_deadline {
+            Some(deadline) => best_height >= deadline,
+            None => now > self.created_at.saturating_add(CLAIM_INTENT_MAX_AGE_SECS),
+        }
+    }
+}
+
 fn channel_open_params_key(id: u128) -> String {
     format!("{CHANNEL_OPENING_PARAMS_PREFIX}{id}")
 }
 
+fn claim_intent_key(payment_hash: &PaymentHash) -> String {
+    format!("{CLAIM_INTENT_PREFIX}{}", payment_hash.0.to_hex())
+}
+
+/// How to fund a channel we are opening. Without any utxos
+/// the funding transaction spends from the wallet at the given fee rate.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct ChannelOpenParams {
     pub sats_per_kw: u32,
@@ -488,17 +762,9 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner, S: MutinyStorage> Persist<Chann
         &self,
         funding_txo: OutPoint,
         monitor: &ChannelMonitor<ChannelSigner>,
-        _update_id: MonitorUpdateId,
+        update_id: MonitorUpdateId,
     ) -> chain::ChannelMonitorUpdateStatus {
-        let key = format!(
-            "{MONITORS_PREFIX_KEY}{}_{}",
-            funding_txo.txid.to_hex(),
-            funding_txo.index
-        );
-        match self.persist_local_storage(&key, monitor) {
-            Ok(()) => chain::ChannelMonitorUpdateStatus::Completed,
-            Err(_) => chain::ChannelMonitorUpdateStatus::PermanentFailure,
-        }
+        self.persist_monitor(funding_txo, monitor, update_id)
     }
 
     fn update_persisted_channel(
@@ -506,29 +772,21 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner, S: MutinyStorage> Persist<Chann
         funding_txo: OutPoint,
         _update: Option<&ChannelMonitorUpdate>,
         monitor: &ChannelMonitor<ChannelSigner>,
-        _update_id: MonitorUpdateId,
+        update_id: MonitorUpdateId,
     ) -> chain::ChannelMonitorUpdateStatus {
-        let key = format!(
-            "{MONITORS_PREFIX_KEY}{}_{}",
-            funding_txo.txid.to_hex(),
-            funding_txo.index
-        );
-        match self.persist_local_storage(&key, monitor) {
-            Ok(()) => chain::ChannelMonitorUpdateStatus::Completed,
-            Err(_) => chain::ChannelMonitorUpdateStatus::PermanentFailure,
-        }
+        self.persist_monitor(funding_txo, monitor, update_id)
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::event::{HTLCStatus, MillisatAmount};
+    use crate::nodemanager::ChannelClosureKind;
     use crate::storage::MemoryStorage;
     use bitcoin::hashes::Hash;
     use bitcoin::secp256k1::PublicKey;
     use bitcoin::Txid;
     use std::str::FromStr;
-    use uuid::Uuid;
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
 
     use super::*;
@@ -563,6 +821,9 @@ mod test {
             fee_paid_msat: None,
             bolt11: None,
             payee_pubkey: Some(pubkey),
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             secret: None,
             last_update: utils::now().as_secs(),
         };
@@ -590,6 +851,178 @@ mod test {
         assert_eq!(list.len(), 1);
         assert_eq!(list[0].0, payment_hash);
         assert_eq!(list[0].1.preimage, Some(preimage));
+
+        persister.delete_payment_info(&payment_hash, true).unwrap();
+
+        let result = persister.read_payment_info(&payment_hash, true, &MutinyLogger::default());
+        assert!(result.is_none());
+        assert!(persister.list_payment_info(true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_claim_intent_survives_restart() {
+        let test_name = "test_claim_intent_survives_restart";
+        log!("{}", test_name);
+
+        let id = Uuid::new_v4().to_string();
+        let storage = MemoryStorage::default();
+        let logger = Arc::new(MutinyLogger::default());
+        let persister = MutinyNodePersister::new(id.clone(), storage.clone(), logger.clone());
+
+        let claimed_hash = PaymentHash([0; 32]);
+        let unclaimed_hash = PaymentHash([1; 32]);
+        for (hash, preimage) in [(claimed_hash, [2; 32]), (unclaimed_hash, [3; 32])] {
+            let intent = ClaimIntent {
+                preimage,
+                amount_msat: 1_000,
+                created_at: utils::now().as_secs(),
+                claim_deadline: Some(100),
+            };
+            persister.persist_claim_intent(&hash, &intent).unwrap();
+        }
+
+        // the first claim finished before we were killed, the second one did not
+        let payment_info = PaymentInfo {
+            preimage: Some([2; 32]),
+            status: HTLCStatus::Succeeded,
+            amt_msat: MillisatAmount(Some(1_000)),
+            fee_paid_msat: None,
+            bolt11: None,
+            payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
+            secret: None,
+            last_update: utils::now().as_secs(),
+        };
+        persister
+            .persist_payment_info(&claimed_hash, &payment_info, true)
+            .unwrap();
+        drop(persister);
+
+        // on restart only the unfinished claim is retried
+        let persister = MutinyNodePersister::new(id, storage, logger);
+        let unfinished = persister.get_unfinished_claims(50).unwrap();
+        assert_eq!(unfinished, vec![PaymentPreimage([3; 32])]);
+        assert_eq!(persister.list_claim_intents().unwrap().len(), 1);
+
+        persister.delete_claim_intent(&unclaimed_hash).unwrap();
+        assert!(persister.get_unfinished_claims(50).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_claim_intent_expiry() {
+        let test_name = "test_claim_intent_expiry";
+        log!("{}", test_name);
+
+        let persister = get_test_persister();
+        let now = utils::now().as_secs();
+
+        let with_deadline = ClaimIntent {
+            preimage: [1; 32],
+            amount_msat: 1_000,
+            created_at: now,
+            claim_deadline: Some(100),
+        };
+        assert!(!with_deadline.is_expired(99, now));
+        assert!(with_deadline.is_expired(100, now));
+
+        // settled hodl payments don't know their deadline, they expire with age
+        let without_deadline = ClaimIntent {
+            claim_deadline: None,
+            ..with_deadline.clone()
+        };
+        assert!(!without_deadline.is_expired(u32::MAX, now));
+        assert!(without_deadline.is_expired(0, now + CLAIM_INTENT_MAX_AGE_SECS + 1));
+
+        // expired claims are dropped on start instead of being retried forever
+        let hash = PaymentHash([2; 32]);
+        persister
+            .persist_claim_intent(&hash, &with_deadline)
+            .unwrap();
+        assert_eq!(
+            persister.get_unfinished_claims(99).unwrap(),
+            vec![PaymentPreimage([1; 32])]
+        );
+        assert!(persister.get_unfinished_claims(100).unwrap().is_empty());
+        assert!(persister.list_claim_intents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fail_inbound_payment() {
+        let test_name = "test_fail_inbound_payment";
+        log!("{}", test_name);
+
+        let persister = get_test_persister();
+        let payment_info = |status| PaymentInfo {
+            preimage: None,
+            status,
+            amt_msat: MillisatAmount(Some(1_000)),
+            fee_paid_msat: None,
+            bolt11: None,
+            payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
+            secret: None,
+            last_update: utils::now().as_secs(),
+        };
+
+        // a held payment that was failed back is failed and its claim dropped
+        let held_hash = PaymentHash([3; 32]);
+        persister
+            .persist_payment_info(&held_hash, &payment_info(HTLCStatus::InFlight), true)
+            .unwrap();
+        let intent = ClaimIntent {
+            preimage: [4; 32],
+            amount_msat: 1_000,
+            created_at: utils::now().as_secs(),
+            claim_deadline: None,
+        };
+        persister.persist_claim_intent(&held_hash, &intent).unwrap();
+
+        persister.fail_inbound_payment(&held_hash).unwrap();
+        let held = persister
+            .read_payment_info(&held_hash, true, &MutinyLogger::default())
+            .unwrap();
+        assert_eq!(held.status, HTLCStatus::Failed);
+        assert!(persister.list_claim_intents().unwrap().is_empty());
+
+        // an invoice that rejected a payment can still be paid
+        let pending_hash = PaymentHash([5; 32]);
+        persister
+            .persist_payment_info(&pending_hash, &payment_info(HTLCStatus::Pending), true)
+            .unwrap();
+        persister.fail_inbound_payment(&pending_hash).unwrap();
+        let pending = persister
+            .read_payment_info(&pending_hash, true, &MutinyLogger::default())
+            .unwrap();
+        assert_eq!(pending.status, HTLCStatus::Pending);
+    }
+
+    #[test]
+    fn test_persist_forwards() {
+        let test_name = "test_persist_forwards";
+        log!("{}", test_name);
+
+        let persister = get_test_persister();
+        assert!(persister.list_forwards().unwrap().is_empty());
+
+        let forward = ForwardedPayment {
+            inbound_channel_id: Some([1; 32].to_hex()),
+            outbound_channel_id: Some([2; 32].to_hex()),
+            fee_earned_msat: Some(1_000),
+            claimed_on_chain: false,
+            timestamp: utils::now().as_secs(),
+        };
+        persister.persist_forward(&forward).unwrap();
+        persister.persist_forward(&forward).unwrap();
+
+        // each forward is kept, even identical ones
+        assert_eq!(
+            persister.list_forwards().unwrap(),
+            vec![forward.clone(), forward]
+        );
     }
 
     #[test]
@@ -604,7 +1037,10 @@ mod test {
             user_channel_id: Some(user_channel_id.to_be_bytes()),
             channel_id: Some([1; 32]),
             node_id: None,
+            kind: ChannelClosureKind::Cooperative,
             reason: "This is a test.".to_string(),
+            closing_txid: None,
+            swept_sats: None,
             timestamp: utils::now().as_secs(),
         };
         let result = persister.persist_channel_closure(user_channel_id, closure.clone());
@@ -617,6 +1053,33 @@ mod test {
         assert_eq!(result, Some(closure));
     }
 
+    #[test]
+    fn test_persist_channel_forensics() {
+        let test_name = "test_persist_channel_forensics";
+        log!("{}", test_name);
+
+        let persister = get_test_persister();
+
+        let channel_id = [2; 32];
+        assert_eq!(persister.get_channel_forensics(&channel_id).unwrap(), None);
+
+        let forensics = ChannelForensics {
+            channel_id: channel_id.to_hex(),
+            peer: None,
+            funding_txo: None,
+            reason: "This is a test.".to_string(),
+            closed_at: utils::now().as_secs(),
+            commitment_txids: vec![Txid::all_zeros()],
+            balances_at_close: vec![],
+            sweep_txids: vec![],
+        };
+        persister.persist_channel_forensics(&forensics).unwrap();
+
+        let result = persister.get_channel_forensics(&channel_id).unwrap();
+        assert_eq!(result, Some(forensics.clone()));
+        assert_eq!(persister.list_channel_forensics().unwrap(), vec![forensics]);
+    }
+
     #[test]
     fn test_persist_spendable_output_descriptor() {
         let test_name = "test_persist_spendable_output_descriptor";
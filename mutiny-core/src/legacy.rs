@@ -0,0 +1,207 @@
+use crate::error::MutinyError;
+use crate::gossip::LN_PEER_METADATA_KEY_PREFIX;
+use crate::labels::{ADDRESS_LABELS_MAP_KEY, CONTACT_PREFIX, INVOICE_LABELS_MAP_KEY, LABEL_PREFIX};
+use crate::ldkstorage::{
+    CHANNEL_CLOSURE_PREFIX, CHANNEL_MANAGER_KEY, CHANNEL_OPENING_PARAMS_PREFIX,
+    MONITORS_PREFIX_KEY, PAYMENT_INBOUND_PREFIX_KEY, PAYMENT_OUTBOUND_PREFIX_KEY,
+};
+use crate::storage::{decrypt_value, MutinyStorage, AUTH_PROFILES_KEY, MNEMONIC_KEY, NODES_KEY};
+use bip39::Mnemonic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// What was brought over from the legacy `node-manager` storage
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LegacyMigrationSummary {
+    /// How many keys were copied into the current storage
+    pub migrated_keys: usize,
+    /// Keys that already existed in the current storage and were left alone
+    pub skipped_keys: usize,
+    pub channel_managers: usize,
+    pub channel_monitors: usize,
+}
+
+/// Keys the legacy `node-manager` crate wrote that mean the same thing today.
+///
+/// The legacy crate used the same key names, the values were only kept in local
+/// storage instead of indexed db. On-chain and gossip caches are not migrated,
+/// they are rebuilt on the first sync.
+fn is_legacy_wallet_key(key: &str) -> bool {
+    match key {
+        MNEMONIC_KEY | NODES_KEY | AUTH_PROFILES_KEY => true,
+        ADDRESS_LABELS_MAP_KEY | INVOICE_LABELS_MAP_KEY => true,
+        str if str.starts_with(CHANNEL_MANAGER_KEY) => true,
+        str if str.starts_with(MONITORS_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_INBOUND_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_OUTBOUND_PREFIX_KEY) => true,
+        str if str.starts_with(CHANNEL_OPENING_PARAMS_PREFIX) => true,
+        str if str.starts_with(CHANNEL_CLOSURE_PREFIX) => true,
+        str if str.starts_with(LN_PEER_METADATA_KEY_PREFIX) => true,
+        str if str.starts_with(LABEL_PREFIX) => true,
+        str if str.starts_with(CONTACT_PREFIX) => true,
+        _ => false,
+    }
+}
+
+/// Copies a wallet from the legacy `node-manager` storage into the current storage,
+/// so early users keep their seed, channels and labels.
+///
+/// Values are decrypted, including the legacy encryption of the seed and channel
+/// managers, and written again so they are encrypted with the current password.
+/// Anything that already exists in the current storage is newer and is left alone.
+/// If the current storage has a different seed, nothing is migrated.
+///
+/// A storage with a password must be unlocked first.
+pub fn migrate_legacy_storage<S: MutinyStorage>(
+    legacy: HashMap<String, Value>,
+    storage: &S,
+) -> Result<LegacyMigrationSummary, MutinyError> {
+    if storage.is_locked()? {
+        return Err(MutinyError::WalletLocked);
+    }
+    let cipher = storage.cipher();
+
+    // either side may be encrypted, so compare the seeds themselves
+    if let Some(legacy_seed) = legacy.get(MNEMONIC_KEY) {
+        let legacy_seed = decrypt_value(MNEMONIC_KEY, legacy_seed.clone(), cipher.as_ref())?;
+        let legacy_seed: Mnemonic = serde_json::from_value(legacy_seed)?;
+        match storage.get_mnemonic() {
+            Ok(seed) if seed != legacy_seed => return Err(MutinyError::WalletExists),
+            Ok(_) | Err(MutinyError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut summary = LegacyMigrationSummary::default();
+    for (key, value) in legacy {
+        if !is_legacy_wallet_key(&key) {
+            continue;
+        }
+
+        if storage.get::<Value>(&key)?.is_some() {
+            summary.skipped_keys += 1;
+            continue;
+        }
+
+        if key.starts_with(CHANNEL_MANAGER_KEY) {
+            summary.channel_managers += 1;
+        } else if key.starts_with(MONITORS_PREFIX_KEY) {
+            summary.channel_monitors += 1;
+        }
+
+        let value = decrypt_value(&key, value, cipher.as_ref())?;
+        storage.set_data(&key, value)?;
+        summary.migrated_keys += 1;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encrypt::{encrypt, is_encrypted};
+    use crate::keymanager::generate_seed;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_migrate_legacy_storage() {
+        let test_name = "test_migrate_legacy_storage";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).unwrap();
+        let storage = MemoryStorage::default();
+        storage
+            .set("monitors/existing_node", Value::String("newer".to_string()))
+            .unwrap();
+
+        let mut legacy: HashMap<String, Value> = [
+            ("manager_node", "manager"),
+            ("monitors/existing_node", "older"),
+            ("monitors/other_node", "monitor"),
+            ("label/abc", "label"),
+            ("some_frontend_setting", "ignored"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+        .collect();
+        legacy.insert(
+            MNEMONIC_KEY.to_string(),
+            serde_json::to_value(&seed).unwrap(),
+        );
+
+        let summary = migrate_legacy_storage(legacy.clone(), &storage).unwrap();
+        assert_eq!(summary.migrated_keys, 4);
+        assert_eq!(summary.skipped_keys, 1);
+        assert_eq!(summary.channel_managers, 1);
+        assert_eq!(summary.channel_monitors, 1);
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+
+        // newer data is kept and unknown keys are not copied
+        let monitor: Option<Value> = storage.get("monitors/existing_node").unwrap();
+        assert_eq!(monitor, Some(Value::String("newer".to_string())));
+        let setting: Option<Value> = storage.get("some_frontend_setting").unwrap();
+        assert_eq!(setting, None);
+
+        // the same wallet can be migrated again
+        assert!(migrate_legacy_storage(legacy.clone(), &storage).is_ok());
+
+        // a different wallet is never mixed in
+        let mut other = legacy;
+        let other_seed = generate_seed(12).unwrap();
+        other.insert(
+            MNEMONIC_KEY.to_string(),
+            serde_json::to_value(other_seed).unwrap(),
+        );
+        assert!(matches!(
+            migrate_legacy_storage(other, &storage),
+            Err(MutinyError::WalletExists)
+        ));
+    }
+
+    #[test]
+    fn test_migrate_legacy_storage_with_password() {
+        let test_name = "test_migrate_legacy_storage_with_password";
+        log!("{}", test_name);
+
+        let password = "password";
+        let seed = generate_seed(12).unwrap();
+        let storage = MemoryStorage::default();
+        storage.unlock(password).unwrap();
+        storage.insert_mnemonic(seed.clone()).unwrap();
+
+        // the legacy seed used the legacy encryption, the monitor was in plaintext
+        let seed_json = serde_json::to_string(&seed).unwrap();
+        let legacy: HashMap<String, Value> = [
+            (MNEMONIC_KEY, encrypt(&seed_json, password)),
+            ("monitors/node", "monitor".to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v)))
+        .collect();
+
+        // the encrypted seeds are the same wallet
+        let summary = migrate_legacy_storage(legacy.clone(), &storage).unwrap();
+        assert_eq!(summary.migrated_keys, 1);
+        assert_eq!(summary.skipped_keys, 1);
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+
+        // the migrated monitor is encrypted with the current password
+        let raw: Value = storage.get("monitors/node").unwrap().unwrap();
+        assert!(is_encrypted(raw.as_str().unwrap()));
+        let monitor: Option<String> = storage.get_data("monitors/node").unwrap();
+        assert_eq!(monitor, Some("monitor".to_string()));
+
+        // nothing is migrated while locked
+        storage.lock();
+        assert!(matches!(
+            migrate_legacy_storage(legacy, &storage),
+            Err(MutinyError::WalletLocked)
+        ));
+    }
+}
@@ -10,43 +10,75 @@
 // background file is mostly an LDK copy paste
 mod background;
 
+pub mod audit;
 mod auth;
 mod chain;
+mod coordinator;
 pub mod encrypt;
 pub mod error;
 pub mod esplora;
+pub mod esplora_failover;
 mod event;
 mod fees;
+pub mod fiat;
 mod gossip;
 mod keymanager;
 pub mod labels;
 mod ldkstorage;
+pub mod legacy;
+pub mod lnurlpay;
 pub mod logging;
 mod lspclient;
 mod networking;
 mod node;
 pub mod nodemanager;
 mod nostr;
+pub mod notifier;
 mod onchain;
+pub mod onionmessage;
+pub mod paymentrequest;
 mod peermanager;
+pub mod peermessage;
+pub mod pos;
+pub mod pricing;
 pub mod redshift;
+pub mod rescue;
+mod scb;
+pub mod signing;
 pub mod storage;
+pub mod swaps;
+pub mod syncstatus;
 
 #[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
 mod utils;
-
-pub use crate::gossip::{GOSSIP_SYNC_TIME_KEY, NETWORK_GRAPH_KEY, PROB_SCORER_KEY};
+pub mod vss;
+pub mod watch;
+
+pub use crate::event::{ChannelAcceptor, ChannelRequest, PaymentFailureReason, PaymentPart};
+pub use crate::fees::{FeeSource, MempoolSpaceFeeSource, MAX_FEE_RATE_SATS_PER_VBYTE};
+pub use crate::gossip::{
+    import_gossip_snapshot, NetworkGraphSize, ReconnectPolicy, GOSSIP_SYNC_TIME_KEY,
+    NETWORK_GRAPH_KEY, PROB_SCORER_KEY,
+};
 pub use crate::keymanager::generate_seed;
 pub use crate::ldkstorage::{CHANNEL_MANAGER_KEY, MONITORS_PREFIX_KEY};
+pub use crate::lspclient::LspSelection;
+pub use crate::nostr::receive::ReceiveCredential;
+pub use crate::nostr::zap::{ZapRecord, ZapTarget};
+pub use crate::onchain::{RevealedAddresses, DEFAULT_STOP_GAP};
 
 use crate::error::MutinyError;
+use crate::nodemanager::MutinyInvoice;
 use crate::nodemanager::NodeManager;
+use crate::nostr::receive::ReceiveCredentialStorage;
+use crate::nostr::zap::ZapStorage;
 use crate::nostr::NostrManager;
-use crate::storage::MutinyStorage;
+use crate::storage::{MemoryStorage, MutinyStorage};
 use ::nostr::Kind;
 pub use auth::AuthProfile;
 use bip39::Mnemonic;
+use bitcoin::hashes::sha256;
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::util::bip32::ExtendedPrivKey;
 use bitcoin::Network;
@@ -54,18 +86,37 @@ use futures::{pin_mut, select, FutureExt};
 use lightning::util::logger::Logger;
 use lightning::{log_error, log_warn};
 use nostr_sdk::{Client, RelayMessage, RelayPoolNotification};
+use serde_json::Value;
+use std::str::FromStr;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct MutinyWalletConfig {
     mnemonic: Option<Mnemonic>,
+    /// A single websocket proxy or a comma separated list of them to fail over between
     #[cfg(target_arch = "wasm32")]
     websocket_proxy_addr: Option<String>,
     network: Option<Network>,
+    /// A single esplora url or a comma separated list of them to fail over between
     user_esplora_url: Option<String>,
+    /// A single RGS url or a comma separated list of them to try in order
     user_rgs_url: Option<String>,
     lsp_url: Option<String>,
+    watch_only: Option<String>,
+    external_signer: Option<String>,
+    vss_url: Option<String>,
+    restore_from_vss: bool,
+    channel_backup: Option<String>,
+    account_index: Option<u32>,
+    fee_source: Option<Arc<dyn FeeSource>>,
+    channel_acceptor: Option<Arc<dyn ChannelAcceptor>>,
+    stop_gap: Option<usize>,
+    address_lookahead: Option<u32>,
+    #[cfg(not(target_arch = "wasm32"))]
+    socks_proxy: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    listen_port: Option<u16>,
 }
 
 impl MutinyWalletConfig {
@@ -86,8 +137,135 @@ impl MutinyWalletConfig {
             user_esplora_url,
             user_rgs_url,
             lsp_url,
+            watch_only: None,
+            external_signer: None,
+            vss_url: None,
+            restore_from_vss: false,
+            channel_backup: None,
+            account_index: None,
+            fee_source: None,
+            channel_acceptor: None,
+            stop_gap: None,
+            address_lookahead: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            listen_port: None,
         }
     }
+
+    /// Mirrors channel state and payment history to the given remote backup server.
+    /// Everything is encrypted with a key derived from the seed before it is uploaded.
+    /// Channel updates are only completed once the server has them.
+    pub fn with_vss_url(mut self, url: String) -> Self {
+        self.vss_url = Some(url);
+        self
+    }
+
+    /// Restores the wallet's state from the remote backup server on startup
+    /// if there are no nodes saved locally. This requires [MutinyWalletConfig::with_vss_url].
+    pub fn with_restore_from_vss(mut self) -> Self {
+        self.restore_from_vss = true;
+        self
+    }
+
+    /// Recovers the wallet's channels from a backup made with
+    /// [NodeManager::export_channel_backup] if there are no nodes saved locally.
+    ///
+    /// The nodes start in recovery mode and reconnect to the peer of every channel in
+    /// the backup, the peers force close the channels and the funds are swept to the
+    /// on-chain wallet once they are claimable. The peers must come online for this.
+    /// This requires the mnemonic the backup was made with.
+    pub fn with_channel_backup(mut self, backup: String) -> Self {
+        self.channel_backup = Some(backup);
+        self
+    }
+
+    /// Makes the wallet watch-only, tracking the given xpub or output descriptor.
+    /// No mnemonic is used and lightning is disabled, spending methods and the
+    /// nostr features of [MutinyWallet] return [MutinyError::WatchOnly].
+    ///
+    /// A watch-only wallet should use its own storage, the descriptor is saved
+    /// there so the wallet is loaded again without it.
+    pub fn with_watch_only(mut self, descriptor: String) -> Self {
+        self.watch_only = Some(descriptor);
+        self.mnemonic = None;
+        self
+    }
+
+    /// Keeps the on-chain keys on an external signer, like a hardware wallet, tracking
+    /// its funds with the given xpub or output descriptor. Lightning still uses the
+    /// mnemonic's keys, but channels can't be funded from the on-chain wallet.
+    ///
+    /// On-chain spends return an unsigned PSBT for the signer, see
+    /// [NodeManager::send_to_address] and [NodeManager::broadcast_psbt].
+    pub fn with_external_signer(mut self, descriptor: String) -> Self {
+        self.external_signer = Some(descriptor);
+        self
+    }
+
+    /// Derives the wallet's keys from the given account index, so one seed can back
+    /// several independent wallets, each with its own storage. On-chain keys use
+    /// `m/86'/coin'/index'`, lightning keys `m/1'/index'` (`m/0'` for index 0).
+    ///
+    /// [NodeManager::rotate_onchain_keys] moves on-chain addresses to `m/86'/coin'/index'/n'`,
+    /// so rotating never reuses the keys of another index.
+    /// An existing wallet can't change its index, see [NodeManager::get_derivation_paths].
+    pub fn with_account_index(mut self, index: u32) -> Self {
+        self.account_index = Some(index);
+        self
+    }
+
+    /// Sets where fee estimates come from, like a [MempoolSpaceFeeSource] or the
+    /// integrator's own node. The esplora server is used when it fails.
+    /// Fee rates from any source are capped at [MAX_FEE_RATE_SATS_PER_VBYTE].
+    pub fn with_fee_source(mut self, fee_source: Arc<dyn FeeSource>) -> Self {
+        self.fee_source = Some(fee_source);
+        self
+    }
+
+    /// Asks the given [ChannelAcceptor] about inbound channels that pass the
+    /// channel acceptance policy before accepting them, like to prompt the user.
+    pub fn with_channel_acceptor(mut self, acceptor: Arc<dyn ChannelAcceptor>) -> Self {
+        self.channel_acceptor = Some(acceptor);
+        self
+    }
+
+    /// Sets how many unused addresses in a row the on-chain sync looks at before it stops,
+    /// [DEFAULT_STOP_GAP] by default. Restores of wallets that handed out a lot of
+    /// addresses without using them need a wider gap to find all their funds.
+    pub fn with_stop_gap(mut self, stop_gap: usize) -> Self {
+        self.stop_gap = Some(stop_gap);
+        self
+    }
+
+    /// Makes every on-chain sync look at this many addresses past the last one handed out,
+    /// however many unused addresses come before them.
+    pub fn with_address_lookahead(mut self, lookahead: u32) -> Self {
+        self.address_lookahead = Some(lookahead);
+        self
+    }
+
+    /// Routes all outbound HTTP requests, nostr relay connections and lightning peer
+    /// connections through the given SOCKS5 proxy, like `127.0.0.1:9050` for a local
+    /// Tor daemon. Host names are resolved by the proxy, so onion addresses work too.
+    /// A [FeeSource] is given the proxied client to make its requests with.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_socks_proxy(mut self, proxy: String) -> Self {
+        self.socks_proxy = Some(proxy);
+        self
+    }
+
+    /// Accepts inbound lightning connections, so peers and LSPs can connect to us.
+    /// Each node listens on its own port, counting up from this one by its index.
+    ///
+    /// Public nodes announce the addresses from [NodeManager::set_node_announcement_config],
+    /// a bare ip there is announced with the node's listening port.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_listen_port(mut self, port: u16) -> Self {
+        self.listen_port = Some(port);
+        self
+    }
 }
 
 #[derive(Clone)]
@@ -98,39 +276,38 @@ pub struct MutinyWallet<S: MutinyStorage> {
     config: MutinyWalletConfig,
     storage: S,
     pub node_manager: Arc<NodeManager<S>>,
-    pub nostr: Arc<NostrManager>,
+    /// Derived from the seed, so watch-only wallets don't have one
+    pub nostr: Option<Arc<NostrManager>>,
 }
 
 impl<S: MutinyStorage> MutinyWallet<S> {
-    #[allow(clippy::too_many_arguments)]
+    /// Creates the wallet, or loads the one saved in storage, with the given config.
+    /// See [MutinyWalletConfig] for the optional settings, like an external signer.
     pub async fn new(
+        config: MutinyWalletConfig,
         storage: S,
-        mnemonic: Option<Mnemonic>,
-        #[cfg(target_arch = "wasm32")] websocket_proxy_addr: Option<String>,
-        network: Option<Network>,
-        user_esplora_url: Option<String>,
-        user_rgs_url: Option<String>,
-        lsp_url: Option<String>,
     ) -> Result<MutinyWallet<S>, MutinyError> {
-        let config = MutinyWalletConfig::new(
-            mnemonic,
-            #[cfg(target_arch = "wasm32")]
-            websocket_proxy_addr,
-            network,
-            user_esplora_url,
-            user_rgs_url,
-            lsp_url,
-        );
-
         let node_manager = Arc::new(NodeManager::new(config.clone(), storage.clone()).await?);
 
-        NodeManager::start_sync(node_manager.clone());
-
         // create nostr manager
-        let seed = node_manager.show_seed().to_seed("");
-        let xprivkey = ExtendedPrivKey::new_master(node_manager.get_network(), &seed)?;
-        let relays = vec!["wss://nostr.mutinywallet.com".to_string()]; // todo make configurable
-        let nostr = Arc::new(NostrManager::from_mnemonic(xprivkey, relays)?);
+        let nostr = match node_manager.show_seed() {
+            Some(mnemonic) => {
+                let seed = mnemonic.to_seed("");
+                let xprivkey = ExtendedPrivKey::new_master(node_manager.get_network(), &seed)?;
+                let relays = vec!["wss://nostr.mutinywallet.com".to_string()]; // todo make configurable
+                Some(Arc::new(NostrManager::from_mnemonic(
+                    xprivkey,
+                    relays,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    config.socks_proxy.as_deref().filter(|p| !p.is_empty()),
+                )?))
+            }
+            None => None,
+        };
+
+        // only start once nothing else can fail, so a failed wallet leaves nothing running
+        NodeManager::start_sync(node_manager.clone());
+        NodeManager::start_rebroadcasts(node_manager.clone());
 
         Ok(Self {
             config,
@@ -147,13 +324,23 @@ impl<S: MutinyStorage> MutinyWallet<S> {
         self.node_manager =
             Arc::new(NodeManager::new(self.config.clone(), self.storage.clone()).await?);
         NodeManager::start_sync(self.node_manager.clone());
+        NodeManager::start_rebroadcasts(self.node_manager.clone());
         NodeManager::start_redshifts(self.node_manager.clone());
+        NodeManager::start_swaps(self.node_manager.clone());
         Ok(())
     }
 
-    /// Starts a background process that will watch for nostr wallet connect events
+    /// The wallet's nostr keys, watch-only wallets don't have any
+    fn nostr(&self) -> Result<&Arc<NostrManager>, MutinyError> {
+        self.nostr.as_ref().ok_or(MutinyError::WatchOnly)
+    }
+
+    /// Starts a background process that will watch for nostr wallet connect events.
+    /// Does nothing for watch-only wallets, they can't pay or create invoices.
     pub async fn start_nostr_wallet_connect(&self, from_node: PublicKey) {
-        let nostr = self.nostr.clone();
+        let Some(nostr) = self.nostr.clone() else {
+            return;
+        };
         let nm = self.node_manager.clone();
         utils::spawn(async move {
             let mut broadcasted_info = false;
@@ -179,20 +366,16 @@ impl<S: MutinyStorage> MutinyWallet<S> {
                 let add_relay_res = client.add_relays(nostr.relays.clone()).await;
 
                 #[cfg(not(target_arch = "wasm32"))]
-                let add_relay_res = client
-                    .add_relays(
-                        nostr
-                            .relays
-                            .clone()
-                            .into_iter()
-                            .map(|s| (s, None))
-                            .collect(),
-                    )
-                    .await;
+                let add_relay_res = client.add_relays(nostr.proxied_relays()).await;
 
                 add_relay_res.expect("Failed to add relays");
                 client.connect().await;
-                client.subscribe(vec![nostr.create_nwc_filter()]).await;
+                client
+                    .subscribe(vec![
+                        nostr.create_nwc_filter(),
+                        nostr.create_receive_filter(),
+                    ])
+                    .await;
 
                 // broadcast NWC info event
                 // todo we only need to broadcast on creation
@@ -261,6 +444,67 @@ impl<S: MutinyStorage> MutinyWallet<S> {
         });
     }
 
+    /// Zaps a nostr note or profile, `target` is a `note` or `npub`.
+    /// The zap is signed with the wallet's nostr key.
+    pub async fn zap(
+        &self,
+        from_node: &PublicKey,
+        target: &str,
+        amount_sats: u64,
+        comment: Option<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let target = ZapTarget::from_str(target)?;
+        self.nostr()?
+            .zap(&self.node_manager, from_node, target, amount_sats, comment)
+            .await
+    }
+
+    /// Gets the zap that was made with the given payment
+    pub fn get_zap(&self, payment_hash: &sha256::Hash) -> Result<Option<ZapRecord>, MutinyError> {
+        self.storage.get_zap(payment_hash)
+    }
+
+    /// Lists all the zaps we have sent
+    pub fn list_zaps(&self) -> Result<Vec<ZapRecord>, MutinyError> {
+        self.storage.list_zaps()
+    }
+
+    /// Creates a credential that lets a third party create invoices for this wallet
+    /// over Nostr Wallet Connect, but nothing else. Returns the credential and the
+    /// connect URI to give to the third party.
+    ///
+    /// Requests are only answered while [MutinyWallet::start_nostr_wallet_connect] is running.
+    pub fn create_receive_credential(
+        &self,
+        name: String,
+        max_invoices_per_hour: u32,
+        max_amount_sats: Option<u64>,
+    ) -> Result<(ReceiveCredential, String), MutinyError> {
+        self.nostr()?.create_receive_credential(
+            &self.storage,
+            name,
+            max_invoices_per_hour,
+            max_amount_sats,
+        )
+    }
+
+    /// Gets the connect URI of a receive credential that hasn't been revoked
+    pub fn get_receive_credential_uri(&self, client_pubkey: &str) -> Result<String, MutinyError> {
+        self.nostr()?
+            .get_receive_credential_uri(&self.storage, client_pubkey)
+    }
+
+    /// Lists all receive credentials, including revoked ones
+    pub fn list_receive_credentials(&self) -> Result<Vec<ReceiveCredential>, MutinyError> {
+        self.storage.list_receive_credentials()
+    }
+
+    /// Revokes a receive credential so it can't create invoices anymore
+    pub fn revoke_receive_credential(&self, client_pubkey: &str) -> Result<(), MutinyError> {
+        self.nostr()?
+            .revoke_receive_credential(&self.storage, client_pubkey)
+    }
+
     /// Stops all of the nodes and background processes.
     /// Returns after node has been stopped.
     pub async fn stop(&self) -> Result<(), MutinyError> {
@@ -287,10 +531,73 @@ impl<S: MutinyStorage> MutinyWallet<S> {
     }
 }
 
+impl MutinyWallet<MemoryStorage> {
+    /// Creates a throwaway wallet with a new seed that is only kept in memory.
+    ///
+    /// Everything is lost once the wallet is dropped, this is meant for demos,
+    /// tipping widgets and tests. Use [MutinyWallet::promote] to keep it.
+    pub async fn new_ephemeral(
+        #[cfg(target_arch = "wasm32")] websocket_proxy_addr: Option<String>,
+        network: Option<Network>,
+        user_esplora_url: Option<String>,
+        user_rgs_url: Option<String>,
+        lsp_url: Option<String>,
+    ) -> Result<Self, MutinyError> {
+        let config = MutinyWalletConfig::new(
+            None,
+            #[cfg(target_arch = "wasm32")]
+            websocket_proxy_addr,
+            network,
+            user_esplora_url,
+            user_rgs_url,
+            lsp_url,
+        );
+        Self::new(config, MemoryStorage::default()).await
+    }
+
+    /// Moves an ephemeral wallet into persistent storage and restarts it there.
+    ///
+    /// The given storage must not already have a wallet in it, if it has a
+    /// password it must be unlocked so the seed can be encrypted.
+    pub async fn promote<T: MutinyStorage>(
+        self,
+        storage: T,
+    ) -> Result<MutinyWallet<T>, MutinyError> {
+        if NodeManager::has_node_manager(storage.clone()) {
+            return Err(MutinyError::WalletExists);
+        }
+
+        // stop first so nothing is written while we copy
+        self.stop().await?;
+
+        for key in self.storage.scan_keys("", None)? {
+            if let Some(value) = self.storage.get_data::<Value>(&key)? {
+                storage.set_data(&key, value)?;
+            }
+        }
+
+        let node_manager = Arc::new(NodeManager::new(self.config.clone(), storage.clone()).await?);
+        NodeManager::start_sync(node_manager.clone());
+        NodeManager::start_rebroadcasts(node_manager.clone());
+
+        Ok(MutinyWallet {
+            config: self.config,
+            storage,
+            node_manager,
+            nostr: self.nostr,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{nodemanager::NodeManager, MutinyWallet};
+    use crate::error::MutinyError;
+    use crate::keymanager::generate_seed;
+    use crate::{nodemanager::NodeManager, MutinyWallet, MutinyWalletConfig};
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
     use bitcoin::Network;
+    use std::str::FromStr;
 
     use crate::test_utils::*;
 
@@ -299,15 +606,8 @@ mod tests {
 
     wasm_bindgen_test_configure!(run_in_browser);
 
-    #[test]
-    async fn create_mutiny_wallet() {
-        let test_name = "create_mutiny_wallet";
-        log!("{}", test_name);
-
-        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
-        assert!(!NodeManager::has_node_manager(storage.clone()));
-        MutinyWallet::new(
-            storage.clone(),
+    fn regtest_config() -> MutinyWalletConfig {
+        MutinyWalletConfig::new(
             None,
             #[cfg(target_arch = "wasm32")]
             None,
@@ -316,11 +616,48 @@ mod tests {
             None,
             None,
         )
-        .await
-        .expect("mutiny wallet should initialize");
+    }
+
+    #[test]
+    async fn create_mutiny_wallet() {
+        let test_name = "create_mutiny_wallet";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        assert!(!NodeManager::has_node_manager(storage.clone()));
+        MutinyWallet::new(regtest_config(), storage.clone())
+            .await
+            .expect("mutiny wallet should initialize");
         assert!(NodeManager::has_node_manager(storage));
     }
 
+    #[test]
+    async fn create_watch_only_mutiny_wallet() {
+        let test_name = "create_watch_only_mutiny_wallet";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).unwrap();
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Regtest, &seed.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &path).unwrap());
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let config = regtest_config().with_watch_only(xpub.to_string());
+        let mw = MutinyWallet::new(config, storage.clone())
+            .await
+            .expect("watch-only mutiny wallet should initialize");
+        assert!(NodeManager::has_node_manager(storage));
+
+        assert!(mw.node_manager.is_watch_only());
+        assert!(mw.nostr.is_none());
+        assert!(mw.node_manager.get_new_address(vec![]).is_ok());
+        assert!(matches!(
+            mw.create_receive_credential("test".to_string(), 10, None),
+            Err(MutinyError::WatchOnly)
+        ));
+    }
+
     #[test]
     async fn restart_mutiny_wallet() {
         let test_name = "restart_mutiny_wallet";
@@ -328,18 +665,9 @@ mod tests {
 
         let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
         assert!(!NodeManager::has_node_manager(storage.clone()));
-        let mut mw = MutinyWallet::new(
-            storage.clone(),
-            None,
-            #[cfg(target_arch = "wasm32")]
-            None,
-            Some(Network::Regtest),
-            None,
-            None,
-            None,
-        )
-        .await
-        .expect("mutiny wallet should initialize");
+        let mut mw = MutinyWallet::new(regtest_config(), storage.clone())
+            .await
+            .expect("mutiny wallet should initialize");
         assert!(NodeManager::has_node_manager(storage));
 
         let first_seed = mw.node_manager.show_seed();
@@ -357,9 +685,26 @@ mod tests {
         let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
 
         assert!(!NodeManager::has_node_manager(storage.clone()));
-        let mut mw = MutinyWallet::new(
-            storage.clone(),
-            None,
+        let mut mw = MutinyWallet::new(regtest_config(), storage.clone())
+            .await
+            .expect("mutiny wallet should initialize");
+        assert!(NodeManager::has_node_manager(storage));
+
+        assert!(mw.node_manager.list_nodes().await.unwrap().is_empty());
+        assert!(mw.node_manager.new_node().await.is_ok());
+        assert!(!mw.node_manager.list_nodes().await.unwrap().is_empty());
+
+        assert!(mw.stop().await.is_ok());
+        assert!(mw.start().await.is_ok());
+        assert!(!mw.node_manager.list_nodes().await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn promote_ephemeral_wallet() {
+        let test_name = "promote_ephemeral_wallet";
+        log!("{}", test_name);
+
+        let mw = MutinyWallet::new_ephemeral(
             #[cfg(target_arch = "wasm32")]
             None,
             Some(Network::Regtest),
@@ -368,15 +713,35 @@ mod tests {
             None,
         )
         .await
-        .expect("mutiny wallet should initialize");
-        assert!(NodeManager::has_node_manager(storage));
-
-        assert!(mw.node_manager.list_nodes().await.unwrap().is_empty());
+        .expect("ephemeral wallet should initialize");
         assert!(mw.node_manager.new_node().await.is_ok());
-        assert!(!mw.node_manager.list_nodes().await.unwrap().is_empty());
+        let seed = mw.node_manager.show_seed();
 
-        assert!(mw.stop().await.is_ok());
-        assert!(mw.start().await.is_ok());
-        assert!(!mw.node_manager.list_nodes().await.unwrap().is_empty());
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        assert!(!NodeManager::has_node_manager(storage.clone()));
+
+        let promoted = mw
+            .promote(storage.clone())
+            .await
+            .expect("wallet should be promoted");
+        assert!(NodeManager::has_node_manager(storage.clone()));
+        assert_eq!(promoted.node_manager.show_seed(), seed);
+        assert!(!promoted.node_manager.list_nodes().await.unwrap().is_empty());
+
+        // can't promote into storage that already has a wallet
+        let other = MutinyWallet::new_ephemeral(
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            other.promote(storage).await,
+            Err(crate::error::MutinyError::WalletExists)
+        ));
     }
 }
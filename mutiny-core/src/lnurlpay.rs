@@ -0,0 +1,333 @@
+use crate::error::MutinyError;
+use crate::storage::MutinyStorage;
+use crate::utils::sleep;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Information about the payer that a LNURL-pay service can ask for (LUD-18)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PayerData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    /// A lightning address or other identifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+struct PayerDataField {
+    mandatory: bool,
+}
+
+/// The payer data fields a service accepts, from the `payerData` of its pay response
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct PayerDataSpec {
+    name: Option<PayerDataField>,
+    pubkey: Option<PayerDataField>,
+    identifier: Option<PayerDataField>,
+    email: Option<PayerDataField>,
+    /// Signing with a LNURL-auth key is not supported
+    auth: Option<PayerDataField>,
+}
+
+/// The parts of a LNURL-pay response for comments and payer data,
+/// which the lnurl client doesn't parse.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PayResponseExtras {
+    /// The longest comment the service accepts (LUD-12)
+    #[serde(rename = "commentAllowed")]
+    comment_allowed: Option<u32>,
+    #[serde(rename = "payerData")]
+    payer_data: Option<PayerDataSpec>,
+}
+
+impl PayResponseExtras {
+    /// Checks the comment is allowed by the service
+    pub fn validate_comment(&self, comment: &str) -> Result<(), MutinyError> {
+        let allowed = self.comment_allowed.unwrap_or(0) as usize;
+        if comment.chars().count() > allowed {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        Ok(())
+    }
+
+    /// Returns only the payer data the service asked for,
+    /// erroring if something it requires is missing.
+    pub fn filter_payer_data(&self, payer_data: &PayerData) -> Result<PayerData, MutinyError> {
+        let spec = self.payer_data.clone().unwrap_or_default();
+        if spec.auth.is_some_and(|a| a.mandatory) {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        fn field(
+            spec: Option<PayerDataField>,
+            value: &Option<String>,
+        ) -> Result<Option<String>, MutinyError> {
+            match spec {
+                Some(f) if f.mandatory && value.is_none() => {
+                    Err(MutinyError::InvalidArgumentsError)
+                }
+                Some(_) => Ok(value.clone()),
+                None => Ok(None),
+            }
+        }
+
+        Ok(PayerData {
+            name: field(spec.name, &payer_data.name)?,
+            pubkey: field(spec.pubkey, &payer_data.pubkey)?,
+            identifier: field(spec.identifier, &payer_data.identifier)?,
+            email: field(spec.email, &payer_data.email)?,
+        })
+    }
+}
+
+/// Checks the amount is within what the service accepts, from its `minSendable`/`maxSendable`
+pub(crate) fn validate_pay_amount(
+    msats: u64,
+    min_sendable: u64,
+    max_sendable: u64,
+) -> Result<(), MutinyError> {
+    if msats < min_sendable || msats > max_sendable {
+        return Err(MutinyError::BadAmountError);
+    }
+    Ok(())
+}
+
+/// Builds the callback url to request an invoice with a comment and payer data
+pub(crate) fn pay_callback_url(
+    callback: &str,
+    msats: u64,
+    comment: Option<&str>,
+    payer_data: Option<&PayerData>,
+) -> Result<Url, MutinyError> {
+    let mut url = Url::parse(callback).map_err(|_| MutinyError::LnUrlFailure)?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("amount", &msats.to_string());
+        if let Some(comment) = comment {
+            query.append_pair("comment", comment);
+        }
+        if let Some(payer_data) = payer_data {
+            query.append_pair("payerdata", &serde_json::to_string(payer_data)?);
+        }
+    }
+    Ok(url)
+}
+
+/// The response to a LNURL-pay callback
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct PayCallbackResponse {
+    pub pr: Option<String>,
+    pub reason: Option<String>,
+    /// Where to check if the invoice was settled (LUD-21)
+    pub verify: Option<String>,
+}
+
+/// The response from a LNURL-verify url (LUD-21)
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct VerifyResponse {
+    #[serde(default)]
+    pub settled: bool,
+    pub preimage: Option<String>,
+}
+
+impl VerifyResponse {
+    /// Returns the preimage if the service says the invoice was settled
+    /// and the preimage matches the payment hash.
+    pub fn confirmed_preimage(&self, payment_hash: &sha256::Hash) -> Option<String> {
+        if !self.settled {
+            return None;
+        }
+        let preimage = self.preimage.as_ref()?;
+        let bytes: Vec<u8> = FromHex::from_hex(preimage).ok()?;
+        (sha256::Hash::hash(&bytes) == *payment_hash).then(|| preimage.clone())
+    }
+}
+
+/// How many times to check the verify url before giving up
+const VERIFY_ATTEMPTS: usize = 5;
+
+/// Polls a LNURL-verify url until the service says the invoice was settled.
+/// Returns the preimage it reported, if it matches the payment hash.
+pub(crate) async fn verify_lnurl_payment(
+    client: &Client,
+    verify_url: &str,
+    payment_hash: &sha256::Hash,
+) -> Option<String> {
+    for attempt in 0..VERIFY_ATTEMPTS {
+        if attempt > 0 {
+            sleep(1_000).await;
+        }
+
+        let Ok(response) = client.get(verify_url).send().await else {
+            continue;
+        };
+        let Ok(verify) = response.json::<VerifyResponse>().await else {
+            continue;
+        };
+        if let Some(preimage) = verify.confirmed_preimage(payment_hash) {
+            return Some(preimage);
+        }
+    }
+
+    None
+}
+
+/// What we sent along with a LNURL-pay payment, and what the service confirmed
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LnUrlPayMetadata {
+    pub comment: Option<String>,
+    pub payer_data: Option<PayerData>,
+    /// The LNURL-verify url the service gave for the invoice
+    #[serde(default)]
+    pub verify_url: Option<String>,
+    /// The preimage the service confirmed through the verify url
+    #[serde(default)]
+    pub verified_preimage: Option<String>,
+}
+
+pub trait LnUrlPayStorage {
+    fn get_lnurl_pay_metadata(
+        &self,
+        payment_hash: &sha256::Hash,
+    ) -> Result<Option<LnUrlPayMetadata>, MutinyError>;
+    fn persist_lnurl_pay_metadata(
+        &self,
+        payment_hash: &sha256::Hash,
+        metadata: LnUrlPayMetadata,
+    ) -> Result<(), MutinyError>;
+}
+
+const LNURL_PAY_METADATA_KEY_PREFIX: &str = "lnurl_pay/";
+
+fn get_lnurl_pay_metadata_key(payment_hash: &sha256::Hash) -> String {
+    format!("{LNURL_PAY_METADATA_KEY_PREFIX}{}", payment_hash.to_hex())
+}
+
+impl<S: MutinyStorage> LnUrlPayStorage for S {
+    fn get_lnurl_pay_metadata(
+        &self,
+        payment_hash: &sha256::Hash,
+    ) -> Result<Option<LnUrlPayMetadata>, MutinyError> {
+        self.get_data(get_lnurl_pay_metadata_key(payment_hash))
+    }
+
+    fn persist_lnurl_pay_metadata(
+        &self,
+        payment_hash: &sha256::Hash,
+        metadata: LnUrlPayMetadata,
+    ) -> Result<(), MutinyError> {
+        self.set_data(get_lnurl_pay_metadata_key(payment_hash), metadata)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_comment_and_payer_data() {
+        let test_name = "test_comment_and_payer_data";
+        log!("{}", test_name);
+
+        let extras: PayResponseExtras = serde_json::from_str(
+            r#"{"commentAllowed": 5, "payerData": {"name": {"mandatory": false}, "identifier": {"mandatory": true}}}"#,
+        )
+        .unwrap();
+
+        assert!(extras.validate_comment("hello").is_ok());
+        assert!(extras.validate_comment("hello!").is_err());
+        // services that don't set commentAllowed don't take comments
+        assert!(PayResponseExtras::default().validate_comment("hi").is_err());
+
+        let payer_data = PayerData {
+            name: Some("satoshi".to_string()),
+            identifier: Some("satoshi@example.com".to_string()),
+            email: Some("satoshi@example.com".to_string()),
+            ..Default::default()
+        };
+        // the email was not asked for so it is not sent
+        let filtered = extras.filter_payer_data(&payer_data).unwrap();
+        assert_eq!(filtered.email, None);
+        assert_eq!(filtered.name, payer_data.name);
+
+        let missing = PayerData {
+            name: Some("satoshi".to_string()),
+            ..Default::default()
+        };
+        assert!(extras.filter_payer_data(&missing).is_err());
+
+        assert!(validate_pay_amount(1_000, 1_000, 5_000).is_ok());
+        assert!(validate_pay_amount(5_000, 1_000, 5_000).is_ok());
+        assert!(validate_pay_amount(999, 1_000, 5_000).is_err());
+        assert!(validate_pay_amount(5_001, 1_000, 5_000).is_err());
+
+        let url = pay_callback_url(
+            "https://example.com/pay?id=1",
+            1_000,
+            Some("thanks!"),
+            Some(&filtered),
+        )
+        .unwrap();
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs[0], ("id".to_string(), "1".to_string()));
+        assert_eq!(pairs[1], ("amount".to_string(), "1000".to_string()));
+        assert_eq!(pairs[2], ("comment".to_string(), "thanks!".to_string()));
+        assert_eq!(pairs[3].0, "payerdata");
+
+        let storage = MemoryStorage::default();
+        let payment_hash = sha256::Hash::hash(&[0; 32]);
+        let metadata = LnUrlPayMetadata {
+            comment: Some("thanks!".to_string()),
+            payer_data: Some(filtered),
+            ..Default::default()
+        };
+        storage
+            .persist_lnurl_pay_metadata(&payment_hash, metadata.clone())
+            .unwrap();
+        assert_eq!(
+            storage.get_lnurl_pay_metadata(&payment_hash).unwrap(),
+            Some(metadata)
+        );
+    }
+
+    #[test]
+    fn test_verify_response() {
+        let test_name = "test_verify_response";
+        log!("{}", test_name);
+
+        let preimage = [1u8; 32];
+        let payment_hash = sha256::Hash::hash(&preimage);
+
+        let settled: VerifyResponse = serde_json::from_str(&format!(
+            r#"{{"status": "OK", "settled": true, "preimage": "{}", "pr": "lnbc1"}}"#,
+            preimage.to_hex()
+        ))
+        .unwrap();
+        assert_eq!(
+            settled.confirmed_preimage(&payment_hash),
+            Some(preimage.to_hex())
+        );
+
+        // a preimage for another payment is not a confirmation
+        let other_hash = sha256::Hash::hash(&[2u8; 32]);
+        assert_eq!(settled.confirmed_preimage(&other_hash), None);
+
+        let unsettled: VerifyResponse =
+            serde_json::from_str(r#"{"status": "OK", "settled": false, "preimage": null}"#)
+                .unwrap();
+        assert_eq!(unsettled.confirmed_preimage(&payment_hash), None);
+    }
+}
@@ -3,6 +3,7 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::error::MutinyError;
+use crate::utils;
 
 #[derive(Clone, Debug)]
 pub(crate) struct LspClient {
@@ -58,9 +59,229 @@ pub struct FeeRequest {
     pub amount_msat: u64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FeeResponse {
     pub fee_amount_msat: u64,
+    /// How many blocks the LSP promises to keep the channel open, if it offers a lease
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_duration_blocks: Option<u32>,
+}
+
+/// A channel bought from an LSP through a JIT invoice, along with what the LSP delivered.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LspOrder {
+    pub payment_hash: String,
+    pub lsp_pubkey: PublicKey,
+    /// The amount of the invoice the channel was bought for
+    pub amount_sat: u64,
+    pub fee_msat: u64,
+    /// How many blocks the LSP promised to keep the channel open, if it offered a lease
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_duration_blocks: Option<u32>,
+    pub created_at: u64,
+    pub status: LspOrderStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<LspOrderChannel>,
+    #[serde(default)]
+    pub discrepancies: Vec<LspDiscrepancy>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LspOrderStatus {
+    /// The invoice has not been paid yet
+    Pending,
+    /// The channel matched the order
+    Delivered,
+    /// The LSP did not deliver what was ordered, see the discrepancies
+    UnderDelivered,
+}
+
+/// The channel the LSP opened for an order
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LspOrderChannel {
+    pub channel_id: String,
+    pub capacity_sat: u64,
+    /// Number of confirmations the funding transaction had when the payment arrived
+    pub confirmations: u32,
+    /// Block height when the payment arrived
+    pub opened_at_height: u32,
+}
+
+/// A way the LSP did not hold up its end of an order
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LspDiscrepancy {
+    /// The channel is smaller than the amount that was paid for
+    InsufficientCapacity { expected_sat: u64, actual_sat: u64 },
+    /// The LSP waited for the funding transaction to confirm before forwarding the payment
+    NotZeroConf { confirmations: u32 },
+    /// The LSP force closed the channel before the lease ran out
+    ClosedBeforeLeaseEnd {
+        closed_at_height: u32,
+        lease_end_height: u32,
+    },
+}
+
+impl LspOrder {
+    /// Checks the delivered channel against the order and records it.
+    /// Returns the discrepancies that were found.
+    pub(crate) fn channel_delivered(&mut self, channel: LspOrderChannel) -> Vec<LspDiscrepancy> {
+        let mut discrepancies = vec![];
+        if channel.capacity_sat < self.amount_sat {
+            discrepancies.push(LspDiscrepancy::InsufficientCapacity {
+                expected_sat: self.amount_sat,
+                actual_sat: channel.capacity_sat,
+            });
+        }
+        if channel.confirmations > 0 {
+            discrepancies.push(LspDiscrepancy::NotZeroConf {
+                confirmations: channel.confirmations,
+            });
+        }
+
+        self.channel = Some(channel);
+        self.record_discrepancies(discrepancies.clone());
+        discrepancies
+    }
+
+    /// Checks if the channel was closed by the LSP before the lease ended.
+    /// Returns the discrepancy if one was found.
+    pub(crate) fn channel_force_closed(&mut self, closed_at_height: u32) -> Option<LspDiscrepancy> {
+        let lease_end_height =
+            self.channel.as_ref()?.opened_at_height + self.lease_duration_blocks?;
+        if closed_at_height >= lease_end_height {
+            return None;
+        }
+
+        let discrepancy = LspDiscrepancy::ClosedBeforeLeaseEnd {
+            closed_at_height,
+            lease_end_height,
+        };
+        self.record_discrepancies(vec![discrepancy.clone()]);
+        Some(discrepancy)
+    }
+
+    fn record_discrepancies(&mut self, discrepancies: Vec<LspDiscrepancy>) {
+        self.discrepancies.extend(discrepancies);
+        self.status = if self.discrepancies.is_empty() {
+            LspOrderStatus::Delivered
+        } else {
+            LspOrderStatus::UnderDelivered
+        };
+    }
+}
+
+/// How often an LSP came through when we asked it to wrap an invoice
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LspReliability {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl LspReliability {
+    /// The chance, in percent, that the LSP comes through next time.
+    /// LSPs we haven't used yet start at 50%.
+    pub fn percent(&self) -> u8 {
+        let total = self.successes as u64 + self.failures as u64;
+        ((self.successes as u64 + 1) * 100 / (total + 2)) as u8
+    }
+
+    pub(crate) fn record(&mut self, success: bool) {
+        if success {
+            self.successes = self.successes.saturating_add(1);
+        } else {
+            self.failures = self.failures.saturating_add(1);
+        }
+    }
+}
+
+/// Which LSP was chosen to wrap an invoice, and what the choice was based on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LspSelection {
+    pub lsp_pubkey: PublicKey,
+    pub fee_msat: u64,
+    /// If we already had enough inbound capacity with the LSP,
+    /// otherwise it opens a new channel for the payment
+    pub has_inbound_capacity: bool,
+    pub reliability_percent: u8,
+    /// How many LSPs gave us a fee quote for the invoice
+    pub candidates: usize,
+    /// The better ranked LSPs that failed to wrap the invoice before this one did
+    #[serde(default)]
+    pub failed_over_from: Vec<PublicKey>,
+}
+
+/// What receiving a payment through an LSP is expected to cost,
+/// quoted by the LSP that would be used for the invoice
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LspFeeQuote {
+    pub lsp_pubkey: PublicKey,
+    /// The fee in sats, rounded up so sub-sat fees still show
+    pub fee_sats: u64,
+    pub fee_msats: u64,
+    /// If the LSP has to open a new channel to us for the payment
+    pub opens_channel: bool,
+    /// How many blocks the LSP promises to keep a new channel open, if it offers a lease
+    pub lease_duration_blocks: Option<u32>,
+}
+
+/// Added to the fee of an LSP that has to open a new channel for a payment,
+/// preferring LSPs we already have inbound capacity with.
+const NEW_CHANNEL_PENALTY_MSAT: u64 = 1_000_000;
+
+/// An LSP that quoted a fee for wrapping an invoice
+#[derive(Clone, Debug)]
+pub(crate) struct LspCandidate {
+    pub client: LspClient,
+    pub fee: FeeResponse,
+    pub has_inbound_capacity: bool,
+    pub reliability: LspReliability,
+}
+
+impl LspCandidate {
+    /// The fee plus the new channel penalty, weighted by how unreliable the LSP has been.
+    /// One msat is added so reliability still decides between LSPs that don't charge a fee.
+    fn weighted_cost(&self) -> u64 {
+        let penalty = if self.has_inbound_capacity {
+            0
+        } else {
+            NEW_CHANNEL_PENALTY_MSAT
+        };
+        let cost = self
+            .fee
+            .fee_amount_msat
+            .saturating_add(penalty)
+            .saturating_add(1);
+        // same as dividing by the chance the LSP comes through
+        let total = self.reliability.successes as u64 + self.reliability.failures as u64;
+        cost.saturating_mul(total + 2) / (self.reliability.successes as u64 + 1)
+    }
+
+    pub fn quote(&self) -> LspFeeQuote {
+        LspFeeQuote {
+            lsp_pubkey: self.client.pubkey,
+            fee_sats: utils::msats_to_sats_round_up(self.fee.fee_amount_msat),
+            fee_msats: self.fee.fee_amount_msat,
+            opens_channel: !self.has_inbound_capacity,
+            lease_duration_blocks: self.fee.lease_duration_blocks,
+        }
+    }
+
+    pub fn selection(&self, candidates: usize, failed_over_from: Vec<PublicKey>) -> LspSelection {
+        LspSelection {
+            lsp_pubkey: self.client.pubkey,
+            fee_msat: self.fee.fee_amount_msat,
+            has_inbound_capacity: self.has_inbound_capacity,
+            reliability_percent: self.reliability.percent(),
+            candidates,
+            failed_over_from,
+        }
+    }
+}
+
+/// Orders the LSPs from the one we should use first to the one we should use last
+pub(crate) fn rank_lsp_candidates(mut candidates: Vec<LspCandidate>) -> Vec<LspCandidate> {
+    candidates.sort_by_key(|c| c.weighted_cost());
+    candidates
 }
 
 const GET_INFO_PATH: &str = "/api/v1/info";
@@ -68,8 +289,7 @@ const PROPOSAL_PATH: &str = "/api/v1/proposal";
 const FEE_PATH: &str = "/api/v1/fee";
 
 impl LspClient {
-    pub async fn new(url: &str) -> anyhow::Result<Self> {
-        let http_client = Client::new();
+    pub async fn new(url: &str, http_client: Client) -> anyhow::Result<Self> {
         let get_info_response: GetInfoResponse = http_client
             .get(format!("{}{}", url, GET_INFO_PATH))
             .send()
@@ -135,7 +355,7 @@ impl LspClient {
         Ok(proposal_response.jit_bolt11)
     }
 
-    pub(crate) async fn get_lsp_fee_msat(&self, fee_request: FeeRequest) -> anyhow::Result<u64> {
+    pub(crate) async fn get_lsp_fee(&self, fee_request: FeeRequest) -> anyhow::Result<FeeResponse> {
         let fee_response: FeeResponse = self
             .http_client
             .post(format!("{}{}", &self.url, FEE_PATH))
@@ -147,6 +367,162 @@ impl LspClient {
             .await
             .map_err(|_| MutinyError::LspFailure)?;
 
-        Ok(fee_response.fee_amount_msat)
+        Ok(fee_response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use std::str::FromStr;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn order(lease_duration_blocks: Option<u32>) -> LspOrder {
+        LspOrder {
+            payment_hash: "00".repeat(32),
+            lsp_pubkey: PublicKey::from_str(
+                "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b",
+            )
+            .unwrap(),
+            amount_sat: 100_000,
+            fee_msat: 1_000_000,
+            lease_duration_blocks,
+            created_at: 0,
+            status: LspOrderStatus::Pending,
+            channel: None,
+            discrepancies: vec![],
+        }
+    }
+
+    fn channel(capacity_sat: u64, confirmations: u32) -> LspOrderChannel {
+        LspOrderChannel {
+            channel_id: "11".repeat(32),
+            capacity_sat,
+            confirmations,
+            opened_at_height: 100,
+        }
+    }
+
+    #[test]
+    fn test_lsp_order_verification() {
+        let test_name = "test_lsp_order_verification";
+        log!("{}", test_name);
+
+        let mut delivered = order(Some(1_000));
+        assert!(delivered.channel_delivered(channel(200_000, 0)).is_empty());
+        assert_eq!(delivered.status, LspOrderStatus::Delivered);
+        // closing after the lease is fine
+        assert_eq!(delivered.channel_force_closed(1_100), None);
+        assert_eq!(delivered.status, LspOrderStatus::Delivered);
+
+        let mut short = order(Some(1_000));
+        let discrepancies = short.channel_delivered(channel(50_000, 1));
+        assert_eq!(
+            discrepancies,
+            vec![
+                LspDiscrepancy::InsufficientCapacity {
+                    expected_sat: 100_000,
+                    actual_sat: 50_000,
+                },
+                LspDiscrepancy::NotZeroConf { confirmations: 1 },
+            ]
+        );
+        assert_eq!(short.status, LspOrderStatus::UnderDelivered);
+
+        let mut closed_early = order(Some(1_000));
+        closed_early.channel_delivered(channel(200_000, 0));
+        assert_eq!(
+            closed_early.channel_force_closed(500),
+            Some(LspDiscrepancy::ClosedBeforeLeaseEnd {
+                closed_at_height: 500,
+                lease_end_height: 1_100,
+            })
+        );
+        assert_eq!(closed_early.status, LspOrderStatus::UnderDelivered);
+
+        // no lease, nothing to break
+        let mut no_lease = order(None);
+        no_lease.channel_delivered(channel(200_000, 0));
+        assert_eq!(no_lease.channel_force_closed(101), None);
+    }
+
+    fn candidate(
+        fee_msat: u64,
+        has_inbound_capacity: bool,
+        successes: u32,
+        failures: u32,
+    ) -> LspCandidate {
+        LspCandidate {
+            client: LspClient {
+                pubkey: order(None).lsp_pubkey,
+                connection_string: String::new(),
+                url: format!("https://lsp{fee_msat}.example.com"),
+                http_client: Client::new(),
+            },
+            fee: FeeResponse {
+                fee_amount_msat: fee_msat,
+                lease_duration_blocks: None,
+            },
+            has_inbound_capacity,
+            reliability: LspReliability {
+                successes,
+                failures,
+            },
+        }
+    }
+
+    #[test]
+    fn test_rank_lsp_candidates() {
+        let test_name = "test_rank_lsp_candidates";
+        log!("{}", test_name);
+
+        assert_eq!(LspReliability::default().percent(), 50);
+        assert_eq!(
+            LspReliability {
+                successes: 8,
+                failures: 0
+            }
+            .percent(),
+            90
+        );
+
+        // the cheapest wins when they are equally reliable
+        let ranked = rank_lsp_candidates(vec![
+            candidate(2_000_000, false, 0, 0),
+            candidate(1_000_000, false, 0, 0),
+        ]);
+        assert_eq!(ranked[0].fee.fee_amount_msat, 1_000_000);
+
+        // existing inbound capacity beats a slightly cheaper new channel
+        let ranked = rank_lsp_candidates(vec![
+            candidate(500_000, false, 0, 0),
+            candidate(1_000_000, true, 0, 0),
+        ]);
+        assert!(ranked[0].has_inbound_capacity);
+
+        // an unreliable LSP loses even when it is cheaper
+        let ranked = rank_lsp_candidates(vec![
+            candidate(1_000_000, false, 0, 10),
+            candidate(1_500_000, false, 10, 0),
+        ]);
+        assert_eq!(ranked[0].fee.fee_amount_msat, 1_500_000);
+
+        // reliability decides between free LSPs
+        let ranked = rank_lsp_candidates(vec![candidate(0, true, 1, 1), candidate(0, true, 5, 0)]);
+        assert_eq!(ranked[0].reliability.successes, 5);
+
+        let failed = ranked[1].client.pubkey;
+        let selection = ranked[0].selection(2, vec![failed]);
+        assert_eq!(selection.candidates, 2);
+        assert_eq!(selection.failed_over_from, vec![failed]);
+        assert_eq!(selection.reliability_percent, 85);
+
+        let quote = candidate(1_500, false, 0, 0).quote();
+        assert_eq!(quote.fee_sats, 2);
+        assert_eq!(quote.fee_msats, 1_500);
+        assert!(quote.opens_channel);
     }
 }
@@ -6,5 +6,8 @@ pub mod proxy;
 #[cfg(target_arch = "wasm32")]
 pub mod ws_socket;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod socks;
+
 #[cfg(not(target_arch = "wasm32"))]
 pub mod tcp_socket;
@@ -5,9 +5,12 @@ use async_trait::async_trait;
 use futures::stream::SplitStream;
 use futures::{lock::Mutex, stream::SplitSink, SinkExt, StreamExt};
 use gloo_net::websocket::{futures::WebSocket, Message, State};
-use lightning::{log_debug, log_trace};
+use lightning::{log_debug, log_trace, log_warn};
 use lightning::{log_error, util::logger::Logger};
-use std::sync::Arc;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 
 use crate::logging::MutinyLogger;
 #[cfg(test)]
@@ -108,6 +111,239 @@ impl Proxy for WsProxy {
     }
 }
 
+/// How many failed connections in a row before we switch to another proxy
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// How often the proxies are health checked, in seconds
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 300;
+
+/// The health of a single websocket proxy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WsProxyHealth {
+    pub url: String,
+    /// If this is the proxy new connections go through
+    pub active: bool,
+    pub consecutive_failures: u32,
+    pub total_successes: u64,
+    pub total_failures: u64,
+    /// Unix timestamp, in seconds, of the last successful connection or health check
+    pub last_success: Option<u64>,
+    /// Unix timestamp, in seconds, of the last failed connection or health check
+    pub last_failure: Option<u64>,
+}
+
+impl WsProxyHealth {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            active: false,
+            consecutive_failures: 0,
+            total_successes: 0,
+            total_failures: 0,
+            last_success: None,
+            last_failure: None,
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.consecutive_failures < MAX_CONSECUTIVE_FAILURES
+    }
+}
+
+/// A list of websocket proxies to connect to peers through.
+///
+/// Connections go through the active proxy until it fails
+/// [MAX_CONSECUTIVE_FAILURES] times in a row, then the next healthy one takes over.
+/// Only failures of the proxy itself count, not peers that can't be reached through it.
+/// Periodic health checks let failed proxies recover.
+pub struct WsProxyFailover {
+    proxies: Vec<RwLock<WsProxyHealth>>,
+    current: AtomicUsize,
+    client: Client,
+    logger: Arc<MutinyLogger>,
+}
+
+impl WsProxyFailover {
+    pub fn new(
+        urls: Vec<String>,
+        client: Client,
+        logger: Arc<MutinyLogger>,
+    ) -> Result<Self, MutinyError> {
+        if urls.is_empty() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        Ok(Self {
+            proxies: urls
+                .into_iter()
+                .map(|url| RwLock::new(WsProxyHealth::new(url)))
+                .collect(),
+            current: AtomicUsize::new(0),
+            client,
+            logger,
+        })
+    }
+
+    /// The url of the proxy new connections go through
+    pub fn active(&self) -> String {
+        self.proxies[self.current.load(Ordering::Relaxed)]
+            .read()
+            .map(|h| h.url.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the health of every proxy
+    pub fn health(&self) -> Vec<WsProxyHealth> {
+        let current = self.current.load(Ordering::Relaxed);
+        self.proxies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| {
+                p.read().ok().map(|h| WsProxyHealth {
+                    active: i == current,
+                    ..h.clone()
+                })
+            })
+            .collect()
+    }
+
+    fn index_of(&self, url: &str) -> Option<usize> {
+        self.proxies
+            .iter()
+            .position(|p| p.read().map(|h| h.url == url).unwrap_or(false))
+    }
+
+    pub fn record_success(&self, url: &str) {
+        let index = match self.index_of(url) {
+            Some(index) => index,
+            None => return,
+        };
+        if let Ok(mut health) = self.proxies[index].write() {
+            health.consecutive_failures = 0;
+            health.total_successes += 1;
+            health.last_success = Some(utils::now().as_secs());
+        }
+    }
+
+    /// Records a failure of the proxy itself, switching to the next healthy proxy
+    /// if the active one keeps failing.
+    pub fn record_failure(&self, url: &str) {
+        let index = match self.index_of(url) {
+            Some(index) => index,
+            None => return,
+        };
+        let healthy = match self.proxies[index].write() {
+            Ok(mut health) => {
+                health.consecutive_failures += 1;
+                health.total_failures += 1;
+                health.last_failure = Some(utils::now().as_secs());
+                health.is_healthy()
+            }
+            Err(_) => return,
+        };
+
+        let current = self.current.load(Ordering::Relaxed);
+        if healthy || index != current || self.proxies.len() == 1 {
+            return;
+        }
+
+        // prefer a healthy proxy, otherwise just move on to the next one
+        let len = self.proxies.len();
+        let next = (1..len)
+            .map(|i| (current + i) % len)
+            .find(|&i| {
+                self.proxies[i]
+                    .read()
+                    .map(|h| h.is_healthy())
+                    .unwrap_or(false)
+            })
+            .unwrap_or((current + 1) % len);
+        self.current.store(next, Ordering::Relaxed);
+        log_warn!(
+            self.logger,
+            "Websocket proxy {url} keeps failing, switching to {}",
+            self.active()
+        );
+    }
+
+    /// Checks that the proxy is reachable over http, any response counts as healthy.
+    /// A connection through it failing may just be the peer being offline,
+    /// so this is what decides if the proxy is at fault.
+    pub async fn check_proxy(&self, url: &str) {
+        match self.client.get(health_check_url(url)).send().await {
+            Ok(_) => self.record_success(url),
+            Err(e) => {
+                log_debug!(self.logger, "Health check for proxy {url} failed: {e}");
+                self.record_failure(url);
+            }
+        }
+    }
+
+    /// Checks that each proxy is reachable over http
+    pub async fn health_check(&self) {
+        let urls: Vec<String> = self
+            .proxies
+            .iter()
+            .filter_map(|p| p.read().ok().map(|h| h.url.clone()))
+            .collect();
+
+        for url in urls {
+            self.check_proxy(&url).await;
+        }
+    }
+
+    /// Creates a background process that health checks the proxies until stopped.
+    pub(crate) fn start_health_checks(failover: Arc<WsProxyFailover>, stop: Arc<AtomicBool>) {
+        // nothing to fail over to
+        if failover.proxies.len() < 2 {
+            return;
+        }
+
+        utils::spawn(async move {
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                failover.health_check().await;
+
+                let mut elapsed = 0;
+                while elapsed < HEALTH_CHECK_INTERVAL_SECS && !stop.load(Ordering::Relaxed) {
+                    sleep(1_000).await;
+                    elapsed += 1;
+                }
+            }
+        });
+    }
+}
+
+/// Parses a single proxy url or a comma separated list of them,
+/// falling back to Mutiny's proxy.
+pub(crate) fn get_websocket_proxy_urls(user_provided_url: Option<String>) -> Vec<String> {
+    let urls: Vec<String> = user_provided_url
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+
+    if urls.is_empty() {
+        return vec![String::from("wss://p.mutinywallet.com")];
+    }
+    urls
+}
+
+/// The http url a websocket proxy can be reached at
+fn health_check_url(proxy_url: &str) -> String {
+    if let Some(rest) = proxy_url.strip_prefix("wss://") {
+        format!("https://{rest}")
+    } else if let Some(rest) = proxy_url.strip_prefix("ws://") {
+        format!("http://{rest}")
+    } else {
+        proxy_url.to_string()
+    }
+}
+
 pub fn tcp_proxy_to_url(proxy_url: &str, peer_addr: &str) -> Result<String, MutinyError> {
     let mut parts = peer_addr.split(':');
     let host = parts.next().ok_or(MutinyError::PeerInfoParseFailed)?;
@@ -126,7 +362,11 @@ mod tests {
 
     use crate::test_utils::*;
 
-    use crate::networking::proxy::tcp_proxy_to_url;
+    use crate::logging::MutinyLogger;
+    use crate::networking::proxy::{
+        get_websocket_proxy_urls, health_check_url, tcp_proxy_to_url, WsProxyFailover,
+    };
+    use std::sync::Arc;
 
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
 
@@ -163,4 +403,74 @@ mod tests {
             tcp_proxy_to_url("ws://127.0.0.1:3001", "127.0.0.1:4000").unwrap()
         );
     }
+
+    #[test]
+    fn test_get_websocket_proxy_urls() {
+        log!("test get websocket proxy urls");
+
+        assert_eq!(
+            get_websocket_proxy_urls(None),
+            vec!["wss://p.mutinywallet.com".to_string()]
+        );
+        assert_eq!(
+            get_websocket_proxy_urls(Some("wss://a.com/, ws://127.0.0.1:3001".to_string())),
+            vec!["wss://a.com".to_string(), "ws://127.0.0.1:3001".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_health_check_url() {
+        log!("test health check url");
+
+        assert_eq!(
+            health_check_url("wss://p.mutinywallet.com"),
+            "https://p.mutinywallet.com"
+        );
+        assert_eq!(
+            health_check_url("ws://127.0.0.1:3001"),
+            "http://127.0.0.1:3001"
+        );
+    }
+
+    #[test]
+    fn test_proxy_failover() {
+        log!("test proxy failover");
+
+        assert!(
+            WsProxyFailover::new(vec![], Client::new(), Arc::new(MutinyLogger::default())).is_err()
+        );
+
+        let first = "wss://first.example.com";
+        let second = "wss://second.example.com";
+        let failover = WsProxyFailover::new(
+            vec![first.to_string(), second.to_string()],
+            Client::new(),
+            Arc::new(MutinyLogger::default()),
+        )
+        .unwrap();
+        assert_eq!(failover.active(), first);
+
+        // a success in between resets the count
+        failover.record_failure(first);
+        failover.record_failure(first);
+        failover.record_success(first);
+        failover.record_failure(first);
+        assert_eq!(failover.active(), first);
+
+        failover.record_failure(first);
+        failover.record_failure(first);
+        assert_eq!(failover.active(), second);
+
+        let health = failover.health();
+        assert_eq!(health.len(), 2);
+        assert!(!health[0].active);
+        assert_eq!(health[0].consecutive_failures, 3);
+        assert_eq!(health[0].total_failures, 4);
+        assert_eq!(health[0].total_successes, 1);
+        assert!(health[1].active);
+
+        // failures of a proxy that isn't active don't move us
+        failover.record_failure(first);
+        assert_eq!(failover.active(), second);
+    }
 }
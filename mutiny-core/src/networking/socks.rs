@@ -0,0 +1,124 @@
+use crate::error::MutinyError;
+use reqwest::{Client, Proxy};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTH: u8 = 0x00;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Builds an http client that sends every request through the SOCKS5 proxy, if one is given.
+/// Host names are resolved by the proxy so requests to onion services work.
+pub fn http_client(socks_proxy: Option<&str>) -> Result<Client, MutinyError> {
+    let builder = match socks_proxy {
+        Some(proxy) => {
+            let proxy = Proxy::all(format!("socks5h://{proxy}"))
+                .map_err(|_| MutinyError::InvalidArgumentsError)?;
+            Client::builder().proxy(proxy)
+        }
+        None => Client::builder(),
+    };
+
+    builder
+        .build()
+        .map_err(|_| MutinyError::InvalidArgumentsError)
+}
+
+/// Opens a tcp connection to `host:port` through the SOCKS5 proxy at `proxy`.
+///
+/// The host is always sent as a domain name so the proxy does the resolving,
+/// this keeps DNS lookups off the local network when running over Tor.
+pub async fn connect(proxy: &str, host: &str, port: u16) -> Result<TcpStream, MutinyError> {
+    if host.is_empty() || host.len() > u8::MAX as usize {
+        return Err(MutinyError::ConnectionFailed);
+    }
+
+    let mut stream = TcpStream::connect(proxy)
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+
+    // greeting, we only offer no authentication
+    stream
+        .write_all(&[SOCKS_VERSION, 1, NO_AUTH])
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+    let mut method = [0u8; 2];
+    stream
+        .read_exact(&mut method)
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+    if method != [SOCKS_VERSION, NO_AUTH] {
+        return Err(MutinyError::ConnectionFailed);
+    }
+
+    stream
+        .write_all(&connect_request(host, port))
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+
+    // reply is version, status, reserved, then the bound address we skip over
+    let mut reply = [0u8; 4];
+    stream
+        .read_exact(&mut reply)
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+    if reply[0] != SOCKS_VERSION || reply[1] != 0x00 {
+        return Err(MutinyError::ConnectionFailed);
+    }
+    let addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream
+            .read_u8()
+            .await
+            .map_err(|_| MutinyError::ConnectionFailed)? as usize,
+        _ => return Err(MutinyError::ConnectionFailed),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream
+        .read_exact(&mut bound)
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+
+    Ok(stream)
+}
+
+fn connect_request(host: &str, port: u16) -> Vec<u8> {
+    let mut request = vec![
+        SOCKS_VERSION,
+        CMD_CONNECT,
+        0x00,
+        ATYP_DOMAIN,
+        host.len() as u8,
+    ];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_request() {
+        let request = connect_request("example.onion", 9735);
+
+        assert_eq!(
+            request[..4],
+            [SOCKS_VERSION, CMD_CONNECT, 0x00, ATYP_DOMAIN]
+        );
+        assert_eq!(request[4] as usize, "example.onion".len());
+        assert_eq!(&request[5..18], b"example.onion");
+        assert_eq!(request[18..], 9735u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_http_client() {
+        assert!(http_client(None).is_ok());
+        assert!(http_client(Some("127.0.0.1:9050")).is_ok());
+    }
+}
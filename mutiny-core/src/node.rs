@@ -1,54 +1,92 @@
 use crate::keymanager::PhantomKeysManager;
 use crate::labels::LabelStorage;
-use crate::ldkstorage::ChannelOpenParams;
-use crate::nodemanager::ChannelClosure;
+use crate::ldkstorage::{ChannelOpenParams, ClaimIntent};
+use crate::nodemanager::{ChannelClosure, NodeAnnouncementConfig};
+use crate::notifier::{EventNotifier, MutinyEvent};
+use crate::onionmessage::{
+    CustomOnionMessage, MutinyOnionMessageHandler, MutinyOnionMessenger,
+    MIN_CUSTOM_ONION_MESSAGE_TYPE,
+};
+use crate::peermessage::{CustomPeerMessage, MutinyCustomMessageHandler};
 use crate::{
     background::process_events_async,
     chain::MutinyChain,
     error::{MutinyError, MutinyStorageError},
-    event::{EventHandler, HTLCStatus, MillisatAmount, PaymentInfo},
+    event::{
+        ChannelAcceptor, EventHandler, HTLCStatus, MillisatAmount, PaymentFailureReason,
+        PaymentInfo,
+    },
     fees::MutinyFeeEstimator,
-    gossip::{get_all_peers, read_peer_info, save_peer_connection_info},
+    gossip::{
+        find_graph_address, get_all_peers, read_peer_info, save_peer_connection_info,
+        ReconnectPolicy,
+    },
     keymanager::{create_keys_manager, pubkey_from_keys_manager},
     ldkstorage::{MutinyNodePersister, PhantomChannelManager},
     logging::MutinyLogger,
     lspclient::LspClient,
-    nodemanager::{MutinyInvoice, NodeIndex},
+    nodemanager::{
+        FeeEstimate, MaxFee, MppOptions, MutinyInvoice, MutinyPayment, NodeIndex,
+        OpenChannelOptions, PaymentEstimate, PaymentOptions, PaymentStatus,
+    },
     onchain::OnChainWallet,
-    peermanager::{GossipMessageHandler, PeerManager, PeerManagerImpl},
+    peermanager::{
+        GossipMessageHandler, PeerManager, PeerManagerImpl, PeerManagerLogger, PeerStats,
+        PeerTracker,
+    },
+    signing::{sign_scoped_message, SigningScope},
     utils::{self, sleep},
 };
 
+use crate::esplora_failover::FailoverEsploraClient;
 use crate::{fees::P2WSH_OUTPUT_SIZE, peermanager::connect_peer_if_necessary};
-use crate::{lspclient::FeeRequest, storage::MutinyStorage};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::peermanager::start_listener;
+
+#[cfg(target_arch = "wasm32")]
+use crate::networking::proxy::WsProxyFailover;
+
+use crate::{
+    lspclient::{
+        rank_lsp_candidates, FeeRequest, LspCandidate, LspFeeQuote, LspOrder, LspOrderStatus,
+        LspSelection,
+    },
+    storage::MutinyStorage,
+};
 use anyhow::{anyhow, Context};
-use bdk_esplora::esplora_client::AsyncClient;
 use bip39::Mnemonic;
+use bitcoin::bech32::ToBase32;
 use bitcoin::hashes::{hex::ToHex, sha256::Hash as Sha256};
 use bitcoin::secp256k1::rand;
 use bitcoin::{hashes::Hash, secp256k1::PublicKey, Network, OutPoint};
 use core::time::Duration;
-use lightning::ln::channelmanager::{RecipientOnionFields, RetryableSendFailure};
+use futures::future::join_all;
+use lightning::ln::channelmanager::{ChannelDetails, RecipientOnionFields, RetryableSendFailure};
 use lightning::{
-    chain::chaininterface::{ConfirmationTarget, FeeEstimator},
+    chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator},
     util::config::ChannelConfig,
 };
 use lightning::{
     chain::{
         chainmonitor,
-        keysinterface::{EntropySource, InMemorySigner},
+        keysinterface::{EntropySource, InMemorySigner, NodeSigner, Recipient},
         Filter, Watch,
     },
     ln::{
         channelmanager::{PaymentId, PhantomRouteHints, Retry},
-        peer_handler::{IgnoringMessageHandler, MessageHandler as LdkMessageHandler},
+        msgs::{NodeAnnouncement, UnsignedGossipMessage, UnsignedNodeAnnouncement},
+        peer_handler::MessageHandler as LdkMessageHandler,
         PaymentHash, PaymentPreimage,
     },
     log_debug, log_error, log_info, log_trace, log_warn,
+    onion_message::{Destination, OnionMessageContents, OnionMessenger},
     routing::{
         gossip,
-        gossip::NodeId,
-        router::{DefaultRouter, PaymentParameters, RouteParameters},
+        gossip::{NodeAlias, NodeId},
+        router::{
+            DefaultRouter, PaymentParameters, Route, RouteHint, RouteParameters, Router as _,
+        },
         scoring::ProbabilisticScorer,
     },
     util::{
@@ -59,21 +97,33 @@ use lightning::{
 use lightning_invoice::payment::PaymentError;
 use lightning_invoice::{
     payment::{pay_invoice, pay_zero_value_invoice},
-    utils::{create_invoice_from_channelmanager_and_duration_since_epoch, create_phantom_invoice},
-    Invoice,
+    utils::{
+        create_invoice_from_channelmanager_and_duration_since_epoch,
+        create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash,
+        create_phantom_invoice,
+    },
+    Invoice, InvoiceBuilder,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::{
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
 };
 
 const DEFAULT_PAYMENT_TIMEOUT: u64 = 30;
-const INITIAL_RECONNECTION_DELAY: u64 = 5;
-const MAX_RECONNECTION_DELAY: u64 = 60;
+pub(crate) const INITIAL_RECONNECTION_DELAY: u64 = 5;
+pub(crate) const MAX_RECONNECTION_DELAY: u64 = 60;
+/// The longest a [ReconnectPolicy] can wait between attempts, a day
+pub(crate) const RECONNECTION_DELAY_LIMIT: u64 = 24 * 60 * 60;
+
+/// How long cached route hints can be used for invoices when our peers are unreachable
+const ROUTE_HINT_CACHE_EXPIRY_SECS: u64 = 60 * 60;
+
+/// How long our invoices can be paid for when no other expiry is asked for
+pub(crate) const DEFAULT_INVOICE_EXPIRY_SECS: u32 = 1500;
 
 pub(crate) type RapidGossipSync =
     lightning_rapid_gossip_sync::RapidGossipSync<Arc<NetworkGraph>, Arc<MutinyLogger>>;
@@ -83,7 +133,7 @@ pub(crate) type NetworkGraph = gossip::NetworkGraph<Arc<MutinyLogger>>;
 pub(crate) type MessageHandler<S: MutinyStorage> = LdkMessageHandler<
     Arc<PhantomChannelManager<S>>,
     Arc<GossipMessageHandler<S>>,
-    Arc<IgnoringMessageHandler>,
+    Arc<MutinyOnionMessenger<S>>,
 >;
 
 pub(crate) type ChainMonitor<S: MutinyStorage> = chainmonitor::ChainMonitor<
@@ -95,6 +145,14 @@ pub(crate) type ChainMonitor<S: MutinyStorage> = chainmonitor::ChainMonitor<
     Arc<MutinyNodePersister<S>>,
 >;
 
+/// The most recent usable route hints for a node, used to create
+/// invoices while our peers or LSP are temporarily unreachable.
+#[derive(Debug, Clone)]
+struct CachedRouteHints {
+    hints: Vec<RouteHint>,
+    timestamp: u64,
+}
+
 pub(crate) type Router =
     DefaultRouter<Arc<NetworkGraph>, Arc<MutinyLogger>, Arc<utils::Mutex<ProbScorer>>>;
 
@@ -134,17 +192,32 @@ pub(crate) struct Node<S: MutinyStorage> {
     stopped_components: Arc<RwLock<Vec<bool>>>,
     pub pubkey: PublicKey,
     pub peer_manager: Arc<dyn PeerManager>,
+    peer_tracker: Arc<PeerTracker>,
     pub keys_manager: Arc<PhantomKeysManager<S>>,
     pub channel_manager: Arc<PhantomChannelManager<S>>,
     pub chain_monitor: Arc<ChainMonitor<S>>,
     network: Network,
     pub persister: Arc<MutinyNodePersister<S>>,
+    router: Arc<Router>,
     wallet: Arc<OnChainWallet<S>>,
     logger: Arc<MutinyLogger>,
-    pub(crate) lsp_client: Option<LspClient>,
+    /// The LSP this node was assigned, changes if that LSP is removed
+    lsp_client: RwLock<Option<LspClient>>,
+    /// Every configured LSP, any of them can be chosen to wrap an invoice
+    lsp_clients: Arc<futures::lock::Mutex<Vec<LspClient>>>,
+    route_hint_cache: Arc<RwLock<Option<CachedRouteHints>>>,
+    /// Peers we asked to open a channel to us through LNURL-channel
+    pending_lnurl_channels: Arc<Mutex<HashSet<PublicKey>>>,
+    onion_messenger: Arc<MutinyOnionMessenger<S>>,
+    custom_message_handler: Arc<MutinyCustomMessageHandler>,
     stop: Arc<AtomicBool>,
     #[cfg(target_arch = "wasm32")]
-    websocket_proxy_addr: String,
+    websocket_proxies: Arc<WsProxyFailover>,
+    #[cfg(not(target_arch = "wasm32"))]
+    socks_proxy: Option<String>,
+    /// The port this node accepts inbound connections on, if any
+    #[cfg(not(target_arch = "wasm32"))]
+    listen_port: Option<u16>,
 }
 
 impl<S: MutinyStorage> Node<S> {
@@ -154,6 +227,7 @@ impl<S: MutinyStorage> Node<S> {
         node_index: &NodeIndex,
         stop: Arc<AtomicBool>,
         mnemonic: &Mnemonic,
+        account_index: u32,
         storage: S,
         gossip_sync: Arc<RapidGossipSync>,
         scorer: Arc<utils::Mutex<ProbScorer>>,
@@ -161,10 +235,16 @@ impl<S: MutinyStorage> Node<S> {
         fee_estimator: Arc<MutinyFeeEstimator<S>>,
         wallet: Arc<OnChainWallet<S>>,
         network: Network,
-        esplora: Arc<AsyncClient>,
-        lsp_clients: &[LspClient],
+        esplora: Arc<FailoverEsploraClient>,
+        lsp_clients: Arc<futures::lock::Mutex<Vec<LspClient>>>,
+        notifier: Arc<EventNotifier>,
+        channel_acceptor: Arc<RwLock<Option<Arc<dyn ChannelAcceptor>>>>,
+        onion_message_handler: Arc<MutinyOnionMessageHandler>,
+        custom_message_handler: Arc<MutinyCustomMessageHandler>,
         logger: Arc<MutinyLogger>,
-        #[cfg(target_arch = "wasm32")] websocket_proxy_addr: String,
+        #[cfg(target_arch = "wasm32")] websocket_proxies: Arc<WsProxyFailover>,
+        #[cfg(not(target_arch = "wasm32"))] socks_proxy: Option<String>,
+        #[cfg(not(target_arch = "wasm32"))] listen_port: Option<u16>,
     ) -> Result<Self, MutinyError> {
         log_info!(logger, "initializing a new node: {uuid}");
 
@@ -174,6 +254,7 @@ impl<S: MutinyStorage> Node<S> {
         let keys_manager = Arc::new(create_keys_manager(
             wallet.clone(),
             mnemonic,
+            account_index,
             node_index.child_index,
             logger.clone(),
         )?);
@@ -231,15 +312,24 @@ impl<S: MutinyStorage> Node<S> {
 
         // Check all existing channels against default configs.
         // If we have default config changes, those should apply
-        // to all existing and new channels.
+        // to all existing and new channels, along with the fee
+        // policies the user set for their channels.
         let default_config = default_user_config().channel_config;
+        let fee_policies = persister
+            .storage
+            .get_channel_fee_policies()
+            .unwrap_or_default();
         for channel in channel_manager.list_channels() {
+            let config = channel
+                .funding_txo
+                .and_then(|f| fee_policies.get(&f.into_bitcoin_outpoint().to_string()))
+                .map_or(default_config, |p| p.apply(default_config));
             // unwrap is safe after LDK.0.0.109
-            if channel.config.unwrap() != default_config {
+            if channel.config.unwrap() != config {
                 match channel_manager.update_channel_config(
                     &channel.counterparty.node_id,
                     &[channel.channel_id],
-                    &default_config,
+                    &config,
                 ) {
                     Ok(_) => {
                         log_debug!(
@@ -259,22 +349,32 @@ impl<S: MutinyStorage> Node<S> {
             }
         }
 
+        let peer_tracker = Arc::new(PeerTracker::default());
         let route_handler = Arc::new(GossipMessageHandler {
             storage: persister.storage.clone(),
             network_graph: gossip_sync.network_graph().clone(),
+            peer_tracker: peer_tracker.clone(),
             logger: logger.clone(),
         });
 
+        let onion_messenger = Arc::new(OnionMessenger::new(
+            keys_manager.clone(),
+            keys_manager.clone(),
+            logger.clone(),
+            onion_message_handler,
+        ));
+
         // init peer manager
         let ln_msg_handler = MessageHandler {
             chan_handler: channel_manager.clone(),
             route_handler,
-            onion_message_handler: Arc::new(IgnoringMessageHandler {}),
+            onion_message_handler: onion_messenger.clone(),
         };
 
         log_info!(logger, "creating lsp client");
         let lsp_client: Option<LspClient> = match node_index.lsp {
             None => {
+                let lsp_clients = lsp_clients.lock().await;
                 if lsp_clients.is_empty() {
                     log_info!(logger, "no lsp saved and no lsp clients available");
                     None
@@ -286,34 +386,68 @@ impl<S: MutinyStorage> Node<S> {
                     Some(lsp_clients[rand].clone())
                 }
             }
-            Some(ref lsp) => lsp_clients.iter().find(|c| &c.url == lsp).cloned(),
+            Some(ref lsp) => lsp_clients
+                .lock()
+                .await
+                .iter()
+                .find(|c| &c.url == lsp)
+                .cloned(),
         };
 
-        let lsp_client_pubkey = lsp_client.clone().map(|lsp| lsp.pubkey);
+        let pending_lnurl_channels = Arc::new(Mutex::new(HashSet::new()));
 
         // init event handler
         let event_handler = EventHandler::new(
             channel_manager.clone(),
+            chain_monitor.clone(),
             fee_estimator,
             wallet.clone(),
             keys_manager.clone(),
             persister.clone(),
-            lsp_client_pubkey,
+            pending_lnurl_channels.clone(),
+            channel_acceptor,
+            notifier.clone(),
             logger.clone(),
         );
+        let custom_message_handler = Arc::new(custom_message_handler.for_node());
         let peer_man = Arc::new(create_peer_manager(
             keys_manager.clone(),
             ln_msg_handler,
+            custom_message_handler.clone(),
+            peer_tracker.clone(),
             logger.clone(),
         ));
 
+        // Channel monitors without a channel manager means we were restored from a
+        // static channel backup. The restored monitors may be out of date, so we must
+        // not broadcast our own commitment from them, that could be a revoked state.
+        // Instead we reconnect to the counterparty, our channel manager doesn't know the
+        // channel so it errors on their reestablish and they force close with the latest
+        // state, the monitors then sweep our funds once they are claimable.
+        let recovering = !read_channel_manager.is_restarting
+            && !read_channel_manager.channel_monitors.is_empty();
+        if recovering {
+            log_warn!(
+                logger,
+                "Recovering {} channels from backup, waiting for peers to force close",
+                read_channel_manager.channel_monitors.len()
+            );
+        }
+
         // sync to chain tip
-        if read_channel_manager.is_restarting {
+        if read_channel_manager.is_restarting || recovering {
             let mut chain_listener_channel_monitors = Vec::new();
+            let mut recovery_peers = HashSet::new();
             for (blockhash, channel_monitor) in read_channel_manager.channel_monitors.drain(..) {
                 // Get channel monitor ready to sync
                 channel_monitor.load_outputs_to_watch(&chain);
 
+                if recovering {
+                    if let Some(counterparty) = channel_monitor.get_counterparty_node_id() {
+                        recovery_peers.insert(counterparty);
+                    }
+                }
+
                 let outpoint = channel_monitor.get_funding_txo().0;
                 chain_listener_channel_monitors.push((
                     blockhash,
@@ -335,6 +469,52 @@ impl<S: MutinyStorage> Node<S> {
                     .clone()
                     .watch_channel(funding_outpoint, channel_monitor);
             }
+
+            // make sure the reconnection loop connects to the peers of recovered channels
+            for counterparty in recovery_peers {
+                let node_id = NodeId::from_pubkey(&counterparty);
+                let saved =
+                    read_peer_info(&persister.storage, &node_id)?.and_then(|p| p.connection_string);
+                let connection_string = saved.or_else(|| {
+                    find_graph_address(gossip_sync.network_graph(), &counterparty)
+                        .map(|addr| format!("{counterparty}@{addr}"))
+                });
+                match connection_string {
+                    Some(connection_string) => {
+                        log_info!(
+                            logger,
+                            "Reconnecting to {counterparty} to recover channel funds"
+                        );
+                        save_peer_connection_info(
+                            &persister.storage,
+                            &uuid,
+                            &node_id,
+                            &connection_string,
+                            None,
+                        )?;
+                    }
+                    None => log_warn!(
+                        logger,
+                        "No address known for recovered channel peer {counterparty}, funds are only recovered once they force close"
+                    ),
+                }
+            }
+        }
+
+        // Finish claiming any payments we were in the middle of claiming when
+        // we were last shut down, otherwise those HTLCs could time out.
+        match persister.get_unfinished_claims(channel_manager.current_best_block().height()) {
+            Ok(preimages) => {
+                for preimage in preimages {
+                    log_info!(
+                        logger,
+                        "Retrying unfinished claim for payment hash: {}",
+                        Sha256::hash(&preimage.0).to_hex()
+                    );
+                    channel_manager.claim_funds(preimage);
+                }
+            }
+            Err(e) => log_warn!(logger, "Failed to read unfinished claims: {e}"),
         }
 
         // Before we start the background processor, retry previously failed
@@ -428,15 +608,62 @@ impl<S: MutinyStorage> Node<S> {
             }
         });
 
+        // with a remote backup, monitor updates are only completed once they are uploaded
+        if persister.storage.vss().is_some() {
+            let monitor_persister = persister.clone();
+            let monitor_chain_monitor = chain_monitor.clone();
+            let monitor_logger = logger.clone();
+            let monitor_stop = stop.clone();
+            stopped_components.try_write()?.push(false);
+            let monitor_stopped_components = stopped_components.clone();
+            utils::spawn(async move {
+                while !monitor_stop.load(Ordering::Relaxed) {
+                    for (funding_txo, update_id) in
+                        monitor_persister.take_backed_up_monitor_updates()
+                    {
+                        if let Err(e) =
+                            monitor_chain_monitor.channel_monitor_updated(funding_txo, update_id)
+                        {
+                            log_error!(
+                                monitor_logger,
+                                "could not complete monitor update for {}: {e:?}",
+                                funding_txo.txid
+                            );
+                        }
+                    }
+                    sleep(1_000).await;
+                }
+                stop_component(&monitor_stopped_components);
+            });
+        }
+
+        // every node gets its own port, counting up from the configured one
+        #[cfg(not(target_arch = "wasm32"))]
+        let listen_port = listen_port.and_then(|port| {
+            let offset = u16::try_from(node_index.child_index).ok()?;
+            port.checked_add(offset)
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(port) = listen_port {
+            if let Err(e) =
+                start_listener(port, logger.clone(), peer_man.clone(), stop.clone()).await
+            {
+                log_error!(logger, "could not listen on port {port}: {e}");
+            }
+        }
+
         start_reconnection_handling(
             &persister.storage,
             pubkey,
             #[cfg(target_arch = "wasm32")]
-            websocket_proxy_addr.clone(),
+            websocket_proxies.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy.clone(),
             peer_man.clone(),
             &logger,
             uuid.clone(),
             &lsp_client,
+            notifier,
             stop.clone(),
             stopped_components.clone(),
             network == Network::Regtest,
@@ -449,20 +676,57 @@ impl<S: MutinyStorage> Node<S> {
             child_index: node_index.child_index,
             pubkey,
             peer_manager: peer_man,
+            peer_tracker,
             keys_manager,
             channel_manager,
             chain_monitor,
             network,
             persister,
+            router,
             wallet,
             logger,
-            lsp_client,
+            lsp_client: RwLock::new(lsp_client),
+            lsp_clients,
+            route_hint_cache: Arc::new(RwLock::new(None)),
+            pending_lnurl_channels,
+            onion_messenger,
+            custom_message_handler,
             stop,
             #[cfg(target_arch = "wasm32")]
-            websocket_proxy_addr,
+            websocket_proxies,
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy,
+            #[cfg(not(target_arch = "wasm32"))]
+            listen_port,
         })
     }
 
+    /// Marks that the peer will open a channel to us because we asked for it with LNURL-channel
+    pub(crate) fn expect_lnurl_channel(&self, peer: PublicKey) {
+        if let Ok(mut pending) = self.pending_lnurl_channels.lock() {
+            pending.insert(peer);
+        }
+    }
+
+    /// Forgets about a LNURL-channel request, used when the service refused it
+    pub(crate) fn cancel_lnurl_channel(&self, peer: &PublicKey) {
+        if let Ok(mut pending) = self.pending_lnurl_channels.lock() {
+            pending.remove(peer);
+        }
+    }
+
+    /// Returns true if we have started claiming a payment that has not completed yet.
+    /// Claims whose HTLCs already expired can't complete, so they don't count.
+    pub(crate) fn has_pending_claims(&self) -> bool {
+        let height = self.channel_manager.current_best_block().height();
+        let now = utils::now().as_secs();
+        self.persister
+            .list_claim_intents()
+            .map_or(false, |intents| {
+                intents.iter().any(|(_, i)| !i.is_expired(height, now))
+            })
+    }
+
     /// stopped will await until the node is fully shut down
     pub async fn stopped(&self) -> Result<(), MutinyError> {
         loop {
@@ -483,14 +747,30 @@ impl<S: MutinyStorage> Node<S> {
         Ok(())
     }
 
+    /// How our connection to the peer was set up, if we have been connected to it
+    pub(crate) fn peer_stats(&self, pubkey: &PublicKey) -> Option<PeerStats> {
+        self.peer_tracker.get(pubkey)
+    }
+
     pub fn node_index(&self) -> NodeIndex {
         NodeIndex {
             child_index: self.child_index,
-            lsp: self.lsp_client.clone().map(|l| l.url),
+            lsp: self.lsp_client().map(|l| l.url),
             archived: Some(false),
         }
     }
 
+    /// The LSP this node uses by default, if any
+    pub(crate) fn lsp_client(&self) -> Option<LspClient> {
+        self.lsp_client.read().ok().and_then(|lsp| lsp.clone())
+    }
+
+    /// Changes the LSP this node uses by default, the caller should save the new [NodeIndex]
+    pub(crate) fn set_lsp_client(&self, lsp: Option<LspClient>) -> Result<(), MutinyError> {
+        *self.lsp_client.try_write()? = lsp;
+        Ok(())
+    }
+
     pub async fn connect_peer(
         &self,
         peer_connection_info: PubkeyConnectionInfo,
@@ -498,7 +778,7 @@ impl<S: MutinyStorage> Node<S> {
     ) -> Result<(), MutinyError> {
         #[cfg(target_arch = "wasm32")]
         let connect_res = connect_peer_if_necessary(
-            &self.websocket_proxy_addr,
+            &self.websocket_proxies,
             &peer_connection_info,
             self.logger.clone(),
             self.peer_manager.clone(),
@@ -508,6 +788,7 @@ impl<S: MutinyStorage> Node<S> {
 
         #[cfg(not(target_arch = "wasm32"))]
         let connect_res = connect_peer_if_necessary(
+            self.socks_proxy.as_deref(),
             &peer_connection_info,
             self.logger.clone(),
             self.peer_manager.clone(),
@@ -569,85 +850,361 @@ impl<S: MutinyStorage> Node<S> {
         self.channel_manager.get_phantom_route_hints()
     }
 
+    /// Signs a message with the node key in the given scope.
+    /// Channel ownership can only be signed for channels this node has open.
+    pub fn sign_message(&self, scope: SigningScope, message: &str) -> Result<String, MutinyError> {
+        if let SigningScope::ChannelOwnership(outpoint) = scope {
+            let has_channel = self
+                .channel_manager
+                .list_channels()
+                .iter()
+                .any(|c| c.funding_txo.map(|f| f.into_bitcoin_outpoint()) == Some(outpoint));
+            if !has_channel {
+                return Err(MutinyError::NotFound);
+            }
+        }
+
+        sign_scoped_message(scope, message, &self.keys_manager.get_node_secret_key())
+    }
+
+    /// Creates a node announcement signed by this node.
+    ///
+    /// We don't broadcast announcements because our channels are private, this is
+    /// for external tooling that wants proof of our node's features and alias.
+    pub fn create_node_announcement(
+        &self,
+        alias: &str,
+        rgb: [u8; 3],
+    ) -> Result<NodeAnnouncement, MutinyError> {
+        if alias.len() > 32 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        let mut alias_bytes = [0u8; 32];
+        alias_bytes[..alias.len()].copy_from_slice(alias.as_bytes());
+
+        let contents = UnsignedNodeAnnouncement {
+            features: self.channel_manager.node_features(),
+            timestamp: utils::now().as_secs() as u32,
+            node_id: NodeId::from_pubkey(&self.pubkey),
+            rgb,
+            alias: NodeAlias(alias_bytes),
+            addresses: vec![],
+            excess_address_data: vec![],
+            excess_data: vec![],
+        };
+        let signature = self
+            .keys_manager
+            .sign_gossip_message(UnsignedGossipMessage::NodeAnnouncement(&contents))
+            .map_err(|_| MutinyError::WalletOperationFailed)?;
+
+        Ok(NodeAnnouncement {
+            signature,
+            contents,
+        })
+    }
+
+    /// Creates an invoice for this node, also returns whether the invoice was
+    /// created using cached route hints because our peers or LSP were unreachable,
+    /// and which LSP was chosen to wrap it.
     pub async fn create_invoice(
         &self,
         amount_sat: Option<u64>,
         labels: Vec<String>,
         route_hints: Option<Vec<PhantomRouteHints>>,
-    ) -> Result<Invoice, MutinyError> {
-        // the amount to create for the invoice whether or not there is an lsp
-        let (amount_sat, lsp_fee_msat) = if let Some(lsp) = self.lsp_client.clone() {
-            // LSP requires an amount:
-            let amount_sat = amount_sat.ok_or(MutinyError::BadAmountError)?;
-
-            // Needs any amount over 0 if channel exists
-            // Needs amount over 10k if no channel
-            let has_usable_channel = self
-                .channel_manager
-                .list_channels_with_counterparty(&lsp.pubkey)
-                .iter()
-                .any(|c| c.inbound_capacity_msat >= amount_sat * 1000);
-            let min_amount_sat = if has_usable_channel {
-                1
-            } else {
-                utils::min_lightning_amount(self.network)
-            };
-            if amount_sat < min_amount_sat {
-                return Err(MutinyError::BadAmountError);
+        lsp_url: Option<&str>,
+        expiry_secs: u32,
+    ) -> Result<(Invoice, bool, Option<LspSelection>), MutinyError> {
+        // phantom invoices get their route hints from all of our nodes
+        if route_hints.is_some() {
+            let (invoice, selection) = self
+                .create_invoice_inner(amount_sat, labels, route_hints, lsp_url, expiry_secs)
+                .await?;
+            return Ok((invoice, false, selection));
+        }
+
+        // if none of our channels are usable our peers are likely offline
+        if self.channel_manager.list_usable_channels().is_empty() {
+            if let Some(hints) = self.get_cached_route_hints() {
+                log_warn!(
+                    self.logger,
+                    "No usable channels, creating invoice with cached route hints"
+                );
+                let invoice =
+                    self.create_degraded_invoice(amount_sat, labels, hints, expiry_secs)?;
+                return Ok((invoice, true, None));
             }
+        }
 
-            // check the fee from the LSP
-            let lsp_fee_msat = lsp
-                .get_lsp_fee_msat(FeeRequest {
-                    pubkey: self.pubkey.to_hex(),
-                    amount_msat: amount_sat * 1000,
-                })
+        match self
+            .create_invoice_inner(amount_sat, labels.clone(), None, lsp_url, expiry_secs)
+            .await
+        {
+            Ok((invoice, selection)) => Ok((invoice, false, selection)),
+            // the LSP may be unreachable, fall back to our cached route hints
+            Err(e) if self.lsp_client().is_some() && !matches!(e, MutinyError::BadAmountError) => {
+                match self.get_cached_route_hints() {
+                    Some(hints) => {
+                        log_warn!(
+                            self.logger,
+                            "Could not create invoice with LSP ({e}), using cached route hints"
+                        );
+                        let invoice =
+                            self.create_degraded_invoice(amount_sat, labels, hints, expiry_secs)?;
+                        Ok((invoice, true, None))
+                    }
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn create_invoice_inner(
+        &self,
+        amount_sat: Option<u64>,
+        labels: Vec<String>,
+        route_hints: Option<Vec<PhantomRouteHints>>,
+        lsp_url: Option<&str>,
+        expiry_secs: u32,
+    ) -> Result<(Invoice, Option<LspSelection>), MutinyError> {
+        if self.lsp_client().is_none() && lsp_url.is_none() {
+            let invoice = self
+                .create_internal_invoice(amount_sat, None, labels, route_hints, expiry_secs)
                 .await?;
+            return Ok((invoice, None));
+        }
 
-            // Convert the fee from msat to sat for comparison and subtraction
-            let lsp_fee_sat = lsp_fee_msat / 1000;
-
-            // Ensure that the fee is less than the amount being requested.
-            // If it isn't, we don't subtract it.
-            // This prevents amount from being subtracted down to 0.
-            // This will mean that the LSP fee will be paid by the payer instead.
-            let amount_minus_fee = if lsp_fee_sat < amount_sat {
-                amount_sat
-                    .checked_sub(lsp_fee_sat)
-                    .ok_or(MutinyError::BadAmountError)?
-            } else {
-                amount_sat
-            };
+        // LSP requires an amount:
+        let amount_sat = amount_sat.ok_or(MutinyError::BadAmountError)?;
+
+        let candidates = rank_lsp_candidates(self.quote_lsps(amount_sat, lsp_url).await?);
+        let count = candidates.len();
+
+        // try the LSPs from best to worst until one wraps the invoice
+        let mut last_error = MutinyError::LspFailure;
+        let mut failed_over_from = vec![];
+        for candidate in candidates {
+            let lsp_pubkey = candidate.client.pubkey;
+            let result = self
+                .create_lsp_invoice(
+                    &candidate,
+                    amount_sat,
+                    labels.clone(),
+                    route_hints.clone(),
+                    expiry_secs,
+                )
+                .await;
+            match result {
+                Ok(invoice) => {
+                    self.record_lsp_result(&lsp_pubkey, true);
+                    let selection = candidate.selection(count, failed_over_from);
+                    self.save_lsp_selection(&invoice, &selection);
+                    return Ok((invoice, Some(selection)));
+                }
+                Err(e) => {
+                    log_warn!(
+                        self.logger,
+                        "Could not create invoice with LSP {lsp_pubkey}: {e}"
+                    );
+                    self.record_lsp_result(&lsp_pubkey, false);
+                    failed_over_from.push(lsp_pubkey);
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Gets the fee the LSP we would wrap an invoice for the amount with charges,
+    /// or None if we don't use an LSP.
+    pub async fn get_lsp_fee_quote(
+        &self,
+        amount_sat: u64,
+    ) -> Result<Option<LspFeeQuote>, MutinyError> {
+        if self.lsp_client().is_none() {
+            return Ok(None);
+        }
+
+        let candidates = rank_lsp_candidates(self.quote_lsps(amount_sat, None).await?);
+        Ok(candidates.first().map(|c| c.quote()))
+    }
+
+    /// Gets fee quotes from every LSP that can take the payment,
+    /// along with our inbound capacity and history with each of them.
+    /// If an LSP url is given only that LSP is quoted.
+    async fn quote_lsps(
+        &self,
+        amount_sat: u64,
+        lsp_url: Option<&str>,
+    ) -> Result<Vec<LspCandidate>, MutinyError> {
+        // Needs any amount over 0 if channel exists
+        // Needs amount over 10k if no channel
+        if amount_sat == 0 {
+            return Err(MutinyError::BadAmountError);
+        }
+        let lsp_clients: Vec<LspClient> = self
+            .lsp_clients
+            .lock()
+            .await
+            .iter()
+            .filter(|lsp| lsp_url.map_or(true, |url| lsp.url == url))
+            .cloned()
+            .collect();
+        if lsp_clients.is_empty() && lsp_url.is_some() {
+            return Err(MutinyError::NotFound);
+        }
+
+        let eligible: Vec<(LspClient, bool)> = lsp_clients
+            .into_iter()
+            .map(|lsp| {
+                let has_inbound_capacity = self
+                    .channel_manager
+                    .list_channels_with_counterparty(&lsp.pubkey)
+                    .iter()
+                    .any(|c| c.inbound_capacity_msat >= amount_sat * 1000);
+                (lsp, has_inbound_capacity)
+            })
+            .filter(|(_, has_inbound_capacity)| {
+                *has_inbound_capacity || amount_sat >= utils::min_lightning_amount(self.network)
+            })
+            .collect();
+        if eligible.is_empty() {
+            return Err(MutinyError::BadAmountError);
+        }
+
+        let quotes = join_all(eligible.iter().map(|(lsp, _)| {
+            lsp.get_lsp_fee(FeeRequest {
+                pubkey: self.pubkey.to_hex(),
+                amount_msat: amount_sat * 1000,
+            })
+        }))
+        .await;
+
+        let mut candidates = vec![];
+        for ((client, has_inbound_capacity), quote) in eligible.into_iter().zip(quotes) {
+            match quote {
+                Ok(fee) => candidates.push(LspCandidate {
+                    reliability: self
+                        .persister
+                        .get_lsp_reliability(&client.pubkey)
+                        .unwrap_or_default(),
+                    client,
+                    fee,
+                    has_inbound_capacity,
+                }),
+                Err(e) => {
+                    log_warn!(
+                        self.logger,
+                        "Could not get fee quote from LSP {}: {e}",
+                        client.pubkey
+                    );
+                    self.record_lsp_result(&client.pubkey, false);
+                }
+            }
+        }
 
-            (Some(amount_minus_fee), Some(lsp_fee_msat))
+        if candidates.is_empty() {
+            return Err(MutinyError::LspFailure);
+        }
+        Ok(candidates)
+    }
+
+    /// Saves which LSP wrapped the invoice with its payment info, so it is kept with the invoice
+    fn save_lsp_selection(&self, invoice: &Invoice, selection: &LspSelection) {
+        let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
+        let Some(mut payment_info) =
+            self.persister
+                .read_payment_info(&payment_hash, true, &self.logger)
+        else {
+            return;
+        };
+        payment_info.lsp_selection = Some(selection.clone());
+        if let Err(e) = self
+            .persister
+            .persist_payment_info(&payment_hash, &payment_info, true)
+        {
+            log_warn!(self.logger, "WARN: could not save LSP selection: {e}");
+        }
+    }
+
+    fn record_lsp_result(&self, lsp_pubkey: &PublicKey, success: bool) {
+        if let Err(e) = self.persister.record_lsp_result(lsp_pubkey, success) {
+            log_warn!(self.logger, "WARN: could not save LSP reliability: {e}");
+        }
+    }
+
+    /// Creates an invoice and has the LSP wrap it, using the fee it quoted
+    async fn create_lsp_invoice(
+        &self,
+        candidate: &LspCandidate,
+        amount_sat: u64,
+        labels: Vec<String>,
+        route_hints: Option<Vec<PhantomRouteHints>>,
+        expiry_secs: u32,
+    ) -> Result<Invoice, MutinyError> {
+        let lsp = &candidate.client;
+        let lsp_fee = &candidate.fee;
+
+        // Convert the fee from msat to sat for comparison and subtraction
+        let lsp_fee_sat = lsp_fee.fee_amount_msat / 1000;
+
+        // Ensure that the fee is less than the amount being requested.
+        // If it isn't, we don't subtract it.
+        // This prevents amount from being subtracted down to 0.
+        // This will mean that the LSP fee will be paid by the payer instead.
+        let amount_minus_fee = if lsp_fee_sat < amount_sat {
+            amount_sat
+                .checked_sub(lsp_fee_sat)
+                .ok_or(MutinyError::BadAmountError)?
         } else {
-            (amount_sat, None)
+            amount_sat
         };
 
+        // only keep track of an order when the LSP has to open a channel for us
+        let lsp_order = (!candidate.has_inbound_capacity).then(|| LspOrder {
+            payment_hash: String::new(),
+            lsp_pubkey: lsp.pubkey,
+            amount_sat,
+            fee_msat: lsp_fee.fee_amount_msat,
+            lease_duration_blocks: lsp_fee.lease_duration_blocks,
+            created_at: utils::now().as_secs(),
+            status: LspOrderStatus::Pending,
+            channel: None,
+            discrepancies: vec![],
+        });
+
         let invoice = self
-            .create_internal_invoice(amount_sat, lsp_fee_msat, labels, route_hints)
+            .create_internal_invoice(
+                Some(amount_minus_fee),
+                Some(lsp_fee.fee_amount_msat),
+                labels,
+                route_hints,
+                expiry_secs,
+            )
             .await?;
 
-        if let Some(lsp) = self.lsp_client.clone() {
-            self.connect_peer(PubkeyConnectionInfo::new(&lsp.connection_string)?, None)
-                .await?;
-            let lsp_invoice_str = lsp.get_lsp_invoice(invoice.to_string()).await?;
-            let lsp_invoice = Invoice::from_str(&lsp_invoice_str)?;
+        self.connect_peer(PubkeyConnectionInfo::new(&lsp.connection_string)?, None)
+            .await?;
+        let lsp_invoice_str = lsp.get_lsp_invoice(invoice.to_string()).await?;
+        let lsp_invoice = Invoice::from_str(&lsp_invoice_str)?;
 
-            if invoice.network() != self.network {
-                return Err(MutinyError::IncorrectNetwork(invoice.network()));
-            }
+        if invoice.network() != self.network {
+            return Err(MutinyError::IncorrectNetwork(invoice.network()));
+        }
 
-            if lsp_invoice.payment_hash() != invoice.payment_hash()
-                || lsp_invoice.recover_payee_pub_key() != lsp.pubkey
-            {
-                return Err(MutinyError::InvoiceCreationFailed);
-            }
+        if lsp_invoice.payment_hash() != invoice.payment_hash()
+            || lsp_invoice.recover_payee_pub_key() != lsp.pubkey
+        {
+            return Err(MutinyError::InvoiceCreationFailed);
+        }
 
-            Ok(lsp_invoice)
-        } else {
-            Ok(invoice)
+        if let Some(mut order) = lsp_order {
+            order.payment_hash = invoice.payment_hash().to_hex();
+            self.persister.persist_lsp_order(&order)?;
         }
+
+        Ok(lsp_invoice)
     }
 
     async fn create_internal_invoice(
@@ -656,6 +1213,7 @@ impl<S: MutinyStorage> Node<S> {
         fee_amount_msat: Option<u64>,
         labels: Vec<String>,
         route_hints: Option<Vec<PhantomRouteHints>>,
+        expiry_secs: u32,
     ) -> Result<Invoice, MutinyError> {
         let amount_msat = amount_sat.map(|s| s * 1_000);
         // Set description to empty string to make smallest possible invoice/QR code
@@ -675,6 +1233,7 @@ impl<S: MutinyStorage> Node<S> {
             sleep(1_000).await;
         }
 
+        let is_phantom = route_hints.is_some();
         let invoice_res = match route_hints {
             None => {
                 let now = crate::utils::now();
@@ -686,7 +1245,7 @@ impl<S: MutinyStorage> Node<S> {
                     amount_msat,
                     description,
                     now,
-                    1500,
+                    expiry_secs,
                     Some(40),
                 )
             }
@@ -694,7 +1253,7 @@ impl<S: MutinyStorage> Node<S> {
                 amount_msat,
                 None,
                 description,
-                1500,
+                expiry_secs,
                 r,
                 self.keys_manager.clone(),
                 self.keys_manager.clone(),
@@ -709,6 +1268,188 @@ impl<S: MutinyStorage> Node<S> {
             MutinyError::InvoiceCreationFailed
         })?;
 
+        // save the route hints so we can still create invoices if our peers go offline
+        if !is_phantom && !invoice.route_hints().is_empty() {
+            let cached = CachedRouteHints {
+                hints: invoice.route_hints(),
+                timestamp: crate::utils::now().as_secs(),
+            };
+            if let Ok(mut cache) = self.route_hint_cache.try_write() {
+                *cache = Some(cached);
+            }
+        }
+
+        self.persist_new_invoice(&invoice, amount_msat, fee_amount_msat, labels)?;
+
+        log_info!(self.logger, "SUCCESS: generated invoice: {invoice}");
+
+        Ok(invoice)
+    }
+
+    /// Returns the cached route hints for this node if they have not expired
+    fn get_cached_route_hints(&self) -> Option<Vec<RouteHint>> {
+        let cache = self.route_hint_cache.try_read().ok()?;
+        let cached = cache.as_ref()?;
+        let now = crate::utils::now().as_secs();
+        if now.saturating_sub(cached.timestamp) > ROUTE_HINT_CACHE_EXPIRY_SECS {
+            return None;
+        }
+        Some(cached.hints.clone())
+    }
+
+    /// Creates an invoice using the given route hints instead of the ones
+    /// from our currently usable channels.
+    fn create_degraded_invoice(
+        &self,
+        amount_sat: Option<u64>,
+        labels: Vec<String>,
+        hints: Vec<RouteHint>,
+        expiry_secs: u32,
+    ) -> Result<Invoice, MutinyError> {
+        let amount_msat = amount_sat.map(|s| s * 1_000);
+        let (payment_hash, payment_secret) = self
+            .channel_manager
+            .create_inbound_payment(amount_msat, expiry_secs, Some(40))
+            .map_err(|_| MutinyError::InvoiceCreationFailed)?;
+
+        let mut builder = InvoiceBuilder::new(self.network.into())
+            .description("".to_string())
+            .duration_since_epoch(crate::utils::now())
+            .payment_hash(Sha256::from_inner(payment_hash.0))
+            .payment_secret(payment_secret)
+            .basic_mpp()
+            .min_final_cltv_expiry_delta(40)
+            .expiry_time(Duration::from_secs(expiry_secs.into()));
+        if let Some(amt) = amount_msat {
+            builder = builder.amount_milli_satoshis(amt);
+        }
+        for hint in hints {
+            builder = builder.private_route(hint);
+        }
+
+        let raw_invoice = builder.build_raw().map_err(|e| {
+            log_error!(self.logger, "ERROR: could not build invoice: {e:?}");
+            MutinyError::InvoiceCreationFailed
+        })?;
+        let hrp_str = raw_invoice.hrp.to_string();
+        let data = raw_invoice.data.to_base32();
+        let invoice = raw_invoice
+            .sign(|_| {
+                self.keys_manager
+                    .sign_invoice(hrp_str.as_bytes(), &data, Recipient::Node)
+            })
+            .ok()
+            .and_then(|signed| Invoice::from_signed(signed).ok())
+            .ok_or(MutinyError::InvoiceCreationFailed)?;
+
+        self.persist_new_invoice(&invoice, amount_msat, None, labels)?;
+
+        log_info!(
+            self.logger,
+            "SUCCESS: generated invoice with cached route hints: {invoice}"
+        );
+
+        Ok(invoice)
+    }
+
+    /// Creates an invoice for a payment hash we don't know the preimage of.
+    ///
+    /// Payments to it are held once they arrive until they are settled with
+    /// [Node::settle_hodl_invoice] or cancelled with [Node::cancel_hodl_invoice].
+    pub fn create_hodl_invoice(
+        &self,
+        amount_sat: Option<u64>,
+        payment_hash: Sha256,
+        labels: Vec<String>,
+    ) -> Result<Invoice, MutinyError> {
+        let amount_msat = amount_sat.map(|s| s * 1_000);
+        let invoice =
+            create_invoice_from_channelmanager_and_duration_since_epoch_with_payment_hash(
+                &self.channel_manager.clone(),
+                self.keys_manager.clone(),
+                self.logger.clone(),
+                self.network.into(),
+                amount_msat,
+                "".to_string(),
+                crate::utils::now(),
+                DEFAULT_INVOICE_EXPIRY_SECS,
+                PaymentHash(payment_hash.into_inner()),
+                Some(40),
+            )
+            .map_err(|e| {
+                log_error!(self.logger, "ERROR: could not generate hodl invoice: {e}");
+                MutinyError::InvoiceCreationFailed
+            })?;
+
+        self.persist_new_invoice(&invoice, amount_msat, None, labels)?;
+
+        log_info!(self.logger, "SUCCESS: generated hodl invoice: {invoice}");
+
+        Ok(invoice)
+    }
+
+    /// Claims a held payment for a hodl invoice with its preimage.
+    /// Errors if there is no held payment for the preimage.
+    pub fn settle_hodl_invoice(&self, preimage: [u8; 32]) -> Result<(), MutinyError> {
+        let payment_hash = PaymentHash(Sha256::hash(&preimage).into_inner());
+        let mut payment_info = self
+            .persister
+            .read_payment_info(&payment_hash, true, &self.logger)
+            .ok_or(MutinyError::NotFound)?;
+        if payment_info.status != HTLCStatus::InFlight {
+            return Err(MutinyError::NotFound);
+        }
+
+        // record the claim before starting it so it can be finished
+        // on the next start if we are shut down in the middle of it
+        let now = crate::utils::now().as_secs();
+        let intent = ClaimIntent {
+            preimage,
+            amount_msat: payment_info.amt_msat.0.unwrap_or_default(),
+            created_at: now,
+            claim_deadline: None,
+        };
+        self.persister
+            .persist_claim_intent(&payment_hash, &intent)?;
+
+        // the claimed event doesn't include the preimage of hodl invoices
+        payment_info.preimage = Some(preimage);
+        payment_info.last_update = now;
+        self.persister
+            .persist_payment_info(&payment_hash, &payment_info, true)?;
+
+        self.channel_manager.claim_funds(PaymentPreimage(preimage));
+        Ok(())
+    }
+
+    /// Cancels a hodl invoice, a held payment is failed back to the sender
+    /// and any later payment is rejected.
+    pub fn cancel_hodl_invoice(&self, payment_hash: &Sha256) -> Result<(), MutinyError> {
+        let payment_hash = PaymentHash(payment_hash.into_inner());
+        let mut payment_info = self
+            .persister
+            .read_payment_info(&payment_hash, true, &self.logger)
+            .ok_or(MutinyError::NotFound)?;
+        if payment_info.status == HTLCStatus::Succeeded || payment_info.preimage.is_some() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        payment_info.status = HTLCStatus::Failed;
+        payment_info.last_update = crate::utils::now().as_secs();
+        self.persister
+            .persist_payment_info(&payment_hash, &payment_info, true)?;
+
+        self.channel_manager.fail_htlc_backwards(&payment_hash);
+        Ok(())
+    }
+
+    fn persist_new_invoice(
+        &self,
+        invoice: &Invoice,
+        amount_msat: Option<u64>,
+        fee_amount_msat: Option<u64>,
+        labels: Vec<String>,
+    ) -> Result<(), MutinyError> {
         let last_update = crate::utils::now().as_secs();
         let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
         let payment_info = PaymentInfo {
@@ -719,6 +1460,9 @@ impl<S: MutinyStorage> Node<S> {
             fee_paid_msat: fee_amount_msat,
             bolt11: Some(invoice.clone()),
             payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             last_update,
         };
         self.persister
@@ -732,9 +1476,7 @@ impl<S: MutinyStorage> Node<S> {
             .storage
             .set_invoice_labels(invoice.clone(), labels)?;
 
-        log_info!(self.logger, "SUCCESS: generated invoice: {invoice}");
-
-        Ok(invoice)
+        Ok(())
     }
 
     pub fn get_invoice(&self, invoice: &Invoice) -> Result<MutinyInvoice, MutinyError> {
@@ -750,12 +1492,16 @@ impl<S: MutinyStorage> Node<S> {
             .and_then(|inv| labels_map.get(inv).cloned())
             .unwrap_or_default();
 
-        MutinyInvoice::from(
+        let invoice = MutinyInvoice::from(
             payment_info,
             PaymentHash(payment_hash.into_inner()),
             inbound,
             labels,
-        )
+        )?;
+        Ok(MutinyInvoice {
+            memo: self.persister.storage.get_memo(payment_hash.to_hex())?,
+            ..invoice
+        })
     }
 
     pub fn list_invoices(&self) -> Result<Vec<MutinyInvoice>, MutinyError> {
@@ -771,6 +1517,7 @@ impl<S: MutinyStorage> Node<S> {
     ) -> Result<Vec<MutinyInvoice>, MutinyError> {
         let now = utils::now();
         let labels_map = self.persister.storage.get_invoice_labels()?;
+        let memos = self.persister.storage.get_memos()?;
 
         Ok(self
             .persister
@@ -781,7 +1528,13 @@ impl<S: MutinyStorage> Node<S> {
                     None => vec![],
                     Some(i) => labels_map.get(&i).cloned().unwrap_or_default(),
                 };
-                let mutiny_invoice = MutinyInvoice::from(i.clone(), h, inbound, labels).ok();
+                let mutiny_invoice =
+                    MutinyInvoice::from(i.clone(), h, inbound, labels)
+                        .ok()
+                        .map(|invoice| MutinyInvoice {
+                            memo: memos.get(&h.0.to_hex()).cloned(),
+                            ..invoice
+                        });
 
                 // filter out expired invoices
                 mutiny_invoice.filter(|invoice| {
@@ -792,6 +1545,76 @@ impl<S: MutinyStorage> Node<S> {
             .collect())
     }
 
+    /// Lists the payments with the given status, or every payment if there is no filter.
+    /// Unpaid invoices that have expired are listed as [PaymentStatus::Expired].
+    pub fn list_payments(
+        &self,
+        status_filter: Option<PaymentStatus>,
+    ) -> Result<Vec<MutinyPayment>, MutinyError> {
+        let now = utils::now();
+        let labels_map = self.persister.storage.get_invoice_labels()?;
+        let memos = self.persister.storage.get_memos()?;
+
+        let mut payments = vec![];
+        for inbound in [true, false] {
+            for (payment_hash, info) in self.persister.list_payment_info(inbound)? {
+                let expired = info.status == HTLCStatus::Pending
+                    && info.bolt11.as_ref().is_some_and(|b| b.would_expire(now));
+                let status = match info.status {
+                    _ if expired => PaymentStatus::Expired,
+                    HTLCStatus::Pending | HTLCStatus::InFlight => PaymentStatus::Pending,
+                    HTLCStatus::Succeeded => PaymentStatus::Succeeded,
+                    HTLCStatus::Failed => PaymentStatus::Failed,
+                };
+                if status_filter.is_some_and(|s| s != status) {
+                    continue;
+                }
+
+                let failure_reason = match status {
+                    PaymentStatus::Expired => Some(PaymentFailureReason::Expired),
+                    PaymentStatus::Succeeded => None,
+                    _ => info.failure_reason,
+                };
+                let labels = match info.bolt11.as_ref() {
+                    None => vec![],
+                    Some(i) => labels_map.get(i).cloned().unwrap_or_default(),
+                };
+                let mut invoice = MutinyInvoice::from(info, payment_hash, inbound, labels)?;
+                invoice.memo = memos.get(&payment_hash.0.to_hex()).cloned();
+                payments.push(MutinyPayment {
+                    invoice,
+                    status,
+                    failure_reason,
+                });
+            }
+        }
+
+        Ok(payments)
+    }
+
+    /// Deletes the failed and expired payments that were last updated before `older_than`,
+    /// a unix timestamp in seconds. Returns the number of payments deleted.
+    pub fn purge_failed_payments(&self, older_than: u64) -> Result<usize, MutinyError> {
+        let now = utils::now();
+        let mut count = 0;
+        for inbound in [true, false] {
+            for (payment_hash, info) in self.persister.list_payment_info(inbound)? {
+                if info.last_update >= older_than {
+                    continue;
+                }
+
+                let expired = info.status == HTLCStatus::Pending
+                    && info.bolt11.as_ref().is_some_and(|b| b.would_expire(now));
+                if info.status == HTLCStatus::Failed || expired {
+                    self.persister.delete_payment_info(&payment_hash, inbound)?;
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
     /// Gets all the closed channels for this node
     pub fn get_channel_closure(
         &self,
@@ -800,6 +1623,11 @@ impl<S: MutinyStorage> Node<S> {
         self.persister.get_channel_closure(user_channel_id)
     }
 
+    /// Gets all the channels bought from our LSP and whether they were delivered as ordered
+    pub fn get_lsp_orders(&self) -> Result<Vec<LspOrder>, MutinyError> {
+        self.persister.list_lsp_orders()
+    }
+
     /// Gets all the closed channels for this node
     pub fn get_channel_closures(&self) -> Result<Vec<ChannelClosure>, MutinyError> {
         Ok(self
@@ -846,6 +1674,7 @@ impl<S: MutinyStorage> Node<S> {
         &self,
         invoice: &Invoice,
         amt_sats: Option<u64>,
+        options: PaymentOptions,
         labels: Vec<String>,
     ) -> Result<PaymentHash, MutinyError> {
         let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
@@ -879,7 +1708,39 @@ impl<S: MutinyStorage> Node<S> {
             sleep(1_000).await;
         }
 
-        let (pay_result, amt_msat) = if invoice.amount_milli_satoshis().is_none() {
+        if options.max_fee.is_some() || options.outgoing_channels.is_some() {
+            return self.pay_invoice_along_route(
+                invoice,
+                amt_sats,
+                options.max_fee,
+                options.outgoing_channels.as_deref(),
+                options.mpp,
+                labels,
+            );
+        }
+
+        let (pay_result, amt_msat) = if let Some(mpp) = options.mpp {
+            // limiting the parts doesn't need a fixed route, so failed parts are still retried
+            let amt_msat = invoice_amount_msats(invoice, amt_sats)?;
+            let mut route_params = invoice_route_params(invoice, amt_msat);
+            route_params.payment_params = route_params
+                .payment_params
+                .with_max_path_count(mpp.max_path_count(amt_msat));
+            let payment_id = PaymentId(payment_hash.0);
+            (
+                self.channel_manager
+                    .send_payment(
+                        payment_hash,
+                        RecipientOnionFields::secret_only(*invoice.payment_secret()),
+                        payment_id,
+                        route_params,
+                        Retry::Attempts(5),
+                    )
+                    .map(|_| payment_id)
+                    .map_err(PaymentError::Sending),
+                amt_msat,
+            )
+        } else if invoice.amount_milli_satoshis().is_none() {
             if amt_sats.is_none() {
                 return Err(MutinyError::InvoiceInvalid);
             }
@@ -920,6 +1781,9 @@ impl<S: MutinyStorage> Node<S> {
             fee_paid_msat: None,
             bolt11: Some(invoice.clone()),
             payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             last_update,
         };
 
@@ -939,6 +1803,7 @@ impl<S: MutinyStorage> Node<S> {
                 );
 
                 payment_info.status = HTLCStatus::Failed;
+                payment_info.failure_reason = Some(PaymentFailureReason::NoRoute);
                 self.persister
                     .persist_payment_info(&payment_hash, &payment_info, false)?;
 
@@ -977,6 +1842,17 @@ impl<S: MutinyStorage> Node<S> {
         loop {
             let now = utils::now().as_secs();
             if now - start > timeout {
+                // the payment may still go through, so it is only marked as timed out
+                if let Some(mut info) =
+                    self.persister
+                        .read_payment_info(&payment_hash, false, &self.logger)
+                {
+                    if info.status == HTLCStatus::InFlight {
+                        info.failure_reason = Some(PaymentFailureReason::Timeout);
+                        self.persister
+                            .persist_payment_info(&payment_hash, &info, false)?;
+                    }
+                }
                 return Err(MutinyError::PaymentTimeout);
             }
 
@@ -1000,28 +1876,219 @@ impl<S: MutinyStorage> Node<S> {
         }
     }
 
+    /// Pays an invoice along a route we find ourselves, failing with
+    /// [MutinyError::FeeTooHigh] if there is none within the fee limit.
+    /// The route can be forced out of some of our channels by their funding outpoints,
+    /// and how many parts it is split into can be limited.
+    ///
+    /// LDK can't limit the fees or first hops of the routes it retries with,
+    /// so the payment is not retried along other routes.
+    fn pay_invoice_along_route(
+        &self,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+        max_fee: Option<MaxFee>,
+        outgoing_channels: Option<&[OutPoint]>,
+        mpp: Option<MppOptions>,
+        labels: Vec<String>,
+    ) -> Result<PaymentHash, MutinyError> {
+        let amt_msat = invoice_amount_msats(invoice, amt_sats)?;
+        let mut route_params = invoice_route_params(invoice, amt_msat);
+        if let Some(mpp) = mpp {
+            route_params.payment_params = route_params
+                .payment_params
+                .with_max_path_count(mpp.max_path_count(amt_msat));
+        }
+        let route = self.find_route_with_max_fee(&route_params, max_fee, outgoing_channels)?;
+
+        if let Err(e) = self
+            .persister
+            .storage
+            .set_invoice_labels(invoice.clone(), labels)
+        {
+            log_error!(self.logger, "could not set invoice label: {e}");
+        }
+
+        let payment_hash = PaymentHash(invoice.payment_hash().into_inner());
+        let mut payment_info = PaymentInfo {
+            preimage: None,
+            secret: None,
+            status: HTLCStatus::InFlight,
+            amt_msat: MillisatAmount(Some(amt_msat)),
+            fee_paid_msat: None,
+            bolt11: Some(invoice.clone()),
+            payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
+            last_update: utils::now().as_secs(),
+        };
+        self.persister
+            .persist_payment_info(&payment_hash, &payment_info, false)?;
+
+        let pay_result = self.channel_manager.send_payment_with_route(
+            &route,
+            payment_hash,
+            RecipientOnionFields::secret_only(*invoice.payment_secret()),
+            PaymentId(payment_hash.0),
+        );
+        if let Err(e) = pay_result {
+            log_error!(self.logger, "failed to make payment: {e:?}");
+            payment_info.status = HTLCStatus::Failed;
+            payment_info.failure_reason = Some(PaymentFailureReason::NoRoute);
+            self.persister
+                .persist_payment_info(&payment_hash, &payment_info, false)?;
+            return Err(MutinyError::RoutingFailed);
+        }
+
+        Ok(payment_hash)
+    }
+
+    /// Finds a route over our usable channels without sending anything,
+    /// only starting with the given channels if there are any.
+    fn find_route(
+        &self,
+        route_params: &RouteParameters,
+        outgoing_channels: Option<&[OutPoint]>,
+    ) -> Result<Route, MutinyError> {
+        let usable_channels = self.channel_manager.list_usable_channels();
+        let first_hops: Vec<&ChannelDetails> = usable_channels
+            .iter()
+            .filter(|c| {
+                outgoing_channels.map_or(true, |outpoints| {
+                    c.funding_txo
+                        .is_some_and(|f| outpoints.contains(&f.into_bitcoin_outpoint()))
+                })
+            })
+            .collect();
+        if first_hops.is_empty() && outgoing_channels.is_some() {
+            log_warn!(self.logger, "none of the selected channels are usable");
+            return Err(MutinyError::NotFound);
+        }
+        let inflight_htlcs = self.channel_manager.compute_inflight_htlcs();
+        self.router
+            .find_route(
+                &self.pubkey,
+                route_params,
+                Some(&first_hops),
+                &inflight_htlcs,
+            )
+            .map_err(|e| {
+                log_warn!(self.logger, "could not find route: {}", e.err);
+                MutinyError::RoutingFailed
+            })
+    }
+
+    /// Finds a route whose fees are within the limit, if there is one
+    fn find_route_with_max_fee(
+        &self,
+        route_params: &RouteParameters,
+        max_fee: Option<MaxFee>,
+        outgoing_channels: Option<&[OutPoint]>,
+    ) -> Result<Route, MutinyError> {
+        let route = self.find_route(route_params, outgoing_channels)?;
+
+        let limit = max_fee.map_or(u64::MAX, |m| m.limit_msat(route_params.final_value_msat));
+        if route.get_total_fees() > limit {
+            log_warn!(
+                self.logger,
+                "cheapest route costs {} msats in fees, over the limit of {limit} msats",
+                route.get_total_fees()
+            );
+            return Err(MutinyError::FeeTooHigh);
+        }
+
+        Ok(route)
+    }
+
     pub async fn pay_invoice_with_timeout(
         &self,
         invoice: &Invoice,
         amt_sats: Option<u64>,
+        options: PaymentOptions,
         timeout_secs: Option<u64>,
         labels: Vec<String>,
     ) -> Result<MutinyInvoice, MutinyError> {
         // initiate payment
         let payment_hash = self
-            .init_invoice_payment(invoice, amt_sats, labels.clone())
+            .init_invoice_payment(invoice, amt_sats, options, labels.clone())
             .await?;
         let timeout: u64 = timeout_secs.unwrap_or(DEFAULT_PAYMENT_TIMEOUT);
 
         self.await_payment(payment_hash, timeout, labels).await
     }
 
+    /// The total we can send right now, summed over the outbound capacity
+    /// of the channels that are usable. A single payment may get less than this
+    /// if it can't be split across them.
+    pub(crate) fn usable_outbound_msat(&self) -> u64 {
+        self.channel_manager
+            .list_usable_channels()
+            .iter()
+            .map(|c| c.outbound_capacity_msat)
+            .sum()
+    }
+
+    /// Finds a route for the given invoice without sending any HTLCs.
+    /// Returns the expected fees and CLTV delta of the payment so it can
+    /// be shown to the user before they confirm it.
+    pub fn dry_run_payment(
+        &self,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<PaymentEstimate, MutinyError> {
+        let (route_params, route) = self.dry_run_route(invoice, amt_sats)?;
+        Ok(payment_estimate(route_params.final_value_msat, &route))
+    }
+
+    /// Finds the route paying an invoice would take, without sending anything
+    fn dry_run_route(
+        &self,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<(RouteParameters, Route), MutinyError> {
+        let amount_msats = invoice_amount_msats(invoice, amt_sats)?;
+
+        if amount_msats > self.usable_outbound_msat() {
+            return Err(MutinyError::InsufficientBalance);
+        }
+
+        let route_params = invoice_route_params(invoice, amount_msats);
+        let route = self.find_route(&route_params, None)?;
+        Ok((route_params, route))
+    }
+
+    /// Estimates the routing fee of paying an invoice with pathfinding against
+    /// the network graph and scorer, without sending any HTLCs.
+    ///
+    /// The cheapest route is the low end of the range. If a payment fails LDK retries
+    /// along other routes, so the high end is the fee of the cheapest route that avoids
+    /// the first route's intermediate channels, when there is one.
+    pub fn estimate_payment_fee(
+        &self,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<FeeEstimate, MutinyError> {
+        let (route_params, route) = self.dry_run_route(invoice, amt_sats)?;
+
+        let mut fallback_params = route_params.clone();
+        fallback_params.payment_params.previously_failed_channels = intermediate_channels(&route);
+        let fallback = self.find_route(&fallback_params, None).ok();
+
+        Ok(fee_estimate(
+            route_params.final_value_msat,
+            &route,
+            fallback.as_ref(),
+        ))
+    }
+
     /// init_keysend_payment sends off the payment but does not wait for results
     /// use keysend_with_timeout to wait for results
     pub fn init_keysend_payment(
         &self,
         to_node: PublicKey,
         amt_sats: u64,
+        max_fee: Option<MaxFee>,
         labels: Vec<String>,
     ) -> Result<MutinyInvoice, MutinyError> {
         let mut entropy = [0u8; 32];
@@ -1040,13 +2107,30 @@ impl<S: MutinyStorage> Node<S> {
             payment_params,
         };
 
-        let pay_result = self.channel_manager.send_spontaneous_payment_with_retry(
-            Some(preimage),
-            RecipientOnionFields::spontaneous_empty(),
-            payment_id,
-            route_params,
-            Retry::Attempts(5),
-        );
+        // LDK can't limit the fees of retries, so with a limit we only try one route
+        let sent = match max_fee {
+            Some(max_fee) => {
+                let route = self.find_route_with_max_fee(&route_params, Some(max_fee), None)?;
+                self.channel_manager
+                    .send_spontaneous_payment(
+                        &route,
+                        Some(preimage),
+                        RecipientOnionFields::spontaneous_empty(),
+                        payment_id,
+                    )
+                    .is_ok()
+            }
+            None => self
+                .channel_manager
+                .send_spontaneous_payment_with_retry(
+                    Some(preimage),
+                    RecipientOnionFields::spontaneous_empty(),
+                    payment_id,
+                    route_params,
+                    Retry::Attempts(5),
+                )
+                .is_ok(),
+        };
 
         let payment_hash = PaymentHash(Sha256::hash(&preimage.0).into_inner());
 
@@ -1059,24 +2143,24 @@ impl<S: MutinyStorage> Node<S> {
             fee_paid_msat: None,
             bolt11: None,
             payee_pubkey: Some(to_node),
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             last_update,
         };
 
         self.persister
             .persist_payment_info(&payment_hash, &payment_info, false)?;
 
-        match pay_result {
-            Ok(_) => {
-                let mutiny_invoice =
-                    MutinyInvoice::from(payment_info, payment_hash, false, labels)?;
-                Ok(mutiny_invoice)
-            }
-            Err(_) => {
-                payment_info.status = HTLCStatus::Failed;
-                self.persister
-                    .persist_payment_info(&payment_hash, &payment_info, false)?;
-                Err(MutinyError::RoutingFailed)
-            }
+        if sent {
+            let mutiny_invoice = MutinyInvoice::from(payment_info, payment_hash, false, labels)?;
+            Ok(mutiny_invoice)
+        } else {
+            payment_info.status = HTLCStatus::Failed;
+            payment_info.failure_reason = Some(PaymentFailureReason::NoRoute);
+            self.persister
+                .persist_payment_info(&payment_hash, &payment_info, false)?;
+            Err(MutinyError::RoutingFailed)
         }
     }
 
@@ -1084,11 +2168,12 @@ impl<S: MutinyStorage> Node<S> {
         &self,
         to_node: PublicKey,
         amt_sats: u64,
+        max_fee: Option<MaxFee>,
         labels: Vec<String>,
         timeout_secs: Option<u64>,
     ) -> Result<MutinyInvoice, MutinyError> {
         // initiate payment
-        let pay = self.init_keysend_payment(to_node, amt_sats, labels.clone())?;
+        let pay = self.init_keysend_payment(to_node, amt_sats, max_fee, labels.clone())?;
 
         let timeout: u64 = timeout_secs.unwrap_or(DEFAULT_PAYMENT_TIMEOUT);
         let payment_hash = PaymentHash(pay.payment_hash.into_inner());
@@ -1130,17 +2215,38 @@ impl<S: MutinyStorage> Node<S> {
         }
     }
 
+    /// Starts opening a channel with the given [OpenChannelOptions]
     pub async fn init_open_channel(
         &self,
         pubkey: PublicKey,
         amount_sat: u64,
-        user_channel_id: Option<u128>,
+        options: OpenChannelOptions,
     ) -> Result<u128, MutinyError> {
-        let mut config = default_user_config();
+        let OpenChannelOptions {
+            fee_rate,
+            push_msat,
+            user_channel_id,
+            announce,
+        } = options;
+        let push_msat = push_msat.unwrap_or(0);
+        if push_msat > amount_sat * 1_000 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        if fee_rate.is_some_and(|rate| !rate.is_finite() || rate < 1.0) {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let mut config = user_config(&self.persister.storage);
+
+        // announced channels can't hide behind an SCID alias
+        if announce {
+            config.channel_handshake_config.announced_channel = true;
+            config.channel_handshake_config.negotiate_scid_privacy = false;
+        }
 
         // if we are opening channel to LSP, turn off SCID alias until CLN is updated
         // LSP protects all invoice information anyways, so no UTXO leakage
-        if let Some(lsp) = self.lsp_client.clone() {
+        if let Some(lsp) = self.lsp_client() {
             if pubkey == lsp.pubkey {
                 config.channel_handshake_config.negotiate_scid_privacy = false;
             }
@@ -1153,10 +2259,22 @@ impl<S: MutinyStorage> Node<S> {
             u128::from_be_bytes(user_channel_id_bytes)
         });
 
+        // save the fee rate to use when we create the funding transaction
+        if let Some(fee_rate) = fee_rate {
+            let params = ChannelOpenParams {
+                // 250 weight units per vbyte
+                sats_per_kw: (fee_rate * 250.0) as u32,
+                utxos: vec![],
+                labels: None,
+            };
+            self.persister
+                .persist_channel_open_params(user_channel_id, params)?;
+        }
+
         match self.channel_manager.create_channel(
             pubkey,
             amount_sat,
-            0,
+            push_msat,
             user_channel_id,
             Some(config),
         ) {
@@ -1172,6 +2290,10 @@ impl<S: MutinyStorage> Node<S> {
                     self.logger,
                     "ERROR: failed to open channel to pubkey {pubkey:?}: {e:?}"
                 );
+                // delete params from db because channel failed
+                if fee_rate.is_some() {
+                    self.persister.delete_channel_open_params(user_channel_id)?;
+                }
                 Err(MutinyError::ChannelCreationFailed)
             }
         }
@@ -1181,16 +2303,87 @@ impl<S: MutinyStorage> Node<S> {
         &self,
         pubkey: PublicKey,
         amount_sat: u64,
-        user_channel_id: Option<u128>,
+        options: OpenChannelOptions,
         timeout: u64,
     ) -> Result<OutPoint, MutinyError> {
-        let init = self
-            .init_open_channel(pubkey, amount_sat, user_channel_id)
-            .await?;
+        let init = self.init_open_channel(pubkey, amount_sat, options).await?;
 
         self.await_chan_funding_tx(init, &pubkey, timeout).await
     }
 
+    /// Broadcasts our node announcement with the given alias, color and addresses.
+    /// Other nodes ignore announcements from nodes without public channels,
+    /// so this does nothing until we have one.
+    pub(crate) fn broadcast_node_announcement(
+        &self,
+        config: &NodeAnnouncementConfig,
+    ) -> Result<(), MutinyError> {
+        let has_public_channel = self
+            .channel_manager
+            .list_channels()
+            .iter()
+            .any(|c| c.is_public && c.is_usable);
+        if !has_public_channel {
+            return Ok(());
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        let listen_port = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        let listen_port = self.listen_port;
+
+        self.peer_manager.broadcast_node_announcement(
+            config.rgb()?,
+            config.alias_bytes()?,
+            config.net_addresses(listen_port)?,
+        );
+        log_info!(self.logger, "broadcasted node announcement");
+        Ok(())
+    }
+
+    /// Sends an onion message to a node, through the given nodes if there are any.
+    /// The first node it goes to has to be one of our peers.
+    pub(crate) fn send_onion_message(
+        &self,
+        to_node: PublicKey,
+        intermediate_nodes: &[PublicKey],
+        message: CustomOnionMessage,
+    ) -> Result<(), MutinyError> {
+        if message.tlv_type < MIN_CUSTOM_ONION_MESSAGE_TYPE {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        self.onion_messenger
+            .send_onion_message(
+                intermediate_nodes,
+                Destination::Node(to_node),
+                OnionMessageContents::Custom(message),
+                None,
+            )
+            .map_err(|e| {
+                log_warn!(self.logger, "could not send onion message: {e:?}");
+                MutinyError::RoutingFailed
+            })?;
+        // send it now instead of on the next background tick
+        self.peer_manager.process_events();
+        Ok(())
+    }
+
+    /// Sends a custom message to a peer we are connected to
+    pub(crate) fn send_peer_message(
+        &self,
+        peer: PublicKey,
+        message: CustomPeerMessage,
+    ) -> Result<(), MutinyError> {
+        if !self.peer_manager.get_peer_node_ids().contains(&peer) {
+            return Err(MutinyError::NotFound);
+        }
+
+        self.custom_message_handler.queue(peer, message)?;
+        self.peer_manager.process_events();
+        Ok(())
+    }
+
     pub async fn init_sweep_utxos_to_channel(
         &self,
         user_chan_id: Option<u128>,
@@ -1228,10 +2421,10 @@ impl<S: MutinyStorage> Node<S> {
         // channel size is the total value of the utxos minus the fee
         let channel_value_satoshis = utxo_value - expected_fee;
 
-        let mut config = default_user_config();
+        let mut config = user_config(&self.persister.storage);
         // if we are opening channel to LSP, turn off SCID alias until CLN is updated
         // LSP protects all invoice information anyways, so no UTXO leakage
-        if let Some(lsp) = self.lsp_client.clone() {
+        if let Some(lsp) = self.lsp_client() {
             if pubkey == lsp.pubkey {
                 config.channel_handshake_config.negotiate_scid_privacy = false;
             }
@@ -1298,11 +2491,13 @@ impl<S: MutinyStorage> Node<S> {
 async fn start_reconnection_handling(
     storage: &impl MutinyStorage,
     node_pubkey: PublicKey,
-    #[cfg(target_arch = "wasm32")] websocket_proxy_addr: String,
+    #[cfg(target_arch = "wasm32")] websocket_proxies: Arc<WsProxyFailover>,
+    #[cfg(not(target_arch = "wasm32"))] socks_proxy: Option<String>,
     peer_man: Arc<dyn PeerManager>,
     logger: &Arc<MutinyLogger>,
     uuid: String,
     lsp_client: &Option<LspClient>,
+    notifier: Arc<EventNotifier>,
     stop: Arc<AtomicBool>,
     stopped_components: Arc<RwLock<Vec<bool>>>,
     skip_fee_estimates: bool,
@@ -1328,7 +2523,9 @@ async fn start_reconnection_handling(
 
     // Attempt initial connections first in the background
     #[cfg(target_arch = "wasm32")]
-    let websocket_proxy_addr_copy_proxy = websocket_proxy_addr.clone();
+    let websocket_proxies_copy = websocket_proxies.clone();
+    #[cfg(not(target_arch = "wasm32"))]
+    let socks_proxy_copy = socks_proxy.clone();
 
     let proxy_logger = logger.clone();
     let peer_man_proxy = peer_man.clone();
@@ -1343,7 +2540,9 @@ async fn start_reconnection_handling(
 
             let connect_res = connect_peer_if_necessary(
                 #[cfg(target_arch = "wasm32")]
-                &websocket_proxy_addr_copy_proxy,
+                &websocket_proxies_copy,
+                #[cfg(not(target_arch = "wasm32"))]
+                socks_proxy_copy.as_deref(),
                 &PubkeyConnectionInfo::new(lsp.connection_string.as_str()).unwrap(),
                 proxy_logger.clone(),
                 peer_man_proxy.clone(),
@@ -1377,8 +2576,10 @@ async fn start_reconnection_handling(
     let connect_storage = storage.clone();
     stopped_components.try_write()?.push(false);
     utils::spawn(async move {
-        // hashMap to store backoff times for each pubkey
-        let mut backoff_times = HashMap::new();
+        // the current backoff, in seconds, and when to try next for each pubkey
+        let mut backoff_times: HashMap<NodeId, (u64, Duration)> = HashMap::new();
+        // the peers we were connected to last time we checked
+        let mut previous_connections: HashSet<PublicKey> = HashSet::new();
 
         loop {
             for _ in 0..5 {
@@ -1403,36 +2604,50 @@ async fn start_reconnection_handling(
             let peer_connections = get_all_peers(&connect_storage).unwrap_or_default();
             let current_connections = connect_peer_man.get_peer_node_ids();
 
-            let not_connected: Vec<(NodeId, String)> = peer_connections
+            let connected: HashSet<PublicKey> = current_connections.iter().copied().collect();
+            for node_id in connected.difference(&previous_connections) {
+                notifier.notify(MutinyEvent::PeerConnected { node_id: *node_id });
+            }
+            for node_id in previous_connections.difference(&connected) {
+                notifier.notify(MutinyEvent::PeerDisconnected { node_id: *node_id });
+            }
+            previous_connections = connected;
+
+            let not_connected: Vec<(NodeId, String, ReconnectPolicy)> = peer_connections
                 .into_iter()
                 .filter(|(_, d)| {
                     d.connection_string.is_some()
                         && d.nodes.binary_search(&uuid.to_string()).is_ok()
                 })
-                .map(|(n, d)| (n, d.connection_string.unwrap()))
-                .filter(|(n, _)| {
+                .map(|(n, d)| {
+                    let policy = d.reconnect_policy.unwrap_or_default();
+                    (n, d.connection_string.unwrap(), policy)
+                })
+                .filter(|(_, _, policy)| policy.always_reconnect)
+                .filter(|(n, _, _)| {
                     !current_connections
                         .iter()
                         .any(|c| &NodeId::from_pubkey(c) == n)
                 })
                 .collect();
 
-            for (pubkey, conn_str) in not_connected.into_iter() {
+            for (pubkey, conn_str, policy) in not_connected.into_iter() {
                 let now = crate::utils::now();
 
-                // initialize backoff time and last attempt time if they do not exist
-                let backoff_entry = backoff_times
-                    .entry(pubkey)
-                    .or_insert((INITIAL_RECONNECTION_DELAY, now));
+                // wait the initial delay before the first attempt
+                let backoff_entry = backoff_times.entry(pubkey).or_insert_with(|| {
+                    let delay = policy.initial_backoff_secs;
+                    (
+                        delay,
+                        now.saturating_add(Duration::from_secs(with_jitter(delay))),
+                    )
+                });
 
-                // skip this pubkey if not enough time has passed since the last attempt
-                if now - backoff_entry.1 < Duration::from_secs(backoff_entry.0) {
+                // skip this pubkey if it isn't time for the next attempt yet
+                if now < backoff_entry.1 {
                     continue;
                 }
 
-                // Update the last attempt time
-                backoff_entry.1 = now;
-
                 log_trace!(connect_logger, "going to auto connect to peer: {pubkey}");
                 let peer_connection_info = match PubkeyConnectionInfo::new(&conn_str) {
                     Ok(p) => p,
@@ -1444,7 +2659,9 @@ async fn start_reconnection_handling(
 
                 let connect_res = connect_peer_if_necessary(
                     #[cfg(target_arch = "wasm32")]
-                    &websocket_proxy_addr,
+                    &websocket_proxies,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    socks_proxy.as_deref(),
                     &peer_connection_info,
                     connect_logger.clone(),
                     connect_peer_man.clone(),
@@ -1455,20 +2672,113 @@ async fn start_reconnection_handling(
                     Ok(_) => {
                         log_trace!(connect_logger, "auto connected peer: {pubkey}");
                         // reset backoff time to initial value if connection is successful
-                        backoff_entry.0 = INITIAL_RECONNECTION_DELAY;
+                        backoff_entry.0 = policy.initial_backoff_secs;
                     }
                     Err(e) => {
                         log_warn!(connect_logger, "could not auto connect peer: {e}");
                         // double the backoff time if connection fails, but do not exceed max
-                        backoff_entry.0 = (backoff_entry.0 * 2).min(MAX_RECONNECTION_DELAY);
+                        backoff_entry.0 = backoff_entry
+                            .0
+                            .saturating_mul(2)
+                            .max(policy.initial_backoff_secs)
+                            .min(policy.max_backoff_secs);
                     }
                 }
+                backoff_entry.1 =
+                    now.saturating_add(Duration::from_secs(with_jitter(backoff_entry.0)));
             }
         }
     });
     Ok(())
 }
 
+/// Adds up to a quarter of the delay at random, so peers that
+/// dropped at the same time aren't all retried at once
+fn with_jitter(delay_secs: u64) -> u64 {
+    delay_secs.saturating_add(rand::random::<u64>() % (delay_secs / 4 + 1))
+}
+
+/// The amount to pay an invoice, a custom amount can only be given for invoices without one
+fn invoice_amount_msats(invoice: &Invoice, amt_sats: Option<u64>) -> Result<u64, MutinyError> {
+    match (invoice.amount_milli_satoshis(), amt_sats) {
+        (Some(amt), None) => Ok(amt),
+        (None, Some(amt)) => Ok(amt * 1_000),
+        _ => Err(MutinyError::InvoiceInvalid),
+    }
+}
+
+/// What paying the amount along the route would cost and how long it could be locked up
+fn payment_estimate(amount_msats: u64, route: &Route) -> PaymentEstimate {
+    // the total cltv of a path is the sum of all the hops' deltas,
+    // the payment will be locked up for at most the longest path
+    let cltv_expiry_delta = route
+        .paths
+        .iter()
+        .map(|p| p.hops.iter().map(|h| h.cltv_expiry_delta).sum::<u32>())
+        .max()
+        .unwrap_or_default();
+
+    PaymentEstimate {
+        amount_msats,
+        fee_msats: route.get_total_fees(),
+        cltv_expiry_delta,
+        num_paths: route.paths.len(),
+    }
+}
+
+/// The fee range of paying the amount along the route, the high end is the fee
+/// of the fallback route the payment would be retried along, if there is one
+fn fee_estimate(amount_msats: u64, route: &Route, fallback: Option<&Route>) -> FeeEstimate {
+    let min_fee_msats = payment_estimate(amount_msats, route).fee_msats;
+    let max_fee_msats = fallback
+        .map(|f| f.get_total_fees().max(min_fee_msats))
+        .unwrap_or(min_fee_msats);
+
+    let num_hops = route
+        .paths
+        .iter()
+        .map(|p| p.hops.len())
+        .max()
+        .unwrap_or_default();
+
+    FeeEstimate {
+        amount_msats,
+        min_fee_msats,
+        max_fee_msats,
+        num_hops,
+    }
+}
+
+/// The channels of a route that a retry can avoid,
+/// our own channels and the payee's last hop can't be avoided
+fn intermediate_channels(route: &Route) -> Vec<u64> {
+    route
+        .paths
+        .iter()
+        .flat_map(|p| p.hops.iter().skip(1).take(p.hops.len().saturating_sub(2)))
+        .map(|h| h.short_channel_id)
+        .collect()
+}
+
+/// The parameters to find a route for paying the given invoice
+fn invoice_route_params(invoice: &Invoice, amount_msats: u64) -> RouteParameters {
+    let expiry = invoice.duration_since_epoch() + invoice.expiry_time();
+    let mut payment_params = PaymentParameters::from_node_id(
+        invoice.recover_payee_pub_key(),
+        invoice.min_final_cltv_expiry_delta() as u32,
+    )
+    .with_expiry_time(expiry.as_secs())
+    .with_route_hints(invoice.route_hints());
+    if let Some(features) = invoice.features() {
+        payment_params = payment_params.with_features(features.clone());
+    }
+
+    RouteParameters {
+        final_value_msat: amount_msats,
+        payment_params,
+    }
+}
+
 fn stop_component(stopped_components: &Arc<RwLock<Vec<bool>>>) {
     let mut stopped = stopped_components
         .try_write()
@@ -1481,6 +2791,8 @@ fn stop_component(stopped_components: &Arc<RwLock<Vec<bool>>>) {
 pub(crate) fn create_peer_manager<S: MutinyStorage>(
     km: Arc<PhantomKeysManager<S>>,
     lightning_msg_handler: MessageHandler<S>,
+    custom_message_handler: Arc<MutinyCustomMessageHandler>,
+    peer_tracker: Arc<PeerTracker>,
     logger: Arc<MutinyLogger>,
 ) -> PeerManagerImpl<S> {
     let now = utils::now().as_secs();
@@ -1491,8 +2803,11 @@ pub(crate) fn create_peer_manager<S: MutinyStorage>(
         lightning_msg_handler,
         now as u32,
         &ephemeral_bytes,
-        logger,
-        Arc::new(IgnoringMessageHandler {}),
+        Arc::new(PeerManagerLogger {
+            logger,
+            peer_tracker,
+        }),
+        custom_message_handler,
         km,
     )
 }
@@ -1525,6 +2840,14 @@ pub(crate) fn split_peer_connection_string(
     Ok((pubkey, peer_addr_str.to_string()))
 }
 
+/// The default [UserConfig] with the user's inbound payment settings applied
+pub(crate) fn user_config<S: MutinyStorage>(storage: &S) -> UserConfig {
+    let mut config = default_user_config();
+    let inbound = storage.get_inbound_payment_config().unwrap_or_default();
+    config.channel_handshake_config.our_htlc_minimum_msat = inbound.min_htlc_msat;
+    config
+}
+
 pub(crate) fn default_user_config() -> UserConfig {
     UserConfig {
         channel_handshake_limits: ChannelHandshakeLimits {
@@ -1556,9 +2879,13 @@ pub(crate) fn default_user_config() -> UserConfig {
 mod tests {
     use crate::test_utils::*;
     use bitcoin::secp256k1::PublicKey;
+    use lightning::ln::features::{ChannelFeatures, NodeFeatures};
+    use lightning::routing::router::{Path, Route, RouteHop};
     use std::str::FromStr;
 
-    use crate::node::parse_peer_info;
+    use crate::node::{
+        fee_estimate, intermediate_channels, parse_peer_info, payment_estimate, with_jitter,
+    };
 
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
 
@@ -1596,4 +2923,79 @@ mod tests {
         assert_eq!(pub_key, peer_pubkey);
         assert_eq!(format!("{addr}:{port}"), peer_addr);
     }
+
+    #[test]
+    fn test_with_jitter() {
+        log!("test reconnection jitter");
+
+        for _ in 0..100 {
+            let delay = with_jitter(60);
+            assert!((60..=75).contains(&delay));
+        }
+        assert_eq!(with_jitter(0), 0);
+        assert_eq!(with_jitter(u64::MAX), u64::MAX);
+    }
+
+    fn route(paths: Vec<Vec<(u64, u64, u32)>>) -> Route {
+        let pubkey = PublicKey::from_str(
+            "0218845781f631c48f1c9709e23092067d06837f30aa0cd0544ac887fe91ddd166",
+        )
+        .unwrap();
+        let paths = paths
+            .into_iter()
+            .map(|hops| Path {
+                hops: hops
+                    .into_iter()
+                    .map(|(short_channel_id, fee_msat, cltv_expiry_delta)| RouteHop {
+                        pubkey,
+                        node_features: NodeFeatures::empty(),
+                        short_channel_id,
+                        channel_features: ChannelFeatures::empty(),
+                        fee_msat,
+                        cltv_expiry_delta,
+                    })
+                    .collect(),
+                blinded_tail: None,
+            })
+            .collect();
+        Route {
+            paths,
+            payment_params: None,
+        }
+    }
+
+    #[test]
+    fn test_payment_fee_estimate() {
+        log!("test payment fee estimate");
+
+        // the last hop of each path carries the amount, the others the fees
+        let cheapest = route(vec![
+            vec![(1, 10, 40), (2, 5, 40), (3, 1_000, 18)],
+            vec![(4, 0, 40), (5, 1_000, 18)],
+        ]);
+
+        let estimate = payment_estimate(2_000, &cheapest);
+        assert_eq!(estimate.amount_msats, 2_000);
+        assert_eq!(estimate.fee_msats, 15);
+        assert_eq!(estimate.cltv_expiry_delta, 98);
+        assert_eq!(estimate.num_paths, 2);
+
+        // only the hops between our channel and the payee's last hop can be avoided
+        assert_eq!(intermediate_channels(&cheapest), vec![2]);
+
+        let fallback = route(vec![vec![(1, 20, 40), (6, 10, 40), (3, 2_000, 18)]]);
+        let fees = fee_estimate(2_000, &cheapest, Some(&fallback));
+        assert_eq!(fees.amount_msats, 2_000);
+        assert_eq!(fees.min_fee_msats, estimate.fee_msats);
+        assert_eq!(fees.max_fee_msats, 30);
+        assert_eq!(fees.num_hops, 3);
+
+        // without a fallback route the fee can't be higher
+        let fees = fee_estimate(2_000, &cheapest, None);
+        assert_eq!(fees.max_fee_msats, 15);
+
+        let cheaper = route(vec![vec![(1, 1, 40), (6, 1, 40), (3, 2_000, 18)]]);
+        let fees = fee_estimate(2_000, &cheapest, Some(&cheaper));
+        assert_eq!(fees.max_fee_msats, 15);
+    }
 }
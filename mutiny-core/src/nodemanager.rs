@@ -1,72 +1,333 @@
 use anyhow::anyhow;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    ops::Deref,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 
-use crate::event::{HTLCStatus, PaymentInfo};
+use crate::audit::{IntegrationAuditRecord, IntegrationAuditStorage};
+use crate::event::{
+    ChannelAcceptor, ChannelRequest, HTLCStatus, PaymentFailureReason, PaymentInfo, PaymentPart,
+};
+use crate::fiat::{
+    FiatInvoice, FiatQuote, FiatQuoteStorage, DEFAULT_FIAT_QUOTE_TTL_SECS,
+    MAX_FIAT_INVOICE_REISSUES,
+};
 use crate::gossip::*;
-use crate::labels::LabelStorage;
+use crate::labels::{export_bip329, import_bip329, LabelStorage};
+use crate::lnurlpay::{
+    pay_callback_url, validate_pay_amount, verify_lnurl_payment, LnUrlPayMetadata, LnUrlPayStorage,
+    PayCallbackResponse, PayResponseExtras, PayerData,
+};
 use crate::logging::LOGGING_KEY;
+use crate::paymentrequest::PaymentRequest;
+use crate::peermanager::{feature_names, try_parse_addr_string};
 use crate::redshift::{RedshiftManager, RedshiftStatus, RedshiftStorage};
-use crate::storage::{MutinyStorage, KEYCHAIN_STORE_KEY};
+use crate::rescue::{
+    default_rescue_paths, sweep_external_key, sweep_external_mnemonic, RescuePath, RescueResult,
+};
+use crate::scb::StaticChannelBackup;
+use crate::signing::{verify_scoped_message, SigningScope};
+use crate::storage::{keychain_store_key, MutinyStorage, MNEMONIC_KEY, WATCH_ONLY_KEY};
+use crate::swaps::{SwapManager, SwapStorage};
 use crate::utils::sleep;
+use crate::vss::VssBackup;
+use crate::watch::{ExternalTransaction, WatchManager};
+
+#[cfg(target_arch = "wasm32")]
+use crate::networking::proxy::{get_websocket_proxy_urls, WsProxyFailover, WsProxyHealth};
+
 use crate::{
     auth::{AuthManager, AuthProfile},
     MutinyWalletConfig,
 };
 use crate::{
     chain::MutinyChain,
+    coordinator::{OperationCoordinator, Resource},
     error::MutinyError,
     esplora::EsploraSyncClient,
+    esplora_failover::{EsploraEndpointHealth, FailoverEsploraClient},
     fees::MutinyFeeEstimator,
-    gossip, keymanager,
+    gossip,
+    gossip::{NetworkGraphSize, ReconnectPolicy},
+    keymanager,
+    keymanager::lightning_derivation_path,
     logging::MutinyLogger,
-    lspclient::LspClient,
-    node::{Node, ProbScorer, PubkeyConnectionInfo, RapidGossipSync},
-    onchain::get_esplora_url,
-    onchain::OnChainWallet,
+    lspclient::{LspClient, LspFeeQuote, LspOrder, LspSelection},
+    node::{Node, ProbScorer, PubkeyConnectionInfo, RapidGossipSync, DEFAULT_INVOICE_EXPIRY_SECS},
+    notifier::{EventNotifier, MutinyEvent},
+    onchain::get_esplora_urls,
+    onchain::{OnChainWallet, RevealedAddresses, DEFAULT_STOP_GAP},
+    onionmessage::{CustomOnionMessage, MutinyOnionMessageHandler, OnionMessageHandler},
+    peermessage::{CustomPeerMessage, MutinyCustomMessageHandler, PeerMessageHandler},
+    pricing::{
+        closest_price, history_range, BitcoinPriceCache, FiatCurrency, FiatValue, HistoricalPrice,
+        HistoricalPriceStorage, PriceConfig,
+    },
+    syncstatus::{SyncStage, SyncStatus, SyncStatusTracker},
     utils,
 };
 use bdk::chain::{BlockId, ConfirmationTime};
 use bdk::{wallet::AddressIndex, LocalUtxo};
-use bdk_esplora::esplora_client::AsyncClient;
 use bip39::Mnemonic;
 use bitcoin::blockdata::script;
 use bitcoin::hashes::hex::{FromHex, ToHex};
 use bitcoin::hashes::{sha256, Hash};
+use bitcoin::psbt::PartiallySignedTransaction;
 use bitcoin::secp256k1::{rand, PublicKey};
-use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey};
 use bitcoin::{Address, Network, OutPoint, Transaction, Txid};
-use core::time::Duration;
-use futures::{future::join_all, lock::Mutex};
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::{future::join_all, lock::Mutex, pin_mut, select, FutureExt, StreamExt};
 use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
-use lightning::chain::channelmonitor::Balance;
+use lightning::chain::channelmonitor::{Balance, LATENCY_GRACE_PERIOD_BLOCKS};
 use lightning::chain::keysinterface::{NodeSigner, Recipient};
 use lightning::chain::Confirm;
 use lightning::events::ClosureReason;
-use lightning::ln::channelmanager::{ChannelDetails, PhantomRouteHints};
+use lightning::ln::channelmanager::{ChannelDetails, PhantomRouteHints, MIN_CLTV_EXPIRY_DELTA};
+use lightning::ln::msgs::NetAddress;
+use lightning::ln::script::ShutdownScript;
 use lightning::ln::PaymentHash;
 use lightning::routing::gossip::NodeId;
+use lightning::routing::router::DEFAULT_MAX_PATH_COUNT;
+use lightning::util::config::ChannelConfig;
 use lightning::util::logger::*;
+use lightning::util::ser::Writeable;
 use lightning::{log_debug, log_error, log_info, log_warn};
 use lightning_invoice::{Invoice, InvoiceDescription};
 use lnurl::lnurl::LnUrl;
 use lnurl::{AsyncClient as LnUrlClient, LnUrlResponse, Response};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use url::Url;
 use uuid::Uuid;
 
-const BITCOIN_PRICE_CACHE_SEC: u64 = 300;
+/// The most withdrawals made from a single LNURL when draining it
+const MAX_LNURL_DRAIN_WITHDRAWALS: usize = 25;
+
+/// How long to wait for a LNURL service to pay our invoice, in seconds
+const LNURL_WITHDRAW_TIMEOUT_SECS: u64 = 60;
+
+/// How many times to check for pending claims, half a second apart, before stopping anyway
+const MAX_CLAIM_WAIT_ATTEMPTS: usize = 20;
+
+/// How many blocks, about a day, before an outgoing HTLC would force close its
+/// channel that we start warning about it
+const HTLC_EXPIRY_WARNING_BLOCKS: u32 = 144;
+
+/// For how long after a payment or transaction happened the current price of bitcoin
+/// is recorded for it after our first sync, in seconds. Older ones are filled in from the price history.
+const RECENT_ACTIVITY_SECS: u64 = 60 * 60;
+
+/// How far from when a payment happened a price from the price history can be, in seconds,
+/// the history only has daily prices far enough back
+const HISTORICAL_PRICE_MAX_DISTANCE_SECS: u64 = 24 * 60 * 60;
+
+/// How often we rebroadcast unconfirmed transactions that fell out of the mempool, in seconds
+const REBROADCAST_INTERVAL_SECS: u64 = 10 * 60;
+
+/// How many confirmations a transaction needs to be considered safe, unless the user set it
+pub const DEFAULT_SAFE_CONFIRMATIONS: u32 = 6;
 
 // This is the NodeStorage object saved to the DB
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct NodeStorage {
     pub nodes: HashMap<String, NodeIndex>,
 }
 
+/// The smallest on-chain output that is not considered dust
+pub(crate) const DUST_LIMIT_SATS: u64 = 546;
+
+/// The fees a channel charges for forwarding payments, set with
+/// [NodeManager::update_channel_config]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelFeePolicy {
+    pub base_fee_msat: u32,
+    /// The fee per millionth of the forwarded amount
+    pub proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+}
+
+impl ChannelFeePolicy {
+    /// Returns the config with this policy's fees and CLTV delta
+    pub(crate) fn apply(&self, config: ChannelConfig) -> ChannelConfig {
+        ChannelConfig {
+            forwarding_fee_base_msat: self.base_fee_msat,
+            forwarding_fee_proportional_millionths: self.proportional_millionths,
+            cltv_expiry_delta: self.cltv_expiry_delta,
+            ..config
+        }
+    }
+}
+
+/// How our nodes present themselves in the public network graph, set with
+/// [NodeManager::set_node_announcement_config]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeAnnouncementConfig {
+    /// The node alias, at most 32 bytes
+    pub alias: String,
+    /// Hex encoded RGB color, ie `ff9900`
+    pub color: String,
+    /// The public `ip:port` addresses our nodes can be reached at. A bare `ip` is
+    /// announced with the port the node accepts inbound connections on.
+    pub addresses: Vec<String>,
+}
+
+impl NodeAnnouncementConfig {
+    /// Checks that the config can be put in a node announcement
+    pub(crate) fn validate(&self) -> Result<(), MutinyError> {
+        self.alias_bytes()?;
+        self.rgb()?;
+        for address in self.addresses.iter() {
+            if address.parse::<IpAddr>().is_err() && address.parse::<SocketAddr>().is_err() {
+                return Err(MutinyError::InvalidArgumentsError);
+            }
+        }
+        Ok(())
+    }
+
+    /// The alias zero padded to 32 bytes
+    pub(crate) fn alias_bytes(&self) -> Result<[u8; 32], MutinyError> {
+        let bytes = self.alias.as_bytes();
+        if bytes.len() > 32 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        let mut alias = [0u8; 32];
+        alias[..bytes.len()].copy_from_slice(bytes);
+        Ok(alias)
+    }
+
+    pub(crate) fn rgb(&self) -> Result<[u8; 3], MutinyError> {
+        let bytes: Vec<u8> = FromHex::from_hex(self.color.trim_start_matches('#'))
+            .map_err(|_| MutinyError::InvalidArgumentsError)?;
+        bytes
+            .try_into()
+            .map_err(|_| MutinyError::InvalidArgumentsError)
+    }
+
+    /// The addresses to announce, bare ips get the given listening port or are skipped
+    /// if the node doesn't listen.
+    pub(crate) fn net_addresses(
+        &self,
+        listen_port: Option<u16>,
+    ) -> Result<Vec<NetAddress>, MutinyError> {
+        self.addresses
+            .iter()
+            .filter_map(|a| match (a.parse::<IpAddr>(), listen_port) {
+                (Ok(ip), Some(port)) => Some(SocketAddr::new(ip, port).to_string()),
+                (Ok(_), None) => None,
+                (Err(_), _) => Some(a.clone()),
+            })
+            .map(|a| {
+                try_parse_addr_string(&a)
+                    .1
+                    .ok_or(MutinyError::InvalidArgumentsError)
+            })
+            .collect()
+    }
+}
+
+/// Which inbound channels our nodes accept, set with [NodeManager::set_channel_acceptance_policy].
+/// Channels we asked for, from our LSPs or with LNURL-channel, are always accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChannelAcceptancePolicy {
+    /// The smallest channel accepted, in sats
+    pub min_channel_size_sats: Option<u64>,
+    /// Only these peers can open channels to us, anyone can when not set
+    pub allowed_peers: Option<Vec<PublicKey>>,
+    /// The most channels a node can have, pending ones included
+    pub max_channels: Option<usize>,
+    /// Only accept channels that let us spend our whole balance, without a reserve
+    #[serde(default)]
+    pub require_zero_reserve: bool,
+}
+
+impl ChannelAcceptancePolicy {
+    /// Returns why the channel request breaks the policy, if it does.
+    /// `num_channels` is how many other channels the node has.
+    pub(crate) fn check(
+        &self,
+        request: &ChannelRequest,
+        num_channels: usize,
+    ) -> Result<(), String> {
+        if let Some(min) = self.min_channel_size_sats {
+            if request.funding_satoshis < min {
+                return Err(format!(
+                    "channel of {} sats is below the minimum of {min} sats",
+                    request.funding_satoshis
+                ));
+            }
+        }
+        if let Some(allowed) = self.allowed_peers.as_ref() {
+            if !allowed.contains(&request.counterparty) {
+                return Err(format!(
+                    "{} is not allowed to open channels",
+                    request.counterparty
+                ));
+            }
+        }
+        if let Some(max) = self.max_channels {
+            if num_channels >= max {
+                return Err(format!("node already has {num_channels} channels"));
+            }
+        }
+        if self.require_zero_reserve && request.our_reserve_sats != Some(0) {
+            return Err("peer requires a channel reserve".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// User settings for which inbound lightning payments will be accepted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InboundPaymentConfig {
+    /// The smallest HTLC our channels will accept, in millisatoshis.
+    /// This only applies to channels opened after it is set.
+    pub min_htlc_msat: u64,
+    /// Whether to accept payments smaller than the dust limit,
+    /// these cost storage and clutter the activity list.
+    pub accept_dust_payments: bool,
+}
+
+impl Default for InboundPaymentConfig {
+    fn default() -> Self {
+        Self {
+            min_htlc_msat: 1,
+            accept_dust_payments: true,
+        }
+    }
+}
+
+impl InboundPaymentConfig {
+    /// The smallest payment we will claim, in millisatoshis
+    pub fn min_payment_msat(&self) -> u64 {
+        if self.accept_dust_payments {
+            self.min_htlc_msat
+        } else {
+            self.min_htlc_msat.max(DUST_LIMIT_SATS * 1_000)
+        }
+    }
+}
+
+/// The derivation paths of the wallet's keys, for restoring the seed in other software
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DerivationPaths {
+    pub account_index: u32,
+    /// The BIP86 account new addresses come from,
+    /// None if the on-chain keys are not derived from the seed
+    pub onchain: Option<String>,
+    /// The BIP86 accounts we rotated away from, see [NodeManager::rotate_onchain_keys]
+    pub retired_onchain: Vec<String>,
+    /// The path each node's keys are derived from, by node pubkey
+    pub lightning: Vec<(PublicKey, String)>,
+}
+
 // This is the NodeIndex reference that is saved to the DB
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct NodeIndex {
     pub child_index: u32,
     pub lsp: Option<String>,
@@ -101,13 +362,61 @@ pub struct MutinyInvoice {
     pub payment_hash: sha256::Hash,
     pub preimage: Option<String>,
     pub payee_pubkey: Option<PublicKey>,
+    /// The amount in sats, rounded down
     pub amount_sats: Option<u64>,
+    /// The exact amount in millisatoshis
+    #[serde(default)]
+    pub amount_msats: Option<u64>,
     pub expire: u64,
     pub paid: bool,
+    /// The fees paid in sats, rounded up so sub-sat fees still show
     pub fees_paid: Option<u64>,
+    /// The exact fees paid in millisatoshis
+    #[serde(default)]
+    pub fees_paid_msats: Option<u64>,
     pub inbound: bool,
     pub labels: Vec<String>,
     pub last_updated: u64,
+    /// If the invoice was created with cached route hints because our
+    /// peers or LSP were unreachable, it may not be payable.
+    #[serde(default)]
+    pub degraded_hints: bool,
+    /// The LSP that was chosen to wrap the invoice, and why
+    #[serde(default)]
+    pub lsp_selection: Option<LspSelection>,
+    /// The parts an outgoing payment was split into, once they arrived
+    #[serde(default)]
+    pub parts: Vec<PaymentPart>,
+    /// The preimage a LNURL-pay service confirmed it was paid with,
+    /// through its LNURL-verify url
+    #[serde(default)]
+    pub lnurl_verified_preimage: Option<String>,
+    /// The private memo the user wrote for this payment
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// The price of bitcoin when the payment was made, if it was recorded
+    #[serde(default)]
+    pub fiat_price_at_time: Option<HistoricalPrice>,
+}
+
+/// What happened to a lightning payment
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentStatus {
+    /// Waiting to be paid, or still in flight
+    Pending,
+    Succeeded,
+    Failed,
+    /// An invoice that expired before it was paid
+    Expired,
+}
+
+/// A lightning payment with its status, from [NodeManager::list_payments]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MutinyPayment {
+    pub invoice: MutinyInvoice,
+    pub status: PaymentStatus,
+    /// Why the payment failed, or why a pending payment timed out
+    pub failure_reason: Option<PaymentFailureReason>,
 }
 
 impl From<Invoice> for MutinyInvoice {
@@ -128,7 +437,7 @@ impl From<Invoice> for MutinyInvoice {
 
         let payment_hash = value.payment_hash().to_owned();
         let payee_pubkey = value.payee_pub_key().map(|p| p.to_owned());
-        let amount_sats = value.amount_milli_satoshis().map(|m| m / 1000);
+        let amount_msats = value.amount_milli_satoshis();
 
         MutinyInvoice {
             bolt11: Some(value),
@@ -136,13 +445,21 @@ impl From<Invoice> for MutinyInvoice {
             payment_hash,
             preimage: None,
             payee_pubkey,
-            amount_sats,
+            amount_sats: amount_msats.map(utils::msats_to_sats),
+            amount_msats,
             expire: expiry,
             paid: false,
             fees_paid: None,
+            fees_paid_msats: None,
             inbound: true,
             labels: vec![],
             last_updated: timestamp,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
         }
     }
 }
@@ -157,31 +474,30 @@ impl MutinyInvoice {
         match i.bolt11 {
             Some(invoice) => {
                 // Construct an invoice from a bolt11, easy
-                let amount_sats = if let Some(inv_amt) = invoice.amount_milli_satoshis() {
-                    if inv_amt == 0 {
-                        i.amt_msat.0.map(|a| a / 1_000)
-                    } else {
-                        Some(inv_amt / 1_000)
-                    }
-                } else {
-                    i.amt_msat.0.map(|a| a / 1_000)
+                let amount_msats = match invoice.amount_milli_satoshis() {
+                    Some(inv_amt) if inv_amt != 0 => Some(inv_amt),
+                    _ => i.amt_msat.0,
                 };
                 Ok(MutinyInvoice {
                     inbound,
                     last_updated: i.last_update,
                     paid: i.status == HTLCStatus::Succeeded,
                     labels,
-                    amount_sats,
+                    amount_sats: amount_msats.map(utils::msats_to_sats),
+                    amount_msats,
                     payee_pubkey: i.payee_pubkey,
                     preimage: i.preimage.map(|p| p.to_hex()),
-                    fees_paid: i.fee_paid_msat.map(|f| f / 1_000),
+                    fees_paid: i.fee_paid_msat.map(utils::msats_to_sats_round_up),
+                    fees_paid_msats: i.fee_paid_msat,
+                    lsp_selection: i.lsp_selection,
+                    parts: i.parts,
                     ..invoice.into()
                 })
             }
             None => {
                 let paid = i.status == HTLCStatus::Succeeded;
-                let amount_sats: Option<u64> = i.amt_msat.0.map(|s| s / 1_000);
-                let fees_paid = i.fee_paid_msat.map(|f| f / 1_000);
+                let amount_msats = i.amt_msat.0;
+                let fees_paid_msats = i.fee_paid_msat;
                 let preimage = i.preimage.map(|p| p.to_hex());
                 let payment_hash = sha256::Hash::from_inner(payment_hash.0);
                 let invoice = MutinyInvoice {
@@ -190,18 +506,38 @@ impl MutinyInvoice {
                     payment_hash,
                     preimage,
                     payee_pubkey: i.payee_pubkey,
-                    amount_sats,
+                    amount_sats: amount_msats.map(utils::msats_to_sats),
+                    amount_msats,
                     expire: i.last_update,
                     paid,
-                    fees_paid,
+                    fees_paid: fees_paid_msats.map(utils::msats_to_sats_round_up),
+                    fees_paid_msats,
                     inbound,
                     labels,
                     last_updated: i.last_update,
+                    degraded_hints: false,
+                    lsp_selection: i.lsp_selection,
+                    parts: i.parts,
+                    lnurl_verified_preimage: None,
+                    memo: None,
+                    fiat_price_at_time: None,
                 };
                 Ok(invoice)
             }
         }
     }
+
+    /// The amount in millisatoshis, invoices saved before msats
+    /// were tracked fall back to their amount in sats.
+    pub(crate) fn amount_in_msats(&self) -> Option<u64> {
+        self.amount_msats.or(self.amount_sats.map(|a| a * 1_000))
+    }
+
+    /// The fees paid in millisatoshis, invoices saved before msats
+    /// were tracked fall back to their fees in sats.
+    pub(crate) fn fees_paid_in_msats(&self) -> Option<u64> {
+        self.fees_paid_msats.or(self.fees_paid.map(|f| f * 1_000))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -212,6 +548,16 @@ pub struct MutinyPeer {
     pub color: Option<String>,
     pub label: Option<String>,
     pub is_connected: bool,
+    /// How our nodes reconnect to the peer
+    pub reconnect_policy: ReconnectPolicy,
+    /// The features the peer told us it supports, empty if we haven't connected to it
+    pub features: Vec<String>,
+    /// How long we have been connected to the peer, in seconds
+    pub uptime_secs: Option<u64>,
+    /// How long the peer took to answer our last ping with a pong, in milliseconds
+    pub latency_ms: Option<u64>,
+    /// How many channels our nodes have with the peer
+    pub num_channels: usize,
 }
 
 impl PartialOrd for MutinyPeer {
@@ -233,7 +579,10 @@ impl Ord for MutinyPeer {
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct MutinyChannel {
     pub user_chan_id: String,
+    /// Our balance in sats, rounded down
     pub balance: u64,
+    /// Our exact balance in millisatoshis
+    pub balance_msats: u64,
     pub size: u64,
     pub reserve: u64,
     pub outpoint: Option<OutPoint>,
@@ -246,7 +595,8 @@ impl From<&ChannelDetails> for MutinyChannel {
     fn from(c: &ChannelDetails) -> Self {
         MutinyChannel {
             user_chan_id: c.user_channel_id.to_hex(),
-            balance: c.outbound_capacity_msat / 1_000,
+            balance: utils::msats_to_sats(c.outbound_capacity_msat),
+            balance_msats: c.outbound_capacity_msat,
             size: c.channel_value_satoshis,
             reserve: c.unspendable_punishment_reserve.unwrap_or(0),
             outpoint: c.funding_txo.map(|f| f.into_bitcoin_outpoint()),
@@ -257,6 +607,356 @@ impl From<&ChannelDetails> for MutinyChannel {
     }
 }
 
+/// How far along the open of a channel is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingChannelStage {
+    /// Agreeing on the channel with the peer, there is no funding transaction yet
+    Negotiating,
+    /// The funding transaction was made and is waiting for its first confirmation
+    FundingBroadcast,
+    /// The funding transaction is confirmed, but not deep enough yet
+    Confirming,
+    /// The funding transaction is deep enough, waiting on the peer to say the channel is ready
+    AwaitingChannelReady,
+}
+
+impl PendingChannelStage {
+    fn new(has_funding_tx: bool, confirmations: u32, confirmations_required: u32) -> Self {
+        if !has_funding_tx {
+            PendingChannelStage::Negotiating
+        } else if confirmations == 0 && confirmations_required > 0 {
+            PendingChannelStage::FundingBroadcast
+        } else if confirmations < confirmations_required {
+            PendingChannelStage::Confirming
+        } else {
+            PendingChannelStage::AwaitingChannelReady
+        }
+    }
+}
+
+/// A channel that is being opened and can't be used yet, see [NodeManager::list_pending_channels]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingChannel {
+    /// The hex channel id, the same one channel events use
+    pub channel_id: String,
+    pub user_chan_id: String,
+    pub peer: PublicKey,
+    pub size: u64,
+    /// If we are the one opening the channel
+    pub is_outbound: bool,
+    pub funding_txid: Option<Txid>,
+    pub confirmations: u32,
+    pub confirmations_required: u32,
+    pub stage: PendingChannelStage,
+}
+
+impl From<&ChannelDetails> for PendingChannel {
+    fn from(c: &ChannelDetails) -> Self {
+        let confirmations = c.confirmations.unwrap_or(0);
+        let confirmations_required = c.confirmations_required.unwrap_or(0);
+        PendingChannel {
+            channel_id: c.channel_id.to_hex(),
+            user_chan_id: c.user_channel_id.to_hex(),
+            peer: c.counterparty.node_id,
+            size: c.channel_value_satoshis,
+            is_outbound: c.is_outbound,
+            funding_txid: c.funding_txo.map(|f| f.txid),
+            confirmations,
+            confirmations_required,
+            stage: PendingChannelStage::new(
+                c.funding_txo.is_some(),
+                confirmations,
+                confirmations_required,
+            ),
+        }
+    }
+}
+
+/// The [MutinyEvent::ChannelConfirmed] events for the pending channels whose funding
+/// transaction got confirmations since the last check, keyed by channel id.
+fn channel_confirmation_events(
+    previous: &HashMap<String, u32>,
+    channels: &[PendingChannel],
+) -> Vec<MutinyEvent> {
+    channels
+        .iter()
+        .filter(|c| c.confirmations > 0)
+        .filter(|c| previous.get(&c.channel_id) != Some(&c.confirmations))
+        .map(|c| MutinyEvent::ChannelConfirmed {
+            channel_id: c.channel_id.clone(),
+            confirmations: c.confirmations,
+            confirmations_required: c.confirmations_required,
+        })
+        .collect()
+}
+
+/// A single claimable balance tracked by a channel monitor.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MonitorBalance {
+    /// The kind of claim, named after LDK's [Balance] variant
+    pub kind: String,
+    pub amount_sats: u64,
+    /// The height at which this balance matures or times out, if any
+    pub height: Option<u32>,
+}
+
+impl From<Balance> for MonitorBalance {
+    fn from(b: Balance) -> Self {
+        let (kind, amount_sats, height) = match b {
+            Balance::ClaimableOnChannelClose {
+                claimable_amount_satoshis,
+            } => ("ClaimableOnChannelClose", claimable_amount_satoshis, None),
+            Balance::ClaimableAwaitingConfirmations {
+                claimable_amount_satoshis,
+                confirmation_height,
+            } => (
+                "ClaimableAwaitingConfirmations",
+                claimable_amount_satoshis,
+                Some(confirmation_height),
+            ),
+            Balance::ContentiousClaimable {
+                claimable_amount_satoshis,
+                timeout_height,
+            } => (
+                "ContentiousClaimable",
+                claimable_amount_satoshis,
+                Some(timeout_height),
+            ),
+            Balance::MaybeTimeoutClaimableHTLC {
+                claimable_amount_satoshis,
+                claimable_height,
+            } => (
+                "MaybeTimeoutClaimableHTLC",
+                claimable_amount_satoshis,
+                Some(claimable_height),
+            ),
+            Balance::MaybePreimageClaimableHTLC {
+                claimable_amount_satoshis,
+                expiry_height,
+            } => (
+                "MaybePreimageClaimableHTLC",
+                claimable_amount_satoshis,
+                Some(expiry_height),
+            ),
+            Balance::CounterpartyRevokedOutputClaimable {
+                claimable_amount_satoshis,
+            } => (
+                "CounterpartyRevokedOutputClaimable",
+                claimable_amount_satoshis,
+                None,
+            ),
+        };
+
+        MonitorBalance {
+            kind: kind.to_string(),
+            amount_sats,
+            height,
+        }
+    }
+}
+
+/// Funds from a closed channel that are still being claimed on-chain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingClaim {
+    pub node: PublicKey,
+    pub outpoint: OutPoint,
+    /// The kind of claim, named after LDK's [Balance] variant
+    pub kind: String,
+    pub amount_sats: u64,
+    /// How many blocks until the funds can be swept back to the wallet, if that is known.
+    /// Zero means they are being claimed now.
+    pub blocks_until_spendable: Option<u32>,
+}
+
+impl PendingClaim {
+    fn new(node: PublicKey, outpoint: OutPoint, balance: Balance, current_height: u32) -> Self {
+        let blocks_until_spendable = match balance {
+            Balance::ClaimableAwaitingConfirmations {
+                confirmation_height,
+                ..
+            } => Some(confirmation_height.saturating_sub(current_height)),
+            Balance::MaybeTimeoutClaimableHTLC {
+                claimable_height, ..
+            } => Some(claimable_height.saturating_sub(current_height)),
+            // we have the preimage and claim these as soon as we can
+            Balance::ContentiousClaimable { .. }
+            | Balance::CounterpartyRevokedOutputClaimable { .. } => Some(0),
+            // these depend on the commitment transaction confirming or on a preimage
+            Balance::ClaimableOnChannelClose { .. }
+            | Balance::MaybePreimageClaimableHTLC { .. } => None,
+        };
+        let balance = MonitorBalance::from(balance);
+
+        PendingClaim {
+            node,
+            outpoint,
+            kind: balance.kind,
+            amount_sats: balance.amount_sats,
+            blocks_until_spendable,
+        }
+    }
+}
+
+/// An outgoing HTLC that hasn't been resolved and will force close its channel if it expires
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExpiringHtlc {
+    pub node: PublicKey,
+    pub channel_id: String,
+    pub counterparty: PublicKey,
+    pub amount_sats: u64,
+    pub cltv_expiry: u32,
+    /// How many blocks are left before the channel is force closed to claim the HTLC back
+    pub blocks_until_force_close: u32,
+}
+
+/// Outgoing HTLCs are claimed back on chain a few blocks after they expire
+fn blocks_until_force_close(cltv_expiry: u32, current_height: u32) -> u32 {
+    (cltv_expiry + LATENCY_GRACE_PERIOD_BLOCKS).saturating_sub(current_height)
+}
+
+/// What we knew about a wallet transaction at the last check, see
+/// [NodeManager::check_transaction_confirmations]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WatchedTransaction {
+    confirmations: u32,
+    /// If it's unconfirmed and our esplora server no longer knows about it
+    evicted: bool,
+}
+
+fn tx_confirmations(confirmation_time: &ConfirmationTime, current_height: u32) -> u32 {
+    match confirmation_time {
+        ConfirmationTime::Confirmed { height, .. } => {
+            current_height.saturating_sub(*height).saturating_add(1)
+        }
+        ConfirmationTime::Unconfirmed => 0,
+    }
+}
+
+/// The events for the transactions that got their first or safe confirmation, or that
+/// went missing while unconfirmed since the last check. Transactions we see for the first
+/// time are only announced if they are not safe yet, so restoring a wallet isn't noisy.
+fn transaction_confirmation_events(
+    previous: &HashMap<Txid, WatchedTransaction>,
+    current: &HashMap<Txid, u32>,
+    safe_confirmations: u32,
+) -> Vec<MutinyEvent> {
+    // with a single safe confirmation both are the same event
+    let mut thresholds = vec![1, safe_confirmations];
+    thresholds.sort_unstable();
+    thresholds.dedup();
+
+    let mut events = vec![];
+    for (txid, confirmations) in current {
+        let previous_confirmations = match previous.get(txid) {
+            Some(watched) => watched.confirmations,
+            None if *confirmations >= safe_confirmations => continue,
+            None => 0,
+        };
+        for threshold in thresholds.iter() {
+            if previous_confirmations < *threshold && confirmations >= threshold {
+                events.push(MutinyEvent::TransactionConfirmed {
+                    txid: *txid,
+                    confirmations: *threshold,
+                });
+            }
+        }
+    }
+
+    // an unconfirmed transaction that disappeared was replaced by a conflicting one
+    for (txid, watched) in previous {
+        if watched.confirmations == 0 && !current.contains_key(txid) {
+            events.push(MutinyEvent::TransactionReplaced { txid: *txid });
+        }
+    }
+
+    events
+}
+
+/// What to expect after force closing a channel with [NodeManager::force_close_channel]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ForceCloseEstimate {
+    pub outpoint: OutPoint,
+    /// Our balance in the channel, on-chain fees are taken from this when it is swept
+    pub amount_sats: u64,
+    /// How many blocks our funds are timelocked for after the commitment transaction confirms
+    pub to_self_delay: u16,
+    /// Roughly how long until the funds are swept back to the wallet, in seconds,
+    /// assuming the commitment transaction confirms in the next block
+    pub sweep_eta_secs: u64,
+}
+
+/// Blocks are found every 10 minutes on average,
+/// the commitment transaction needs one before the timelock starts
+fn force_close_sweep_eta_secs(to_self_delay: u16) -> u64 {
+    (to_self_delay as u64 + 1) * 600
+}
+
+/// A human-readable summary of a channel monitor's state.
+/// Useful for support and debugging without having to decode the raw monitor bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMonitorSummary {
+    pub node: PublicKey,
+    pub outpoint: OutPoint,
+    pub counterparty: Option<PublicKey>,
+    /// The latest update id applied to the monitor, this increases with every commitment update
+    pub latest_update_id: u64,
+    pub best_block_height: u32,
+    /// Balances that are still pending claims on-chain
+    pub balances: Vec<MonitorBalance>,
+}
+
+impl ChannelMonitorSummary {
+    /// Formats the summary as CSV, one row per pending claim.
+    /// A monitor without any pending claims will output a single row with an empty claim.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "node,outpoint,counterparty,latest_update_id,best_block_height,claim,amount_sats,height\n",
+        );
+
+        let prefix = format!(
+            "{},{},{},{},{}",
+            self.node,
+            self.outpoint,
+            self.counterparty.map(|c| c.to_string()).unwrap_or_default(),
+            self.latest_update_id,
+            self.best_block_height,
+        );
+
+        if self.balances.is_empty() {
+            csv.push_str(&format!("{prefix},,,\n"));
+        }
+
+        for balance in self.balances.iter() {
+            csv.push_str(&format!(
+                "{prefix},{},{},{}\n",
+                balance.kind,
+                balance.amount_sats,
+                balance.height.map(|h| h.to_string()).unwrap_or_default(),
+            ));
+        }
+
+        csv
+    }
+}
+
+/// A record of how a channel closed, kept so old closes can be looked into
+/// without digging through the logs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChannelForensics {
+    pub channel_id: String,
+    pub peer: Option<PublicKey>,
+    pub funding_txo: Option<OutPoint>,
+    pub reason: String,
+    /// Unix timestamp, in seconds, of when the channel closed
+    pub closed_at: u64,
+    /// Transactions seen on chain that spent from the channel, such as the commitment transaction
+    pub commitment_txids: Vec<Txid>,
+    /// The balances the channel monitor was still tracking when the channel closed
+    pub balances_at_close: Vec<MonitorBalance>,
+    /// Transactions we broadcast to sweep our funds from the channel back into the wallet
+    pub sweep_txids: Vec<Txid>,
+}
+
 /// A wallet transaction
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct TransactionDetails {
@@ -277,6 +977,12 @@ pub struct TransactionDetails {
     pub confirmation_time: ConfirmationTime,
     /// Labels associated with this transaction
     pub labels: Vec<String>,
+    /// The private memo the user wrote for this transaction
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// The price of bitcoin when the transaction confirmed, if it was recorded
+    #[serde(default)]
+    pub fiat_price_at_time: Option<HistoricalPrice>,
 }
 
 impl PartialOrd for TransactionDetails {
@@ -303,6 +1009,61 @@ impl From<bdk::TransactionDetails> for TransactionDetails {
             fee: t.fee,
             confirmation_time: t.confirmation_time,
             labels: vec![],
+            memo: None,
+            fiat_price_at_time: None,
+        }
+    }
+}
+
+/// Why a channel closed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChannelClosureKind {
+    /// Both sides agreed to close the channel
+    Cooperative,
+    /// We force closed the channel
+    ForceClosedByUs,
+    /// The peer force closed the channel
+    ForceClosedByPeer,
+    /// A commitment transaction confirmed on chain without either side asking to
+    /// force close, like an old revoked one the peer broadcast
+    CommitmentConfirmed,
+    /// The funding transaction didn't confirm in time
+    FundingTimedOut,
+    /// The peer disconnected before the channel was funded
+    DisconnectedPeer,
+    /// The channel broke the protocol or failed to update
+    ProcessingError,
+    /// The channel manager was older than the channel's monitor, so it was force closed
+    OutdatedChannelManager,
+    /// Closures saved before the reason was recorded
+    #[default]
+    Unknown,
+}
+
+impl ChannelClosureKind {
+    /// If closing the channel spent its funding output on chain
+    pub fn has_closing_tx(&self) -> bool {
+        matches!(
+            self,
+            ChannelClosureKind::Cooperative
+                | ChannelClosureKind::ForceClosedByUs
+                | ChannelClosureKind::ForceClosedByPeer
+                | ChannelClosureKind::CommitmentConfirmed
+        )
+    }
+}
+
+impl From<&ClosureReason> for ChannelClosureKind {
+    fn from(reason: &ClosureReason) -> Self {
+        match reason {
+            ClosureReason::CooperativeClosure => ChannelClosureKind::Cooperative,
+            ClosureReason::HolderForceClosed => ChannelClosureKind::ForceClosedByUs,
+            ClosureReason::CounterpartyForceClosed { .. } => ChannelClosureKind::ForceClosedByPeer,
+            ClosureReason::CommitmentTxConfirmed => ChannelClosureKind::CommitmentConfirmed,
+            ClosureReason::FundingTimedOut => ChannelClosureKind::FundingTimedOut,
+            ClosureReason::DisconnectedPeer => ChannelClosureKind::DisconnectedPeer,
+            ClosureReason::ProcessingError { .. } => ChannelClosureKind::ProcessingError,
+            ClosureReason::OutdatedChannelManager => ChannelClosureKind::OutdatedChannelManager,
         }
     }
 }
@@ -313,7 +1074,18 @@ pub struct ChannelClosure {
     pub user_channel_id: Option<[u8; 16]>,
     pub channel_id: Option<[u8; 32]>,
     pub node_id: Option<PublicKey>,
+    /// Why the channel closed
+    #[serde(default)]
+    pub kind: ChannelClosureKind,
+    /// LDK's description of why the channel closed, with the peer's message if it sent one
     pub reason: String,
+    /// The transaction that spent the funding output, once it is seen on chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closing_txid: Option<Txid>,
+    /// What came back to the wallet from the channel, in sats, once it is known.
+    /// Force closes add each sweep as it happens, before the sweep's fees.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swept_sats: Option<u64>,
     pub timestamp: u64,
 }
 
@@ -328,7 +1100,10 @@ impl ChannelClosure {
             user_channel_id: Some(user_channel_id.to_be_bytes()),
             channel_id: Some(channel_id),
             node_id,
+            kind: ChannelClosureKind::from(&reason),
             reason: reason.to_string(),
+            closing_txid: None,
+            swept_sats: None,
             timestamp: utils::now().as_secs(),
         }
     }
@@ -351,9 +1126,25 @@ pub enum ActivityItem {
     OnChain(TransactionDetails),
     Lightning(Box<MutinyInvoice>),
     ChannelClosed(ChannelClosure),
+    AggregatedLightning(Box<AggregatedPayments>),
+    /// A transaction of a watched third-party address or txid
+    External(ExternalTransaction),
 }
 
 impl ActivityItem {
+    /// A unique identifier for the item, this is used to hide items from the activity
+    pub fn id(&self) -> String {
+        match self {
+            ActivityItem::OnChain(t) => t.txid.to_hex(),
+            ActivityItem::Lightning(ln) => ln.payment_hash.to_hex(),
+            ActivityItem::ChannelClosed(c) => {
+                c.user_channel_id.map(|c| c.to_hex()).unwrap_or_default()
+            }
+            ActivityItem::AggregatedLightning(a) => a.id(),
+            ActivityItem::External(t) => t.id(),
+        }
+    }
+
     pub fn last_updated(&self) -> Option<u64> {
         match self {
             ActivityItem::OnChain(t) => match t.confirmation_time {
@@ -362,6 +1153,11 @@ impl ActivityItem {
             },
             ActivityItem::Lightning(i) => Some(i.last_updated),
             ActivityItem::ChannelClosed(c) => Some(c.timestamp),
+            ActivityItem::AggregatedLightning(a) => Some(a.last_updated),
+            ActivityItem::External(t) => match t.confirmation_time {
+                ConfirmationTime::Confirmed { time, .. } => Some(time),
+                ConfirmationTime::Unconfirmed => None,
+            },
         }
     }
 
@@ -370,6 +1166,18 @@ impl ActivityItem {
             ActivityItem::OnChain(t) => t.labels.clone(),
             ActivityItem::Lightning(i) => i.labels.clone(),
             ActivityItem::ChannelClosed(_) => vec![],
+            ActivityItem::AggregatedLightning(_) => vec![],
+            ActivityItem::External(t) => t.label.clone().into_iter().collect(),
+        }
+    }
+
+    pub fn memo(&self) -> Option<String> {
+        match self {
+            ActivityItem::OnChain(t) => t.memo.clone(),
+            ActivityItem::Lightning(i) => i.memo.clone(),
+            ActivityItem::ChannelClosed(_) => None,
+            ActivityItem::AggregatedLightning(_) => None,
+            ActivityItem::External(_) => None,
         }
     }
 
@@ -380,10 +1188,57 @@ impl ActivityItem {
             }
             ActivityItem::Lightning(_) => false,
             ActivityItem::ChannelClosed(_) => false,
+            ActivityItem::AggregatedLightning(_) => false,
+            ActivityItem::External(_) => false,
+        }
+    }
+
+    /// What the item was worth in fiat when it happened, if the price of bitcoin was recorded.
+    /// Aggregated payments are only valued if all of their payments are, in the same currency.
+    pub fn fiat_value_at_time(&self) -> Option<FiatValue> {
+        match self {
+            ActivityItem::OnChain(t) => t
+                .fiat_price_at_time
+                .map(|price| price.value_of(t.received.abs_diff(t.sent))),
+            ActivityItem::Lightning(i) => invoice_fiat_value(i),
+            ActivityItem::ChannelClosed(_) => None,
+            ActivityItem::AggregatedLightning(a) => {
+                let mut values = a.payments.iter().map(invoice_fiat_value);
+                let first = values.next()??;
+                values.try_fold(first, |total, value| {
+                    let value = value.filter(|v| v.currency == total.currency)?;
+                    Some(FiatValue {
+                        currency: total.currency,
+                        amount: total.amount + value.amount,
+                    })
+                })
+            }
+            ActivityItem::External(_) => None,
         }
     }
 }
 
+/// The ids and times of the payments and transactions without a recorded price that
+/// happened at or after `since`, a unix timestamp in seconds.
+/// Unconfirmed transactions are priced once they confirm.
+fn unpriced_activity(activity: &[ActivityItem], since: u64) -> Vec<(String, u64)> {
+    activity
+        .iter()
+        .filter(|item| match item {
+            ActivityItem::OnChain(t) => t.fiat_price_at_time.is_none(),
+            ActivityItem::Lightning(i) => i.fiat_price_at_time.is_none(),
+            _ => false,
+        })
+        .filter_map(|item| Some((item.id(), item.last_updated()?)))
+        .filter(|(_, time)| *time >= since)
+        .collect()
+}
+
+fn invoice_fiat_value(invoice: &MutinyInvoice) -> Option<FiatValue> {
+    let price = invoice.fiat_price_at_time?;
+    Some(price.value_of(invoice.amount_sats?))
+}
+
 impl PartialOrd for ActivityItem {
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
@@ -403,133 +1258,768 @@ impl Ord for ActivityItem {
     }
 }
 
-pub struct MutinyBalance {
-    pub confirmed: u64,
-    pub unconfirmed: u64,
-    pub lightning: u64,
-    pub force_close: u64,
-}
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
 
-pub struct LnUrlParams {
-    pub max: u64,
-    pub min: u64,
-    pub tag: String,
+/// Small lightning payments to or from the same node on the same day,
+/// grouped into a single activity item.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AggregatedPayments {
+    pub inbound: bool,
+    /// The node that was paid, this is unknown for inbound payments
+    pub counterparty: Option<PublicKey>,
+    /// The start of the day (UTC) the payments were made, as a unix timestamp
+    pub day: u64,
+    /// The total amount in sats, rounded down
+    pub total_sats: u64,
+    pub total_msats: u64,
+    /// The total fees in sats, rounded up
+    pub total_fees: u64,
+    pub total_fees_msats: u64,
+    pub last_updated: u64,
+    /// The individual payments, newest first
+    pub payments: Vec<MutinyInvoice>,
 }
 
-/// The [NodeManager] is the main entry point for interacting with the Mutiny Wallet.
-/// It is responsible for managing the on-chain wallet and the lightning nodes.
-///
-/// It can be used to create a new wallet, or to load an existing wallet.
-///
-/// It can be configured to use all different custom backend services, or to use the default
-/// services provided by Mutiny.
-pub struct NodeManager<S: MutinyStorage> {
-    pub(crate) stop: Arc<AtomicBool>,
-    mnemonic: Mnemonic,
-    network: Network,
-    #[cfg(target_arch = "wasm32")]
-    websocket_proxy_addr: String,
-    esplora: Arc<AsyncClient>,
-    wallet: Arc<OnChainWallet<S>>,
-    gossip_sync: Arc<RapidGossipSync>,
-    scorer: Arc<utils::Mutex<ProbScorer>>,
-    chain: Arc<MutinyChain<S>>,
-    fee_estimator: Arc<MutinyFeeEstimator<S>>,
-    pub(crate) storage: S,
-    pub(crate) node_storage: Mutex<NodeStorage>,
-    pub(crate) nodes: Arc<Mutex<HashMap<PublicKey, Arc<Node<S>>>>>,
-    auth: AuthManager<S>,
-    lnurl_client: LnUrlClient,
-    pub(crate) lsp_clients: Vec<LspClient>,
-    pub(crate) logger: Arc<MutinyLogger>,
-    bitcoin_price_cache: Arc<Mutex<Option<(f32, Duration)>>>,
+impl AggregatedPayments {
+    /// A stable identifier for the group
+    pub fn id(&self) -> String {
+        let direction = if self.inbound { "in" } else { "out" };
+        let counterparty = self
+            .counterparty
+            .map(|c| c.to_hex())
+            .unwrap_or_else(|| "unknown".to_string());
+        format!("{direction}-{counterparty}-{}", self.day)
+    }
 }
 
-impl<S: MutinyStorage> NodeManager<S> {
-    /// Returns if there is a saved wallet in storage.
-    /// This is checked by seeing if a mnemonic seed exists in storage.
-    pub fn has_node_manager(storage: S) -> bool {
-        storage.get_mnemonic().is_ok()
+/// Groups paid lightning payments below the threshold into [AggregatedPayments]
+/// per counterparty per day. Groups with a single payment are left as is.
+pub(crate) fn aggregate_dust_payments(
+    activity: Vec<ActivityItem>,
+    threshold_sats: u64,
+) -> Vec<ActivityItem> {
+    let mut groups: HashMap<(bool, Option<PublicKey>, u64), Vec<MutinyInvoice>> = HashMap::new();
+    let mut result = Vec::with_capacity(activity.len());
+    for item in activity {
+        match item {
+            ActivityItem::Lightning(ln)
+                if ln.paid && ln.amount_sats.is_some_and(|a| a < threshold_sats) =>
+            {
+                let counterparty = if ln.inbound { None } else { ln.payee_pubkey };
+                let day = ln.last_updated - ln.last_updated % SECONDS_PER_DAY;
+                groups
+                    .entry((ln.inbound, counterparty, day))
+                    .or_default()
+                    .push(*ln);
+            }
+            item => result.push(item),
+        }
     }
 
-    /// Creates a new [NodeManager] with the given parameters.
-    /// The mnemonic seed is read from storage, unless one is provided.
-    /// If no mnemonic is provided, a new one is generated and stored.
-    pub async fn new(c: MutinyWalletConfig, storage: S) -> Result<NodeManager<S>, MutinyError> {
-        let stop = Arc::new(AtomicBool::new(false));
+    for ((inbound, counterparty, day), mut payments) in groups {
+        if payments.len() == 1 {
+            result.push(ActivityItem::Lightning(Box::new(payments.remove(0))));
+            continue;
+        }
 
-        #[cfg(target_arch = "wasm32")]
-        let websocket_proxy_addr = c
-            .websocket_proxy_addr
-            .unwrap_or_else(|| String::from("wss://p.mutinywallet.com"));
+        payments.sort_by(|a, b| b.last_updated.cmp(&a.last_updated));
+        // sum the msats before rounding so sub-sat amounts add up
+        let total_msats = payments.iter().filter_map(|p| p.amount_in_msats()).sum();
+        let total_fees_msats = payments.iter().filter_map(|p| p.fees_paid_in_msats()).sum();
+        let aggregated = AggregatedPayments {
+            inbound,
+            counterparty,
+            day,
+            total_sats: utils::msats_to_sats(total_msats),
+            total_msats,
+            total_fees: utils::msats_to_sats_round_up(total_fees_msats),
+            total_fees_msats,
+            last_updated: payments[0].last_updated,
+            payments,
+        };
+        result.push(ActivityItem::AggregatedLightning(Box::new(aggregated)));
+    }
 
-        // todo we should eventually have default mainnet
-        let network: Network = c.network.unwrap_or(Network::Signet);
+    // Newest first
+    result.sort_by(|a, b| b.cmp(a));
+    result
+}
 
-        let mnemonic = match c.mnemonic {
-            Some(seed) => storage.insert_mnemonic(seed)?,
-            None => match storage.get_mnemonic() {
-                Ok(mnemonic) => mnemonic,
-                Err(_) => {
+/// The projected cost of a lightning payment, found without sending any HTLCs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PaymentEstimate {
+    pub amount_msats: u64,
+    pub fee_msats: u64,
+    /// The total CLTV delta of the longest path, funds may be locked for this many blocks
+    pub cltv_expiry_delta: u32,
+    pub num_paths: usize,
+}
+
+/// The expected routing fee of a lightning payment, found without sending any HTLCs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub amount_msats: u64,
+    /// The fee of the cheapest route
+    pub min_fee_msats: u64,
+    /// The fee if the payment has to be retried along another route
+    pub max_fee_msats: u64,
+    /// The number of hops on the longest path of the cheapest route
+    pub num_hops: usize,
+}
+
+/// A limit on the routing fees of a lightning payment.
+/// When both are set the lower one is used.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MaxFee {
+    pub max_fee_sats: Option<u64>,
+    /// Percent of the amount being sent, ie 1.0 is 1%
+    pub max_fee_percent: Option<f64>,
+}
+
+impl MaxFee {
+    /// The most that can be paid in fees when sending the given amount
+    pub fn limit_msat(&self, amount_msat: u64) -> u64 {
+        let sats = self.max_fee_sats.map(|s| s * 1_000);
+        let percent = self
+            .max_fee_percent
+            .map(|p| (amount_msat as f64 * p / 100.0) as u64);
+        match (sats, percent) {
+            (Some(sats), Some(percent)) => sats.min(percent),
+            (Some(limit), None) | (None, Some(limit)) => limit,
+            (None, None) => u64::MAX,
+        }
+    }
+}
+
+/// Optional settings for opening a channel
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct OpenChannelOptions {
+    /// Fee rate of the funding transaction in sat/vbyte, estimated when not set
+    pub fee_rate: Option<f32>,
+    /// Given to the peer when the channel opens, so the channel starts with inbound liquidity
+    pub push_msat: Option<u64>,
+    /// Random when not set
+    pub user_channel_id: Option<u128>,
+    /// Announced channels are gossiped to the network and can route payments
+    pub announce: bool,
+}
+
+/// Optional settings for paying a lightning invoice
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaymentOptions {
+    /// With a limit the payment fails with [MutinyError::FeeTooHigh] if no route
+    /// is within it, and it is only tried along one route
+    pub max_fee: Option<MaxFee>,
+    /// Funding outpoints of the channels the payment has to go out of,
+    /// it is then only tried along one route
+    pub outgoing_channels: Option<Vec<OutPoint>>,
+    /// Limits how the payment is split into parts, failed parts are still retried
+    /// unless a fee limit or outgoing channels are given too
+    pub mpp: Option<MppOptions>,
+}
+
+/// Controls how a lightning payment may be split into parts sent along different routes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MppOptions {
+    /// If false the payment is sent along a single route
+    pub allow_split: bool,
+    pub max_parts: Option<u8>,
+    /// The smallest part in sats, the router can't size the parts so this
+    /// limits the number of parts to the amount divided by it
+    pub min_part_sats: Option<u64>,
+}
+
+impl MppOptions {
+    /// The most routes the payment of the given amount may be split across
+    pub fn max_path_count(&self, amount_msat: u64) -> u8 {
+        if !self.allow_split {
+            return 1;
+        }
+        let mut max = self.max_parts.unwrap_or(DEFAULT_MAX_PATH_COUNT);
+        if let Some(min_part_sats) = self.min_part_sats.filter(|m| *m > 0) {
+            let parts = amount_msat / (min_part_sats * 1_000);
+            max = max.min(parts.min(u8::MAX as u64) as u8);
+        }
+        max.max(1)
+    }
+}
+
+/// Totals of the fees paid by the wallet over a period of time, in sats.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeSummary {
+    /// Routing fees paid for outgoing lightning payments
+    pub lightning_fees: u64,
+    /// Fees taken by the LSP for incoming lightning payments
+    pub lsp_fees: u64,
+    /// Fees paid for on-chain transactions we sent, including channel opens
+    pub onchain_fees: u64,
+    pub total: u64,
+}
+
+impl FeeSummary {
+    /// Aggregates the fees paid from the given invoices and transactions.
+    /// Only items that were last updated between `start` and `end` (inclusive) are counted.
+    pub(crate) fn from_activity(
+        invoices: &[MutinyInvoice],
+        transactions: &[TransactionDetails],
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Self {
+        let in_period =
+            |time: u64| start.map_or(true, |s| time >= s) && end.map_or(true, |e| time <= e);
+
+        let mut summary = FeeSummary::default();
+
+        // sum the msats before rounding so sub-sat fees add up
+        let mut lsp_fees_msats = 0;
+        let mut lightning_fees_msats = 0;
+        for invoice in invoices
+            .iter()
+            .filter(|i| i.paid && in_period(i.last_updated))
+        {
+            let fee = invoice.fees_paid_in_msats().unwrap_or(0);
+            if invoice.inbound {
+                lsp_fees_msats += fee;
+            } else {
+                lightning_fees_msats += fee;
+            }
+        }
+        summary.lsp_fees = utils::msats_to_sats_round_up(lsp_fees_msats);
+        summary.lightning_fees = utils::msats_to_sats_round_up(lightning_fees_msats);
+
+        // we only pay fees for transactions we sent, unconfirmed ones count as happening now
+        let now = utils::now().as_secs();
+        summary.onchain_fees = transactions
+            .iter()
+            .filter(|t| t.sent > 0)
+            .filter(|t| {
+                let time = match t.confirmation_time {
+                    ConfirmationTime::Confirmed { time, .. } => time,
+                    ConfirmationTime::Unconfirmed => now,
+                };
+                in_period(time)
+            })
+            .map(|t| t.fee.unwrap_or(0))
+            .sum();
+
+        summary.total = summary.lightning_fees + summary.lsp_fees + summary.onchain_fees;
+        summary
+    }
+}
+
+/// A payment one of our nodes routed between two of its channels
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedPayment {
+    /// The hex id of the channel the payment came in on, if it is known
+    pub inbound_channel_id: Option<String>,
+    /// The hex id of the channel the payment went out on, if it is known
+    pub outbound_channel_id: Option<String>,
+    /// The fee we earned, it isn't always known for forwards claimed on chain
+    pub fee_earned_msat: Option<u64>,
+    /// If the payment was claimed from an on-chain transaction after a force close
+    pub claimed_on_chain: bool,
+    /// Unix timestamp, in seconds, of when the forward settled
+    pub timestamp: u64,
+}
+
+/// How much routing a channel did, see [RoutingStats]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelRoutingStats {
+    pub channel_id: String,
+    /// Forwards that came in on this channel
+    pub forwards_in: u64,
+    /// Forwards that went out on this channel
+    pub forwards_out: u64,
+    /// Fees earned by the forwards that went out on this channel, in msats
+    pub fees_earned_msat: u64,
+    /// Unix timestamp, in seconds, of the last forward through this channel
+    pub last_forward: u64,
+}
+
+/// Totals of the payments our nodes routed over a period of time
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutingStats {
+    pub forwards: u64,
+    pub fees_earned_msat: u64,
+    /// The channels that routed payments, the ones that earned the most first
+    pub channels: Vec<ChannelRoutingStats>,
+}
+
+impl RoutingStats {
+    /// Aggregates the given forwards, only the ones that settled between
+    /// `start` and `end` (inclusive) are counted.
+    pub(crate) fn from_forwards(
+        forwards: &[ForwardedPayment],
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Self {
+        let in_period =
+            |time: u64| start.map_or(true, |s| time >= s) && end.map_or(true, |e| time <= e);
+
+        let mut stats = RoutingStats::default();
+        let mut channels: HashMap<String, ChannelRoutingStats> = HashMap::new();
+        for forward in forwards.iter().filter(|f| in_period(f.timestamp)) {
+            let fee = forward.fee_earned_msat.unwrap_or(0);
+            stats.forwards += 1;
+            stats.fees_earned_msat += fee;
+
+            let hops = [
+                (forward.inbound_channel_id.as_ref(), false),
+                (forward.outbound_channel_id.as_ref(), true),
+            ];
+            for (id, outbound) in hops {
+                let Some(id) = id else {
+                    continue;
+                };
+                let channel = channels
+                    .entry(id.clone())
+                    .or_insert_with(|| ChannelRoutingStats {
+                        channel_id: id.clone(),
+                        ..Default::default()
+                    });
+                channel.last_forward = channel.last_forward.max(forward.timestamp);
+                if outbound {
+                    channel.forwards_out += 1;
+                    channel.fees_earned_msat += fee;
+                } else {
+                    channel.forwards_in += 1;
+                }
+            }
+        }
+
+        stats.channels = channels.into_values().collect();
+        stats.channels.sort_by(|a, b| {
+            b.fees_earned_msat
+                .cmp(&a.fees_earned_msat)
+                .then_with(|| b.last_forward.cmp(&a.last_forward))
+        });
+        stats
+    }
+}
+
+/// Statistics about how the wallet has been used, to see how reliable it has been.
+/// These are only computed locally from what the wallet already stores, and only hold
+/// counts and amounts, never who was paid.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageStats {
+    /// Outgoing lightning payments that succeeded
+    pub payments_sent: u64,
+    /// Outgoing lightning payments that failed
+    pub payments_failed: u64,
+    /// Percent of the finished outgoing payments that succeeded
+    pub payment_success_rate: Option<u8>,
+    /// Incoming lightning payments that were received
+    pub payments_received: u64,
+    /// Average routing fee of the payments sent, in msats
+    pub average_lightning_fee_msats: Option<u64>,
+    /// On-chain transactions we sent, including channel opens
+    pub onchain_transactions_sent: u64,
+    /// Average fee of the on-chain transactions we sent, in sats
+    pub average_onchain_fee: Option<u64>,
+}
+
+impl UsageStats {
+    pub(crate) fn from_activity(
+        outbound: &[PaymentInfo],
+        inbound: &[PaymentInfo],
+        transactions: &[TransactionDetails],
+    ) -> Self {
+        let average = |total: u64, count: u64| (count > 0).then(|| total / count);
+
+        let sent: Vec<&PaymentInfo> = outbound
+            .iter()
+            .filter(|p| p.status == HTLCStatus::Succeeded)
+            .collect();
+        let payments_sent = sent.len() as u64;
+        let payments_failed = outbound
+            .iter()
+            .filter(|p| p.status == HTLCStatus::Failed)
+            .count() as u64;
+        let lightning_fees: u64 = sent.iter().map(|p| p.fee_paid_msat.unwrap_or(0)).sum();
+
+        let onchain_sent: Vec<&TransactionDetails> =
+            transactions.iter().filter(|t| t.sent > 0).collect();
+        let onchain_fees: u64 = onchain_sent.iter().map(|t| t.fee.unwrap_or(0)).sum();
+
+        UsageStats {
+            payments_sent,
+            payments_failed,
+            payment_success_rate: average(payments_sent * 100, payments_sent + payments_failed)
+                .map(|p| p as u8),
+            payments_received: inbound
+                .iter()
+                .filter(|p| p.status == HTLCStatus::Succeeded)
+                .count() as u64,
+            average_lightning_fee_msats: average(lightning_fees, payments_sent),
+            onchain_transactions_sent: onchain_sent.len() as u64,
+            average_onchain_fee: average(onchain_fees, onchain_sent.len() as u64),
+        }
+    }
+}
+
+/// The highest fee rate, in sat/vbyte, that funds from rotated on-chain keys are swept at
+/// when no other is given.
+pub const DEFAULT_ROTATION_SWEEP_MAX_FEE_RATE: f32 = 5.0;
+
+/// The result of switching to a new on-chain account
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OnChainKeyRotation {
+    /// How many times the keys were rotated, new addresses are
+    /// derived from `m/86'/coin'/index'/rotation'`
+    pub rotation: u32,
+    /// Funds left in the old accounts that still need to be swept, in sats
+    pub retired_balance: u64,
+}
+
+pub struct MutinyBalance {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+    /// The lightning balance in sats, rounded down
+    pub lightning: u64,
+    /// The exact lightning balance in millisatoshis
+    pub lightning_msats: u64,
+    pub force_close: u64,
+    /// Funds on on-chain keys we rotated away from, in sats. They can't be spent
+    /// until they are swept with [NodeManager::sweep_rotated_onchain_funds].
+    pub retired: u64,
+}
+
+/// How much we can receive over lightning, from [NodeManager::get_inbound_liquidity]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InboundLiquidity {
+    /// The inbound capacity of all our channels, in sats
+    pub total_sats: u64,
+    /// The inbound capacity of the channels that can receive right now, in sats
+    pub usable_sats: u64,
+    /// The most a single payment can be without multi-path, in sats
+    pub largest_channel_sats: u64,
+    pub channels: usize,
+    /// If an LSP can open a channel for a payment bigger than our inbound capacity
+    pub lsp_available: bool,
+    /// The alert threshold, if one was set with [NodeManager::set_inbound_liquidity_threshold]
+    pub threshold_sats: Option<u64>,
+}
+
+/// What would be left behind by moving the wallet to another network
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NetworkMigrationCheck {
+    pub from: Network,
+    pub to: Network,
+    /// On-chain balance in sats, including unconfirmed funds
+    pub onchain_balance: u64,
+    /// Lightning balance in sats
+    pub lightning_balance: u64,
+    pub open_channels: usize,
+    /// If the migration can go ahead. Funds on test networks have no value,
+    /// so only mainnet funds hold it up.
+    pub ready: bool,
+}
+
+pub struct LnUrlParams {
+    pub max: u64,
+    pub min: u64,
+    pub tag: String,
+}
+
+/// The outcome of draining a LNURL-withdraw endpoint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LnUrlDrainResult {
+    /// The total amount withdrawn, in sats
+    pub withdrawn_sats: u64,
+    /// The invoices the service paid
+    pub invoices: Vec<MutinyInvoice>,
+    /// How much the service still allows withdrawing, in sats.
+    /// This is non-zero if we ran out of inbound liquidity or the service stopped paying.
+    pub remaining_sats: u64,
+}
+
+/// The result of [NodeManager::send_to_address]
+#[derive(Debug, Clone, PartialEq)]
+pub enum OnChainSend {
+    /// The transaction was signed and broadcast
+    Broadcast(Txid),
+    /// The wallet uses an external signer, the PSBT needs to be signed by it
+    /// and then broadcast with [NodeManager::broadcast_psbt]
+    NeedsSignature(PartiallySignedTransaction),
+}
+
+/// The [NodeManager] is the main entry point for interacting with the Mutiny Wallet.
+/// It is responsible for managing the on-chain wallet and the lightning nodes.
+///
+/// It can be used to create a new wallet, or to load an existing wallet.
+///
+/// It can be configured to use all different custom backend services, or to use the default
+/// services provided by Mutiny.
+pub struct NodeManager<S: MutinyStorage> {
+    pub(crate) stop: Arc<AtomicBool>,
+    /// The mnemonic seed, this is None for watch-only wallets
+    mnemonic: Option<Mnemonic>,
+    /// If on-chain keys are on an external signer instead of derived from the mnemonic
+    external_signer: bool,
+    /// The account index the wallet's keys are derived with
+    account_index: u32,
+    network: Network,
+    #[cfg(target_arch = "wasm32")]
+    websocket_proxies: Arc<WsProxyFailover>,
+    /// The SOCKS5 proxy outbound connections go through, if any
+    #[cfg(not(target_arch = "wasm32"))]
+    socks_proxy: Option<String>,
+    /// The first port nodes accept inbound connections on, if any
+    #[cfg(not(target_arch = "wasm32"))]
+    listen_port: Option<u16>,
+    /// Shared client for http requests, it goes through the SOCKS5 proxy when one is set
+    pub(crate) http_client: Client,
+    pub(crate) esplora: Arc<FailoverEsploraClient>,
+    wallet: Arc<OnChainWallet<S>>,
+    gossip_sync: Arc<RapidGossipSync>,
+    /// A single RGS url or a comma separated list of them to try in order
+    user_rgs_url: Option<String>,
+    scorer: Arc<utils::Mutex<ProbScorer>>,
+    chain: Arc<MutinyChain<S>>,
+    pub(crate) fee_estimator: Arc<MutinyFeeEstimator<S>>,
+    pub(crate) storage: S,
+    pub(crate) node_storage: Mutex<NodeStorage>,
+    pub(crate) nodes: Arc<Mutex<HashMap<PublicKey, Arc<Node<S>>>>>,
+    auth: Option<AuthManager<S>>,
+    lnurl_client: LnUrlClient,
+    pub(crate) lsp_clients: Arc<Mutex<Vec<LspClient>>>,
+    pub(crate) logger: Arc<MutinyLogger>,
+    bitcoin_price_cache: Arc<BitcoinPriceCache>,
+    sync_status: Arc<SyncStatusTracker>,
+    notifier: Arc<EventNotifier>,
+    /// Decides on inbound channels that pass the [ChannelAcceptancePolicy], shared with the nodes
+    channel_acceptor: Arc<RwLock<Option<Arc<dyn ChannelAcceptor>>>>,
+    onion_message_handler: Arc<MutinyOnionMessageHandler>,
+    custom_message_handler: Arc<MutinyCustomMessageHandler>,
+    coordinator: OperationCoordinator,
+    /// The countdown of each expiring HTLC we last sent an event for
+    notified_htlc_expiries: Mutex<HashMap<String, u32>>,
+    /// If we already sent a [MutinyEvent::InboundLiquidityLow] that has not recovered since
+    inbound_liquidity_low: AtomicBool,
+    /// The wallet transactions as of the last confirmation check, None before the first one
+    watched_transactions: Mutex<Option<HashMap<Txid, WatchedTransaction>>>,
+    /// The transactions we already sent a [MutinyEvent::RebroadcastFailed] for
+    rebroadcast_failures: Mutex<HashSet<Txid>>,
+    /// The confirmations of each pending channel we last sent an event for
+    notified_channel_confirmations: Mutex<HashMap<String, u32>>,
+}
+
+impl<S: MutinyStorage> NodeManager<S> {
+    /// Returns if there is a saved wallet in storage.
+    /// This is checked by seeing if a mnemonic seed, or the descriptor of
+    /// a watch-only wallet, exists in storage.
+    pub fn has_node_manager(storage: S) -> bool {
+        // the mnemonic may be encrypted, even in the legacy format that can't be
+        // told apart from plaintext, so only check that one is saved
+        matches!(storage.get::<Value>(MNEMONIC_KEY), Ok(Some(_)))
+            || matches!(storage.get::<Value>(WATCH_ONLY_KEY), Ok(Some(_)))
+    }
+
+    /// Creates a new [NodeManager] with the given parameters.
+    /// The mnemonic seed is read from storage, unless one is provided.
+    /// If the config is watch-only, no mnemonic is used and no lightning nodes are started.
+    /// A saved watch-only wallet is loaded again when no mnemonic or descriptor is given.
+    /// If no mnemonic is provided, a new one is generated and stored.
+    pub async fn new(c: MutinyWalletConfig, storage: S) -> Result<NodeManager<S>, MutinyError> {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let socks_proxy = c.socks_proxy.clone().filter(|p| !p.is_empty());
+        #[cfg(not(target_arch = "wasm32"))]
+        let http_client = crate::networking::socks::http_client(socks_proxy.as_deref())?;
+        #[cfg(target_arch = "wasm32")]
+        let http_client = Client::new();
+
+        // mainnet has to be configured explicitly so a wallet set up for
+        // testing never ends up holding real funds by accident
+        let network: Network = c.network.unwrap_or(Network::Signet);
+
+        // keep the descriptor so the wallet is found again, like a saved mnemonic
+        let watch_only = match c.watch_only {
+            Some(descriptor) => {
+                storage.set_watch_only_descriptor(&descriptor)?;
+                Some(descriptor)
+            }
+            None if c.mnemonic.is_none() => storage.get_watch_only_descriptor()?,
+            None => None,
+        };
+
+        let mut generated_seed = false;
+        let mnemonic = match (c.mnemonic, watch_only.as_ref()) {
+            (_, Some(_)) => None,
+            (Some(seed), None) => Some(storage.insert_mnemonic(seed)?),
+            (None, None) => match storage.get_mnemonic() {
+                Ok(mnemonic) => Some(mnemonic),
+                Err(_) => {
                     let seed = keymanager::generate_seed(12)?;
-                    storage.insert_mnemonic(seed)?
+                    generated_seed = true;
+                    Some(storage.insert_mnemonic(seed)?)
                 }
             },
         };
 
         let logger = Arc::new(MutinyLogger::with_writer(stop.clone(), storage.clone()));
 
-        let esplora_server_url = get_esplora_url(network, c.user_esplora_url);
-        let tx_sync = Arc::new(EsploraSyncClient::new(esplora_server_url, logger.clone()));
+        #[cfg(target_arch = "wasm32")]
+        let websocket_proxies = {
+            let urls = get_websocket_proxy_urls(c.websocket_proxy_addr.clone());
+            let proxies = Arc::new(WsProxyFailover::new(
+                urls,
+                http_client.clone(),
+                logger.clone(),
+            )?);
+            WsProxyFailover::start_health_checks(proxies.clone(), stop.clone());
+            proxies
+        };
 
-        let esplora = Arc::new(tx_sync.client().clone());
-        let fee_estimator = Arc::new(MutinyFeeEstimator::new(
-            storage.clone(),
+        // set up the remote backup before anything is written
+        match (c.vss_url.as_ref(), mnemonic.as_ref()) {
+            (Some(url), Some(mnemonic)) if !url.is_empty() => {
+                let xprivkey = ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))?;
+                let backup = Arc::new(VssBackup::new(
+                    url.clone(),
+                    xprivkey,
+                    http_client.clone(),
+                    logger.clone(),
+                )?);
+
+                // only restore into a fresh wallet so restarts don't overwrite newer state
+                if c.restore_from_vss && storage.get_nodes()?.nodes.is_empty() {
+                    backup.restore(&storage).await?;
+                }
+
+                storage.set_vss(Some(backup.clone()));
+                VssBackup::start(backup, stop.clone());
+            }
+            _ => storage.set_vss(None),
+        }
+
+        // after restoring, so restored data is checked too
+        check_network(&storage, network)?;
+        let account_index = check_account_index(&storage, c.account_index)?;
+
+        if let (Some(backup), Some(mnemonic)) = (c.channel_backup.as_ref(), mnemonic.as_ref()) {
+            if storage.get_nodes()?.nodes.is_empty() {
+                let xprivkey = ExtendedPrivKey::new_master(network, &mnemonic.to_seed(""))?;
+                let backup = StaticChannelBackup::decrypt(backup, xprivkey)?;
+                if backup.network != network {
+                    return Err(MutinyError::IncorrectNetwork(backup.network));
+                }
+                backup.restore(&storage)?;
+                log_info!(
+                    logger,
+                    "Restored {} channel monitors from backup",
+                    backup.monitors.len()
+                );
+            }
+        }
+
+        let esplora = Arc::new(FailoverEsploraClient::with_proxy(
+            get_esplora_urls(network, c.user_esplora_url),
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy.as_deref(),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            logger.clone(),
+        )?);
+        let tx_sync = Arc::new(EsploraSyncClient::from_client(
             esplora.clone(),
             logger.clone(),
         ));
 
-        let wallet = Arc::new(OnChainWallet::new(
-            &mnemonic,
+        // a new seed can't have been used before the current block, wallets that scan
+        // blocks instead of addresses only need to start here when it is restored
+        if generated_seed {
+            let esplora = esplora.clone();
+            let storage = storage.clone();
+            let logger = logger.clone();
+            utils::spawn(async move {
+                match esplora.get_height().await {
+                    Ok(height) => {
+                        if let Err(e) = storage.set_wallet_birthday(height) {
+                            log_warn!(logger, "Could not save wallet birthday: {e}");
+                        }
+                    }
+                    Err(e) => log_warn!(logger, "Could not get wallet birthday: {e}"),
+                }
+            });
+        }
+
+        let fee_estimator = Arc::new(MutinyFeeEstimator::new(
             storage.clone(),
-            network,
             esplora.clone(),
-            fee_estimator.clone(),
+            c.fee_source.clone(),
             logger.clone(),
-        )?);
+        ));
+
+        let external_signer = c.external_signer.is_some();
+        let wallet = match (&mnemonic, c.external_signer.or(watch_only)) {
+            // lightning keys stay local, on-chain keys are on the external signer
+            (Some(_), Some(descriptor)) if external_signer => OnChainWallet::new_watch_only(
+                &descriptor,
+                storage.clone(),
+                network,
+                esplora.clone(),
+                fee_estimator.clone(),
+                logger.clone(),
+            )?,
+            (Some(mnemonic), _) => OnChainWallet::new(
+                mnemonic,
+                storage.clone(),
+                network,
+                account_index,
+                esplora.clone(),
+                fee_estimator.clone(),
+                logger.clone(),
+            )?,
+            (None, Some(descriptor)) => OnChainWallet::new_watch_only(
+                &descriptor,
+                storage.clone(),
+                network,
+                esplora.clone(),
+                fee_estimator.clone(),
+                logger.clone(),
+            )?,
+            (None, None) => return Err(MutinyError::InvalidMnemonic),
+        };
+        let wallet = Arc::new(wallet.with_scan_limits(
+            c.stop_gap.unwrap_or(DEFAULT_STOP_GAP),
+            c.address_lookahead.unwrap_or(0),
+        ));
 
         let chain = Arc::new(MutinyChain::new(tx_sync, wallet.clone(), logger.clone()));
 
-        let (gossip_sync, scorer) =
-            gossip::get_gossip_sync(&storage, c.user_rgs_url, network, logger.clone()).await?;
+        let (gossip_sync, scorer) = gossip::get_gossip_sync(
+            &storage,
+            c.user_rgs_url.clone(),
+            network,
+            &http_client,
+            logger.clone(),
+        )
+        .await?;
 
         let scorer = Arc::new(utils::Mutex::new(scorer));
 
         let gossip_sync = Arc::new(gossip_sync);
 
         // load lsp clients, if any
-        let lsp_clients: Vec<LspClient> = match c.lsp_url.clone() {
-            // check if string is some and not an empty string
-            Some(lsp_urls) if !lsp_urls.is_empty() => {
-                let urls: Vec<&str> = lsp_urls.split(',').collect();
-
-                let futs = urls.into_iter().map(|url| LspClient::new(url.trim()));
+        let lsp_urls = configured_lsp_urls(c.lsp_url.as_deref(), storage.get_lsp_urls()?);
 
-                let results = futures::future::join_all(futs).await;
-
-                results
-                    .into_iter()
-                    .flat_map(|res| match res {
-                        Ok(client) => Some(client),
-                        Err(e) => {
-                            log_warn!(logger, "Error starting up lsp client: {e}");
-                            None
-                        }
-                    })
-                    .collect()
-            }
-            _ => Vec::new(),
-        };
+        let futs = lsp_urls
+            .iter()
+            .map(|url| LspClient::new(url, http_client.clone()));
+        let lsp_clients: Vec<LspClient> = futures::future::join_all(futs)
+            .await
+            .into_iter()
+            .flat_map(|res| match res {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    log_warn!(logger, "Error starting up lsp client: {e}");
+                    None
+                }
+            })
+            .collect();
+        let lsp_clients = Arc::new(Mutex::new(lsp_clients));
 
         let node_storage = storage.get_nodes()?;
 
@@ -540,72 +2030,112 @@ impl<S: MutinyStorage> NodeManager<S> {
             .into_iter()
             .filter(|(_, n)| !n.is_archived());
 
+        let notifier = Arc::new(EventNotifier::default());
+        let channel_acceptor = Arc::new(RwLock::new(c.channel_acceptor.clone()));
+        let onion_message_handler = Arc::new(MutinyOnionMessageHandler::new(logger.clone()));
+        let custom_message_handler = Arc::new(MutinyCustomMessageHandler::new(logger.clone()));
         let mut nodes_map = HashMap::new();
 
-        for node_item in unarchived_nodes {
-            let node = Node::new(
-                node_item.0,
-                &node_item.1,
-                stop.clone(),
-                &mnemonic,
-                storage.clone(),
-                gossip_sync.clone(),
-                scorer.clone(),
-                chain.clone(),
-                fee_estimator.clone(),
-                wallet.clone(),
-                network,
-                esplora.clone(),
-                &lsp_clients,
-                logger.clone(),
-                #[cfg(target_arch = "wasm32")]
-                websocket_proxy_addr.clone(),
-            )
-            .await?;
-
-            let id = node
-                .keys_manager
-                .get_node_id(Recipient::Node)
-                .expect("Failed to get node id");
-
-            nodes_map.insert(id, Arc::new(node));
+        // Watch-only wallets have no keys for lightning, so no nodes are started.
+        if let Some(mnemonic) = mnemonic.as_ref() {
+            for node_item in unarchived_nodes {
+                let node = Node::new(
+                    node_item.0,
+                    &node_item.1,
+                    stop.clone(),
+                    mnemonic,
+                    account_index,
+                    storage.clone(),
+                    gossip_sync.clone(),
+                    scorer.clone(),
+                    chain.clone(),
+                    fee_estimator.clone(),
+                    wallet.clone(),
+                    network,
+                    esplora.clone(),
+                    lsp_clients.clone(),
+                    notifier.clone(),
+                    channel_acceptor.clone(),
+                    onion_message_handler.clone(),
+                    custom_message_handler.clone(),
+                    logger.clone(),
+                    #[cfg(target_arch = "wasm32")]
+                    websocket_proxies.clone(),
+                    #[cfg(not(target_arch = "wasm32"))]
+                    socks_proxy.clone(),
+                    #[cfg(not(target_arch = "wasm32"))]
+                    c.listen_port,
+                )
+                .await?;
+
+                let id = node
+                    .keys_manager
+                    .get_node_id(Recipient::Node)
+                    .expect("Failed to get node id");
+
+                nodes_map.insert(id, Arc::new(node));
+            }
         }
 
         // when we create the nodes we set the LSP if one is missing
         // we need to save it to local storage after startup in case
         // a LSP was set.
-        let updated_nodes: HashMap<String, NodeIndex> = nodes_map
-            .values()
-            .map(|n| (n._uuid.clone(), n.node_index()))
-            .collect();
+        // Watch-only wallets never started any nodes, so leave storage untouched.
+        if mnemonic.is_some() {
+            let updated_nodes: HashMap<String, NodeIndex> = nodes_map
+                .values()
+                .map(|n| (n._uuid.clone(), n.node_index()))
+                .collect();
 
-        log_info!(logger, "inserting updated nodes");
+            log_info!(logger, "inserting updated nodes");
 
-        storage.insert_nodes(NodeStorage {
-            nodes: updated_nodes,
-        })?;
+            storage.insert_nodes(NodeStorage {
+                nodes: updated_nodes,
+            })?;
 
-        log_info!(logger, "inserted updated nodes");
+            log_info!(logger, "inserted updated nodes");
+        }
 
         let nodes = Arc::new(Mutex::new(nodes_map));
 
-        let seed = mnemonic.to_seed("");
-        let xprivkey = ExtendedPrivKey::new_master(network, &seed)?;
-        let auth = AuthManager::new(xprivkey, storage.clone())?;
+        let auth = match mnemonic.as_ref() {
+            Some(mnemonic) => {
+                let seed = mnemonic.to_seed("");
+                let xprivkey = ExtendedPrivKey::new_master(network, &seed)?;
+                let auth = AuthManager::new(xprivkey, storage.clone())?;
 
-        // Create default profile if it doesn't exist
-        auth.create_init()?;
+                // Create default profile if it doesn't exist
+                auth.create_init()?;
+                Some(auth)
+            }
+            None => None,
+        };
 
-        let lnurl_client = lnurl::Builder::default()
+        let lnurl_builder = lnurl::Builder::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let lnurl_builder = match socks_proxy.as_deref() {
+            Some(proxy) => lnurl_builder.proxy(&format!("socks5h://{proxy}")),
+            None => lnurl_builder,
+        };
+        let lnurl_client = lnurl_builder
             .build_async()
             .expect("failed to make lnurl client");
 
+        let bitcoin_price_cache = Arc::new(BitcoinPriceCache::new(
+            http_client.clone(),
+            storage.get_price_config()?.unwrap_or_default(),
+            logger.clone(),
+        ));
+
         let nm = NodeManager {
             stop,
             mnemonic,
+            external_signer,
+            account_index,
             network,
             wallet,
             gossip_sync,
+            user_rgs_url: c.user_rgs_url,
             scorer,
             chain,
             fee_estimator,
@@ -613,13 +2143,29 @@ impl<S: MutinyStorage> NodeManager<S> {
             node_storage: Mutex::new(node_storage),
             nodes,
             #[cfg(target_arch = "wasm32")]
-            websocket_proxy_addr,
+            websocket_proxies,
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy,
+            #[cfg(not(target_arch = "wasm32"))]
+            listen_port: c.listen_port,
+            http_client,
             esplora,
             auth,
             lnurl_client,
             lsp_clients,
             logger,
-            bitcoin_price_cache: Arc::new(Mutex::new(None)),
+            bitcoin_price_cache,
+            sync_status: Arc::new(SyncStatusTracker::default()),
+            notifier,
+            channel_acceptor,
+            onion_message_handler,
+            custom_message_handler,
+            coordinator: OperationCoordinator::default(),
+            notified_htlc_expiries: Mutex::new(HashMap::new()),
+            inbound_liquidity_low: AtomicBool::new(false),
+            watched_transactions: Mutex::new(None),
+            rebroadcast_failures: Mutex::new(HashSet::new()),
+            notified_channel_confirmations: Mutex::new(HashMap::new()),
         };
 
         Ok(nm)
@@ -635,8 +2181,21 @@ impl<S: MutinyStorage> NodeManager<S> {
     /// Stops all of the nodes and background processes.
     /// Returns after node has been stopped.
     pub async fn stop(&self) -> Result<(), MutinyError> {
-        self.stop.swap(true, Ordering::Relaxed);
         let mut nodes = self.nodes.lock().await;
+
+        // give payments we are in the middle of claiming a chance to finish,
+        // anything left over is claimed again on the next start
+        for _ in 0..MAX_CLAIM_WAIT_ATTEMPTS {
+            if !nodes.values().any(|n| n.has_pending_claims()) {
+                break;
+            }
+            log_debug!(self.logger, "waiting for pending claims before stopping");
+            utils::sleep(500).await;
+        }
+
+        self.stop.swap(true, Ordering::Relaxed);
+        self.sync_status.unsubscribe_all();
+        self.notifier.unsubscribe_all();
         let node_futures = nodes.iter().map(|(_, n)| async {
             match n.stopped().await {
                 Ok(_) => {
@@ -708,13 +2267,22 @@ impl<S: MutinyStorage> NodeManager<S> {
                 // find redshifts with channels ready
                 // use unwrap_or_default() to handle errors
                 let all = nm.storage.get_redshifts().unwrap_or_default();
-                for mut redshift in all {
+                for redshift in all {
                     if redshift.status == RedshiftStatus::ChannelOpened {
-                        // update status
-                        redshift.status = RedshiftStatus::AttemptingPayments;
-                        if let Err(e) = nm.storage.persist_redshift(redshift.clone()) {
-                            log_error!(nm.logger, "Error persisting redshift status update: {e}");
-                        }
+                        // update status, unless it was cancelled since we read it
+                        let redshift = match nm.storage.update_redshift(&redshift.id, |r| {
+                            r.status = RedshiftStatus::AttemptingPayments
+                        }) {
+                            Ok(r) if r.status == RedshiftStatus::AttemptingPayments => r,
+                            Ok(_) => continue,
+                            Err(e) => {
+                                log_error!(
+                                    nm.logger,
+                                    "Error persisting redshift status update: {e}"
+                                );
+                                continue;
+                            }
+                        };
 
                         // start attempting payments
                         let payment_nm = nm.clone();
@@ -735,27 +2303,144 @@ impl<S: MutinyStorage> NodeManager<S> {
         });
     }
 
-    /// Creates a background process that will sync the wallet with the blockchain.
-    /// This will also update the fee estimates every 10 minutes.
-    pub fn start_sync(nm: Arc<NodeManager<S>>) {
-        // If we are stopped, don't sync
-        if nm.stop.load(Ordering::Relaxed) {
-            return;
-        }
-
+    /// Starts a background task that moves swaps along until they complete or fail,
+    /// including the ones that were in progress when the node manager was shut down.
+    pub(crate) fn start_swaps(nm: Arc<NodeManager<S>>) {
         utils::spawn(async move {
-            let mut sync_count: u64 = 0;
             loop {
-                // If we are stopped, don't sync
                 if nm.stop.load(Ordering::Relaxed) {
-                    return;
+                    break;
                 }
 
-                // we don't need to re-sync fees every time
-                // just do it every 10 minutes
-                if sync_count % 10 == 0 {
-                    if let Err(e) = nm.fee_estimator.update_fee_estimates().await {
-                        log_error!(nm.logger, "Failed to update fee estimates: {e}");
+                // use unwrap_or_default() to handle errors
+                let all = nm.storage.get_swaps().unwrap_or_default();
+                for swap in all.into_iter().filter(|s| s.status.is_in_progress()) {
+                    if let Err(e) = nm.check_swap_out(swap).await {
+                        log_error!(nm.logger, "Error checking swap: {e}");
+                    }
+                }
+
+                // sleep 10 seconds
+                sleep(10_000).await;
+            }
+        });
+    }
+
+    /// Starts a background task that rebroadcasts our unconfirmed transactions that fell
+    /// out of the mempool, including the ones LDK broadcast for our channels. Browser
+    /// wallets are often closed for a long time, so this also runs right away on start.
+    pub fn start_rebroadcasts(nm: Arc<NodeManager<S>>) {
+        utils::spawn(async move {
+            loop {
+                if nm.stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                nm.rebroadcast_unconfirmed().await;
+
+                // sleep 10 minutes, checking graceful shutdown check each 1s.
+                for _ in 0..REBROADCAST_INTERVAL_SECS {
+                    if nm.stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    sleep(1_000).await;
+                }
+            }
+        });
+    }
+
+    /// Rebroadcasts the unconfirmed wallet and LDK transactions our esplora server no
+    /// longer knows about. Sends a [MutinyEvent::RebroadcastFailed] the first time
+    /// one of them can't be rebroadcast.
+    async fn rebroadcast_unconfirmed(&self) {
+        let mut txs: HashMap<Txid, Transaction> = HashMap::new();
+        match self.wallet.list_transactions(true) {
+            Ok(wallet_txs) => {
+                for details in wallet_txs {
+                    if details.confirmation_time != ConfirmationTime::Unconfirmed {
+                        continue;
+                    }
+                    if let Some(tx) = details.transaction {
+                        txs.insert(details.txid, tx);
+                    }
+                }
+            }
+            Err(e) => log_warn!(self.logger, "Could not list wallet transactions: {e}"),
+        }
+        let ldk_txids: HashSet<Txid> = match self.storage.get_broadcast_transactions() {
+            Ok(ldk_txs) => ldk_txs
+                .into_iter()
+                .map(|tx| {
+                    let txid = tx.txid();
+                    txs.insert(txid, tx);
+                    txid
+                })
+                .collect(),
+            Err(e) => {
+                log_warn!(self.logger, "Could not read broadcast transactions: {e}");
+                HashSet::new()
+            }
+        };
+
+        for (txid, tx) in txs {
+            match self.esplora.get_tx_status(&txid).await {
+                Ok(Some(status)) => {
+                    if status.confirmed && ldk_txids.contains(&txid) {
+                        if let Err(e) = self.storage.remove_broadcast_transaction(&txid) {
+                            log_warn!(self.logger, "Could not remove transaction {txid}: {e}");
+                        }
+                    }
+                }
+                Ok(None) => {
+                    log_info!(self.logger, "Rebroadcasting transaction {txid}");
+                    match self.esplora.broadcast(&tx).await {
+                        Ok(()) => {
+                            self.rebroadcast_failures.lock().await.remove(&txid);
+                        }
+                        Err(e) => {
+                            log_error!(self.logger, "Failed to rebroadcast {txid}: {e}");
+                            // LDK broadcasts its own replacement if this one conflicts
+                            if ldk_txids.contains(&txid) {
+                                let _ = self.storage.remove_broadcast_transaction(&txid);
+                            }
+                            if self.rebroadcast_failures.lock().await.insert(txid) {
+                                self.notifier.notify(MutinyEvent::RebroadcastFailed {
+                                    txid,
+                                    error: e.to_string(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Err(e) => log_warn!(self.logger, "Could not check transaction {txid}: {e}"),
+            }
+        }
+    }
+
+    /// Creates a background process that will sync the wallet with the blockchain.
+    /// This will also update the fee estimates every 10 minutes.
+    pub fn start_sync(nm: Arc<NodeManager<S>>) {
+        // If we are stopped, don't sync
+        if nm.stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        NodeManager::start_fiat_pricing(nm.clone());
+
+        utils::spawn(async move {
+            let mut sync_count: u64 = 0;
+            loop {
+                // If we are stopped, don't sync
+                if nm.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                // we don't need to re-sync fees every time
+                // just do it every 10 minutes
+                if sync_count % 10 == 0 {
+                    nm.sync_status.set_stage(SyncStage::Fees);
+                    if let Err(e) = nm.fee_estimator.update_fee_estimates().await {
+                        log_error!(nm.logger, "Failed to update fee estimates: {e}");
                     } else {
                         log_info!(nm.logger, "Updated fee estimates!");
                     }
@@ -765,6 +2450,26 @@ impl<S: MutinyStorage> NodeManager<S> {
                     log_error!(nm.logger, "Failed to sync: {e}");
                 }
 
+                if let Err(e) = nm.sync_watched().await {
+                    log_error!(nm.logger, "Failed to sync watched addresses: {e}");
+                }
+
+                nm.check_expiring_htlcs().await;
+                nm.check_inbound_liquidity().await;
+                nm.check_transaction_confirmations().await;
+                nm.check_pending_channels().await;
+                nm.check_channel_closures().await;
+
+                // re-announce our nodes every hour so they stay in the network graph,
+                // and prune the network graph so it doesn't keep growing
+                if sync_count % 60 == 0 {
+                    nm.broadcast_node_announcements().await;
+
+                    if let Err(e) = nm.prune_network_graph().await {
+                        log_error!(nm.logger, "Failed to prune network graph: {e}");
+                    }
+                }
+
                 // if this is the first sync, set the done_first_sync flag
                 if sync_count == 0 {
                     let _ = nm.storage.set_done_first_sync();
@@ -784,6 +2489,344 @@ impl<S: MutinyStorage> NodeManager<S> {
         });
     }
 
+    /// Sends a [MutinyEvent::HtlcExpiring] for each expiring HTLC whose countdown
+    /// changed since the last check, so there is one event per block at most.
+    async fn check_expiring_htlcs(&self) {
+        let htlcs = match self.list_expiring_htlcs().await {
+            Ok(htlcs) => htlcs,
+            Err(e) => {
+                log_error!(self.logger, "Failed to check expiring htlcs: {e}");
+                return;
+            }
+        };
+
+        let mut notified = self.notified_htlc_expiries.lock().await;
+        let mut current = HashMap::new();
+        for htlc in htlcs {
+            let key = format!(
+                "{}:{}:{}",
+                htlc.channel_id, htlc.cltv_expiry, htlc.amount_sats
+            );
+            if notified.get(&key) != Some(&htlc.blocks_until_force_close) {
+                log_warn!(
+                    self.logger,
+                    "HTLC of {} sats in channel {} force closes in {} blocks",
+                    htlc.amount_sats,
+                    htlc.channel_id,
+                    htlc.blocks_until_force_close
+                );
+                self.notifier.notify(MutinyEvent::HtlcExpiring {
+                    channel_id: htlc.channel_id.clone(),
+                    amount_sats: htlc.amount_sats,
+                    cltv_expiry: htlc.cltv_expiry,
+                    blocks_until_force_close: htlc.blocks_until_force_close,
+                });
+            }
+            current.insert(key, htlc.blocks_until_force_close);
+        }
+        *notified = current;
+    }
+
+    /// Sends a [MutinyEvent::TransactionConfirmed] when a wallet transaction reaches 1 and
+    /// [NodeManager::get_safe_confirmations] confirmations, and a [MutinyEvent::TransactionReplaced]
+    /// or [MutinyEvent::TransactionEvicted] when an unconfirmed one is dropped.
+    async fn check_transaction_confirmations(&self) {
+        let txs = match self.wallet.list_transactions(false) {
+            Ok(txs) => txs,
+            Err(e) => {
+                log_error!(
+                    self.logger,
+                    "Failed to check transaction confirmations: {e}"
+                );
+                return;
+            }
+        };
+        let current_height = match self.esplora.get_height().await {
+            Ok(height) => height,
+            Err(e) => {
+                log_error!(
+                    self.logger,
+                    "Failed to check transaction confirmations: {e}"
+                );
+                return;
+            }
+        };
+        let current: HashMap<Txid, u32> = txs
+            .iter()
+            .map(|tx| {
+                (
+                    tx.txid,
+                    tx_confirmations(&tx.confirmation_time, current_height),
+                )
+            })
+            .collect();
+
+        let mut watched = self.watched_transactions.lock().await;
+        let previous = match watched.take() {
+            Some(previous) => previous,
+            // nothing changed yet on the first check
+            None => current
+                .iter()
+                .map(|(txid, confirmations)| {
+                    let watched = WatchedTransaction {
+                        confirmations: *confirmations,
+                        evicted: false,
+                    };
+                    (*txid, watched)
+                })
+                .collect(),
+        };
+
+        let safe_confirmations = self.get_safe_confirmations();
+        for event in transaction_confirmation_events(&previous, &current, safe_confirmations) {
+            log_info!(self.logger, "Transaction event: {event:?}");
+            self.notifier.notify(event);
+        }
+
+        let mut next = HashMap::with_capacity(current.len());
+        for (txid, confirmations) in current {
+            let previous = previous.get(&txid);
+            let mut evicted = previous.map(|p| p.evicted).unwrap_or(false);
+            // give new transactions until the next check to reach our esplora server
+            if confirmations == 0 && previous.is_some_and(|p| p.confirmations == 0) {
+                match self.esplora.get_tx(&txid).await {
+                    Ok(Some(_)) => evicted = false,
+                    Ok(None) => {
+                        if !evicted {
+                            log_warn!(
+                                self.logger,
+                                "Transaction {txid} was evicted from the mempool"
+                            );
+                            self.notifier
+                                .notify(MutinyEvent::TransactionEvicted { txid });
+                        }
+                        evicted = true;
+                    }
+                    Err(e) => log_warn!(self.logger, "Could not check transaction {txid}: {e}"),
+                }
+            }
+            next.insert(
+                txid,
+                WatchedTransaction {
+                    confirmations,
+                    evicted,
+                },
+            );
+        }
+        *watched = Some(next);
+    }
+
+    /// Sends a [MutinyEvent::ChannelConfirmed] each time the funding transaction
+    /// of a pending channel gets another confirmation.
+    async fn check_pending_channels(&self) {
+        let channels = match self.list_pending_channels().await {
+            Ok(channels) => channels,
+            Err(e) => {
+                log_error!(self.logger, "Failed to check pending channels: {e}");
+                return;
+            }
+        };
+
+        let mut notified = self.notified_channel_confirmations.lock().await;
+        for event in channel_confirmation_events(&notified, &channels) {
+            log_info!(self.logger, "Channel event: {event:?}");
+            self.notifier.notify(event);
+        }
+        *notified = channels
+            .into_iter()
+            .map(|c| (c.channel_id, c.confirmations))
+            .collect();
+    }
+
+    /// Fills in the closing transaction of closed channels once it is on chain,
+    /// and for cooperative closes what it paid back to the wallet.
+    async fn check_channel_closures(&self) {
+        // collect what needs checking first so the nodes aren't locked during the requests
+        let mut unresolved = vec![];
+        {
+            let nodes = self.nodes.lock().await;
+            for node in nodes.values() {
+                let closures = match node.persister.list_channel_closures() {
+                    Ok(closures) => closures,
+                    Err(e) => {
+                        log_error!(self.logger, "Failed to check channel closures: {e}");
+                        continue;
+                    }
+                };
+
+                for (user_channel_id, closure) in closures {
+                    let cooperative = closure.kind == ChannelClosureKind::Cooperative;
+                    let resolved = closure.closing_txid.is_some()
+                        && (!cooperative || closure.swept_sats.is_some());
+                    if resolved || !closure.kind.has_closing_tx() {
+                        continue;
+                    }
+
+                    // closures saved before forensics were recorded don't know the funding output
+                    let funding_txo = closure
+                        .channel_id
+                        .and_then(|id| node.persister.get_channel_forensics(&id).ok().flatten())
+                        .and_then(|f| f.funding_txo);
+                    if let Some(funding_txo) = funding_txo {
+                        unresolved.push((
+                            node.persister.clone(),
+                            user_channel_id,
+                            closure,
+                            funding_txo,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (persister, user_channel_id, mut closure, funding_txo) in unresolved {
+            let status = match self
+                .esplora
+                .get_output_status(&funding_txo.txid, funding_txo.vout as u64)
+                .await
+            {
+                Ok(Some(status)) if status.spent => status,
+                Ok(_) => continue,
+                Err(e) => {
+                    log_warn!(self.logger, "Could not check channel closure: {e}");
+                    continue;
+                }
+            };
+            let Some(closing_txid) = status.txid else {
+                continue;
+            };
+
+            closure.closing_txid = Some(closing_txid);
+            if closure.kind == ChannelClosureKind::Cooperative {
+                // a confirmed close the wallet doesn't know about paid nothing back to us
+                let confirmed = status.status.is_some_and(|s| s.confirmed);
+                closure.swept_sats = match self.wallet.get_transaction(closing_txid, false) {
+                    Ok(Some(tx)) => Some(tx.received),
+                    _ if confirmed => Some(0),
+                    _ => None,
+                };
+            }
+
+            if let Err(e) = persister.persist_channel_closure(user_channel_id, closure) {
+                log_error!(self.logger, "Failed to persist channel closure: {e}");
+            }
+        }
+    }
+
+    /// Records the current price of bitcoin for payments when they complete and for
+    /// transactions when they confirm, so [ActivityItem::fiat_value_at_time] shows what
+    /// they were worth. What happened in the last hour is priced once after our first sync.
+    fn start_fiat_pricing(nm: Arc<NodeManager<S>>) {
+        let mut events = nm.notifier.subscribe();
+        utils::spawn(async move {
+            let mut caught_up = false;
+
+            // the stream ends when we are stopped
+            while let Some(event) = events.next().await {
+                let id = match event {
+                    MutinyEvent::PaymentReceived { payment_hash, .. }
+                    | MutinyEvent::PaymentSent { payment_hash, .. } => payment_hash,
+                    MutinyEvent::TransactionConfirmed { txid, .. } => txid.to_hex(),
+                    // wait for the first sync so our on-chain transactions are loaded
+                    MutinyEvent::SyncCompleted { .. } if !caught_up => {
+                        caught_up = true;
+                        nm.record_recent_fiat_prices().await;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                nm.record_fiat_prices(vec![id]).await;
+            }
+        });
+    }
+
+    /// Records the current price of bitcoin for the payments and transactions
+    /// that happened in the last hour, which may have been while we were not running.
+    async fn record_recent_fiat_prices(&self) {
+        let activity = match self.get_activity().await {
+            Ok(activity) => activity,
+            Err(e) => {
+                log_error!(self.logger, "Failed to get activity to price: {e}");
+                return;
+            }
+        };
+
+        let since = utils::now().as_secs().saturating_sub(RECENT_ACTIVITY_SECS);
+        let unpriced = unpriced_activity(&activity, since);
+        self.record_fiat_prices(unpriced.into_iter().map(|(id, _)| id).collect())
+            .await;
+    }
+
+    /// Records the current price of bitcoin for the given activity items,
+    /// the ones that already have a price keep it.
+    async fn record_fiat_prices(&self, ids: Vec<String>) {
+        let unpriced: Vec<String> = ids
+            .into_iter()
+            .filter(|id| matches!(self.storage.get_historical_price(id), Ok(None)))
+            .collect();
+        if unpriced.is_empty() {
+            return;
+        }
+
+        let price = match self.get_fiat_currency() {
+            Ok(currency) => self
+                .get_bitcoin_price(Some(currency))
+                .await
+                .map(|price| (currency, price)),
+            Err(e) => Err(e),
+        };
+        let (currency, price) = match price {
+            Ok(price) => price,
+            Err(e) => {
+                // expected when fetching prices is disabled
+                log_debug!(self.logger, "No bitcoin price to record: {e}");
+                return;
+            }
+        };
+
+        let now = utils::now().as_secs();
+        for id in unpriced {
+            let price = HistoricalPrice {
+                currency,
+                price,
+                timestamp: now,
+            };
+            if let Err(e) = self.storage.persist_historical_price(id, price) {
+                log_error!(self.logger, "Failed to persist historical price: {e}");
+            }
+        }
+    }
+
+    /// Sends a [MutinyEvent::InboundLiquidityLow] when our usable inbound liquidity
+    /// drops below the threshold, and again only after it has recovered.
+    async fn check_inbound_liquidity(&self) {
+        let liquidity = match self.get_inbound_liquidity().await {
+            Ok(liquidity) => liquidity,
+            Err(e) => {
+                log_error!(self.logger, "Failed to check inbound liquidity: {e}");
+                return;
+            }
+        };
+        let Some(threshold_sats) = liquidity.threshold_sats else {
+            self.inbound_liquidity_low.store(false, Ordering::Relaxed);
+            return;
+        };
+
+        if liquidity.usable_sats >= threshold_sats {
+            self.inbound_liquidity_low.store(false, Ordering::Relaxed);
+        } else if !self.inbound_liquidity_low.swap(true, Ordering::Relaxed) {
+            log_warn!(
+                self.logger,
+                "Inbound liquidity of {} sats is below {threshold_sats} sats",
+                liquidity.usable_sats
+            );
+            self.notifier.notify(MutinyEvent::InboundLiquidityLow {
+                inbound_sats: liquidity.usable_sats,
+                threshold_sats,
+            });
+        }
+    }
+
     /// Broadcast a transaction to the network.
     /// The transaction is broadcast through the configured esplora server.
     pub async fn broadcast_transaction(&self, tx: Transaction) -> Result<(), MutinyError> {
@@ -791,15 +2834,192 @@ impl<S: MutinyStorage> NodeManager<S> {
     }
 
     /// Returns the mnemonic seed phrase for the wallet.
-    pub fn show_seed(&self) -> Mnemonic {
+    /// Watch-only wallets do not have a seed.
+    pub fn show_seed(&self) -> Option<Mnemonic> {
         self.mnemonic.clone()
     }
 
+    /// Returns if the wallet is watch-only.
+    /// Watch-only wallets can track funds and generate addresses but cannot spend.
+    pub fn is_watch_only(&self) -> bool {
+        self.mnemonic.is_none()
+    }
+
+    /// Returns if the wallet's storage is encrypted and has not been unlocked.
+    pub fn is_locked(&self) -> Result<bool, MutinyError> {
+        self.storage.is_locked()
+    }
+
+    /// Locks the wallet. All the nodes are stopped and the encryption key is
+    /// removed from memory, so nothing sensitive can be read or written.
+    ///
+    /// The wallet needs to be unlocked and restarted before it can be used again.
+    /// If the wallet has no password, this only stops the nodes.
+    pub async fn lock(&self) -> Result<(), MutinyError> {
+        self.stop().await?;
+        self.storage.lock();
+        Ok(())
+    }
+
+    /// Unlocks the wallet with the given password.
+    /// Returns [MutinyError::IncorrectPassword] if it cannot decrypt the storage.
+    pub fn unlock(&self, password: String) -> Result<(), MutinyError> {
+        self.storage.unlock(&password)
+    }
+
+    /// Changes the password used to encrypt the wallet's storage and re-encrypts
+    /// the mnemonic, channel state, and payment history with it.
+    ///
+    /// If the wallet has no password, the old password should be None.
+    /// Setting the new password to None removes the encryption.
+    pub fn change_password(
+        &self,
+        old_password: Option<String>,
+        new_password: Option<String>,
+    ) -> Result<(), MutinyError> {
+        self.storage
+            .change_password(old_password.as_deref(), new_password.as_deref())
+    }
+
+    /// Exports an encrypted static channel backup of all the nodes.
+    ///
+    /// The backup can only be read with this wallet's seed. Restoring it never broadcasts
+    /// our own channel state, the channel peers are asked to force close instead, so it
+    /// only needs to be exported again after channels are opened.
+    /// See [MutinyWalletConfig::with_channel_backup] to restore it.
+    pub fn export_channel_backup(&self) -> Result<String, MutinyError> {
+        let mnemonic = self.mnemonic.as_ref().ok_or(MutinyError::WatchOnly)?;
+        let xprivkey = ExtendedPrivKey::new_master(self.network, &mnemonic.to_seed(""))?;
+        StaticChannelBackup::from_storage(&self.storage, self.network)?.encrypt(xprivkey)
+    }
+
+    /// Returns if the on-chain keys are on an external signer,
+    /// see [MutinyWalletConfig::with_external_signer].
+    pub fn uses_external_signer(&self) -> bool {
+        self.external_signer
+    }
+
+    /// Returns an error if the wallet is watch-only
+    fn check_can_spend(&self) -> Result<(), MutinyError> {
+        if self.is_watch_only() {
+            return Err(MutinyError::WatchOnly);
+        }
+        Ok(())
+    }
+
+    /// Returns an error if on-chain funds can't be signed for in the wallet
+    fn check_can_sign_onchain(&self) -> Result<(), MutinyError> {
+        self.check_can_spend()?;
+        if self.external_signer {
+            return Err(MutinyError::ExternalSigner);
+        }
+        Ok(())
+    }
+
     /// Returns the network of the wallet.
     pub fn get_network(&self) -> Network {
         self.network
     }
 
+    /// Returns the derivation paths of the wallet's on-chain and lightning keys,
+    /// what another wallet would need to derive to find them from the seed.
+    pub async fn get_derivation_paths(&self) -> Result<DerivationPaths, MutinyError> {
+        let onchain = self.wallet.account_derivation_path()?;
+        let retired_onchain = self.wallet.retired_derivation_paths()?;
+
+        let base_path = lightning_derivation_path(self.account_index)?;
+        let nodes = self.nodes.lock().await;
+        let mut lightning = nodes
+            .values()
+            .map(|node| {
+                let path = base_path.extend([ChildNumber::from_hardened_idx(node.child_index)?]);
+                Ok((node.child_index, node.pubkey, path.to_string()))
+            })
+            .collect::<Result<Vec<_>, MutinyError>>()?;
+        lightning.sort_by_key(|(child_index, _, _)| *child_index);
+
+        Ok(DerivationPaths {
+            account_index: self.account_index,
+            onchain: onchain.map(|path| path.to_string()),
+            retired_onchain: retired_onchain
+                .iter()
+                .map(|path| path.to_string())
+                .collect(),
+            lightning: lightning
+                .into_iter()
+                .map(|(_, pubkey, path)| (pubkey, path))
+                .collect(),
+        })
+    }
+
+    /// Checks what would be left behind by moving the wallet to the given network,
+    /// see [NodeManager::migrate_network].
+    pub async fn check_network_migration(
+        &self,
+        network: Network,
+    ) -> Result<NetworkMigrationCheck, MutinyError> {
+        if network == self.network {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let balance = self.get_balance().await?;
+        let onchain_balance =
+            balance.confirmed + balance.unconfirmed + balance.force_close + balance.retired;
+        let open_channels = self.list_channels().await?.len();
+        let ready = self.network != Network::Bitcoin
+            || (onchain_balance == 0 && balance.lightning == 0 && open_channels == 0);
+
+        Ok(NetworkMigrationCheck {
+            from: self.network,
+            to: network,
+            onchain_balance,
+            lightning_balance: balance.lightning,
+            open_channels,
+            ready,
+        })
+    }
+
+    /// Moves the wallet to another network, for example from signet testing to mainnet.
+    ///
+    /// This stops the node manager and deletes everything but the seed, the wallet
+    /// then has to be created again with the new network configured. Moving away
+    /// from mainnet is refused while there are funds or channels left, use
+    /// [NodeManager::check_network_migration] to see what has to be moved first.
+    pub async fn migrate_network(&self, network: Network) -> Result<(), MutinyError> {
+        let check = self.check_network_migration(network).await?;
+        if !check.ready {
+            return Err(MutinyError::NetworkMigrationNotReady);
+        }
+
+        self.stop().await?;
+        self.storage.clear_network_data()?;
+        self.storage.set_network(network)?;
+        log_info!(
+            self.logger,
+            "Migrated wallet from {} to {network}",
+            self.network
+        );
+
+        Ok(())
+    }
+
+    /// Returns the health of each of the configured esplora servers.
+    pub fn get_esplora_health(&self) -> Vec<EsploraEndpointHealth> {
+        self.esplora.health()
+    }
+
+    /// The websocket proxy new peer connections currently go through.
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_active_websocket_proxy(&self) -> String {
+        self.websocket_proxies.active()
+    }
+
+    /// Returns the health of each of the configured websocket proxies.
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_websocket_proxy_health(&self) -> Vec<WsProxyHealth> {
+        self.websocket_proxies.health()
+    }
+
     /// Gets a new bitcoin address from the wallet.
     /// Will generate a new address on every call.
     ///
@@ -824,6 +3044,8 @@ impl<S: MutinyStorage> NodeManager<S> {
         amount: Option<u64>,
         labels: Vec<String>,
     ) -> Result<MutinyBip21RawMaterials, MutinyError> {
+        // the invoice and address are labeled together, so concurrent calls are done in order
+        let _guard = self.coordinator.lock(Resource::Addresses).await;
         let invoice = self.create_invoice(amount, labels.clone()).await?;
 
         let Ok(address) = self.get_new_address(labels.clone()) else {
@@ -846,18 +3068,100 @@ impl<S: MutinyStorage> NodeManager<S> {
     /// The amount is in satoshis and the fee rate is in sat/vbyte.
     ///
     /// If a fee rate is not provided, one will be used from the fee estimator.
+    ///
+    /// With an external signer nothing is broadcast, the unsigned PSBT is returned
+    /// to be signed by it and then broadcast with [NodeManager::broadcast_psbt].
+    /// The labels are applied to the PSBT's outputs right away.
     pub async fn send_to_address(
         &self,
         send_to: Address,
         amount: u64,
         labels: Vec<String>,
         fee_rate: Option<f32>,
-    ) -> Result<Txid, MutinyError> {
+    ) -> Result<OnChainSend, MutinyError> {
+        self.check_can_spend()?;
         if !send_to.is_valid_for_network(self.network) {
             return Err(MutinyError::IncorrectNetwork(send_to.network));
         }
 
-        self.wallet.send(send_to, amount, labels, fee_rate).await
+        if self.external_signer {
+            let psbt = self.wallet.create_psbt(send_to, amount, fee_rate)?;
+            self.wallet.label_psbt(&psbt, labels)?;
+            return Ok(OnChainSend::NeedsSignature(psbt));
+        }
+
+        let txid = self.wallet.send(send_to, amount, labels, fee_rate).await?;
+        Ok(OnChainSend::Broadcast(txid))
+    }
+
+    /// Sends a single on-chain transaction paying each of the addresses its amount,
+    /// this saves fees compared to sending to each of them separately.
+    /// The amounts are in satoshis and the fee rate is in sat/vbyte.
+    ///
+    /// Each recipient is an address, an amount and the labels for that address,
+    /// they can be changed per recipient afterwards with [LabelStorage::set_address_labels].
+    /// The change output is not labeled.
+    ///
+    /// With an external signer nothing is broadcast, the unsigned PSBT is returned
+    /// to be signed by it and then broadcast with [NodeManager::broadcast_psbt].
+    pub async fn send_to_addresses(
+        &self,
+        recipients: Vec<(Address, u64, Vec<String>)>,
+        fee_rate: Option<f32>,
+    ) -> Result<OnChainSend, MutinyError> {
+        self.check_can_spend()?;
+
+        let payments: Vec<(Address, u64)> = recipients
+            .iter()
+            .map(|(address, amount, _)| (address.clone(), *amount))
+            .collect();
+        let sent = if self.external_signer {
+            let psbt = self.wallet.create_batch_psbt(payments, fee_rate)?;
+            OnChainSend::NeedsSignature(psbt)
+        } else {
+            let txid = self.wallet.send_to_many(payments, fee_rate).await?;
+            OnChainSend::Broadcast(txid)
+        };
+
+        for (address, _, labels) in recipients {
+            if !labels.is_empty() {
+                self.storage.set_address_labels(address, labels)?;
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Creates an unsigned PSBT sending to the given address, this can be signed
+    /// by an external signer or with [NodeManager::sign_psbt].
+    /// The amount is in satoshis and the fee rate is in sat/vbyte.
+    ///
+    /// If a fee rate is not provided, one will be used from the fee estimator.
+    pub fn create_psbt(
+        &self,
+        send_to: Address,
+        amount: u64,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        self.wallet.create_psbt(send_to, amount, fee_rate)
+    }
+
+    /// Signs all the inputs of the PSBT that belong to the on-chain wallet.
+    pub fn sign_psbt(
+        &self,
+        psbt: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        self.check_can_sign_onchain()?;
+        self.wallet.sign_psbt(psbt)
+    }
+
+    /// Finalizes and broadcasts a fully signed PSBT.
+    pub async fn broadcast_psbt(
+        &self,
+        psbt: PartiallySignedTransaction,
+        labels: Vec<String>,
+    ) -> Result<Txid, MutinyError> {
+        self.wallet.broadcast_psbt(psbt, labels).await
     }
 
     /// Sweeps all the funds from the wallet to the given address.
@@ -870,6 +3174,7 @@ impl<S: MutinyStorage> NodeManager<S> {
         labels: Vec<String>,
         fee_rate: Option<f32>,
     ) -> Result<Txid, MutinyError> {
+        self.check_can_sign_onchain()?;
         if !send_to.is_valid_for_network(self.network) {
             return Err(MutinyError::IncorrectNetwork(send_to.network));
         }
@@ -877,6 +3182,93 @@ impl<S: MutinyStorage> NodeManager<S> {
         self.wallet.sweep(send_to, labels, fee_rate).await
     }
 
+    /// Bumps the fee of an unconfirmed incoming transaction by spending our
+    /// outputs of it with a higher fee (child pays for parent).
+    /// The fee rate is in sat/vbyte and is the target for the parent and child together.
+    ///
+    /// If a fee rate is not provided, a high priority one will be used from the fee estimator.
+    pub async fn cpfp_accelerate(
+        &self,
+        txid: Txid,
+        fee_rate: Option<f32>,
+    ) -> Result<Txid, MutinyError> {
+        self.check_can_sign_onchain()?;
+        self.wallet.cpfp_accelerate(txid, fee_rate).await
+    }
+
+    /// Switches new on-chain addresses to a new account of the same seed, for when the xpub
+    /// of the current account may have leaked. Funds in the old account are kept, and are
+    /// reported apart from the spendable balance until they are swept
+    /// with [NodeManager::sweep_rotated_onchain_funds].
+    /// Returns [MutinyError::Busy] while the wallet is syncing.
+    pub async fn rotate_onchain_keys(&self) -> Result<OnChainKeyRotation, MutinyError> {
+        self.check_can_sign_onchain()?;
+        let _sync = self.coordinator.try_lock(Resource::Sync)?;
+        let _addresses = self.coordinator.lock(Resource::Addresses).await;
+
+        let rotation = self.wallet.rotate_keys()?;
+        let retired_balance = self.wallet.retired_balance()?;
+        log_info!(self.logger, "Rotated on-chain keys, rotation {rotation}");
+
+        Ok(OnChainKeyRotation {
+            rotation,
+            retired_balance,
+        })
+    }
+
+    /// Sweeps the funds left in accounts from [NodeManager::rotate_onchain_keys] to the current
+    /// account. To keep fees low nothing is swept while the low priority fee rate is above
+    /// `max_fee_rate` (sat/vbyte), so this can be called again later, such as after each sync.
+    ///
+    /// If a max fee rate is not provided, [DEFAULT_ROTATION_SWEEP_MAX_FEE_RATE] is used.
+    /// Returns the txids of the sweeps that were broadcast.
+    pub async fn sweep_rotated_onchain_funds(
+        &self,
+        max_fee_rate: Option<f32>,
+    ) -> Result<Vec<Txid>, MutinyError> {
+        self.check_can_sign_onchain()?;
+        self.wallet
+            .sweep_retired_accounts(max_fee_rate.unwrap_or(DEFAULT_ROTATION_SWEEP_MAX_FEE_RATE))
+            .await
+    }
+
+    /// Scans a mnemonic from another wallet and sweeps any on-chain funds found into this wallet.
+    /// The fee rate is in sat/vbyte.
+    ///
+    /// If no derivation paths are given, the common BIP44/49/84/86 paths and some
+    /// nonstandard variants will be scanned. LND aezeed seeds are not supported.
+    pub async fn sweep_external_mnemonic(
+        &self,
+        mnemonic: Mnemonic,
+        passphrase: Option<String>,
+        paths: Option<Vec<RescuePath>>,
+        fee_rate: Option<f32>,
+    ) -> Result<Vec<RescueResult>, MutinyError> {
+        let paths = paths.unwrap_or_else(|| default_rescue_paths(self.network));
+        sweep_external_mnemonic(
+            &self.wallet,
+            &mnemonic,
+            passphrase,
+            paths,
+            fee_rate,
+            &self.logger,
+        )
+        .await
+    }
+
+    /// Sweeps the on-chain funds of a key from elsewhere, like a paper wallet or a gift,
+    /// into this wallet. The fee rate is in sat/vbyte.
+    ///
+    /// The key can be a WIF private key, which is checked as every standard script type,
+    /// or a master xprv or mnemonic, which are scanned like [NodeManager::sweep_external_mnemonic].
+    pub async fn sweep_external_key(
+        &self,
+        wif_or_seed: String,
+        fee_rate: Option<f32>,
+    ) -> Result<Vec<RescueResult>, MutinyError> {
+        sweep_external_key(&self.wallet, &wif_or_seed, fee_rate, &self.logger).await
+    }
+
     /// Estimates the onchain fee for a transaction sending to the given address.
     /// The amount is in satoshis and the fee rate is in sat/vbyte.
     pub fn estimate_tx_fee(
@@ -949,6 +3341,8 @@ impl<S: MutinyStorage> NodeManager<S> {
                 fee: None,
                 confirmation_time,
                 labels,
+                memo: self.get_memo(tx.txid.to_hex()).unwrap_or_default(),
+                fiat_price_at_time: None,
             };
 
             let block_id = match tx.status.block_hash {
@@ -988,20 +3382,35 @@ impl<S: MutinyStorage> NodeManager<S> {
         let lightning = lightning?;
         let closures = closures?;
         let onchain = self.list_onchain()?;
+        let external = self.list_external_transactions()?;
+        let hidden = self.storage.get_hidden_activity()?;
+        let prices = self.storage.get_historical_prices()?;
 
         let mut activity = Vec::with_capacity(lightning.len() + onchain.len());
-        for ln in lightning {
+        for mut ln in lightning {
             // Only show paid invoices
             if ln.paid {
+                ln.fiat_price_at_time = prices.get(&ln.payment_hash.to_hex()).copied();
                 activity.push(ActivityItem::Lightning(Box::new(ln)));
             }
         }
-        for on in onchain {
+        let onchain_txids: HashSet<Txid> = onchain.iter().map(|tx| tx.txid).collect();
+        for mut on in onchain {
+            on.fiat_price_at_time = prices.get(&on.txid.to_hex()).copied();
             activity.push(ActivityItem::OnChain(on));
         }
         for chan in closures {
             activity.push(ActivityItem::ChannelClosed(chan));
         }
+        // the wallet's own transactions already show up
+        for tx in external {
+            if !onchain_txids.contains(&tx.txid) {
+                activity.push(ActivityItem::External(tx));
+            }
+        }
+
+        // Remove items the user has hidden
+        activity.retain(|item| !hidden.contains(&item.id()));
 
         // Newest first
         activity.sort_by(|a, b| b.cmp(a));
@@ -1009,13 +3418,44 @@ impl<S: MutinyStorage> NodeManager<S> {
         Ok(activity)
     }
 
-    /// Adds labels to the TransactionDetails based on the address labels.
+    /// Returns all the on-chain and lightning activity from the wallet, with paid lightning
+    /// payments below the threshold grouped per counterparty per day.
+    /// The individual payments are included in each group.
+    pub async fn get_aggregated_activity(
+        &self,
+        threshold_sats: u64,
+    ) -> Result<Vec<ActivityItem>, MutinyError> {
+        let activity = self.get_activity().await?;
+        let hidden = self.storage.get_hidden_activity()?;
+        let mut activity = aggregate_dust_payments(activity, threshold_sats);
+        activity.retain(|item| !hidden.contains(&item.id()));
+        Ok(activity)
+    }
+
+    /// Hides an item from the activity, the id is the same as [ActivityItem::id].
+    pub fn hide_activity_item(&self, id: String) -> Result<(), MutinyError> {
+        self.storage.hide_activity_item(id)
+    }
+
+    /// Deletes the failed and expired lightning payments that were last updated before
+    /// `older_than`, a unix timestamp in seconds. Returns the number of payments deleted.
+    pub async fn purge_failed_payments(&self, older_than: u64) -> Result<usize, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let mut count = 0;
+        for node in nodes.values() {
+            count += node.purge_failed_payments(older_than)?;
+        }
+        Ok(count)
+    }
+
+    /// Adds labels to the TransactionDetails based on the address labels, and its memo.
     /// This will panic if the TransactionDetails does not have a transaction.
     /// Make sure you flag `include_raw` when calling `list_transactions` to
     /// ensure that the transaction is included.
     fn add_onchain_labels(
         &self,
         address_labels: &HashMap<String, Vec<String>>,
+        memos: &HashMap<String, String>,
         tx: bdk::TransactionDetails,
     ) -> TransactionDetails {
         // find the first output address that has a label
@@ -1036,6 +3476,7 @@ impl<S: MutinyStorage> NodeManager<S> {
 
         TransactionDetails {
             labels,
+            memo: memos.get(&tx.txid.to_hex()).cloned(),
             ..tx.into()
         }
     }
@@ -1046,9 +3487,10 @@ impl<S: MutinyStorage> NodeManager<S> {
         let mut txs = self.wallet.list_transactions(true)?;
         txs.sort();
         let address_labels = self.get_address_labels()?;
+        let memos = self.get_memos()?;
         let txs = txs
             .into_iter()
-            .map(|tx| self.add_onchain_labels(&address_labels, tx))
+            .map(|tx| self.add_onchain_labels(&address_labels, &memos, tx))
             .collect();
 
         Ok(txs)
@@ -1059,17 +3501,52 @@ impl<S: MutinyStorage> NodeManager<S> {
         match self.wallet.get_transaction(txid, true)? {
             Some(tx) => {
                 let address_labels = self.get_address_labels()?;
-                let tx_details = self.add_onchain_labels(&address_labels, tx);
+                let memos = self.get_memos()?;
+                let tx_details = self.add_onchain_labels(&address_labels, &memos, tx);
                 Ok(Some(tx_details))
             }
             None => Ok(None),
         }
     }
 
+    /// Exports the address and transaction labels in the BIP329 JSONL format,
+    /// so they can be imported by other wallets. Each record's label is plain text,
+    /// our labels written together like "rent, march", since BIP329 has one label per record.
+    ///
+    /// Memos are private, so they are only added to the transaction labels when
+    /// `include_memos` is set. Invoices have no BIP329 type, so their labels are not exported.
+    pub fn export_bip329_labels(&self, include_memos: bool) -> Result<String, MutinyError> {
+        let tx_labels: Vec<(Txid, Vec<String>)> = self
+            .list_onchain()?
+            .into_iter()
+            .map(|tx| {
+                let mut labels = tx.labels;
+                if let Some(memo) = tx.memo.filter(|_| include_memos) {
+                    labels.push(memo);
+                }
+                (tx.txid, labels)
+            })
+            .collect();
+
+        export_bip329(&self.get_address_labels()?, &tx_labels)
+    }
+
+    /// Imports labels from the BIP329 JSONL format, they are added to our existing labels.
+    /// Each record's label is plain text, so it is added as a single label.
+    ///
+    /// Transactions have no labels of their own, so their labels are only imported,
+    /// as the memo of transactions that don't have one yet, when `memos` is set.
+    ///
+    /// Returns the number of labels imported, records we have no use for are skipped.
+    pub fn import_bip329_labels(&self, jsonl: &str, memos: bool) -> Result<usize, MutinyError> {
+        import_bip329(self, jsonl, self.network, memos)
+    }
+
     /// Gets the current balance of the wallet.
     /// This includes both on-chain and lightning funds.
     ///
     /// This will not include any funds in an unconfirmed lightning channel.
+    /// Funds on rotated on-chain keys are not spendable and only count towards `retired`.
     pub async fn get_balance(&self) -> Result<MutinyBalance, MutinyError> {
         let onchain = self.wallet.wallet.try_read()?.get_balance();
 
@@ -1115,11 +3592,16 @@ impl<S: MutinyStorage> NodeManager<S> {
             })
             .sum();
 
+        // funds from rotated keys are still ours but can't be spent until they are swept
+        let retired = self.wallet.retired_balance()?;
+
         Ok(MutinyBalance {
             confirmed: onchain.confirmed + onchain.trusted_pending,
             unconfirmed: onchain.untrusted_pending + onchain.immature,
-            lightning: lightning_msats / 1_000,
+            lightning: utils::msats_to_sats(lightning_msats),
+            lightning_msats,
             force_close,
+            retired,
         })
     }
 
@@ -1164,26 +3646,132 @@ impl<S: MutinyStorage> NodeManager<S> {
         if self.stop.load(Ordering::Relaxed) {
             return Ok(());
         }
+        let _guard = self.coordinator.lock(Resource::Sync).await;
 
         // Sync ldk first because it may broadcast transactions
         // to addresses that are in our bdk wallet. This way
         // they are found on this iteration of syncing instead
         // of the next one.
+        self.sync_status.set_stage(SyncStage::Lightning);
         if let Err(e) = self.sync_ldk().await {
             log_error!(self.logger, "Failed to sync ldk: {e}");
+            self.sync_status.failed(e.to_string());
             return Err(e);
         }
 
         // sync bdk wallet
+        self.sync_status.set_stage(SyncStage::OnChain);
         match self.wallet.sync().await {
-            Ok(()) => Ok(log_info!(self.logger, "We are synced!")),
+            Ok(()) => {
+                self.sync_status.synced();
+                self.notifier.notify(MutinyEvent::SyncCompleted {
+                    timestamp: utils::now().as_secs(),
+                });
+                Ok(log_info!(self.logger, "We are synced!"))
+            }
             Err(e) => {
                 log_error!(self.logger, "Failed to sync on-chain wallet: {e}");
+                self.sync_status.failed(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Gets the block height the wallet was created at, this is only known for wallets
+    /// created with a new seed or if it was set with [NodeManager::set_wallet_birthday].
+    pub fn get_wallet_birthday(&self) -> Result<Option<u32>, MutinyError> {
+        self.storage.get_wallet_birthday()
+    }
+
+    /// Sets the block height the wallet was created at, for seeds that were restored.
+    /// Our scans look up each address's whole history, this is kept for exporting the seed
+    /// to wallets that scan blocks, so they don't have to start from the genesis block.
+    pub fn set_wallet_birthday(&self, height: u32) -> Result<(), MutinyError> {
+        self.storage.set_wallet_birthday(height)
+    }
+
+    /// Returns how many receive and change addresses the on-chain wallet has handed out.
+    pub fn get_revealed_addresses(&self) -> Result<RevealedAddresses, MutinyError> {
+        self.wallet.revealed_addresses()
+    }
+
+    /// Rescans the on-chain wallet with a much wider stop gap than a regular sync,
+    /// for seeds that were used elsewhere with deep address gaps. The whole history of
+    /// each address is looked up, so there is no block height to start from.
+    /// The progress is reported through [NodeManager::subscribe_sync_status].
+    pub async fn full_rescan(&self) -> Result<(), MutinyError> {
+        let _guard = self.coordinator.lock(Resource::Sync).await;
+        log_info!(self.logger, "Rescanning on-chain wallet");
+
+        let res = self
+            .wallet
+            .rescan(|percent| self.sync_status.set_progress(SyncStage::Rescan, percent))
+            .await;
+
+        match res {
+            Ok(()) => {
+                self.sync_status.synced();
+                log_info!(self.logger, "Finished rescanning on-chain wallet");
+                Ok(())
+            }
+            Err(e) => {
+                log_error!(self.logger, "Failed to rescan on-chain wallet: {e}");
+                self.sync_status.failed(e.to_string());
                 Err(e)
             }
         }
     }
 
+    /// Returns the progress of syncing the wallet with the blockchain.
+    pub fn get_sync_status(&self) -> SyncStatus {
+        self.sync_status.status()
+    }
+
+    /// Returns a stream of every change to the sync status, starting with the current one.
+    pub fn subscribe_sync_status(&self) -> UnboundedReceiver<SyncStatus> {
+        self.sync_status.subscribe()
+    }
+
+    /// Returns a stream of the events that happen in the wallet from now on, such as
+    /// payments, channel opens and closes, syncs, peer connections and transaction confirmations.
+    pub fn subscribe_events(&self) -> UnboundedReceiver<MutinyEvent> {
+        self.notifier.subscribe()
+    }
+
+    /// Calls the callback for every event that happens in the wallet,
+    /// until the node manager is stopped.
+    pub fn watch_events<F>(nm: Arc<NodeManager<S>>, on_event: F)
+    where
+        F: Fn(MutinyEvent) + 'static,
+    {
+        let mut events = nm.subscribe_events();
+        utils::spawn(async move {
+            while let Some(event) = events.next().await {
+                if nm.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                on_event(event);
+            }
+        });
+    }
+
+    /// Calls the callback with the current sync status and every time it changes,
+    /// until the node manager is stopped.
+    pub fn watch_sync_status<F>(nm: Arc<NodeManager<S>>, on_update: F)
+    where
+        F: Fn(SyncStatus) + 'static,
+    {
+        let mut updates = nm.subscribe_sync_status();
+        utils::spawn(async move {
+            while let Some(status) = updates.next().await {
+                if nm.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                on_update(status);
+            }
+        });
+    }
+
     /// Gets a fee estimate for an average priority transaction.
     /// Value is in sat/vbyte.
     pub fn estimate_fee_normal(&self) -> u32 {
@@ -1200,6 +3788,7 @@ impl<S: MutinyStorage> NodeManager<S> {
 
     /// Creates a new lightning node and adds it to the manager.
     pub async fn new_node(&self) -> Result<NodeIdentity, MutinyError> {
+        self.check_can_spend()?;
         create_new_node_from_node_manager(self).await
     }
 
@@ -1251,13 +3840,66 @@ impl<S: MutinyStorage> NodeManager<S> {
         Ok(peers)
     }
 
+    /// Finds the address a lightning node announced, as `host:port`.
+    ///
+    /// Our network graph is checked first, then a lightning explorer for
+    /// nodes we don't have gossip for.
+    pub async fn find_peer_address(
+        &self,
+        pubkey: &PublicKey,
+    ) -> Result<Option<String>, MutinyError> {
+        if let Some(address) = gossip::find_graph_address(self.gossip_sync.network_graph(), pubkey)
+        {
+            return Ok(Some(address));
+        }
+
+        let url = match gossip::explorer_node_url(self.network, pubkey) {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::ConnectionFailed)?;
+
+        // the explorer doesn't know about the node
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let node: gossip::ExplorerNode = response
+            .error_for_status()
+            .map_err(|_| MutinyError::ConnectionFailed)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::ConnectionFailed)?;
+        Ok(node.address())
+    }
+
     /// Attempts to connect to a peer from the selected node.
+    ///
+    /// The connection string is `pubkey@host:port`, or just the pubkey to
+    /// connect to the address found with [NodeManager::find_peer_address].
     pub async fn connect_to_peer(
         &self,
         self_node_pubkey: &PublicKey,
         connection_string: &str,
         label: Option<String>,
     ) -> Result<(), MutinyError> {
+        let connection_string = match PublicKey::from_str(connection_string.trim()) {
+            Ok(pubkey) => {
+                let address = self
+                    .find_peer_address(&pubkey)
+                    .await?
+                    .ok_or(MutinyError::PeerInfoParseFailed)?;
+                format!("{pubkey}@{address}")
+            }
+            Err(_) => connection_string.to_string(),
+        };
+        let connection_string = connection_string.as_str();
+
         if let Some(node) = self.nodes.lock().await.get(self_node_pubkey) {
             let connect_info = PubkeyConnectionInfo::new(connection_string)?;
             let label_opt = label.filter(|s| !s.is_empty()); // filter out empty strings
@@ -1328,6 +3970,20 @@ impl<S: MutinyStorage> NodeManager<S> {
         Ok(())
     }
 
+    /// Sets how our nodes reconnect to a peer after losing the connection,
+    /// `None` goes back to the default policy.
+    ///
+    /// Peers that are always reconnected to are retried with an exponential backoff,
+    /// other peers are only connected to with [NodeManager::connect_to_peer].
+    pub fn set_peer_reconnect_policy(
+        &self,
+        node_id: &NodeId,
+        policy: Option<ReconnectPolicy>,
+    ) -> Result<(), MutinyError> {
+        gossip::set_peer_reconnect_policy(&self.storage, node_id, policy)?;
+        Ok(())
+    }
+
     // all values in sats
 
     /// Creates a lightning invoice. The amount should be in satoshis.
@@ -1340,9 +3996,32 @@ impl<S: MutinyStorage> NodeManager<S> {
         &self,
         amount: Option<u64>,
         labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        self.create_invoice_with_lsp(amount, labels, None).await
+    }
+
+    /// Creates a lightning invoice wrapped by the given LSP, one of [NodeManager::list_lsps].
+    /// If no LSP is given the best one is picked, same as [NodeManager::create_invoice].
+    pub async fn create_invoice_with_lsp(
+        &self,
+        amount: Option<u64>,
+        labels: Vec<String>,
+        lsp_url: Option<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        self.create_invoice_with_expiry(amount, labels, lsp_url, DEFAULT_INVOICE_EXPIRY_SECS)
+            .await
+    }
+
+    /// Creates a lightning invoice that can be paid for the given number of seconds
+    async fn create_invoice_with_expiry(
+        &self,
+        amount: Option<u64>,
+        labels: Vec<String>,
+        lsp_url: Option<String>,
+        expiry_secs: u32,
     ) -> Result<MutinyInvoice, MutinyError> {
         let nodes = self.nodes.lock().await;
-        let use_phantom = nodes.len() > 1 && self.lsp_clients.is_empty();
+        let use_phantom = nodes.len() > 1 && self.lsp_clients().await.is_empty();
         if nodes.len() == 0 {
             return Err(MutinyError::InvoiceCreationFailed);
         }
@@ -1363,21 +4042,180 @@ impl<S: MutinyStorage> NodeManager<S> {
         } else {
             return Err(MutinyError::WalletOperationFailed);
         };
-        let invoice = first_node
-            .create_invoice(amount, labels, route_hints)
+        let (invoice, degraded_hints, lsp_selection) = first_node
+            .create_invoice(amount, labels, route_hints, lsp_url.as_deref(), expiry_secs)
             .await?;
 
+        // the fee the LSP takes from the payment
+        let fees_paid_msats = lsp_selection.as_ref().map(|s| s.fee_msat);
+        Ok(MutinyInvoice {
+            degraded_hints,
+            lsp_selection,
+            fees_paid: fees_paid_msats.map(utils::msats_to_sats_round_up),
+            fees_paid_msats,
+            ..invoice.into()
+        })
+    }
+
+    /// Gets what receiving the amount will cost in LSP fees, before creating the invoice
+    /// with [NodeManager::create_invoice]. The amount should be in satoshis.
+    ///
+    /// Returns None if no LSP is used, in which case there is no fee.
+    pub async fn get_lsp_fee_quote(&self, amount: u64) -> Result<Option<LspFeeQuote>, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let first_node = nodes
+            .values()
+            .next()
+            .ok_or(MutinyError::InvoiceCreationFailed)?;
+        // phantom invoices don't use an LSP
+        if nodes.len() > 1 && self.lsp_clients().await.is_empty() {
+            return Ok(None);
+        }
+        first_node.get_lsp_fee_quote(amount).await
+    }
+
+    /// The LSPs we can use to wrap invoices.
+    pub(crate) async fn lsp_clients(&self) -> Vec<LspClient> {
+        self.lsp_clients.lock().await.clone()
+    }
+
+    /// Lists the urls of the LSPs we use.
+    pub async fn list_lsps(&self) -> Vec<String> {
+        self.lsp_clients()
+            .await
+            .into_iter()
+            .map(|lsp| lsp.url)
+            .collect()
+    }
+
+    /// Adds an LSP without recreating the wallet, it is saved for the next start up.
+    /// LSPs set in the config are used instead of the saved ones when the wallet starts.
+    ///
+    /// Nodes that don't have an LSP are assigned this one.
+    pub async fn add_lsp(&self, url: String) -> Result<(), MutinyError> {
+        let url = url.trim().to_string();
+        if self.list_lsps().await.contains(&url) {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let client = LspClient::new(&url, self.http_client.clone())
+            .await
+            .map_err(|e| {
+                log_warn!(self.logger, "Error starting up lsp client: {e}");
+                MutinyError::LspFailure
+            })?;
+        let urls = {
+            let mut lsp_clients = self.lsp_clients.lock().await;
+            lsp_clients.push(client.clone());
+            lsp_clients.iter().map(|lsp| lsp.url.clone()).collect()
+        };
+        self.storage.set_lsp_urls(urls)?;
+
+        self.reassign_lsps(|current| current.is_none(), Some(client))
+            .await
+    }
+
+    /// Removes an LSP, it is saved for the next start up.
+    /// LSPs set in the config are used instead of the saved ones when the wallet starts.
+    ///
+    /// Nodes that used this LSP are assigned the first remaining one, if any.
+    pub async fn remove_lsp(&self, url: String) -> Result<(), MutinyError> {
+        let remaining = {
+            let mut lsp_clients = self.lsp_clients.lock().await;
+            let len = lsp_clients.len();
+            lsp_clients.retain(|lsp| lsp.url != url);
+            if lsp_clients.len() == len {
+                return Err(MutinyError::NotFound);
+            }
+            lsp_clients.clone()
+        };
+        self.storage
+            .set_lsp_urls(remaining.iter().map(|lsp| lsp.url.clone()).collect())?;
+
+        self.reassign_lsps(
+            |current| current.map_or(false, |lsp| lsp.url == url),
+            remaining.first().cloned(),
+        )
+        .await
+    }
+
+    /// Switches the nodes whose current LSP matches to the given one and saves their [NodeIndex].
+    async fn reassign_lsps(
+        &self,
+        matches: impl Fn(Option<&LspClient>) -> bool,
+        lsp: Option<LspClient>,
+    ) -> Result<(), MutinyError> {
+        // lock the node storage first, same as when creating a node
+        let mut node_storage = self.node_storage.lock().await;
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            if matches(node.lsp_client().as_ref()) {
+                node.set_lsp_client(lsp.clone())?;
+                node_storage
+                    .nodes
+                    .insert(node._uuid.clone(), node.node_index());
+            }
+        }
+        self.storage.insert_nodes(node_storage.clone())
+    }
+
+    /// Creates a hodl invoice for a payment hash, the preimage is not needed until
+    /// the payment is settled. The amount should be in satoshis.
+    ///
+    /// When a payment arrives it is held and a [MutinyEvent::HodlInvoiceAccepted] is sent,
+    /// it should be settled with [NodeManager::settle_invoice] or cancelled with
+    /// [NodeManager::cancel_invoice] before the payment expires, otherwise it is failed back.
+    pub async fn create_hodl_invoice(
+        &self,
+        amount: Option<u64>,
+        payment_hash: sha256::Hash,
+        labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let node = nodes
+            .values()
+            .next()
+            .ok_or(MutinyError::InvoiceCreationFailed)?;
+        let invoice = node.create_hodl_invoice(amount, payment_hash, labels)?;
         Ok(invoice.into())
     }
 
+    /// Settles a held payment to a hodl invoice with its preimage
+    pub async fn settle_invoice(&self, preimage: [u8; 32]) -> Result<(), MutinyError> {
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            match node.settle_hodl_invoice(preimage) {
+                Err(MutinyError::NotFound) => continue,
+                res => return res,
+            }
+        }
+        Err(MutinyError::NotFound)
+    }
+
+    /// Cancels a hodl invoice, failing back any held payment
+    pub async fn cancel_invoice(&self, payment_hash: &sha256::Hash) -> Result<(), MutinyError> {
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            match node.cancel_hodl_invoice(payment_hash) {
+                Err(MutinyError::NotFound) => continue,
+                res => return res,
+            }
+        }
+        Err(MutinyError::NotFound)
+    }
+
     /// Pays a lightning invoice from the selected node.
     /// An amount should only be provided if the invoice does not have an amount.
     /// The amount should be in satoshis.
+    ///
+    /// The fee can be limited, the outgoing channels picked and the splitting into parts
+    /// limited with [PaymentOptions]. The parts that were sent are in [MutinyInvoice::parts].
     pub async fn pay_invoice(
         &self,
         from_node: &PublicKey,
         invoice: &Invoice,
         amt_sats: Option<u64>,
+        options: PaymentOptions,
         labels: Vec<String>,
     ) -> Result<MutinyInvoice, MutinyError> {
         if invoice.network() != self.network {
@@ -1385,41 +4223,170 @@ impl<S: MutinyStorage> NodeManager<S> {
         }
 
         let node = self.get_node(from_node).await?;
-        node.pay_invoice_with_timeout(invoice, amt_sats, None, labels)
+        node.pay_invoice_with_timeout(invoice, amt_sats, options, None, labels)
             .await
     }
 
-    /// Sends a spontaneous payment to a node from the selected node.
-    /// The amount should be in satoshis.
-    pub async fn keysend(
+    /// Pays a lightning invoice from whichever of our nodes can pay it for the lowest fee,
+    /// so a wallet with several nodes can pay like it has one balance.
+    /// Otherwise the same as [NodeManager::pay_invoice].
+    ///
+    /// A payment can't be split across nodes, if no single node has the outbound
+    /// liquidity for it this fails with [MutinyError::InsufficientBalance].
+    /// Outgoing channels can't be picked since the node isn't known up front.
+    pub async fn pay_invoice_from_best_node(
         &self,
-        from_node: &PublicKey,
-        to_node: PublicKey,
-        amt_sats: u64,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+        options: PaymentOptions,
         labels: Vec<String>,
     ) -> Result<MutinyInvoice, MutinyError> {
-        let node = self.get_node(from_node).await?;
-        log_debug!(self.logger, "Keysending to {to_node}");
-        node.keysend_with_timeout(to_node, amt_sats, labels, None)
-            .await
-    }
-
-    /// Decodes a lightning invoice into useful information.
-    /// Will return an error if the invoice is for a different network.
-    pub async fn decode_invoice(&self, invoice: Invoice) -> Result<MutinyInvoice, MutinyError> {
         if invoice.network() != self.network {
             return Err(MutinyError::IncorrectNetwork(invoice.network()));
         }
+        if options.outgoing_channels.is_some() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
 
-        Ok(invoice.into())
+        let node = self.select_node_for_invoice(invoice, amt_sats).await?;
+        log_debug!(self.logger, "Paying invoice from node {}", node.pubkey);
+        node.pay_invoice_with_timeout(invoice, amt_sats, options, None, labels)
+            .await
     }
 
-    /// Calls upon a LNURL to get the parameters for it.
-    /// This contains what kind of LNURL it is (pay, withdrawal, auth, etc).
-    // todo revamp LnUrlParams to be well designed
-    pub async fn decode_lnurl(&self, lnurl: LnUrl) -> Result<LnUrlParams, MutinyError> {
-        // handle LNURL-AUTH
-        if lnurl.is_lnurl_auth() {
+    /// Finds the node with the cheapest route for the invoice, scored by the router,
+    /// among the nodes that have enough outbound liquidity.
+    async fn select_node_for_invoice(
+        &self,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<Arc<Node<S>>, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let mut best: Option<(Arc<Node<S>>, u64)> = None;
+        let mut last_error = MutinyError::InsufficientBalance;
+        for node in nodes.values() {
+            match node.dry_run_payment(invoice, amt_sats) {
+                Ok(estimate) => {
+                    if best
+                        .as_ref()
+                        .map_or(true, |(_, fee)| estimate.fee_msats < *fee)
+                    {
+                        best = Some((node.clone(), estimate.fee_msats));
+                    }
+                }
+                // not having the liquidity shouldn't hide why the other nodes failed
+                Err(MutinyError::InsufficientBalance) => {}
+                Err(e) => last_error = e,
+            }
+        }
+        best.map(|(node, _)| node).ok_or(last_error)
+    }
+
+    /// Finds a route for a lightning invoice from the selected node without paying it.
+    /// This can be used to show the expected fee before the user confirms the payment.
+    /// An amount should only be provided if the invoice does not have an amount.
+    pub async fn dry_run_payment(
+        &self,
+        from_node: &PublicKey,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<PaymentEstimate, MutinyError> {
+        if invoice.network() != self.network {
+            return Err(MutinyError::IncorrectNetwork(invoice.network()));
+        }
+
+        let node = self.get_node(from_node).await?;
+        node.dry_run_payment(invoice, amt_sats)
+    }
+
+    /// Estimates the routing fee range and hop count of paying a lightning invoice
+    /// from the selected node, so it can be shown before the user confirms the payment.
+    /// An amount should only be provided if the invoice does not have an amount.
+    pub async fn estimate_payment_fee(
+        &self,
+        from_node: &PublicKey,
+        invoice: &Invoice,
+        amt_sats: Option<u64>,
+    ) -> Result<FeeEstimate, MutinyError> {
+        if invoice.network() != self.network {
+            return Err(MutinyError::IncorrectNetwork(invoice.network()));
+        }
+
+        let node = self.get_node(from_node).await?;
+        node.estimate_payment_fee(invoice, amt_sats)
+    }
+
+    /// Sends a spontaneous payment to a node from the selected node.
+    /// The amount should be in satoshis.
+    ///
+    /// The fees can be limited like with [NodeManager::pay_invoice].
+    pub async fn keysend(
+        &self,
+        from_node: &PublicKey,
+        to_node: PublicKey,
+        amt_sats: u64,
+        max_fee: Option<MaxFee>,
+        labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let node = self.get_node(from_node).await?;
+        log_debug!(self.logger, "Keysending to {to_node}");
+        node.keysend_with_timeout(to_node, amt_sats, max_fee, labels, None)
+            .await
+    }
+
+    /// Sends a spontaneous payment from the node with the most outbound liquidity,
+    /// otherwise the same as [NodeManager::keysend].
+    ///
+    /// Fails with [MutinyError::InsufficientBalance] if no single node can send the amount.
+    pub async fn keysend_from_best_node(
+        &self,
+        to_node: PublicKey,
+        amt_sats: u64,
+        max_fee: Option<MaxFee>,
+        labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let node = {
+            let nodes = self.nodes.lock().await;
+            nodes
+                .values()
+                .map(|n| (n, n.usable_outbound_msat()))
+                .filter(|(_, outbound)| *outbound >= amt_sats * 1_000)
+                .max_by_key(|(_, outbound)| *outbound)
+                .map(|(n, _)| n.clone())
+                .ok_or(MutinyError::InsufficientBalance)?
+        };
+        log_debug!(
+            self.logger,
+            "Keysending to {to_node} from node {}",
+            node.pubkey
+        );
+        node.keysend_with_timeout(to_node, amt_sats, max_fee, labels, None)
+            .await
+    }
+
+    /// Decodes a lightning invoice into useful information.
+    /// Will return an error if the invoice is for a different network.
+    pub async fn decode_invoice(&self, invoice: Invoice) -> Result<MutinyInvoice, MutinyError> {
+        if invoice.network() != self.network {
+            return Err(MutinyError::IncorrectNetwork(invoice.network()));
+        }
+
+        Ok(invoice.into())
+    }
+
+    /// Parses any kind of payment string a user may give us, this can be a
+    /// BOLT11 invoice, BIP21 URI, on-chain address, LNURL, lightning address or node pubkey.
+    /// Will return an error if the invoice or address is for a different network.
+    pub fn parse_payment_request(&self, str: &str) -> Result<PaymentRequest, MutinyError> {
+        PaymentRequest::parse(str, self.network)
+    }
+
+    /// Calls upon a LNURL to get the parameters for it.
+    /// This contains what kind of LNURL it is (pay, withdrawal, auth, etc).
+    // todo revamp LnUrlParams to be well designed
+    pub async fn decode_lnurl(&self, lnurl: LnUrl) -> Result<LnUrlParams, MutinyError> {
+        // handle LNURL-AUTH
+        if lnurl.is_lnurl_auth() {
             return Ok(LnUrlParams {
                 max: 0,
                 min: 0,
@@ -1452,26 +4419,151 @@ impl<S: MutinyStorage> NodeManager<S> {
 
     /// Calls upon a LNURL and pays it.
     /// This will fail if the LNURL is not a LNURL pay.
+    ///
+    /// A comment (LUD-12) and payer data (LUD-18) can be sent along if the service
+    /// accepts them, only the payer data it asks for is sent. They are saved with
+    /// the payment, see [NodeManager::get_lnurl_pay_metadata].
+    ///
+    /// The fees can be limited like with [NodeManager::pay_invoice].
+    #[allow(clippy::too_many_arguments)]
     pub async fn lnurl_pay(
         &self,
         from_node: &PublicKey,
         lnurl: &LnUrl,
         amount_sats: u64,
+        comment: Option<String>,
+        payer_data: Option<PayerData>,
+        max_fee: Option<MaxFee>,
         labels: Vec<String>,
     ) -> Result<MutinyInvoice, MutinyError> {
-        let response = self.lnurl_client.make_request(&lnurl.url).await?;
+        // the lnurl client doesn't know about comments, payer data or verify urls,
+        // so we fetch the pay response once and read them from the raw json
+        let json: serde_json::Value = self
+            .http_client
+            .get(&lnurl.url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?;
+        let extras: PayResponseExtras =
+            serde_json::from_value(json.clone()).map_err(|_| MutinyError::LnUrlFailure)?;
+
+        let pay = match lnurl::decode_ln_url_response_from_json(json)? {
+            LnUrlResponse::LnUrlPayResponse(pay) => pay,
+            LnUrlResponse::LnUrlWithdrawResponse(_) => {
+                return Err(MutinyError::IncorrectLnUrlFunction)
+            }
+            LnUrlResponse::LnUrlChannelResponse(_) => {
+                return Err(MutinyError::IncorrectLnUrlFunction)
+            }
+        };
 
-        match response {
-            LnUrlResponse::LnUrlPayResponse(pay) => {
-                let msats = amount_sats * 1000;
-                let invoice = self.lnurl_client.get_invoice(&pay, msats).await?;
+        let msats = amount_sats * 1000;
+        validate_pay_amount(msats, pay.min_sendable, pay.max_sendable)?;
 
-                self.pay_invoice(from_node, &invoice.invoice(), None, labels)
-                    .await
+        let comment = comment.filter(|c| !c.is_empty());
+        if let Some(comment) = comment.as_ref() {
+            extras.validate_comment(comment)?;
+        }
+        let payer_data = payer_data
+            .map(|p| extras.filter_payer_data(&p))
+            .transpose()?;
+
+        let url = pay_callback_url(
+            &pay.callback,
+            msats,
+            comment.as_deref(),
+            payer_data.as_ref(),
+        )?;
+        let callback: PayCallbackResponse = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?;
+
+        let Some(pr) = callback.pr else {
+            log_error!(
+                self.logger,
+                "LNURL pay callback failed: {}",
+                callback.reason.unwrap_or_default()
+            );
+            return Err(MutinyError::LnUrlFailure);
+        };
+        let invoice: Invoice = pr.parse()?;
+        if invoice.amount_milli_satoshis() != Some(msats) {
+            return Err(MutinyError::LnUrlFailure);
+        }
+
+        let mut paid = self
+            .pay_invoice(
+                from_node,
+                &invoice,
+                None,
+                PaymentOptions {
+                    max_fee,
+                    ..Default::default()
+                },
+                labels,
+            )
+            .await?;
+
+        // the preimage alone doesn't show a custodial service credited the payment,
+        // so ask it to confirm the invoice was settled (LUD-21)
+        if let Some(verify_url) = callback.verify.as_ref() {
+            paid.lnurl_verified_preimage =
+                verify_lnurl_payment(&self.http_client, verify_url, &paid.payment_hash).await;
+            if paid.lnurl_verified_preimage.is_none() {
+                log_warn!(
+                    self.logger,
+                    "LNURL pay service did not confirm the payment was settled"
+                );
             }
-            LnUrlResponse::LnUrlWithdrawResponse(_) => Err(MutinyError::IncorrectLnUrlFunction),
-            LnUrlResponse::LnUrlChannelResponse(_) => Err(MutinyError::IncorrectLnUrlFunction),
         }
+
+        let metadata = LnUrlPayMetadata {
+            comment,
+            payer_data,
+            verify_url: callback.verify,
+            verified_preimage: paid.lnurl_verified_preimage.clone(),
+        };
+        if metadata != LnUrlPayMetadata::default() {
+            if let Err(e) = self
+                .storage
+                .persist_lnurl_pay_metadata(&paid.payment_hash, metadata)
+            {
+                log_error!(self.logger, "Failed to save LNURL pay metadata: {e}");
+            }
+        }
+
+        Ok(paid)
+    }
+
+    /// Records something a connected app did with the wallet
+    pub fn audit_integration_action(&self, record: IntegrationAuditRecord) {
+        if let Err(e) = self.storage.append_integration_audit_record(&record) {
+            log_error!(self.logger, "Failed to save integration audit record: {e}");
+        }
+    }
+
+    /// Returns everything connected apps, like Nostr Wallet Connect clients,
+    /// have done with the wallet, newest first.
+    pub fn get_integration_audit_log(&self) -> Result<Vec<IntegrationAuditRecord>, MutinyError> {
+        self.storage.get_integration_audit_log()
+    }
+
+    /// Gets the comment and payer data sent along with a LNURL-pay payment,
+    /// and the preimage the service confirmed if it supports LNURL-verify.
+    pub fn get_lnurl_pay_metadata(
+        &self,
+        payment_hash: &sha256::Hash,
+    ) -> Result<Option<LnUrlPayMetadata>, MutinyError> {
+        self.storage.get_lnurl_pay_metadata(payment_hash)
     }
 
     /// Calls upon a LNURL and withdraws from it.
@@ -1505,14 +4597,251 @@ impl<S: MutinyStorage> NodeManager<S> {
         }
     }
 
+    /// Calls upon a LNURL and asks the service to open a channel to the selected node (LUD-2).
+    /// This will fail if the LNURL is not a LNURL channel.
+    ///
+    /// We connect to the node the service gave us and then ask it to open a private channel,
+    /// which is accepted when its open channel request comes in. This returns once the service
+    /// agreed to open it, the channel itself shows up after it is funded.
+    pub async fn lnurl_channel(
+        &self,
+        from_node: &PublicKey,
+        lnurl: &LnUrl,
+    ) -> Result<(), MutinyError> {
+        let response = self.lnurl_client.make_request(&lnurl.url).await?;
+
+        let channel = match response {
+            LnUrlResponse::LnUrlChannelResponse(channel) => channel,
+            LnUrlResponse::LnUrlPayResponse(_) => return Err(MutinyError::IncorrectLnUrlFunction),
+            LnUrlResponse::LnUrlWithdrawResponse(_) => {
+                return Err(MutinyError::IncorrectLnUrlFunction)
+            }
+        };
+
+        let nodes = self.nodes.lock().await;
+        let node = nodes.get(from_node).ok_or(MutinyError::NotFound)?;
+
+        let connect_info = PubkeyConnectionInfo::new(&channel.uri)?;
+        let peer = connect_info.pubkey;
+        node.connect_peer(connect_info, None).await?;
+        node.expect_lnurl_channel(peer);
+
+        let mut url = Url::parse(&channel.callback)?;
+        url.query_pairs_mut()
+            .append_pair("k1", &channel.k1)
+            .append_pair("remoteid", &node.pubkey.to_hex())
+            .append_pair("private", "1");
+
+        let res: Result<Response, MutinyError> = async {
+            self.http_client
+                .get(url)
+                .send()
+                .await
+                .map_err(|_| MutinyError::LnUrlFailure)?
+                .json()
+                .await
+                .map_err(|_| MutinyError::LnUrlFailure)
+        }
+        .await;
+
+        match res {
+            Ok(Response::Ok { .. }) => {
+                log_info!(self.logger, "LNURL channel requested from {peer}");
+                Ok(())
+            }
+            Ok(Response::Error { reason }) => {
+                log_error!(self.logger, "LNURL channel request failed: {reason}");
+                node.cancel_lnurl_channel(&peer);
+                Err(MutinyError::LnUrlFailure)
+            }
+            Err(e) => {
+                node.cancel_lnurl_channel(&peer);
+                Err(e)
+            }
+        }
+    }
+
+    /// Withdraws everything from a LNURL-withdraw endpoint, such as a custodial wallet,
+    /// to help migrate to self-custody.
+    ///
+    /// This repeatedly withdraws the most the service allows per request, limited by our
+    /// inbound liquidity, and waits for each invoice to be paid before requesting the next.
+    /// It stops once the service is empty, we can't receive any more, or a withdrawal fails.
+    /// Only a failure of the first withdrawal is returned as an error.
+    pub async fn drain_lnurl_withdraw(
+        &self,
+        lnurl: &LnUrl,
+        labels: Vec<String>,
+    ) -> Result<LnUrlDrainResult, MutinyError> {
+        self.check_can_spend()?;
+
+        let mut result = LnUrlDrainResult {
+            withdrawn_sats: 0,
+            invoices: vec![],
+            remaining_sats: 0,
+        };
+
+        for _ in 0..MAX_LNURL_DRAIN_WITHDRAWALS {
+            let withdraw = match self.lnurl_client.make_request(&lnurl.url).await? {
+                LnUrlResponse::LnUrlWithdrawResponse(withdraw) => withdraw,
+                _ => return Err(MutinyError::IncorrectLnUrlFunction),
+            };
+
+            let max_sats = withdraw.max_withdrawable / 1_000;
+            // round the minimum up so we never request less than it
+            let min_sats = ((withdraw.min_withdrawable.unwrap_or(0) + 999) / 1_000).max(1);
+            result.remaining_sats = max_sats;
+
+            let amount_sats = match self.max_receivable_sats().await {
+                Some(capacity) => capacity.min(max_sats),
+                None => max_sats,
+            };
+            if amount_sats < min_sats {
+                log_info!(
+                    self.logger,
+                    "Finished draining LNURL, {max_sats} sats left, can receive {amount_sats} sats"
+                );
+                break;
+            }
+
+            let attempt = self
+                .withdraw_and_wait(&withdraw, amount_sats, labels.clone())
+                .await;
+            match attempt {
+                Ok(invoice) => {
+                    result.withdrawn_sats += amount_sats;
+                    result.remaining_sats = max_sats - amount_sats;
+                    result.invoices.push(invoice);
+                }
+                Err(e) if result.invoices.is_empty() => return Err(e),
+                Err(e) => {
+                    log_warn!(self.logger, "Stopped draining LNURL: {e}");
+                    break;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Summarizes how much we can receive over lightning across all our channels.
+    pub async fn get_inbound_liquidity(&self) -> Result<InboundLiquidity, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let channels: Vec<ChannelDetails> = nodes
+            .values()
+            .flat_map(|n| n.channel_manager.list_channels())
+            .collect();
+
+        let inbound_sats = |c: &ChannelDetails| c.inbound_capacity_msat / 1_000;
+        let usable: Vec<u64> = channels
+            .iter()
+            .filter(|c| c.is_usable)
+            .map(inbound_sats)
+            .collect();
+
+        Ok(InboundLiquidity {
+            total_sats: channels.iter().map(inbound_sats).sum(),
+            usable_sats: usable.iter().sum(),
+            largest_channel_sats: usable.iter().max().copied().unwrap_or(0),
+            channels: channels.len(),
+            lsp_available: !self.lsp_clients().await.is_empty(),
+            threshold_sats: self.storage.get_inbound_liquidity_threshold()?,
+        })
+    }
+
+    /// Gets the number of confirmations after which a transaction is considered safe,
+    /// when a second [MutinyEvent::TransactionConfirmed] is sent for it.
+    pub fn get_safe_confirmations(&self) -> u32 {
+        self.storage
+            .get_safe_confirmations()
+            .ok()
+            .flatten()
+            .unwrap_or(DEFAULT_SAFE_CONFIRMATIONS)
+    }
+
+    /// Sets the number of confirmations after which a transaction is considered safe
+    pub fn set_safe_confirmations(&self, confirmations: u32) -> Result<(), MutinyError> {
+        if confirmations < 1 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        self.storage.set_safe_confirmations(confirmations)
+    }
+
+    /// Gets the inbound liquidity, in sats, below which a [MutinyEvent::InboundLiquidityLow] is sent.
+    pub fn get_inbound_liquidity_threshold(&self) -> Result<Option<u64>, MutinyError> {
+        self.storage.get_inbound_liquidity_threshold()
+    }
+
+    /// Sets the inbound liquidity, in sats, below which a [MutinyEvent::InboundLiquidityLow] is sent.
+    /// None turns the alert off.
+    pub fn set_inbound_liquidity_threshold(
+        &self,
+        threshold_sats: Option<u64>,
+    ) -> Result<(), MutinyError> {
+        self.storage
+            .set_inbound_liquidity_threshold(threshold_sats)?;
+        // check again against the new threshold
+        self.inbound_liquidity_low.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The most we can receive over lightning right now, in sats.
+    /// None if there is no limit because an LSP can open a channel for us.
+    async fn max_receivable_sats(&self) -> Option<u64> {
+        if !self.lsp_clients().await.is_empty() {
+            return None;
+        }
+
+        let nodes = self.nodes.lock().await;
+        let inbound_msat: u64 = nodes
+            .values()
+            .flat_map(|n| n.channel_manager.list_usable_channels())
+            .map(|c| c.inbound_capacity_msat)
+            .sum();
+        Some(inbound_msat / 1_000)
+    }
+
+    /// Asks a LNURL-withdraw service to pay a new invoice and waits for it to be paid.
+    async fn withdraw_and_wait(
+        &self,
+        withdraw: &lnurl::withdraw::WithdrawalResponse,
+        amount_sats: u64,
+        labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let invoice = self.create_invoice(Some(amount_sats), labels).await?;
+        let bolt11 = invoice
+            .bolt11
+            .as_ref()
+            .ok_or(MutinyError::InvoiceCreationFailed)?;
+
+        if let Response::Error { reason } = self
+            .lnurl_client
+            .do_withdrawal(withdraw, &bolt11.to_string())
+            .await?
+        {
+            log_warn!(self.logger, "LNURL withdrawal was rejected: {reason}");
+            return Err(MutinyError::LnUrlFailure);
+        }
+
+        self.wait_for_payment(&invoice.payment_hash, LNURL_WITHDRAW_TIMEOUT_SECS)
+            .await
+    }
+
     /// Creates a new LNURL-auth profile.
     pub fn create_lnurl_auth_profile(&self, name: String) -> Result<u32, MutinyError> {
-        self.auth.add_profile(name)
+        self.get_auth()?.add_profile(name)
     }
 
     /// Gets all the LNURL-auth profiles.
     pub fn get_lnurl_auth_profiles(&self) -> Result<Vec<AuthProfile>, MutinyError> {
-        self.auth.get_profiles()
+        match self.auth.as_ref() {
+            Some(auth) => auth.get_profiles(),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn get_auth(&self) -> Result<&AuthManager<S>, MutinyError> {
+        self.auth.as_ref().ok_or(MutinyError::WatchOnly)
     }
 
     /// Authenticates with a LNURL-auth for the given profile.
@@ -1525,13 +4854,14 @@ impl<S: MutinyStorage> NodeManager<S> {
 
         let k1 = query_pairs.get("k1").ok_or(MutinyError::LnUrlFailure)?;
         let k1: [u8; 32] = FromHex::from_hex(k1).map_err(|_| MutinyError::LnUrlFailure)?;
-        let (sig, key) = self.auth.sign(profile_index, url.clone(), &k1)?;
+        let auth = self.get_auth()?;
+        let (sig, key) = auth.sign(profile_index, url.clone(), &k1)?;
 
         let response = self.lnurl_client.lnurl_auth(lnurl, sig, key).await;
         match response {
             Ok(Response::Ok { .. }) => {
                 // don't fail if we just can't save the service
-                if let Err(e) = self.auth.add_used_service(profile_index, url) {
+                if let Err(e) = auth.add_used_service(profile_index, url) {
                     log_error!(self.logger, "Failed to save used lnurl auth service: {e}");
                 }
 
@@ -1577,6 +4907,59 @@ impl<S: MutinyStorage> NodeManager<S> {
         Err(MutinyError::NotFound)
     }
 
+    /// Waits until the invoice with the given payment hash is paid and returns it.
+    ///
+    /// Returns [MutinyError::PaymentTimeout] if it isn't paid within `timeout_secs`
+    /// and [MutinyError::InvoiceExpired] if the invoice expires first.
+    pub async fn wait_for_payment(
+        &self,
+        hash: &sha256::Hash,
+        timeout_secs: u64,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        // subscribe before checking so a payment in between isn't missed
+        let mut events = self.subscribe_events();
+
+        let invoice = self.get_invoice_by_hash(hash).await?;
+        if invoice.paid {
+            return Ok(invoice);
+        }
+
+        let timeout_at = utils::now().as_secs() + timeout_secs;
+        let deadline = timeout_at.min(invoice.expire);
+        let payment_hash = hash.to_hex();
+        loop {
+            let remaining_secs = deadline.saturating_sub(utils::now().as_secs());
+            if remaining_secs == 0 {
+                break;
+            }
+
+            let event_fut = events.next().fuse();
+            // wake up at least every minute so long waits don't overflow the timer
+            let delay_fut = Box::pin(sleep((remaining_secs.min(60) * 1_000) as i32)).fuse();
+            pin_mut!(event_fut);
+            pin_mut!(delay_fut);
+            select! {
+                event = event_fut => match event {
+                    Some(MutinyEvent::PaymentReceived { payment_hash: paid, .. })
+                        if paid == payment_hash =>
+                    {
+                        return self.get_invoice_by_hash(hash).await;
+                    }
+                    Some(_) => {}
+                    // the node manager was stopped
+                    None => return Err(MutinyError::NotRunning),
+                },
+                _ = delay_fut => {}
+            }
+        }
+
+        if deadline < timeout_at {
+            Err(MutinyError::InvoiceExpired)
+        } else {
+            Err(MutinyError::PaymentTimeout)
+        }
+    }
+
     /// Gets an invoice from the node manager.
     /// This includes sent and received invoices.
     pub async fn list_invoices(&self) -> Result<Vec<MutinyInvoice>, MutinyError> {
@@ -1590,6 +4973,78 @@ impl<S: MutinyStorage> NodeManager<S> {
         Ok(invoices)
     }
 
+    /// Lists the lightning payments with the given status, newest first.
+    /// Without a filter every payment is returned, including expired invoices
+    /// that [NodeManager::list_invoices] leaves out.
+    pub async fn list_payments(
+        &self,
+        status_filter: Option<PaymentStatus>,
+    ) -> Result<Vec<MutinyPayment>, MutinyError> {
+        let mut payments: Vec<MutinyPayment> = vec![];
+        let nodes = self.nodes.lock().await;
+        for (_, node) in nodes.iter() {
+            payments.append(&mut node.list_payments(status_filter)?);
+        }
+        payments.sort_by(|a, b| b.invoice.last_updated.cmp(&a.invoice.last_updated));
+        Ok(payments)
+    }
+
+    /// Gets a summary of all the fees the wallet has paid between `start` and `end`,
+    /// these are unix timestamps in seconds. If either is not provided the period is unbounded.
+    pub async fn get_fee_summary(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<FeeSummary, MutinyError> {
+        let invoices = self.list_invoices().await?;
+        let transactions = self.list_onchain()?;
+
+        Ok(FeeSummary::from_activity(
+            &invoices,
+            &transactions,
+            start,
+            end,
+        ))
+    }
+
+    /// Turns the local usage statistics on or off, they are off by default
+    pub fn set_usage_stats_enabled(&self, enabled: bool) -> Result<(), MutinyError> {
+        self.storage.set_usage_stats_enabled(enabled)
+    }
+
+    /// Gets statistics about the wallet's payments, such as how many succeeded and the
+    /// average fees. Returns `None` unless the user opted in with
+    /// [NodeManager::set_usage_stats_enabled].
+    pub async fn get_usage_stats(&self) -> Result<Option<UsageStats>, MutinyError> {
+        if !self.storage.usage_stats_enabled()? {
+            return Ok(None);
+        }
+
+        let mut outbound = vec![];
+        let mut inbound = vec![];
+        for node in self.nodes.lock().await.values() {
+            outbound.extend(
+                node.persister
+                    .list_payment_info(false)?
+                    .into_iter()
+                    .map(|(_, i)| i),
+            );
+            inbound.extend(
+                node.persister
+                    .list_payment_info(true)?
+                    .into_iter()
+                    .map(|(_, i)| i),
+            );
+        }
+        let transactions = self.list_onchain()?;
+
+        Ok(Some(UsageStats::from_activity(
+            &outbound,
+            &inbound,
+            &transactions,
+        )))
+    }
+
     pub async fn get_channel_closure(
         &self,
         user_channel_id: u128,
@@ -1604,6 +5059,19 @@ impl<S: MutinyStorage> NodeManager<S> {
         Err(MutinyError::NotFound)
     }
 
+    /// Lists the channels bought from LSPs, newest first.
+    /// Orders where the LSP did not deliver what was paid for are marked as under-delivered
+    /// and list what was wrong with the channel.
+    pub async fn get_lsp_orders(&self) -> Result<Vec<LspOrder>, MutinyError> {
+        let mut orders: Vec<LspOrder> = vec![];
+        let nodes = self.nodes.lock().await;
+        for (_, node) in nodes.iter() {
+            orders.append(&mut node.get_lsp_orders()?);
+        }
+        orders.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(orders)
+    }
+
     pub async fn list_channel_closures(&self) -> Result<Vec<ChannelClosure>, MutinyError> {
         let mut channels: Vec<ChannelClosure> = vec![];
         let nodes = self.nodes.lock().await;
@@ -1616,7 +5084,8 @@ impl<S: MutinyStorage> NodeManager<S> {
     }
 
     /// Opens a channel from our selected node to the given pubkey.
-    /// The amount is in satoshis.
+    /// The amount is in satoshis, see [OpenChannelOptions] for the fee rate,
+    /// pushing an amount to the peer and announcing the channel.
     ///
     /// The node must be online and have a connection to the peer.
     /// The wallet much have enough funds to open the channel.
@@ -1625,22 +5094,19 @@ impl<S: MutinyStorage> NodeManager<S> {
         from_node: &PublicKey,
         to_pubkey: Option<PublicKey>,
         amount: u64,
-        user_channel_id: Option<u128>,
+        options: OpenChannelOptions,
     ) -> Result<MutinyChannel, MutinyError> {
+        // the funding transaction is signed by the on-chain wallet
+        self.check_can_sign_onchain()?;
         let node = self.get_node(from_node).await?;
 
         let to_pubkey = match to_pubkey {
             Some(pubkey) => pubkey,
-            None => {
-                node.lsp_client
-                    .as_ref()
-                    .ok_or(MutinyError::PubkeyInvalid)?
-                    .pubkey
-            }
+            None => node.lsp_client().ok_or(MutinyError::PubkeyInvalid)?.pubkey,
         };
 
         let outpoint = node
-            .open_channel_with_timeout(to_pubkey, amount, user_channel_id, 60)
+            .open_channel_with_timeout(to_pubkey, amount, options, 60)
             .await?;
 
         let all_channels = node.channel_manager.list_channels();
@@ -1666,16 +5132,13 @@ impl<S: MutinyStorage> NodeManager<S> {
         utxos: &[OutPoint],
         to_pubkey: Option<PublicKey>,
     ) -> Result<MutinyChannel, MutinyError> {
+        // the funding transaction is signed by the on-chain wallet
+        self.check_can_sign_onchain()?;
         let node = self.get_node(from_node).await?;
 
         let to_pubkey = match to_pubkey {
             Some(pubkey) => pubkey,
-            None => {
-                node.lsp_client
-                    .as_ref()
-                    .ok_or(MutinyError::PubkeyInvalid)?
-                    .pubkey
-            }
+            None => node.lsp_client().ok_or(MutinyError::PubkeyInvalid)?.pubkey,
         };
 
         let outpoint = node
@@ -1714,7 +5177,26 @@ impl<S: MutinyStorage> NodeManager<S> {
     }
 
     /// Closes a channel with the given outpoint.
-    pub async fn close_channel(&self, outpoint: &OutPoint) -> Result<(), MutinyError> {
+    ///
+    /// If an address is given the funds are sent there on a cooperative close,
+    /// rather than back to the wallet. The peer must support the address type.
+    pub async fn close_channel(
+        &self,
+        outpoint: &OutPoint,
+        address: Option<Address>,
+    ) -> Result<(), MutinyError> {
+        let shutdown_script = match address {
+            Some(address) => {
+                if !address.is_valid_for_network(self.network) {
+                    return Err(MutinyError::IncorrectNetwork(address.network));
+                }
+                let script = ShutdownScript::try_from(address.script_pubkey())
+                    .map_err(|_| MutinyError::InvalidArgumentsError)?;
+                Some(script)
+            }
+            None => None,
+        };
+
         let nodes = self.nodes.lock().await;
         let channel_opt: Option<(Arc<Node<S>>, ChannelDetails)> =
             nodes.iter().find_map(|(_, n)| {
@@ -1727,17 +5209,22 @@ impl<S: MutinyStorage> NodeManager<S> {
 
         match channel_opt {
             Some((node, channel)) => {
-                node.channel_manager
-                    .close_channel(&channel.channel_id, &channel.counterparty.node_id)
-                    .map_err(|e| {
-                        log_error!(
-                            self.logger,
-                            "had an error closing channel {} with node {} : {e:?}",
-                            &channel.channel_id.to_hex(),
-                            &channel.counterparty.node_id.to_hex()
-                        );
-                        MutinyError::ChannelClosingFailed
-                    })?;
+                // the nodes lock is held, so no other close can use this script
+                node.keys_manager.set_next_shutdown_script(shutdown_script);
+                let res = node
+                    .channel_manager
+                    .close_channel(&channel.channel_id, &channel.counterparty.node_id);
+                node.keys_manager.set_next_shutdown_script(None);
+
+                res.map_err(|e| {
+                    log_error!(
+                        self.logger,
+                        "had an error closing channel {} with node {} : {e:?}",
+                        &channel.channel_id.to_hex(),
+                        &channel.counterparty.node_id.to_hex()
+                    );
+                    MutinyError::ChannelClosingFailed
+                })?;
 
                 Ok(())
             }
@@ -1751,6 +5238,311 @@ impl<S: MutinyStorage> NodeManager<S> {
         }
     }
 
+    /// Finds the node and channel with the given funding outpoint
+    async fn find_channel(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<(Arc<Node<S>>, ChannelDetails), MutinyError> {
+        let nodes = self.nodes.lock().await;
+        nodes
+            .values()
+            .find_map(|n| {
+                n.channel_manager
+                    .list_channels()
+                    .into_iter()
+                    .find(|c| c.funding_txo.map(|f| f.into_bitcoin_outpoint()) == Some(*outpoint))
+                    .map(|c| (n.clone(), c))
+            })
+            .ok_or_else(|| {
+                log_error!(
+                    self.logger,
+                    "Channel not found with this transaction: {outpoint}",
+                );
+                MutinyError::NotFound
+            })
+    }
+
+    /// Force closes the channel with the given outpoint by broadcasting our latest
+    /// commitment transaction. Prefer [NodeManager::close_channel], this is for
+    /// when the peer is unresponsive and a cooperative close isn't possible.
+    ///
+    /// Our funds are timelocked for the returned number of blocks and on-chain
+    /// fees are paid to sweep them, HTLCs in flight have to be resolved on-chain too.
+    pub async fn force_close_channel(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<ForceCloseEstimate, MutinyError> {
+        let (node, channel) = self.find_channel(outpoint).await?;
+
+        log_warn!(
+            self.logger,
+            "Force closing channel {} with node {}",
+            channel.channel_id.to_hex(),
+            channel.counterparty.node_id.to_hex()
+        );
+        node.channel_manager
+            .force_close_broadcasting_latest_txn(&channel.channel_id, &channel.counterparty.node_id)
+            .map_err(|e| {
+                log_error!(
+                    self.logger,
+                    "had an error force closing channel {} with node {} : {e:?}",
+                    channel.channel_id.to_hex(),
+                    channel.counterparty.node_id.to_hex()
+                );
+                MutinyError::ChannelClosingFailed
+            })?;
+
+        let to_self_delay = channel.force_close_spend_delay.unwrap_or_default();
+        Ok(ForceCloseEstimate {
+            outpoint: *outpoint,
+            amount_sats: utils::msats_to_sats(channel.balance_msat),
+            to_self_delay,
+            sweep_eta_secs: force_close_sweep_eta_secs(to_self_delay),
+        })
+    }
+
+    /// Abandons a channel whose funding transaction never confirmed, without broadcasting
+    /// anything. This is for funding transactions that were never broadcast or were dropped
+    /// from the mempool, it errors while our esplora server still knows about the transaction.
+    ///
+    /// If the funding transaction confirms anyways the funds are stuck in the channel,
+    /// so only use this once it can't confirm, e.g. its inputs have been double spent.
+    pub async fn abandon_channel(&self, outpoint: &OutPoint) -> Result<(), MutinyError> {
+        let (node, channel) = self.find_channel(outpoint).await?;
+
+        if channel.confirmations.unwrap_or_default() > 0 || channel.is_channel_ready {
+            log_error!(
+                self.logger,
+                "Can't abandon channel {}, its funding transaction confirmed",
+                channel.channel_id.to_hex()
+            );
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        // the funding transaction can still confirm while it is in the mempool
+        if let Some(status) = self.esplora.get_tx_status(&outpoint.txid).await? {
+            log_error!(
+                self.logger,
+                "Can't abandon channel {}, its funding transaction is {}",
+                channel.channel_id.to_hex(),
+                if status.confirmed {
+                    "confirmed"
+                } else {
+                    "in the mempool"
+                }
+            );
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        log_warn!(
+            self.logger,
+            "Abandoning channel {} with node {}",
+            channel.channel_id.to_hex(),
+            channel.counterparty.node_id.to_hex()
+        );
+        node.channel_manager
+            .force_close_without_broadcasting_txn(
+                &channel.channel_id,
+                &channel.counterparty.node_id,
+            )
+            .map_err(|e| {
+                log_error!(
+                    self.logger,
+                    "had an error abandoning channel {} with node {} : {e:?}",
+                    channel.channel_id.to_hex(),
+                    channel.counterparty.node_id.to_hex()
+                );
+                MutinyError::ChannelClosingFailed
+            })?;
+
+        // don't rebroadcast the funding transaction once the channel is gone
+        if let Err(e) = self.storage.remove_broadcast_transaction(&outpoint.txid) {
+            log_warn!(
+                self.logger,
+                "Could not stop rebroadcasting funding transaction {}: {e}",
+                outpoint.txid
+            );
+        }
+        Ok(())
+    }
+
+    /// Lists the outgoing HTLCs that will force close their channel within
+    /// [HTLC_EXPIRY_WARNING_BLOCKS] if they aren't resolved, soonest first.
+    ///
+    /// Reconnecting to the peer or keeping the wallet online is usually
+    /// enough to resolve them before the channel is lost.
+    pub async fn list_expiring_htlcs(&self) -> Result<Vec<ExpiringHtlc>, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let mut htlcs = vec![];
+        for (pubkey, node) in nodes.iter() {
+            let height = node.channel_manager.current_best_block().height();
+            for channel in node.channel_manager.list_channels() {
+                let Some(funding_txo) = channel.funding_txo else {
+                    continue;
+                };
+                let Ok(monitor) = node.chain_monitor.get_monitor(funding_txo) else {
+                    continue;
+                };
+
+                for balance in monitor.get_claimable_balances() {
+                    if let Balance::MaybeTimeoutClaimableHTLC {
+                        claimable_amount_satoshis,
+                        claimable_height,
+                    } = balance
+                    {
+                        let blocks_left = blocks_until_force_close(claimable_height, height);
+                        if blocks_left <= HTLC_EXPIRY_WARNING_BLOCKS {
+                            htlcs.push(ExpiringHtlc {
+                                node: *pubkey,
+                                channel_id: channel.channel_id.to_hex(),
+                                counterparty: channel.counterparty.node_id,
+                                amount_sats: claimable_amount_satoshis,
+                                cltv_expiry: claimable_height,
+                                blocks_until_force_close: blocks_left,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        htlcs.sort_by_key(|h| h.blocks_until_force_close);
+        Ok(htlcs)
+    }
+
+    /// Lists the claimable balances of closed channels that haven't been swept back
+    /// to the wallet yet, soonest first. These make up the force close balance.
+    pub async fn list_pending_claims(&self) -> Result<Vec<PendingClaim>, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let mut claims = vec![];
+        for (pubkey, node) in nodes.iter() {
+            let height = node.channel_manager.current_best_block().height();
+            let open_channels: HashSet<_> = node
+                .channel_manager
+                .list_channels()
+                .into_iter()
+                .filter_map(|c| c.funding_txo)
+                .collect();
+
+            for funding_txo in node.chain_monitor.list_monitors() {
+                if open_channels.contains(&funding_txo) {
+                    continue;
+                }
+                let Ok(monitor) = node.chain_monitor.get_monitor(funding_txo) else {
+                    continue;
+                };
+
+                let outpoint = funding_txo.into_bitcoin_outpoint();
+                claims.extend(
+                    monitor
+                        .get_claimable_balances()
+                        .into_iter()
+                        .map(|b| PendingClaim::new(*pubkey, outpoint, b, height)),
+                );
+            }
+        }
+
+        claims.sort_by_key(|c| c.blocks_until_spendable.unwrap_or(u32::MAX));
+        Ok(claims)
+    }
+
+    /// Gets a summary of the channel monitor for the channel with the given funding outpoint.
+    /// This works for closed channels as well, as long as the monitor has not been archived.
+    pub async fn export_channel_monitor_summary(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<ChannelMonitorSummary, MutinyError> {
+        let funding_txo = lightning::chain::transaction::OutPoint {
+            txid: outpoint.txid,
+            index: outpoint.vout as u16,
+        };
+
+        let nodes = self.nodes.lock().await;
+        for (pubkey, node) in nodes.iter() {
+            if let Ok(monitor) = node.chain_monitor.get_monitor(funding_txo) {
+                let balances = monitor
+                    .get_claimable_balances()
+                    .into_iter()
+                    .map(MonitorBalance::from)
+                    .collect();
+
+                return Ok(ChannelMonitorSummary {
+                    node: *pubkey,
+                    outpoint: *outpoint,
+                    counterparty: monitor.get_counterparty_node_id(),
+                    latest_update_id: monitor.get_latest_update_id(),
+                    best_block_height: monitor.current_best_block().height(),
+                    balances,
+                });
+            }
+        }
+
+        log_error!(
+            self.logger,
+            "Channel monitor not found with this transaction: {outpoint}"
+        );
+        Err(MutinyError::NotFound)
+    }
+
+    /// Gets the forensic record of a closed channel, see [ChannelForensics].
+    pub async fn get_channel_forensics(
+        &self,
+        channel_id: &[u8; 32],
+    ) -> Result<ChannelForensics, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            if let Some(forensics) = node.persister.get_channel_forensics(channel_id)? {
+                return Ok(forensics);
+            }
+        }
+
+        Err(MutinyError::NotFound)
+    }
+
+    /// Signs a message with the given node's key, for external tooling
+    /// to verify we control the node or one of its channels.
+    ///
+    /// The scope is signed along with the message, see [SigningScope].
+    pub async fn sign_message(
+        &self,
+        from_node: &PublicKey,
+        scope: SigningScope,
+        message: &str,
+    ) -> Result<String, MutinyError> {
+        self.check_can_spend()?;
+        let node = self.get_node(from_node).await?;
+        node.sign_message(scope, message)
+    }
+
+    /// Verifies a message signed by a node with [NodeManager::sign_message]
+    pub fn verify_message(
+        &self,
+        node_id: &PublicKey,
+        scope: SigningScope,
+        message: &str,
+        signature: &str,
+    ) -> bool {
+        verify_scoped_message(scope, message, signature, node_id)
+    }
+
+    /// Creates a node announcement signed by the given node, hex encoded in the
+    /// lightning wire format. The color should be a hex string, ie "ff0000".
+    pub async fn create_node_announcement(
+        &self,
+        from_node: &PublicKey,
+        alias: String,
+        color: String,
+    ) -> Result<String, MutinyError> {
+        self.check_can_spend()?;
+        let rgb: [u8; 3] = Vec::<u8>::from_hex(color.trim_start_matches('#'))
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(MutinyError::InvalidArgumentsError)?;
+        let node = self.get_node(from_node).await?;
+        let announcement = node.create_node_announcement(&alias, rgb)?;
+        Ok(announcement.encode().to_hex())
+    }
+
     /// Lists all the channels for all the nodes in the node manager.
     pub async fn list_channels(&self) -> Result<Vec<MutinyChannel>, MutinyError> {
         let nodes = self.nodes.lock().await;
@@ -1765,6 +5557,44 @@ impl<S: MutinyStorage> NodeManager<S> {
         Ok(mutiny_channels)
     }
 
+    /// Lists the channels that are still being opened, with how far along each one is.
+    /// A [MutinyEvent::ChannelPending], [MutinyEvent::ChannelConfirmed] and
+    /// [MutinyEvent::ChannelOpened] are sent as they progress.
+    pub async fn list_pending_channels(&self) -> Result<Vec<PendingChannel>, MutinyError> {
+        let nodes = self.nodes.lock().await;
+        let pending = nodes
+            .iter()
+            .flat_map(|(_, n)| n.channel_manager.list_channels())
+            .filter(|c| !c.is_channel_ready)
+            .map(|c| PendingChannel::from(&c))
+            .collect();
+
+        Ok(pending)
+    }
+
+    /// Lists the payments our nodes routed, newest first.
+    pub async fn list_forwards(&self) -> Result<Vec<ForwardedPayment>, MutinyError> {
+        let mut forwards = vec![];
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            forwards.append(&mut node.persister.list_forwards()?);
+        }
+        forwards.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(forwards)
+    }
+
+    /// Totals the routing fees our nodes earned and how much each channel routed.
+    /// Only forwards that settled between the `start` and `end` unix timestamps
+    /// (inclusive) are counted, when given.
+    pub async fn get_routing_stats(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<RoutingStats, MutinyError> {
+        let forwards = self.list_forwards().await?;
+        Ok(RoutingStats::from_forwards(&forwards, start, end))
+    }
+
     /// Lists all the peers for all the nodes in the node manager.
     pub async fn list_peers(&self) -> Result<Vec<MutinyPeer>, MutinyError> {
         let peer_data = gossip::get_all_peers(&self.storage)?;
@@ -1780,6 +5610,11 @@ impl<S: MutinyStorage> NodeManager<S> {
                 color: metadata.color.clone(),
                 label: metadata.label.clone(),
                 is_connected: false,
+                reconnect_policy: metadata.reconnect_policy.unwrap_or_default(),
+                features: vec![],
+                uptime_secs: None,
+                latency_ms: None,
+                num_channels: 0,
             })
             .collect();
 
@@ -1810,83 +5645,569 @@ impl<S: MutinyStorage> NodeManager<S> {
                     color: None,
                     label: None,
                     is_connected: true,
+                    reconnect_policy: ReconnectPolicy::default(),
+                    features: vec![],
+                    uptime_secs: None,
+                    latency_ms: None,
+                    num_channels: 0,
                 };
                 missing.push(new);
             }
         }
 
         storage_peers.append(&mut missing);
+
+        // fill in connection details and channel counts from our nodes
+        let now = utils::now();
+        let channels: Vec<ChannelDetails> = nodes
+            .values()
+            .flat_map(|n| n.channel_manager.list_channels())
+            .collect();
+        for peer in storage_peers.iter_mut() {
+            let stats = nodes.values().find_map(|n| n.peer_stats(&peer.pubkey));
+            if let Some(stats) = stats {
+                peer.features = feature_names(&stats.features);
+                peer.latency_ms = stats.latency_ms;
+                if peer.is_connected {
+                    peer.uptime_secs = Some(now.saturating_sub(stats.connected_at).as_secs());
+                }
+            }
+            peer.num_channels = channels
+                .iter()
+                .filter(|c| c.counterparty.node_id == peer.pubkey)
+                .count();
+        }
+
         storage_peers.sort();
 
-        Ok(storage_peers)
+        Ok(storage_peers)
+    }
+
+    /// Gets the current bitcoin price in the given fiat currency,
+    /// or the one from [NodeManager::set_fiat_currency] if none is given.
+    pub async fn get_bitcoin_price(
+        &self,
+        currency: Option<FiatCurrency>,
+    ) -> Result<f32, MutinyError> {
+        let currency = match currency {
+            Some(currency) => currency,
+            None => self.get_fiat_currency()?,
+        };
+        self.bitcoin_price_cache.get_price(currency).await
+    }
+
+    /// Gets the current bitcoin price in the given currency code, ie "usd" or "sek".
+    #[deprecated(note = "use get_bitcoin_price with a FiatCurrency")]
+    pub async fn get_bitcoin_price_in(&self, currency: &str) -> Result<f32, MutinyError> {
+        self.bitcoin_price_cache
+            .get_price_in(&currency.trim().to_lowercase())
+            .await
+    }
+
+    /// Fills in what bitcoin was worth when each payment and transaction without a
+    /// recorded price happened, in the current fiat currency, from the price history
+    /// of the [crate::pricing::PriceSource]. Returns how many were filled in.
+    ///
+    /// Prices are recorded automatically for new activity, this is for older activity
+    /// and activity from while the wallet was not running.
+    ///
+    /// The price api is asked for the history from the day of the oldest unpriced item
+    /// to the day of the newest, so it learns roughly how long the wallet has been used.
+    /// Use [crate::pricing::PriceSource::Disabled] or a self hosted source to avoid that.
+    pub async fn backfill_fiat_prices(&self) -> Result<usize, MutinyError> {
+        let activity = self.get_activity().await?;
+        let unpriced = unpriced_activity(&activity, 0);
+        let times = unpriced.iter().map(|(_, time)| *time);
+        let (Some(from), Some(to)) = (times.clone().min(), times.max()) else {
+            return Ok(0);
+        };
+
+        let currency = self.get_fiat_currency()?;
+        // widen the range so there are prices on both sides of the first and last items,
+        // rounded to whole days so the price api doesn't learn when payments happened
+        let (from, to) = history_range(
+            from,
+            to,
+            HISTORICAL_PRICE_MAX_DISTANCE_SECS,
+            utils::now().as_secs(),
+        );
+        let history = self
+            .bitcoin_price_cache
+            .get_price_history(currency, from, to)
+            .await?;
+
+        let mut count = 0;
+        for (id, time) in unpriced {
+            let Some((timestamp, price)) =
+                closest_price(&history, time, HISTORICAL_PRICE_MAX_DISTANCE_SECS)
+            else {
+                continue;
+            };
+            let price = HistoricalPrice {
+                currency,
+                price,
+                timestamp,
+            };
+            self.storage.persist_historical_price(id, price)?;
+            count += 1;
+        }
+
+        log_info!(self.logger, "Backfilled the fiat price of {count} payments");
+        Ok(count)
+    }
+
+    /// Gets the fiat currency prices are shown in, USD unless another one was set.
+    pub fn get_fiat_currency(&self) -> Result<FiatCurrency, MutinyError> {
+        Ok(self.storage.get_fiat_currency()?.unwrap_or_default())
+    }
+
+    /// Sets the fiat currency prices are shown in.
+    pub fn set_fiat_currency(&self, currency: FiatCurrency) -> Result<(), MutinyError> {
+        self.storage.set_fiat_currency(currency)
+    }
+
+    /// Gets where bitcoin prices are fetched from and how long they are kept.
+    pub fn get_price_config(&self) -> Result<PriceConfig, MutinyError> {
+        Ok(self.storage.get_price_config()?.unwrap_or_default())
+    }
+
+    /// Sets where bitcoin prices are fetched from and how long they are kept.
+    /// With [crate::pricing::PriceSource::Disabled] no price api is ever contacted,
+    /// prices then have to be given with [NodeManager::set_bitcoin_price].
+    pub async fn set_price_config(&self, config: PriceConfig) -> Result<(), MutinyError> {
+        config.validate()?;
+        self.storage.set_price_config(config.clone())?;
+        self.bitcoin_price_cache.set_config(config).await;
+        Ok(())
+    }
+
+    /// Gives the current bitcoin price in a fiat currency, for apps that get
+    /// prices themselves. It is used until it expires like a fetched price,
+    /// or until a new one is given when fetching prices is disabled.
+    pub async fn set_bitcoin_price(
+        &self,
+        currency: FiatCurrency,
+        price: f32,
+    ) -> Result<(), MutinyError> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        self.bitcoin_price_cache.set_price(currency, price).await;
+        Ok(())
+    }
+
+    /// Creates a lightning invoice for a fiat amount at the current exchange rate.
+    /// The currency is a code the price source supports, ie "usd" or "sek",
+    /// it doesn't have to be one of the [FiatCurrency] prices are shown in.
+    /// The quoted rate is saved alongside the invoice and is honored for `ttl` seconds,
+    /// defaulting to 10 minutes, the invoice expires along with the quote.
+    ///
+    /// Use [NodeManager::watch_fiat_invoice] to re-issue the invoice at a fresh
+    /// rate if it is not paid before the quote expires.
+    pub async fn create_fiat_invoice(
+        &self,
+        fiat_amount: f64,
+        currency: String,
+        ttl: Option<u64>,
+        labels: Vec<String>,
+    ) -> Result<FiatInvoice, MutinyError> {
+        if !fiat_amount.is_finite() || fiat_amount <= 0.0 {
+            return Err(MutinyError::BadAmountError);
+        }
+
+        let currency = currency.trim().to_lowercase();
+        if currency.is_empty() || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        let ttl = ttl.unwrap_or(DEFAULT_FIAT_QUOTE_TTL_SECS);
+        if ttl == 0 {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let rate = self.bitcoin_price_cache.get_price_in(&currency).await?;
+        let quote = FiatQuote::new(
+            fiat_amount,
+            currency,
+            rate,
+            crate::utils::now().as_secs(),
+            ttl,
+        );
+        if quote.amount_sats == 0 {
+            return Err(MutinyError::BadAmountError);
+        }
+
+        // the invoice can't be paid at the quoted rate after the quote expires
+        let expiry_secs = u32::try_from(quote.ttl).unwrap_or(u32::MAX);
+        let invoice = self
+            .create_invoice_with_expiry(Some(quote.amount_sats), labels, None, expiry_secs)
+            .await?;
+        self.storage
+            .persist_fiat_quote(&invoice.payment_hash, quote.clone())?;
+
+        Ok(FiatInvoice { invoice, quote })
+    }
+
+    /// Gets the fiat quote an invoice was created with, if it was created
+    /// with [NodeManager::create_fiat_invoice].
+    pub fn get_fiat_quote(
+        &self,
+        payment_hash: &sha256::Hash,
+    ) -> Result<Option<FiatQuote>, MutinyError> {
+        self.storage.get_fiat_quote(payment_hash)
+    }
+
+    /// Creates a background process that watches a fiat invoice. If the invoice
+    /// is still unpaid when its quote expires, a new invoice is created for the same
+    /// fiat amount at the current rate and passed to `on_reissue`.
+    ///
+    /// This stops once the latest invoice is paid, the node manager is stopped,
+    /// or the invoice has been re-issued too many times.
+    pub fn watch_fiat_invoice<F>(
+        nm: Arc<NodeManager<S>>,
+        fiat_invoice: FiatInvoice,
+        labels: Vec<String>,
+        on_reissue: F,
+    ) where
+        F: Fn(FiatInvoice) + 'static,
+    {
+        utils::spawn(async move {
+            let mut current = fiat_invoice;
+            let mut reissues = 0;
+            loop {
+                if nm.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                match nm.get_invoice_by_hash(&current.invoice.payment_hash).await {
+                    Ok(invoice) if invoice.paid => return,
+                    Ok(_) => {}
+                    Err(e) => log_warn!(nm.logger, "Could not check fiat invoice status: {e}"),
+                }
+
+                if current.quote.is_expired(crate::utils::now().as_secs()) {
+                    if reissues >= MAX_FIAT_INVOICE_REISSUES {
+                        log_info!(
+                            nm.logger,
+                            "Fiat invoice was never paid, no longer re-issuing"
+                        );
+                        return;
+                    }
+
+                    match nm
+                        .create_fiat_invoice(
+                            current.quote.fiat_amount,
+                            current.quote.currency.clone(),
+                            Some(current.quote.ttl),
+                            labels.clone(),
+                        )
+                        .await
+                    {
+                        Ok(new) => {
+                            log_debug!(nm.logger, "Re-issued expired fiat invoice");
+                            reissues += 1;
+                            current = new;
+                            on_reissue(current.clone());
+                        }
+                        Err(e) => log_error!(nm.logger, "Failed to re-issue fiat invoice: {e}"),
+                    }
+                }
+
+                sleep(1_000).await;
+            }
+        });
+    }
+
+    /// Gets the settings for which inbound payments will be accepted.
+    pub fn get_inbound_payment_config(&self) -> Result<InboundPaymentConfig, MutinyError> {
+        self.storage.get_inbound_payment_config()
+    }
+
+    /// Sets the settings for which inbound payments will be accepted.
+    /// Payments below the minimum will be rejected when they are received.
+    ///
+    /// The HTLC minimum only applies to channels opened after it is set,
+    /// inbound channels will use it after the node is restarted.
+    pub fn set_inbound_payment_config(
+        &self,
+        config: InboundPaymentConfig,
+    ) -> Result<(), MutinyError> {
+        self.storage.set_inbound_payment_config(config)
+    }
+
+    /// Sets the fees a channel charges for forwarding payments, identified by its
+    /// funding outpoint. The policy is saved and applied again whenever the node starts.
+    ///
+    /// The CLTV delta can't be below LDK's minimum of [MIN_CLTV_EXPIRY_DELTA] blocks.
+    pub async fn update_channel_config(
+        &self,
+        outpoint: &OutPoint,
+        base_fee_msat: u32,
+        proportional_millionths: u32,
+        cltv_expiry_delta: u16,
+    ) -> Result<(), MutinyError> {
+        if cltv_expiry_delta < MIN_CLTV_EXPIRY_DELTA {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let nodes = self.nodes.lock().await;
+        let (node, channel) = nodes
+            .values()
+            .find_map(|n| {
+                n.channel_manager
+                    .list_channels()
+                    .into_iter()
+                    .find(|c| c.funding_txo.map(|f| f.into_bitcoin_outpoint()) == Some(*outpoint))
+                    .map(|c| (n.clone(), c))
+            })
+            .ok_or(MutinyError::NotFound)?;
+
+        let policy = ChannelFeePolicy {
+            base_fee_msat,
+            proportional_millionths,
+            cltv_expiry_delta,
+        };
+        let config = policy.apply(channel.config.unwrap_or_default());
+        node.channel_manager
+            .update_channel_config(
+                &channel.counterparty.node_id,
+                &[channel.channel_id],
+                &config,
+            )
+            .map_err(|e| {
+                log_error!(
+                    self.logger,
+                    "could not update config of channel {}: {e:?}",
+                    channel.channel_id.to_hex()
+                );
+                MutinyError::InvalidArgumentsError
+            })?;
+
+        self.storage
+            .set_channel_fee_policy(outpoint.to_string(), policy)
+    }
+
+    /// Sends an onion message from the selected node, through the intermediate nodes
+    /// if there are any, otherwise the recipient has to be one of our peers.
+    ///
+    /// The message type has to be a custom one, at least [crate::onionmessage::MIN_CUSTOM_ONION_MESSAGE_TYPE].
+    pub async fn send_onion_message(
+        &self,
+        from_node: &PublicKey,
+        to_node: PublicKey,
+        intermediate_nodes: Vec<PublicKey>,
+        message: CustomOnionMessage,
+    ) -> Result<(), MutinyError> {
+        let node = self.get_node(from_node).await?;
+        node.send_onion_message(to_node, &intermediate_nodes, message)
+    }
+
+    /// Registers the handler for the onion messages of a type any of our nodes receive,
+    /// replacing the handler that type had. Messages of types without a handler are ignored.
+    pub fn register_onion_message_handler(
+        &self,
+        tlv_type: u64,
+        handler: Arc<dyn OnionMessageHandler>,
+    ) {
+        self.onion_message_handler.register(tlv_type, handler)
+    }
+
+    /// Stops handling the onion messages of a type
+    pub fn unregister_onion_message_handler(&self, tlv_type: u64) {
+        self.onion_message_handler.unregister(tlv_type)
+    }
+
+    /// Sends a custom message from the selected node to one of its connected peers.
+    ///
+    /// The message type has to be a custom one, at least [crate::peermessage::MIN_CUSTOM_MESSAGE_TYPE].
+    pub async fn send_peer_message(
+        &self,
+        from_node: &PublicKey,
+        peer: PublicKey,
+        message: CustomPeerMessage,
+    ) -> Result<(), MutinyError> {
+        let node = self.get_node(from_node).await?;
+        node.send_peer_message(peer, message)
+    }
+
+    /// Registers the handler for the custom messages of a type our peers send,
+    /// replacing the handler that type had. Messages of types without a handler are ignored.
+    pub fn register_peer_message_handler(
+        &self,
+        message_type: u16,
+        handler: Arc<dyn PeerMessageHandler>,
+    ) {
+        self.custom_message_handler.register(message_type, handler)
+    }
+
+    /// Stops handling the custom messages of a type
+    pub fn unregister_peer_message_handler(&self, message_type: u16) {
+        self.custom_message_handler.unregister(message_type)
+    }
+
+    /// Signals a custom feature bit to our peers so they know we support a protocol,
+    /// odd bits are optional and even bits required.
+    ///
+    /// The bit has to be at least [crate::peermessage::MIN_CUSTOM_FEATURE_BIT]
+    /// and is only signaled on connections made after it was added.
+    pub fn add_feature_bit(&self, bit: usize) -> Result<(), MutinyError> {
+        self.custom_message_handler.add_feature_bit(bit)
+    }
+
+    /// Gets the alias, color and addresses our nodes announce, if they were set
+    pub fn get_node_announcement_config(
+        &self,
+    ) -> Result<Option<NodeAnnouncementConfig>, MutinyError> {
+        self.storage.get_node_announcement_config()
+    }
+
+    /// Sets the alias, color and addresses our nodes announce to the network
+    /// and broadcasts the new announcement. Only nodes with public channels
+    /// are announced, see [NodeManager::open_channel].
+    pub async fn set_node_announcement_config(
+        &self,
+        config: NodeAnnouncementConfig,
+    ) -> Result<(), MutinyError> {
+        config.validate()?;
+        self.storage.set_node_announcement_config(config)?;
+        self.broadcast_node_announcements().await;
+        Ok(())
+    }
+
+    /// Gets which inbound channels our nodes accept
+    pub fn get_channel_acceptance_policy(&self) -> Result<ChannelAcceptancePolicy, MutinyError> {
+        Ok(self
+            .storage
+            .get_channel_acceptance_policy()?
+            .unwrap_or_default())
     }
 
-    /// Gets the current bitcoin price in USD.
-    pub async fn get_bitcoin_price(&self) -> Result<f32, MutinyError> {
-        let now = crate::utils::now();
+    /// Sets which inbound channels our nodes accept, channels that break it
+    /// are rejected before the [ChannelAcceptor] is asked about them.
+    pub fn set_channel_acceptance_policy(
+        &self,
+        policy: ChannelAcceptancePolicy,
+    ) -> Result<(), MutinyError> {
+        self.storage.set_channel_acceptance_policy(policy)
+    }
 
-        let mut bitcoin_price_cache = self.bitcoin_price_cache.lock().await;
+    /// Sets what decides on inbound channels that pass the [ChannelAcceptancePolicy],
+    /// replacing the one from [MutinyWalletConfig::with_channel_acceptor].
+    /// Without one they are accepted.
+    pub fn set_channel_acceptor(&self, acceptor: Option<Arc<dyn ChannelAcceptor>>) {
+        if let Ok(mut current) = self.channel_acceptor.write() {
+            *current = acceptor;
+        }
+    }
 
-        let (price, timestamp) = match bitcoin_price_cache.as_ref() {
-            Some((price, timestamp))
-                if *timestamp + Duration::from_secs(BITCOIN_PRICE_CACHE_SEC) > now =>
-            {
-                // Cache is not expired
-                (*price, *timestamp)
-            }
-            _ => {
-                // Cache is either expired or empty, fetch new price
-                match self.fetch_bitcoin_price().await {
-                    Ok(new_price) => (new_price, now),
-                    Err(e) => {
-                        // If fetching price fails, return the cached price (if any)
-                        if let Some((price, timestamp)) = bitcoin_price_cache.as_ref() {
-                            log_warn!(self.logger, "price api failed, returning cached price");
-                            (*price, *timestamp)
-                        } else {
-                            // If there is no cached price, return the error
-                            log_error!(self.logger, "no cached price and price api failed");
-                            return Err(e);
-                        }
-                    }
-                }
+    /// Broadcasts the node announcement of each of our nodes with public channels
+    async fn broadcast_node_announcements(&self) {
+        let config = match self.storage.get_node_announcement_config() {
+            Ok(Some(config)) => config,
+            Ok(None) => return,
+            Err(e) => {
+                log_error!(self.logger, "Failed to read node announcement config: {e}");
+                return;
             }
         };
 
-        *bitcoin_price_cache = Some((price, timestamp));
-        Ok(price)
+        let nodes = self.nodes.lock().await;
+        for node in nodes.values() {
+            if let Err(e) = node.broadcast_node_announcement(&config) {
+                log_error!(self.logger, "Failed to broadcast node announcement: {e}");
+            }
+        }
     }
 
-    async fn fetch_bitcoin_price(&self) -> Result<f32, MutinyError> {
-        log_debug!(self.logger, "fetching new bitcoin price");
+    /// Retrieves the logs from storage.
+    pub fn get_logs(&self) -> Result<Option<Vec<String>>, MutinyError> {
+        self.logger.get_logs(&self.storage)
+    }
 
-        let client = Client::builder()
-            .build()
-            .map_err(|_| MutinyError::BitcoinPriceError)?;
+    /// Applies a rapid gossip sync snapshot that was fetched out of band,
+    /// for setups that can't reach a RGS server. Returns the timestamp the
+    /// network graph is now synced to.
+    pub async fn import_rgs_snapshot(&self, snapshot: Vec<u8>) -> Result<u32, MutinyError> {
+        let _guard = self.coordinator.lock(Resource::Sync).await;
+        gossip::apply_rgs_snapshot(&snapshot, &self.gossip_sync, &self.storage, &self.logger)
+    }
 
-        let resp = client
-            .get("https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies=usd")
-            .send()
-            .await
-            .map_err(|_| MutinyError::BitcoinPriceError)?;
+    /// Fetches what changed in the network graph from the RGS servers right away,
+    /// like before a big payment. Returns the timestamp the graph is now synced to.
+    pub async fn sync_gossip_now(&self) -> Result<u32, MutinyError> {
+        let _guard = self.coordinator.lock(Resource::Sync).await;
+        let last_sync_timestamp = self.storage.get_data(GOSSIP_SYNC_TIME_KEY)?.unwrap_or(0);
+        gossip::sync_gossip(
+            &self.storage,
+            self.user_rgs_url.clone(),
+            self.network,
+            last_sync_timestamp,
+            &self.gossip_sync,
+            &self.http_client,
+            &self.logger,
+        )
+        .await
+    }
 
-        let response: CoingeckoResponse = resp
-            .error_for_status()
-            .map_err(|_| MutinyError::BitcoinPriceError)?
-            .json()
-            .await
-            .map_err(|_| MutinyError::BitcoinPriceError)?;
+    /// How many channels and nodes are in the network graph and how big it is.
+    pub fn get_network_graph_size(&self) -> NetworkGraphSize {
+        gossip::network_graph_size(self.gossip_sync.network_graph())
+    }
 
-        Ok(response.bitcoin.usd)
+    /// Gets the size, in bytes, the network graph is pruned down to.
+    pub fn get_network_graph_size_limit(&self) -> Result<Option<u64>, MutinyError> {
+        self.storage.get_network_graph_size_limit()
     }
 
-    /// Retrieves the logs from storage.
-    pub fn get_logs(&self) -> Result<Option<Vec<String>>, MutinyError> {
-        self.logger.get_logs(&self.storage)
+    /// Sets the size, in bytes, the network graph is pruned down to, the smallest channels
+    /// are removed first. None lets it grow as big as the network.
+    ///
+    /// A full mainnet graph takes tens of megabytes, so a limit helps in browsers
+    /// with tight memory limits. It is applied on the next [NodeManager::prune_network_graph].
+    pub fn set_network_graph_size_limit(
+        &self,
+        limit_bytes: Option<u64>,
+    ) -> Result<(), MutinyError> {
+        if limit_bytes == Some(0) {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        self.storage.set_network_graph_size_limit(limit_bytes)
+    }
+
+    /// Removes stale channels and nodes from the network graph, then the smallest channels
+    /// until it fits in the [NodeManager::set_network_graph_size_limit], and saves it.
+    /// Channels of our own nodes are kept. This runs every hour in the background.
+    pub async fn prune_network_graph(&self) -> Result<NetworkGraphSize, MutinyError> {
+        let _guard = self.coordinator.lock(Resource::Sync).await;
+        let limit = self.storage.get_network_graph_size_limit()?;
+        let keep: Vec<NodeId> = {
+            let nodes = self.nodes.lock().await;
+            nodes
+                .values()
+                .map(|n| NodeId::from_pubkey(&n.pubkey))
+                .collect()
+        };
+
+        let network_graph = self.gossip_sync.network_graph();
+        let (channels_before, nodes_before) = {
+            let graph = network_graph.read_only();
+            (graph.channels().len(), graph.nodes().len())
+        };
+
+        let size = gossip::prune_network_graph(network_graph, limit, &keep, &self.logger);
+
+        if size.num_channels != channels_before || size.num_nodes != nodes_before {
+            let last_sync_timestamp = self.storage.get_data(GOSSIP_SYNC_TIME_KEY)?.unwrap_or(0);
+            gossip::write_gossip_data(&self.storage, last_sync_timestamp, network_graph)?;
+        }
+
+        Ok(size)
     }
 
     /// Resets the scorer and network graph. This can be useful if you get stuck in a bad state.
+    /// Returns [MutinyError::Busy] while the wallet is syncing.
     pub async fn reset_router(&self) -> Result<(), MutinyError> {
+        let _guard = self.coordinator.try_lock(Resource::Sync)?;
+
         // if we're not connected to the db, start it up
         let needs_db_connection = !self.storage.clone().connected().unwrap_or(true);
         if needs_db_connection {
@@ -1908,15 +6229,21 @@ impl<S: MutinyStorage> NodeManager<S> {
     /// Resets BDK's keychain tracker. This will require a re-sync of the blockchain.
     ///
     /// This can be useful if you get stuck in a bad state.
+    /// Returns [MutinyError::Busy] while the wallet is syncing.
     pub async fn reset_onchain_tracker(&self) -> Result<(), MutinyError> {
+        let _guard = self.coordinator.try_lock(Resource::Sync)?;
+
         // if we're not connected to the db, start it up
         let needs_db_connection = !self.storage.clone().connected().unwrap_or(true);
         if needs_db_connection {
             self.storage.clone().start().await?;
         }
 
-        // delete the bdk keychain store
-        self.storage.delete(&[KEYCHAIN_STORE_KEY])?;
+        // delete the bdk keychain stores, including those of rotated accounts
+        let keys: Vec<String> = (0..=self.storage.get_onchain_rotation()?)
+            .map(keychain_store_key)
+            .collect();
+        self.storage.delete(&keys)?;
 
         // shut back down after reading if it was already closed
         if needs_db_connection {
@@ -1950,14 +6277,47 @@ impl<S: MutinyStorage> NodeManager<S> {
     }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
-struct CoingeckoResponse {
-    pub bitcoin: CoingeckoPrice,
+/// Makes sure the saved data was created for the configured network.
+/// Wallets from before the network was saved are assumed to match.
+fn check_network<S: MutinyStorage>(storage: &S, network: Network) -> Result<(), MutinyError> {
+    match storage.get_network()? {
+        Some(saved) if saved != network => Err(MutinyError::NetworkMismatch(saved)),
+        Some(_) => Ok(()),
+        None => storage.set_network(network),
+    }
+}
+
+/// Makes sure the saved data was created with the configured account index,
+/// an existing wallet keeps its index when none is configured.
+fn check_account_index<S: MutinyStorage>(
+    storage: &S,
+    account_index: Option<u32>,
+) -> Result<u32, MutinyError> {
+    match (storage.get_account_index()?, account_index) {
+        (Some(saved), Some(index)) if saved != index => {
+            Err(MutinyError::AccountIndexMismatch(saved))
+        }
+        (Some(saved), _) => Ok(saved),
+        (None, index) => {
+            let index = index.unwrap_or(0);
+            storage.set_account_index(index)?;
+            Ok(index)
+        }
+    }
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
-struct CoingeckoPrice {
-    pub usd: f32,
+/// The urls of the LSPs to start up with. The comma separated LSPs of the config are used when
+/// it sets any, otherwise the ones saved by [NodeManager::add_lsp] and [NodeManager::remove_lsp].
+fn configured_lsp_urls(config: Option<&str>, saved: Option<Vec<String>>) -> Vec<String> {
+    match config {
+        // check if string is some and not an empty string
+        Some(lsp_urls) if !lsp_urls.trim().is_empty() => lsp_urls
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect(),
+        _ => saved.unwrap_or_default(),
+    }
 }
 
 // This will create a new node with a node manager and return the PublicKey of the node created.
@@ -1986,7 +6346,8 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
     // Create and save a new node using the next child index
     let next_node_uuid = Uuid::new_v4().to_string();
 
-    let lsp = if node_manager.lsp_clients.is_empty() {
+    let lsp_clients = node_manager.lsp_clients().await;
+    let lsp = if lsp_clients.is_empty() {
         log_info!(
             node_manager.logger,
             "no lsp saved and no lsp clients available"
@@ -1996,8 +6357,8 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         log_info!(node_manager.logger, "no lsp saved, picking random one");
         // If we don't have an lsp saved we should pick a random
         // one from our client list and save it for next time
-        let rand = rand::random::<usize>() % node_manager.lsp_clients.len();
-        Some(node_manager.lsp_clients[rand].url.clone())
+        let rand = rand::random::<usize>() % lsp_clients.len();
+        Some(lsp_clients[rand].url.clone())
     };
 
     let next_node = NodeIndex {
@@ -2019,7 +6380,11 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         next_node_uuid.clone(),
         &next_node,
         node_manager.stop.clone(),
-        &node_manager.mnemonic,
+        node_manager
+            .mnemonic
+            .as_ref()
+            .ok_or(MutinyError::WatchOnly)?,
+        node_manager.account_index,
         node_manager.storage.clone(),
         node_manager.gossip_sync.clone(),
         node_manager.scorer.clone(),
@@ -2028,9 +6393,13 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         node_manager.wallet.clone(),
         node_manager.network,
         node_manager.esplora.clone(),
-        &node_manager.lsp_clients,
+        node_manager.lsp_clients.clone(),
+        node_manager.notifier.clone(),
+        node_manager.channel_acceptor.clone(),
+        node_manager.onion_message_handler.clone(),
+        node_manager.custom_message_handler.clone(),
         node_manager.logger.clone(),
-        node_manager.websocket_proxy_addr.clone(),
+        node_manager.websocket_proxies.clone(),
     )
     .await;
 
@@ -2039,7 +6408,11 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         next_node_uuid.clone(),
         &next_node,
         node_manager.stop.clone(),
-        &node_manager.mnemonic,
+        node_manager
+            .mnemonic
+            .as_ref()
+            .ok_or(MutinyError::WatchOnly)?,
+        node_manager.account_index,
         node_manager.storage.clone(),
         node_manager.gossip_sync.clone(),
         node_manager.scorer.clone(),
@@ -2048,8 +6421,14 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         node_manager.wallet.clone(),
         node_manager.network,
         node_manager.esplora.clone(),
-        &node_manager.lsp_clients,
+        node_manager.lsp_clients.clone(),
+        node_manager.notifier.clone(),
+        node_manager.channel_acceptor.clone(),
+        node_manager.onion_message_handler.clone(),
+        node_manager.custom_message_handler.clone(),
         node_manager.logger.clone(),
+        node_manager.socks_proxy.clone(),
+        node_manager.listen_port,
     )
     .await;
 
@@ -2066,47 +6445,339 @@ pub(crate) async fn create_new_node_from_node_manager<S: MutinyStorage>(
         .await
         .insert(node_pubkey, Arc::new(new_node));
 
-    Ok(NodeIdentity {
-        uuid: next_node_uuid.clone(),
-        pubkey: node_pubkey,
-    })
-}
+    Ok(NodeIdentity {
+        uuid: next_node_uuid.clone(),
+        pubkey: node_pubkey,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MutinyError;
+    use crate::nodemanager::{
+        aggregate_dust_payments, blocks_until_force_close, channel_confirmation_events,
+        configured_lsp_urls, force_close_sweep_eta_secs, transaction_confirmation_events,
+        tx_confirmations, unpriced_activity, ActivityItem, ChannelAcceptancePolicy, ChannelClosure,
+        ChannelClosureKind, ChannelFeePolicy, ChannelMonitorSummary, ChannelRoutingStats,
+        FeeSummary, ForwardedPayment, MaxFee, MonitorBalance, MppOptions, MutinyInvoice,
+        NodeAnnouncementConfig, NodeManager, PendingChannel, PendingChannelStage, PendingClaim,
+        RoutingStats, TransactionDetails, UsageStats, WatchedTransaction,
+    };
+    use crate::pricing::{FiatCurrency, HistoricalPrice, PriceConfig, PriceSource};
+    use crate::{keymanager::generate_seed, MutinyWalletConfig};
+    use bdk::chain::ConfirmationTime;
+    use bitcoin::hashes::hex::{FromHex, ToHex};
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::PublicKey;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::{Network, OutPoint, PackedLockTime, Transaction, TxOut, Txid};
+    use lightning::chain::channelmonitor::{Balance, LATENCY_GRACE_PERIOD_BLOCKS};
+    use lightning::events::ClosureReason;
+    use lightning::ln::PaymentHash;
+    use lightning::routing::router::DEFAULT_MAX_PATH_COUNT;
+    use lightning::util::config::ChannelConfig;
+    use lightning_invoice::Invoice;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use crate::test_utils::*;
+
+    use crate::event::{ChannelRequest, HTLCStatus, MillisatAmount, PaymentInfo};
+    use crate::notifier::MutinyEvent;
+    use crate::storage::{MemoryStorage, MutinyStorage};
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const BOLT_11: &str = "lntbs1m1pjrmuu3pp52hk0j956d7s8azaps87amadshnrcvqtkvk06y2nue2w69g6e5vasdqqcqzpgxqyz5vqsp5wu3py6257pa3yzarw0et2200c08r5fu6k3u94yfwmlnc8skdkc9s9qyyssqc783940p82c64qq9pu3xczt4tdxzex9wpjn54486y866aayft2cxxusl9eags4cs3kcmuqdrvhvs0gudpj5r2a6awu4wcq29crpesjcqhdju55";
+
+    #[test]
+    async fn create_node_manager() {
+        let test_name = "create_node_manager";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+
+        assert!(!NodeManager::has_node_manager(storage.clone()));
+        let c = MutinyWalletConfig::new(
+            None,
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        NodeManager::new(c, storage.clone())
+            .await
+            .expect("node manager should initialize");
+        assert!(NodeManager::has_node_manager(storage));
+    }
+
+    #[test]
+    async fn legacy_encrypted_wallet_has_node_manager() {
+        let test_name = "legacy_encrypted_wallet_has_node_manager";
+        log!("{}", test_name);
+
+        // legacy ciphertext can't be read without the password, it still counts
+        let storage = MemoryStorage::new(None);
+        let seed = crate::keymanager::generate_seed(12).unwrap();
+        let plaintext = serde_json::to_string(&seed).unwrap();
+        let legacy = crate::encrypt::encrypt(&plaintext, "password");
+        storage.set(crate::storage::MNEMONIC_KEY, legacy).unwrap();
+
+        assert!(NodeManager::has_node_manager(storage));
+    }
+
+    #[test]
+    async fn correctly_show_seed() {
+        let test_name = "correctly_show_seed";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let c = MutinyWalletConfig::new(
+            Some(seed.clone()),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, ()).await.unwrap();
+
+        assert_eq!(Some(seed), nm.show_seed());
+    }
+
+    #[test]
+    async fn create_watch_only_node_manager() {
+        let test_name = "create_watch_only_node_manager";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Regtest, &seed.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &path).unwrap());
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let c = MutinyWalletConfig::new(
+            None,
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        )
+        .with_watch_only(xpub.to_string());
+        assert!(!NodeManager::has_node_manager(storage.clone()));
+        let nm = NodeManager::new(c, storage.clone())
+            .await
+            .expect("node manager should initialize");
+
+        assert!(nm.is_watch_only());
+        assert_eq!(None, nm.show_seed());
+        assert!(nm.get_new_address(vec![]).is_ok());
+        assert!(matches!(nm.new_node().await, Err(MutinyError::WatchOnly)));
+        assert!(nm.list_nodes().await.unwrap().is_empty());
+
+        // the saved watch-only wallet is found and loaded without the descriptor
+        assert!(NodeManager::has_node_manager(storage.clone()));
+        nm.stop().await.unwrap();
+        let c = MutinyWalletConfig::new(
+            None,
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should load");
+        assert!(nm.is_watch_only());
+    }
+
+    #[test]
+    async fn create_external_signer_node_manager() {
+        let test_name = "create_external_signer_node_manager";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let secp = Secp256k1::new();
+        let device_seed = generate_seed(12).expect("Failed to gen seed");
+        let xprv = ExtendedPrivKey::new_master(Network::Regtest, &device_seed.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &path).unwrap());
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let c = MutinyWalletConfig::new(
+            Some(seed.clone()),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        )
+        .with_external_signer(xpub.to_string());
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
+
+        // lightning keys are still local
+        assert!(!nm.is_watch_only());
+        assert!(nm.uses_external_signer());
+        assert_eq!(Some(seed), nm.show_seed());
+        assert!(nm.get_new_address(vec![]).is_ok());
+
+        let address = nm.get_new_address(vec![]).unwrap();
+        assert!(matches!(
+            nm.sweep_wallet(address, vec![], None).await,
+            Err(MutinyError::ExternalSigner)
+        ));
+    }
+
+    #[test]
+    async fn created_new_nodes() {
+        let test_name = "created_new_nodes";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let c = MutinyWalletConfig::new(
+            Some(seed),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
+
+        {
+            let node_identity = nm.new_node().await.expect("should create new node");
+            let node_storage = nm.node_storage.lock().await;
+            assert_ne!("", node_identity.uuid);
+            assert_ne!("", node_identity.pubkey.to_string());
+            assert_eq!(1, node_storage.nodes.len());
+
+            let retrieved_node = node_storage.nodes.get(&node_identity.uuid).unwrap();
+            assert_eq!(0, retrieved_node.child_index);
+        }
+
+        {
+            let node_identity = nm.new_node().await.expect("node manager should initialize");
+            let node_storage = nm.node_storage.lock().await;
+
+            assert_ne!("", node_identity.uuid);
+            assert_ne!("", node_identity.pubkey.to_string());
+            assert_eq!(2, node_storage.nodes.len());
+
+            let retrieved_node = node_storage.nodes.get(&node_identity.uuid).unwrap();
+            assert_eq!(1, retrieved_node.child_index);
+        }
+    }
+
+    #[test]
+    async fn inbound_liquidity_alert() {
+        let test_name = "inbound_liquidity_alert";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let c = MutinyWalletConfig::new(
+            Some(seed),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
+        nm.new_node().await.expect("should create new node");
+
+        let liquidity = nm.get_inbound_liquidity().await.unwrap();
+        assert_eq!(liquidity.usable_sats, 0);
+        assert_eq!(liquidity.channels, 0);
+        assert_eq!(liquidity.threshold_sats, None);
+
+        let mut events = nm.notifier.subscribe();
+        // no alert without a threshold
+        nm.check_inbound_liquidity().await;
+        assert!(events.try_next().is_err());
+
+        nm.set_inbound_liquidity_threshold(Some(10_000)).unwrap();
+        nm.check_inbound_liquidity().await;
+        assert_eq!(
+            events.try_next().unwrap(),
+            Some(MutinyEvent::InboundLiquidityLow {
+                inbound_sats: 0,
+                threshold_sats: 10_000,
+            })
+        );
+
+        // only alerted once until it recovers
+        nm.check_inbound_liquidity().await;
+        assert!(events.try_next().is_err());
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::nodemanager::{
-        ActivityItem, ChannelClosure, MutinyInvoice, NodeManager, TransactionDetails,
-    };
-    use crate::{keymanager::generate_seed, MutinyWalletConfig};
-    use bdk::chain::ConfirmationTime;
-    use bitcoin::hashes::hex::{FromHex, ToHex};
-    use bitcoin::hashes::{sha256, Hash};
-    use bitcoin::secp256k1::PublicKey;
-    use bitcoin::{Network, PackedLockTime, Transaction, TxOut, Txid};
-    use lightning::ln::PaymentHash;
-    use lightning_invoice::Invoice;
-    use std::str::FromStr;
+    #[test]
+    async fn network_graph_size_limit() {
+        let test_name = "network_graph_size_limit";
+        log!("{}", test_name);
 
-    use crate::test_utils::*;
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let c = MutinyWalletConfig::new(
+            Some(seed),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
 
-    use crate::event::{HTLCStatus, MillisatAmount, PaymentInfo};
-    use crate::storage::MemoryStorage;
-    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+        assert_eq!(nm.get_network_graph_size_limit().unwrap(), None);
+        assert!(nm.set_network_graph_size_limit(Some(0)).is_err());
+        nm.set_network_graph_size_limit(Some(1_000)).unwrap();
+        assert_eq!(nm.get_network_graph_size_limit().unwrap(), Some(1_000));
 
-    wasm_bindgen_test_configure!(run_in_browser);
+        let size = nm.get_network_graph_size();
+        assert_eq!(size.num_channels, 0);
+        assert_eq!(size.num_nodes, 0);
+        assert!(size.size_bytes > 0);
 
-    const BOLT_11: &str = "lntbs1m1pjrmuu3pp52hk0j956d7s8azaps87amadshnrcvqtkvk06y2nue2w69g6e5vasdqqcqzpgxqyz5vqsp5wu3py6257pa3yzarw0et2200c08r5fu6k3u94yfwmlnc8skdkc9s9qyyssqc783940p82c64qq9pu3xczt4tdxzex9wpjn54486y866aayft2cxxusl9eags4cs3kcmuqdrvhvs0gudpj5r2a6awu4wcq29crpesjcqhdju55";
+        // nothing to prune in an empty graph
+        let pruned = nm.prune_network_graph().await.unwrap();
+        assert_eq!(pruned, size);
+
+        nm.set_network_graph_size_limit(None).unwrap();
+        assert_eq!(nm.get_network_graph_size_limit().unwrap(), None);
+    }
 
     #[test]
-    async fn create_node_manager() {
-        let test_name = "create_node_manager";
+    async fn disabled_price_fetching() {
+        let test_name = "disabled_price_fetching";
         log!("{}", test_name);
 
         let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
-
-        assert!(!NodeManager::has_node_manager(storage.clone()));
+        let seed = generate_seed(12).expect("Failed to gen seed");
         let c = MutinyWalletConfig::new(
-            None,
+            Some(seed),
             #[cfg(target_arch = "wasm32")]
             None,
             Some(Network::Regtest),
@@ -2114,20 +6785,48 @@ mod tests {
             None,
             None,
         );
-        NodeManager::new(c, storage.clone())
+        let nm = NodeManager::new(c, storage.clone())
             .await
             .expect("node manager should initialize");
-        assert!(NodeManager::has_node_manager(storage));
+
+        assert_eq!(nm.get_price_config().unwrap(), PriceConfig::default());
+
+        let bad = PriceConfig {
+            source: PriceSource::Custom("ftp://prices.example.com".to_string()),
+            cache_ttl_secs: 60,
+        };
+        assert!(nm.set_price_config(bad).await.is_err());
+
+        let config = PriceConfig {
+            source: PriceSource::Disabled,
+            cache_ttl_secs: 60,
+        };
+        nm.set_price_config(config.clone()).await.unwrap();
+        assert_eq!(nm.get_price_config().unwrap(), config);
+        assert_eq!(storage.get_price_config().unwrap(), Some(config));
+
+        // no price until the app gives us one
+        assert!(nm.get_bitcoin_price(None).await.is_err());
+        assert!(nm
+            .set_bitcoin_price(FiatCurrency::Usd, f32::NAN)
+            .await
+            .is_err());
+        nm.set_bitcoin_price(FiatCurrency::Usd, 30_000.0)
+            .await
+            .unwrap();
+        assert_eq!(nm.get_bitcoin_price(None).await.unwrap(), 30_000.0);
+        assert!(nm.get_bitcoin_price(Some(FiatCurrency::Eur)).await.is_err());
     }
 
     #[test]
-    async fn correctly_show_seed() {
-        let test_name = "correctly_show_seed";
+    async fn pay_from_best_node_needs_liquidity() {
+        let test_name = "pay_from_best_node_needs_liquidity";
         log!("{}", test_name);
 
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
         let seed = generate_seed(12).expect("Failed to gen seed");
         let c = MutinyWalletConfig::new(
-            Some(seed.clone()),
+            Some(seed),
             #[cfg(target_arch = "wasm32")]
             None,
             Some(Network::Regtest),
@@ -2135,17 +6834,27 @@ mod tests {
             None,
             None,
         );
-        let nm = NodeManager::new(c, ()).await.unwrap();
-
-        assert_eq!(seed, nm.show_seed());
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
+        nm.new_node().await.expect("should create new node");
+        nm.new_node().await.expect("should create new node");
+
+        // neither node has a channel to pay from
+        let to_node = nm.list_nodes().await.unwrap()[0];
+        let result = nm
+            .keysend_from_best_node(to_node, 1_000, None, vec![])
+            .await;
+        assert!(matches!(result, Err(MutinyError::InsufficientBalance)));
     }
 
     #[test]
-    async fn created_new_nodes() {
-        let test_name = "created_new_nodes";
+    async fn manage_lsps() {
+        let test_name = "manage_lsps";
         log!("{}", test_name);
 
         let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        storage.set_lsp_urls(vec![]).unwrap();
         let seed = generate_seed(12).expect("Failed to gen seed");
         let c = MutinyWalletConfig::new(
             Some(seed),
@@ -2159,29 +6868,54 @@ mod tests {
         let nm = NodeManager::new(c, storage)
             .await
             .expect("node manager should initialize");
+        nm.new_node().await.expect("should create new node");
 
-        {
-            let node_identity = nm.new_node().await.expect("should create new node");
-            let node_storage = nm.node_storage.lock().await;
-            assert_ne!("", node_identity.uuid);
-            assert_ne!("", node_identity.pubkey.to_string());
-            assert_eq!(1, node_storage.nodes.len());
+        assert!(nm.list_lsps().await.is_empty());
+        assert!(matches!(
+            nm.remove_lsp("https://lsp.example.com".to_string()).await,
+            Err(MutinyError::NotFound)
+        ));
 
-            let retrieved_node = node_storage.nodes.get(&node_identity.uuid).unwrap();
-            assert_eq!(0, retrieved_node.child_index);
-        }
+        // an LSP that isn't configured can't wrap the invoice
+        let result = nm
+            .create_invoice_with_lsp(
+                Some(10_000),
+                vec![],
+                Some("https://lsp.example.com".to_string()),
+            )
+            .await;
+        assert!(matches!(result, Err(MutinyError::NotFound)));
+    }
 
-        {
-            let node_identity = nm.new_node().await.expect("node manager should initialize");
-            let node_storage = nm.node_storage.lock().await;
+    #[test]
+    fn test_configured_lsp_urls() {
+        let test_name = "test_configured_lsp_urls";
+        log!("{}", test_name);
 
-            assert_ne!("", node_identity.uuid);
-            assert_ne!("", node_identity.pubkey.to_string());
-            assert_eq!(2, node_storage.nodes.len());
+        let saved = Some(vec!["https://saved.example.com".to_string()]);
 
-            let retrieved_node = node_storage.nodes.get(&node_identity.uuid).unwrap();
-            assert_eq!(1, retrieved_node.child_index);
-        }
+        // the config takes precedence over the saved LSPs
+        assert_eq!(
+            configured_lsp_urls(
+                Some("https://a.example.com, https://b.example.com"),
+                saved.clone()
+            ),
+            vec![
+                "https://a.example.com".to_string(),
+                "https://b.example.com".to_string()
+            ]
+        );
+
+        // the saved LSPs are used when the config doesn't set any
+        assert_eq!(
+            configured_lsp_urls(None, saved.clone()),
+            vec!["https://saved.example.com".to_string()]
+        );
+        assert_eq!(
+            configured_lsp_urls(Some(""), saved),
+            vec!["https://saved.example.com".to_string()]
+        );
+        assert!(configured_lsp_urls(None, None).is_empty());
     }
 
     #[test]
@@ -2245,6 +6979,354 @@ mod tests {
         assert_eq!(tx.labels, labels);
     }
 
+    #[test]
+    fn test_pending_claim() {
+        let test_name = "test_pending_claim";
+        log!("{}", test_name);
+
+        let node = PublicKey::from_str(
+            "02cae09cf2c8842ace44068a5bf3117a494ebbf69a99e79712483c36f97cdb7b54",
+        )
+        .unwrap();
+        let outpoint = OutPoint {
+            txid: Txid::all_zeros(),
+            vout: 0,
+        };
+
+        let awaiting = PendingClaim::new(
+            node,
+            outpoint,
+            Balance::ClaimableAwaitingConfirmations {
+                claimable_amount_satoshis: 10_000,
+                confirmation_height: 1_144,
+            },
+            1_000,
+        );
+        assert_eq!(awaiting.kind, "ClaimableAwaitingConfirmations");
+        assert_eq!(awaiting.amount_sats, 10_000);
+        assert_eq!(awaiting.blocks_until_spendable, Some(144));
+
+        let matured = PendingClaim::new(
+            node,
+            outpoint,
+            Balance::MaybeTimeoutClaimableHTLC {
+                claimable_amount_satoshis: 1_000,
+                claimable_height: 900,
+            },
+            1_000,
+        );
+        assert_eq!(matured.blocks_until_spendable, Some(0));
+
+        let unknown = PendingClaim::new(
+            node,
+            outpoint,
+            Balance::ClaimableOnChannelClose {
+                claimable_amount_satoshis: 1_000,
+            },
+            1_000,
+        );
+        assert_eq!(unknown.blocks_until_spendable, None);
+    }
+
+    #[test]
+    fn test_force_close_sweep_eta() {
+        let test_name = "test_force_close_sweep_eta";
+        log!("{}", test_name);
+
+        assert_eq!(force_close_sweep_eta_secs(0), 600);
+        // a 144 block delay is about a day
+        assert_eq!(force_close_sweep_eta_secs(144), 145 * 600);
+    }
+
+    #[test]
+    fn test_channel_closure_kind() {
+        let test_name = "test_channel_closure_kind";
+        log!("{}", test_name);
+
+        assert_eq!(
+            ChannelClosureKind::from(&ClosureReason::CooperativeClosure),
+            ChannelClosureKind::Cooperative
+        );
+        assert_eq!(
+            ChannelClosureKind::from(&ClosureReason::HolderForceClosed),
+            ChannelClosureKind::ForceClosedByUs
+        );
+        let kind = ChannelClosureKind::from(&ClosureReason::CounterpartyForceClosed {
+            peer_msg: "bye".to_string(),
+        });
+        assert_eq!(kind, ChannelClosureKind::ForceClosedByPeer);
+        assert!(kind.has_closing_tx());
+        assert!(!ChannelClosureKind::FundingTimedOut.has_closing_tx());
+
+        // closures saved before the kind was recorded still load
+        let old = serde_json::json!({
+            "user_channel_id": null,
+            "channel_id": null,
+            "node_id": null,
+            "reason": "coop-closed",
+            "timestamp": 1686258926,
+        });
+        let closure: ChannelClosure = serde_json::from_value(old).unwrap();
+        assert_eq!(closure.kind, ChannelClosureKind::Unknown);
+        assert_eq!(closure.reason, "coop-closed");
+        assert_eq!(closure.closing_txid, None);
+        assert_eq!(closure.swept_sats, None);
+    }
+
+    #[test]
+    fn test_pending_channel_stage() {
+        let test_name = "test_pending_channel_stage";
+        log!("{}", test_name);
+
+        assert_eq!(
+            PendingChannelStage::new(false, 0, 3),
+            PendingChannelStage::Negotiating
+        );
+        assert_eq!(
+            PendingChannelStage::new(true, 0, 3),
+            PendingChannelStage::FundingBroadcast
+        );
+        assert_eq!(
+            PendingChannelStage::new(true, 2, 3),
+            PendingChannelStage::Confirming
+        );
+        assert_eq!(
+            PendingChannelStage::new(true, 3, 3),
+            PendingChannelStage::AwaitingChannelReady
+        );
+        // zero conf channels only wait on the peer
+        assert_eq!(
+            PendingChannelStage::new(true, 0, 0),
+            PendingChannelStage::AwaitingChannelReady
+        );
+    }
+
+    #[test]
+    fn test_channel_confirmation_events() {
+        let test_name = "test_channel_confirmation_events";
+        log!("{}", test_name);
+
+        let channel = |id: &str, confirmations: u32| PendingChannel {
+            channel_id: id.to_string(),
+            user_chan_id: id.to_string(),
+            peer: PublicKey::from_str(
+                "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b",
+            )
+            .unwrap(),
+            size: 100_000,
+            is_outbound: true,
+            funding_txid: None,
+            confirmations,
+            confirmations_required: 3,
+            stage: PendingChannelStage::new(true, confirmations, 3),
+        };
+        let previous = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        let channels = vec![channel("a", 2), channel("b", 2), channel("c", 0)];
+
+        let events = channel_confirmation_events(&previous, &channels);
+        assert_eq!(
+            events,
+            vec![MutinyEvent::ChannelConfirmed {
+                channel_id: "a".to_string(),
+                confirmations: 2,
+                confirmations_required: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transaction_confirmation_events() {
+        let test_name = "test_transaction_confirmation_events";
+        log!("{}", test_name);
+
+        let txid = |i: u8| Txid::from_slice(&[i; 32]).unwrap();
+        let watched = |confirmations: u32| WatchedTransaction {
+            confirmations,
+            evicted: false,
+        };
+        let previous = HashMap::from([
+            (txid(1), watched(0)),
+            (txid(2), watched(3)),
+            (txid(3), watched(0)),
+            (txid(4), watched(10)),
+        ]);
+        let current = HashMap::from([
+            // confirmed since the last check
+            (txid(1), 1),
+            // became safe
+            (txid(2), 6),
+            // txid 3 was replaced, txid 4 is long safe
+            (txid(4), 11),
+            // new transactions are announced unless they are already safe
+            (txid(5), 0),
+            (txid(6), 2),
+            (txid(7), 100),
+        ]);
+
+        let mut events = transaction_confirmation_events(&previous, &current, 6);
+        events.sort_by_key(|e| format!("{e:?}"));
+        let mut expected = vec![
+            MutinyEvent::TransactionConfirmed {
+                txid: txid(1),
+                confirmations: 1,
+            },
+            MutinyEvent::TransactionConfirmed {
+                txid: txid(2),
+                confirmations: 6,
+            },
+            MutinyEvent::TransactionConfirmed {
+                txid: txid(6),
+                confirmations: 1,
+            },
+            MutinyEvent::TransactionReplaced { txid: txid(3) },
+        ];
+        expected.sort_by_key(|e| format!("{e:?}"));
+        assert_eq!(events, expected);
+
+        // the first confirmation is the safe one, it is only announced once
+        let previous = HashMap::from([(txid(1), watched(0))]);
+        let current = HashMap::from([(txid(1), 1)]);
+        assert_eq!(
+            transaction_confirmation_events(&previous, &current, 1),
+            vec![MutinyEvent::TransactionConfirmed {
+                txid: txid(1),
+                confirmations: 1,
+            }]
+        );
+
+        assert_eq!(
+            tx_confirmations(
+                &ConfirmationTime::Confirmed {
+                    height: 100,
+                    time: 0
+                },
+                105
+            ),
+            6
+        );
+        assert_eq!(tx_confirmations(&ConfirmationTime::Unconfirmed, 105), 0);
+    }
+
+    #[test]
+    fn test_blocks_until_force_close() {
+        let test_name = "test_blocks_until_force_close";
+        log!("{}", test_name);
+
+        assert_eq!(
+            blocks_until_force_close(1_000, 900),
+            100 + LATENCY_GRACE_PERIOD_BLOCKS
+        );
+        assert_eq!(
+            blocks_until_force_close(1_000, 1_000),
+            LATENCY_GRACE_PERIOD_BLOCKS
+        );
+        assert_eq!(blocks_until_force_close(1_000, 2_000), 0);
+    }
+
+    #[test]
+    async fn test_account_index() {
+        let test_name = "test_account_index";
+        log!("{}", test_name);
+
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let config = |account_index: Option<u32>| {
+            let c = MutinyWalletConfig::new(
+                Some(seed.clone()),
+                #[cfg(target_arch = "wasm32")]
+                None,
+                Some(Network::Regtest),
+                None,
+                None,
+                None,
+            );
+            match account_index {
+                Some(index) => c.with_account_index(index),
+                None => c,
+            }
+        };
+
+        let default_storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let nm = NodeManager::new(config(None), default_storage)
+            .await
+            .expect("node manager should initialize");
+        let default_node = nm.new_node().await.expect("should create new node");
+        let paths = nm.get_derivation_paths().await.unwrap();
+        assert_eq!(paths.account_index, 0);
+        assert_eq!(paths.onchain, Some("m/86'/1'/0'".to_string()));
+        assert!(paths.retired_onchain.is_empty());
+        assert_eq!(paths.lightning.len(), 1);
+        assert_eq!(paths.lightning[0].1, "m/0'/0'");
+
+        // the same seed with another account index is a separate wallet
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let nm = NodeManager::new(config(Some(3)), storage.clone())
+            .await
+            .expect("node manager should initialize");
+        let node = nm.new_node().await.expect("should create new node");
+        assert_ne!(node.pubkey, default_node.pubkey);
+        let paths = nm.get_derivation_paths().await.unwrap();
+        assert_eq!(paths.account_index, 3);
+        assert_eq!(paths.onchain, Some("m/86'/1'/3'".to_string()));
+        assert_eq!(
+            paths.lightning,
+            vec![(node.pubkey, "m/1'/3'/0'".to_string())]
+        );
+        assert_eq!(storage.get_account_index().unwrap(), Some(3));
+
+        // the saved index is kept, but can't be changed
+        let nm = NodeManager::new(config(None), storage.clone())
+            .await
+            .expect("node manager should initialize");
+        assert_eq!(nm.get_derivation_paths().await.unwrap().account_index, 3);
+        let err = NodeManager::new(config(Some(4)), storage).await;
+        assert!(matches!(err, Err(MutinyError::AccountIndexMismatch(3))));
+    }
+
+    #[test]
+    async fn test_network_mismatch_and_migration() {
+        let test_name = "test_network_mismatch_and_migration";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let config = |network: Option<Network>| {
+            MutinyWalletConfig::new(
+                Some(seed.clone()),
+                #[cfg(target_arch = "wasm32")]
+                None,
+                network,
+                None,
+                None,
+                None,
+            )
+        };
+
+        // the default network is signet, never mainnet
+        let nm = NodeManager::new(config(None), storage.clone())
+            .await
+            .expect("node manager should initialize");
+        assert_eq!(nm.get_network(), Network::Signet);
+        assert_eq!(storage.get_network().unwrap(), Some(Network::Signet));
+        nm.new_node().await.expect("should create new node");
+
+        // starting the same data on mainnet is refused
+        let err = NodeManager::new(config(Some(Network::Bitcoin)), storage.clone()).await;
+        assert!(matches!(
+            err,
+            Err(MutinyError::NetworkMismatch(Network::Signet))
+        ));
+
+        // test funds don't hold up moving to mainnet
+        let check = nm.check_network_migration(Network::Bitcoin).await.unwrap();
+        assert!(check.ready);
+        assert!(nm.check_network_migration(Network::Signet).await.is_err());
+
+        nm.migrate_network(Network::Bitcoin).await.unwrap();
+        assert_eq!(storage.get_network().unwrap(), Some(Network::Bitcoin));
+        assert!(storage.get_nodes().unwrap().nodes.is_empty());
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+    }
+
     #[test]
     fn test_bolt11_payment_info_into_mutiny_invoice() {
         let preimage: [u8; 32] =
@@ -2271,6 +7353,9 @@ mod tests {
             fee_paid_msat: None,
             bolt11: Some(invoice.clone()),
             payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             last_update: 1681781585,
         };
 
@@ -2281,12 +7366,20 @@ mod tests {
             preimage: Some(preimage.to_hex()),
             payee_pubkey: None,
             amount_sats: Some(100_000),
+            amount_msats: Some(100_000_000),
             expire: 1681781649 + 86400,
             paid: true,
             fees_paid: None,
+            fees_paid_msats: None,
             inbound: true,
             labels: labels.clone(),
             last_updated: 1681781585,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
         };
 
         let actual = MutinyInvoice::from(
@@ -2324,25 +7417,68 @@ mod tests {
             fee_paid_msat: Some(1_000),
             bolt11: None,
             payee_pubkey: Some(pubkey),
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
             last_update: 1681781585,
         };
 
         let expected: MutinyInvoice = MutinyInvoice {
             bolt11: None,
-            description: None,
-            payment_hash,
-            preimage: Some(preimage.to_hex()),
-            payee_pubkey: Some(pubkey),
-            amount_sats: Some(100),
-            expire: 1681781585,
-            paid: true,
-            fees_paid: Some(1),
-            inbound: false,
-            labels: vec![],
-            last_updated: 1681781585,
+            description: None,
+            payment_hash,
+            preimage: Some(preimage.to_hex()),
+            payee_pubkey: Some(pubkey),
+            amount_sats: Some(100),
+            amount_msats: Some(100_000),
+            expire: 1681781585,
+            paid: true,
+            fees_paid: Some(1),
+            fees_paid_msats: Some(1_000),
+            inbound: false,
+            labels: vec![],
+            last_updated: 1681781585,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
+        };
+
+        let actual = MutinyInvoice::from(
+            payment_info,
+            PaymentHash(payment_hash.into_inner()),
+            false,
+            vec![],
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_msat_rounding() {
+        let payment_hash = sha256::Hash::from_hex(
+            "55ecf9169a6fa07e8ba181fdddf5b0bcc7860176659fa22a7cca9da2a359a33b",
+        )
+        .unwrap();
+
+        let payment_info = PaymentInfo {
+            preimage: None,
+            secret: None,
+            status: HTLCStatus::Succeeded,
+            amt_msat: MillisatAmount(Some(100_999)),
+            fee_paid_msat: Some(1),
+            bolt11: None,
+            payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
+            last_update: 1681781585,
         };
 
-        let actual = MutinyInvoice::from(
+        let invoice = MutinyInvoice::from(
             payment_info,
             PaymentHash(payment_hash.into_inner()),
             false,
@@ -2350,7 +7486,11 @@ mod tests {
         )
         .unwrap();
 
-        assert_eq!(actual, expected);
+        // amounts round down and fees round up, the exact values are kept in msats
+        assert_eq!(invoice.amount_sats, Some(100));
+        assert_eq!(invoice.amount_msats, Some(100_999));
+        assert_eq!(invoice.fees_paid, Some(1));
+        assert_eq!(invoice.fees_paid_msats, Some(1));
     }
 
     #[test]
@@ -2373,7 +7513,10 @@ mod tests {
             user_channel_id: None,
             channel_id: None,
             node_id: None,
+            kind: ChannelClosureKind::Unknown,
             reason: "".to_string(),
+            closing_txid: None,
+            swept_sats: None,
             timestamp: 1686258926,
         };
 
@@ -2385,6 +7528,8 @@ mod tests {
             fee: None,
             confirmation_time: ConfirmationTime::Unconfirmed,
             labels: vec![],
+            memo: None,
+            fiat_price_at_time: None,
         };
 
         let tx2: TransactionDetails = TransactionDetails {
@@ -2398,6 +7543,8 @@ mod tests {
                 time: 1234,
             },
             labels: vec![],
+            memo: None,
+            fiat_price_at_time: None,
         };
 
         let invoice1: MutinyInvoice = MutinyInvoice {
@@ -2407,12 +7554,20 @@ mod tests {
             preimage: Some(preimage.to_hex()),
             payee_pubkey: Some(pubkey),
             amount_sats: Some(100),
+            amount_msats: Some(100_000),
             expire: 1681781585,
             paid: true,
             fees_paid: Some(1),
+            fees_paid_msats: Some(1_000),
             inbound: false,
             labels: vec![],
             last_updated: 1681781585,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
         };
 
         let invoice2: MutinyInvoice = MutinyInvoice {
@@ -2422,12 +7577,20 @@ mod tests {
             preimage: Some(preimage.to_hex()),
             payee_pubkey: Some(pubkey),
             amount_sats: Some(100),
+            amount_msats: Some(100_000),
             expire: 1681781585,
             paid: true,
             fees_paid: Some(1),
+            fees_paid_msats: Some(1_000),
             inbound: false,
             labels: vec![],
             last_updated: 1781781585,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
         };
 
         let mut vec = vec![
@@ -2450,4 +7613,643 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_channel_monitor_summary_csv() {
+        let pubkey = PublicKey::from_str(
+            "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b",
+        )
+        .unwrap();
+
+        let mut summary = ChannelMonitorSummary {
+            node: pubkey,
+            outpoint: OutPoint::null(),
+            counterparty: None,
+            latest_update_id: 3,
+            best_block_height: 100,
+            balances: vec![],
+        };
+
+        let csv = summary.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with(",,3,100,,,"));
+
+        summary.balances = vec![
+            MonitorBalance {
+                kind: "ClaimableOnChannelClose".to_string(),
+                amount_sats: 10_000,
+                height: None,
+            },
+            MonitorBalance {
+                kind: "ClaimableAwaitingConfirmations".to_string(),
+                amount_sats: 5_000,
+                height: Some(144),
+            },
+        ];
+
+        let csv = summary.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with(",ClaimableOnChannelClose,10000,"));
+        assert!(lines[2].ends_with(",ClaimableAwaitingConfirmations,5000,144"));
+    }
+
+    #[test]
+    fn test_aggregate_dust_payments() {
+        let payment_hash = sha256::Hash::from_hex(
+            "55ecf9169a6fa07e8ba181fdddf5b0bcc7860176659fa22a7cca9da2a359a33b",
+        )
+        .unwrap();
+        let pubkey = PublicKey::from_str(
+            "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b",
+        )
+        .unwrap();
+
+        let boost = MutinyInvoice {
+            bolt11: None,
+            description: None,
+            payment_hash,
+            preimage: None,
+            payee_pubkey: Some(pubkey),
+            amount_sats: Some(10),
+            amount_msats: Some(10_000),
+            expire: 1681781585,
+            paid: true,
+            fees_paid: Some(1),
+            fees_paid_msats: Some(1_000),
+            inbound: false,
+            labels: vec![],
+            last_updated: 1681781585,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
+        };
+        let boost2 = MutinyInvoice {
+            last_updated: 1681781600,
+            ..boost.clone()
+        };
+        let next_day = MutinyInvoice {
+            last_updated: 1681781585 + 60 * 60 * 24,
+            ..boost.clone()
+        };
+        let large = MutinyInvoice {
+            amount_sats: Some(10_000),
+            amount_msats: Some(10_000_000),
+            ..boost.clone()
+        };
+
+        let activity = vec![
+            ActivityItem::Lightning(Box::new(boost.clone())),
+            ActivityItem::Lightning(Box::new(boost2.clone())),
+            ActivityItem::Lightning(Box::new(next_day.clone())),
+            ActivityItem::Lightning(Box::new(large.clone())),
+        ];
+        let aggregated = aggregate_dust_payments(activity, 100);
+
+        assert_eq!(aggregated.len(), 3);
+        assert_eq!(aggregated[0], ActivityItem::Lightning(Box::new(next_day)));
+        match &aggregated[1] {
+            ActivityItem::AggregatedLightning(a) => {
+                assert!(!a.inbound);
+                assert_eq!(a.counterparty, Some(pubkey));
+                assert_eq!(a.day, 1681776000);
+                assert_eq!(a.total_sats, 20);
+                assert_eq!(a.total_fees, 2);
+                assert_eq!(a.last_updated, boost2.last_updated);
+                assert_eq!(a.payments, vec![boost2, boost]);
+            }
+            _ => panic!("expected aggregated payments"),
+        }
+        assert_eq!(aggregated[2], ActivityItem::Lightning(Box::new(large)));
+    }
+
+    #[test]
+    fn test_fee_summary() {
+        let payment_hash = sha256::Hash::from_hex(
+            "55ecf9169a6fa07e8ba181fdddf5b0bcc7860176659fa22a7cca9da2a359a33b",
+        )
+        .unwrap();
+
+        let outbound = MutinyInvoice {
+            bolt11: None,
+            description: None,
+            payment_hash,
+            preimage: None,
+            payee_pubkey: None,
+            amount_sats: Some(100),
+            amount_msats: None,
+            expire: 1681781585,
+            paid: true,
+            fees_paid: Some(2),
+            fees_paid_msats: None,
+            inbound: false,
+            labels: vec![],
+            last_updated: 1000,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: None,
+        };
+        let inbound = MutinyInvoice {
+            fees_paid: Some(5),
+            inbound: true,
+            last_updated: 2000,
+            ..outbound.clone()
+        };
+        let unpaid = MutinyInvoice {
+            fees_paid: Some(50),
+            paid: false,
+            ..outbound.clone()
+        };
+
+        let sent_tx = TransactionDetails {
+            transaction: None,
+            txid: Txid::all_zeros(),
+            received: 0,
+            sent: 10_000,
+            fee: Some(200),
+            confirmation_time: ConfirmationTime::Confirmed {
+                height: 1,
+                time: 1500,
+            },
+            labels: vec![],
+            memo: None,
+            fiat_price_at_time: None,
+        };
+        let received_tx = TransactionDetails {
+            received: 10_000,
+            sent: 0,
+            fee: Some(300),
+            ..sent_tx.clone()
+        };
+
+        let invoices = vec![outbound, inbound, unpaid];
+        let txs = vec![sent_tx, received_tx];
+
+        let summary = FeeSummary::from_activity(&invoices, &txs, None, None);
+        assert_eq!(
+            summary,
+            FeeSummary {
+                lightning_fees: 2,
+                lsp_fees: 5,
+                onchain_fees: 200,
+                total: 207,
+            }
+        );
+
+        let summary = FeeSummary::from_activity(&invoices, &txs, Some(1200), Some(1800));
+        assert_eq!(
+            summary,
+            FeeSummary {
+                lightning_fees: 0,
+                lsp_fees: 0,
+                onchain_fees: 200,
+                total: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fiat_value_at_time() {
+        let test_name = "test_fiat_value_at_time";
+        log!("{}", test_name);
+
+        let price = HistoricalPrice {
+            currency: FiatCurrency::Usd,
+            price: 25_000.0,
+            timestamp: 1000,
+        };
+
+        let payment = MutinyInvoice {
+            bolt11: None,
+            description: None,
+            payment_hash: sha256::Hash::hash(&[1; 32]),
+            preimage: None,
+            payee_pubkey: None,
+            amount_sats: Some(40_000),
+            amount_msats: None,
+            expire: 1681781585,
+            paid: true,
+            fees_paid: None,
+            fees_paid_msats: None,
+            inbound: true,
+            labels: vec![],
+            last_updated: 1000,
+            degraded_hints: false,
+            lsp_selection: None,
+            parts: vec![],
+            lnurl_verified_preimage: None,
+            memo: None,
+            fiat_price_at_time: Some(price),
+        };
+        let unpriced = MutinyInvoice {
+            payment_hash: sha256::Hash::hash(&[2; 32]),
+            last_updated: 2000,
+            fiat_price_at_time: None,
+            ..payment.clone()
+        };
+        let sent_tx = TransactionDetails {
+            transaction: None,
+            txid: Txid::all_zeros(),
+            received: 20_000,
+            sent: 100_000,
+            fee: Some(200),
+            confirmation_time: ConfirmationTime::Confirmed {
+                height: 1,
+                time: 1500,
+            },
+            labels: vec![],
+            memo: None,
+            fiat_price_at_time: Some(price),
+        };
+
+        let item = ActivityItem::Lightning(Box::new(payment.clone()));
+        let value = item.fiat_value_at_time().unwrap();
+        assert_eq!(value.amount, 10.0);
+        assert_eq!(value.format(), "$10.00");
+        assert_eq!(
+            ActivityItem::OnChain(sent_tx.clone())
+                .fiat_value_at_time()
+                .unwrap()
+                .amount,
+            20.0
+        );
+        assert_eq!(
+            ActivityItem::Lightning(Box::new(unpriced.clone())).fiat_value_at_time(),
+            None
+        );
+
+        // aggregated payments are only valued when all of them are
+        let priced_twice = MutinyInvoice {
+            payment_hash: sha256::Hash::hash(&[3; 32]),
+            ..payment.clone()
+        };
+        let activity = vec![
+            ActivityItem::Lightning(Box::new(payment.clone())),
+            ActivityItem::Lightning(Box::new(priced_twice)),
+        ];
+        let aggregated = aggregate_dust_payments(activity, 100_000);
+        assert_eq!(aggregated[0].fiat_value_at_time().unwrap().amount, 20.0);
+
+        let activity = vec![
+            ActivityItem::Lightning(Box::new(payment.clone())),
+            ActivityItem::Lightning(Box::new(unpriced.clone())),
+        ];
+        let aggregated = aggregate_dust_payments(activity, 100_000);
+        assert_eq!(aggregated[0].fiat_value_at_time(), None);
+
+        let activity = vec![
+            ActivityItem::Lightning(Box::new(payment)),
+            ActivityItem::Lightning(Box::new(unpriced.clone())),
+            ActivityItem::OnChain(TransactionDetails {
+                fiat_price_at_time: None,
+                confirmation_time: ConfirmationTime::Unconfirmed,
+                ..sent_tx
+            }),
+        ];
+        assert_eq!(
+            unpriced_activity(&activity, 0),
+            vec![(unpriced.payment_hash.to_hex(), 2000)]
+        );
+        assert!(unpriced_activity(&activity, 2001).is_empty());
+    }
+
+    #[test]
+    fn test_max_fee_limit() {
+        let test_name = "test_max_fee_limit";
+        log!("{}", test_name);
+
+        assert_eq!(MaxFee::default().limit_msat(100_000), u64::MAX);
+
+        let sats = MaxFee {
+            max_fee_sats: Some(5),
+            max_fee_percent: None,
+        };
+        assert_eq!(sats.limit_msat(100_000), 5_000);
+
+        let percent = MaxFee {
+            max_fee_sats: None,
+            max_fee_percent: Some(1.0),
+        };
+        assert_eq!(percent.limit_msat(100_000), 1_000);
+
+        // the lower limit is used
+        let both = MaxFee {
+            max_fee_sats: Some(5),
+            max_fee_percent: Some(1.0),
+        };
+        assert_eq!(both.limit_msat(100_000), 1_000);
+        assert_eq!(both.limit_msat(10_000_000), 5_000);
+    }
+
+    #[test]
+    fn test_mpp_max_path_count() {
+        let test_name = "test_mpp_max_path_count";
+        log!("{}", test_name);
+
+        let no_split = MppOptions {
+            allow_split: false,
+            max_parts: Some(5),
+            min_part_sats: None,
+        };
+        assert_eq!(no_split.max_path_count(1_000_000), 1);
+
+        let default = MppOptions {
+            allow_split: true,
+            max_parts: None,
+            min_part_sats: None,
+        };
+        assert_eq!(default.max_path_count(1_000_000), DEFAULT_MAX_PATH_COUNT);
+
+        // the min part size limits the parts to 1_000 / 300 = 3
+        let min_part = MppOptions {
+            allow_split: true,
+            max_parts: Some(5),
+            min_part_sats: Some(300),
+        };
+        assert_eq!(min_part.max_path_count(1_000_000), 3);
+        // always at least one part
+        assert_eq!(min_part.max_path_count(100_000), 1);
+    }
+
+    #[test]
+    fn test_channel_fee_policy() {
+        let test_name = "test_channel_fee_policy";
+        log!("{}", test_name);
+
+        let policy = ChannelFeePolicy {
+            base_fee_msat: 1_000,
+            proportional_millionths: 100,
+            cltv_expiry_delta: 144,
+        };
+        let default_config = ChannelConfig {
+            max_dust_htlc_exposure_msat: 20_000_000,
+            ..Default::default()
+        };
+        let config = policy.apply(default_config);
+        assert_eq!(config.forwarding_fee_base_msat, 1_000);
+        assert_eq!(config.forwarding_fee_proportional_millionths, 100);
+        assert_eq!(config.cltv_expiry_delta, 144);
+        // everything else is left alone
+        assert_eq!(config.max_dust_htlc_exposure_msat, 20_000_000);
+
+        let storage = MemoryStorage::default();
+        assert!(storage.get_channel_fee_policies().unwrap().is_empty());
+        let outpoint = "0000000000000000000000000000000000000000000000000000000000000000:1";
+        storage
+            .set_channel_fee_policy(outpoint.to_string(), policy)
+            .unwrap();
+        assert_eq!(
+            storage.get_channel_fee_policies().unwrap().get(outpoint),
+            Some(&policy)
+        );
+    }
+
+    #[test]
+    fn test_node_announcement_config() {
+        let test_name = "test_node_announcement_config";
+        log!("{}", test_name);
+
+        let config = NodeAnnouncementConfig {
+            alias: "mutiny".to_string(),
+            color: "#ff9900".to_string(),
+            addresses: vec!["1.2.3.4:9735".to_string(), "5.6.7.8".to_string()],
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rgb().unwrap(), [0xff, 0x99, 0x00]);
+        assert_eq!(&config.alias_bytes().unwrap()[..6], b"mutiny");
+        assert_eq!(config.alias_bytes().unwrap()[6..], [0u8; 26]);
+        assert_eq!(config.net_addresses(None).unwrap().len(), 1);
+        assert_eq!(
+            config.net_addresses(Some(9736)).unwrap()[1],
+            NetAddress::IPv4 {
+                addr: [5, 6, 7, 8],
+                port: 9736
+            }
+        );
+
+        let long_alias = NodeAnnouncementConfig {
+            alias: "a".repeat(33),
+            ..config.clone()
+        };
+        assert!(long_alias.validate().is_err());
+
+        let bad_color = NodeAnnouncementConfig {
+            color: "ff99".to_string(),
+            ..config.clone()
+        };
+        assert!(bad_color.validate().is_err());
+
+        let bad_address = NodeAnnouncementConfig {
+            addresses: vec!["not an address".to_string()],
+            ..config.clone()
+        };
+        assert!(bad_address.validate().is_err());
+
+        let storage = MemoryStorage::default();
+        assert!(storage.get_node_announcement_config().unwrap().is_none());
+        storage
+            .set_node_announcement_config(config.clone())
+            .unwrap();
+        assert_eq!(
+            storage.get_node_announcement_config().unwrap(),
+            Some(config)
+        );
+    }
+
+    #[test]
+    fn test_channel_acceptance_policy() {
+        let test_name = "test_channel_acceptance_policy";
+        log!("{}", test_name);
+
+        let peer = PublicKey::from_str(
+            "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b",
+        )
+        .unwrap();
+        let other_peer = PublicKey::from_str(
+            "02cae09cf2c8842ace44068a5bf3117a494ebbf69a99e79712483c36f97cdb7b54",
+        )
+        .unwrap();
+        let request = ChannelRequest {
+            counterparty: peer,
+            funding_satoshis: 100_000,
+            push_msat: 0,
+            our_reserve_sats: Some(1_000),
+        };
+
+        // everything is accepted by default
+        let policy = ChannelAcceptancePolicy::default();
+        assert!(policy.check(&request, 100).is_ok());
+
+        let policy = ChannelAcceptancePolicy {
+            min_channel_size_sats: Some(50_000),
+            allowed_peers: Some(vec![peer]),
+            max_channels: Some(2),
+            require_zero_reserve: false,
+        };
+        assert!(policy.check(&request, 1).is_ok());
+        assert!(policy.check(&request, 2).is_err());
+
+        let small = ChannelRequest {
+            funding_satoshis: 20_000,
+            ..request.clone()
+        };
+        assert!(policy.check(&small, 0).is_err());
+
+        let stranger = ChannelRequest {
+            counterparty: other_peer,
+            ..request.clone()
+        };
+        assert!(policy.check(&stranger, 0).is_err());
+
+        let zero_reserve = ChannelAcceptancePolicy {
+            require_zero_reserve: true,
+            ..Default::default()
+        };
+        assert!(zero_reserve.check(&request, 0).is_err());
+        let no_reserve = ChannelRequest {
+            our_reserve_sats: Some(0),
+            ..request
+        };
+        assert!(zero_reserve.check(&no_reserve, 0).is_ok());
+
+        let storage = MemoryStorage::default();
+        assert!(storage.get_channel_acceptance_policy().unwrap().is_none());
+        storage
+            .set_channel_acceptance_policy(policy.clone())
+            .unwrap();
+        assert_eq!(
+            storage.get_channel_acceptance_policy().unwrap(),
+            Some(policy)
+        );
+    }
+
+    #[test]
+    fn test_routing_stats() {
+        let test_name = "test_routing_stats";
+        log!("{}", test_name);
+
+        let forward = |from: &str, to: &str, fee: Option<u64>, timestamp: u64| ForwardedPayment {
+            inbound_channel_id: Some(from.to_string()),
+            outbound_channel_id: Some(to.to_string()),
+            fee_earned_msat: fee,
+            claimed_on_chain: false,
+            timestamp,
+        };
+        let forwards = vec![
+            forward("a", "b", Some(1_000), 100),
+            forward("b", "a", Some(500), 200),
+            forward("a", "b", None, 300),
+            forward("c", "b", Some(2_000), 400),
+        ];
+
+        let stats = RoutingStats::from_forwards(&forwards, None, None);
+        assert_eq!(stats.forwards, 4);
+        assert_eq!(stats.fees_earned_msat, 3_500);
+        assert_eq!(
+            stats.channels,
+            vec![
+                ChannelRoutingStats {
+                    channel_id: "b".to_string(),
+                    forwards_in: 1,
+                    forwards_out: 3,
+                    fees_earned_msat: 3_000,
+                    last_forward: 400,
+                },
+                ChannelRoutingStats {
+                    channel_id: "a".to_string(),
+                    forwards_in: 2,
+                    forwards_out: 1,
+                    fees_earned_msat: 500,
+                    last_forward: 300,
+                },
+                ChannelRoutingStats {
+                    channel_id: "c".to_string(),
+                    forwards_in: 1,
+                    forwards_out: 0,
+                    fees_earned_msat: 0,
+                    last_forward: 400,
+                },
+            ]
+        );
+
+        let stats = RoutingStats::from_forwards(&forwards, Some(200), Some(300));
+        assert_eq!(stats.forwards, 2);
+        assert_eq!(stats.fees_earned_msat, 500);
+        assert_eq!(stats.channels.len(), 2);
+    }
+
+    #[test]
+    fn test_usage_stats() {
+        let test_name = "test_usage_stats";
+        log!("{}", test_name);
+
+        let payment = |status: HTLCStatus, fee_paid_msat: Option<u64>| PaymentInfo {
+            preimage: None,
+            secret: None,
+            status,
+            amt_msat: MillisatAmount(Some(100_000)),
+            fee_paid_msat,
+            bolt11: None,
+            payee_pubkey: None,
+            failure_reason: None,
+            lsp_selection: None,
+            parts: vec![],
+            last_update: 1000,
+        };
+        let outbound = vec![
+            payment(HTLCStatus::Succeeded, Some(1_000)),
+            payment(HTLCStatus::Succeeded, Some(3_000)),
+            payment(HTLCStatus::Succeeded, None),
+            payment(HTLCStatus::Failed, None),
+            // in flight payments are not counted yet
+            payment(HTLCStatus::InFlight, None),
+        ];
+        let inbound = vec![
+            payment(HTLCStatus::Succeeded, None),
+            payment(HTLCStatus::Pending, None),
+        ];
+
+        let sent_tx = TransactionDetails {
+            transaction: None,
+            txid: Txid::all_zeros(),
+            received: 0,
+            sent: 10_000,
+            fee: Some(300),
+            confirmation_time: ConfirmationTime::Unconfirmed,
+            labels: vec![],
+            memo: None,
+            fiat_price_at_time: None,
+        };
+        let received_tx = TransactionDetails {
+            received: 10_000,
+            sent: 0,
+            ..sent_tx.clone()
+        };
+
+        let stats = UsageStats::from_activity(&outbound, &inbound, &[sent_tx, received_tx]);
+        assert_eq!(
+            stats,
+            UsageStats {
+                payments_sent: 3,
+                payments_failed: 1,
+                payment_success_rate: Some(75),
+                payments_received: 1,
+                average_lightning_fee_msats: Some(1_333),
+                onchain_transactions_sent: 1,
+                average_onchain_fee: Some(300),
+            }
+        );
+
+        // nothing to average without any activity
+        let empty = UsageStats::from_activity(&[], &[], &[]);
+        assert_eq!(empty.payment_success_rate, None);
+        assert_eq!(empty.average_lightning_fee_msats, None);
+    }
 }
@@ -1,13 +1,27 @@
+use crate::audit::{Integration, IntegrationAuditRecord};
 use crate::error::MutinyError;
-use crate::nodemanager::NodeManager;
+use crate::lnurlpay::PayCallbackResponse;
+use crate::nodemanager::{MutinyInvoice, NodeManager, PaymentOptions};
+use crate::nostr::receive::{
+    MakeInvoiceParams, MakeInvoiceResult, ReceiveCredential, ReceiveCredentialStorage,
+    ReceiveRateLimiter, ReceiveRequest, ReceiveResponse,
+};
+use crate::nostr::zap::{
+    create_zap_request, zap_callback_url, ProfileLnurl, ZapPayResponse, ZapRecord, ZapStorage,
+    ZapTarget,
+};
 use crate::storage::MutinyStorage;
 use crate::utils;
 use anyhow::anyhow;
-use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, XOnlyPublicKey};
 use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
 use lightning::util::logger::Logger;
 use lightning::{log_error, log_warn};
-use lightning_invoice::Invoice;
+use lightning_invoice::{Invoice, InvoiceDescription};
+use lnurl::lightning_address::LightningAddress;
+use lnurl::lnurl::LnUrl;
 use nostr::key::SecretKey;
 use nostr::nips::nip47::{
     ErrorCode, Method, NIP47Error, NostrWalletConnectURI, Request, Response, ResponseResult,
@@ -15,7 +29,16 @@ use nostr::nips::nip47::{
 use nostr::prelude::{decrypt, encrypt};
 use nostr::{Event, EventBuilder, EventId, Filter, Keys, Kind, Tag, Timestamp};
 use nostr_sdk::Client;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::str::FromStr;
+use std::time::Duration;
+
+pub mod receive;
+pub mod zap;
+
+/// Receive credential keys are derived after the keys we use ourselves
+const RECEIVE_CREDENTIAL_KEY_OFFSET: u32 = 1_000;
 
 const MAX_ZAP_AMOUNT_SATS: u64 = 10_000;
 
@@ -32,6 +55,13 @@ pub struct NostrManager {
     /// Mutiny will use this key to decrypt messages from the nostr client.
     nwc_server_key: Keys,
     pub relays: Vec<String>,
+    /// The SOCKS5 proxy relay connections go through, if any.
+    /// On wasm the browser makes the websocket connections.
+    #[cfg(not(target_arch = "wasm32"))]
+    relay_proxy: Option<SocketAddr>,
+    /// Used to derive the keys of receive credentials
+    xprivkey: ExtendedPrivKey,
+    receive_rate_limiter: ReceiveRateLimiter,
 }
 
 impl NostrManager {
@@ -46,15 +76,28 @@ impl NostrManager {
         Ok(Keys::new(secret_key))
     }
 
-    /// Creates a new NostrManager
+    /// Creates a new NostrManager, connecting to the relays through `socks_proxy` if one is given
     pub fn from_mnemonic(
         xprivkey: ExtendedPrivKey,
         relays: Vec<String>,
+        #[cfg(not(target_arch = "wasm32"))] socks_proxy: Option<&str>,
     ) -> Result<Self, MutinyError> {
         if relays.is_empty() {
             return Err(MutinyError::Other(anyhow!("No relays provided")));
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        let relay_proxy = match socks_proxy {
+            Some(proxy) => Some(
+                proxy
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                    .ok_or(MutinyError::InvalidArgumentsError)?,
+            ),
+            None => None,
+        };
+
         let primary_key = Self::derive_nostr_key(xprivkey, 0)?;
         let nwc_client_key = Self::derive_nostr_key(xprivkey, 1)?;
         let nwc_server_key = Self::derive_nostr_key(xprivkey, 2)?;
@@ -64,9 +107,22 @@ impl NostrManager {
             nwc_client_key,
             nwc_server_key,
             relays,
+            #[cfg(not(target_arch = "wasm32"))]
+            relay_proxy,
+            xprivkey,
+            receive_rate_limiter: ReceiveRateLimiter::default(),
         })
     }
 
+    /// Our relays with the proxy to connect to them through, for [Client::add_relays]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn proxied_relays(&self) -> Vec<(String, Option<SocketAddr>)> {
+        self.relays
+            .iter()
+            .map(|relay| (relay.clone(), self.relay_proxy))
+            .collect()
+    }
+
     pub fn get_nwc_uri(&self) -> anyhow::Result<String> {
         let relay_url = self.relays.first().ok_or(anyhow!("No relays"))?;
         let uri = NostrWalletConnectURI::new(
@@ -79,6 +135,93 @@ impl NostrManager {
         Ok(uri.to_string())
     }
 
+    fn receive_credential_key(&self, index: u32) -> Result<Keys, MutinyError> {
+        Self::derive_nostr_key(
+            self.xprivkey,
+            (RECEIVE_CREDENTIAL_KEY_OFFSET + index) as i32,
+        )
+    }
+
+    /// Creates a Nostr Wallet Connect URI for a receive credential
+    fn receive_credential_uri(&self, index: u32) -> Result<String, MutinyError> {
+        let keys = self.receive_credential_key(index)?;
+        let relay_url = self
+            .relays
+            .first()
+            .ok_or(MutinyError::Other(anyhow!("No relays")))?;
+        let uri = NostrWalletConnectURI::new(
+            self.nwc_server_key.public_key(),
+            relay_url
+                .parse()
+                .map_err(|_| MutinyError::InvalidArgumentsError)?,
+            Some(
+                keys.secret_key()
+                    .map_err(|e| MutinyError::Other(e.into()))?,
+            ),
+            None,
+        )
+        .map_err(|e| MutinyError::Other(anyhow!("Failed to create NWC URI: {e}")))?;
+
+        Ok(uri.to_string())
+    }
+
+    /// Creates a credential a third party can use to create invoices for this wallet,
+    /// it can't pay invoices or see anything else. Returns the credential and the
+    /// Nostr Wallet Connect URI to give to the third party.
+    pub fn create_receive_credential<S: MutinyStorage>(
+        &self,
+        storage: &S,
+        name: String,
+        max_invoices_per_hour: u32,
+        max_amount_sats: Option<u64>,
+    ) -> Result<(ReceiveCredential, String), MutinyError> {
+        // revoked credentials are kept so their index is never reused
+        let index = storage.list_receive_credentials()?.len() as u32;
+        let keys = self.receive_credential_key(index)?;
+
+        let credential = ReceiveCredential {
+            index,
+            name,
+            client_pubkey: keys.public_key().to_string(),
+            max_invoices_per_hour,
+            max_amount_sats,
+            revoked: false,
+            created_at: utils::now().as_secs(),
+        };
+        storage.persist_receive_credential(&credential)?;
+
+        let uri = self.receive_credential_uri(index)?;
+        Ok((credential, uri))
+    }
+
+    /// Gets the Nostr Wallet Connect URI of a receive credential again
+    pub fn get_receive_credential_uri<S: MutinyStorage>(
+        &self,
+        storage: &S,
+        client_pubkey: &str,
+    ) -> Result<String, MutinyError> {
+        let credential = storage
+            .get_receive_credential(client_pubkey)?
+            .ok_or(MutinyError::NotFound)?;
+        if credential.revoked {
+            return Err(MutinyError::NotFound);
+        }
+        self.receive_credential_uri(credential.index)
+    }
+
+    /// Revokes a receive credential, its requests are rejected from now on
+    pub fn revoke_receive_credential<S: MutinyStorage>(
+        &self,
+        storage: &S,
+        client_pubkey: &str,
+    ) -> Result<(), MutinyError> {
+        let mut credential = storage
+            .get_receive_credential(client_pubkey)?
+            .ok_or(MutinyError::NotFound)?;
+        credential.revoked = true;
+        storage.persist_receive_credential(&credential)
+    }
+
     pub fn create_nwc_filter(&self) -> Filter {
         let client_pubkey = self.nwc_client_key.public_key();
         let server_pubkey = self.nwc_server_key.public_key();
@@ -92,6 +235,19 @@ impl NostrManager {
             .since(Timestamp::from(fifteen_mins_ago))
     }
 
+    /// Filter for requests from receive credentials. Any author is accepted
+    /// so credentials created or revoked later are handled without resubscribing.
+    pub fn create_receive_filter(&self) -> Filter {
+        let server_pubkey = self.nwc_server_key.public_key();
+
+        let fifteen_mins_ago = utils::now().as_secs() - 15 * 60;
+
+        Filter::new()
+            .kinds(vec![Kind::WalletConnectRequest])
+            .pubkey(server_pubkey)
+            .since(Timestamp::from(fifteen_mins_ago))
+    }
+
     /// Create Nostr Wallet Connect Info event
     pub fn create_nwc_info_event(&self) -> anyhow::Result<Event> {
         let info = EventBuilder::new(Kind::WalletConnectInfo, "pay_invoice".to_string(), &[])
@@ -106,9 +262,7 @@ impl NostrManager {
         client.add_relays(self.relays.clone()).await?;
 
         #[cfg(not(target_arch = "wasm32"))]
-        client
-            .add_relays(self.relays.clone().into_iter().map(|s| (s, None)).collect())
-            .await?;
+        client.add_relays(self.proxied_relays()).await?;
 
         client.connect().await;
 
@@ -118,6 +272,172 @@ impl NostrManager {
         Ok(event_id)
     }
 
+    /// Creates a client for our primary key that is connected to our relays
+    async fn connect_client(&self) -> Result<Client, MutinyError> {
+        let client = Client::new(&self.primary_key);
+
+        #[cfg(target_arch = "wasm32")]
+        let res = client.add_relays(self.relays.clone()).await;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let res = client.add_relays(self.proxied_relays()).await;
+
+        res.map_err(|e| MutinyError::Other(anyhow!("Failed to add relays: {e}")))?;
+        client.connect().await;
+
+        Ok(client)
+    }
+
+    /// Fetches the most recent event matching the filter from our relays
+    async fn fetch_event(&self, client: &Client, filter: Filter) -> Result<Event, MutinyError> {
+        let events = client
+            .get_events_of(vec![filter.limit(1)], Some(Duration::from_secs(10)))
+            .await
+            .map_err(|e| MutinyError::Other(anyhow!("Failed to fetch nostr event: {e}")))?;
+
+        events
+            .into_iter()
+            .max_by_key(|e| e.created_at)
+            .ok_or(MutinyError::NotFound)
+    }
+
+    /// Zaps a note or profile (NIP-57).
+    ///
+    /// The zap request is signed with our primary key and sent to the LNURL in the
+    /// recipient's profile, the invoice it returns is paid and the zap is saved
+    /// with the note it was for, see [ZapStorage].
+    pub async fn zap<S: MutinyStorage>(
+        &self,
+        node_manager: &NodeManager<S>,
+        from_node: &PublicKey,
+        target: ZapTarget,
+        amount_sats: u64,
+        comment: Option<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let client = self.connect_client().await?;
+
+        // zaps for a note go to its author
+        let recipient: XOnlyPublicKey = match &target {
+            ZapTarget::Note(id) => {
+                let note = self
+                    .fetch_event(&client, Filter::new().id(id.to_hex()))
+                    .await?;
+                note.pubkey
+            }
+            ZapTarget::Profile(pubkey) => *pubkey,
+        };
+
+        let profile = self
+            .fetch_event(
+                &client,
+                Filter::new()
+                    .kinds(vec![Kind::Metadata])
+                    .author(recipient.to_string()),
+            )
+            .await?;
+        if let Err(e) = client.disconnect().await {
+            log_warn!(
+                node_manager.logger,
+                "Error disconnecting from nostr relay: {e}"
+            );
+        }
+
+        let profile: ProfileLnurl = serde_json::from_str(&profile.content)?;
+        let lnurl = match (profile.lud16, profile.lud06) {
+            (Some(lud16), _) => LightningAddress::from_str(&lud16)
+                .map_err(|_| MutinyError::LnUrlFailure)?
+                .lnurl(),
+            (None, Some(lud06)) => {
+                LnUrl::from_str(&lud06).map_err(|_| MutinyError::LnUrlFailure)?
+            }
+            (None, None) => return Err(MutinyError::LnUrlFailure),
+        };
+
+        let pay: ZapPayResponse = node_manager
+            .http_client
+            .get(&lnurl.url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?;
+
+        let msats = amount_sats * 1_000;
+        if !pay.allows_nostr || pay.nostr_pubkey.is_none() {
+            log_error!(node_manager.logger, "LNURL does not accept zaps");
+            return Err(MutinyError::IncorrectLnUrlFunction);
+        }
+        if msats < pay.min_sendable || msats > pay.max_sendable {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        let comment = comment.filter(|c| !c.is_empty());
+        let lnurl_bech32 = lnurl.encode();
+        let zap_request = create_zap_request(
+            &self.primary_key,
+            &target,
+            recipient,
+            msats,
+            &lnurl_bech32,
+            &self.relays,
+            comment.as_deref().unwrap_or_default(),
+        )?;
+
+        let url = zap_callback_url(&pay.callback, msats, &zap_request, &lnurl_bech32)?;
+        let callback: PayCallbackResponse = http
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::LnUrlFailure)?;
+
+        let Some(pr) = callback.pr else {
+            log_error!(
+                node_manager.logger,
+                "Zap callback failed: {}",
+                callback.reason.unwrap_or_default()
+            );
+            return Err(MutinyError::LnUrlFailure);
+        };
+        let invoice: Invoice = pr.parse()?;
+
+        // the invoice has to commit to our zap request, otherwise there won't be a receipt
+        let description_hash = sha256::Hash::hash(serde_json::to_string(&zap_request)?.as_bytes());
+        let commits_to_request = matches!(
+            invoice.description(),
+            InvoiceDescription::Hash(h) if h.0 == description_hash
+        );
+        if invoice.amount_milli_satoshis() != Some(msats) || !commits_to_request {
+            return Err(MutinyError::LnUrlFailure);
+        }
+
+        let labels = vec!["Zap".to_string()];
+        let paid = node_manager
+            .pay_invoice(from_node, &invoice, None, PaymentOptions::default(), labels)
+            .await?;
+
+        let zap = ZapRecord {
+            payment_hash: paid.payment_hash.to_hex(),
+            recipient: recipient.to_string(),
+            note_id: match target {
+                ZapTarget::Note(id) => Some(id.to_hex()),
+                ZapTarget::Profile(_) => None,
+            },
+            amount_sats,
+            comment,
+            zap_request_id: zap_request.id.to_hex(),
+            created_at: utils::now().as_secs(),
+        };
+        if let Err(e) = node_manager.storage.persist_zap(&zap) {
+            log_error!(node_manager.logger, "Failed to save zap: {e}");
+        }
+
+        Ok(paid)
+    }
+
     /// Handle a Nostr Wallet Connect request, returns a response event if one is needed
     pub async fn handle_nwc_request<S: MutinyStorage>(
         &self,
@@ -146,7 +466,7 @@ impl NostrManager {
                 // todo we could get the author of the event we zapping and use that as the label
                 let labels = vec!["Zap!".to_string()];
                 match node_manager
-                    .pay_invoice(from_node, &invoice, None, labels)
+                    .pay_invoice(from_node, &invoice, None, PaymentOptions::default(), labels)
                     .await
                 {
                     Ok(inv) => {
@@ -186,6 +506,14 @@ impl NostrManager {
                 }
             };
 
+            node_manager.audit_integration_action(IntegrationAuditRecord::new(
+                Integration::NostrWalletConnect,
+                client_pubkey.to_string(),
+                "pay_invoice",
+                Some(msats / 1_000),
+                content.error.as_ref().map(|e| e.message.clone()),
+            ));
+
             let encrypted = encrypt(&server_key, &client_pubkey, content.as_json())?;
 
             let p_tag = Tag::PubKey(event.pubkey, None);
@@ -197,6 +525,116 @@ impl NostrManager {
             return Ok(Some(response));
         }
 
+        if event.kind == Kind::WalletConnectRequest {
+            return self.handle_receive_request(event, node_manager).await;
+        }
+
         Ok(None)
     }
+
+    /// Handle a request from a receive credential, only invoices can be created
+    async fn handle_receive_request<S: MutinyStorage>(
+        &self,
+        event: Event,
+        node_manager: &NodeManager<S>,
+    ) -> anyhow::Result<Option<Event>> {
+        let Some(credential) = node_manager
+            .storage
+            .get_receive_credential(&event.pubkey.to_string())?
+        else {
+            return Ok(None);
+        };
+
+        let server_key = self.nwc_server_key.secret_key()?;
+        let decrypted = decrypt(&server_key, &event.pubkey, &event.content)?;
+        let req: ReceiveRequest = serde_json::from_str(&decrypted)?;
+        let amount_sats = req
+            .params
+            .get("amount")
+            .and_then(|a| a.as_u64())
+            .map(|msats| msats / 1_000);
+
+        let content = if credential.revoked {
+            ReceiveResponse::error(req.method, "UNAUTHORIZED", "Credential was revoked")
+        } else if req.method != "make_invoice" {
+            ReceiveResponse::error(
+                req.method,
+                "RESTRICTED",
+                "Credential can only make invoices",
+            )
+        } else {
+            match serde_json::from_value::<MakeInvoiceParams>(req.params) {
+                Err(_) => ReceiveResponse::error(req.method, "OTHER", "Invalid parameters"),
+                Ok(params) => {
+                    self.make_receive_invoice(&credential, params, node_manager)
+                        .await
+                }
+            }
+        };
+
+        node_manager.audit_integration_action(IntegrationAuditRecord::new(
+            Integration::ReceiveCredential,
+            credential.client_pubkey.clone(),
+            &content.result_type,
+            amount_sats,
+            content.error.as_ref().map(|e| e.message.clone()),
+        ));
+
+        let encrypted = encrypt(&server_key, &event.pubkey, serde_json::to_string(&content)?)?;
+
+        let p_tag = Tag::PubKey(event.pubkey, None);
+        let e_tag = Tag::Event(event.id, None, None);
+        let response = EventBuilder::new(Kind::WalletConnectResponse, encrypted, &[p_tag, e_tag])
+            .to_event(&self.nwc_server_key)?;
+
+        Ok(Some(response))
+    }
+
+    async fn make_receive_invoice<S: MutinyStorage>(
+        &self,
+        credential: &ReceiveCredential,
+        params: MakeInvoiceParams,
+        node_manager: &NodeManager<S>,
+    ) -> ReceiveResponse {
+        let result_type = "make_invoice".to_string();
+        let amount_sats = params.amount / 1_000;
+        if amount_sats == 0 {
+            return ReceiveResponse::error(result_type, "OTHER", "Amount is too small");
+        }
+        if credential
+            .max_amount_sats
+            .is_some_and(|max| amount_sats > max)
+        {
+            return ReceiveResponse::error(result_type, "QUOTA_EXCEEDED", "Amount is too high");
+        }
+        if !self
+            .receive_rate_limiter
+            .check(credential, utils::now().as_secs())
+        {
+            log_warn!(
+                node_manager.logger,
+                "Receive credential {} is rate limited",
+                credential.name
+            );
+            return ReceiveResponse::error(result_type, "RATE_LIMITED", "Too many invoices");
+        }
+
+        let mut labels = vec![credential.name.clone()];
+        labels.extend(params.description.filter(|d| !d.is_empty()));
+        match node_manager.create_invoice(Some(amount_sats), labels).await {
+            Ok(invoice) => ReceiveResponse {
+                result_type,
+                error: None,
+                result: Some(MakeInvoiceResult {
+                    invoice: invoice.bolt11.map(|i| i.to_string()).unwrap_or_default(),
+                    payment_hash: invoice.payment_hash.to_hex(),
+                    amount: amount_sats * 1_000,
+                }),
+            },
+            Err(e) => {
+                log_error!(node_manager.logger, "failed to create invoice: {e}");
+                ReceiveResponse::error(result_type, "INTERNAL", "Failed to create invoice")
+            }
+        }
+    }
 }
@@ -0,0 +1,189 @@
+use crate::error::MutinyError;
+use crate::storage::MutinyStorage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A credential that lets a third party, like a donation page backend,
+/// create invoices for this wallet over Nostr Wallet Connect and nothing else.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReceiveCredential {
+    /// Index the credential's key is derived with
+    pub index: u32,
+    pub name: String,
+    /// Hex pubkey the third party signs its requests with
+    pub client_pubkey: String,
+    pub max_invoices_per_hour: u32,
+    /// The largest invoice that can be requested, none for no limit
+    pub max_amount_sats: Option<u64>,
+    pub revoked: bool,
+    pub created_at: u64,
+}
+
+pub trait ReceiveCredentialStorage {
+    fn get_receive_credential(
+        &self,
+        client_pubkey: &str,
+    ) -> Result<Option<ReceiveCredential>, MutinyError>;
+    fn persist_receive_credential(&self, credential: &ReceiveCredential)
+        -> Result<(), MutinyError>;
+    fn list_receive_credentials(&self) -> Result<Vec<ReceiveCredential>, MutinyError>;
+}
+
+const RECEIVE_CREDENTIAL_PREFIX: &str = "receive_credential/";
+
+impl<S: MutinyStorage> ReceiveCredentialStorage for S {
+    fn get_receive_credential(
+        &self,
+        client_pubkey: &str,
+    ) -> Result<Option<ReceiveCredential>, MutinyError> {
+        self.get_data(format!("{RECEIVE_CREDENTIAL_PREFIX}{client_pubkey}"))
+    }
+
+    fn persist_receive_credential(
+        &self,
+        credential: &ReceiveCredential,
+    ) -> Result<(), MutinyError> {
+        self.set_data(
+            format!("{RECEIVE_CREDENTIAL_PREFIX}{}", credential.client_pubkey),
+            credential,
+        )
+    }
+
+    fn list_receive_credentials(&self) -> Result<Vec<ReceiveCredential>, MutinyError> {
+        let map: HashMap<String, ReceiveCredential> = self.scan(RECEIVE_CREDENTIAL_PREFIX, None)?;
+        let mut credentials: Vec<ReceiveCredential> = map.into_values().collect();
+        credentials.sort_by_key(|c| c.index);
+        Ok(credentials)
+    }
+}
+
+/// Keeps track of when each credential last created invoices
+#[derive(Default)]
+pub(crate) struct ReceiveRateLimiter {
+    requests: Mutex<HashMap<u32, Vec<u64>>>,
+}
+
+impl ReceiveRateLimiter {
+    /// Returns true and records the request if the credential
+    /// has created less than its limit in the last hour.
+    pub fn check(&self, credential: &ReceiveCredential, now: u64) -> bool {
+        let Ok(mut requests) = self.requests.lock() else {
+            return false;
+        };
+        let times = requests.entry(credential.index).or_default();
+        times.retain(|t| *t + 60 * 60 > now);
+
+        if times.len() >= credential.max_invoices_per_hour as usize {
+            return false;
+        }
+        times.push(now);
+        true
+    }
+}
+
+/// A NIP-47 request from a receive credential, only `make_invoice` is allowed
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct ReceiveRequest {
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct MakeInvoiceParams {
+    /// Amount in millisatoshis
+    pub amount: u64,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct ReceiveError {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct MakeInvoiceResult {
+    pub invoice: String,
+    pub payment_hash: String,
+    pub amount: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct ReceiveResponse {
+    pub result_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ReceiveError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<MakeInvoiceResult>,
+}
+
+impl ReceiveResponse {
+    pub fn error(result_type: String, code: &str, message: &str) -> Self {
+        Self {
+            result_type,
+            error: Some(ReceiveError {
+                code: code.to_string(),
+                message: message.to_string(),
+            }),
+            result: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_receive_credentials() {
+        let test_name = "test_receive_credentials";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        let credential = ReceiveCredential {
+            index: 0,
+            name: "donations".to_string(),
+            client_pubkey: "abcd".to_string(),
+            max_invoices_per_hour: 2,
+            max_amount_sats: Some(1_000),
+            revoked: false,
+            created_at: 0,
+        };
+        storage.persist_receive_credential(&credential).unwrap();
+        assert_eq!(
+            storage.get_receive_credential("abcd").unwrap(),
+            Some(credential.clone())
+        );
+        assert_eq!(
+            storage.list_receive_credentials().unwrap(),
+            vec![credential.clone()]
+        );
+
+        let limiter = ReceiveRateLimiter::default();
+        assert!(limiter.check(&credential, 100));
+        assert!(limiter.check(&credential, 200));
+        assert!(!limiter.check(&credential, 300));
+        // the limit resets once the first request is an hour old
+        assert!(limiter.check(&credential, 100 + 60 * 60));
+
+        let request: ReceiveRequest = serde_json::from_str(
+            r#"{"method": "make_invoice", "params": {"amount": 21000, "description": "tip"}}"#,
+        )
+        .unwrap();
+        let params: MakeInvoiceParams = serde_json::from_value(request.params).unwrap();
+        assert_eq!(params.amount, 21_000);
+
+        let response =
+            ReceiveResponse::error("pay_invoice".to_string(), "RESTRICTED", "receive only");
+        let json = serde_json::to_value(response).unwrap();
+        assert_eq!(json["error"]["code"], "RESTRICTED");
+        assert!(json.get("result").is_none());
+    }
+}
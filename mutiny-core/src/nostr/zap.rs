@@ -0,0 +1,219 @@
+use crate::error::MutinyError;
+use crate::storage::MutinyStorage;
+use anyhow::anyhow;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::sha256;
+use bitcoin::XOnlyPublicKey;
+use nostr::nips::nip19::FromBech32;
+use nostr::{Event, EventBuilder, EventId, Keys, Kind, Tag, TagKind};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use url::Url;
+
+/// What is being zapped, a note or a whole profile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZapTarget {
+    Note(EventId),
+    Profile(XOnlyPublicKey),
+}
+
+impl FromStr for ZapTarget {
+    type Err = MutinyError;
+
+    /// Parses a `note` or `npub`, with or without the `nostr:` prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("nostr:").unwrap_or(s);
+
+        if let Ok(id) = EventId::from_bech32(s) {
+            return Ok(ZapTarget::Note(id));
+        }
+        if let Ok(pubkey) = XOnlyPublicKey::from_bech32(s) {
+            return Ok(ZapTarget::Profile(pubkey));
+        }
+
+        Err(MutinyError::InvalidArgumentsError)
+    }
+}
+
+/// The lightning fields of a nostr profile (kind 0) that zaps are sent to
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct ProfileLnurl {
+    pub lud06: Option<String>,
+    pub lud16: Option<String>,
+}
+
+/// The parts of a LNURL-pay response that say if the service accepts zaps
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct ZapPayResponse {
+    pub callback: String,
+    #[serde(rename = "minSendable")]
+    pub min_sendable: u64,
+    #[serde(rename = "maxSendable")]
+    pub max_sendable: u64,
+    #[serde(rename = "allowsNostr", default)]
+    pub allows_nostr: bool,
+    #[serde(rename = "nostrPubkey")]
+    pub nostr_pubkey: Option<String>,
+}
+
+/// Builds the zap request (kind 9734) that is sent to the recipient's LNURL callback
+pub(crate) fn create_zap_request(
+    keys: &Keys,
+    target: &ZapTarget,
+    recipient: XOnlyPublicKey,
+    amount_msats: u64,
+    lnurl: &str,
+    relays: &[String],
+    comment: &str,
+) -> Result<Event, MutinyError> {
+    let mut tags = vec![
+        Tag::Generic(TagKind::Custom("relays".to_string()), relays.to_vec()),
+        Tag::Generic(
+            TagKind::Custom("amount".to_string()),
+            vec![amount_msats.to_string()],
+        ),
+        Tag::Generic(
+            TagKind::Custom("lnurl".to_string()),
+            vec![lnurl.to_string()],
+        ),
+        Tag::PubKey(recipient, None),
+    ];
+    if let ZapTarget::Note(id) = target {
+        tags.push(Tag::Event(*id, None, None));
+    }
+
+    EventBuilder::new(Kind::ZapRequest, comment, &tags)
+        .to_event(keys)
+        .map_err(|e| MutinyError::Other(anyhow!("Could not create zap request: {e}")))
+}
+
+/// Builds the callback url to get the invoice for a zap
+pub(crate) fn zap_callback_url(
+    callback: &str,
+    amount_msats: u64,
+    zap_request: &Event,
+    lnurl: &str,
+) -> Result<Url, MutinyError> {
+    let mut url = Url::parse(callback).map_err(|_| MutinyError::LnUrlFailure)?;
+    url.query_pairs_mut()
+        .append_pair("amount", &amount_msats.to_string())
+        .append_pair("nostr", &serde_json::to_string(zap_request)?)
+        .append_pair("lnurl", lnurl);
+    Ok(url)
+}
+
+/// A zap we sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ZapRecord {
+    pub payment_hash: String,
+    /// Hex pubkey of who was zapped
+    pub recipient: String,
+    /// Hex id of the note that was zapped, none for profile zaps
+    pub note_id: Option<String>,
+    pub amount_sats: u64,
+    pub comment: Option<String>,
+    /// Id of the zap request, the recipient's zap receipt refers to it
+    pub zap_request_id: String,
+    pub created_at: u64,
+}
+
+pub trait ZapStorage {
+    fn get_zap(&self, payment_hash: &sha256::Hash) -> Result<Option<ZapRecord>, MutinyError>;
+    fn persist_zap(&self, zap: &ZapRecord) -> Result<(), MutinyError>;
+    fn list_zaps(&self) -> Result<Vec<ZapRecord>, MutinyError>;
+}
+
+const ZAP_KEY_PREFIX: &str = "zap/";
+
+impl<S: MutinyStorage> ZapStorage for S {
+    fn get_zap(&self, payment_hash: &sha256::Hash) -> Result<Option<ZapRecord>, MutinyError> {
+        self.get_data(format!("{ZAP_KEY_PREFIX}{}", payment_hash.to_hex()))
+    }
+
+    fn persist_zap(&self, zap: &ZapRecord) -> Result<(), MutinyError> {
+        self.set_data(format!("{ZAP_KEY_PREFIX}{}", zap.payment_hash), zap)
+    }
+
+    fn list_zaps(&self) -> Result<Vec<ZapRecord>, MutinyError> {
+        let map: std::collections::HashMap<String, ZapRecord> = self.scan(ZAP_KEY_PREFIX, None)?;
+        Ok(map.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use nostr::nips::nip19::ToBech32;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_zap_request() {
+        let test_name = "test_zap_request";
+        log!("{}", test_name);
+
+        let keys = Keys::generate();
+        let recipient = Keys::generate().public_key();
+        let note = EventId::from_slice(&[1; 32]).unwrap();
+
+        let target = ZapTarget::from_str(&format!("nostr:{}", note.to_bech32().unwrap())).unwrap();
+        assert_eq!(target, ZapTarget::Note(note));
+        let profile = ZapTarget::from_str(&recipient.to_bech32().unwrap()).unwrap();
+        assert_eq!(profile, ZapTarget::Profile(recipient));
+        assert!(ZapTarget::from_str("not a note").is_err());
+
+        let relays = vec!["wss://nostr.mutinywallet.com".to_string()];
+        let request = create_zap_request(
+            &keys,
+            &target,
+            recipient,
+            21_000,
+            "lnurl1test",
+            &relays,
+            "great post",
+        )
+        .unwrap();
+        assert_eq!(request.kind, Kind::ZapRequest);
+        assert_eq!(request.content, "great post");
+        assert_eq!(request.pubkey, keys.public_key());
+        assert!(request.tags.contains(&Tag::PubKey(recipient, None)));
+        assert!(request.tags.contains(&Tag::Event(note, None, None)));
+
+        // profile zaps don't tag a note
+        let request = create_zap_request(
+            &keys,
+            &profile,
+            recipient,
+            21_000,
+            "lnurl1test",
+            &relays,
+            "",
+        )
+        .unwrap();
+        assert!(!request.tags.iter().any(|t| matches!(t, Tag::Event(..))));
+
+        let url =
+            zap_callback_url("https://example.com/zap", 21_000, &request, "lnurl1test").unwrap();
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert_eq!(pairs[0], ("amount".to_string(), "21000".to_string()));
+        let sent: Event = serde_json::from_str(&pairs[1].1).unwrap();
+        assert_eq!(sent.id, request.id);
+
+        let storage = MemoryStorage::default();
+        let zap = ZapRecord {
+            payment_hash: "00".repeat(32),
+            recipient: recipient.to_string(),
+            note_id: None,
+            amount_sats: 21,
+            comment: None,
+            zap_request_id: request.id.to_hex(),
+            created_at: 0,
+        };
+        storage.persist_zap(&zap).unwrap();
+        assert_eq!(storage.list_zaps().unwrap(), vec![zap]);
+    }
+}
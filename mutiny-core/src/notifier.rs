@@ -0,0 +1,170 @@
+use crate::nodemanager::ChannelClosureKind;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Txid;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Something that happened in the wallet that a UI may want to react to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MutinyEvent {
+    PaymentReceived {
+        payment_hash: String,
+        amount_msats: u64,
+    },
+    /// A payment to a hodl invoice arrived and is held until it is settled or cancelled
+    HodlInvoiceAccepted {
+        payment_hash: String,
+        amount_msats: u64,
+    },
+    PaymentSent {
+        payment_hash: String,
+        fees_paid_msats: Option<u64>,
+    },
+    /// One of our nodes routed a payment through its channels
+    PaymentForwarded {
+        fee_earned_msat: Option<u64>,
+    },
+    /// The funding transaction of a new channel was broadcast,
+    /// it can be used once it has enough confirmations
+    ChannelPending {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+        funding_txid: Txid,
+    },
+    /// The funding transaction of a pending channel got another confirmation
+    ChannelConfirmed {
+        channel_id: String,
+        confirmations: u32,
+        confirmations_required: u32,
+    },
+    /// The channel is ready to use
+    ChannelOpened {
+        channel_id: String,
+        counterparty_node_id: PublicKey,
+    },
+    ChannelClosed {
+        channel_id: String,
+        kind: ChannelClosureKind,
+        reason: String,
+    },
+    SyncCompleted {
+        /// Unix timestamp, in seconds, of when the sync completed
+        timestamp: u64,
+    },
+    PeerConnected {
+        node_id: PublicKey,
+    },
+    PeerDisconnected {
+        node_id: PublicKey,
+    },
+    /// An outgoing HTLC is close to expiring, if it isn't resolved in time the
+    /// channel is force closed. Bringing the peer back online usually resolves it.
+    HtlcExpiring {
+        channel_id: String,
+        amount_sats: u64,
+        cltv_expiry: u32,
+        blocks_until_force_close: u32,
+    },
+    /// What we can receive over lightning dropped below the alert threshold,
+    /// sent once each time it crosses below it.
+    InboundLiquidityLow {
+        inbound_sats: u64,
+        threshold_sats: u64,
+    },
+    /// A wallet transaction got its first confirmation, or reached the number of
+    /// confirmations after which it is considered safe
+    TransactionConfirmed {
+        txid: Txid,
+        confirmations: u32,
+    },
+    /// An unconfirmed wallet transaction was replaced by a conflicting one
+    TransactionReplaced {
+        txid: Txid,
+    },
+    /// An unconfirmed wallet transaction was dropped from the mempool
+    TransactionEvicted {
+        txid: Txid,
+    },
+    /// An unconfirmed transaction that fell out of the mempool could not be rebroadcast
+    RebroadcastFailed {
+        txid: Txid,
+        error: String,
+    },
+}
+
+impl MutinyEvent {
+    /// Events the user should be told about right away because funds are at stake
+    pub fn is_high_priority(&self) -> bool {
+        matches!(self, MutinyEvent::HtlcExpiring { .. })
+    }
+}
+
+/// Sends [MutinyEvent]s to everyone that subscribed to them
+#[derive(Default)]
+pub(crate) struct EventNotifier {
+    subscribers: Mutex<Vec<UnboundedSender<MutinyEvent>>>,
+}
+
+impl EventNotifier {
+    /// Returns a stream of every event from now on
+    pub fn subscribe(&self) -> UnboundedReceiver<MutinyEvent> {
+        let (sender, receiver) = unbounded();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Ends the streams of all the subscribers
+    pub fn unsubscribe_all(&self) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.clear();
+        }
+    }
+
+    pub fn notify(&self, event: MutinyEvent) {
+        // drop subscribers that have gone away
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|s| s.unbounded_send(event.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_event_notifier() {
+        let test_name = "test_event_notifier";
+        log!("{}", test_name);
+
+        let notifier = EventNotifier::default();
+        let event = MutinyEvent::SyncCompleted { timestamp: 1 };
+
+        // events from before subscribing are not sent
+        notifier.notify(event.clone());
+        let mut first = notifier.subscribe();
+        let mut second = notifier.subscribe();
+        assert!(first.try_next().is_err());
+
+        notifier.notify(event.clone());
+        assert_eq!(first.try_next().unwrap(), Some(event.clone()));
+        assert_eq!(second.try_next().unwrap(), Some(event.clone()));
+
+        // dropped subscribers are cleaned up
+        drop(second);
+        notifier.notify(event.clone());
+        assert_eq!(notifier.subscribers.lock().unwrap().len(), 1);
+
+        // unsubscribing ends the stream
+        notifier.unsubscribe_all();
+        assert_eq!(first.try_next().unwrap(), Some(event));
+        assert_eq!(first.try_next().unwrap(), None);
+    }
+}
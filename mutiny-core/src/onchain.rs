@@ -1,57 +1,141 @@
 use anyhow::anyhow;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 
 use bdk::chain::{BlockId, ConfirmationTime};
 use bdk::psbt::PsbtUtils;
 use bdk::template::DescriptorTemplateOut;
-use bdk::{FeeRate, LocalUtxo, SignOptions, TransactionDetails, Wallet};
+use bdk::wallet::AddressIndex;
+use bdk::{FeeRate, KeychainKind, LocalUtxo, SignOptions, TransactionDetails, Wallet};
 use bdk_esplora::{esplora_client, EsploraAsyncExt};
 use bip39::Mnemonic;
 use bitcoin::psbt::PartiallySignedTransaction;
-use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
 use bitcoin::{Address, Network, OutPoint, Script, Transaction, Txid};
-use esplora_client::AsyncClient;
 use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
 use lightning::util::logger::Logger;
 use lightning::{log_debug, log_error, log_warn};
+use serde::{Deserialize, Serialize};
 
 use crate::error::MutinyError;
-use crate::fees::MutinyFeeEstimator;
+use crate::esplora_failover::FailoverEsploraClient;
+use crate::fees::{MutinyFeeEstimator, MIN_RELAY_FEE_RATE_SATS_PER_VBYTE};
 use crate::labels::*;
 use crate::logging::MutinyLogger;
 use crate::storage::{MutinyStorage, OnChainStorage};
 
+/// How many unused addresses in a row we look at before we stop looking for more,
+/// unless the wallet was configured with another stop gap
+pub const DEFAULT_STOP_GAP: usize = 50;
+
+/// The stop gap of a rescan, wide enough for seeds used by wallets that skip
+/// a lot of addresses
+const RESCAN_STOP_GAP: usize = 1_000;
+
+/// An on-chain account that was replaced by [OnChainWallet::rotate_keys].
+/// It is still synced so its funds can be swept to the current account.
+pub(crate) struct RetiredAccount<S: MutinyStorage> {
+    pub rotation: u32,
+    pub wallet: Arc<RwLock<Wallet<OnChainStorage<S>>>>,
+}
+
+impl<S: MutinyStorage> Clone for RetiredAccount<S> {
+    fn clone(&self) -> Self {
+        Self {
+            rotation: self.rotation,
+            wallet: self.wallet.clone(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct OnChainWallet<S: MutinyStorage> {
     pub wallet: Arc<RwLock<Wallet<OnChainStorage<S>>>>,
     pub(crate) storage: S,
     pub network: Network,
-    pub blockchain: Arc<AsyncClient>,
+    pub blockchain: Arc<FailoverEsploraClient>,
     pub fees: Arc<MutinyFeeEstimator<S>>,
+    /// Needed to derive new accounts, watch-only wallets don't have it
+    xprivkey: Option<ExtendedPrivKey>,
+    /// The account index the keys are derived from, rotated keys stay under it
+    account_index: u32,
+    retired_accounts: Arc<RwLock<Vec<RetiredAccount<S>>>>,
+    stop_gap: usize,
+    /// How many addresses past the last revealed one are always checked when syncing
+    address_lookahead: u32,
     logger: Arc<MutinyLogger>,
 }
 
+/// How many addresses of each keychain have been handed out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevealedAddresses {
+    pub receive: u32,
+    pub change: u32,
+}
+
 impl<S: MutinyStorage> OnChainWallet<S> {
+    /// Creates a wallet using the keys of the given account index.
+    /// Rotated keys are derived from a branch under the account,
+    /// so they can't collide with the keys of another account index.
     pub fn new(
         mnemonic: &Mnemonic,
         db: S,
         network: Network,
-        esplora: Arc<AsyncClient>,
+        account_index: u32,
+        esplora: Arc<FailoverEsploraClient>,
         fees: Arc<MutinyFeeEstimator<S>>,
         logger: Arc<MutinyLogger>,
     ) -> Result<OnChainWallet<S>, MutinyError> {
         let seed = mnemonic.to_seed("");
         let xprivkey = ExtendedPrivKey::new_master(network, &seed)?;
-        let account_number = 0;
-        let (receive_descriptor_template, change_descriptor_template) =
-            get_tr_descriptors_for_extended_key(xprivkey, network, account_number)?;
+        let rotation = db.get_onchain_rotation()?;
+        let wallet = create_account_wallet(xprivkey, network, account_index, rotation, db.clone())?;
+
+        // keep the accounts we rotated away from until their funds are swept
+        let retired_accounts = (0..rotation)
+            .map(|rotation| {
+                let wallet =
+                    create_account_wallet(xprivkey, network, account_index, rotation, db.clone())?;
+                Ok(RetiredAccount {
+                    rotation,
+                    wallet: Arc::new(RwLock::new(wallet)),
+                })
+            })
+            .collect::<Result<Vec<_>, MutinyError>>()?;
+
+        Ok(OnChainWallet {
+            wallet: Arc::new(RwLock::new(wallet)),
+            storage: db,
+            network,
+            blockchain: esplora,
+            fees,
+            xprivkey: Some(xprivkey),
+            account_index,
+            retired_accounts: Arc::new(RwLock::new(retired_accounts)),
+            stop_gap: DEFAULT_STOP_GAP,
+            address_lookahead: 0,
+            logger,
+        })
+    }
+
+    /// Creates a watch-only wallet from an xpub or output descriptor.
+    /// The wallet can track funds and generate addresses but cannot sign transactions.
+    pub fn new_watch_only(
+        descriptor: &str,
+        db: S,
+        network: Network,
+        esplora: Arc<FailoverEsploraClient>,
+        fees: Arc<MutinyFeeEstimator<S>>,
+        logger: Arc<MutinyLogger>,
+    ) -> Result<OnChainWallet<S>, MutinyError> {
+        let (receive_descriptor, change_descriptor) =
+            get_watch_only_descriptors(descriptor, network)?;
 
         let wallet = Wallet::new(
-            receive_descriptor_template,
-            Some(change_descriptor_template),
-            OnChainStorage(db.clone()),
+            receive_descriptor.as_str(),
+            change_descriptor.as_deref(),
+            OnChainStorage::new(db.clone()),
             network,
         )?;
 
@@ -61,10 +145,33 @@ impl<S: MutinyStorage> OnChainWallet<S> {
             network,
             blockchain: esplora,
             fees,
+            xprivkey: None,
+            account_index: 0,
+            retired_accounts: Arc::new(RwLock::new(vec![])),
+            stop_gap: DEFAULT_STOP_GAP,
+            address_lookahead: 0,
             logger,
         })
     }
 
+    /// Sets how many unused addresses in a row a sync looks at before it stops, and how
+    /// many addresses past the last revealed one it always looks at.
+    pub fn with_scan_limits(mut self, stop_gap: usize, address_lookahead: u32) -> Self {
+        self.stop_gap = stop_gap.max(1);
+        self.address_lookahead = address_lookahead;
+        self
+    }
+
+    /// How many receive and change addresses have been handed out in the current account
+    pub fn revealed_addresses(&self) -> Result<RevealedAddresses, MutinyError> {
+        let wallet = self.wallet.try_read()?;
+        let revealed = |keychain| wallet.derivation_index(keychain).map_or(0, |i| i + 1);
+        Ok(RevealedAddresses {
+            receive: revealed(KeychainKind::External),
+            change: revealed(KeychainKind::Internal),
+        })
+    }
+
     pub async fn broadcast_transaction(&self, tx: Transaction) -> Result<(), MutinyError> {
         let txid = tx.txid();
         if let Err(e) = self.blockchain.broadcast(&tx).await {
@@ -83,33 +190,103 @@ impl<S: MutinyStorage> OnChainWallet<S> {
     }
 
     pub async fn sync(&self) -> Result<(), MutinyError> {
+        self.sync_wallet(&self.wallet, self.stop_gap, None).await?;
+
+        let retired = self.retired_accounts.try_read()?.clone();
+        for account in retired {
+            if let Err(e) = self.sync_wallet(&account.wallet, self.stop_gap, None).await {
+                log_warn!(
+                    self.logger,
+                    "Could not sync retired on-chain account {}: {e}",
+                    account.rotation
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans the wallet's keychains again with a much wider stop gap, to find funds
+    /// on addresses far past the last one we know was used.
+    /// The progress, from 0 to 100, is reported after each keychain of each account.
+    pub async fn rescan(&self, on_progress: impl Fn(u8)) -> Result<(), MutinyError> {
+        let mut wallets = vec![self.wallet.clone()];
+        wallets.extend(
+            self.retired_accounts
+                .try_read()?
+                .iter()
+                .map(|account| account.wallet.clone()),
+        );
+        let keychains = [KeychainKind::External, KeychainKind::Internal];
+
+        let stop_gap = self.stop_gap.max(RESCAN_STOP_GAP);
+        let total = wallets.len() * keychains.len();
+        let mut done = 0;
+        on_progress(0);
+        for wallet in wallets.iter() {
+            for keychain in keychains {
+                self.sync_wallet(wallet, stop_gap, Some(keychain)).await?;
+                done += 1;
+                on_progress((done * 100 / total) as u8);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Syncs the wallet, only the given keychain if there is one.
+    async fn sync_wallet(
+        &self,
+        wallet: &RwLock<Wallet<OnChainStorage<S>>>,
+        stop_gap: usize,
+        keychain: Option<KeychainKind>,
+    ) -> Result<(), MutinyError> {
         // get first wallet lock that only needs to read
-        let (checkpoints, spks) = {
-            let wallet = self.wallet.try_read()?;
-            let checkpoints = wallet.checkpoints();
-            let spks = wallet
+        let (checkpoints, spks, stop_gap) = {
+            let wallet = wallet.try_read()?;
+            let checkpoints = wallet.checkpoints().clone();
+            let spks: BTreeMap<_, _> = wallet
                 .spks_of_all_keychains()
                 .into_iter()
-                .map(|(k, spks)| (k, spks))
+                .filter(|(k, _)| keychain.map_or(true, |keychain| *k == keychain))
                 .collect();
 
-            (checkpoints.clone(), spks)
+            // the gap starts counting from the first address, so it has to reach past
+            // every revealed address to be sure the lookahead is checked
+            let stop_gap = match self.address_lookahead {
+                0 => stop_gap,
+                lookahead => spks
+                    .keys()
+                    .filter_map(|k| wallet.derivation_index(*k))
+                    .map(|i| i as usize + 1 + lookahead as usize)
+                    .fold(stop_gap, usize::max),
+            };
+
+            (checkpoints, spks, stop_gap)
         };
 
         let update = self
             .blockchain
-            .scan(
-                &checkpoints,
-                spks,
-                core::iter::empty(),
-                core::iter::empty(),
-                50,
-                5,
-            )
+            .call(|client| {
+                let checkpoints = &checkpoints;
+                let spks = spks.clone();
+                async move {
+                    client
+                        .scan(
+                            checkpoints,
+                            spks,
+                            core::iter::empty(),
+                            core::iter::empty(),
+                            stop_gap,
+                            5,
+                        )
+                        .await
+                }
+            })
             .await?;
 
         // get new wallet lock for writing and apply the update
-        match self.wallet.try_write() {
+        match wallet.try_write() {
             Ok(mut wallet) => match wallet.apply_update(update) {
                 Ok(_) => wallet.commit()?,
                 Err(e) => {
@@ -271,31 +448,113 @@ impl<S: MutinyStorage> OnChainWallet<S> {
         amount: u64,
         fee_rate: Option<f32>,
     ) -> Result<PartiallySignedTransaction, MutinyError> {
+        self.create_signed_psbt_to_outputs(vec![(spk, amount)], fee_rate)
+    }
+
+    /// Creates a signed PSBT with an output for each of the given scripts and amounts
+    pub fn create_signed_psbt_to_outputs(
+        &self,
+        outputs: Vec<(Script, u64)>,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        if outputs.is_empty() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        check_dust(&outputs)?;
+
         let mut wallet = self.wallet.try_write()?;
 
-        let fee_rate = if let Some(rate) = fee_rate {
-            FeeRate::from_sat_per_vb(rate)
-        } else {
-            let sat_per_kwu = self
-                .fees
-                .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
-            FeeRate::from_sat_per_kwu(sat_per_kwu as f32)
-        };
+        let fee_rate = self.fee_rate(fee_rate, ConfirmationTarget::Normal)?;
         let (mut psbt, details) = {
             let mut builder = wallet.build_tx();
             builder
-                .add_recipient(spk, amount)
+                .set_recipients(outputs)
+                .enable_rbf()
+                .fee_rate(fee_rate);
+            builder.finish()?
+        };
+        log_debug!(self.logger, "Transaction details: {details:#?}");
+        log_debug!(self.logger, "Unsigned PSBT: {psbt}");
+        let finalized = wallet.sign(&mut psbt, SignOptions::default())?;
+        log_debug!(self.logger, "finalized: {finalized}");
+        Ok(psbt)
+    }
+
+    /// Creates an unsigned PSBT sending the given amount to the address.
+    /// This can be given to an external signer before being broadcast.
+    pub fn create_psbt(
+        &self,
+        send_to: Address,
+        amount: u64,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        self.create_batch_psbt(vec![(send_to, amount)], fee_rate)
+    }
+
+    /// Creates an unsigned PSBT paying each of the addresses its amount in a single transaction.
+    pub fn create_batch_psbt(
+        &self,
+        recipients: Vec<(Address, u64)>,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        let outputs = self.recipient_outputs(recipients)?;
+        check_dust(&outputs)?;
+        let mut wallet = self.wallet.try_write()?;
+
+        let fee_rate = self.fee_rate(fee_rate, ConfirmationTarget::Normal)?;
+        let (psbt, details) = {
+            let mut builder = wallet.build_tx();
+            builder
+                .set_recipients(outputs)
                 .enable_rbf()
                 .fee_rate(fee_rate);
             builder.finish()?
         };
         log_debug!(self.logger, "Transaction details: {details:#?}");
         log_debug!(self.logger, "Unsigned PSBT: {psbt}");
+        Ok(psbt)
+    }
+
+    /// Signs any inputs of the PSBT that belong to our wallet.
+    /// Inputs that are not ours are left as is so that they can be signed elsewhere.
+    pub fn sign_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        let wallet = self.wallet.try_read()?;
         let finalized = wallet.sign(&mut psbt, SignOptions::default())?;
         log_debug!(self.logger, "finalized: {finalized}");
         Ok(psbt)
     }
 
+    /// Finalizes and broadcasts a fully signed PSBT.
+    pub async fn broadcast_psbt(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+        labels: Vec<String>,
+    ) -> Result<Txid, MutinyError> {
+        let finalized = {
+            let wallet = self.wallet.try_read()?;
+            wallet.finalize_psbt(&mut psbt, SignOptions::default())?
+        };
+        if !finalized {
+            log_error!(
+                self.logger,
+                "Could not finalize PSBT, it is missing signatures"
+            );
+            return Err(MutinyError::WalletSigningFailed);
+        }
+
+        self.label_psbt(&psbt, labels)?;
+
+        let raw_transaction = psbt.extract_tx();
+        let txid = raw_transaction.txid();
+
+        self.broadcast_transaction(raw_transaction).await?;
+        log_debug!(self.logger, "Transaction broadcast! TXID: {txid}");
+        Ok(txid)
+    }
+
     pub async fn send(
         &self,
         destination_address: Address,
@@ -314,6 +573,63 @@ impl<S: MutinyStorage> OnChainWallet<S> {
         Ok(txid)
     }
 
+    /// Pays each of the addresses its amount in a single transaction,
+    /// which saves fees compared to a transaction per address.
+    pub async fn send_to_many(
+        &self,
+        recipients: Vec<(Address, u64)>,
+        fee_rate: Option<f32>,
+    ) -> Result<Txid, MutinyError> {
+        let outputs = self.recipient_outputs(recipients)?;
+        let psbt = self.create_signed_psbt_to_outputs(outputs, fee_rate)?;
+
+        let raw_transaction = psbt.extract_tx();
+        let txid = raw_transaction.txid();
+
+        self.broadcast_transaction(raw_transaction).await?;
+        log_debug!(self.logger, "Transaction broadcast! TXID: {txid}");
+        Ok(txid)
+    }
+
+    /// The fee rate to use, the estimate for the target if none is given.
+    /// A given fee rate has to be at least the network's minimum relay fee rate.
+    fn fee_rate(
+        &self,
+        fee_rate: Option<f32>,
+        target: ConfirmationTarget,
+    ) -> Result<FeeRate, MutinyError> {
+        match fee_rate {
+            Some(rate) if rate.is_nan() || rate < MIN_RELAY_FEE_RATE_SATS_PER_VBYTE => {
+                Err(MutinyError::FeeRateBelowMinimum)
+            }
+            Some(rate) => Ok(FeeRate::from_sat_per_vb(rate)),
+            None => {
+                let sat_per_kwu = self.fees.get_est_sat_per_1000_weight(target);
+                Ok(FeeRate::from_sat_per_kwu(sat_per_kwu as f32))
+            }
+        }
+    }
+
+    /// Checks the recipients of a transaction and turns them into its outputs
+    fn recipient_outputs(
+        &self,
+        recipients: Vec<(Address, u64)>,
+    ) -> Result<Vec<(Script, u64)>, MutinyError> {
+        if recipients.is_empty() {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        recipients
+            .into_iter()
+            .map(|(address, amount)| {
+                if !address.is_valid_for_network(self.network) {
+                    return Err(MutinyError::IncorrectNetwork(address.network));
+                }
+                Ok((address.script_pubkey(), amount))
+            })
+            .collect()
+    }
+
     pub fn create_sweep_psbt(
         &self,
         destination_address: Address,
@@ -325,14 +641,7 @@ impl<S: MutinyStorage> OnChainWallet<S> {
 
         let mut wallet = self.wallet.try_write()?;
 
-        let fee_rate = if let Some(rate) = fee_rate {
-            FeeRate::from_sat_per_vb(rate)
-        } else {
-            let sat_per_kwu = self
-                .fees
-                .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
-            FeeRate::from_sat_per_kwu(sat_per_kwu as f32)
-        };
+        let fee_rate = self.fee_rate(fee_rate, ConfirmationTarget::Normal)?;
         let spk = destination_address.script_pubkey();
         let (mut psbt, details) = {
             let mut builder = wallet.build_tx();
@@ -392,6 +701,247 @@ impl<S: MutinyStorage> OnChainWallet<S> {
         Ok(psbt)
     }
 
+    /// Creates a child transaction that spends our outputs of the given unconfirmed
+    /// transaction back to ourselves, paying enough fees to bump the whole package
+    /// to the given fee rate.
+    pub fn create_cpfp_psbt(
+        &self,
+        txid: Txid,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, MutinyError> {
+        let mut wallet = self.wallet.try_write()?;
+
+        let parent = wallet.get_tx(txid, true).ok_or(MutinyError::NotFound)?;
+        if parent.confirmation_time != ConfirmationTime::Unconfirmed {
+            return Err(MutinyError::Other(anyhow!(
+                "Transaction {txid} is already confirmed"
+            )));
+        }
+        let parent_tx = parent.transaction.ok_or(MutinyError::NotFound)?;
+
+        let utxos: Vec<OutPoint> = wallet
+            .list_unspent()
+            .into_iter()
+            .filter(|u| u.outpoint.txid == txid)
+            .map(|u| u.outpoint)
+            .collect();
+        if utxos.is_empty() {
+            return Err(MutinyError::Other(anyhow!(
+                "No spendable outputs in transaction {txid}"
+            )));
+        }
+
+        let fee_rate = self.fee_rate(fee_rate, ConfirmationTarget::HighPriority)?;
+
+        let spk = wallet
+            .get_internal_address(AddressIndex::New)
+            .address
+            .script_pubkey();
+
+        // first build the child at the target fee rate to find its size
+        let child_vsize = {
+            let (mut psbt, _) = {
+                let mut builder = wallet.build_tx();
+                builder
+                    .manually_selected_only()
+                    .add_utxos(&utxos)?
+                    .drain_to(spk.clone())
+                    .enable_rbf()
+                    .fee_rate(fee_rate);
+                builder.finish()?
+            };
+            wallet.sign(&mut psbt, SignOptions::default())?;
+            psbt.extract_tx().vsize()
+        };
+
+        // If we don't know the parent's fee (eg: someone else paid us),
+        // assume it paid nothing so the child covers the whole package.
+        let parent_fee = parent.fee.unwrap_or(0);
+        let package_fee = fee_rate.fee_vb(parent_tx.vsize() + child_vsize);
+        let child_fee = package_fee.saturating_sub(parent_fee);
+
+        let (mut psbt, details) = {
+            let mut builder = wallet.build_tx();
+            builder
+                .manually_selected_only()
+                .add_utxos(&utxos)?
+                .drain_to(spk)
+                .enable_rbf()
+                .fee_absolute(child_fee);
+            builder.finish().map_err(|e| {
+                log_error!(self.logger, "Could not create CPFP transaction: {e}");
+                MutinyError::InsufficientBalance
+            })?
+        };
+        log_debug!(self.logger, "Transaction details: {details:#?}");
+        log_debug!(self.logger, "Unsigned PSBT: {psbt}");
+        let finalized = wallet.sign(&mut psbt, SignOptions::default())?;
+        log_debug!(self.logger, "finalized: {finalized}");
+        Ok(psbt)
+    }
+
+    /// Accelerates the confirmation of an unconfirmed transaction that pays us
+    /// by spending its outputs with a higher fee (child pays for parent).
+    pub async fn cpfp_accelerate(
+        &self,
+        txid: Txid,
+        fee_rate: Option<f32>,
+    ) -> Result<Txid, MutinyError> {
+        let psbt = self.create_cpfp_psbt(txid, fee_rate)?;
+
+        let raw_transaction = psbt.extract_tx();
+        let child_txid = raw_transaction.txid();
+
+        self.broadcast_transaction(raw_transaction).await?;
+        log_debug!(
+            self.logger,
+            "CPFP transaction broadcast for {txid}! TXID: {child_txid}"
+        );
+        Ok(child_txid)
+    }
+
+    /// Switches new addresses to keys from the next rotation, for when the xpub of the current
+    /// account may have leaked. The old account is kept so its funds can be swept with
+    /// [OnChainWallet::sweep_retired_accounts]. Returns the new rotation number.
+    pub fn rotate_keys(&self) -> Result<u32, MutinyError> {
+        let xprivkey = self.xprivkey.ok_or(MutinyError::WatchOnly)?;
+        let rotation = self.storage.get_onchain_rotation()?;
+        let new_rotation = rotation
+            .checked_add(1)
+            .ok_or(MutinyError::WalletOperationFailed)?;
+        let new_wallet = create_account_wallet(
+            xprivkey,
+            self.network,
+            self.account_index,
+            new_rotation,
+            self.storage.clone(),
+        )?;
+
+        let mut retired_accounts = self.retired_accounts.try_write()?;
+        let mut wallet = self.wallet.try_write()?;
+        let old_wallet = std::mem::replace(&mut *wallet, new_wallet);
+        retired_accounts.push(RetiredAccount {
+            rotation,
+            wallet: Arc::new(RwLock::new(old_wallet)),
+        });
+        self.storage.set_onchain_rotation(new_rotation)?;
+
+        Ok(new_rotation)
+    }
+
+    /// The derivation path of the account new addresses come from,
+    /// None if the on-chain keys are not derived from our seed.
+    pub fn account_derivation_path(&self) -> Result<Option<DerivationPath>, MutinyError> {
+        if self.xprivkey.is_none() {
+            return Ok(None);
+        }
+        let rotation = self.storage.get_onchain_rotation()?;
+        Ok(Some(onchain_derivation_path(
+            self.network,
+            self.account_index,
+            rotation,
+        )?))
+    }
+
+    /// The derivation paths of the accounts we rotated away from
+    pub fn retired_derivation_paths(&self) -> Result<Vec<DerivationPath>, MutinyError> {
+        self.retired_accounts
+            .try_read()?
+            .iter()
+            .map(|account| {
+                onchain_derivation_path(self.network, self.account_index, account.rotation)
+            })
+            .collect()
+    }
+
+    /// The funds still in accounts we rotated away from, in sats, confirmed or not
+    pub fn retired_balance(&self) -> Result<u64, MutinyError> {
+        let mut total = 0;
+        for account in self.retired_accounts.try_read()?.iter() {
+            let balance = account.wallet.try_read()?.get_balance();
+            total += balance.confirmed
+                + balance.trusted_pending
+                + balance.untrusted_pending
+                + balance.immature;
+        }
+        Ok(total)
+    }
+
+    /// Sweeps the funds of the accounts we rotated away from to the current account,
+    /// one transaction per account. Nothing is swept while the low priority fee rate is
+    /// above `max_fee_rate` (sat/vB), so this can be retried until fees come down.
+    pub async fn sweep_retired_accounts(
+        &self,
+        max_fee_rate: f32,
+    ) -> Result<Vec<Txid>, MutinyError> {
+        let sat_per_kwu = self
+            .fees
+            .get_est_sat_per_1000_weight(ConfirmationTarget::Background);
+        let fee_rate = FeeRate::from_sat_per_kwu(sat_per_kwu as f32);
+        if fee_rate.as_sat_per_vb() > max_fee_rate {
+            log_debug!(
+                self.logger,
+                "Not sweeping retired accounts, fee rate is {} sat/vB",
+                fee_rate.as_sat_per_vb()
+            );
+            return Ok(vec![]);
+        }
+
+        let retired = self.retired_accounts.try_read()?.clone();
+        let mut txids = vec![];
+        for account in retired {
+            if account.wallet.try_read()?.get_balance().total() == 0 {
+                continue;
+            }
+
+            let spk = self
+                .wallet
+                .try_write()?
+                .get_internal_address(AddressIndex::New)
+                .address
+                .script_pubkey();
+            let tx = {
+                let mut wallet = account.wallet.try_write()?;
+                let mut builder = wallet.build_tx();
+                builder
+                    .drain_wallet()
+                    .drain_to(spk)
+                    .enable_rbf()
+                    .fee_rate(fee_rate);
+                // the funds may not be worth sweeping at this fee rate
+                let (mut psbt, _) = match builder.finish() {
+                    Ok(res) => res,
+                    Err(e) => {
+                        log_warn!(
+                            self.logger,
+                            "Could not sweep retired account {}: {e}",
+                            account.rotation
+                        );
+                        continue;
+                    }
+                };
+                wallet.sign(&mut psbt, SignOptions::default())?;
+                psbt.extract_tx()
+            };
+
+            let txid = tx.txid();
+            self.broadcast_transaction(tx.clone()).await?;
+            // so the funds aren't swept again before the next sync
+            account
+                .wallet
+                .try_write()?
+                .insert_tx(tx, ConfirmationTime::Unconfirmed)?;
+            log_debug!(
+                self.logger,
+                "Swept retired account {}: {txid}",
+                account.rotation
+            );
+            txids.push(txid);
+        }
+
+        Ok(txids)
+    }
+
     pub fn estimate_tx_fee(
         &self,
         spk: Script,
@@ -404,11 +954,42 @@ impl<S: MutinyStorage> OnChainWallet<S> {
     }
 }
 
-fn get_tr_descriptors_for_extended_key(
+/// Makes sure none of the outputs are dust, which nodes won't relay
+fn check_dust(outputs: &[(Script, u64)]) -> Result<(), MutinyError> {
+    if outputs
+        .iter()
+        .any(|(spk, amount)| *amount < spk.dust_value().to_sat())
+    {
+        return Err(MutinyError::AmountBelowDust);
+    }
+    Ok(())
+}
+
+fn create_account_wallet<S: MutinyStorage>(
     master_xprv: ExtendedPrivKey,
     network: Network,
     account_number: u32,
-) -> Result<(DescriptorTemplateOut, DescriptorTemplateOut), MutinyError> {
+    rotation: u32,
+    db: S,
+) -> Result<Wallet<OnChainStorage<S>>, MutinyError> {
+    let (receive_descriptor_template, change_descriptor_template) =
+        get_tr_descriptors_for_extended_key(master_xprv, network, account_number, rotation)?;
+
+    Ok(Wallet::new(
+        receive_descriptor_template,
+        Some(change_descriptor_template),
+        OnChainStorage::for_rotation(db, rotation),
+        network,
+    )?)
+}
+
+/// The BIP86 taproot path of an on-chain account, `m/86'/coin'/account'`.
+/// Keys rotated away from it are derived from `m/86'/coin'/account'/rotation'`.
+pub(crate) fn onchain_derivation_path(
+    network: Network,
+    account_number: u32,
+    rotation: u32,
+) -> Result<DerivationPath, MutinyError> {
     let coin_type = match network {
         Network::Bitcoin => 0,
         Network::Testnet => 1,
@@ -417,10 +998,23 @@ fn get_tr_descriptors_for_extended_key(
     };
 
     let base_path = DerivationPath::from_str("m/86'")?;
-    let derivation_path = base_path.extend([
+    let account_path = base_path.extend([
         ChildNumber::from_hardened_idx(coin_type)?,
         ChildNumber::from_hardened_idx(account_number)?,
     ]);
+    match rotation {
+        0 => Ok(account_path),
+        n => Ok(account_path.extend([ChildNumber::from_hardened_idx(n)?])),
+    }
+}
+
+fn get_tr_descriptors_for_extended_key(
+    master_xprv: ExtendedPrivKey,
+    network: Network,
+    account_number: u32,
+    rotation: u32,
+) -> Result<(DescriptorTemplateOut, DescriptorTemplateOut), MutinyError> {
+    let derivation_path = onchain_derivation_path(network, account_number, rotation)?;
 
     let receive_descriptor_template = bdk::descriptor!(tr((
         master_xprv,
@@ -434,18 +1028,64 @@ fn get_tr_descriptors_for_extended_key(
     Ok((receive_descriptor_template, change_descriptor_template))
 }
 
-pub(crate) fn get_esplora_url(network: Network, user_provided_url: Option<String>) -> String {
-    if let Some(url) = user_provided_url {
-        url
-    } else {
-        match network {
-            Network::Bitcoin => "https://mempool.space/api",
-            Network::Testnet => "https://mempool.space/testnet/api",
-            Network::Signet => "https://mutinynet.com/api",
-            Network::Regtest => "http://localhost:3003",
+/// Converts an xpub or output descriptor into receive and change descriptors.
+/// A bare xpub is treated as a BIP84 native segwit account, descriptors using
+/// `/0/*` for receiving will use `/1/*` for change.
+pub(crate) fn get_watch_only_descriptors(
+    descriptor: &str,
+    network: Network,
+) -> Result<(String, Option<String>), MutinyError> {
+    let descriptor = descriptor.trim();
+
+    if let Ok(xpub) = ExtendedPubKey::from_str(descriptor) {
+        // test networks all share the same xpub version
+        if (xpub.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+            return Err(MutinyError::IncorrectNetwork(xpub.network));
         }
-        .to_string()
+        return Ok((
+            format!("wpkh({xpub}/0/*)"),
+            Some(format!("wpkh({xpub}/1/*)")),
+        ));
+    }
+
+    // remove the checksum, it would be invalid for the change descriptor
+    let descriptor = descriptor.split('#').next().unwrap_or_default();
+    if descriptor.is_empty() {
+        return Err(MutinyError::InvalidArgumentsError);
     }
+
+    let change = descriptor
+        .contains("/0/*")
+        .then(|| descriptor.replacen("/0/*", "/1/*", 1));
+
+    Ok((descriptor.to_string(), change))
+}
+
+/// Returns the esplora servers to use, the user provided url can be a comma separated list.
+pub(crate) fn get_esplora_urls(network: Network, user_provided_url: Option<String>) -> Vec<String> {
+    let urls: Vec<String> = user_provided_url
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+
+    if !urls.is_empty() {
+        return urls;
+    }
+
+    match network {
+        Network::Bitcoin => vec!["https://mempool.space/api", "https://blockstream.info/api"],
+        Network::Testnet => vec![
+            "https://mempool.space/testnet/api",
+            "https://blockstream.info/testnet/api",
+        ],
+        Network::Signet => vec!["https://mutinynet.com/api"],
+        Network::Regtest => vec!["http://localhost:3003"],
+    }
+    .into_iter()
+    .map(|url| url.to_string())
+    .collect()
 }
 
 #[cfg(test)]
@@ -453,28 +1093,31 @@ mod tests {
     use super::*;
     use crate::storage::MemoryStorage;
     use crate::test_utils::*;
+    use bitcoin::secp256k1::Secp256k1;
     use bitcoin::Address;
-    use esplora_client::Builder;
     use std::str::FromStr;
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
     wasm_bindgen_test_configure!(run_in_browser);
 
     async fn create_wallet() -> OnChainWallet<MemoryStorage> {
         let mnemonic = Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").expect("could not generate");
-        let esplora = Arc::new(
-            Builder::new("https://blockstream.info/testnet/api/")
-                .build_async()
-                .unwrap(),
-        );
         let db = MemoryStorage::new(Some(uuid::Uuid::new_v4().to_string()));
         let logger = Arc::new(MutinyLogger::default());
+        let esplora = Arc::new(
+            FailoverEsploraClient::new(
+                vec!["https://blockstream.info/testnet/api/".to_string()],
+                logger.clone(),
+            )
+            .unwrap(),
+        );
         let fees = Arc::new(MutinyFeeEstimator::new(
             db.clone(),
             esplora.clone(),
+            None,
             logger.clone(),
         ));
 
-        OnChainWallet::new(&mnemonic, db, Network::Testnet, esplora, fees, logger).unwrap()
+        OnChainWallet::new(&mnemonic, db, Network::Testnet, 0, esplora, fees, logger).unwrap()
     }
 
     #[test]
@@ -484,6 +1127,235 @@ mod tests {
         let _wallet = create_wallet().await;
     }
 
+    #[test]
+    async fn test_send_validation() {
+        let test_name = "send_validation";
+        log!("{}", test_name);
+        let wallet = create_wallet().await;
+
+        assert!(matches!(
+            wallet.fee_rate(Some(0.5), ConfirmationTarget::Normal),
+            Err(MutinyError::FeeRateBelowMinimum)
+        ));
+        assert!(matches!(
+            wallet.fee_rate(Some(f32::NAN), ConfirmationTarget::Normal),
+            Err(MutinyError::FeeRateBelowMinimum)
+        ));
+        assert_eq!(
+            wallet
+                .fee_rate(Some(1.0), ConfirmationTarget::Normal)
+                .unwrap(),
+            FeeRate::from_sat_per_vb(1.0)
+        );
+
+        // a P2WPKH output is dust below 294 sats
+        let spk = Address::from_str("tb1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpw0yxjz")
+            .unwrap()
+            .script_pubkey();
+        assert!(check_dust(&[(spk.clone(), 294)]).is_ok());
+        assert!(matches!(
+            wallet.estimate_tx_fee(spk.clone(), 293, None),
+            Err(MutinyError::AmountBelowDust)
+        ));
+        assert!(matches!(
+            wallet.estimate_tx_fee(spk, 10_000, Some(0.1)),
+            Err(MutinyError::FeeRateBelowMinimum)
+        ));
+    }
+
+    #[test]
+    async fn test_batch_recipients() {
+        let test_name = "batch_recipients";
+        log!("{}", test_name);
+        let wallet = create_wallet().await;
+
+        assert!(matches!(
+            wallet.recipient_outputs(vec![]),
+            Err(MutinyError::InvalidArgumentsError)
+        ));
+
+        let first = Address::from_str("tb1qqyqszqgpqyqszqgpqyqszqgpqyqszqgpw0yxjz").unwrap();
+        let second = Address::from_str("tb1qqgpqyqszqgpqyqszqgpqyqszqgpqyqszltzre5").unwrap();
+        let outputs = wallet
+            .recipient_outputs(vec![(first.clone(), 10_000), (second.clone(), 20_000)])
+            .unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                (first.script_pubkey(), 10_000),
+                (second.script_pubkey(), 20_000)
+            ]
+        );
+
+        // every address has to be for our network
+        let mainnet = Address::from_str("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu").unwrap();
+        assert!(matches!(
+            wallet.recipient_outputs(vec![(first, 10_000), (mainnet, 20_000)]),
+            Err(MutinyError::IncorrectNetwork(Network::Bitcoin))
+        ));
+    }
+
+    #[test]
+    async fn test_revealed_addresses() {
+        let test_name = "revealed_addresses";
+        log!("{}", test_name);
+        let wallet = create_wallet().await.with_scan_limits(0, 100);
+        assert_eq!(wallet.stop_gap, 1);
+
+        for _ in 0..3 {
+            wallet
+                .wallet
+                .try_write()
+                .unwrap()
+                .get_address(AddressIndex::New);
+        }
+        assert_eq!(
+            wallet.revealed_addresses().unwrap(),
+            RevealedAddresses {
+                receive: 3,
+                change: 0
+            }
+        );
+    }
+
+    #[test]
+    async fn test_rotate_keys() {
+        let test_name = "rotate_keys";
+        log!("{}", test_name);
+
+        let wallet = create_wallet().await;
+        let old_address = wallet
+            .wallet
+            .try_write()
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .address;
+
+        assert_eq!(wallet.rotate_keys().unwrap(), 1);
+        assert_eq!(wallet.storage.get_onchain_rotation().unwrap(), 1);
+        assert_eq!(wallet.retired_accounts.read().unwrap().len(), 1);
+        assert_eq!(wallet.retired_balance().unwrap(), 0);
+
+        // new addresses come from the new account
+        let new_address = wallet
+            .wallet
+            .try_write()
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .address;
+        assert_ne!(old_address, new_address);
+
+        // the old account is still loaded after a restart
+        let restarted = OnChainWallet::new(
+            &Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap(),
+            wallet.storage.clone(),
+            Network::Testnet,
+            0,
+            wallet.blockchain.clone(),
+            wallet.fees.clone(),
+            wallet.logger.clone(),
+        )
+        .unwrap();
+        let retired = restarted.retired_accounts.read().unwrap();
+        assert_eq!(retired.len(), 1);
+        assert_eq!(retired[0].rotation, 0);
+        let restarted_address = restarted
+            .wallet
+            .try_write()
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .address;
+        assert_eq!(restarted_address, new_address);
+    }
+
+    #[test]
+    fn test_rotated_paths_dont_collide() {
+        let test_name = "rotated_paths_dont_collide";
+        log!("{}", test_name);
+
+        let path = |index, rotation| {
+            onchain_derivation_path(Network::Testnet, index, rotation)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(path(1, 0), "m/86'/1'/1'");
+        assert_eq!(path(1, 1), "m/86'/1'/1'/1'");
+        // a rotation of one index is not the next index
+        assert_ne!(path(1, 1), path(2, 0));
+        assert_ne!(path(1, 1), path(2, 1));
+    }
+
+    #[test]
+    async fn test_create_watch_only_wallet() {
+        let test_name = "create_watch_only_wallet";
+        log!("{}", test_name);
+
+        let mnemonic = Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").expect("could not generate");
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Bitcoin, &mnemonic.to_seed("")).unwrap();
+        let path = DerivationPath::from_str("m/84'/0'/0'").unwrap();
+        let xpub = ExtendedPubKey::from_priv(&secp, &xprv.derive_priv(&secp, &path).unwrap());
+
+        let (receive, change) =
+            get_watch_only_descriptors(&xpub.to_string(), Network::Bitcoin).unwrap();
+        assert_eq!(receive, format!("wpkh({xpub}/0/*)"));
+        assert_eq!(change, Some(format!("wpkh({xpub}/1/*)")));
+
+        // xpub is for mainnet
+        assert!(get_watch_only_descriptors(&xpub.to_string(), Network::Testnet).is_err());
+
+        let db = MemoryStorage::new(None);
+        let logger = Arc::new(MutinyLogger::default());
+        let esplora = Arc::new(
+            FailoverEsploraClient::new(
+                vec!["https://mempool.space/api".to_string()],
+                logger.clone(),
+            )
+            .unwrap(),
+        );
+        let fees = Arc::new(MutinyFeeEstimator::new(
+            db.clone(),
+            esplora.clone(),
+            None,
+            logger.clone(),
+        ));
+        let wallet = OnChainWallet::new_watch_only(
+            &xpub.to_string(),
+            db,
+            Network::Bitcoin,
+            esplora,
+            fees,
+            logger,
+        )
+        .unwrap();
+
+        // first address from the BIP84 test vectors
+        let address = wallet
+            .wallet
+            .try_write()
+            .unwrap()
+            .get_address(AddressIndex::New)
+            .address;
+        assert_eq!(
+            address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+    }
+
+    #[test]
+    fn test_watch_only_descriptor_change() {
+        let desc = "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#abcdefgh";
+        let (receive, change) = get_watch_only_descriptors(desc, Network::Bitcoin).unwrap();
+        assert!(!receive.contains('#'));
+        assert!(receive.ends_with("/0/*)"));
+        assert!(change.unwrap().ends_with("/1/*)"));
+
+        let single = "addr(bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu)";
+        let (receive, change) = get_watch_only_descriptors(single, Network::Bitcoin).unwrap();
+        assert_eq!(receive, single);
+        assert_eq!(change, None);
+    }
+
     #[test]
     async fn test_label_psbt() {
         let test_name = "label_psbt";
@@ -526,4 +1398,31 @@ mod tests {
         assert!(label.clone().unwrap().addresses.contains(&send_to_addr));
         assert!(label.unwrap().addresses.contains(&change_addr));
     }
+
+    #[test]
+    async fn test_cpfp_unknown_tx() {
+        let test_name = "cpfp_unknown_tx";
+        log!("{}", test_name);
+        let wallet = create_wallet().await;
+
+        let txid =
+            Txid::from_str("4b3b0d0b3b6d1b5a3f3e3f0e1c0b1c2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c")
+                .unwrap();
+        let result = wallet.create_cpfp_psbt(txid, Some(10.0));
+        assert!(matches!(result, Err(MutinyError::NotFound)));
+    }
+
+    #[test]
+    async fn test_sign_foreign_psbt() {
+        let test_name = "sign_foreign_psbt";
+        log!("{}", test_name);
+        let wallet = create_wallet().await;
+
+        let psbt = PartiallySignedTransaction::from_str("cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEHakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpIAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA").unwrap();
+
+        // none of the inputs are ours so nothing should be signed
+        let signed = wallet.sign_psbt(psbt.clone()).unwrap();
+        assert_eq!(signed.unsigned_tx, psbt.unsigned_tx);
+        assert_eq!(signed.inputs[1].partial_sigs, psbt.inputs[1].partial_sigs);
+    }
 }
@@ -0,0 +1,163 @@
+use crate::keymanager::PhantomKeysManager;
+use crate::logging::MutinyLogger;
+use crate::storage::MutinyStorage;
+use bitcoin::hashes::hex::ToHex;
+use lightning::io::{self, Read};
+use lightning::ln::msgs::DecodeError;
+use lightning::onion_message::{
+    CustomOnionMessageContents, CustomOnionMessageHandler, OnionMessenger,
+};
+use lightning::util::logger::Logger;
+use lightning::util::ser::{Writeable, Writer};
+use lightning::{log_debug, log_warn};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Onion message types below this are reserved for the lightning protocol
+pub const MIN_CUSTOM_ONION_MESSAGE_TYPE: u64 = 64;
+
+pub(crate) type MutinyOnionMessenger<S: MutinyStorage> = OnionMessenger<
+    Arc<PhantomKeysManager<S>>,
+    Arc<PhantomKeysManager<S>>,
+    Arc<MutinyLogger>,
+    Arc<MutinyOnionMessageHandler>,
+>;
+
+/// An onion message of a protocol built on top of lightning,
+/// the data is whatever the protocol puts in its TLV.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomOnionMessage {
+    pub tlv_type: u64,
+    pub data: Vec<u8>,
+}
+
+impl CustomOnionMessageContents for CustomOnionMessage {
+    fn tlv_type(&self) -> u64 {
+        self.tlv_type
+    }
+}
+
+impl Writeable for CustomOnionMessage {
+    fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+        w.write_all(&self.data)
+    }
+}
+
+/// Receives the onion messages of the type it was registered for with
+/// [crate::nodemanager::NodeManager::register_onion_message_handler].
+///
+/// Onion messages can't be replied to directly, a request/response protocol
+/// should include where to send the response in its requests.
+pub trait OnionMessageHandler: Send + Sync {
+    fn handle_onion_message(&self, message: CustomOnionMessage);
+}
+
+/// Hands the onion messages our nodes receive to the handlers registered for their type,
+/// messages of other types are not read.
+pub(crate) struct MutinyOnionMessageHandler {
+    handlers: RwLock<HashMap<u64, Arc<dyn OnionMessageHandler>>>,
+    logger: Arc<MutinyLogger>,
+}
+
+impl MutinyOnionMessageHandler {
+    pub fn new(logger: Arc<MutinyLogger>) -> Self {
+        Self {
+            handlers: RwLock::new(HashMap::new()),
+            logger,
+        }
+    }
+
+    /// Registers the handler for a type, replacing the one it had
+    pub fn register(&self, tlv_type: u64, handler: Arc<dyn OnionMessageHandler>) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.insert(tlv_type, handler);
+        }
+    }
+
+    pub fn unregister(&self, tlv_type: u64) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.remove(&tlv_type);
+        }
+    }
+
+    fn handler(&self, tlv_type: u64) -> Option<Arc<dyn OnionMessageHandler>> {
+        self.handlers.read().ok()?.get(&tlv_type).cloned()
+    }
+}
+
+impl CustomOnionMessageHandler for MutinyOnionMessageHandler {
+    type CustomMessage = CustomOnionMessage;
+
+    fn handle_custom_message(&self, msg: Self::CustomMessage) {
+        match self.handler(msg.tlv_type) {
+            Some(handler) => handler.handle_onion_message(msg),
+            None => log_warn!(
+                self.logger,
+                "No handler for onion message of type {}",
+                msg.tlv_type
+            ),
+        }
+    }
+
+    fn read_custom_message<R: Read>(
+        &self,
+        message_type: u64,
+        buffer: &mut R,
+    ) -> Result<Option<Self::CustomMessage>, DecodeError> {
+        if self.handler(message_type).is_none() {
+            return Ok(None);
+        }
+
+        let mut data = vec![];
+        buffer
+            .read_to_end(&mut data)
+            .map_err(|e| DecodeError::Io(e.kind()))?;
+        log_debug!(
+            self.logger,
+            "Received onion message of type {message_type}: {}",
+            data.to_hex()
+        );
+        Ok(Some(CustomOnionMessage {
+            tlv_type: message_type,
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_onion_message_handler() {
+        let test_name = "test_onion_message_handler";
+        log!("{}", test_name);
+
+        let handler = MutinyOnionMessageHandler::new(Arc::new(MutinyLogger::default()));
+        let test_handler = Arc::new(TestMessageHandler::default());
+
+        // types without a handler are not read
+        let mut data: &[u8] = &[1, 2, 3];
+        assert_eq!(handler.read_custom_message(65, &mut data).unwrap(), None);
+
+        handler.register(65, test_handler.clone());
+        let mut data: &[u8] = &[1, 2, 3];
+        let message = handler
+            .read_custom_message(65, &mut data)
+            .unwrap()
+            .expect("registered type should be read");
+        assert_eq!(message.data, vec![1, 2, 3]);
+        assert_eq!(message.encode(), vec![1, 2, 3]);
+
+        handler.handle_custom_message(message.clone());
+        assert_eq!(*test_handler.onion_messages.lock().unwrap(), vec![message]);
+
+        handler.unregister(65);
+        let mut data: &[u8] = &[1, 2, 3];
+        assert_eq!(handler.read_custom_message(65, &mut data).unwrap(), None);
+    }
+}
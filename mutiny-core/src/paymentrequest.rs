@@ -0,0 +1,230 @@
+use crate::error::MutinyError;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::util::amount::Denomination;
+use bitcoin::{Address, Amount, Network};
+use lightning_invoice::Invoice;
+use lnurl::lightning_address::LightningAddress;
+use lnurl::lnurl::LnUrl;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use url::Url;
+
+const BITCOIN_PREFIX: &str = "bitcoin:";
+const LIGHTNING_PREFIX: &str = "lightning:";
+
+/// A parsed payment string, this can be anything a user may paste or scan
+/// to pay or connect to someone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PaymentRequest {
+    /// A BOLT11 lightning invoice
+    Bolt11(Invoice),
+    /// A BIP21 URI, this may have a lightning invoice attached
+    Bip21 {
+        address: Address,
+        amount_sats: Option<u64>,
+        label: Option<String>,
+        message: Option<String>,
+        invoice: Option<Invoice>,
+    },
+    /// A plain on-chain address
+    OnChain(Address),
+    /// A bech32 encoded LNURL
+    LnUrl(LnUrl),
+    /// A lightning address, eg: satoshi@mutinywallet.com
+    LightningAddress(LightningAddress),
+    /// A lightning node pubkey, with the connection string if one was given
+    NodePubkey {
+        pubkey: PublicKey,
+        connection_string: Option<String>,
+    },
+}
+
+impl PaymentRequest {
+    /// Parses the given string into a [PaymentRequest], checking that
+    /// any invoices or addresses are for the given network.
+    pub fn parse(str: &str, network: Network) -> Result<Self, MutinyError> {
+        let str = str.trim();
+        let lower = str.to_lowercase();
+
+        if lower.starts_with(BITCOIN_PREFIX) {
+            return parse_bip21(str, network);
+        }
+
+        // bech32 strings can be uppercase when coming from a QR code
+        let lower = lower.strip_prefix(LIGHTNING_PREFIX).unwrap_or(&lower);
+
+        if let Ok(invoice) = Invoice::from_str(lower) {
+            check_invoice_network(&invoice, network)?;
+            return Ok(PaymentRequest::Bolt11(invoice));
+        }
+
+        if let Ok(lnurl) = LnUrl::from_str(lower) {
+            return Ok(PaymentRequest::LnUrl(lnurl));
+        }
+
+        if let Ok(address) = Address::from_str(str) {
+            if !address.is_valid_for_network(network) {
+                return Err(MutinyError::IncorrectNetwork(address.network));
+            }
+            return Ok(PaymentRequest::OnChain(address));
+        }
+
+        // node pubkeys may be given with a connection string, eg: pubkey@host:port
+        let (pubkey_str, connection_string) = match str.split_once('@') {
+            Some((pubkey, _)) => (pubkey, Some(str.to_string())),
+            None => (str, None),
+        };
+        if let Ok(pubkey) = PublicKey::from_str(pubkey_str) {
+            return Ok(PaymentRequest::NodePubkey {
+                pubkey,
+                connection_string,
+            });
+        }
+
+        if let Ok(ln_address) = LightningAddress::from_str(str) {
+            return Ok(PaymentRequest::LightningAddress(ln_address));
+        }
+
+        Err(MutinyError::InvalidArgumentsError)
+    }
+}
+
+fn check_invoice_network(invoice: &Invoice, network: Network) -> Result<(), MutinyError> {
+    if invoice.network() != network {
+        return Err(MutinyError::IncorrectNetwork(invoice.network()));
+    }
+    Ok(())
+}
+
+fn parse_bip21(str: &str, network: Network) -> Result<PaymentRequest, MutinyError> {
+    let url = Url::parse(str).map_err(|_| MutinyError::InvalidArgumentsError)?;
+
+    let address = Address::from_str(url.path()).map_err(|_| MutinyError::InvalidArgumentsError)?;
+    if !address.is_valid_for_network(network) {
+        return Err(MutinyError::IncorrectNetwork(address.network));
+    }
+
+    let mut amount_sats = None;
+    let mut label = None;
+    let mut message = None;
+    let mut invoice = None;
+
+    for (key, value) in url.query_pairs() {
+        match key.to_lowercase().as_str() {
+            "amount" => {
+                let amount = Amount::from_str_in(&value, Denomination::Bitcoin)
+                    .map_err(|_| MutinyError::BadAmountError)?;
+                amount_sats = Some(amount.to_sat());
+            }
+            "label" => label = Some(value.to_string()),
+            "message" => message = Some(value.to_string()),
+            "lightning" => {
+                let inv = Invoice::from_str(&value.to_lowercase())
+                    .map_err(|_| MutinyError::InvoiceInvalid)?;
+                check_invoice_network(&inv, network)?;
+                invoice = Some(inv);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(PaymentRequest::Bip21 {
+        address,
+        amount_sats,
+        label,
+        message,
+        invoice,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const SIGNET_INVOICE: &str = "lntbs1m1pjrmuu3pp52hk0j956d7s8azaps87amadshnrcvqtkvk06y2nue2w69g6e5vasdqqcqzpgxqyz5vqsp5wu3py6257pa3yzarw0et2200c08r5fu6k3u94yfwmlnc8skdkc9s9qyyssqc783940p82c64qq9pu3xczt4tdxzex9wpjn54486y866aayft2cxxusl9eags4cs3kcmuqdrvhvs0gudpj5r2a6awu4wcq29crpesjcqhdju55";
+    const SIGNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+    const PUBKEY: &str = "02465ed5be53d04fde66c9418ff14a5f2267723810176c9212b722e542dc1afb1b";
+
+    #[test]
+    fn test_parse_invoice() {
+        let parsed = PaymentRequest::parse(SIGNET_INVOICE, Network::Signet).unwrap();
+        assert!(matches!(parsed, PaymentRequest::Bolt11(_)));
+
+        let prefixed = format!("LIGHTNING:{}", SIGNET_INVOICE.to_uppercase());
+        let parsed = PaymentRequest::parse(&prefixed, Network::Signet).unwrap();
+        assert!(matches!(parsed, PaymentRequest::Bolt11(_)));
+
+        let err = PaymentRequest::parse(SIGNET_INVOICE, Network::Bitcoin);
+        assert!(matches!(err, Err(MutinyError::IncorrectNetwork(_))));
+    }
+
+    #[test]
+    fn test_parse_address() {
+        let parsed = PaymentRequest::parse(SIGNET_ADDRESS, Network::Signet).unwrap();
+        assert!(matches!(parsed, PaymentRequest::OnChain(_)));
+
+        let err = PaymentRequest::parse(SIGNET_ADDRESS, Network::Bitcoin);
+        assert!(matches!(err, Err(MutinyError::IncorrectNetwork(_))));
+    }
+
+    #[test]
+    fn test_parse_bip21() {
+        let uri =
+            format!("bitcoin:{SIGNET_ADDRESS}?amount=0.001&label=test&lightning={SIGNET_INVOICE}");
+        let parsed = PaymentRequest::parse(&uri, Network::Signet).unwrap();
+
+        match parsed {
+            PaymentRequest::Bip21 {
+                address,
+                amount_sats,
+                label,
+                message,
+                invoice,
+            } => {
+                assert_eq!(address.to_string(), SIGNET_ADDRESS);
+                assert_eq!(amount_sats, Some(100_000));
+                assert_eq!(label, Some("test".to_string()));
+                assert_eq!(message, None);
+                assert!(invoice.is_some());
+            }
+            _ => panic!("expected bip21"),
+        }
+    }
+
+    #[test]
+    fn test_parse_pubkey() {
+        let parsed = PaymentRequest::parse(PUBKEY, Network::Signet).unwrap();
+        assert_eq!(
+            parsed,
+            PaymentRequest::NodePubkey {
+                pubkey: PublicKey::from_str(PUBKEY).unwrap(),
+                connection_string: None,
+            }
+        );
+
+        let with_host = format!("{PUBKEY}@127.0.0.1:9735");
+        let parsed = PaymentRequest::parse(&with_host, Network::Signet).unwrap();
+        assert_eq!(
+            parsed,
+            PaymentRequest::NodePubkey {
+                pubkey: PublicKey::from_str(PUBKEY).unwrap(),
+                connection_string: Some(with_host),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_lightning_address() {
+        let parsed = PaymentRequest::parse("ben@mutinywallet.com", Network::Signet).unwrap();
+        assert!(matches!(parsed, PaymentRequest::LightningAddress(_)));
+    }
+
+    #[test]
+    fn test_parse_garbage() {
+        let err = PaymentRequest::parse("not a payment", Network::Signet);
+        assert!(matches!(err, Err(MutinyError::InvalidArgumentsError)));
+    }
+}
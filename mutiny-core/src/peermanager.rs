@@ -1,5 +1,6 @@
 use crate::error::MutinyError;
 use crate::node::NetworkGraph;
+use crate::onionmessage::MutinyOnionMessenger;
 use crate::storage::MutinyStorage;
 use crate::{gossip, ldkstorage::PhantomChannelManager, logging::MutinyLogger};
 use crate::{gossip::read_peer_info, node::PubkeyConnectionInfo};
@@ -12,33 +13,45 @@ use lightning::{
 use std::{net::SocketAddr, sync::atomic::AtomicBool};
 
 use crate::networking::socket::{schedule_descriptor_read, MutinySocketDescriptor};
+use crate::peermessage::MutinyCustomMessageHandler;
 use bitcoin::BlockHash;
 use lightning::events::{MessageSendEvent, MessageSendEventsProvider};
 use lightning::ln::features::{InitFeatures, NodeFeatures};
 use lightning::ln::msgs;
 use lightning::ln::msgs::{LightningError, RoutingMessageHandler};
 use lightning::ln::peer_handler::PeerHandleError;
-use lightning::ln::peer_handler::{IgnoringMessageHandler, PeerManager as LdkPeerManager};
+use lightning::ln::peer_handler::PeerManager as LdkPeerManager;
 use lightning::log_warn;
 use lightning::routing::gossip::NodeId;
 use lightning::routing::utxo::{UtxoLookup, UtxoLookupError, UtxoResult};
-use lightning::util::logger::Logger;
-use std::sync::Arc;
+use lightning::util::logger::{Level, Logger, Record};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::utils;
 
 #[cfg(target_arch = "wasm32")]
 use crate::networking::ws_socket::WsTcpSocketDescriptor;
 
 #[cfg(target_arch = "wasm32")]
-use crate::networking::proxy::WsProxy;
+use crate::networking::proxy::{WsProxy, WsProxyFailover};
 
 #[cfg(not(target_arch = "wasm32"))]
 use tokio::time;
 
 #[cfg(not(target_arch = "wasm32"))]
-use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+#[cfg(not(target_arch = "wasm32"))]
+use lightning::log_info;
 
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::net::TcpStream;
+use std::sync::atomic::Ordering;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::networking::socks;
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::networking::tcp_socket::TcpSocketDescriptor;
@@ -92,9 +105,9 @@ pub(crate) type PeerManagerImpl<S: MutinyStorage> = LdkPeerManager<
     MutinySocketDescriptor,
     Arc<PhantomChannelManager<S>>,
     Arc<GossipMessageHandler<S>>,
-    Arc<IgnoringMessageHandler>,
-    Arc<MutinyLogger>,
-    Arc<IgnoringMessageHandler>,
+    Arc<MutinyOnionMessenger<S>>,
+    Arc<PeerManagerLogger>,
+    Arc<MutinyCustomMessageHandler>,
     Arc<PhantomKeysManager<S>>,
 >;
 
@@ -165,10 +178,148 @@ impl<S: MutinyStorage> PeerManager for PeerManagerImpl<S> {
     }
 }
 
+/// Connection details of a peer, recorded when it sends us its init message
+#[derive(Debug, Clone)]
+pub(crate) struct PeerStats {
+    pub features: InitFeatures,
+    /// When the current connection was set up
+    pub connected_at: Duration,
+    /// How long the peer took to answer our last ping with a pong, in milliseconds
+    pub latency_ms: Option<u64>,
+}
+
+/// Keeps track of our connections to peers and how quickly they answer our pings
+#[derive(Default)]
+pub(crate) struct PeerTracker {
+    peers: Mutex<HashMap<PublicKey, PeerStats>>,
+    /// When we sent each peer the ping it hasn't answered yet
+    pings: Mutex<HashMap<PublicKey, Duration>>,
+}
+
+impl PeerTracker {
+    fn connected(&self, pubkey: PublicKey, features: InitFeatures) {
+        // a ping from an earlier connection will never be answered
+        if let Ok(mut pings) = self.pings.lock() {
+            pings.remove(&pubkey);
+        }
+
+        if let Ok(mut peers) = self.peers.lock() {
+            // keep the last measured latency until the peer answers a new ping
+            let latency_ms = peers.get(&pubkey).and_then(|p| p.latency_ms);
+            peers.insert(
+                pubkey,
+                PeerStats {
+                    features,
+                    connected_at: utils::now(),
+                    latency_ms,
+                },
+            );
+        }
+    }
+
+    fn ping_sent(&self, pubkey: PublicKey) {
+        if let Ok(mut pings) = self.pings.lock() {
+            // pongs come back in order, so time the oldest unanswered ping
+            pings.entry(pubkey).or_insert_with(utils::now);
+        }
+    }
+
+    fn pong_received(&self, pubkey: &PublicKey) {
+        let sent = self
+            .pings
+            .lock()
+            .ok()
+            .and_then(|mut pings| pings.remove(pubkey));
+        if let (Some(sent), Ok(mut peers)) = (sent, self.peers.lock()) {
+            if let Some(stats) = peers.get_mut(pubkey) {
+                let latency = utils::now().saturating_sub(sent);
+                stats.latency_ms = Some(latency.as_millis() as u64);
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, pubkey: &PublicKey) -> Option<PeerStats> {
+        self.peers.lock().ok()?.get(pubkey).cloned()
+    }
+}
+
+/// The logger given to our peer managers.
+///
+/// LDK sends the pings and handles the pongs itself, the only place they show up
+/// is its trace logs, so we watch those to time how long peers take to answer.
+pub(crate) struct PeerManagerLogger {
+    pub(crate) logger: Arc<MutinyLogger>,
+    pub(crate) peer_tracker: Arc<PeerTracker>,
+}
+
+impl Logger for PeerManagerLogger {
+    fn log(&self, record: &Record) {
+        if record.level == Level::Trace && record.module_path == "lightning::ln::peer_handler" {
+            match parse_ping_log(&record.args.to_string()) {
+                Some((PingLog::Ping, pubkey)) => self.peer_tracker.ping_sent(pubkey),
+                Some((PingLog::Pong, pubkey)) => self.peer_tracker.pong_received(&pubkey),
+                None => (),
+            }
+        }
+        self.logger.log(record)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PingLog {
+    Ping,
+    Pong,
+}
+
+/// Parses the logs LDK writes when it sends a peer a ping or gets a pong back,
+/// ie "Enqueueing message Ping { .. } to <pubkey>" and "Received message Pong(..) from <pubkey>"
+fn parse_ping_log(log: &str) -> Option<(PingLog, PublicKey)> {
+    let ping_log = if log.starts_with("Enqueueing message Ping") {
+        PingLog::Ping
+    } else if log.starts_with("Received message Pong") {
+        PingLog::Pong
+    } else {
+        return None;
+    };
+    let pubkey = PublicKey::from_str(log.rsplit(' ').next()?).ok()?;
+    Some((ping_log, pubkey))
+}
+
+/// The names of the features a peer supports
+pub(crate) fn feature_names(features: &InitFeatures) -> Vec<String> {
+    let known = [
+        ("data_loss_protect", features.supports_data_loss_protect()),
+        (
+            "upfront_shutdown_script",
+            features.supports_upfront_shutdown_script(),
+        ),
+        ("gossip_queries", features.supports_gossip_queries()),
+        (
+            "variable_length_onion",
+            features.supports_variable_length_onion(),
+        ),
+        ("static_remote_key", features.supports_static_remote_key()),
+        ("payment_secret", features.supports_payment_secret()),
+        ("basic_mpp", features.supports_basic_mpp()),
+        ("wumbo", features.supports_wumbo()),
+        ("shutdown_anysegwit", features.supports_shutdown_anysegwit()),
+        ("onion_messages", features.supports_onion_messages()),
+        ("channel_type", features.supports_channel_type()),
+        ("scid_privacy", features.supports_scid_privacy()),
+        ("zero_conf", features.supports_zero_conf()),
+    ];
+    known
+        .into_iter()
+        .filter(|(_, supported)| *supported)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct GossipMessageHandler<S: MutinyStorage> {
     pub(crate) storage: S,
     pub(crate) network_graph: Arc<NetworkGraph>,
+    pub(crate) peer_tracker: Arc<PeerTracker>,
     pub(crate) logger: Arc<MutinyLogger>,
 }
 
@@ -256,10 +407,12 @@ impl<S: MutinyStorage> RoutingMessageHandler for GossipMessageHandler<S> {
 
     fn peer_connected(
         &self,
-        _their_node_id: &PublicKey,
-        _init: &msgs::Init,
+        their_node_id: &PublicKey,
+        init: &msgs::Init,
         _inbound: bool,
     ) -> Result<(), ()> {
+        self.peer_tracker
+            .connected(*their_node_id, init.features.clone());
         Ok(())
     }
 
@@ -309,7 +462,8 @@ impl<S: MutinyStorage> RoutingMessageHandler for GossipMessageHandler<S> {
 }
 
 pub(crate) async fn connect_peer_if_necessary(
-    #[cfg(target_arch = "wasm32")] websocket_proxy_addr: &str,
+    #[cfg(target_arch = "wasm32")] websocket_proxies: &WsProxyFailover,
+    #[cfg(not(target_arch = "wasm32"))] socks_proxy: Option<&str>,
     peer_connection_info: &PubkeyConnectionInfo,
     logger: Arc<MutinyLogger>,
     peer_manager: Arc<dyn PeerManager>,
@@ -323,7 +477,9 @@ pub(crate) async fn connect_peer_if_necessary(
     } else {
         connect_peer(
             #[cfg(target_arch = "wasm32")]
-            websocket_proxy_addr,
+            websocket_proxies,
+            #[cfg(not(target_arch = "wasm32"))]
+            socks_proxy,
             peer_connection_info,
             logger,
             peer_manager,
@@ -334,7 +490,8 @@ pub(crate) async fn connect_peer_if_necessary(
 }
 
 async fn connect_peer(
-    #[cfg(target_arch = "wasm32")] websocket_proxy_addr: &str,
+    #[cfg(target_arch = "wasm32")] websocket_proxies: &WsProxyFailover,
+    #[cfg(not(target_arch = "wasm32"))] socks_proxy: Option<&str>,
     peer_connection_info: &PubkeyConnectionInfo,
     logger: Arc<MutinyLogger>,
     peer_manager: Arc<dyn PeerManager>,
@@ -344,12 +501,21 @@ async fn connect_peer(
         ConnectionType::Tcp(ref t) => {
             #[cfg(target_arch = "wasm32")]
             {
-                let proxy = WsProxy::new(
-                    websocket_proxy_addr,
-                    peer_connection_info.clone(),
-                    logger.clone(),
-                )
-                .await?;
+                let proxy_url = websocket_proxies.active();
+                let proxy =
+                    match WsProxy::new(&proxy_url, peer_connection_info.clone(), logger.clone())
+                        .await
+                    {
+                        Ok(proxy) => {
+                            websocket_proxies.record_success(&proxy_url);
+                            proxy
+                        }
+                        Err(e) => {
+                            // the peer may just be offline, only blame the proxy if it is down
+                            websocket_proxies.check_proxy(&proxy_url).await;
+                            return Err(e);
+                        }
+                    };
                 let (_, net_addr) = try_parse_addr_string(t);
                 (
                     MutinySocketDescriptor::Tcp(WsTcpSocketDescriptor::new(Arc::new(proxy))),
@@ -359,13 +525,27 @@ async fn connect_peer(
             #[cfg(not(target_arch = "wasm32"))]
             {
                 let (socket_addr, net_addr) = try_parse_addr_string(t);
-                let socket_addr = socket_addr.ok_or(MutinyError::ConnectionFailed)?;
 
-                let stream =
-                    time::timeout(Duration::from_secs(10), TcpStream::connect(&socket_addr))
-                        .await
-                        .map_err(|_| MutinyError::ConnectionFailed)?
-                        .map_err(|_| MutinyError::ConnectionFailed)?;
+                let stream = match socks_proxy {
+                    // let the proxy resolve the host, this also allows onion addresses
+                    Some(proxy) => {
+                        let (host, port) = t
+                            .rsplit_once(':')
+                            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+                            .ok_or(MutinyError::ConnectionFailed)?;
+                        let host = host.trim_start_matches('[').trim_end_matches(']');
+                        time::timeout(Duration::from_secs(30), socks::connect(proxy, host, port))
+                            .await
+                            .map_err(|_| MutinyError::ConnectionFailed)??
+                    }
+                    None => {
+                        let socket_addr = socket_addr.ok_or(MutinyError::ConnectionFailed)?;
+                        time::timeout(Duration::from_secs(10), TcpStream::connect(&socket_addr))
+                            .await
+                            .map_err(|_| MutinyError::ConnectionFailed)?
+                            .map_err(|_| MutinyError::ConnectionFailed)?
+                    }
+                };
 
                 let stream = stream.into_std().unwrap();
                 (
@@ -405,7 +585,72 @@ async fn connect_peer(
     Ok(())
 }
 
-fn try_parse_addr_string(addr: &str) -> (Option<SocketAddr>, Option<NetAddress>) {
+/// Accepts inbound connections on the given port and hands them to the peer manager,
+/// until the node is stopped.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn start_listener(
+    port: u16,
+    logger: Arc<MutinyLogger>,
+    peer_manager: Arc<dyn PeerManager>,
+    stop: Arc<AtomicBool>,
+) -> Result<(), MutinyError> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|_| MutinyError::ConnectionFailed)?;
+    log_info!(logger, "listening for inbound connections on port {port}");
+
+    utils::spawn(async move {
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                log_debug!(logger, "stopped listening on port {port}");
+                break;
+            }
+
+            // wake up every second to check if we should stop
+            let (stream, remote_addr) =
+                match time::timeout(Duration::from_secs(1), listener.accept()).await {
+                    Err(_) => continue,
+                    Ok(Err(e)) => {
+                        log_warn!(logger, "could not accept inbound connection: {e}");
+                        continue;
+                    }
+                    Ok(Ok(accepted)) => accepted,
+                };
+
+            let stream = match stream.into_std() {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log_warn!(logger, "could not accept inbound connection: {e}");
+                    continue;
+                }
+            };
+            let descriptor = MutinySocketDescriptor::Native(TcpSocketDescriptor::new(Arc::new(
+                tokio::sync::Mutex::new(stream),
+            )));
+
+            let (_, net_addr) = try_parse_addr_string(&remote_addr.to_string());
+            if let Err(e) = peer_manager.new_inbound_connection(descriptor.clone(), net_addr) {
+                log_warn!(
+                    logger,
+                    "rejected inbound connection from {remote_addr}: {e:?}"
+                );
+                continue;
+            }
+            log_debug!(logger, "accepted inbound connection from {remote_addr}");
+
+            schedule_descriptor_read(
+                descriptor,
+                peer_manager.clone(),
+                logger.clone(),
+                stop.clone(),
+            );
+        }
+    });
+
+    Ok(())
+}
+
+pub(crate) fn try_parse_addr_string(addr: &str) -> (Option<SocketAddr>, Option<NetAddress>) {
     let socket_addr = addr.parse::<SocketAddr>().ok();
     let net_addr = socket_addr.map(|socket_addr| match socket_addr {
         SocketAddr::V4(sockaddr) => NetAddress::IPv4 {
@@ -419,3 +664,74 @@ fn try_parse_addr_string(addr: &str) -> (Option<SocketAddr>, Option<NetAddress>)
     });
     (socket_addr, net_addr)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_peer_tracker() {
+        let test_name = "test_peer_tracker";
+        log!("{}", test_name);
+
+        let secp = Secp256k1::new();
+        let pubkey = SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp);
+        let tracker = PeerTracker::default();
+        assert!(tracker.get(&pubkey).is_none());
+
+        tracker.connected(pubkey, InitFeatures::empty());
+        let stats = tracker.get(&pubkey).unwrap();
+        assert!(stats.latency_ms.is_none());
+        assert!(feature_names(&stats.features).is_empty());
+
+        // a pong without a ping isn't counted
+        tracker.pong_received(&pubkey);
+        assert!(tracker.get(&pubkey).unwrap().latency_ms.is_none());
+
+        tracker.ping_sent(pubkey);
+        tracker.pong_received(&pubkey);
+        let stats = tracker.get(&pubkey).unwrap();
+        assert!(stats.latency_ms.is_some());
+        assert!(tracker.pings.lock().unwrap().is_empty());
+
+        // a reconnection keeps the latency we measured before,
+        // but forgets the ping the old connection never answered
+        tracker.ping_sent(pubkey);
+        let mut features = InitFeatures::empty();
+        features.set_static_remote_key_optional();
+        tracker.connected(pubkey, features);
+        let reconnected = tracker.get(&pubkey).unwrap();
+        assert_eq!(reconnected.latency_ms, stats.latency_ms);
+        assert!(reconnected.connected_at >= stats.connected_at);
+        assert!(tracker.pings.lock().unwrap().is_empty());
+        assert_eq!(
+            feature_names(&reconnected.features),
+            vec!["static_remote_key".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_ping_log() {
+        let test_name = "test_parse_ping_log";
+        log!("{}", test_name);
+
+        let secp = Secp256k1::new();
+        let pubkey = SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp);
+
+        let ping = format!("Enqueueing message Ping {{ ponglen: 0, byteslen: 64 }} to {pubkey}");
+        assert_eq!(parse_ping_log(&ping), Some((PingLog::Ping, pubkey)));
+
+        let pong = format!("Received message Pong(Pong {{ byteslen: 0 }}) from {pubkey}");
+        assert_eq!(parse_ping_log(&pong), Some((PingLog::Pong, pubkey)));
+
+        let other =
+            format!("Received message Ping(Ping {{ ponglen: 0, byteslen: 64 }}) from {pubkey}");
+        assert_eq!(parse_ping_log(&other), None);
+        assert_eq!(parse_ping_log("Enqueueing message Ping to nobody"), None);
+    }
+}
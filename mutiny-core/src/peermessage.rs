@@ -0,0 +1,256 @@
+use crate::error::MutinyError;
+use crate::logging::MutinyLogger;
+use bitcoin::secp256k1::PublicKey;
+use lightning::io::{self, Read};
+use lightning::ln::features::{InitFeatures, NodeFeatures};
+use lightning::ln::msgs::{DecodeError, LightningError};
+use lightning::ln::peer_handler::CustomMessageHandler;
+use lightning::ln::wire::{CustomMessageReader, Type};
+use lightning::log_warn;
+use lightning::util::logger::Logger;
+use lightning::util::ser::{Writeable, Writer};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Peer message types below this are reserved for the lightning protocol
+pub const MIN_CUSTOM_MESSAGE_TYPE: u16 = 32768;
+
+/// Feature bits below this are reserved for the lightning protocol
+pub const MIN_CUSTOM_FEATURE_BIT: usize = 256;
+
+/// A message of a protocol built on top of lightning, sent directly to a peer
+/// over our existing connection to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomPeerMessage {
+    pub message_type: u16,
+    pub data: Vec<u8>,
+}
+
+impl Type for CustomPeerMessage {
+    fn type_id(&self) -> u16 {
+        self.message_type
+    }
+}
+
+impl Writeable for CustomPeerMessage {
+    fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+        w.write_all(&self.data)
+    }
+}
+
+/// Receives the peer messages of the type it was registered for with
+/// [crate::nodemanager::NodeManager::register_peer_message_handler].
+pub trait PeerMessageHandler: Send + Sync {
+    fn handle_peer_message(&self, from: PublicKey, message: CustomPeerMessage);
+}
+
+/// Hands the custom messages our peers send to the handlers registered for their type,
+/// sends the messages queued for our peers, and adds the custom feature bits we signal.
+///
+/// Handlers and feature bits are shared by all our nodes, queued messages are per node.
+pub(crate) struct MutinyCustomMessageHandler {
+    handlers: Arc<RwLock<HashMap<u16, Arc<dyn PeerMessageHandler>>>>,
+    feature_bits: Arc<RwLock<Vec<usize>>>,
+    pending: Mutex<Vec<(PublicKey, CustomPeerMessage)>>,
+    logger: Arc<MutinyLogger>,
+}
+
+impl MutinyCustomMessageHandler {
+    pub fn new(logger: Arc<MutinyLogger>) -> Self {
+        Self {
+            handlers: Arc::new(RwLock::new(HashMap::new())),
+            feature_bits: Arc::new(RwLock::new(vec![])),
+            pending: Mutex::new(vec![]),
+            logger,
+        }
+    }
+
+    /// A handler for a node's peer manager, sharing our handlers and feature bits
+    pub fn for_node(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+            feature_bits: self.feature_bits.clone(),
+            pending: Mutex::new(vec![]),
+            logger: self.logger.clone(),
+        }
+    }
+
+    /// Registers the handler for a type, replacing the one it had
+    pub fn register(&self, message_type: u16, handler: Arc<dyn PeerMessageHandler>) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.insert(message_type, handler);
+        }
+    }
+
+    pub fn unregister(&self, message_type: u16) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.remove(&message_type);
+        }
+    }
+
+    /// Signals a custom feature bit to our peers, odd bits are optional and even bits required
+    pub fn add_feature_bit(&self, bit: usize) -> Result<(), MutinyError> {
+        if bit < MIN_CUSTOM_FEATURE_BIT {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        let mut feature_bits = self.feature_bits.try_write()?;
+        if !feature_bits.contains(&bit) {
+            feature_bits.push(bit);
+        }
+        Ok(())
+    }
+
+    /// Queues a message for a peer, it is sent the next time the peer manager processes events
+    pub fn queue(&self, peer: PublicKey, message: CustomPeerMessage) -> Result<(), MutinyError> {
+        if message.message_type < MIN_CUSTOM_MESSAGE_TYPE {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+        self.pending.try_lock()?.push((peer, message));
+        Ok(())
+    }
+
+    fn handler(&self, message_type: u16) -> Option<Arc<dyn PeerMessageHandler>> {
+        self.handlers.read().ok()?.get(&message_type).cloned()
+    }
+
+    fn feature_bits(&self) -> Vec<usize> {
+        self.feature_bits
+            .read()
+            .map(|bits| bits.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Sets our custom feature bits, LDK's feature contexts are sealed so this can't be generic
+macro_rules! with_custom_bits {
+    ($handler:expr, $features:expr) => {{
+        let mut features = $features;
+        for bit in $handler.feature_bits() {
+            let res = if bit % 2 == 0 {
+                features.set_required_custom_bit(bit)
+            } else {
+                features.set_optional_custom_bit(bit)
+            };
+            if res.is_err() {
+                log_warn!($handler.logger, "Could not set custom feature bit {bit}");
+            }
+        }
+        features
+    }};
+}
+
+impl CustomMessageReader for MutinyCustomMessageHandler {
+    type CustomMessage = CustomPeerMessage;
+
+    fn read<R: Read>(
+        &self,
+        message_type: u16,
+        buffer: &mut R,
+    ) -> Result<Option<Self::CustomMessage>, DecodeError> {
+        if self.handler(message_type).is_none() {
+            return Ok(None);
+        }
+
+        let mut data = vec![];
+        buffer
+            .read_to_end(&mut data)
+            .map_err(|e| DecodeError::Io(e.kind()))?;
+        Ok(Some(CustomPeerMessage { message_type, data }))
+    }
+}
+
+impl CustomMessageHandler for MutinyCustomMessageHandler {
+    fn handle_custom_message(
+        &self,
+        msg: Self::CustomMessage,
+        sender_node_id: &PublicKey,
+    ) -> Result<(), LightningError> {
+        if let Some(handler) = self.handler(msg.message_type) {
+            handler.handle_peer_message(*sender_node_id, msg);
+        }
+        Ok(())
+    }
+
+    fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, Self::CustomMessage)> {
+        self.pending
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default()
+    }
+
+    fn provided_node_features(&self) -> NodeFeatures {
+        with_custom_bits!(self, NodeFeatures::empty())
+    }
+
+    fn provided_init_features(&self, _their_node_id: &PublicKey) -> InitFeatures {
+        with_custom_bits!(self, InitFeatures::empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_custom_message_handler() {
+        let test_name = "test_custom_message_handler";
+        log!("{}", test_name);
+
+        let handler = MutinyCustomMessageHandler::new(Arc::new(MutinyLogger::default()));
+        let test_handler = Arc::new(TestMessageHandler::default());
+        let peer = PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+
+        // types without a handler are not read
+        let mut data: &[u8] = &[1, 2, 3];
+        assert_eq!(handler.read(32769, &mut data).unwrap(), None);
+
+        handler.register(32769, test_handler.clone());
+        let mut data: &[u8] = &[1, 2, 3];
+        let message = handler
+            .read(32769, &mut data)
+            .unwrap()
+            .expect("registered type should be read");
+        handler
+            .handle_custom_message(message.clone(), &peer)
+            .unwrap();
+        assert_eq!(
+            *test_handler.peer_messages.lock().unwrap(),
+            vec![(peer, message.clone())]
+        );
+
+        // only custom types can be sent
+        let reserved = CustomPeerMessage {
+            message_type: 1,
+            data: vec![],
+        };
+        assert!(handler.queue(peer, reserved).is_err());
+        handler.queue(peer, message.clone()).unwrap();
+        assert_eq!(
+            handler.get_and_clear_pending_msg(),
+            vec![(peer, message.clone())]
+        );
+        assert!(handler.get_and_clear_pending_msg().is_empty());
+
+        // nodes share the handlers and feature bits but not the queue
+        let node_handler = handler.for_node();
+        assert!(node_handler.handler(32769).is_some());
+        node_handler.queue(peer, message).unwrap();
+        assert!(handler.get_and_clear_pending_msg().is_empty());
+        assert_eq!(node_handler.get_and_clear_pending_msg().len(), 1);
+
+        assert!(handler.add_feature_bit(10).is_err());
+        handler.add_feature_bit(301).unwrap();
+        assert!(!node_handler
+            .provided_init_features(&peer)
+            .le_flags()
+            .is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::audit::{Integration, IntegrationAuditRecord};
+use crate::error::MutinyError;
+use crate::nodemanager::{MutinyBip21RawMaterials, MutinyInvoice, NodeManager};
+use crate::storage::MutinyStorage;
+use bitcoin::hashes::sha256;
+use bitcoin::{Address, Network};
+use futures::lock::Mutex;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// A restricted handle to a [NodeManager] for point-of-sale use.
+///
+/// It can only create invoices and BIP21s and check if they have been paid.
+/// It cannot spend funds, read the seed, or see any payments it did not create,
+/// so it is safe to hand to an untrusted cashier device or kiosk page.
+#[derive(Clone)]
+pub struct PosHandle<S: MutinyStorage> {
+    node_manager: Arc<NodeManager<S>>,
+    /// Payment hashes of the invoices created through this handle
+    invoices: Arc<Mutex<HashSet<sha256::Hash>>>,
+    /// Addresses of the BIP21s created through this handle
+    addresses: Arc<Mutex<HashSet<Address>>>,
+}
+
+impl<S: MutinyStorage> PosHandle<S> {
+    pub fn new(node_manager: Arc<NodeManager<S>>) -> Self {
+        Self {
+            node_manager,
+            invoices: Arc::new(Mutex::new(HashSet::new())),
+            addresses: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    fn audit<T>(&self, action: &str, amount: Option<u64>, result: &Result<T, MutinyError>) {
+        self.node_manager
+            .audit_integration_action(IntegrationAuditRecord::new(
+                Integration::PointOfSale,
+                "pos".to_string(),
+                action,
+                amount,
+                result.as_ref().err().map(|e| e.to_string()),
+            ));
+    }
+
+    /// Returns the network of the wallet.
+    pub fn get_network(&self) -> Network {
+        self.node_manager.get_network()
+    }
+
+    /// Creates a lightning invoice. The amount should be in satoshis.
+    pub async fn create_invoice(
+        &self,
+        amount: Option<u64>,
+        labels: Vec<String>,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        let result = self.node_manager.create_invoice(amount, labels).await;
+        self.audit("create_invoice", amount, &result);
+        let invoice = result?;
+        self.invoices.lock().await.insert(invoice.payment_hash);
+        Ok(invoice)
+    }
+
+    /// Creates a BIP 21 invoice with a new address and a lightning invoice.
+    /// The amount should be in satoshis.
+    pub async fn create_bip21(
+        &self,
+        amount: Option<u64>,
+        labels: Vec<String>,
+    ) -> Result<MutinyBip21RawMaterials, MutinyError> {
+        let result = self.node_manager.create_bip21(amount, labels).await;
+        self.audit("create_bip21", amount, &result);
+        let bip21 = result?;
+        self.invoices
+            .lock()
+            .await
+            .insert(*bip21.invoice.payment_hash());
+        self.addresses.lock().await.insert(bip21.address.clone());
+        Ok(bip21)
+    }
+
+    /// Returns if an invoice created by this handle has been paid.
+    pub async fn is_invoice_paid(&self, payment_hash: &sha256::Hash) -> Result<bool, MutinyError> {
+        if !self.invoices.lock().await.contains(payment_hash) {
+            return Err(MutinyError::NotFound);
+        }
+
+        let invoice = self.node_manager.get_invoice_by_hash(payment_hash).await?;
+        Ok(invoice.paid)
+    }
+
+    /// Waits until an invoice created by this handle is paid, see [NodeManager::wait_for_payment].
+    pub async fn wait_for_invoice_paid(
+        &self,
+        payment_hash: &sha256::Hash,
+        timeout_secs: u64,
+    ) -> Result<MutinyInvoice, MutinyError> {
+        if !self.invoices.lock().await.contains(payment_hash) {
+            return Err(MutinyError::NotFound);
+        }
+
+        self.node_manager
+            .wait_for_payment(payment_hash, timeout_secs)
+            .await
+    }
+
+    /// Returns if an address created by this handle has received an on-chain payment.
+    pub async fn is_address_paid(&self, address: &Address) -> Result<bool, MutinyError> {
+        if !self.addresses.lock().await.contains(address) {
+            return Err(MutinyError::NotFound);
+        }
+
+        let tx = self.node_manager.check_address(address).await?;
+        Ok(tx.is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use crate::{keymanager::generate_seed, MutinyWalletConfig};
+    use bitcoin::hashes::Hash;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    async fn pos_handle_only_sees_its_own_payments() {
+        let test_name = "pos_handle_only_sees_its_own_payments";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(None);
+        let seed = generate_seed(12).expect("Failed to gen seed");
+        let c = MutinyWalletConfig::new(
+            Some(seed),
+            #[cfg(target_arch = "wasm32")]
+            None,
+            Some(Network::Regtest),
+            None,
+            None,
+            None,
+        );
+        let nm = NodeManager::new(c, storage)
+            .await
+            .expect("node manager should initialize");
+        let pos = PosHandle::new(Arc::new(nm));
+        assert_eq!(pos.get_network(), Network::Regtest);
+
+        let payment_hash = sha256::Hash::hash(&[0; 32]);
+        assert!(matches!(
+            pos.is_invoice_paid(&payment_hash).await,
+            Err(MutinyError::NotFound)
+        ));
+
+        let address = pos.node_manager.get_new_address(vec![]).unwrap();
+        assert!(matches!(
+            pos.is_address_paid(&address).await,
+            Err(MutinyError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,709 @@
+use crate::error::MutinyError;
+use crate::logging::MutinyLogger;
+use crate::storage::MutinyStorage;
+use crate::utils;
+use futures::lock::Mutex;
+use lightning::util::logger::Logger;
+use lightning::{log_debug, log_error, log_warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a fetched bitcoin price is used for before fetching a new one by default, in seconds
+pub const DEFAULT_PRICE_CACHE_TTL_SECS: u64 = 300;
+
+/// The longest a fetched bitcoin price can be used for, in seconds
+pub const MAX_PRICE_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+const COINGECKO_API_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// A fiat currency bitcoin can be priced in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FiatCurrency {
+    #[default]
+    Usd,
+    Eur,
+    Gbp,
+    Jpy,
+    Cad,
+    Aud,
+    Chf,
+    Cny,
+    Inr,
+    Brl,
+    Mxn,
+    Krw,
+}
+
+impl FiatCurrency {
+    /// All the currencies bitcoin can be priced in
+    pub const ALL: [FiatCurrency; 12] = [
+        FiatCurrency::Usd,
+        FiatCurrency::Eur,
+        FiatCurrency::Gbp,
+        FiatCurrency::Jpy,
+        FiatCurrency::Cad,
+        FiatCurrency::Aud,
+        FiatCurrency::Chf,
+        FiatCurrency::Cny,
+        FiatCurrency::Inr,
+        FiatCurrency::Brl,
+        FiatCurrency::Mxn,
+        FiatCurrency::Krw,
+    ];
+
+    /// The lowercase ISO 4217 code, ie "usd"
+    pub fn code(&self) -> &'static str {
+        match self {
+            FiatCurrency::Usd => "usd",
+            FiatCurrency::Eur => "eur",
+            FiatCurrency::Gbp => "gbp",
+            FiatCurrency::Jpy => "jpy",
+            FiatCurrency::Cad => "cad",
+            FiatCurrency::Aud => "aud",
+            FiatCurrency::Chf => "chf",
+            FiatCurrency::Cny => "cny",
+            FiatCurrency::Inr => "inr",
+            FiatCurrency::Brl => "brl",
+            FiatCurrency::Mxn => "mxn",
+            FiatCurrency::Krw => "krw",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            FiatCurrency::Usd => "$",
+            FiatCurrency::Eur => "€",
+            FiatCurrency::Gbp => "£",
+            FiatCurrency::Jpy => "¥",
+            FiatCurrency::Cad => "CA$",
+            FiatCurrency::Aud => "A$",
+            FiatCurrency::Chf => "CHF",
+            FiatCurrency::Cny => "CN¥",
+            FiatCurrency::Inr => "₹",
+            FiatCurrency::Brl => "R$",
+            FiatCurrency::Mxn => "MX$",
+            FiatCurrency::Krw => "₩",
+        }
+    }
+
+    /// How many decimal places amounts are shown with
+    pub fn decimals(&self) -> usize {
+        match self {
+            FiatCurrency::Jpy | FiatCurrency::Krw => 0,
+            _ => 2,
+        }
+    }
+
+    /// Formats an amount in this currency with its symbol and thousands separators,
+    /// ie "$1,234.56" or "¥1,235".
+    pub fn format(&self, amount: f64) -> String {
+        let number = format!("{:.*}", self.decimals(), amount.abs());
+        let (whole, fraction) = match number.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (number.as_str(), None),
+        };
+
+        let mut formatted = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                formatted.push(',');
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push('.');
+            formatted.push_str(fraction);
+        }
+
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let symbol = self.symbol();
+        // letter symbols like "CHF" need a space before the number
+        let separator = if symbol.chars().all(char::is_alphabetic) {
+            " "
+        } else {
+            ""
+        };
+
+        format!("{sign}{symbol}{separator}{formatted}")
+    }
+}
+
+impl FromStr for FiatCurrency {
+    type Err = MutinyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_lowercase();
+        FiatCurrency::ALL
+            .into_iter()
+            .find(|c| c.code() == code)
+            .ok_or(MutinyError::InvalidArgumentsError)
+    }
+}
+
+impl fmt::Display for FiatCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code().to_uppercase())
+    }
+}
+
+/// Converts an amount of sats to fiat at the given price of one bitcoin
+pub fn sats_to_fiat(sats: u64, price: f32) -> f64 {
+    sats as f64 * price as f64 / 100_000_000.0
+}
+
+/// Formats an amount of sats in a fiat currency at the given price of one bitcoin
+pub fn format_sats_as_fiat(sats: u64, price: f32, currency: FiatCurrency) -> String {
+    currency.format(sats_to_fiat(sats, price))
+}
+
+/// Where bitcoin prices are fetched from
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "url", rename_all = "snake_case")]
+pub enum PriceSource {
+    /// The public coingecko api
+    #[default]
+    Coingecko,
+    /// A coingecko compatible https api, like `https://api.coingecko.com/api/v3`
+    Custom(String),
+    /// Prices are never fetched, so no price api learns about us.
+    /// Only prices given with [crate::nodemanager::NodeManager::set_bitcoin_price] are used.
+    Disabled,
+}
+
+impl PriceSource {
+    fn api_url(&self) -> Option<&str> {
+        match self {
+            PriceSource::Coingecko => Some(COINGECKO_API_URL),
+            PriceSource::Custom(url) => Some(url.trim_end_matches('/')),
+            PriceSource::Disabled => None,
+        }
+    }
+
+    fn price_url(&self, code: &str) -> Option<String> {
+        Some(format!(
+            "{}/simple/price?ids=bitcoin&vs_currencies={code}",
+            self.api_url()?
+        ))
+    }
+
+    /// The url of the prices between two unix timestamps, in seconds
+    fn history_url(&self, currency: FiatCurrency, from: u64, to: u64) -> Option<String> {
+        Some(format!(
+            "{}/coins/bitcoin/market_chart/range?vs_currency={}&from={from}&to={to}",
+            self.api_url()?,
+            currency.code()
+        ))
+    }
+}
+
+/// How bitcoin prices are fetched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceConfig {
+    pub source: PriceSource,
+    /// How long a fetched price is used for before fetching a new one, in seconds,
+    /// at most [MAX_PRICE_CACHE_TTL_SECS]
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for PriceConfig {
+    fn default() -> Self {
+        Self {
+            source: PriceSource::default(),
+            cache_ttl_secs: DEFAULT_PRICE_CACHE_TTL_SECS,
+        }
+    }
+}
+
+impl PriceConfig {
+    pub(crate) fn validate(&self) -> Result<(), MutinyError> {
+        if self.cache_ttl_secs > MAX_PRICE_CACHE_TTL_SECS {
+            return Err(MutinyError::InvalidArgumentsError);
+        }
+
+        // prices are trusted for fiat invoices, so they can't come over plain http
+        if let PriceSource::Custom(url) = &self.source {
+            let url = reqwest::Url::parse(url).map_err(|_| MutinyError::InvalidArgumentsError)?;
+            if url.scheme() != "https" {
+                return Err(MutinyError::InvalidArgumentsError);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The price of bitcoin when a payment or transaction happened
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalPrice {
+    pub currency: FiatCurrency,
+    /// The price of one bitcoin
+    pub price: f32,
+    /// Unix timestamp, in seconds, of when it was the price
+    pub timestamp: u64,
+}
+
+// prices are never NaN, they come from a price api or are checked when given to us
+impl Eq for HistoricalPrice {}
+
+impl HistoricalPrice {
+    /// What an amount of sats was worth at this price
+    pub fn value_of(&self, sats: u64) -> FiatValue {
+        FiatValue {
+            currency: self.currency,
+            amount: sats_to_fiat(sats, self.price),
+        }
+    }
+}
+
+/// An amount in a fiat currency
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FiatValue {
+    pub currency: FiatCurrency,
+    pub amount: f64,
+}
+
+// values are never NaN, they are made from prices that aren't
+impl Eq for FiatValue {}
+
+impl FiatValue {
+    /// Formats the amount with its currency symbol, ie "$1,234.56"
+    pub fn format(&self) -> String {
+        self.currency.format(self.amount)
+    }
+}
+
+/// Finds the price closest to the given unix timestamp, in seconds, that is at most
+/// `max_distance` seconds away. The prices have to be sorted by timestamp.
+pub(crate) fn closest_price(
+    prices: &[(u64, f32)],
+    timestamp: u64,
+    max_distance: u64,
+) -> Option<(u64, f32)> {
+    let index = prices.partition_point(|(time, _)| *time < timestamp);
+    let before = index.checked_sub(1).and_then(|i| prices.get(i));
+    let after = prices.get(index);
+
+    [before, after]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(time, _)| time.abs_diff(timestamp))
+        .filter(|(time, _)| time.abs_diff(timestamp) <= max_distance)
+        .copied()
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The range of price history to fetch for activity between `from` and `to`, unix
+/// timestamps in seconds. It is widened by `margin` on both sides and then to whole
+/// UTC days, ending no later than `now`, so the price api only learns which days the
+/// wallet's first and last activity were on and not the exact times of payments.
+pub(crate) fn history_range(from: u64, to: u64, margin: u64, now: u64) -> (u64, u64) {
+    let from = from.saturating_sub(margin) / SECS_PER_DAY * SECS_PER_DAY;
+    let to = to
+        .saturating_add(margin)
+        .div_ceil(SECS_PER_DAY)
+        .saturating_mul(SECS_PER_DAY);
+    (from, to.min(now).max(from))
+}
+
+pub trait HistoricalPriceStorage {
+    /// Gets the recorded prices keyed by the id of the activity item they are for
+    fn get_historical_prices(&self) -> Result<HashMap<String, HistoricalPrice>, MutinyError>;
+    /// Gets the recorded price of a single activity item
+    fn get_historical_price(
+        &self,
+        id: impl AsRef<str>,
+    ) -> Result<Option<HistoricalPrice>, MutinyError>;
+    fn persist_historical_price(
+        &self,
+        id: impl AsRef<str>,
+        price: HistoricalPrice,
+    ) -> Result<(), MutinyError>;
+}
+
+pub(crate) const HISTORICAL_PRICE_PREFIX: &str = "historical_price/";
+
+impl<S: MutinyStorage> HistoricalPriceStorage for S {
+    fn get_historical_prices(&self) -> Result<HashMap<String, HistoricalPrice>, MutinyError> {
+        let all: HashMap<String, HistoricalPrice> = self.scan(HISTORICAL_PRICE_PREFIX, None)?;
+        // remove the prefix from the keys
+        Ok(all
+            .into_iter()
+            .map(|(key, price)| (key.replace(HISTORICAL_PRICE_PREFIX, ""), price))
+            .collect())
+    }
+
+    fn get_historical_price(
+        &self,
+        id: impl AsRef<str>,
+    ) -> Result<Option<HistoricalPrice>, MutinyError> {
+        self.get_data(format!("{HISTORICAL_PRICE_PREFIX}{}", id.as_ref()))
+    }
+
+    fn persist_historical_price(
+        &self,
+        id: impl AsRef<str>,
+        price: HistoricalPrice,
+    ) -> Result<(), MutinyError> {
+        self.set_data(format!("{HISTORICAL_PRICE_PREFIX}{}", id.as_ref()), price)
+    }
+}
+
+/// Fetches bitcoin prices from the [PriceSource] and keeps them for a few minutes,
+/// each currency separately. Prices are keyed by the lowercase currency code so any
+/// currency the source supports can be used, not just the [FiatCurrency] ones.
+pub(crate) struct BitcoinPriceCache {
+    http_client: Client,
+    config: Mutex<PriceConfig>,
+    prices: Mutex<HashMap<String, (f32, Duration)>>,
+    logger: Arc<MutinyLogger>,
+}
+
+impl BitcoinPriceCache {
+    pub fn new(http_client: Client, config: PriceConfig, logger: Arc<MutinyLogger>) -> Self {
+        Self {
+            http_client,
+            config: Mutex::new(config),
+            prices: Mutex::new(HashMap::new()),
+            logger,
+        }
+    }
+
+    /// Switches to the new config, forgetting the prices we got so far
+    /// so none from the old source are used.
+    pub async fn set_config(&self, config: PriceConfig) {
+        let mut prices = self.prices.lock().await;
+        *self.config.lock().await = config;
+        prices.clear();
+    }
+
+    /// Uses the given price until it expires, or for as long as there is
+    /// no newer one when fetching prices is disabled.
+    pub async fn set_price(&self, currency: FiatCurrency, price: f32) {
+        let mut prices = self.prices.lock().await;
+        prices.insert(currency.code().to_string(), (price, utils::now()));
+    }
+
+    /// Gets the current bitcoin price in the given currency. If the price api fails,
+    /// the last price we got is returned, even if it is old.
+    pub async fn get_price(&self, currency: FiatCurrency) -> Result<f32, MutinyError> {
+        self.get_price_in(currency.code()).await
+    }
+
+    /// Gets the current bitcoin price in any currency the price source supports,
+    /// by its lowercase code, ie "sek".
+    pub async fn get_price_in(&self, code: &str) -> Result<f32, MutinyError> {
+        let now = utils::now();
+        let config = self.config.lock().await.clone();
+
+        let mut prices = self.prices.lock().await;
+
+        let url = match config.source.price_url(code) {
+            Some(url) => url,
+            None => {
+                // fetching is disabled, use whatever price we were given
+                return prices
+                    .get(code)
+                    .map(|(price, _)| *price)
+                    .ok_or(MutinyError::BitcoinPriceError);
+            }
+        };
+
+        let (price, timestamp) = match prices.get(code) {
+            Some((price, timestamp))
+                if timestamp
+                    .checked_add(Duration::from_secs(config.cache_ttl_secs))
+                    .is_some_and(|expiry| expiry > now) =>
+            {
+                // Cache is not expired
+                (*price, *timestamp)
+            }
+            _ => {
+                // Cache is either expired or empty, fetch new price
+                match self.fetch_price(&url, code).await {
+                    Ok(new_price) => (new_price, now),
+                    Err(e) => {
+                        // If fetching price fails, return the cached price (if any)
+                        if let Some((price, timestamp)) = prices.get(code) {
+                            log_warn!(self.logger, "price api failed, returning cached price");
+                            (*price, *timestamp)
+                        } else {
+                            // If there is no cached price, return the error
+                            log_error!(self.logger, "no cached price and price api failed");
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        };
+
+        prices.insert(code.to_string(), (price, timestamp));
+        Ok(price)
+    }
+
+    /// Gets the bitcoin prices between two unix timestamps, in seconds, sorted by time.
+    /// The further back the range goes the fewer prices there are, down to one a day.
+    pub async fn get_price_history(
+        &self,
+        currency: FiatCurrency,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, f32)>, MutinyError> {
+        let config = self.config.lock().await.clone();
+        let url = config
+            .source
+            .history_url(currency, from, to)
+            .ok_or(MutinyError::BitcoinPriceError)?;
+
+        log_debug!(self.logger, "fetching bitcoin price history in {currency}");
+
+        let response: CoingeckoHistoryResponse = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::BitcoinPriceError)?
+            .error_for_status()
+            .map_err(|_| MutinyError::BitcoinPriceError)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::BitcoinPriceError)?;
+
+        // timestamps are in milliseconds
+        let mut prices: Vec<(u64, f32)> = response
+            .prices
+            .into_iter()
+            .filter(|(_, price)| price.is_finite() && *price > 0.0)
+            .map(|(time, price)| ((time / 1_000.0) as u64, price as f32))
+            .collect();
+        prices.sort_by_key(|(time, _)| *time);
+
+        Ok(prices)
+    }
+
+    async fn fetch_price(&self, url: &str, code: &str) -> Result<f32, MutinyError> {
+        log_debug!(self.logger, "fetching new bitcoin price in {code}");
+
+        let resp = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|_| MutinyError::BitcoinPriceError)?;
+
+        let response: CoingeckoResponse = resp
+            .error_for_status()
+            .map_err(|_| MutinyError::BitcoinPriceError)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::BitcoinPriceError)?;
+
+        response
+            .bitcoin
+            .get(code)
+            .copied()
+            .ok_or(MutinyError::BitcoinPriceError)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct CoingeckoResponse {
+    /// The bitcoin price keyed by lowercase currency code
+    pub bitcoin: HashMap<String, f32>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct CoingeckoHistoryResponse {
+    /// Pairs of a unix timestamp in milliseconds and the price at that time
+    pub prices: Vec<(f64, f64)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_fiat_currency() {
+        let test_name = "test_fiat_currency";
+        log!("{}", test_name);
+
+        assert_eq!(FiatCurrency::from_str("EUR").unwrap(), FiatCurrency::Eur);
+        assert_eq!(FiatCurrency::from_str(" gbp ").unwrap(), FiatCurrency::Gbp);
+        assert!(FiatCurrency::from_str("xyz").is_err());
+        assert_eq!(FiatCurrency::default(), FiatCurrency::Usd);
+        assert_eq!(FiatCurrency::Jpy.to_string(), "JPY");
+
+        for currency in FiatCurrency::ALL {
+            assert_eq!(FiatCurrency::from_str(currency.code()).unwrap(), currency);
+            let json = serde_json::to_string(&currency).unwrap();
+            assert_eq!(json, format!("\"{}\"", currency.code()));
+        }
+    }
+
+    #[test]
+    fn test_format_fiat() {
+        let test_name = "test_format_fiat";
+        log!("{}", test_name);
+
+        assert_eq!(FiatCurrency::Usd.format(1234.567), "$1,234.57");
+        assert_eq!(FiatCurrency::Usd.format(0.5), "$0.50");
+        assert_eq!(FiatCurrency::Usd.format(-12.0), "-$12.00");
+        assert_eq!(FiatCurrency::Eur.format(999.0), "€999.00");
+        assert_eq!(FiatCurrency::Gbp.format(1_000_000.0), "£1,000,000.00");
+        assert_eq!(FiatCurrency::Jpy.format(4_321_987.6), "¥4,321,988");
+        assert_eq!(FiatCurrency::Chf.format(25.0), "CHF 25.00");
+
+        // 40,000 sats at $25,000 per bitcoin is $10
+        assert_eq!(sats_to_fiat(40_000, 25_000.0), 10.0);
+        assert_eq!(
+            format_sats_as_fiat(40_000, 25_000.0, FiatCurrency::Usd),
+            "$10.00"
+        );
+    }
+
+    #[test]
+    fn test_price_source() {
+        let test_name = "test_price_source";
+        log!("{}", test_name);
+
+        assert_eq!(
+            PriceSource::Coingecko.price_url(FiatCurrency::Eur).unwrap(),
+            "https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies=eur"
+        );
+        let custom = PriceSource::Custom("https://prices.example.com/api/".to_string());
+        assert_eq!(
+            custom.price_url(FiatCurrency::Usd).unwrap(),
+            "https://prices.example.com/api/simple/price?ids=bitcoin&vs_currencies=usd"
+        );
+        assert_eq!(PriceSource::Disabled.price_url(FiatCurrency::Usd), None);
+
+        let config = PriceConfig {
+            source: custom,
+            cache_ttl_secs: 60,
+        };
+        assert!(config.validate().is_ok());
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":{"type":"custom","url":"https://prices.example.com/api/"},"cache_ttl_secs":60}"#
+        );
+        assert_eq!(serde_json::from_str::<PriceConfig>(&json).unwrap(), config);
+
+        let bad = PriceConfig {
+            source: PriceSource::Custom("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(bad.validate().is_err());
+
+        let http = PriceConfig {
+            source: PriceSource::Custom("http://prices.example.com/api".to_string()),
+            ..Default::default()
+        };
+        assert!(http.validate().is_err());
+
+        let long_ttl = PriceConfig {
+            cache_ttl_secs: MAX_PRICE_CACHE_TTL_SECS + 1,
+            ..Default::default()
+        };
+        assert!(long_ttl.validate().is_err());
+    }
+
+    #[test]
+    fn test_historical_prices() {
+        let test_name = "test_historical_prices";
+        log!("{}", test_name);
+
+        let price = HistoricalPrice {
+            currency: FiatCurrency::Eur,
+            price: 25_000.0,
+            timestamp: 1_000,
+        };
+        let value = price.value_of(40_000);
+        assert_eq!(value.amount, 10.0);
+        assert_eq!(value.format(), "€10.00");
+
+        let storage = MemoryStorage::default();
+        assert!(storage.get_historical_prices().unwrap().is_empty());
+        storage.persist_historical_price("abc", price).unwrap();
+        let prices = storage.get_historical_prices().unwrap();
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices.get("abc"), Some(&price));
+        assert_eq!(storage.get_historical_price("abc").unwrap(), Some(price));
+        assert_eq!(storage.get_historical_price("def").unwrap(), None);
+
+        assert_eq!(
+            PriceSource::Coingecko
+                .history_url(FiatCurrency::Usd, 100, 200)
+                .unwrap(),
+            "https://api.coingecko.com/api/v3/coins/bitcoin/market_chart/range?vs_currency=usd&from=100&to=200"
+        );
+        assert_eq!(
+            PriceSource::Disabled.history_url(FiatCurrency::Usd, 100, 200),
+            None
+        );
+
+        let history = vec![(100, 1.0), (200, 2.0), (300, 3.0)];
+        assert_eq!(closest_price(&history, 90, 50), Some((100, 1.0)));
+        assert_eq!(closest_price(&history, 240, 50), Some((200, 2.0)));
+        assert_eq!(closest_price(&history, 260, 50), Some((300, 3.0)));
+        assert_eq!(closest_price(&history, 400, 50), None);
+        assert_eq!(closest_price(&[], 100, 50), None);
+
+        let day = SECS_PER_DAY;
+        assert_eq!(
+            history_range(3 * day + 500, 5 * day + 10, 100, 10 * day),
+            (3 * day, 6 * day)
+        );
+        assert_eq!(
+            history_range(3 * day + 50, 5 * day, 100, 10 * day),
+            (2 * day, 6 * day)
+        );
+        assert_eq!(
+            history_range(3 * day, 5 * day, 100, 5 * day + 200),
+            (2 * day, 5 * day + 200)
+        );
+    }
+
+    #[test]
+    async fn test_disabled_price_source() {
+        let test_name = "test_disabled_price_source";
+        log!("{}", test_name);
+
+        let config = PriceConfig {
+            source: PriceSource::Disabled,
+            ..Default::default()
+        };
+        let cache =
+            BitcoinPriceCache::new(Client::new(), config, Arc::new(MutinyLogger::default()));
+
+        // nothing is fetched, so there is no price until one is given
+        assert!(cache.get_price(FiatCurrency::Usd).await.is_err());
+
+        cache.set_price(FiatCurrency::Usd, 30_000.0).await;
+        assert_eq!(cache.get_price(FiatCurrency::Usd).await.unwrap(), 30_000.0);
+        assert!(cache.get_price(FiatCurrency::Eur).await.is_err());
+        // prices are kept by their code, so any currency can be looked up
+        assert_eq!(cache.get_price_in("usd").await.unwrap(), 30_000.0);
+        assert!(cache.get_price_in("sek").await.is_err());
+
+        // changing the config forgets the prices from before
+        cache
+            .set_config(PriceConfig {
+                source: PriceSource::Disabled,
+                cache_ttl_secs: 60,
+            })
+            .await;
+        assert!(cache.get_price(FiatCurrency::Usd).await.is_err());
+    }
+}
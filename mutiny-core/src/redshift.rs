@@ -1,5 +1,6 @@
 use crate::error::MutinyError;
-use crate::nodemanager::NodeManager;
+use crate::node::DEFAULT_INVOICE_EXPIRY_SECS;
+use crate::nodemanager::{MaxFee, NodeManager, PaymentOptions};
 use crate::storage::MutinyStorage;
 use crate::utils;
 use crate::utils::sleep;
@@ -12,10 +13,16 @@ use lightning::{log_warn, util::logger::Logger};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 
 // When creating a new node sleep for 5 seconds to give it time to start up.
 const NEW_NODE_SLEEP_DURATION: i32 = 5_000;
 
+/// Held while a redshift is read, changed and saved. Redshifts are updated by the
+/// payment loop, the event handler and cancellations, so this keeps them from
+/// overwriting each other's changes.
+static REDSHIFT_LOCK: Mutex<()> = Mutex::new(());
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RedshiftStatus {
     /// The channel to the introduction node is opening.
@@ -35,6 +42,9 @@ pub enum RedshiftStatus {
     Completed,
     /// The redshift failed. The error is given.
     Failed(String),
+    /// The redshift was cancelled by the user, payments stopped
+    /// and the channels it opened were closed.
+    Cancelled,
 }
 
 impl RedshiftStatus {
@@ -47,6 +57,7 @@ impl RedshiftStatus {
             RedshiftStatus::ClosingChannels => true,
             RedshiftStatus::Completed => false,
             RedshiftStatus::Failed(_) => false,
+            RedshiftStatus::Cancelled => false,
         }
     }
 }
@@ -64,6 +75,44 @@ pub enum RedshiftRecipient {
     OnChain(Option<Address>),
 }
 
+/// Limits on what a redshift may spend and how fast it retries failed payments.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedshiftOptions {
+    /// The most the routing fees of all its payments may add up to,
+    /// the percent is of the redshifted amount. The channel opening fee is not included.
+    pub fee_budget: Option<MaxFee>,
+    /// Seconds to wait after a failed payment attempt before trying again
+    pub retry_interval_secs: u64,
+    /// Each failed attempt in a row doubles the wait, up to this many seconds
+    pub max_retry_interval_secs: u64,
+    /// Gives up after this many payment attempts
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RedshiftOptions {
+    fn default() -> Self {
+        Self {
+            fee_budget: None,
+            retry_interval_secs: 10,
+            max_retry_interval_secs: 300,
+            max_attempts: None,
+        }
+    }
+}
+
+impl RedshiftOptions {
+    /// Seconds to wait before the next attempt after this many failures in a row
+    pub fn retry_delay_secs(&self, consecutive_failures: u32) -> u64 {
+        if consecutive_failures == 0 {
+            return 0;
+        }
+        let multiplier = 2u64.saturating_pow(consecutive_failures - 1);
+        self.retry_interval_secs
+            .saturating_mul(multiplier)
+            .min(self.max_retry_interval_secs.max(self.retry_interval_secs))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Redshift {
     pub id: [u8; 16],
@@ -84,17 +133,92 @@ pub struct Redshift {
     pub sats_sent: u64,
     pub change_amt: Option<u64>,
     pub fees_paid: u64,
+    /// Number of payments attempted, successful or not
+    #[serde(default)]
+    pub attempts: u32,
+    /// When we started attempting payments, in seconds since the epoch
+    #[serde(default)]
+    pub payments_started_at: Option<u64>,
+    /// The routing fees of its payments, these are included in fees_paid too
+    #[serde(default)]
+    pub routing_fees_paid: u64,
+    #[serde(default)]
+    pub options: RedshiftOptions,
+}
+
+/// How far along a redshift is, from [RedshiftManager::get_redshift_status].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedshiftProgress {
+    pub status: RedshiftStatus,
+    pub amount_sats: u64,
+    /// Sats moved to the receiving node so far
+    pub sats_sent: u64,
+    /// Sats left to move, not counting the channel reserve we can't send
+    pub remaining_sats: u64,
+    pub fees_paid: u64,
+    pub attempts: u32,
+    /// Estimated seconds until all sats are moved, based on the rate so far.
+    /// None until the first payment succeeds or once the redshift is done.
+    pub eta_secs: Option<u64>,
 }
 
 impl Redshift {
     pub fn channel_opened(&mut self, chan_id: OutPoint) {
         self.introduction_channel = Some(chan_id);
-        self.status = RedshiftStatus::ChannelOpened;
+        // a redshift cancelled while its channel was opening stays cancelled
+        if self.status == RedshiftStatus::ChannelOpening {
+            self.status = RedshiftStatus::ChannelOpened;
+        }
+    }
+
+    pub fn payment_attempted(&mut self) {
+        self.attempts += 1;
     }
 
     pub fn payment_successful(&mut self, amount: u64, fees_paid: u64) {
         self.sats_sent += amount;
         self.fees_paid += fees_paid;
+        self.routing_fees_paid += fees_paid;
+    }
+
+    /// The routing fees left in its budget, None if it has no budget
+    pub fn remaining_fee_budget(&self) -> Option<u64> {
+        self.options.fee_budget.map(|budget| {
+            let limit = budget.limit_msat(self.amount_sats * 1_000) / 1_000;
+            limit.saturating_sub(self.routing_fees_paid)
+        })
+    }
+
+    /// If it used up its attempts
+    pub fn attempts_exhausted(&self) -> bool {
+        self.options
+            .max_attempts
+            .map_or(false, |max| self.attempts >= max)
+    }
+
+    pub fn progress(&self, now: u64) -> RedshiftProgress {
+        let remaining_sats = self
+            .amount_sats
+            .saturating_sub(self.fees_paid)
+            .saturating_sub(self.sats_sent);
+
+        let eta_secs = match self.payments_started_at {
+            Some(started) if self.status.is_in_progress() && self.sats_sent > 0 => {
+                let elapsed = now.saturating_sub(started).max(1);
+                Some(remaining_sats * elapsed / self.sats_sent)
+            }
+            _ => None,
+        };
+
+        RedshiftProgress {
+            status: self.status.clone(),
+            amount_sats: self.amount_sats,
+            sats_sent: self.sats_sent,
+            remaining_sats,
+            fees_paid: self.fees_paid,
+            attempts: self.attempts,
+            eta_secs,
+        }
     }
 
     pub fn fail(&mut self, error: String) {
@@ -106,6 +230,13 @@ pub trait RedshiftStorage {
     fn get_redshift(&self, utxo: &[u8; 16]) -> Result<Option<Redshift>, MutinyError>;
     fn get_redshifts(&self) -> Result<Vec<Redshift>, MutinyError>;
     fn persist_redshift(&self, redshift: Redshift) -> Result<(), MutinyError>;
+    /// Reads, changes and saves a redshift in one step, returning the saved redshift.
+    /// A cancelled redshift stays cancelled whatever the change does.
+    fn update_redshift(
+        &self,
+        id: &[u8; 16],
+        update: impl FnOnce(&mut Redshift),
+    ) -> Result<Redshift, MutinyError>;
 }
 
 const REDSHIFT_KEY_PREFIX: &str = "redshift/";
@@ -128,6 +259,26 @@ impl<S: MutinyStorage> RedshiftStorage for S {
     fn persist_redshift(&self, redshift: Redshift) -> Result<(), MutinyError> {
         self.set_data(get_redshift_key(&redshift.id), redshift)
     }
+
+    fn update_redshift(
+        &self,
+        id: &[u8; 16],
+        update: impl FnOnce(&mut Redshift),
+    ) -> Result<Redshift, MutinyError> {
+        let _guard = REDSHIFT_LOCK
+            .lock()
+            .map_err(|_| MutinyError::Other(anyhow!("redshift lock poisoned")))?;
+        let mut redshift = self.get_redshift(id)?.ok_or(MutinyError::NotFound)?;
+        let cancelled = redshift.status == RedshiftStatus::Cancelled;
+
+        update(&mut redshift);
+        if cancelled {
+            redshift.status = RedshiftStatus::Cancelled;
+        }
+
+        self.persist_redshift(redshift.clone())?;
+        Ok(redshift)
+    }
 }
 
 pub trait RedshiftManager {
@@ -139,10 +290,19 @@ pub trait RedshiftManager {
         recipient: RedshiftRecipient,
         introduction_node: Option<PublicKey>,
         connection_string: Option<&str>,
+        options: RedshiftOptions,
     ) -> Result<Redshift, MutinyError>;
 
     fn get_redshift(&self, id: &[u8; 16]) -> Result<Option<Redshift>, MutinyError>;
 
+    /// Gets how far along a redshift is
+    fn get_redshift_status(&self, id: &[u8; 16]) -> Result<RedshiftProgress, MutinyError>;
+
+    /// Cancels a redshift that is in progress. Payment attempts stop and the
+    /// channels it opened are closed back on-chain, sats already moved stay moved.
+    /// A channel that is still opening is closed once it is ready.
+    async fn cancel_redshift(&self, id: &[u8; 16]) -> Result<Redshift, MutinyError>;
+
     async fn attempt_payments(&self, rs: Redshift) -> Result<(), MutinyError>;
 
     async fn close_channels(&self, rs: Redshift) -> Result<(), MutinyError>;
@@ -155,6 +315,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
         recipient: RedshiftRecipient,
         introduction_node: Option<PublicKey>,
         connection_string: Option<&str>,
+        options: RedshiftOptions,
     ) -> Result<Redshift, MutinyError> {
         // verify utxo exists
         let utxos = self.list_utxos()?;
@@ -190,7 +351,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
             _ => {
                 // TODO this would be better if it was a random node
                 let node = self.get_node(&node.pubkey).await?;
-                match &node.lsp_client {
+                match node.lsp_client() {
                     Some(lsp) => lsp.pubkey,
                     None => return Err(MutinyError::LspFailure),
                 }
@@ -232,6 +393,10 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
             sats_sent: 0,
             change_amt: None,
             fees_paid: fees,
+            attempts: 0,
+            payments_started_at: None,
+            routing_fees_paid: 0,
+            options,
         };
         self.storage.persist_redshift(redshift.clone())?;
 
@@ -242,6 +407,42 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
         self.storage.get_redshift(id)
     }
 
+    fn get_redshift_status(&self, id: &[u8; 16]) -> Result<RedshiftProgress, MutinyError> {
+        let rs = self
+            .storage
+            .get_redshift(id)?
+            .ok_or(MutinyError::NotFound)?;
+        Ok(rs.progress(utils::now().as_secs()))
+    }
+
+    async fn cancel_redshift(&self, id: &[u8; 16]) -> Result<Redshift, MutinyError> {
+        let mut previous = None;
+        let rs = self.storage.update_redshift(id, |rs| {
+            previous = Some(rs.status.clone());
+            if matches!(
+                rs.status,
+                RedshiftStatus::ChannelOpening
+                    | RedshiftStatus::ChannelOpened
+                    | RedshiftStatus::AttemptingPayments
+            ) {
+                rs.status = RedshiftStatus::Cancelled;
+            }
+        })?;
+
+        match previous {
+            // the event handler closes the channel once it is ready
+            Some(RedshiftStatus::ChannelOpening) => {}
+            Some(RedshiftStatus::ChannelOpened) => self.close_channels(rs.clone()).await?,
+            // the payment loop closes the channels once it sees the cancellation
+            Some(RedshiftStatus::AttemptingPayments) => {}
+            // channels are already closing or the redshift is done
+            _ => return Err(MutinyError::InvalidArgumentsError),
+        }
+        log_info!(&self.logger, "Cancelled redshift {}", rs.id.to_hex());
+
+        Ok(rs)
+    }
+
     async fn attempt_payments(&self, mut rs: Redshift) -> Result<(), MutinyError> {
         log_info!(
             &self.logger,
@@ -289,30 +490,44 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
         };
         // save receiving node to db
         rs.receiving_node = Some(receiving_node.pubkey);
-        self.storage.persist_redshift(rs.clone())?;
+        if rs.payments_started_at.is_none() {
+            rs.payments_started_at = Some(utils::now().as_secs());
+        }
+        self.save_redshift(&mut rs)?;
 
         // attempt payments in loop until we sent all or hit min sats
         let mut local_max_sats = max_sats;
         let get_invoice_failures = 0;
+        let mut consecutive_failures = 0;
         loop {
             // stop looping if ordered to stop
             if self.stop.load(Ordering::Relaxed) {
                 break;
             }
 
+            if rs.status == RedshiftStatus::Cancelled || self.redshift_cancelled(&rs.id) {
+                log_info!(&self.logger, "Redshift {} was cancelled", rs.id.to_hex());
+                rs.status = RedshiftStatus::Cancelled;
+                break;
+            }
+
             log_debug!(
                 &self.logger,
                 "Looping through payments for redshift {}: sats={}",
                 rs.id.to_hex(),
                 local_max_sats,
             );
-            // keep trying until the amount is too small to send through
-            if local_max_sats < min_sats {
+            // keep trying until the amount is too small to send through,
+            // or we are out of attempts or fee budget
+            let fee_budget = rs.remaining_fee_budget();
+            if local_max_sats < min_sats || rs.attempts_exhausted() || fee_budget == Some(0) {
                 log_debug!(
                     &self.logger,
-                    "Local max amount is less than min for redshift {}: sats={}",
+                    "Stopping payments for redshift {}: sats={}, attempts={}, fee budget left={:?}",
                     rs.id.to_hex(),
                     local_max_sats,
+                    rs.attempts,
+                    fee_budget,
                 );
                 // if no payments were made, consider it a fail
                 if rs.sats_sent == 0 {
@@ -337,10 +552,16 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
 
             // get an invoice from the receiving node
             let invoice = match receiving_node
-                .create_invoice(Some(local_max_sats), vec!["Redshift".to_string()], None)
+                .create_invoice(
+                    Some(local_max_sats),
+                    vec!["Redshift".to_string()],
+                    None,
+                    None,
+                    DEFAULT_INVOICE_EXPIRY_SECS,
+                )
                 .await
             {
-                Ok(i) => i,
+                Ok((i, _, _)) => i,
                 Err(_) => {
                     if get_invoice_failures > 3 {
                         break;
@@ -362,9 +583,24 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
             );
 
             let label = format!("Redshift: {}", rs.id.to_hex());
+            rs.payment_attempted();
+            // the fees of this payment can use up whatever is left of the budget
+            let max_fee = fee_budget.map(|sats| MaxFee {
+                max_fee_sats: Some(sats),
+                max_fee_percent: None,
+            });
             // make attempts to pay it
             match sending_node
-                .pay_invoice_with_timeout(&invoice, None, None, vec![label])
+                .pay_invoice_with_timeout(
+                    &invoice,
+                    None,
+                    PaymentOptions {
+                        max_fee,
+                        ..Default::default()
+                    },
+                    None,
+                    vec![label],
+                )
                 .await
             {
                 Ok(i) => {
@@ -376,6 +612,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
                         );
                         // update the redshift with the payment
                         rs.payment_successful(amount_sent, i.fees_paid.unwrap_or(0));
+                        consecutive_failures = 0;
 
                         // check if the max amount was sent on all tries
                         if rs.sats_sent >= max_sats {
@@ -385,7 +622,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
 
                         // save to db, to update the frontend
                         // do it after the if statement so we don't save the redshift twice
-                        self.storage.persist_redshift(rs.clone())?;
+                        self.save_redshift(&mut rs)?;
 
                         // keep trying with the remaining amount
                         local_max_sats = max_sats.saturating_sub(rs.sats_sent);
@@ -399,10 +636,19 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
                     // Keep trying to pay but go down 5% of the channel amount
                     let decrement = (max_sats as f64 * 0.05) as u64;
                     local_max_sats = local_max_sats.saturating_sub(decrement);
+
+                    // save the attempt and wait before trying again
+                    self.save_redshift(&mut rs)?;
+                    consecutive_failures += 1;
+                    let delay_secs = rs.options.retry_delay_secs(consecutive_failures);
+                    sleep((delay_secs * 1_000).min(i32::MAX as u64) as i32).await;
                 }
             }
         }
 
+        // save to db
+        self.save_redshift(&mut rs)?;
+
         log_debug!(
             &self.logger,
             "Redshift {} completed with status: {:?}",
@@ -410,9 +656,6 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
             rs.status
         );
 
-        // save to db
-        self.storage.persist_redshift(rs.clone())?;
-
         // begin closing channels
         self.close_channels(rs).await?;
 
@@ -423,7 +666,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
         // close introduction channel
         match rs.introduction_channel.as_ref() {
             Some(chan) => {
-                self.close_channel(chan).await?
+                self.close_channel(chan, None).await?
                 // todo need to set change amount to on the amount we get back
             }
             None => log_debug!(&self.logger, "no introduction channel to close"),
@@ -434,6 +677,8 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
             RedshiftRecipient::Lightning(_) => {} // Keep channel open in lightning case
             RedshiftRecipient::OnChain(_addr) => {
                 let receiving_node = match &rs.receiving_node {
+                    // cancelled before payments started, there is no receiving node yet
+                    None if rs.status == RedshiftStatus::Cancelled => return Ok(()),
                     None => {
                         log_error!(
                             &self.logger,
@@ -454,7 +699,7 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
                 for c in receiving_node.channel_manager.list_channels() {
                     if let Some(funding_txo) = c.funding_txo {
                         let channel_outpoint = funding_txo.into_bitcoin_outpoint();
-                        self.close_channel(&channel_outpoint).await?;
+                        self.close_channel(&channel_outpoint, None).await?;
                         channel_outpoints.push(channel_outpoint);
                     }
                 }
@@ -473,14 +718,37 @@ impl<S: MutinyStorage> RedshiftManager for NodeManager<S> {
 
         // TODO archive nodes afterwards
 
+        // save to db, a cancelled redshift stays cancelled
         rs.status = RedshiftStatus::Completed;
-        // save to db
-        self.storage.persist_redshift(rs)?;
+        self.save_redshift(&mut rs)?;
 
         Ok(())
     }
 }
 
+impl<S: MutinyStorage> NodeManager<S> {
+    /// Saves our copy of the redshift without overwriting a cancellation,
+    /// the copy's status is updated if it was cancelled
+    fn save_redshift(&self, rs: &mut Redshift) -> Result<(), MutinyError> {
+        let saved = self
+            .storage
+            .update_redshift(&rs.id, |saved| *saved = rs.clone())?;
+        rs.status = saved.status;
+        Ok(())
+    }
+
+    /// If the redshift was cancelled since we last read it
+    fn redshift_cancelled(&self, id: &[u8; 16]) -> bool {
+        matches!(
+            self.storage.get_redshift(id),
+            Ok(Some(Redshift {
+                status: RedshiftStatus::Cancelled,
+                ..
+            }))
+        )
+    }
+}
+
 // TODO add more redshift tests
 #[cfg(test)]
 mod test {
@@ -515,6 +783,10 @@ mod test {
             sats_sent: 0,
             change_amt: None,
             fees_paid: 123,
+            attempts: 0,
+            payments_started_at: None,
+            routing_fees_paid: 0,
+            options: RedshiftOptions::default(),
         }
     }
 
@@ -536,4 +808,109 @@ mod test {
         let all = storage.get_redshifts().unwrap();
         assert_eq!(all, vec![rs]);
     }
+
+    #[test]
+    fn test_redshift_progress() {
+        let test_name = "test_redshift_progress";
+        log!("{}", test_name);
+
+        let mut rs = dummy_redshift();
+        let progress = rs.progress(1_000);
+        assert_eq!(progress.remaining_sats, 69_420 - 123);
+        assert_eq!(progress.eta_secs, None);
+
+        rs.status = RedshiftStatus::AttemptingPayments;
+        rs.payments_started_at = Some(1_000);
+        rs.payment_attempted();
+        rs.payment_attempted();
+        rs.payment_successful(30_000, 100);
+
+        // 30k sats moved in 60 seconds, so the rest takes about as long
+        let progress = rs.progress(1_060);
+        assert_eq!(progress.attempts, 2);
+        assert_eq!(progress.sats_sent, 30_000);
+        assert_eq!(progress.fees_paid, 223);
+        assert_eq!(progress.remaining_sats, 69_420 - 223 - 30_000);
+        assert_eq!(progress.eta_secs, Some(78));
+
+        rs.status = RedshiftStatus::Cancelled;
+        assert_eq!(rs.progress(1_060).eta_secs, None);
+    }
+
+    #[test]
+    fn test_cancelled_redshift_stays_cancelled() {
+        let test_name = "test_cancelled_redshift_stays_cancelled";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        let rs = dummy_redshift();
+        storage.persist_redshift(rs.clone()).unwrap();
+
+        // an update from a copy read before the cancellation
+        let mut stale = rs.clone();
+        stale.status = RedshiftStatus::AttemptingPayments;
+        stale.sats_sent = 1_000;
+
+        let cancelled = storage
+            .update_redshift(&rs.id, |r| r.status = RedshiftStatus::Cancelled)
+            .unwrap();
+        assert_eq!(cancelled.status, RedshiftStatus::Cancelled);
+
+        let saved = storage
+            .update_redshift(&rs.id, |r| *r = stale.clone())
+            .unwrap();
+        assert_eq!(saved.status, RedshiftStatus::Cancelled);
+        assert_eq!(saved.sats_sent, 1_000);
+
+        // the channel becoming ready doesn't resume it either
+        let saved = storage
+            .update_redshift(&rs.id, |r| r.channel_opened(OutPoint::default()))
+            .unwrap();
+        assert_eq!(saved.status, RedshiftStatus::Cancelled);
+        assert_eq!(saved.introduction_channel, Some(OutPoint::default()));
+        assert!(!saved.status.is_in_progress());
+        assert_eq!(storage.get_redshift(&rs.id).unwrap(), Some(saved));
+
+        assert!(matches!(
+            storage.update_redshift(&[1; 16], |_| {}),
+            Err(MutinyError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_redshift_options() {
+        let test_name = "test_redshift_options";
+        log!("{}", test_name);
+
+        let options = RedshiftOptions::default();
+        assert_eq!(options.retry_delay_secs(0), 0);
+        assert_eq!(options.retry_delay_secs(1), 10);
+        assert_eq!(options.retry_delay_secs(3), 40);
+        assert_eq!(options.retry_delay_secs(10), 300);
+        assert_eq!(options.retry_delay_secs(100), 300);
+
+        let mut rs = dummy_redshift();
+        assert_eq!(rs.remaining_fee_budget(), None);
+        assert!(!rs.attempts_exhausted());
+
+        // 1% of 69,420 sats is lower than the 1,000 sat limit
+        rs.options.fee_budget = Some(MaxFee {
+            max_fee_sats: Some(1_000),
+            max_fee_percent: Some(1.0),
+        });
+        rs.options.max_attempts = Some(2);
+        assert_eq!(rs.remaining_fee_budget(), Some(694));
+
+        // the opening fee doesn't count against the budget
+        rs.payment_attempted();
+        rs.payment_successful(30_000, 600);
+        assert_eq!(rs.fees_paid, 723);
+        assert_eq!(rs.remaining_fee_budget(), Some(94));
+
+        rs.payment_successful(30_000, 600);
+        assert_eq!(rs.remaining_fee_budget(), Some(0));
+
+        rs.payment_attempted();
+        assert!(rs.attempts_exhausted());
+    }
 }
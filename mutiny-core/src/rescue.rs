@@ -0,0 +1,421 @@
+use crate::error::MutinyError;
+use crate::labels::LabelStorage;
+use crate::logging::MutinyLogger;
+use crate::onchain::OnChainWallet;
+use crate::storage::{MemoryStorage, MutinyStorage, OnChainStorage};
+use bdk::template::DescriptorTemplateOut;
+use bdk::wallet::AddressIndex;
+use bdk::{FeeRate, SignOptions, Wallet};
+use bdk_esplora::EsploraAsyncExt;
+use bip39::Mnemonic;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::{Network, PrivateKey, Txid};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::util::logger::Logger;
+use lightning::{log_debug, log_info, log_warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The script type used by a wallet for a given derivation path
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RescueScriptType {
+    /// Legacy P2PKH, used by BIP44 wallets
+    Pkh,
+    /// Nested segwit P2SH-P2WPKH, used by BIP49 wallets
+    ShWpkh,
+    /// Native segwit P2WPKH, used by BIP84 wallets
+    Wpkh,
+    /// Taproot P2TR, used by BIP86 wallets
+    Tr,
+}
+
+/// An account level derivation path to scan when rescuing funds from another wallet.
+/// Receive and change addresses are derived from `/0/*` and `/1/*` under this path.
+///
+/// Single private keys, like WIF keys, have no derivation so their path is just `m`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RescuePath {
+    pub script_type: RescueScriptType,
+    pub account_path: DerivationPath,
+}
+
+impl RescuePath {
+    pub fn new(script_type: RescueScriptType, account_path: &str) -> Result<Self, MutinyError> {
+        Ok(Self {
+            script_type,
+            account_path: DerivationPath::from_str(account_path)?,
+        })
+    }
+
+    fn descriptors(
+        &self,
+        xprivkey: ExtendedPrivKey,
+    ) -> Result<(DescriptorTemplateOut, DescriptorTemplateOut), MutinyError> {
+        let receive = self.account_path.extend([ChildNumber::Normal { index: 0 }]);
+        let change = self.account_path.extend([ChildNumber::Normal { index: 1 }]);
+
+        let descriptors = match self.script_type {
+            RescueScriptType::Pkh => (
+                bdk::descriptor!(pkh((xprivkey, receive)))?,
+                bdk::descriptor!(pkh((xprivkey, change)))?,
+            ),
+            RescueScriptType::ShWpkh => (
+                bdk::descriptor!(sh(wpkh((xprivkey, receive))))?,
+                bdk::descriptor!(sh(wpkh((xprivkey, change))))?,
+            ),
+            RescueScriptType::Wpkh => (
+                bdk::descriptor!(wpkh((xprivkey, receive)))?,
+                bdk::descriptor!(wpkh((xprivkey, change)))?,
+            ),
+            RescueScriptType::Tr => (
+                bdk::descriptor!(tr((xprivkey, receive)))?,
+                bdk::descriptor!(tr((xprivkey, change)))?,
+            ),
+        };
+
+        Ok(descriptors)
+    }
+}
+
+/// The derivation paths that are scanned by default when rescuing funds.
+/// This includes the standard BIP44/49/84/86 paths as well as some nonstandard
+/// ones that other wallets are known to use.
+pub fn default_rescue_paths(network: Network) -> Vec<RescuePath> {
+    let coin_type = match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    };
+
+    let mut paths = vec![];
+    // some wallets always use the mainnet coin type, even on test networks
+    let coin_types = if coin_type == 0 { vec![0] } else { vec![1, 0] };
+    for coin in coin_types {
+        paths.push((RescueScriptType::Pkh, format!("m/44'/{coin}'/0'")));
+        paths.push((RescueScriptType::ShWpkh, format!("m/49'/{coin}'/0'")));
+        paths.push((RescueScriptType::Wpkh, format!("m/84'/{coin}'/0'")));
+        paths.push((RescueScriptType::Tr, format!("m/86'/{coin}'/0'")));
+    }
+    // older wallets like breadwallet use m/0'
+    paths.push((RescueScriptType::Pkh, "m/0'".to_string()));
+    paths.push((RescueScriptType::Wpkh, "m/0'".to_string()));
+
+    paths
+        .into_iter()
+        .map(|(script_type, path)| {
+            RescuePath::new(script_type, &path).expect("default paths are valid")
+        })
+        .collect()
+}
+
+/// The outcome of scanning a single derivation path
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RescueResult {
+    pub path: RescuePath,
+    /// The balance found on this path in sats
+    pub balance: u64,
+    /// The sweep transaction, if funds were found and swept
+    pub txid: Option<Txid>,
+}
+
+/// The descriptors of each script type a single private key could have been used with.
+/// Uncompressed keys can only be used for legacy addresses.
+fn key_descriptors(
+    key: PrivateKey,
+) -> Result<Vec<(RescuePath, DescriptorTemplateOut)>, MutinyError> {
+    let mut descriptors = vec![(RescueScriptType::Pkh, bdk::descriptor!(pkh(key))?)];
+    if key.compressed {
+        descriptors.push((RescueScriptType::ShWpkh, bdk::descriptor!(sh(wpkh(key)))?));
+        descriptors.push((RescueScriptType::Wpkh, bdk::descriptor!(wpkh(key))?));
+        descriptors.push((RescueScriptType::Tr, bdk::descriptor!(tr(key))?));
+    }
+
+    descriptors
+        .into_iter()
+        .map(|(script_type, descriptor)| Ok((RescuePath::new(script_type, "m")?, descriptor)))
+        .collect()
+}
+
+/// Keys only know if they are for mainnet or not, so all test networks match each other
+fn check_key_network(key_network: Network, network: Network) -> Result<(), MutinyError> {
+    if (key_network == Network::Bitcoin) != (network == Network::Bitcoin) {
+        return Err(MutinyError::IncorrectNetwork(key_network));
+    }
+    Ok(())
+}
+
+fn sweep_fee_rate<S: MutinyStorage>(wallet: &OnChainWallet<S>, fee_rate: Option<f32>) -> FeeRate {
+    if let Some(rate) = fee_rate {
+        FeeRate::from_sat_per_vb(rate)
+    } else {
+        let sat_per_kwu = wallet
+            .fees
+            .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
+        FeeRate::from_sat_per_kwu(sat_per_kwu as f32)
+    }
+}
+
+/// Scans the given derivation paths of a mnemonic from another wallet and
+/// sweeps any funds found into our on-chain wallet.
+/// Only paths that had funds are returned.
+pub(crate) async fn sweep_external_mnemonic<S: MutinyStorage>(
+    wallet: &OnChainWallet<S>,
+    mnemonic: &Mnemonic,
+    passphrase: Option<String>,
+    paths: Vec<RescuePath>,
+    fee_rate: Option<f32>,
+    logger: &Arc<MutinyLogger>,
+) -> Result<Vec<RescueResult>, MutinyError> {
+    let seed = mnemonic.to_seed(passphrase.as_deref().unwrap_or(""));
+    let xprivkey = ExtendedPrivKey::new_master(wallet.network, &seed)?;
+    sweep_external_xprivkey(wallet, xprivkey, paths, fee_rate, logger).await
+}
+
+async fn sweep_external_xprivkey<S: MutinyStorage>(
+    wallet: &OnChainWallet<S>,
+    xprivkey: ExtendedPrivKey,
+    paths: Vec<RescuePath>,
+    fee_rate: Option<f32>,
+    logger: &Arc<MutinyLogger>,
+) -> Result<Vec<RescueResult>, MutinyError> {
+    let descriptors = paths
+        .into_iter()
+        .map(|path| {
+            let (receive, change) = path.descriptors(xprivkey)?;
+            Ok((path, receive, Some(change)))
+        })
+        .collect::<Result<Vec<_>, MutinyError>>()?;
+    sweep_descriptors(
+        wallet,
+        descriptors,
+        sweep_fee_rate(wallet, fee_rate),
+        logger,
+    )
+    .await
+}
+
+/// Sweeps any funds found on a key from another wallet into our on-chain wallet.
+/// The key can be a WIF private key, a master xprv or a mnemonic without a passphrase.
+///
+/// A WIF key is checked as every standard script type, the others are scanned
+/// on the [default_rescue_paths]. Only script types or paths that had funds are returned.
+pub(crate) async fn sweep_external_key<S: MutinyStorage>(
+    wallet: &OnChainWallet<S>,
+    wif_or_seed: &str,
+    fee_rate: Option<f32>,
+    logger: &Arc<MutinyLogger>,
+) -> Result<Vec<RescueResult>, MutinyError> {
+    let wif_or_seed = wif_or_seed.trim();
+
+    if let Ok(key) = PrivateKey::from_wif(wif_or_seed) {
+        check_key_network(key.network, wallet.network)?;
+        let descriptors = key_descriptors(key)?
+            .into_iter()
+            .map(|(path, descriptor)| (path, descriptor, None))
+            .collect();
+        sweep_descriptors(
+            wallet,
+            descriptors,
+            sweep_fee_rate(wallet, fee_rate),
+            logger,
+        )
+        .await
+    } else if let Ok(xprivkey) = ExtendedPrivKey::from_str(wif_or_seed) {
+        check_key_network(xprivkey.network, wallet.network)?;
+        let paths = default_rescue_paths(wallet.network);
+        sweep_external_xprivkey(wallet, xprivkey, paths, fee_rate, logger).await
+    } else if let Ok(mnemonic) = Mnemonic::from_str(wif_or_seed) {
+        let paths = default_rescue_paths(wallet.network);
+        sweep_external_mnemonic(wallet, &mnemonic, None, paths, fee_rate, logger).await
+    } else {
+        Err(MutinyError::InvalidArgumentsError)
+    }
+}
+
+async fn sweep_descriptors<S: MutinyStorage>(
+    wallet: &OnChainWallet<S>,
+    descriptors: Vec<(
+        RescuePath,
+        DescriptorTemplateOut,
+        Option<DescriptorTemplateOut>,
+    )>,
+    fee_rate: FeeRate,
+    logger: &Arc<MutinyLogger>,
+) -> Result<Vec<RescueResult>, MutinyError> {
+    let mut results = vec![];
+    for (path, receive, change) in descriptors {
+        let mut rescue_wallet = Wallet::new(
+            receive,
+            change,
+            OnChainStorage::new(MemoryStorage::default()),
+            wallet.network,
+        )?;
+
+        let checkpoints = rescue_wallet.checkpoints().clone();
+        let spks: BTreeMap<_, _> = rescue_wallet.spks_of_all_keychains().into_iter().collect();
+
+        let update = wallet
+            .blockchain
+            .call(|client| {
+                let checkpoints = &checkpoints;
+                let spks = spks.clone();
+                async move {
+                    client
+                        .scan(
+                            checkpoints,
+                            spks,
+                            core::iter::empty(),
+                            core::iter::empty(),
+                            50,
+                            5,
+                        )
+                        .await
+                }
+            })
+            .await?;
+        rescue_wallet
+            .apply_update(update)
+            .map_err(|e| MutinyError::Other(anyhow::anyhow!("Could not apply update: {e}")))?;
+
+        let balance = rescue_wallet.get_balance().total();
+        if balance == 0 {
+            log_debug!(logger, "No funds found on {:?}", path);
+            continue;
+        }
+        log_info!(logger, "Found {balance} sats on {:?}, sweeping", path);
+
+        let address = wallet
+            .wallet
+            .try_write()?
+            .get_address(AddressIndex::New)
+            .address;
+        let psbt = {
+            let mut builder = rescue_wallet.build_tx();
+            builder
+                .drain_wallet()
+                .drain_to(address.script_pubkey())
+                .enable_rbf()
+                .fee_rate(fee_rate);
+            match builder.finish() {
+                Ok((mut psbt, _)) => {
+                    rescue_wallet.sign(&mut psbt, SignOptions::default())?;
+                    Some(psbt)
+                }
+                Err(e) => {
+                    // most likely the funds are not enough to pay the fee
+                    log_warn!(logger, "Could not create sweep for {:?}: {e}", path);
+                    None
+                }
+            }
+        };
+
+        let txid = match psbt {
+            Some(psbt) => {
+                let tx = psbt.extract_tx();
+                let txid = tx.txid();
+                wallet.broadcast_transaction(tx).await?;
+                wallet
+                    .storage
+                    .set_address_labels(address, vec!["Rescued Funds".to_string()])?;
+                Some(txid)
+            }
+            None => None,
+        };
+
+        results.push(RescueResult {
+            path,
+            balance,
+            txid,
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_default_rescue_paths() {
+        let mainnet = default_rescue_paths(Network::Bitcoin);
+        let testnet = default_rescue_paths(Network::Testnet);
+
+        assert_eq!(mainnet.len(), 6);
+        // test networks also scan the mainnet coin type
+        assert_eq!(testnet.len(), 10);
+
+        let bip84 = RescuePath::new(RescueScriptType::Wpkh, "m/84'/0'/0'").unwrap();
+        assert!(mainnet.contains(&bip84));
+        assert!(testnet.contains(&bip84));
+    }
+
+    #[test]
+    fn test_rescue_descriptors() {
+        let mnemonic = Mnemonic::from_str("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+        let seed = mnemonic.to_seed("");
+        let xprivkey = ExtendedPrivKey::new_master(Network::Bitcoin, &seed).unwrap();
+
+        let path = RescuePath::new(RescueScriptType::Wpkh, "m/84'/0'/0'").unwrap();
+        let (receive, change) = path.descriptors(xprivkey).unwrap();
+        let mut wallet = Wallet::new(
+            receive,
+            Some(change),
+            OnChainStorage::new(MemoryStorage::default()),
+            Network::Bitcoin,
+        )
+        .unwrap();
+
+        // first address from the BIP84 test vectors
+        let address = wallet.get_address(AddressIndex::New).address;
+        assert_eq!(
+            address.to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+    }
+
+    #[test]
+    fn test_key_descriptors() {
+        let key =
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn").unwrap();
+        let descriptors = key_descriptors(key).unwrap();
+        assert_eq!(descriptors.len(), 4);
+
+        let (path, descriptor) = descriptors
+            .into_iter()
+            .find(|(path, _)| path.script_type == RescueScriptType::Wpkh)
+            .unwrap();
+        assert_eq!(path.account_path, DerivationPath::master());
+        let mut wallet = Wallet::new(
+            descriptor,
+            None,
+            OnChainStorage::new(MemoryStorage::default()),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        // the P2WPKH address of the private key 1
+        let address = wallet.get_address(AddressIndex::New).address;
+        assert_eq!(
+            address.to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+
+        // uncompressed keys only have legacy addresses
+        let uncompressed =
+            PrivateKey::from_wif("5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf").unwrap();
+        let descriptors = key_descriptors(uncompressed).unwrap();
+        assert_eq!(descriptors.len(), 1);
+        assert_eq!(descriptors[0].0.script_type, RescueScriptType::Pkh);
+
+        // keys have to be for the same kind of network
+        assert!(check_key_network(Network::Testnet, Network::Signet).is_ok());
+        assert!(matches!(
+            check_key_network(Network::Bitcoin, Network::Signet),
+            Err(MutinyError::IncorrectNetwork(Network::Bitcoin))
+        ));
+    }
+}
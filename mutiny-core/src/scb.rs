@@ -0,0 +1,147 @@
+use crate::encrypt::Cipher;
+use crate::error::MutinyError;
+use crate::gossip::{get_all_peers, save_ln_peer_info, LnPeerMetadata};
+use crate::ldkstorage::MONITORS_PREFIX_KEY;
+use crate::nodemanager::NodeStorage;
+use crate::storage::MutinyStorage;
+use crate::utils;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey};
+use bitcoin::Network;
+use lightning::routing::gossip::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// A static channel backup of the wallet's lightning nodes.
+///
+/// This holds the channel monitors and the peers they were opened with. It is
+/// not enough to keep using the channels, but with the seed it is enough to
+/// force close them and sweep the funds back on-chain.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct StaticChannelBackup {
+    pub network: Network,
+    /// Unix timestamp, in seconds, of when the backup was created
+    pub created_at: u64,
+    pub nodes: NodeStorage,
+    /// The encoded channel monitors, keyed by their storage key
+    pub monitors: HashMap<String, Vec<u8>>,
+    /// The peers of our nodes, keyed by their node id
+    pub peers: HashMap<String, LnPeerMetadata>,
+}
+
+impl StaticChannelBackup {
+    /// Creates a backup from the state currently in storage
+    pub fn from_storage<S: MutinyStorage>(
+        storage: &S,
+        network: Network,
+    ) -> Result<Self, MutinyError> {
+        let nodes = storage.get_nodes()?;
+        let monitors: HashMap<String, Vec<u8>> = storage.scan(MONITORS_PREFIX_KEY, None)?;
+        let peers = get_all_peers(storage)?
+            .into_iter()
+            .map(|(node_id, info)| (node_id.to_string(), info))
+            .collect();
+
+        Ok(Self {
+            network,
+            created_at: utils::now().as_secs(),
+            nodes,
+            monitors,
+            peers,
+        })
+    }
+
+    /// Writes the backup to storage. The nodes will start in recovery mode
+    /// because they have channel monitors but no channel manager.
+    pub fn restore<S: MutinyStorage>(&self, storage: &S) -> Result<(), MutinyError> {
+        for (key, monitor) in self.monitors.iter() {
+            storage.set_data(key, monitor)?;
+        }
+        for (node_id, info) in self.peers.iter() {
+            save_ln_peer_info(storage, &NodeId::from_str(node_id)?, info)?;
+        }
+        storage.insert_nodes(self.nodes.clone())
+    }
+
+    /// Encrypts the backup with a key derived from the seed
+    pub fn encrypt(&self, xprivkey: ExtendedPrivKey) -> Result<String, MutinyError> {
+        let json = serde_json::to_string(self)?;
+        backup_cipher(xprivkey)?.encrypt(&json)
+    }
+
+    /// Decrypts a backup made with [StaticChannelBackup::encrypt].
+    /// Returns [MutinyError::IncorrectPassword] if it was made with a different seed.
+    pub fn decrypt(backup: &str, xprivkey: ExtendedPrivKey) -> Result<Self, MutinyError> {
+        let json = backup_cipher(xprivkey)?.decrypt(backup)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+fn backup_cipher(xprivkey: ExtendedPrivKey) -> Result<Cipher, MutinyError> {
+    let context = Secp256k1::new();
+    let key = xprivkey.derive_priv(
+        &context,
+        &[
+            ChildNumber::from_hardened_idx(138)?,
+            ChildNumber::from_hardened_idx(1)?,
+        ],
+    )?;
+    Ok(Cipher::from_key(key.private_key.secret_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nodemanager::NodeIndex;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_channel_backup_round_trip() {
+        let test_name = "test_channel_backup_round_trip";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            "node".to_string(),
+            NodeIndex {
+                child_index: 0,
+                lsp: None,
+                archived: None,
+            },
+        );
+        storage.insert_nodes(NodeStorage { nodes }).unwrap();
+        storage
+            .set_data("monitors/abc_node", vec![1u8, 2, 3])
+            .unwrap();
+
+        let backup = StaticChannelBackup::from_storage(&storage, Network::Regtest).unwrap();
+        assert_eq!(backup.monitors.len(), 1);
+        assert_eq!(backup.nodes.nodes.len(), 1);
+
+        let xprivkey = ExtendedPrivKey::new_master(Network::Regtest, &[0; 32]).unwrap();
+        let encrypted = backup.encrypt(xprivkey).unwrap();
+        assert_eq!(
+            StaticChannelBackup::decrypt(&encrypted, xprivkey).unwrap(),
+            backup
+        );
+
+        // a different seed can't read it
+        let other = ExtendedPrivKey::new_master(Network::Regtest, &[1; 32]).unwrap();
+        assert!(matches!(
+            StaticChannelBackup::decrypt(&encrypted, other),
+            Err(MutinyError::IncorrectPassword)
+        ));
+
+        let restored = MemoryStorage::default();
+        backup.restore(&restored).unwrap();
+        assert_eq!(restored.get_nodes().unwrap().nodes.len(), 1);
+        let monitor: Option<Vec<u8>> = restored.get_data("monitors/abc_node").unwrap();
+        assert_eq!(monitor, Some(vec![1, 2, 3]));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::MutinyError;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::OutPoint;
+use lightning::util::message_signing;
+use serde::{Deserialize, Serialize};
+
+/// What a message signed with a node key is meant to prove.
+///
+/// The scope is part of the signed data, so a signature made for one purpose
+/// can't be passed off as one for another and the node key is never used to
+/// sign raw data chosen by someone else.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningScope {
+    /// Proves we control the node
+    NodeOwnership,
+    /// Proves we are a party to the channel with the given funding outpoint
+    ChannelOwnership(OutPoint),
+}
+
+impl SigningScope {
+    fn prefix(&self) -> String {
+        match self {
+            SigningScope::NodeOwnership => "mutiny-node-ownership:".to_string(),
+            SigningScope::ChannelOwnership(outpoint) => {
+                format!("mutiny-channel-ownership:{outpoint}:")
+            }
+        }
+    }
+
+    /// The data that is actually signed for the given message
+    pub fn scoped_message(&self, message: &str) -> String {
+        format!("{}{message}", self.prefix())
+    }
+}
+
+/// Signs a message with the node key in the given scope.
+/// The signature is zbase32 encoded and compatible with lnd's `signmessage`.
+pub(crate) fn sign_scoped_message(
+    scope: SigningScope,
+    message: &str,
+    secret_key: &SecretKey,
+) -> Result<String, MutinyError> {
+    message_signing::sign(scope.scoped_message(message).as_bytes(), secret_key)
+        .map_err(|_| MutinyError::InvalidArgumentsError)
+}
+
+/// Verifies a signature made by the given node in the given scope
+pub fn verify_scoped_message(
+    scope: SigningScope,
+    message: &str,
+    signature: &str,
+    node_id: &PublicKey,
+) -> bool {
+    message_signing::verify(scope.scoped_message(message).as_bytes(), signature, node_id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::Txid;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_scoped_signatures() {
+        let test_name = "test_scoped_signatures";
+        log!("{}", test_name);
+
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let node_id = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        let message = "challenge";
+
+        let sig = sign_scoped_message(SigningScope::NodeOwnership, message, &secret_key).unwrap();
+        assert!(verify_scoped_message(
+            SigningScope::NodeOwnership,
+            message,
+            &sig,
+            &node_id
+        ));
+
+        // the signature is only valid in the scope it was made for
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+        assert!(!verify_scoped_message(
+            SigningScope::ChannelOwnership(outpoint),
+            message,
+            &sig,
+            &node_id
+        ));
+        assert!(!message_signing::verify(message.as_bytes(), &sig, &node_id));
+    }
+}
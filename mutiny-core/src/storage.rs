@@ -1,25 +1,89 @@
 use crate::auth::AuthProfile;
-use crate::encrypt::{decrypt, encrypt};
+use crate::encrypt::{is_encrypted, Cipher};
 use crate::error::{MutinyError, MutinyStorageError};
-use crate::ldkstorage::CHANNEL_MANAGER_KEY;
-use crate::nodemanager::NodeStorage;
+use crate::labels::MEMO_PREFIX;
+use crate::ldkstorage::{
+    CHANNEL_MANAGER_KEY, MONITORS_PREFIX_KEY, PAYMENT_INBOUND_PREFIX_KEY,
+    PAYMENT_OUTBOUND_PREFIX_KEY,
+};
+use crate::nodemanager::{
+    ChannelAcceptancePolicy, ChannelFeePolicy, InboundPaymentConfig, NodeAnnouncementConfig,
+    NodeStorage,
+};
+use crate::pricing::{FiatCurrency, PriceConfig, HISTORICAL_PRICE_PREFIX};
+use crate::swaps::SWAP_KEY_PREFIX;
+use crate::vss::{needs_backup, VssBackup};
+use crate::watch::{EXTERNAL_TX_KEY_PREFIX, WATCHED_ADDRESSES_KEY, WATCHED_TXIDS_KEY};
 use anyhow::anyhow;
 use bdk::chain::keychain::{KeychainChangeSet, KeychainTracker, PersistBackend};
 use bdk::chain::sparse_chain::ChainPosition;
 use bip39::Mnemonic;
+use bitcoin::{Network, Transaction, Txid};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 pub const KEYCHAIN_STORE_KEY: &str = "keychain_store";
 pub(crate) const MNEMONIC_KEY: &str = "mnemonic";
-const NODES_KEY: &str = "nodes";
-const AUTH_PROFILES_KEY: &str = "auth_profiles";
+pub(crate) const WATCH_ONLY_KEY: &str = "watch_only_descriptor";
+pub(crate) const NODES_KEY: &str = "nodes";
+pub(crate) const AUTH_PROFILES_KEY: &str = "auth_profiles";
 const FEE_ESTIMATES_KEY: &str = "fee_estimates";
 const FIRST_SYNC_KEY: &str = "first_sync";
+const INBOUND_PAYMENT_CONFIG_KEY: &str = "inbound_payment_config";
+const CHANNEL_FEE_POLICIES_KEY: &str = "channel_fee_policies";
+const NODE_ANNOUNCEMENT_CONFIG_KEY: &str = "node_announcement_config";
+const CHANNEL_ACCEPTANCE_POLICY_KEY: &str = "channel_acceptance_policy";
+const LSP_URLS_KEY: &str = "lsp_urls";
+const INBOUND_LIQUIDITY_THRESHOLD_KEY: &str = "inbound_liquidity_threshold";
+const SAFE_CONFIRMATIONS_KEY: &str = "safe_confirmations";
+const NETWORK_GRAPH_SIZE_LIMIT_KEY: &str = "network_graph_size_limit";
+const FIAT_CURRENCY_KEY: &str = "fiat_currency";
+const PRICE_CONFIG_KEY: &str = "price_config";
+const HIDDEN_ACTIVITY_KEY: &str = "hidden_activity";
+const ENCRYPTION_SALT_KEY: &str = "encryption_salt";
+const PASSWORD_CHANGE_PREFIX: &str = "password_change/";
+const PASSWORD_CHANGE_SALT_KEY: &str = "password_change_salt";
+const NETWORK_KEY: &str = "network";
+const USAGE_STATS_ENABLED_KEY: &str = "usage_stats_enabled";
+const ONCHAIN_ROTATION_KEY: &str = "onchain_rotation";
+const ACCOUNT_INDEX_KEY: &str = "account_index";
+const WALLET_BIRTHDAY_KEY: &str = "wallet_birthday";
+const BROADCAST_TX_PREFIX: &str = "broadcast_tx/";
+
+/// The key the bdk keychain store of the on-chain keys after the given number of
+/// rotations is saved under, the original keys keep the original key.
+pub(crate) fn keychain_store_key(rotation: u32) -> String {
+    match rotation {
+        0 => KEYCHAIN_STORE_KEY.to_string(),
+        n => format!("{KEYCHAIN_STORE_KEY}_{n}"),
+    }
+}
 
 fn needs_encryption(key: &str) -> bool {
+    match key {
+        MNEMONIC_KEY => true,
+        str if str.starts_with(CHANNEL_MANAGER_KEY) => true,
+        str if str.starts_with(MONITORS_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_INBOUND_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_OUTBOUND_PREFIX_KEY) => true,
+        str if str.starts_with(MEMO_PREFIX) => true,
+        // swaps hold the preimage and claim key
+        str if str.starts_with(SWAP_KEY_PREFIX) => true,
+        // prices reveal when and how much each payment was
+        str if str.starts_with(HISTORICAL_PRICE_PREFIX) => true,
+        // watched addresses and their transactions link the user to them
+        WATCHED_ADDRESSES_KEY => true,
+        WATCHED_TXIDS_KEY => true,
+        str if str.starts_with(EXTERNAL_TX_KEY_PREFIX) => true,
+        _ => false,
+    }
+}
+
+/// Keys that were encrypted before the argon2 [Cipher] was introduced,
+/// these may still be stored in the legacy format.
+fn legacy_encrypted(key: &str) -> bool {
     match key {
         MNEMONIC_KEY => true,
         str if str.starts_with(CHANNEL_MANAGER_KEY) => true,
@@ -30,13 +94,13 @@ fn needs_encryption(key: &str) -> bool {
 pub fn encrypt_value(
     key: impl AsRef<str>,
     value: Value,
-    password: Option<&str>,
+    cipher: Option<&Cipher>,
 ) -> Result<Value, MutinyError> {
     // Only bother encrypting if a password is set
-    let res = match password {
-        Some(pw) if needs_encryption(key.as_ref()) => {
+    let res = match cipher {
+        Some(cipher) if needs_encryption(key.as_ref()) => {
             let str = serde_json::to_string(&value)?;
-            let ciphertext = encrypt(&str, pw);
+            let ciphertext = cipher.encrypt(&str)?;
             Value::String(ciphertext)
         }
         _ => value,
@@ -48,24 +112,72 @@ pub fn encrypt_value(
 pub fn decrypt_value(
     key: impl AsRef<str>,
     value: Value,
-    password: Option<&str>,
+    cipher: Option<&Cipher>,
 ) -> Result<Value, MutinyError> {
-    // Only bother encrypting if a password is set
-    let json: Value = match password {
-        Some(pw) if needs_encryption(key.as_ref()) => {
-            let str: String = serde_json::from_value(value)?;
-            let ciphertext = decrypt(&str, pw);
-            serde_json::from_str(&ciphertext)?
+    let key = key.as_ref();
+    if !needs_encryption(key) {
+        return Ok(value);
+    }
+
+    // Values saved before a password was set are still in plaintext,
+    // they will be encrypted the next time they are written.
+    let json: Value = match (value, cipher) {
+        (Value::String(str), Some(cipher)) if is_encrypted(&str) || legacy_encrypted(key) => {
+            let plaintext = cipher.decrypt(&str)?;
+            serde_json::from_str(&plaintext)?
         }
-        _ => value,
+        (Value::String(str), None) if is_encrypted(&str) => return Err(MutinyError::WalletLocked),
+        (value, _) => value,
     };
 
     Ok(json)
 }
 
+fn generate_salt() -> Result<[u8; 16], MutinyError> {
+    let mut salt = [0u8; 16];
+    getrandom::getrandom(&mut salt)
+        .map_err(|e| MutinyError::Other(anyhow!("Failed to generate salt: {e}")))?;
+    Ok(salt)
+}
+
+/// Writes every sensitive value re-encrypted with the new cipher next to the current one,
+/// followed by the new salt, an empty salt means the password is being removed.
+fn stage_password_change<S: MutinyStorage>(
+    storage: &S,
+    cipher: Option<&Cipher>,
+    salt: &str,
+) -> Result<(), MutinyError> {
+    for key in storage.scan_keys("", None)? {
+        if needs_encryption(&key) {
+            if let Some(value) = storage.get_data::<Value>(&key)? {
+                let value = encrypt_value(&key, value, cipher)?;
+                storage.set(format!("{PASSWORD_CHANGE_PREFIX}{key}"), value)?;
+            }
+        }
+    }
+    storage.set(PASSWORD_CHANGE_SALT_KEY, salt)
+}
+
+/// Deletes the values and salt written by [stage_password_change]
+fn clear_password_change<S: MutinyStorage>(storage: &S) -> Result<(), MutinyError> {
+    let mut keys = storage.scan_keys(PASSWORD_CHANGE_PREFIX, None)?;
+    keys.push(PASSWORD_CHANGE_SALT_KEY.to_string());
+    storage.delete(&keys)
+}
+
 pub trait MutinyStorage: Clone + Sized + 'static {
-    /// Get the password used to encrypt the storage
-    fn password(&self) -> Option<&str>;
+    /// Get the cipher used to encrypt the storage, this is None if the
+    /// storage has no password or is locked
+    fn cipher(&self) -> Option<Cipher>;
+
+    /// Set the cipher used to encrypt the storage, setting None locks the storage
+    fn set_cipher(&self, cipher: Option<Cipher>);
+
+    /// Get the remote backup that critical writes are mirrored to, if one is set
+    fn vss(&self) -> Option<Arc<VssBackup>>;
+
+    /// Set the remote backup that critical writes are mirrored to
+    fn set_vss(&self, vss: Option<Arc<VssBackup>>);
 
     /// Set a value in the storage, the value will already be encrypted if needed
     fn set<T>(&self, key: impl AsRef<str>, value: T) -> Result<(), MutinyError>
@@ -81,9 +193,25 @@ pub trait MutinyStorage: Clone + Sized + 'static {
             source: MutinyStorageError::SerdeError { source: e },
         })?;
 
-        let json: Value = encrypt_value(key.as_ref(), data, self.password())?;
+        let cipher = self.cipher();
+        // Never write sensitive data in plaintext while locked
+        if cipher.is_none() && needs_encryption(key.as_ref()) && self.is_encrypted()? {
+            return Err(MutinyError::WalletLocked);
+        }
 
-        self.set(key, json)
+        // queue the plaintext, the backup encrypts it with its own key
+        let backup = self.vss().filter(|_| needs_backup(key.as_ref()));
+        let backup_data = backup.as_ref().map(|_| data.clone());
+
+        let json: Value = encrypt_value(key.as_ref(), data, cipher.as_ref())?;
+
+        self.set(key.as_ref(), json)?;
+
+        if let (Some(backup), Some(data)) = (backup, backup_data) {
+            backup.queue(key.as_ref(), data);
+        }
+
+        Ok(())
     }
 
     /// Get a value from the storage, use get_data if you want the value to be decrypted
@@ -99,7 +227,7 @@ pub trait MutinyStorage: Clone + Sized + 'static {
         match self.get(&key)? {
             None => Ok(None),
             Some(value) => {
-                let json: Value = decrypt_value(&key, value, self.password())?;
+                let json: Value = decrypt_value(&key, value, self.cipher().as_ref())?;
                 let data: T = serde_json::from_value(json)?;
                 Ok(Some(data))
             }
@@ -140,6 +268,123 @@ pub trait MutinyStorage: Clone + Sized + 'static {
             .collect())
     }
 
+    /// Returns if a password has been set for the storage
+    fn is_encrypted(&self) -> Result<bool, MutinyError> {
+        Ok(self.get::<String>(ENCRYPTION_SALT_KEY)?.is_some())
+    }
+
+    /// Returns if the storage has a password and has not been unlocked
+    fn is_locked(&self) -> Result<bool, MutinyError> {
+        Ok(self.cipher().is_none() && self.is_encrypted()?)
+    }
+
+    /// Unlocks the storage by deriving the encryption key from the password.
+    /// If a mnemonic is saved, the password must be able to decrypt it.
+    fn unlock(&self, password: &str) -> Result<(), MutinyError> {
+        self.finish_password_change()?;
+
+        let saved_salt: Option<String> = self.get(ENCRYPTION_SALT_KEY)?;
+        let salt = match saved_salt.as_ref() {
+            Some(salt) => base64::decode(salt).map_err(|e| {
+                MutinyError::read_err(MutinyStorageError::Other(anyhow!("Invalid salt: {e}")))
+            })?,
+            None => generate_salt()?.to_vec(),
+        };
+        let cipher = Cipher::new(password, &salt)?;
+
+        if let Some(value) = self.get::<Value>(MNEMONIC_KEY)? {
+            decrypt_value(MNEMONIC_KEY, value, Some(&cipher))
+                .map_err(|_| MutinyError::IncorrectPassword)?;
+        }
+
+        if saved_salt.is_none() {
+            self.set(ENCRYPTION_SALT_KEY, base64::encode(salt))?;
+        }
+        self.set_cipher(Some(cipher));
+
+        Ok(())
+    }
+
+    /// Locks the storage, removing the encryption key from memory.
+    fn lock(&self) {
+        self.set_cipher(None)
+    }
+
+    /// Changes the password used to encrypt the storage and re-encrypts all
+    /// sensitive data with it. A new password of None removes the encryption.
+    fn change_password(
+        &self,
+        old_password: Option<&str>,
+        new_password: Option<&str>,
+    ) -> Result<(), MutinyError> {
+        match old_password.filter(|p| !p.is_empty()) {
+            Some(password) => self.unlock(password)?,
+            None if self.is_encrypted()? => return Err(MutinyError::IncorrectPassword),
+            None => {}
+        }
+
+        // an earlier change that was interrupted has to be finished before staging this one
+        self.finish_password_change()?;
+
+        let new_key = match new_password.filter(|p| !p.is_empty()) {
+            Some(password) => {
+                let salt = generate_salt()?;
+                Some((Cipher::new(password, &salt)?, base64::encode(salt)))
+            }
+            None => None,
+        };
+        let new_cipher = new_key.as_ref().map(|(cipher, _)| cipher);
+        let new_salt = new_key
+            .as_ref()
+            .map(|(_, salt)| salt.clone())
+            .unwrap_or_default();
+
+        // write the re-encrypted values under temporary keys first,
+        // so a failure leaves the current values and salt untouched
+        if let Err(e) = stage_password_change(self, new_cipher, &new_salt) {
+            let _ = clear_password_change(self);
+            return Err(e);
+        }
+
+        // switching the salt commits the change, if moving the values fails
+        // after this it is finished the next time the storage is loaded
+        match new_key {
+            Some((cipher, salt)) => {
+                self.set(ENCRYPTION_SALT_KEY, salt)?;
+                self.set_cipher(Some(cipher));
+            }
+            None => {
+                self.delete(&[ENCRYPTION_SALT_KEY])?;
+                self.set_cipher(None);
+            }
+        }
+
+        self.finish_password_change()
+    }
+
+    /// Moves the values staged by an interrupted password change into place if its
+    /// new salt was saved, otherwise discards them as the old password is still in use.
+    fn finish_password_change(&self) -> Result<(), MutinyError> {
+        let staged_keys = self.scan_keys(PASSWORD_CHANGE_PREFIX, None)?;
+        let new_salt: Option<String> = self.get(PASSWORD_CHANGE_SALT_KEY)?;
+        if staged_keys.is_empty() && new_salt.is_none() {
+            return Ok(());
+        }
+
+        // without the new salt the change never finished staging, so it was never committed
+        let saved_salt: Option<String> = self.get(ENCRYPTION_SALT_KEY)?;
+        if new_salt.is_some_and(|salt| salt == saved_salt.unwrap_or_default()) {
+            for staged in staged_keys {
+                if let Some(value) = self.get::<Value>(&staged)? {
+                    let key = staged.trim_start_matches(PASSWORD_CHANGE_PREFIX);
+                    self.set(key, value)?;
+                }
+            }
+        }
+
+        clear_password_change(self)
+    }
+
     /// Insert a mnemonic into the storage
     fn insert_mnemonic(&self, mnemonic: Mnemonic) -> Result<Mnemonic, MutinyError> {
         self.set_data(MNEMONIC_KEY, &mnemonic)?;
@@ -155,6 +400,54 @@ pub trait MutinyStorage: Clone + Sized + 'static {
         }
     }
 
+    /// Gets the xpub or output descriptor a watch-only wallet tracks
+    fn get_watch_only_descriptor(&self) -> Result<Option<String>, MutinyError> {
+        self.get_data(WATCH_ONLY_KEY)
+    }
+
+    fn set_watch_only_descriptor(&self, descriptor: &str) -> Result<(), MutinyError> {
+        self.set_data(WATCH_ONLY_KEY, descriptor)
+    }
+
+    /// Gets the network the wallet's data was created for
+    fn get_network(&self) -> Result<Option<Network>, MutinyError> {
+        self.get_data(NETWORK_KEY)
+    }
+
+    /// Saves the network the wallet's data was created for
+    fn set_network(&self, network: Network) -> Result<(), MutinyError> {
+        self.set_data(NETWORK_KEY, network)
+    }
+
+    /// Gets how many times the on-chain keys were rotated
+    fn get_onchain_rotation(&self) -> Result<u32, MutinyError> {
+        Ok(self.get_data(ONCHAIN_ROTATION_KEY)?.unwrap_or(0))
+    }
+
+    fn set_onchain_rotation(&self, rotation: u32) -> Result<(), MutinyError> {
+        self.set_data(ONCHAIN_ROTATION_KEY, rotation)
+    }
+
+    /// Gets the account index the wallet's keys were derived with
+    fn get_account_index(&self) -> Result<Option<u32>, MutinyError> {
+        self.get_data(ACCOUNT_INDEX_KEY)
+    }
+
+    fn set_account_index(&self, index: u32) -> Result<(), MutinyError> {
+        self.set_data(ACCOUNT_INDEX_KEY, index)
+    }
+
+    /// Deletes everything except the seed and what is needed to decrypt it,
+    /// used when the wallet is moved to another network.
+    fn clear_network_data(&self) -> Result<(), MutinyError> {
+        let keys: Vec<String> = self
+            .scan_keys("", None)?
+            .into_iter()
+            .filter(|k| k != MNEMONIC_KEY && k != ENCRYPTION_SALT_KEY)
+            .collect();
+        self.delete(&keys)
+    }
+
     /// Override the storage with the new JSON object
     async fn import(json: Value) -> Result<(), MutinyError>;
 
@@ -217,20 +510,191 @@ pub trait MutinyStorage: Clone + Sized + 'static {
     fn set_done_first_sync(&self) -> Result<(), MutinyError> {
         self.set_data(FIRST_SYNC_KEY, true)
     }
+
+    /// Gets the inbound payment settings, if none are saved the defaults are returned
+    fn get_inbound_payment_config(&self) -> Result<InboundPaymentConfig, MutinyError> {
+        let res: Option<InboundPaymentConfig> = self.get_data(INBOUND_PAYMENT_CONFIG_KEY)?;
+        Ok(res.unwrap_or_default())
+    }
+
+    /// Saves the inbound payment settings
+    fn set_inbound_payment_config(&self, config: InboundPaymentConfig) -> Result<(), MutinyError> {
+        self.set_data(INBOUND_PAYMENT_CONFIG_KEY, config)
+    }
+
+    /// Gets the fee policies set for our channels, keyed by their funding outpoint
+    fn get_channel_fee_policies(&self) -> Result<HashMap<String, ChannelFeePolicy>, MutinyError> {
+        let res: Option<HashMap<String, ChannelFeePolicy>> =
+            self.get_data(CHANNEL_FEE_POLICIES_KEY)?;
+        Ok(res.unwrap_or_default())
+    }
+
+    /// Saves the fee policy of the channel with the given funding outpoint
+    fn set_channel_fee_policy(
+        &self,
+        outpoint: String,
+        policy: ChannelFeePolicy,
+    ) -> Result<(), MutinyError> {
+        let mut policies = self.get_channel_fee_policies()?;
+        policies.insert(outpoint, policy);
+        self.set_data(CHANNEL_FEE_POLICIES_KEY, policies)
+    }
+
+    /// Gets the alias, color and addresses our nodes announce, if they were set
+    fn get_node_announcement_config(&self) -> Result<Option<NodeAnnouncementConfig>, MutinyError> {
+        self.get_data(NODE_ANNOUNCEMENT_CONFIG_KEY)
+    }
+
+    /// Saves the alias, color and addresses our nodes announce
+    fn set_node_announcement_config(
+        &self,
+        config: NodeAnnouncementConfig,
+    ) -> Result<(), MutinyError> {
+        self.set_data(NODE_ANNOUNCEMENT_CONFIG_KEY, config)
+    }
+
+    /// Gets which inbound channels our nodes accept, if it was set
+    fn get_channel_acceptance_policy(
+        &self,
+    ) -> Result<Option<ChannelAcceptancePolicy>, MutinyError> {
+        self.get_data(CHANNEL_ACCEPTANCE_POLICY_KEY)
+    }
+
+    /// Saves which inbound channels our nodes accept
+    fn set_channel_acceptance_policy(
+        &self,
+        policy: ChannelAcceptancePolicy,
+    ) -> Result<(), MutinyError> {
+        self.set_data(CHANNEL_ACCEPTANCE_POLICY_KEY, policy)
+    }
+
+    /// Gets the LSPs that were added or removed at runtime, these replace the ones from the config
+    fn get_lsp_urls(&self) -> Result<Option<Vec<String>>, MutinyError> {
+        self.get_data(LSP_URLS_KEY)
+    }
+
+    /// Saves the LSPs we use
+    fn set_lsp_urls(&self, urls: Vec<String>) -> Result<(), MutinyError> {
+        self.set_data(LSP_URLS_KEY, urls)
+    }
+
+    /// Gets the inbound liquidity, in sats, below which we alert the user
+    fn get_inbound_liquidity_threshold(&self) -> Result<Option<u64>, MutinyError> {
+        self.get_data(INBOUND_LIQUIDITY_THRESHOLD_KEY)
+    }
+
+    /// Sets the inbound liquidity, in sats, below which we alert the user.
+    /// None turns the alert off.
+    fn set_inbound_liquidity_threshold(&self, threshold: Option<u64>) -> Result<(), MutinyError> {
+        self.set_data(INBOUND_LIQUIDITY_THRESHOLD_KEY, threshold)
+    }
+
+    /// Gets the number of confirmations after which a transaction is considered safe
+    fn get_safe_confirmations(&self) -> Result<Option<u32>, MutinyError> {
+        self.get_data(SAFE_CONFIRMATIONS_KEY)
+    }
+
+    fn set_safe_confirmations(&self, confirmations: u32) -> Result<(), MutinyError> {
+        self.set_data(SAFE_CONFIRMATIONS_KEY, confirmations)
+    }
+
+    /// Gets the size, in bytes, the network graph is pruned down to
+    fn get_network_graph_size_limit(&self) -> Result<Option<u64>, MutinyError> {
+        self.get_data(NETWORK_GRAPH_SIZE_LIMIT_KEY)
+    }
+
+    /// Sets the size, in bytes, the network graph is pruned down to.
+    /// None lets it grow as big as the network.
+    fn set_network_graph_size_limit(&self, limit: Option<u64>) -> Result<(), MutinyError> {
+        self.set_data(NETWORK_GRAPH_SIZE_LIMIT_KEY, limit)
+    }
+
+    /// Gets the fiat currency prices are shown in
+    fn get_fiat_currency(&self) -> Result<Option<FiatCurrency>, MutinyError> {
+        self.get_data(FIAT_CURRENCY_KEY)
+    }
+
+    fn set_fiat_currency(&self, currency: FiatCurrency) -> Result<(), MutinyError> {
+        self.set_data(FIAT_CURRENCY_KEY, currency)
+    }
+
+    /// Gets where bitcoin prices are fetched from
+    fn get_price_config(&self) -> Result<Option<PriceConfig>, MutinyError> {
+        self.get_data(PRICE_CONFIG_KEY)
+    }
+
+    fn set_price_config(&self, config: PriceConfig) -> Result<(), MutinyError> {
+        self.set_data(PRICE_CONFIG_KEY, config)
+    }
+
+    /// Gets the block height the wallet was created at
+    fn get_wallet_birthday(&self) -> Result<Option<u32>, MutinyError> {
+        self.get_data(WALLET_BIRTHDAY_KEY)
+    }
+
+    fn set_wallet_birthday(&self, height: u32) -> Result<(), MutinyError> {
+        self.set_data(WALLET_BIRTHDAY_KEY, height)
+    }
+
+    /// Gets the transactions LDK broadcast that have not confirmed yet
+    fn get_broadcast_transactions(&self) -> Result<Vec<Transaction>, MutinyError> {
+        let map: HashMap<String, Transaction> = self.scan(BROADCAST_TX_PREFIX, None)?;
+        Ok(map.into_values().collect())
+    }
+
+    /// Keeps the transaction so it can be rebroadcast until it confirms
+    fn add_broadcast_transaction(&self, tx: &Transaction) -> Result<(), MutinyError> {
+        self.set_data(format!("{BROADCAST_TX_PREFIX}{}", tx.txid()), tx)
+    }
+
+    fn remove_broadcast_transaction(&self, txid: &Txid) -> Result<(), MutinyError> {
+        self.delete(&[format!("{BROADCAST_TX_PREFIX}{txid}")])
+    }
+
+    /// Gets the ids of the activity items the user has hidden
+    fn get_hidden_activity(&self) -> Result<HashSet<String>, MutinyError> {
+        let res: Option<HashSet<String>> = self.get_data(HIDDEN_ACTIVITY_KEY)?;
+        Ok(res.unwrap_or_default())
+    }
+
+    /// Hides the activity item with the given id
+    fn hide_activity_item(&self, id: String) -> Result<(), MutinyError> {
+        let mut hidden = self.get_hidden_activity()?;
+        hidden.insert(id);
+        self.set_data(HIDDEN_ACTIVITY_KEY, hidden)
+    }
+
+    /// Whether the user opted in to usage statistics
+    fn usage_stats_enabled(&self) -> Result<bool, MutinyError> {
+        self.get_data::<bool>(USAGE_STATS_ENABLED_KEY)
+            .map(|v| v == Some(true))
+    }
+
+    fn set_usage_stats_enabled(&self, enabled: bool) -> Result<(), MutinyError> {
+        self.set_data(USAGE_STATS_ENABLED_KEY, enabled)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MemoryStorage {
-    pub password: Option<String>,
+    pub cipher: Arc<RwLock<Option<Cipher>>>,
+    pub vss: Arc<RwLock<Option<Arc<VssBackup>>>>,
     pub memory: Arc<RwLock<HashMap<String, Value>>>,
 }
 
 impl MemoryStorage {
     pub fn new(password: Option<String>) -> Self {
-        Self {
-            password,
+        let storage = Self {
+            cipher: Arc::new(RwLock::new(None)),
+            vss: Arc::new(RwLock::new(None)),
             memory: Arc::new(RwLock::new(HashMap::new())),
+        };
+        if let Some(password) = password.filter(|p| !p.is_empty()) {
+            storage
+                .unlock(&password)
+                .expect("empty storage can always be unlocked");
         }
+        storage
     }
 }
 
@@ -241,8 +705,24 @@ impl Default for MemoryStorage {
 }
 
 impl MutinyStorage for MemoryStorage {
-    fn password(&self) -> Option<&str> {
-        self.password.as_deref()
+    fn cipher(&self) -> Option<Cipher> {
+        self.cipher.read().ok().and_then(|c| c.clone())
+    }
+
+    fn set_cipher(&self, cipher: Option<Cipher>) {
+        if let Ok(mut lock) = self.cipher.write() {
+            *lock = cipher;
+        }
+    }
+
+    fn vss(&self) -> Option<Arc<VssBackup>> {
+        self.vss.read().ok().and_then(|v| v.clone())
+    }
+
+    fn set_vss(&self, vss: Option<Arc<VssBackup>>) {
+        if let Ok(mut lock) = self.vss.write() {
+            *lock = vss;
+        }
     }
 
     fn set<T>(&self, key: impl AsRef<str>, value: T) -> Result<(), MutinyError>
@@ -283,6 +763,10 @@ impl MutinyStorage for MemoryStorage {
     fn delete(&self, keys: &[impl AsRef<str>]) -> Result<(), MutinyError> {
         let keys: Vec<String> = keys.iter().map(|k| k.as_ref().to_string()).collect();
 
+        if let Some(vss) = self.vss() {
+            vss.queue_deletes(&keys);
+        }
+
         let mut map = self
             .memory
             .try_write()
@@ -331,10 +815,18 @@ impl MutinyStorage for MemoryStorage {
 
 // Dummy implementation for testing or if people want to ignore persistence
 impl MutinyStorage for () {
-    fn password(&self) -> Option<&str> {
+    fn cipher(&self) -> Option<Cipher> {
         None
     }
 
+    fn set_cipher(&self, _cipher: Option<Cipher>) {}
+
+    fn vss(&self) -> Option<Arc<VssBackup>> {
+        None
+    }
+
+    fn set_vss(&self, _vss: Option<Arc<VssBackup>>) {}
+
     fn set<T>(&self, _key: impl AsRef<str>, _value: T) -> Result<(), MutinyError>
     where
         T: Serialize,
@@ -376,8 +868,25 @@ impl MutinyStorage for () {
     }
 }
 
+/// Persists the bdk keychain store of an on-chain account
 #[derive(Clone)]
-pub struct OnChainStorage<S: MutinyStorage>(pub(crate) S);
+pub struct OnChainStorage<S: MutinyStorage> {
+    pub(crate) storage: S,
+    key: String,
+}
+
+impl<S: MutinyStorage> OnChainStorage<S> {
+    pub(crate) fn new(storage: S) -> Self {
+        Self::for_rotation(storage, 0)
+    }
+
+    pub(crate) fn for_rotation(storage: S, rotation: u32) -> Self {
+        Self {
+            storage,
+            key: keychain_store_key(rotation),
+        }
+    }
+}
 
 impl<K, P, S: MutinyStorage> PersistBackend<K, P> for OnChainStorage<S>
 where
@@ -397,14 +906,14 @@ where
         }
 
         match self
-            .0
-            .get_data::<KeychainChangeSet<K, P>>(KEYCHAIN_STORE_KEY)?
+            .storage
+            .get_data::<KeychainChangeSet<K, P>>(&self.key)?
         {
             Some(mut keychain_store) => {
                 keychain_store.append(changeset.clone());
-                self.0.set_data(KEYCHAIN_STORE_KEY, keychain_store)
+                self.storage.set_data(&self.key, keychain_store)
             }
-            None => self.0.set_data(KEYCHAIN_STORE_KEY, changeset),
+            None => self.storage.set_data(&self.key, changeset),
         }
     }
 
@@ -412,7 +921,7 @@ where
         &mut self,
         tracker: &mut KeychainTracker<K, P>,
     ) -> Result<(), Self::LoadError> {
-        if let Some(k) = self.0.get_data(KEYCHAIN_STORE_KEY)? {
+        if let Some(k) = self.storage.get_data(&self.key)? {
             tracker.apply_changeset(k);
         }
 
@@ -422,9 +931,17 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::storage::MemoryStorage;
+    use crate::encrypt::{is_encrypted, Cipher};
+    use crate::error::MutinyError;
+    use crate::nodemanager::InboundPaymentConfig;
+    use crate::storage::{
+        generate_salt, stage_password_change, MemoryStorage, ENCRYPTION_SALT_KEY, MNEMONIC_KEY,
+        PASSWORD_CHANGE_PREFIX, PASSWORD_CHANGE_SALT_KEY,
+    };
     use crate::test_utils::*;
     use crate::{keymanager, storage::MutinyStorage};
+    use bitcoin::{PackedLockTime, Transaction};
+    use serde_json::Value;
     use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
 
     wasm_bindgen_test_configure!(run_in_browser);
@@ -457,4 +974,166 @@ mod tests {
         let stored_mnemonic = storage.get_mnemonic().unwrap();
         assert_eq!(mnemonic, stored_mnemonic);
     }
+
+    #[test]
+    fn lock_unlock_and_change_password() {
+        let test_name = "lock_unlock_and_change_password";
+        log!("{}", test_name);
+
+        let seed = keymanager::generate_seed(12).unwrap();
+
+        // start without a password, the mnemonic is stored in plaintext
+        let storage = MemoryStorage::new(None);
+        storage.insert_mnemonic(seed.clone()).unwrap();
+        assert!(!storage.is_encrypted().unwrap());
+        let raw: Value = storage.get(MNEMONIC_KEY).unwrap().unwrap();
+        assert_eq!(raw, Value::String(seed.to_string()));
+
+        storage.change_password(None, Some("password")).unwrap();
+        assert!(storage.is_encrypted().unwrap());
+        let raw: String = storage.get(MNEMONIC_KEY).unwrap().unwrap();
+        assert!(is_encrypted(&raw));
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+
+        // locked storage can't read or write sensitive data
+        storage.lock();
+        assert!(storage.is_locked().unwrap());
+        assert!(matches!(
+            storage.get_mnemonic(),
+            Err(MutinyError::WalletLocked)
+        ));
+        assert!(matches!(
+            storage.insert_mnemonic(seed.clone()),
+            Err(MutinyError::WalletLocked)
+        ));
+
+        assert!(matches!(
+            storage.unlock("wrong password"),
+            Err(MutinyError::IncorrectPassword)
+        ));
+        storage.unlock("password").unwrap();
+        assert!(!storage.is_locked().unwrap());
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+
+        // the old password must be given to change it
+        assert!(storage.change_password(None, Some("new")).is_err());
+        storage
+            .change_password(Some("password"), Some("new"))
+            .unwrap();
+        storage.lock();
+        assert!(storage.unlock("password").is_err());
+        storage.unlock("new").unwrap();
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+
+        // removing the password stores everything in plaintext again
+        storage.change_password(Some("new"), None).unwrap();
+        assert!(!storage.is_encrypted().unwrap());
+        let raw: Value = storage.get(MNEMONIC_KEY).unwrap().unwrap();
+        assert_eq!(raw, Value::String(seed.to_string()));
+    }
+
+    #[test]
+    fn interrupted_password_change() {
+        let test_name = "interrupted_password_change";
+        log!("{}", test_name);
+
+        let seed = keymanager::generate_seed(12).unwrap();
+        let storage = MemoryStorage::new(Some("password".to_string()));
+        storage.insert_mnemonic(seed.clone()).unwrap();
+
+        let salt = generate_salt().unwrap();
+        let cipher = Cipher::new("new", &salt).unwrap();
+        let salt = base64::encode(salt);
+
+        // interrupted before the new salt was saved, the old password still works
+        stage_password_change(&storage, Some(&cipher), &salt).unwrap();
+        storage.lock();
+        assert!(matches!(
+            storage.unlock("new"),
+            Err(MutinyError::IncorrectPassword)
+        ));
+        storage.unlock("password").unwrap();
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+        assert!(storage
+            .scan_keys(PASSWORD_CHANGE_PREFIX, None)
+            .unwrap()
+            .is_empty());
+
+        // interrupted after the new salt was saved, the change is finished on unlock
+        stage_password_change(&storage, Some(&cipher), &salt).unwrap();
+        storage.set(ENCRYPTION_SALT_KEY, &salt).unwrap();
+        storage.lock();
+        storage.unlock("new").unwrap();
+        assert_eq!(storage.get_mnemonic().unwrap(), seed);
+        assert!(storage
+            .get::<String>(PASSWORD_CHANGE_SALT_KEY)
+            .unwrap()
+            .is_none());
+        storage.lock();
+        assert!(storage.unlock("password").is_err());
+    }
+
+    #[test]
+    fn insert_and_get_inbound_payment_config() {
+        let test_name = "insert_and_get_inbound_payment_config";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(None);
+        let default = storage.get_inbound_payment_config().unwrap();
+        assert_eq!(default, InboundPaymentConfig::default());
+        assert_eq!(default.min_payment_msat(), 1);
+
+        let config = InboundPaymentConfig {
+            min_htlc_msat: 1_000,
+            accept_dust_payments: false,
+        };
+        storage.set_inbound_payment_config(config).unwrap();
+
+        let stored = storage.get_inbound_payment_config().unwrap();
+        assert_eq!(stored, config);
+        assert_eq!(stored.min_payment_msat(), 546_000);
+    }
+
+    #[test]
+    fn hide_activity_items() {
+        let test_name = "hide_activity_items";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(None);
+        assert!(storage.get_hidden_activity().unwrap().is_empty());
+
+        storage.hide_activity_item("a".to_string()).unwrap();
+        storage.hide_activity_item("b".to_string()).unwrap();
+        storage.hide_activity_item("a".to_string()).unwrap();
+
+        let hidden = storage.get_hidden_activity().unwrap();
+        assert_eq!(hidden.len(), 2);
+        assert!(hidden.contains("a"));
+        assert!(hidden.contains("b"));
+    }
+
+    #[test]
+    fn add_and_remove_broadcast_transactions() {
+        let test_name = "add_and_remove_broadcast_transactions";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::new(None);
+        assert!(storage.get_broadcast_transactions().unwrap().is_empty());
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        storage.add_broadcast_transaction(&tx).unwrap();
+        storage.add_broadcast_transaction(&tx).unwrap();
+        assert_eq!(
+            storage.get_broadcast_transactions().unwrap(),
+            vec![tx.clone()]
+        );
+
+        storage.remove_broadcast_transaction(&tx.txid()).unwrap();
+        assert!(storage.get_broadcast_transactions().unwrap().is_empty());
+    }
 }
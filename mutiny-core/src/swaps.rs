@@ -0,0 +1,770 @@
+use crate::error::MutinyError;
+use crate::event::HTLCStatus;
+use crate::labels::LabelStorage;
+use crate::nodemanager::{NodeManager, PaymentOptions, DUST_LIMIT_SATS};
+use crate::storage::MutinyStorage;
+use crate::utils;
+use anyhow::anyhow;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::{hash160, sha256, Hash};
+use bitcoin::secp256k1::{rand, Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::util::sighash::SighashCache;
+use bitcoin::{
+    Address, EcdsaSighashType, Network, OutPoint, PackedLockTime, Script, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::ln::PaymentHash;
+use lightning::{log_debug, log_error, log_info, util::logger::Logger};
+use lightning_invoice::Invoice;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const BOLTZ_PAIR: &str = "BTC/BTC";
+
+/// When the lockup is this close to timing out, unconfirmed claims are bumped
+/// to a high priority fee so Boltz can't refund itself first
+const CLAIM_URGENT_BLOCKS: u32 = 6;
+
+/// The Boltz API for the network, swaps are not available on signet
+fn boltz_url(network: Network) -> Option<&'static str> {
+    match network {
+        Network::Bitcoin => Some("https://api.boltz.exchange"),
+        Network::Testnet => Some("https://testnet.boltz.exchange/api"),
+        Network::Regtest => Some("http://localhost:9001"),
+        Network::Signet => None,
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BoltzPairs {
+    pub pairs: HashMap<String, BoltzPair>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BoltzPair {
+    pub hash: String,
+    pub limits: BoltzLimits,
+    pub fees: BoltzFees,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BoltzLimits {
+    pub minimal: u64,
+    pub maximal: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BoltzFees {
+    /// The service fee of a reverse swap, in percent
+    pub percentage: f64,
+    pub miner_fees: BoltzMinerFees,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BoltzMinerFees {
+    pub base_asset: BoltzAssetFees,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BoltzAssetFees {
+    pub reverse: BoltzReverseFees,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct BoltzReverseFees {
+    pub claim: u64,
+    pub lockup: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateReverseSwapRequest {
+    #[serde(rename = "type")]
+    pub swap_type: String,
+    pub pair_id: String,
+    pub order_side: String,
+    pub invoice_amount: u64,
+    pub preimage_hash: String,
+    pub claim_public_key: String,
+    pub pair_hash: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateReverseSwapResponse {
+    pub id: String,
+    pub invoice: String,
+    pub redeem_script: String,
+    pub lockup_address: String,
+    pub timeout_block_height: u32,
+    pub onchain_amount: u64,
+}
+
+/// A client for the Boltz swap API
+pub(crate) struct BoltzClient {
+    url: String,
+    http_client: Client,
+}
+
+impl BoltzClient {
+    pub fn new(network: Network, http_client: Client) -> Result<Self, MutinyError> {
+        let url = boltz_url(network)
+            .ok_or_else(|| MutinyError::Other(anyhow!("Swaps are not available on {network}")))?;
+        Ok(Self {
+            url: url.to_string(),
+            http_client,
+        })
+    }
+
+    pub async fn get_pair(&self) -> Result<BoltzPair, MutinyError> {
+        let pairs: BoltzPairs = self
+            .http_client
+            .get(format!("{}/getpairs", self.url))
+            .send()
+            .await
+            .map_err(|_| MutinyError::SwapFailed)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::SwapFailed)?;
+
+        pairs
+            .pairs
+            .get(BOLTZ_PAIR)
+            .cloned()
+            .ok_or(MutinyError::SwapFailed)
+    }
+
+    pub async fn create_reverse_swap(
+        &self,
+        request: &CreateReverseSwapRequest,
+    ) -> Result<CreateReverseSwapResponse, MutinyError> {
+        self.http_client
+            .post(format!("{}/createswap", self.url))
+            .json(request)
+            .send()
+            .await
+            .map_err(|_| MutinyError::SwapFailed)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::SwapFailed)
+    }
+}
+
+/// What swapping an amount of lightning balance on-chain would cost
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SwapOutQuote {
+    /// The amount paid over lightning
+    pub amount_sats: u64,
+    pub service_fee_sats: u64,
+    /// The fee Boltz charges for locking up the on-chain funds
+    pub lockup_fee_sats: u64,
+    /// The estimated fee of our claim transaction
+    pub claim_fee_sats: u64,
+    /// The amount expected to arrive on-chain
+    pub onchain_amount_sats: u64,
+    pub min_amount_sats: u64,
+    pub max_amount_sats: u64,
+}
+
+impl SwapOutQuote {
+    pub(crate) fn from_pair(pair: &BoltzPair, amount_sats: u64) -> Result<Self, MutinyError> {
+        if amount_sats < pair.limits.minimal || amount_sats > pair.limits.maximal {
+            return Err(MutinyError::BadAmountError);
+        }
+
+        let service_fee_sats = (amount_sats as f64 * pair.fees.percentage / 100.0).ceil() as u64;
+        let fees = &pair.fees.miner_fees.base_asset.reverse;
+        let onchain_amount_sats = amount_sats
+            .checked_sub(service_fee_sats + fees.lockup + fees.claim)
+            .filter(|a| *a > DUST_LIMIT_SATS)
+            .ok_or(MutinyError::BadAmountError)?;
+
+        Ok(Self {
+            amount_sats,
+            service_fee_sats,
+            lockup_fee_sats: fees.lockup,
+            claim_fee_sats: fees.claim,
+            onchain_amount_sats,
+            min_amount_sats: pair.limits.minimal,
+            max_amount_sats: pair.limits.maximal,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwapStatus {
+    /// The swap was created with Boltz, the invoice has not been paid yet.
+    Created,
+    /// The invoice payment is in flight, Boltz holds it until
+    /// we claim the funds it locks up on-chain.
+    InvoicePaid,
+    /// The on-chain funds were claimed, which lets Boltz settle the payment.
+    Claimed,
+    /// The swap was a success and is now complete.
+    Completed,
+    /// The swap failed. The error is given.
+    Failed(String),
+}
+
+impl SwapStatus {
+    /// Returns true if the swap is in progress.
+    pub fn is_in_progress(&self) -> bool {
+        match self {
+            SwapStatus::Created => true,
+            SwapStatus::InvoicePaid => true,
+            SwapStatus::Claimed => true,
+            SwapStatus::Completed => false,
+            SwapStatus::Failed(_) => false,
+        }
+    }
+}
+
+/// A swap of lightning balance to an on-chain address through Boltz
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapOut {
+    pub id: String,
+    pub status: SwapStatus,
+    pub sending_node: PublicKey,
+    pub amount_sats: u64,
+    /// The amount Boltz locks up on-chain, before our claim fee
+    pub onchain_amount_sats: u64,
+    pub invoice: Invoice,
+    /// Where the claimed funds are sent
+    pub address: Address,
+    pub lockup_address: Address,
+    pub redeem_script: Script,
+    /// After this height Boltz can refund the locked up funds to itself
+    pub timeout_block_height: u32,
+    /// Kept so the swap can still be claimed after a restart
+    pub preimage: [u8; 32],
+    pub claim_key: SecretKey,
+    pub lockup_txid: Option<Txid>,
+    pub claim_txid: Option<Txid>,
+    /// The fee rate of the latest claim transaction, used to bump it
+    #[serde(default)]
+    pub claim_sats_per_kw: Option<u32>,
+    pub created_at: u64,
+}
+
+impl SwapOut {
+    pub fn fail(&mut self, error: String) {
+        self.status = SwapStatus::Failed(error);
+    }
+
+    fn payment_hash(&self) -> PaymentHash {
+        PaymentHash(self.invoice.payment_hash().into_inner())
+    }
+}
+
+/// Checks the lockup address pays to the redeem script, and that the script
+/// can be claimed with our preimage and key.
+pub(crate) fn verify_redeem_script(
+    redeem_script: &Script,
+    lockup_address: &Address,
+    preimage: &[u8; 32],
+    claim_pubkey: &PublicKey,
+    network: Network,
+) -> bool {
+    if Address::p2wsh(redeem_script, network) != *lockup_address {
+        return false;
+    }
+
+    let script = redeem_script.as_bytes();
+    let contains = |needle: &[u8]| script.windows(needle.len()).any(|w| w == needle);
+    contains(&hash160::Hash::hash(preimage)[..]) && contains(&claim_pubkey.serialize())
+}
+
+/// Builds and signs the transaction spending the lockup output to `address`
+/// by revealing the preimage.
+pub(crate) fn build_claim_transaction(
+    swap: &SwapOut,
+    lockup: OutPoint,
+    lockup_value: u64,
+    sats_per_kw: u32,
+) -> Result<Transaction, MutinyError> {
+    let mut tx = Transaction {
+        version: 2,
+        lock_time: PackedLockTime(0),
+        input: vec![TxIn {
+            previous_output: lockup,
+            script_sig: Script::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: lockup_value,
+            script_pubkey: swap.address.script_pubkey(),
+        }],
+    };
+
+    // size the fee with a witness of the largest signature
+    tx.input[0].witness = Witness::from_vec(vec![
+        vec![0; 73],
+        swap.preimage.to_vec(),
+        swap.redeem_script.to_bytes(),
+    ]);
+    let fee = sats_per_kw as u64 * tx.weight() as u64 / 1_000;
+    tx.output[0].value = lockup_value
+        .checked_sub(fee)
+        .filter(|v| *v > DUST_LIMIT_SATS)
+        .ok_or(MutinyError::SwapFailed)?;
+
+    let sighash = SighashCache::new(&tx)
+        .segwit_signature_hash(0, &swap.redeem_script, lockup_value, EcdsaSighashType::All)
+        .map_err(|_| MutinyError::SwapFailed)?;
+    let message = Message::from_slice(&sighash[..]).map_err(|_| MutinyError::SwapFailed)?;
+    let signature = Secp256k1::signing_only().sign_ecdsa(&message, &swap.claim_key);
+    let mut signature = signature.serialize_der().to_vec();
+    signature.push(EcdsaSighashType::All as u8);
+
+    tx.input[0].witness = Witness::from_vec(vec![
+        signature,
+        swap.preimage.to_vec(),
+        swap.redeem_script.to_bytes(),
+    ]);
+    Ok(tx)
+}
+
+pub trait SwapStorage {
+    fn get_swap(&self, id: &str) -> Result<Option<SwapOut>, MutinyError>;
+    fn get_swaps(&self) -> Result<Vec<SwapOut>, MutinyError>;
+    fn persist_swap(&self, swap: &SwapOut) -> Result<(), MutinyError>;
+}
+
+pub(crate) const SWAP_KEY_PREFIX: &str = "swap/";
+
+impl<S: MutinyStorage> SwapStorage for S {
+    fn get_swap(&self, id: &str) -> Result<Option<SwapOut>, MutinyError> {
+        self.get_data(format!("{SWAP_KEY_PREFIX}{id}"))
+    }
+
+    fn get_swaps(&self) -> Result<Vec<SwapOut>, MutinyError> {
+        let map: HashMap<String, SwapOut> = self.scan(SWAP_KEY_PREFIX, None)?;
+        let mut swaps: Vec<SwapOut> = map.into_values().collect();
+        swaps.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(swaps)
+    }
+
+    fn persist_swap(&self, swap: &SwapOut) -> Result<(), MutinyError> {
+        self.set_data(format!("{SWAP_KEY_PREFIX}{}", swap.id), swap)
+    }
+}
+
+pub trait SwapManager {
+    /// Gets what swapping the amount of lightning balance on-chain would cost
+    async fn swap_out_quote(&self, amount_sats: u64) -> Result<SwapOutQuote, MutinyError>;
+
+    /// Starts swapping lightning balance from the node to an on-chain address,
+    /// a new address in the wallet is used if none is given.
+    async fn init_swap_out(
+        &self,
+        from_node: &PublicKey,
+        amount_sats: u64,
+        address: Option<Address>,
+    ) -> Result<SwapOut, MutinyError>;
+
+    fn get_swap(&self, id: &str) -> Result<Option<SwapOut>, MutinyError>;
+
+    /// Returns every swap, newest first
+    fn list_swaps(&self) -> Result<Vec<SwapOut>, MutinyError>;
+
+    /// Moves the swap along as far as it can go right now and saves it
+    async fn check_swap_out(&self, swap: SwapOut) -> Result<SwapOut, MutinyError>;
+}
+
+impl<S: MutinyStorage> SwapManager for NodeManager<S> {
+    async fn swap_out_quote(&self, amount_sats: u64) -> Result<SwapOutQuote, MutinyError> {
+        let pair = BoltzClient::new(self.get_network(), self.http_client.clone())?
+            .get_pair()
+            .await?;
+        SwapOutQuote::from_pair(&pair, amount_sats)
+    }
+
+    async fn init_swap_out(
+        &self,
+        from_node: &PublicKey,
+        amount_sats: u64,
+        address: Option<Address>,
+    ) -> Result<SwapOut, MutinyError> {
+        let network = self.get_network();
+        let address = match address {
+            Some(address) if !address.is_valid_for_network(network) => {
+                return Err(MutinyError::IncorrectNetwork(address.network));
+            }
+            Some(address) => address,
+            None => self.get_new_address(vec![])?,
+        };
+        // make sure the node exists before creating the swap
+        let node = self.get_node(from_node).await?;
+
+        let client = BoltzClient::new(network, self.http_client.clone())?;
+        let pair = client.get_pair().await?;
+        let quote = SwapOutQuote::from_pair(&pair, amount_sats)?;
+
+        let mut preimage = [0u8; 32];
+        getrandom::getrandom(&mut preimage)
+            .map_err(|_| MutinyError::Other(anyhow!("Failed to generate preimage")))?;
+        let payment_hash = sha256::Hash::hash(&preimage);
+        let claim_key = SecretKey::new(&mut rand::thread_rng());
+        let claim_pubkey = claim_key.public_key(&Secp256k1::signing_only());
+
+        let request = CreateReverseSwapRequest {
+            swap_type: "reversesubmarine".to_string(),
+            pair_id: BOLTZ_PAIR.to_string(),
+            order_side: "buy".to_string(),
+            invoice_amount: amount_sats,
+            preimage_hash: payment_hash.to_string(),
+            claim_public_key: claim_pubkey.to_string(),
+            pair_hash: pair.hash,
+        };
+        let response = client.create_reverse_swap(&request).await?;
+
+        // don't pay anything unless Boltz can only be paid by locking up what it quoted
+        let invoice = Invoice::from_str(&response.invoice).map_err(|_| MutinyError::SwapFailed)?;
+        let redeem_script = Script::from(
+            Vec::<u8>::from_hex(&response.redeem_script).map_err(|_| MutinyError::SwapFailed)?,
+        );
+        let lockup_address =
+            Address::from_str(&response.lockup_address).map_err(|_| MutinyError::SwapFailed)?;
+        let expected_onchain = quote.onchain_amount_sats + quote.claim_fee_sats;
+        if *invoice.payment_hash() != payment_hash
+            || invoice.amount_milli_satoshis() != Some(amount_sats * 1_000)
+            || response.onchain_amount < expected_onchain
+            || !verify_redeem_script(
+                &redeem_script,
+                &lockup_address,
+                &preimage,
+                &claim_pubkey,
+                network,
+            )
+        {
+            log_error!(self.logger, "Boltz swap {} did not match", response.id);
+            return Err(MutinyError::SwapFailed);
+        }
+
+        let mut swap = SwapOut {
+            id: response.id,
+            status: SwapStatus::Created,
+            sending_node: node.pubkey,
+            amount_sats,
+            onchain_amount_sats: response.onchain_amount,
+            invoice,
+            address: address.clone(),
+            lockup_address,
+            redeem_script,
+            timeout_block_height: response.timeout_block_height,
+            preimage,
+            claim_key,
+            lockup_txid: None,
+            claim_txid: None,
+            claim_sats_per_kw: None,
+            created_at: utils::now().as_secs(),
+        };
+        self.storage.persist_swap(&swap)?;
+        if let Err(e) = self
+            .storage
+            .set_address_labels(address, vec!["Swap".to_string()])
+        {
+            log_error!(self.logger, "could not label swap address: {e}");
+        }
+
+        log_info!(self.logger, "Created swap {}", swap.id);
+        swap = self.check_swap_out(swap).await?;
+        Ok(swap)
+    }
+
+    fn get_swap(&self, id: &str) -> Result<Option<SwapOut>, MutinyError> {
+        self.storage.get_swap(id)
+    }
+
+    fn list_swaps(&self) -> Result<Vec<SwapOut>, MutinyError> {
+        self.storage.get_swaps()
+    }
+
+    async fn check_swap_out(&self, mut swap: SwapOut) -> Result<SwapOut, MutinyError> {
+        let node = self.get_node(&swap.sending_node).await?;
+        let payment_status = node
+            .persister
+            .read_payment_info(&swap.payment_hash(), false, &self.logger)
+            .map(|p| p.status);
+
+        match swap.status {
+            SwapStatus::Created => {
+                // the payment may have gone out before a restart
+                let label = format!("Swap: {}", swap.id);
+                match node
+                    .init_invoice_payment(
+                        &swap.invoice,
+                        None,
+                        PaymentOptions::default(),
+                        vec![label],
+                    )
+                    .await
+                {
+                    Ok(_) | Err(MutinyError::NonUniquePaymentHash) => {
+                        swap.status = SwapStatus::InvoicePaid
+                    }
+                    Err(e) => swap.fail(format!("could not pay the swap invoice: {e}")),
+                }
+            }
+            SwapStatus::InvoicePaid => {
+                if payment_status == Some(HTLCStatus::Failed) {
+                    swap.fail("the swap invoice payment failed".to_string());
+                } else if let Some((lockup, value)) = self.find_swap_lockup(&swap).await? {
+                    if value < swap.onchain_amount_sats {
+                        swap.fail(format!(
+                            "Boltz locked up {value} sats instead of {}",
+                            swap.onchain_amount_sats
+                        ));
+                    } else {
+                        let sats_per_kw = self
+                            .fee_estimator
+                            .get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
+                        let tx = build_claim_transaction(&swap, lockup, value, sats_per_kw)?;
+                        self.broadcast_transaction(tx.clone()).await?;
+                        log_info!(self.logger, "Claimed swap {}: {}", swap.id, tx.txid());
+                        swap.lockup_txid = Some(lockup.txid);
+                        swap.claim_txid = Some(tx.txid());
+                        swap.claim_sats_per_kw = Some(sats_per_kw);
+                        swap.status = SwapStatus::Claimed;
+                    }
+                } else if self.esplora.get_height().await? >= swap.timeout_block_height {
+                    swap.fail("Boltz did not lock up the funds in time".to_string());
+                }
+            }
+            SwapStatus::Claimed => {
+                let claim_confirmed = match swap.claim_txid {
+                    Some(txid) => self
+                        .esplora
+                        .get_tx_status(&txid)
+                        .await?
+                        .is_some_and(|s| s.confirmed),
+                    None => false,
+                };
+                if claim_confirmed {
+                    if payment_status == Some(HTLCStatus::Succeeded) {
+                        swap.status = SwapStatus::Completed;
+                    }
+                } else {
+                    self.rebroadcast_swap_claim(&mut swap).await?;
+                }
+            }
+            SwapStatus::Completed | SwapStatus::Failed(_) => return Ok(swap),
+        }
+
+        self.storage.persist_swap(&swap)?;
+        Ok(swap)
+    }
+}
+
+impl<S: MutinyStorage> NodeManager<S> {
+    /// Rebroadcasts an unconfirmed claim, it may have been dropped from the mempool.
+    /// The claim is replaced with a higher fee one if fee estimates went up,
+    /// or if the lockup is close to timing out.
+    async fn rebroadcast_swap_claim(&self, swap: &mut SwapOut) -> Result<(), MutinyError> {
+        let Some((lockup, value)) = self.find_swap_lockup(swap).await? else {
+            return Ok(());
+        };
+
+        let height = self.esplora.get_height().await?;
+        let target = if height + CLAIM_URGENT_BLOCKS >= swap.timeout_block_height {
+            ConfirmationTarget::HighPriority
+        } else {
+            ConfirmationTarget::Normal
+        };
+        let sats_per_kw = self
+            .fee_estimator
+            .get_est_sat_per_1000_weight(target)
+            .max(swap.claim_sats_per_kw.unwrap_or_default());
+
+        // signing is deterministic, so the same fee rate rebuilds the same transaction
+        let tx = build_claim_transaction(swap, lockup, value, sats_per_kw)?;
+        if swap.claim_txid != Some(tx.txid()) {
+            log_info!(
+                self.logger,
+                "Bumping claim of swap {} to {sats_per_kw} sats/kw: {}",
+                swap.id,
+                tx.txid()
+            );
+        }
+        // fails if the claim is still in the mempool, which is fine
+        if let Err(e) = self.broadcast_transaction(tx.clone()).await {
+            log_debug!(self.logger, "Could not rebroadcast swap claim: {e}");
+        }
+        swap.claim_txid = Some(tx.txid());
+        swap.claim_sats_per_kw = Some(sats_per_kw);
+
+        Ok(())
+    }
+
+    /// Finds the confirmed output Boltz locked the swap funds up in.
+    /// Claiming reveals the preimage, so an unconfirmed lockup is not trusted.
+    async fn find_swap_lockup(
+        &self,
+        swap: &SwapOut,
+    ) -> Result<Option<(OutPoint, u64)>, MutinyError> {
+        let script = swap.lockup_address.script_pubkey();
+        let txs = self.esplora.scripthash_txs(&script, None).await?;
+
+        Ok(txs.iter().filter(|tx| tx.status.confirmed).find_map(|tx| {
+            tx.vout
+                .iter()
+                .position(|v| v.scriptpubkey == script)
+                .map(|vout| (OutPoint::new(tx.txid, vout as u32), tx.vout[vout].value))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::blockdata::opcodes::all::{
+        OP_CHECKSIG, OP_CLTV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160,
+        OP_IF, OP_SIZE,
+    };
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::secp256k1::ecdsa::Signature;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const INVOICE: &str = "lntbs1m1pjrmuu3pp52hk0j956d7s8azaps87amadshnrcvqtkvk06y2nue2w69g6e5vasdqqcqzpgxqyz5vqsp5wu3py6257pa3yzarw0et2200c08r5fu6k3u94yfwmlnc8skdkc9s9qyyssqc783940p82c64qq9pu3xczt4tdxzex9wpjn54486y866aayft2cxxusl9eags4cs3kcmuqdrvhvs0gudpj5r2a6awu4wcq29crpesjcqhdju55";
+
+    fn reverse_swap_script(preimage: &[u8; 32], claim: &PublicKey, refund: &PublicKey) -> Script {
+        Builder::new()
+            .push_opcode(OP_SIZE)
+            .push_int(32)
+            .push_opcode(OP_EQUAL)
+            .push_opcode(OP_IF)
+            .push_opcode(OP_HASH160)
+            .push_slice(&hash160::Hash::hash(preimage)[..])
+            .push_opcode(OP_EQUALVERIFY)
+            .push_slice(&claim.serialize())
+            .push_opcode(OP_ELSE)
+            .push_opcode(OP_DROP)
+            .push_int(800_000)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_slice(&refund.serialize())
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    #[test]
+    fn test_swap_out_quote() {
+        let test_name = "test_swap_out_quote";
+        log!("{}", test_name);
+
+        let pairs: BoltzPairs = serde_json::from_str(
+            r#"{"pairs": {"BTC/BTC": {
+                "hash": "abc",
+                "rate": 1,
+                "limits": {"maximal": 10000000, "minimal": 10000},
+                "fees": {
+                    "percentage": 0.5,
+                    "percentageSwapIn": 0.1,
+                    "minerFees": {"baseAsset": {"normal": 3400, "reverse": {"claim": 2760, "lockup": 3060}}}
+                }
+            }}}"#,
+        )
+        .unwrap();
+        let pair = pairs.pairs.get(BOLTZ_PAIR).unwrap();
+
+        let quote = SwapOutQuote::from_pair(pair, 100_000).unwrap();
+        assert_eq!(quote.service_fee_sats, 500);
+        assert_eq!(quote.lockup_fee_sats, 3_060);
+        assert_eq!(quote.claim_fee_sats, 2_760);
+        assert_eq!(quote.onchain_amount_sats, 100_000 - 500 - 3_060 - 2_760);
+
+        assert!(SwapOutQuote::from_pair(pair, 1_000).is_err());
+        assert!(SwapOutQuote::from_pair(pair, 100_000_000).is_err());
+    }
+
+    #[test]
+    fn test_claim_transaction() {
+        let test_name = "test_claim_transaction";
+        log!("{}", test_name);
+
+        let secp = Secp256k1::new();
+        let preimage = [7u8; 32];
+        let claim_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let claim_pubkey = claim_key.public_key(&secp);
+        let refund_pubkey = SecretKey::from_slice(&[2u8; 32]).unwrap().public_key(&secp);
+
+        let redeem_script = reverse_swap_script(&preimage, &claim_pubkey, &refund_pubkey);
+        let lockup_address = Address::p2wsh(&redeem_script, Network::Regtest);
+        assert!(verify_redeem_script(
+            &redeem_script,
+            &lockup_address,
+            &preimage,
+            &claim_pubkey,
+            Network::Regtest,
+        ));
+        // a script that doesn't pay us or use our preimage is rejected
+        let other_script = reverse_swap_script(&[8u8; 32], &claim_pubkey, &refund_pubkey);
+        assert!(!verify_redeem_script(
+            &other_script,
+            &Address::p2wsh(&other_script, Network::Regtest),
+            &preimage,
+            &claim_pubkey,
+            Network::Regtest,
+        ));
+        assert!(!verify_redeem_script(
+            &redeem_script,
+            &lockup_address,
+            &preimage,
+            &refund_pubkey,
+            Network::Regtest,
+        ));
+
+        let swap = SwapOut {
+            id: "swap".to_string(),
+            status: SwapStatus::InvoicePaid,
+            sending_node: claim_pubkey,
+            amount_sats: 100_000,
+            onchain_amount_sats: 96_440,
+            invoice: Invoice::from_str(INVOICE).unwrap(),
+            address: Address::p2wpkh(&bitcoin::PublicKey::new(refund_pubkey), Network::Regtest)
+                .unwrap(),
+            lockup_address,
+            redeem_script: redeem_script.clone(),
+            timeout_block_height: 800_000,
+            preimage,
+            claim_key,
+            lockup_txid: None,
+            claim_txid: None,
+            claim_sats_per_kw: None,
+            created_at: 0,
+        };
+
+        let lockup = OutPoint::new(Txid::all_zeros(), 0);
+        let tx = build_claim_transaction(&swap, lockup, 96_440, 253).unwrap();
+        let fee = 96_440 - tx.output[0].value;
+        assert_eq!(fee, 253 * tx.weight() as u64 / 1_000);
+        assert_eq!(tx.output[0].script_pubkey, swap.address.script_pubkey());
+
+        let witness = tx.input[0].witness.to_vec();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], preimage.to_vec());
+        assert_eq!(witness[2], redeem_script.to_bytes());
+
+        let sighash = SighashCache::new(&tx)
+            .segwit_signature_hash(0, &redeem_script, 96_440, EcdsaSighashType::All)
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        let signature = Signature::from_der(&witness[0][..witness[0].len() - 1]).unwrap();
+        assert!(secp
+            .verify_ecdsa(&message, &signature, &claim_pubkey)
+            .is_ok());
+
+        // the lockup has to be worth more than the fee
+        assert!(build_claim_transaction(&swap, lockup, 600, 253).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::utils;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, RwLock};
+
+/// The part of a sync that is currently running
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncStage {
+    /// No sync has been started yet
+    NotStarted,
+    /// Updating fee estimates
+    Fees,
+    /// Syncing the lightning channels with the blockchain
+    Lightning,
+    /// Syncing the on-chain wallet with the blockchain
+    OnChain,
+    /// Rescanning the on-chain wallet with a wider stop gap, see `NodeManager::full_rescan`
+    Rescan,
+    /// The last sync completed
+    Synced,
+    /// The last sync failed, see the last error
+    Failed,
+}
+
+impl SyncStage {
+    /// How far along a sync is when it reaches this stage
+    fn percent(&self) -> u8 {
+        match self {
+            SyncStage::NotStarted => 0,
+            SyncStage::Fees => 0,
+            SyncStage::Lightning => 10,
+            SyncStage::OnChain => 50,
+            SyncStage::Rescan => 0,
+            SyncStage::Synced => 100,
+            SyncStage::Failed => 100,
+        }
+    }
+}
+
+/// The progress of syncing the wallet with the blockchain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub stage: SyncStage,
+    /// How far along the current sync is, from 0 to 100
+    pub percent: u8,
+    /// Unix timestamp, in seconds, of the last sync that completed
+    pub last_synced: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        Self {
+            stage: SyncStage::NotStarted,
+            percent: 0,
+            last_synced: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Keeps track of the [SyncStatus] and sends every change to its subscribers
+#[derive(Default)]
+pub(crate) struct SyncStatusTracker {
+    status: RwLock<SyncStatus>,
+    subscribers: Mutex<Vec<UnboundedSender<SyncStatus>>>,
+}
+
+impl SyncStatusTracker {
+    pub fn status(&self) -> SyncStatus {
+        self.status.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Returns a stream of every change to the sync status, starting with the current one
+    pub fn subscribe(&self) -> UnboundedReceiver<SyncStatus> {
+        let (sender, receiver) = unbounded();
+        // can't fail, we are still holding the receiver
+        let _ = sender.unbounded_send(self.status());
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Ends the streams of all the subscribers
+    pub fn unsubscribe_all(&self) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.clear();
+        }
+    }
+
+    /// Moves the sync to the given stage
+    pub fn set_stage(&self, stage: SyncStage) {
+        self.update(|status| {
+            status.stage = stage;
+            status.percent = stage.percent();
+        });
+    }
+
+    /// Updates how far along the current stage is, from 0 to 100
+    pub fn set_progress(&self, stage: SyncStage, percent: u8) {
+        self.update(|status| {
+            status.stage = stage;
+            status.percent = percent.min(100);
+        });
+    }
+
+    /// Marks the sync as completed
+    pub fn synced(&self) {
+        self.update(|status| {
+            status.stage = SyncStage::Synced;
+            status.percent = SyncStage::Synced.percent();
+            status.last_synced = Some(utils::now().as_secs());
+            status.last_error = None;
+        });
+    }
+
+    /// Marks the sync as failed with the given error
+    pub fn failed(&self, error: String) {
+        self.update(|status| {
+            status.stage = SyncStage::Failed;
+            status.percent = SyncStage::Failed.percent();
+            status.last_error = Some(error);
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SyncStatus)) {
+        let status = match self.status.write() {
+            Ok(mut status) => {
+                f(&mut status);
+                status.clone()
+            }
+            Err(_) => return,
+        };
+
+        // drop subscribers that have gone away
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|s| s.unbounded_send(status.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_sync_status_updates() {
+        let test_name = "test_sync_status_updates";
+        log!("{}", test_name);
+
+        let tracker = SyncStatusTracker::default();
+        let mut receiver = tracker.subscribe();
+        assert_eq!(receiver.try_next().unwrap(), Some(SyncStatus::default()));
+
+        tracker.set_stage(SyncStage::Lightning);
+        let status = receiver.try_next().unwrap().unwrap();
+        assert_eq!(status.stage, SyncStage::Lightning);
+        assert_eq!(status.percent, 10);
+
+        tracker.set_progress(SyncStage::Rescan, 150);
+        let status = receiver.try_next().unwrap().unwrap();
+        assert_eq!(status.stage, SyncStage::Rescan);
+        assert_eq!(status.percent, 100);
+
+        tracker.failed("esplora is down".to_string());
+        let status = receiver.try_next().unwrap().unwrap();
+        assert_eq!(status.stage, SyncStage::Failed);
+        assert_eq!(status.last_error, Some("esplora is down".to_string()));
+        assert_eq!(status.last_synced, None);
+
+        tracker.synced();
+        let status = receiver.try_next().unwrap().unwrap();
+        assert_eq!(status.stage, SyncStage::Synced);
+        assert_eq!(status.last_error, None);
+        assert!(status.last_synced.is_some());
+        assert_eq!(tracker.status(), status);
+
+        // dropped subscribers are cleaned up
+        drop(receiver);
+        tracker.set_stage(SyncStage::Fees);
+        assert!(tracker.subscribers.lock().unwrap().is_empty());
+
+        // unsubscribing ends the stream
+        let mut receiver = tracker.subscribe();
+        tracker.unsubscribe_all();
+        assert!(receiver.try_next().unwrap().is_some());
+        assert_eq!(receiver.try_next().unwrap(), None);
+    }
+}
@@ -9,3 +9,27 @@ macro_rules! log {
     }
 #[allow(unused_imports)]
 pub(crate) use log;
+
+use crate::onionmessage::{CustomOnionMessage, OnionMessageHandler};
+use crate::peermessage::{CustomPeerMessage, PeerMessageHandler};
+use bitcoin::secp256k1::PublicKey;
+use std::sync::Mutex;
+
+/// Keeps the onion and peer messages it is handed so tests can check them
+#[derive(Default)]
+pub struct TestMessageHandler {
+    pub onion_messages: Mutex<Vec<CustomOnionMessage>>,
+    pub peer_messages: Mutex<Vec<(PublicKey, CustomPeerMessage)>>,
+}
+
+impl OnionMessageHandler for TestMessageHandler {
+    fn handle_onion_message(&self, message: CustomOnionMessage) {
+        self.onion_messages.lock().unwrap().push(message);
+    }
+}
+
+impl PeerMessageHandler for TestMessageHandler {
+    fn handle_peer_message(&self, from: PublicKey, message: CustomPeerMessage) {
+        self.peer_messages.lock().unwrap().push((from, message));
+    }
+}
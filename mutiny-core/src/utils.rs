@@ -14,6 +14,18 @@ pub(crate) fn min_lightning_amount(network: Network) -> u64 {
     }
 }
 
+/// Converts millisatoshis to satoshis, rounding down.
+/// Used for amounts so we never show more than can actually be spent.
+pub(crate) fn msats_to_sats(msats: u64) -> u64 {
+    msats / 1_000
+}
+
+/// Converts millisatoshis to satoshis, rounding up.
+/// Used for fees so sub-satoshi fees are never shown as free.
+pub(crate) fn msats_to_sats_round_up(msats: u64) -> u64 {
+    (msats + 999) / 1_000
+}
+
 pub async fn sleep(millis: i32) {
     #[cfg(target_arch = "wasm32")]
     {
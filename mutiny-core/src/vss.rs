@@ -0,0 +1,454 @@
+use crate::encrypt::Cipher;
+use crate::error::MutinyError;
+use crate::ldkstorage::{
+    CHANNEL_MANAGER_KEY, MONITORS_PREFIX_KEY, PAYMENT_INBOUND_PREFIX_KEY,
+    PAYMENT_OUTBOUND_PREFIX_KEY,
+};
+use crate::logging::MutinyLogger;
+use crate::storage::{MutinyStorage, NODES_KEY};
+use crate::utils::sleep;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey};
+use lightning::util::logger::*;
+use lightning::{log_debug, log_error, log_info, log_warn};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// How often pending writes are uploaded, in milliseconds
+const VSS_SYNC_INTERVAL_MS: i32 = 5_000;
+
+/// The longest we wait between retries when the server is unreachable, in milliseconds
+const VSS_MAX_BACKOFF_MS: i32 = 5 * 60 * 1_000;
+
+/// Returns if the key holds state we can't recover from the seed alone
+/// and needs to be mirrored to the backup server.
+pub(crate) fn needs_backup(key: &str) -> bool {
+    match key {
+        NODES_KEY => true,
+        str if str.starts_with(CHANNEL_MANAGER_KEY) => true,
+        str if str.starts_with(MONITORS_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_INBOUND_PREFIX_KEY) => true,
+        str if str.starts_with(PAYMENT_OUTBOUND_PREFIX_KEY) => true,
+        _ => false,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct KeyValue {
+    key: String,
+    /// The value encrypted with our backup key
+    value: String,
+    version: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct KeyVersion {
+    key: String,
+    version: i64,
+}
+
+#[derive(Serialize)]
+struct PutObjectsRequest<'a> {
+    store_id: &'a str,
+    transaction_items: Vec<KeyValue>,
+    delete_items: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct GetObjectRequest<'a> {
+    store_id: &'a str,
+    key: &'a str,
+}
+
+#[derive(Deserialize)]
+struct GetObjectResponse {
+    value: KeyValue,
+}
+
+#[derive(Serialize)]
+struct ListKeyVersionsRequest<'a> {
+    store_id: &'a str,
+    key_prefix: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ListKeyVersionsResponse {
+    key_versions: Vec<KeyVersion>,
+}
+
+/// Mirrors critical storage keys to a remote versioned storage server.
+///
+/// Values are encrypted with a key derived from the seed before they are uploaded,
+/// so the server never sees any channel or payment data. Writes are queued and
+/// uploaded in the background, failed uploads are retried with backoff.
+/// Deletes are queued the same way.
+pub struct VssBackup {
+    url: String,
+    store_id: String,
+    cipher: Cipher,
+    client: Client,
+    /// Writes that have not been uploaded yet with their sequence number,
+    /// only the latest value of a key is kept, `None` deletes the key
+    pending: RwLock<HashMap<String, (Option<Value>, u64)>>,
+    /// The server's version of each key, needed to overwrite it
+    versions: RwLock<HashMap<String, i64>>,
+    /// The sequence number of the last write queued for each key and of the last one uploaded
+    sequences: RwLock<HashMap<String, (u64, u64)>>,
+    next_sequence: AtomicU64,
+    logger: Arc<MutinyLogger>,
+}
+
+impl VssBackup {
+    /// Creates a backup client for the given server. The encryption key and
+    /// store id are both derived from the wallet's master key.
+    pub fn new(
+        url: String,
+        xprivkey: ExtendedPrivKey,
+        client: Client,
+        logger: Arc<MutinyLogger>,
+    ) -> Result<Self, MutinyError> {
+        let context = Secp256k1::new();
+        let key = xprivkey.derive_priv(
+            &context,
+            &[
+                ChildNumber::from_hardened_idx(138)?,
+                ChildNumber::from_hardened_idx(0)?,
+            ],
+        )?;
+        let secret = key.private_key.secret_bytes();
+        // the store id only needs to be unique, hashing keeps the key private
+        let store_id = sha256::Hash::hash(&secret).to_hex();
+
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            store_id,
+            cipher: Cipher::from_key(secret),
+            client,
+            pending: RwLock::new(HashMap::new()),
+            versions: RwLock::new(HashMap::new()),
+            sequences: RwLock::new(HashMap::new()),
+            next_sequence: AtomicU64::new(1),
+            logger,
+        })
+    }
+
+    /// Queues a value to be uploaded on the next sync
+    pub(crate) fn queue(&self, key: &str, value: Value) {
+        self.queue_write(key, Some(value))
+    }
+
+    /// Queues the keys that need a backup to be deleted on the next sync
+    pub fn queue_deletes(&self, keys: &[impl AsRef<str>]) {
+        for key in keys.iter().filter(|k| needs_backup(k.as_ref())) {
+            self.queue_write(key.as_ref(), None)
+        }
+    }
+
+    fn queue_write(&self, key: &str, value: Option<Value>) {
+        let sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed);
+        match (self.pending.write(), self.sequences.write()) {
+            (Ok(mut pending), Ok(mut sequences)) => {
+                pending.insert(key.to_string(), (value, sequence));
+                sequences.entry(key.to_string()).or_default().0 = sequence;
+            }
+            _ => log_error!(self.logger, "Could not queue {key} for backup"),
+        }
+    }
+
+    /// Returns the sequence number of the last write queued for the key
+    pub(crate) fn last_queued(&self, key: &str) -> Option<u64> {
+        let sequences = self.sequences.read().ok()?;
+        sequences.get(key).map(|(queued, _)| *queued)
+    }
+
+    /// Returns if the write with the given sequence number, or a newer one, has been uploaded
+    pub(crate) fn is_uploaded(&self, key: &str, sequence: u64) -> bool {
+        self.sequences
+            .read()
+            .map(|s| {
+                s.get(key)
+                    .is_some_and(|(_, uploaded)| *uploaded >= sequence)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns how many writes are waiting to be uploaded
+    pub fn pending_count(&self) -> usize {
+        self.pending.read().map(|p| p.len()).unwrap_or_default()
+    }
+
+    /// Uploads all the pending writes in one transaction.
+    /// On failure the writes are put back in the queue to be retried.
+    pub async fn flush(&self) -> Result<(), MutinyError> {
+        let batch: HashMap<String, (Option<Value>, u64)> = {
+            let mut pending = self.pending.write().map_err(|_| MutinyError::VssError)?;
+            pending.drain().collect()
+        };
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        match self.put_objects(&batch).await {
+            Ok(()) => {
+                log_debug!(self.logger, "Backed up {} keys", batch.len());
+                let mut sequences = self.sequences.write().map_err(|_| MutinyError::VssError)?;
+                for (key, (_, sequence)) in batch {
+                    let entry = sequences.entry(key).or_default();
+                    entry.1 = entry.1.max(sequence);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                // requeue, keeping any newer writes made while we were uploading
+                if let Ok(mut pending) = self.pending.write() {
+                    for (key, value) in batch {
+                        pending.entry(key).or_insert(value);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn put_objects(
+        &self,
+        batch: &HashMap<String, (Option<Value>, u64)>,
+    ) -> Result<(), MutinyError> {
+        let mut items = Vec::new();
+        let mut delete_items = Vec::new();
+        {
+            let versions = self.versions.read().map_err(|_| MutinyError::VssError)?;
+            for (key, (value, _)) in batch {
+                let version = versions.get(key).copied().unwrap_or(0);
+                match value {
+                    Some(value) => items.push(KeyValue {
+                        key: key.clone(),
+                        value: self.cipher.encrypt(&serde_json::to_string(value)?)?,
+                        version,
+                    }),
+                    // nothing to delete if the server never had the key
+                    None if versions.contains_key(key) => delete_items.push(KeyValue {
+                        key: key.clone(),
+                        value: String::new(),
+                        version,
+                    }),
+                    None => {}
+                }
+            }
+        }
+
+        let request = PutObjectsRequest {
+            store_id: &self.store_id,
+            transaction_items: items,
+            delete_items,
+        };
+        let resp = self
+            .client
+            .post(format!("{}/putObjects", self.url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|_| MutinyError::VssError)?;
+
+        if resp.status() == StatusCode::CONFLICT {
+            // another device wrote to the store, get the latest versions and retry
+            log_warn!(self.logger, "Backup version conflict, refreshing versions");
+            self.refresh_versions().await?;
+            return Err(MutinyError::VssError);
+        }
+        resp.error_for_status().map_err(|_| MutinyError::VssError)?;
+
+        let mut versions = self.versions.write().map_err(|_| MutinyError::VssError)?;
+        for item in request.transaction_items {
+            versions.insert(item.key, item.version + 1);
+        }
+        for item in request.delete_items {
+            versions.remove(&item.key);
+        }
+
+        Ok(())
+    }
+
+    async fn list_key_versions(&self) -> Result<Vec<KeyVersion>, MutinyError> {
+        let request = ListKeyVersionsRequest {
+            store_id: &self.store_id,
+            key_prefix: "",
+        };
+        let resp: ListKeyVersionsResponse = self
+            .client
+            .post(format!("{}/listKeyVersions", self.url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|_| MutinyError::VssError)?
+            .error_for_status()
+            .map_err(|_| MutinyError::VssError)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::VssError)?;
+
+        Ok(resp.key_versions)
+    }
+
+    /// Gets the server's current version of every key
+    pub async fn refresh_versions(&self) -> Result<(), MutinyError> {
+        let key_versions = self.list_key_versions().await?;
+        let mut versions = self.versions.write().map_err(|_| MutinyError::VssError)?;
+        *versions = key_versions
+            .into_iter()
+            .map(|kv| (kv.key, kv.version))
+            .collect();
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<KeyValue, MutinyError> {
+        let request = GetObjectRequest {
+            store_id: &self.store_id,
+            key,
+        };
+        let resp: GetObjectResponse = self
+            .client
+            .post(format!("{}/getObject", self.url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|_| MutinyError::VssError)?
+            .error_for_status()
+            .map_err(|_| MutinyError::VssError)?
+            .json()
+            .await
+            .map_err(|_| MutinyError::VssError)?;
+
+        Ok(resp.value)
+    }
+
+    /// Downloads every backed up key and writes it to local storage,
+    /// overwriting what is there. Returns the number of keys restored.
+    ///
+    /// This should be done before the nodes are started.
+    pub async fn restore<S: MutinyStorage>(&self, storage: &S) -> Result<usize, MutinyError> {
+        let key_versions = self.list_key_versions().await?;
+        let mut versions = HashMap::with_capacity(key_versions.len());
+
+        for kv in key_versions {
+            let obj = self.get_object(&kv.key).await?;
+            let plaintext = self.cipher.decrypt(&obj.value)?;
+            let value: Value = serde_json::from_str(&plaintext)?;
+            storage.set_data(&obj.key, value)?;
+            versions.insert(obj.key, obj.version);
+        }
+
+        let count = versions.len();
+        *self.versions.write().map_err(|_| MutinyError::VssError)? = versions;
+        log_info!(self.logger, "Restored {count} keys from backup");
+
+        Ok(count)
+    }
+
+    /// Creates a background process that uploads queued writes until stopped.
+    pub(crate) fn start(backup: Arc<VssBackup>, stop: Arc<AtomicBool>) {
+        crate::utils::spawn(async move {
+            if let Err(e) = backup.refresh_versions().await {
+                log_warn!(backup.logger, "Could not get backup versions: {e}");
+            }
+
+            let mut delay = VSS_SYNC_INTERVAL_MS;
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    // one last attempt so we don't lose writes made while stopping
+                    if let Err(e) = backup.flush().await {
+                        log_error!(backup.logger, "Failed to back up before stopping: {e}");
+                    }
+                    break;
+                }
+
+                match backup.flush().await {
+                    Ok(()) => delay = VSS_SYNC_INTERVAL_MS,
+                    Err(e) => {
+                        delay = (delay * 2).min(VSS_MAX_BACKOFF_MS);
+                        log_warn!(backup.logger, "Backup failed, retrying in {delay}ms: {e}");
+                    }
+                }
+
+                sleep(delay).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::Network;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_needs_backup() {
+        let test_name = "test_needs_backup";
+        log!("{}", test_name);
+
+        assert!(needs_backup(NODES_KEY));
+        assert!(needs_backup("monitors/abc_123"));
+        assert!(needs_backup("payment_inbound/abc_123"));
+        assert!(!needs_backup("mnemonic"));
+        assert!(!needs_backup("network_graph"));
+    }
+
+    #[test]
+    async fn test_queue_and_requeue() {
+        let test_name = "test_queue_and_requeue";
+        log!("{}", test_name);
+
+        let xprivkey = ExtendedPrivKey::new_master(Network::Regtest, &[0; 32]).unwrap();
+        // nothing is listening here, so uploads will fail
+        let backup = VssBackup::new(
+            "http://127.0.0.1:1/vss/".to_string(),
+            xprivkey,
+            Client::new(),
+            Arc::new(MutinyLogger::default()),
+        )
+        .unwrap();
+        assert_eq!(backup.url, "http://127.0.0.1:1/vss");
+
+        backup.queue(NODES_KEY, Value::from(1));
+        backup.queue(NODES_KEY, Value::from(2));
+        assert_eq!(backup.pending_count(), 1);
+
+        assert!(backup.flush().await.is_err());
+        // the failed write is still queued
+        assert_eq!(backup.pending_count(), 1);
+        assert_eq!(
+            backup
+                .pending
+                .read()
+                .unwrap()
+                .get(NODES_KEY)
+                .and_then(|(v, _)| v.clone()),
+            Some(Value::from(2))
+        );
+        let sequence = backup.last_queued(NODES_KEY).unwrap();
+        assert!(!backup.is_uploaded(NODES_KEY, sequence));
+
+        // only keys that are backed up have their deletes mirrored
+        backup.queue_deletes(&[NODES_KEY, "network_graph"]);
+        assert_eq!(backup.pending_count(), 1);
+        assert!(backup
+            .pending
+            .read()
+            .unwrap()
+            .get(NODES_KEY)
+            .unwrap()
+            .0
+            .is_none());
+        assert!(backup.last_queued(NODES_KEY).unwrap() > sequence);
+    }
+}
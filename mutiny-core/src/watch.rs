@@ -0,0 +1,444 @@
+use crate::error::MutinyError;
+use crate::esplora_failover::FailoverEsploraClient;
+use crate::nodemanager::NodeManager;
+use crate::storage::MutinyStorage;
+use bdk::chain::ConfirmationTime;
+use bitcoin::{Address, Script, Txid};
+use esplora_client::{Tx, TxStatus};
+use lightning::{log_warn, util::logger::Logger};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+pub(crate) const WATCHED_ADDRESSES_KEY: &str = "watched_addresses";
+pub(crate) const WATCHED_TXIDS_KEY: &str = "watched_txids";
+pub(crate) const EXTERNAL_TX_KEY_PREFIX: &str = "external_tx/";
+
+/// Esplora returns the confirmed history of an address in pages of this size
+const ESPLORA_PAGE_SIZE: usize = 25;
+
+/// A third-party address the wallet tracks, such as a cold storage address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WatchedAddress {
+    pub address: Address,
+    pub label: Option<String>,
+}
+
+/// A transaction that is not the wallet's own, found through a watched address or txid.
+/// It shows up in the activity but its funds can't be spent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExternalTransaction {
+    pub txid: Txid,
+    /// The watched address it paid, None if the txid itself was watched
+    pub address: Option<Address>,
+    pub label: Option<String>,
+    /// The sats paid to the watched address, or the total output value for a watched txid
+    pub amount_sats: u64,
+    /// The sats spent from the watched address, always 0 for a watched txid
+    #[serde(default)]
+    pub sent_sats: u64,
+    pub confirmation_time: ConfirmationTime,
+}
+
+impl ExternalTransaction {
+    /// A transaction can pay several watched addresses, so each one gets its own entry
+    pub fn id(&self) -> String {
+        match self.address {
+            Some(ref address) => format!("{address}/{}", self.txid),
+            None => self.txid.to_string(),
+        }
+    }
+}
+
+impl PartialOrd for ExternalTransaction {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ExternalTransaction {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.confirmation_time
+            .cmp(&other.confirmation_time)
+            .then_with(|| self.txid.cmp(&other.txid))
+    }
+}
+
+fn status_confirmation_time(status: &TxStatus) -> ConfirmationTime {
+    match (status.confirmed, status.block_height, status.block_time) {
+        (true, Some(height), Some(time)) => ConfirmationTime::Confirmed { height, time },
+        _ => ConfirmationTime::Unconfirmed,
+    }
+}
+
+/// The transaction as seen from a watched address, the outputs paying
+/// it are received and the inputs spending its outputs are sent
+fn address_transaction(tx: &Tx, watched: &WatchedAddress, script: &Script) -> ExternalTransaction {
+    let amount_sats = tx
+        .vout
+        .iter()
+        .filter(|v| &v.scriptpubkey == script)
+        .map(|v| v.value)
+        .sum();
+    let sent_sats = tx
+        .vin
+        .iter()
+        .filter_map(|v| v.prevout.as_ref())
+        .filter(|prevout| &prevout.scriptpubkey == script)
+        .map(|prevout| prevout.value)
+        .sum();
+
+    ExternalTransaction {
+        txid: tx.txid,
+        address: Some(watched.address.clone()),
+        label: watched.label.clone(),
+        amount_sats,
+        sent_sats,
+        confirmation_time: status_confirmation_time(&tx.status),
+    }
+}
+
+/// Every transaction of the address, following the pages esplora splits its history into
+async fn address_history(
+    esplora: &FailoverEsploraClient,
+    script: &Script,
+) -> Result<Vec<Tx>, esplora_client::Error> {
+    let mut txs: Vec<Tx> = Vec::new();
+    let mut last_seen = None;
+    loop {
+        let page = esplora.scripthash_txs(script, last_seen).await?;
+        let confirmed = page.iter().filter(|tx| tx.status.confirmed).count();
+        last_seen = page.last().map(|tx| tx.txid);
+        txs.extend(page);
+
+        // a full page of confirmed transactions means there may be more
+        if confirmed < ESPLORA_PAGE_SIZE {
+            return Ok(txs);
+        }
+    }
+}
+
+pub trait WatchStorage {
+    fn get_watched_addresses(&self) -> Result<Vec<WatchedAddress>, MutinyError>;
+    fn set_watched_addresses(&self, addresses: Vec<WatchedAddress>) -> Result<(), MutinyError>;
+    fn get_watched_txids(&self) -> Result<HashSet<Txid>, MutinyError>;
+    fn set_watched_txids(&self, txids: HashSet<Txid>) -> Result<(), MutinyError>;
+    fn get_external_transactions(&self) -> Result<Vec<ExternalTransaction>, MutinyError>;
+    fn persist_external_transaction(&self, tx: &ExternalTransaction) -> Result<(), MutinyError>;
+    fn delete_external_transactions(&self, txs: &[ExternalTransaction]) -> Result<(), MutinyError>;
+}
+
+impl<S: MutinyStorage> WatchStorage for S {
+    fn get_watched_addresses(&self) -> Result<Vec<WatchedAddress>, MutinyError> {
+        let res: Option<Vec<WatchedAddress>> = self.get_data(WATCHED_ADDRESSES_KEY)?;
+        Ok(res.unwrap_or_default())
+    }
+
+    fn set_watched_addresses(&self, addresses: Vec<WatchedAddress>) -> Result<(), MutinyError> {
+        self.set_data(WATCHED_ADDRESSES_KEY, addresses)
+    }
+
+    fn get_watched_txids(&self) -> Result<HashSet<Txid>, MutinyError> {
+        let res: Option<HashSet<Txid>> = self.get_data(WATCHED_TXIDS_KEY)?;
+        Ok(res.unwrap_or_default())
+    }
+
+    fn set_watched_txids(&self, txids: HashSet<Txid>) -> Result<(), MutinyError> {
+        self.set_data(WATCHED_TXIDS_KEY, txids)
+    }
+
+    fn get_external_transactions(&self) -> Result<Vec<ExternalTransaction>, MutinyError> {
+        let map: HashMap<String, ExternalTransaction> = self.scan(EXTERNAL_TX_KEY_PREFIX, None)?;
+        let mut txs: Vec<ExternalTransaction> = map.into_values().collect();
+        txs.sort();
+        Ok(txs)
+    }
+
+    fn persist_external_transaction(&self, tx: &ExternalTransaction) -> Result<(), MutinyError> {
+        self.set_data(format!("{EXTERNAL_TX_KEY_PREFIX}{}", tx.id()), tx)
+    }
+
+    fn delete_external_transactions(&self, txs: &[ExternalTransaction]) -> Result<(), MutinyError> {
+        let keys: Vec<String> = txs
+            .iter()
+            .map(|tx| format!("{EXTERNAL_TX_KEY_PREFIX}{}", tx.id()))
+            .collect();
+        self.delete(&keys)
+    }
+}
+
+pub trait WatchManager {
+    /// Starts tracking the payments to a third-party address, they show up
+    /// in the activity as read-only items once the next sync finds them.
+    fn watch_address(&self, address: Address, label: Option<String>) -> Result<(), MutinyError>;
+
+    /// Stops tracking the address and removes its transactions from the activity
+    fn unwatch_address(&self, address: &Address) -> Result<(), MutinyError>;
+
+    fn list_watched_addresses(&self) -> Result<Vec<WatchedAddress>, MutinyError>;
+
+    /// Starts tracking the confirmation status of a third-party transaction
+    fn watch_txid(&self, txid: Txid) -> Result<(), MutinyError>;
+
+    /// Stops tracking the transaction and removes it from the activity
+    fn unwatch_txid(&self, txid: &Txid) -> Result<(), MutinyError>;
+
+    fn list_watched_txids(&self) -> Result<Vec<Txid>, MutinyError>;
+
+    /// The transactions found for the watched addresses and txids, sorted by confirmation time
+    fn list_external_transactions(&self) -> Result<Vec<ExternalTransaction>, MutinyError>;
+
+    /// Updates the transactions of the watched addresses and txids
+    async fn sync_watched(&self) -> Result<(), MutinyError>;
+}
+
+impl<S: MutinyStorage> WatchManager for NodeManager<S> {
+    fn watch_address(&self, address: Address, label: Option<String>) -> Result<(), MutinyError> {
+        if !address.is_valid_for_network(self.get_network()) {
+            return Err(MutinyError::IncorrectNetwork(address.network));
+        }
+
+        let mut addresses = self.storage.get_watched_addresses()?;
+        addresses.retain(|w| w.address != address);
+        addresses.push(WatchedAddress { address, label });
+        self.storage.set_watched_addresses(addresses)
+    }
+
+    fn unwatch_address(&self, address: &Address) -> Result<(), MutinyError> {
+        let mut addresses = self.storage.get_watched_addresses()?;
+        addresses.retain(|w| &w.address != address);
+        self.storage.set_watched_addresses(addresses)?;
+
+        let txs: Vec<ExternalTransaction> = self
+            .storage
+            .get_external_transactions()?
+            .into_iter()
+            .filter(|tx| tx.address.as_ref() == Some(address))
+            .collect();
+        self.storage.delete_external_transactions(&txs)
+    }
+
+    fn list_watched_addresses(&self) -> Result<Vec<WatchedAddress>, MutinyError> {
+        self.storage.get_watched_addresses()
+    }
+
+    fn watch_txid(&self, txid: Txid) -> Result<(), MutinyError> {
+        let mut txids = self.storage.get_watched_txids()?;
+        txids.insert(txid);
+        self.storage.set_watched_txids(txids)
+    }
+
+    fn unwatch_txid(&self, txid: &Txid) -> Result<(), MutinyError> {
+        let mut txids = self.storage.get_watched_txids()?;
+        txids.remove(txid);
+        self.storage.set_watched_txids(txids)?;
+
+        // the entries found through a watched address stay
+        let txs: Vec<ExternalTransaction> = self
+            .storage
+            .get_external_transactions()?
+            .into_iter()
+            .filter(|tx| &tx.txid == txid && tx.address.is_none())
+            .collect();
+        self.storage.delete_external_transactions(&txs)
+    }
+
+    fn list_watched_txids(&self) -> Result<Vec<Txid>, MutinyError> {
+        Ok(self.storage.get_watched_txids()?.into_iter().collect())
+    }
+
+    fn list_external_transactions(&self) -> Result<Vec<ExternalTransaction>, MutinyError> {
+        self.storage.get_external_transactions()
+    }
+
+    async fn sync_watched(&self) -> Result<(), MutinyError> {
+        let known: HashMap<String, ExternalTransaction> = self
+            .storage
+            .get_external_transactions()?
+            .into_iter()
+            .map(|tx| (tx.id(), tx))
+            .collect();
+
+        for watched in self.storage.get_watched_addresses()? {
+            let script = watched.address.script_pubkey();
+            let txs = match address_history(&self.esplora, &script).await {
+                Ok(txs) => txs,
+                Err(e) => {
+                    log_warn!(
+                        self.logger,
+                        "Could not sync watched address {}: {e}",
+                        watched.address
+                    );
+                    continue;
+                }
+            };
+
+            let found: HashSet<Txid> = txs.iter().map(|tx| tx.txid).collect();
+            for tx in txs.iter() {
+                let external = address_transaction(tx, &watched, &script);
+                if known.get(&external.id()) != Some(&external) {
+                    self.storage.persist_external_transaction(&external)?;
+                }
+            }
+
+            // unconfirmed transactions that went missing were replaced or evicted
+            let dropped: Vec<ExternalTransaction> = known
+                .values()
+                .filter(|tx| {
+                    tx.address.as_ref() == Some(&watched.address)
+                        && tx.confirmation_time == ConfirmationTime::Unconfirmed
+                        && !found.contains(&tx.txid)
+                })
+                .cloned()
+                .collect();
+            self.storage.delete_external_transactions(&dropped)?;
+        }
+
+        let found_by_address: HashSet<Txid> = self
+            .storage
+            .get_external_transactions()?
+            .into_iter()
+            .filter(|tx| tx.address.is_some())
+            .map(|tx| tx.txid)
+            .collect();
+
+        for txid in self.storage.get_watched_txids()? {
+            // transactions found through an address are already up to date
+            if found_by_address.contains(&txid) {
+                continue;
+            }
+            let existing = known.get(&txid.to_string());
+
+            let status = match self.esplora.get_tx_status(&txid).await {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
+                Err(e) => {
+                    log_warn!(self.logger, "Could not sync watched txid {txid}: {e}");
+                    continue;
+                }
+            };
+
+            // the outputs never change, so we only need to fetch the transaction once
+            let amount_sats = match existing {
+                Some(tx) => tx.amount_sats,
+                None => match self.esplora.get_tx(&txid).await {
+                    Ok(Some(tx)) => tx.output.iter().map(|o| o.value).sum(),
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log_warn!(self.logger, "Could not sync watched txid {txid}: {e}");
+                        continue;
+                    }
+                },
+            };
+
+            let external = ExternalTransaction {
+                txid,
+                address: None,
+                label: None,
+                amount_sats,
+                sent_sats: 0,
+                confirmation_time: status_confirmation_time(&status),
+            };
+            if existing != Some(&external) {
+                self.storage.persist_external_transaction(&external)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::test_utils::*;
+    use bitcoin::hashes::Hash;
+    use std::str::FromStr;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_status_confirmation_time() {
+        let test_name = "test_status_confirmation_time";
+        log!("{}", test_name);
+
+        let confirmed = TxStatus {
+            confirmed: true,
+            block_height: Some(100),
+            block_hash: None,
+            block_time: Some(1_000),
+        };
+        assert_eq!(
+            status_confirmation_time(&confirmed),
+            ConfirmationTime::Confirmed {
+                height: 100,
+                time: 1_000
+            }
+        );
+
+        let unconfirmed = TxStatus {
+            confirmed: false,
+            block_height: None,
+            block_hash: None,
+            block_time: None,
+        };
+        assert_eq!(
+            status_confirmation_time(&unconfirmed),
+            ConfirmationTime::Unconfirmed
+        );
+    }
+
+    #[test]
+    fn test_external_transaction_storage() {
+        let test_name = "test_external_transaction_storage";
+        log!("{}", test_name);
+
+        let storage = MemoryStorage::default();
+        let address = Address::from_str("tb1qd28npep0s8frcm3y7dxqajkcy2m40eysplyr9v").unwrap();
+        let tx = |i: u8, confirmation_time| ExternalTransaction {
+            txid: Txid::from_slice(&[i; 32]).unwrap(),
+            address: Some(address.clone()),
+            label: Some("cold storage".to_string()),
+            amount_sats: 10_000,
+            sent_sats: 0,
+            confirmation_time,
+        };
+        let confirmed = tx(
+            1,
+            ConfirmationTime::Confirmed {
+                height: 100,
+                time: 1_000,
+            },
+        );
+        let pending = tx(2, ConfirmationTime::Unconfirmed);
+
+        storage.persist_external_transaction(&pending).unwrap();
+        storage.persist_external_transaction(&confirmed).unwrap();
+        assert_eq!(
+            storage.get_external_transactions().unwrap(),
+            vec![confirmed.clone(), pending.clone()]
+        );
+
+        storage
+            .delete_external_transactions(&[pending.clone()])
+            .unwrap();
+        assert_eq!(
+            storage.get_external_transactions().unwrap(),
+            vec![confirmed.clone()]
+        );
+
+        // the same transaction spending from one watched address to another
+        let other = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        let spend = ExternalTransaction {
+            address: Some(other),
+            label: None,
+            amount_sats: 0,
+            sent_sats: 10_000,
+            ..confirmed.clone()
+        };
+        storage.persist_external_transaction(&spend).unwrap();
+        assert_ne!(spend.id(), confirmed.id());
+        assert_eq!(storage.get_external_transactions().unwrap().len(), 2);
+    }
+}
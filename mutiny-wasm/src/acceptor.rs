@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+use gloo_utils::format::JsValueSerdeExt;
+use mutiny_core::{ChannelAcceptor, ChannelRequest};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// Asks a JS callback about inbound channels. The callback gets the `ChannelRequest`
+/// and returns a boolean, or a promise of one so it can prompt the user.
+pub(crate) struct JsChannelAcceptor {
+    callback: js_sys::Function,
+}
+
+impl JsChannelAcceptor {
+    pub(crate) fn new(callback: js_sys::Function) -> Self {
+        Self { callback }
+    }
+}
+
+// wasm is single threaded, the callback never leaves this thread
+unsafe impl Send for JsChannelAcceptor {}
+unsafe impl Sync for JsChannelAcceptor {}
+
+#[async_trait(?Send)]
+impl ChannelAcceptor for JsChannelAcceptor {
+    async fn accept_channel(&self, request: ChannelRequest) -> bool {
+        let Ok(request) = JsValue::from_serde(&request) else {
+            return false;
+        };
+        let Ok(answer) = self.callback.call1(&JsValue::NULL, &request) else {
+            return false;
+        };
+        let answer = match answer.dyn_into::<js_sys::Promise>() {
+            Ok(promise) => match JsFuture::from(promise).await {
+                Ok(answer) => answer,
+                Err(_) => return false,
+            },
+            Err(answer) => answer,
+        };
+        answer.as_bool().unwrap_or(false)
+    }
+}
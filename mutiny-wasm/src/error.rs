@@ -24,6 +24,15 @@ pub enum MutinyJsError {
     /// The invoice or address is on a different network
     #[error("The invoice or address is on a different network.")]
     IncorrectNetwork(Network),
+    /// The wallet's saved data is for a different network than the one configured
+    #[error("The wallet was created for a different network.")]
+    NetworkMismatch(Network),
+    /// The wallet's saved data is for a different account index than the one configured
+    #[error("The wallet was created with a different account index.")]
+    AccountIndexMismatch(u32),
+    /// There are still funds on the current network that would be lost by migrating
+    #[error("Move all funds out of the wallet before changing networks.")]
+    NetworkMigrationNotReady,
     /// Payment of the given invoice has already been initiated.
     #[error("An invoice must not get payed twice.")]
     NonUniquePaymentHash,
@@ -33,6 +42,9 @@ pub enum MutinyJsError {
     /// The given invoice is invalid.
     #[error("The given invoice is invalid.")]
     InvoiceInvalid,
+    /// The invoice expired before it was paid.
+    #[error("The invoice has expired.")]
+    InvoiceExpired,
     /// Invoice creation failed.
     #[error("Failed to create invoice.")]
     InvoiceCreationFailed,
@@ -54,6 +66,18 @@ pub enum MutinyJsError {
     /// No route for the given target could be found.
     #[error("Failed to find route.")]
     RoutingFailed,
+    /// No route could be found with fees under the given limit.
+    #[error("Failed to find a route with fees under the limit.")]
+    FeeTooHigh,
+    /// The fee rate is below the minimum fee rate nodes will relay transactions at
+    #[error("The fee rate is below the network minimum.")]
+    FeeRateBelowMinimum,
+    /// An output's amount is so small it costs more to spend than it is worth
+    #[error("The amount is below the dust limit.")]
+    AmountBelowDust,
+    /// A swap with Boltz could not be created or completed.
+    #[error("Failed to complete the swap.")]
+    SwapFailed,
     /// A given peer info could not be parsed.
     #[error("Failed to parse the given peer information.")]
     PeerInfoParseFailed,
@@ -112,6 +136,30 @@ pub enum MutinyJsError {
     /// Invalid Arguments were given
     #[error("Invalid Arguments were given")]
     InvalidArgumentsError,
+    /// The wallet is watch-only and cannot spend funds
+    #[error("This wallet is watch-only.")]
+    WatchOnly,
+    /// The on-chain wallet is signed for by an external signer
+    #[error("On-chain transactions must be signed by the external signer.")]
+    ExternalSigner,
+    /// The password given could not decrypt the storage
+    #[error("Incorrect password entered.")]
+    IncorrectPassword,
+    /// The storage is encrypted and has not been unlocked
+    #[error("The wallet is locked.")]
+    WalletLocked,
+    /// Failed to read from or write to the remote backup server
+    #[error("Failed to sync with the backup server.")]
+    VssError,
+    /// The storage already has a wallet in it
+    #[error("A wallet already exists in this storage.")]
+    WalletExists,
+    /// Another operation that conflicts with this one is in progress
+    #[error("Another operation is in progress, try again later.")]
+    Busy,
+    /// The user did not approve the payment and it did not fit the spending budget
+    #[error("Payment was not approved.")]
+    PaymentNotApproved,
     /// Unknown error.
     #[error("Unknown Error")]
     UnknownError,
@@ -126,15 +174,23 @@ impl From<MutinyError> for MutinyJsError {
             MutinyError::FundingTxCreationFailed => MutinyJsError::FundingTxCreationFailed,
             MutinyError::ConnectionFailed => MutinyJsError::ConnectionFailed,
             MutinyError::IncorrectNetwork(net) => MutinyJsError::IncorrectNetwork(net),
+            MutinyError::NetworkMismatch(net) => MutinyJsError::NetworkMismatch(net),
+            MutinyError::AccountIndexMismatch(index) => MutinyJsError::AccountIndexMismatch(index),
+            MutinyError::NetworkMigrationNotReady => MutinyJsError::NetworkMigrationNotReady,
             MutinyError::NonUniquePaymentHash => MutinyJsError::NonUniquePaymentHash,
             MutinyError::PaymentTimeout => MutinyJsError::PaymentTimeout,
             MutinyError::InvoiceInvalid => MutinyJsError::InvoiceInvalid,
+            MutinyError::InvoiceExpired => MutinyJsError::InvoiceExpired,
             MutinyError::InvoiceCreationFailed => MutinyJsError::InvoiceCreationFailed,
             MutinyError::ReserveAmountError => MutinyJsError::ReserveAmountError,
             MutinyError::InsufficientBalance => MutinyJsError::InsufficientBalance,
             MutinyError::LnUrlFailure => MutinyJsError::LnUrlFailure,
             MutinyError::LspFailure => MutinyJsError::LspFailure,
             MutinyError::RoutingFailed => MutinyJsError::RoutingFailed,
+            MutinyError::FeeTooHigh => MutinyJsError::FeeTooHigh,
+            MutinyError::FeeRateBelowMinimum => MutinyJsError::FeeRateBelowMinimum,
+            MutinyError::AmountBelowDust => MutinyJsError::AmountBelowDust,
+            MutinyError::SwapFailed => MutinyJsError::SwapFailed,
             MutinyError::PeerInfoParseFailed => MutinyJsError::PeerInfoParseFailed,
             MutinyError::ChannelCreationFailed => MutinyJsError::ChannelCreationFailed,
             MutinyError::ChannelClosingFailed => MutinyJsError::ChannelClosingFailed,
@@ -153,6 +209,14 @@ impl From<MutinyError> for MutinyJsError {
             MutinyError::IncorrectLnUrlFunction => MutinyJsError::IncorrectLnUrlFunction,
             MutinyError::BadAmountError => MutinyJsError::BadAmountError,
             MutinyError::BitcoinPriceError => MutinyJsError::BitcoinPriceError,
+            MutinyError::InvalidArgumentsError => MutinyJsError::InvalidArgumentsError,
+            MutinyError::WatchOnly => MutinyJsError::WatchOnly,
+            MutinyError::ExternalSigner => MutinyJsError::ExternalSigner,
+            MutinyError::IncorrectPassword => MutinyJsError::IncorrectPassword,
+            MutinyError::WalletLocked => MutinyJsError::WalletLocked,
+            MutinyError::VssError => MutinyJsError::VssError,
+            MutinyError::WalletExists => MutinyJsError::WalletExists,
+            MutinyError::Busy => MutinyJsError::Busy,
             MutinyError::Other(_) => MutinyJsError::UnknownError,
         }
     }
@@ -4,9 +4,11 @@ use gloo_utils::format::JsValueSerdeExt;
 use lightning::util::logger::Logger;
 use lightning::{log_debug, log_error};
 use log::error;
+use mutiny_core::encrypt::Cipher;
 use mutiny_core::error::{MutinyError, MutinyStorageError};
 use mutiny_core::logging::MutinyLogger;
 use mutiny_core::storage::{MutinyStorage, KEYCHAIN_STORE_KEY};
+use mutiny_core::vss::VssBackup;
 use mutiny_core::*;
 use rexie::{ObjectStore, Rexie, TransactionMode};
 use serde::{Deserialize, Serialize};
@@ -21,7 +23,11 @@ pub(crate) const WALLET_OBJECT_STORE_NAME: &str = "wallet_store";
 
 #[derive(Clone)]
 pub struct IndexedDbStorage {
-    pub(crate) password: Option<String>,
+    /// The cipher derived from the user's password, None if there is no password
+    /// or the storage is locked
+    cipher: Arc<RwLock<Option<Cipher>>>,
+    /// The remote backup that critical writes are mirrored to
+    vss: Arc<RwLock<Option<Arc<VssBackup>>>>,
     /// In-memory cache of the wallet data
     /// This is used to avoid having to read from IndexedDB on every get.
     /// This is a RwLock because we want to be able to read from it without blocking
@@ -40,13 +46,21 @@ impl IndexedDbStorage {
         let map = Self::read_all(&indexed_db, &logger).await?;
         let memory = Arc::new(RwLock::new(map));
 
-        let password = password.filter(|p| !p.is_empty());
-        Ok(IndexedDbStorage {
-            password,
+        let storage = IndexedDbStorage {
+            cipher: Arc::new(RwLock::new(None)),
+            vss: Arc::new(RwLock::new(None)),
             memory,
             indexed_db,
             logger,
-        })
+        };
+
+        match password.filter(|p| !p.is_empty()) {
+            Some(password) => storage.unlock(&password)?,
+            // unlocking finishes an interrupted password change, without a password we do it here
+            None => storage.finish_password_change()?,
+        }
+
+        Ok(storage)
     }
 
     async fn save_to_indexed_db(
@@ -196,6 +210,21 @@ impl IndexedDbStorage {
         Ok(map)
     }
 
+    /// Reads everything in local storage, this is where the legacy
+    /// `node-manager` crate kept the whole wallet.
+    pub(crate) fn read_legacy_local_storage() -> HashMap<String, Value> {
+        let local_storage = LocalStorage::raw();
+        let mut map = HashMap::new();
+        for index in 0..LocalStorage::length() {
+            if let Ok(Some(key)) = local_storage.key(index) {
+                if let Ok(value) = LocalStorage::get::<Value>(&key) {
+                    map.insert(key, value);
+                }
+            }
+        }
+        map
+    }
+
     async fn build_indexed_db_database() -> Result<Rexie, MutinyError> {
         let rexie = Rexie::builder(WALLET_DATABASE_NAME)
             .version(1)
@@ -226,10 +255,12 @@ impl IndexedDbStorage {
 ///
 /// We also need to skip writing them to the in memory storage on updates.
 fn used_once(key: &str) -> bool {
-    matches!(
-        key,
-        NETWORK_GRAPH_KEY | PROB_SCORER_KEY | GOSSIP_SYNC_TIME_KEY | KEYCHAIN_STORE_KEY
-    )
+    match key {
+        NETWORK_GRAPH_KEY | PROB_SCORER_KEY | GOSSIP_SYNC_TIME_KEY => true,
+        // includes the keychain stores of rotated on-chain accounts
+        str if str.starts_with(KEYCHAIN_STORE_KEY) => true,
+        _ => false,
+    }
 }
 
 /// To help prevent force closes we save to local storage as well as indexed db.
@@ -245,8 +276,24 @@ fn write_to_local_storage(key: &str) -> bool {
 }
 
 impl MutinyStorage for IndexedDbStorage {
-    fn password(&self) -> Option<&str> {
-        self.password.as_deref()
+    fn cipher(&self) -> Option<Cipher> {
+        self.cipher.try_read().ok().and_then(|c| c.clone())
+    }
+
+    fn set_cipher(&self, cipher: Option<Cipher>) {
+        if let Ok(mut lock) = self.cipher.try_write() {
+            *lock = cipher;
+        }
+    }
+
+    fn vss(&self) -> Option<Arc<VssBackup>> {
+        self.vss.try_read().ok().and_then(|v| v.clone())
+    }
+
+    fn set_vss(&self, vss: Option<Arc<VssBackup>>) {
+        if let Ok(mut lock) = self.vss.try_write() {
+            *lock = vss;
+        }
     }
 
     fn set<T>(&self, key: impl AsRef<str>, value: T) -> Result<(), MutinyError>
@@ -324,6 +371,10 @@ impl MutinyStorage for IndexedDbStorage {
     fn delete(&self, keys: &[impl AsRef<str>]) -> Result<(), MutinyError> {
         let keys: Vec<String> = keys.iter().map(|k| k.as_ref().to_string()).collect();
 
+        if let Some(vss) = self.vss() {
+            vss.queue_deletes(&keys);
+        }
+
         let indexed_db = self.indexed_db.clone();
         let keys_clone = keys.clone();
         let logger = self.logger.clone();
@@ -477,7 +528,8 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(storage.password, None);
+        assert!(storage.cipher().is_none());
+        assert!(!storage.is_encrypted().unwrap());
     }
 
     #[test]
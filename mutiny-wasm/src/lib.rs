@@ -4,19 +4,26 @@
 
 extern crate mutiny_core;
 
+mod acceptor;
 mod error;
 mod indexed_db;
+mod messages;
 mod models;
 mod utils;
+mod webln;
 
+use crate::acceptor::JsChannelAcceptor;
 use crate::error::MutinyJsError;
 use crate::indexed_db::IndexedDbStorage;
+use crate::messages::{JsOnionMessageHandler, JsPeerMessageHandler};
 use crate::models::*;
 use crate::utils::sleep;
+use crate::webln::{WebLn, WebLnOptions};
 use bip39::Mnemonic;
 use bitcoin::consensus::deserialize;
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::sha256;
+use bitcoin::psbt::PartiallySignedTransaction;
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::{Address, Network, OutPoint, Transaction, Txid};
 use gloo_utils::format::JsValueSerdeExt;
@@ -24,10 +31,24 @@ use lightning::routing::gossip::NodeId;
 use lightning_invoice::Invoice;
 use lnurl::lnurl::LnUrl;
 use mutiny_core::labels::LabelStorage;
+use mutiny_core::lnurlpay::PayerData;
 use mutiny_core::logging::MutinyLogger;
-use mutiny_core::redshift::RedshiftManager;
+use mutiny_core::nodemanager::{
+    MaxFee, MppOptions, OpenChannelOptions, PaymentOptions, PaymentStatus,
+};
+use mutiny_core::onionmessage::CustomOnionMessage;
+use mutiny_core::peermessage::CustomPeerMessage;
+use mutiny_core::pricing::{self, FiatCurrency, PriceConfig, PriceSource};
+use mutiny_core::redshift::{RedshiftManager, RedshiftOptions};
+use mutiny_core::rescue::RescuePath;
+use mutiny_core::signing::SigningScope;
 use mutiny_core::storage::MutinyStorage;
-use mutiny_core::{nodemanager, redshift::RedshiftRecipient};
+use mutiny_core::swaps::SwapManager;
+use mutiny_core::watch::WatchManager;
+use mutiny_core::{
+    nodemanager, redshift::RedshiftRecipient, MempoolSpaceFeeSource, MutinyWalletConfig,
+    ReconnectPolicy,
+};
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -50,7 +71,12 @@ impl MutinyWallet {
     /// Creates a new [MutinyWallet] with the given parameters.
     /// The mnemonic seed is read from storage, unless one is provided.
     /// If no mnemonic is provided, a new one is generated and stored.
+    ///
+    /// The options are an optional object, all of its fields are optional too:
+    /// `{ externalSigner, watchOnly, accountIndex, feeSourceUrl, stopGap, addressLookahead,
+    /// vssUrl, restoreFromVss, channelBackup }`.
     #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         password: Option<String>,
         mnemonic_str: Option<String>,
@@ -59,6 +85,7 @@ impl MutinyWallet {
         user_esplora_url: Option<String>,
         user_rgs_url: Option<String>,
         lsp_url: Option<String>,
+        options: JsValue, /* Option<WalletOptions> */
     ) -> Result<MutinyWallet, MutinyJsError> {
         utils::set_panic_hook();
 
@@ -69,11 +96,12 @@ impl MutinyWallet {
             None => None,
         };
 
-        let logger = Arc::new(MutinyLogger::default());
-        let storage = IndexedDbStorage::new(password, logger).await?;
+        let options = options
+            .into_serde::<Option<WalletOptions>>()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?
+            .unwrap_or_default();
 
-        let inner = mutiny_core::MutinyWallet::new(
-            storage,
+        let mut config = MutinyWalletConfig::new(
             mnemonic,
             #[cfg(target_arch = "wasm32")]
             websocket_proxy_addr,
@@ -81,22 +109,98 @@ impl MutinyWallet {
             user_esplora_url,
             user_rgs_url,
             lsp_url,
-        )
-        .await?;
+        );
+        if let Some(descriptor) = options.watch_only {
+            config = config.with_watch_only(descriptor);
+        }
+        if let Some(descriptor) = options.external_signer {
+            config = config.with_external_signer(descriptor);
+        }
+        if let Some(index) = options.account_index {
+            config = config.with_account_index(index);
+        }
+        if let Some(url) = options.fee_source_url {
+            config = config.with_fee_source(Arc::new(MempoolSpaceFeeSource::new(url)));
+        }
+        if let Some(stop_gap) = options.stop_gap {
+            config = config.with_stop_gap(stop_gap);
+        }
+        if let Some(lookahead) = options.address_lookahead {
+            config = config.with_address_lookahead(lookahead);
+        }
+        if let Some(url) = options.vss_url {
+            config = config.with_vss_url(url);
+        }
+        if options.restore_from_vss {
+            config = config.with_restore_from_vss();
+        }
+        if let Some(backup) = options.channel_backup {
+            config = config.with_channel_backup(backup);
+        }
+
+        let logger = Arc::new(MutinyLogger::default());
+        let storage = IndexedDbStorage::new(password, logger).await?;
+
+        let inner = mutiny_core::MutinyWallet::new(config, storage).await?;
         Ok(MutinyWallet { inner })
     }
 
     /// Returns if there is a saved wallet in storage.
     /// This is checked by seeing if a mnemonic seed exists in storage.
+    ///
+    /// The password is not needed to check this, a locked wallet still counts.
     #[wasm_bindgen]
-    pub async fn has_node_manager(password: Option<String>) -> bool {
+    pub async fn has_node_manager(_password: Option<String>) -> bool {
         let logger = Arc::new(MutinyLogger::default());
-        let storage = IndexedDbStorage::new(password, logger)
+        let storage = IndexedDbStorage::new(None, logger)
             .await
             .expect("Failed to init");
         nodemanager::NodeManager::has_node_manager(storage)
     }
 
+    /// Copies a wallet made with the legacy `node-manager` package into the current
+    /// storage, keeping its seed, channels and labels. Existing data is never overwritten.
+    ///
+    /// This should be called before creating the [MutinyWallet].
+    #[wasm_bindgen]
+    pub async fn migrate_legacy_storage(
+        password: Option<String>,
+    ) -> Result<JsValue /* LegacyMigrationSummary */, MutinyJsError> {
+        let logger = Arc::new(MutinyLogger::default());
+        let storage = IndexedDbStorage::new(password, logger).await?;
+        let legacy = IndexedDbStorage::read_legacy_local_storage();
+        let summary = mutiny_core::legacy::migrate_legacy_storage(legacy, &storage)?;
+        // Sleep to wait for indexed db to finish writing
+        sleep(500).await;
+        Ok(JsValue::from_serde(&summary)?)
+    }
+
+    /// Saves a rapid gossip sync snapshot bundled with the app so routing works on first run
+    /// without waiting for the initial download. Does nothing if a network graph is already saved.
+    /// Returns the timestamp the network graph is synced to.
+    ///
+    /// This should be called before creating the [MutinyWallet].
+    #[wasm_bindgen]
+    pub async fn import_gossip_snapshot(
+        password: Option<String>,
+        network_str: Option<String>,
+        snapshot: Vec<u8>,
+    ) -> Result<u32, MutinyJsError> {
+        let network = match network_str {
+            Some(network) => {
+                Network::from_str(&network).map_err(|_| MutinyJsError::InvalidArgumentsError)?
+            }
+            None => Network::Signet,
+        };
+        let logger = Arc::new(MutinyLogger::default());
+        let storage = IndexedDbStorage::new(password, logger.clone()).await?;
+        let timestamp =
+            mutiny_core::import_gossip_snapshot(&storage, network, &snapshot, logger).await?;
+        // Sleep to wait for indexed db to finish writing
+        sleep(500).await;
+        Ok(timestamp)
+    }
+
     /// Starts up all the nodes again.
     /// Not needed after [NodeManager]'s `new()` function.
     #[wasm_bindgen]
@@ -122,10 +226,66 @@ impl MutinyWallet {
         Ok(self.inner.node_manager.broadcast_transaction(tx).await?)
     }
 
+    /// Returns if the wallet's storage is encrypted and has not been unlocked.
+    #[wasm_bindgen]
+    pub fn is_locked(&self) -> Result<bool, MutinyJsError> {
+        Ok(self.inner.node_manager.is_locked()?)
+    }
+
+    /// Locks the wallet, stopping all the nodes and removing the encryption key from memory.
+    /// The wallet needs to be unlocked and started again before it can be used.
+    #[wasm_bindgen]
+    pub async fn lock(&self) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.lock().await?)
+    }
+
+    /// Unlocks the wallet with the given password.
+    #[wasm_bindgen]
+    pub fn unlock(&self, password: String) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.unlock(password)?)
+    }
+
+    /// Changes the password used to encrypt the wallet.
+    /// If the wallet has no password, the old password should be undefined.
+    /// Setting the new password to undefined removes the encryption.
+    #[wasm_bindgen]
+    pub fn change_password(
+        &self,
+        old_password: Option<String>,
+        new_password: Option<String>,
+    ) -> Result<(), MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .change_password(old_password, new_password)?)
+    }
+
     /// Returns the mnemonic seed phrase for the wallet.
+    /// Watch-only wallets do not have a seed.
+    #[wasm_bindgen]
+    pub fn show_seed(&self) -> Option<String> {
+        self.inner.node_manager.show_seed().map(|m| m.to_string())
+    }
+
+    /// Returns if the wallet is watch-only.
+    #[wasm_bindgen]
+    pub fn is_watch_only(&self) -> bool {
+        self.inner.node_manager.is_watch_only()
+    }
+
+    /// Returns if on-chain transactions are signed by an external signer,
+    /// in which case `send_to_address` returns a PSBT to sign.
+    #[wasm_bindgen]
+    pub fn uses_external_signer(&self) -> bool {
+        self.inner.node_manager.uses_external_signer()
+    }
+
+    /// Exports an encrypted static channel backup that can be used with the
+    /// seed to have the channel peers force close and recover the funds.
+    /// This should be exported again after opening channels.
     #[wasm_bindgen]
-    pub fn show_seed(&self) -> String {
-        self.inner.node_manager.show_seed().to_string()
+    pub fn export_channel_backup(&self) -> Result<String, MutinyJsError> {
+        Ok(self.inner.node_manager.export_channel_backup()?)
     }
 
     /// Returns the network of the wallet.
@@ -134,6 +294,130 @@ impl MutinyWallet {
         self.inner.node_manager.get_network().to_string()
     }
 
+    /// Returns the derivation paths of the wallet's on-chain and lightning keys,
+    /// for restoring the seed in other software.
+    #[wasm_bindgen]
+    pub async fn get_derivation_paths(
+        &self,
+    ) -> Result<JsValue /* DerivationPaths */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_derivation_paths().await?,
+        )?)
+    }
+
+    /// Checks what would be left behind by moving the wallet to the given network.
+    #[wasm_bindgen]
+    pub async fn check_network_migration(
+        &self,
+        network: String,
+    ) -> Result<JsValue /* NetworkMigrationCheck */, MutinyJsError> {
+        let network =
+            Network::from_str(&network).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(JsValue::from_serde(
+            &self
+                .inner
+                .node_manager
+                .check_network_migration(network)
+                .await?,
+        )?)
+    }
+
+    /// Moves the wallet to another network, for example from signet testing to mainnet.
+    /// Everything but the seed is deleted, afterwards the wallet has to be created
+    /// again with the new network.
+    #[wasm_bindgen]
+    pub async fn migrate_network(&self, network: String) -> Result<(), MutinyJsError> {
+        let network =
+            Network::from_str(&network).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.node_manager.migrate_network(network).await?)
+    }
+
+    /// Returns the progress of syncing the wallet with the blockchain.
+    #[wasm_bindgen]
+    pub fn get_sync_status(&self) -> Result<JsValue /* SyncStatus */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_sync_status(),
+        )?)
+    }
+
+    /// Calls the callback for every event that happens in the wallet, such as
+    /// payments, channel opens and closes, syncs, peer connections,
+    /// transaction confirmations and HTLCs that are about to force close a channel.
+    #[wasm_bindgen]
+    pub fn subscribe_events(&self, callback: js_sys::Function) {
+        nodemanager::NodeManager::watch_events(self.inner.node_manager.clone(), move |event| {
+            if let Ok(event) = JsValue::from_serde(&event) {
+                let _ = callback.call1(&JsValue::NULL, &event);
+            }
+        });
+    }
+
+    /// Gets the block height the wallet was created at, if it is known.
+    #[wasm_bindgen]
+    pub fn get_wallet_birthday(&self) -> Result<Option<u32>, MutinyJsError> {
+        Ok(self.inner.node_manager.get_wallet_birthday()?)
+    }
+
+    /// Sets the block height the wallet was created at, for restored seeds.
+    #[wasm_bindgen]
+    pub fn set_wallet_birthday(&self, height: u32) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.set_wallet_birthday(height)?)
+    }
+
+    /// Returns how many receive and change addresses the on-chain wallet has handed out.
+    #[wasm_bindgen]
+    pub fn get_revealed_addresses(&self) -> Result<JsValue /* RevealedAddresses */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_revealed_addresses()?,
+        )?)
+    }
+
+    /// Rescans the on-chain wallet with a much wider address gap than a regular sync.
+    /// The progress is sent to `on_sync_status`.
+    #[wasm_bindgen]
+    pub async fn full_rescan(&self) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.full_rescan().await?)
+    }
+
+    /// Calls the callback with the current sync status and every time it changes.
+    #[wasm_bindgen]
+    pub fn on_sync_status(&self, callback: js_sys::Function) {
+        nodemanager::NodeManager::watch_sync_status(
+            self.inner.node_manager.clone(),
+            move |status| {
+                if let Ok(status) = JsValue::from_serde(&status) {
+                    let _ = callback.call1(&JsValue::NULL, &status);
+                }
+            },
+        );
+    }
+
+    /// Returns the health of each of the configured esplora servers.
+    #[wasm_bindgen]
+    pub fn get_esplora_health(
+        &self,
+    ) -> Result<JsValue /* Vec<EsploraEndpointHealth> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_esplora_health(),
+        )?)
+    }
+
+    /// The websocket proxy new peer connections currently go through.
+    #[wasm_bindgen]
+    pub fn get_active_websocket_proxy(&self) -> String {
+        self.inner.node_manager.get_active_websocket_proxy()
+    }
+
+    /// Returns the health of each of the configured websocket proxies.
+    #[wasm_bindgen]
+    pub fn get_websocket_proxy_health(
+        &self,
+    ) -> Result<JsValue /* Vec<WsProxyHealth> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_websocket_proxy_health(),
+        )?)
+    }
+
     /// Gets a new bitcoin address from the wallet.
     /// Will generate a new address on every call.
     ///
@@ -177,6 +461,9 @@ impl MutinyWallet {
     /// The amount is in satoshis and the fee rate is in sat/vbyte.
     ///
     /// If a fee rate is not provided, one will be used from the fee estimator.
+    ///
+    /// Returns the txid, or with an external signer the unsigned base64 encoded PSBT
+    /// to sign with it and then broadcast with `broadcast_psbt`, see [OnChainSendResult].
     #[wasm_bindgen]
     pub async fn send_to_address(
         &self,
@@ -184,15 +471,91 @@ impl MutinyWallet {
         amount: u64,
         labels: JsValue, /* Vec<String> */
         fee_rate: Option<f32>,
+    ) -> Result<OnChainSendResult, MutinyJsError> {
+        let send_to = Address::from_str(&destination_address)?;
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let sent = self
+            .inner
+            .node_manager
+            .send_to_address(send_to, amount, labels, fee_rate)
+            .await?;
+        Ok(sent.into())
+    }
+
+    /// Sends a single on-chain transaction paying several addresses.
+    /// The recipients are a list of `[address, amount, labels]`, the amounts are in satoshis
+    /// and the labels are given to that address only.
+    ///
+    /// Returns the txid, or with an external signer the unsigned base64 encoded PSBT
+    /// to sign with it and then broadcast with `broadcast_psbt`, see [OnChainSendResult].
+    #[wasm_bindgen]
+    pub async fn send_to_addresses(
+        &self,
+        recipients: JsValue, /* Vec<(String, u64, Vec<String>)> */
+        fee_rate: Option<f32>,
+    ) -> Result<OnChainSendResult, MutinyJsError> {
+        let recipients: Vec<(String, u64, Vec<String>)> = recipients
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let recipients = recipients
+            .into_iter()
+            .map(|(address, amount, labels)| Ok((Address::from_str(&address)?, amount, labels)))
+            .collect::<Result<Vec<_>, MutinyJsError>>()?;
+        let sent = self
+            .inner
+            .node_manager
+            .send_to_addresses(recipients, fee_rate)
+            .await?;
+        Ok(sent.into())
+    }
+
+    /// Creates an unsigned PSBT sending to the given address.
+    /// The PSBT is returned base64 encoded so it can be given to an external signer.
+    /// The amount is in satoshis and the fee rate is in sat/vbyte.
+    ///
+    /// If a fee rate is not provided, one will be used from the fee estimator.
+    #[wasm_bindgen]
+    pub fn create_psbt(
+        &self,
+        destination_address: String,
+        amount: u64,
+        fee_rate: Option<f32>,
     ) -> Result<String, MutinyJsError> {
         let send_to = Address::from_str(&destination_address)?;
+        Ok(self
+            .inner
+            .node_manager
+            .create_psbt(send_to, amount, fee_rate)?
+            .to_string())
+    }
+
+    /// Signs the inputs of a base64 encoded PSBT that belong to the wallet.
+    /// Returns the signed PSBT base64 encoded.
+    #[wasm_bindgen]
+    pub fn sign_psbt(&self, psbt: String) -> Result<String, MutinyJsError> {
+        let psbt = PartiallySignedTransaction::from_str(&psbt)
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.node_manager.sign_psbt(psbt)?.to_string())
+    }
+
+    /// Finalizes and broadcasts a fully signed base64 encoded PSBT.
+    #[wasm_bindgen]
+    pub async fn broadcast_psbt(
+        &self,
+        psbt: String,
+        labels: JsValue, /* Vec<String> */
+    ) -> Result<String, MutinyJsError> {
+        let psbt = PartiallySignedTransaction::from_str(&psbt)
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
         let labels: Vec<String> = labels
             .into_serde()
             .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
         Ok(self
             .inner
             .node_manager
-            .send_to_address(send_to, amount, labels, fee_rate)
+            .broadcast_psbt(psbt, labels)
             .await?
             .to_string())
     }
@@ -220,6 +583,100 @@ impl MutinyWallet {
             .to_string())
     }
 
+    /// Switches new on-chain addresses to a new account of the same seed, for when
+    /// the xpub may have leaked. Funds in the old account are swept with
+    /// `sweep_rotated_onchain_funds`.
+    #[wasm_bindgen]
+    pub async fn rotate_onchain_keys(
+        &self,
+    ) -> Result<JsValue /* OnChainKeyRotation */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.rotate_onchain_keys().await?,
+        )?)
+    }
+
+    /// Sweeps the funds left from rotated on-chain keys to the current account, as long as
+    /// the low priority fee rate is below the max fee rate (sat/vbyte).
+    /// Returns the txids of the sweeps, nothing is swept while fees are too high.
+    #[wasm_bindgen]
+    pub async fn sweep_rotated_onchain_funds(
+        &self,
+        max_fee_rate: Option<f32>,
+    ) -> Result<JsValue /* Vec<String> */, MutinyJsError> {
+        let txids: Vec<String> = self
+            .inner
+            .node_manager
+            .sweep_rotated_onchain_funds(max_fee_rate)
+            .await?
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        Ok(JsValue::from_serde(&txids)?)
+    }
+
+    /// Bumps the fee of an unconfirmed incoming transaction by spending our
+    /// outputs of it with a higher fee (child pays for parent).
+    /// The fee rate is in sat/vbyte.
+    ///
+    /// If a fee rate is not provided, one will be used from the fee estimator.
+    #[wasm_bindgen]
+    pub async fn cpfp_accelerate(
+        &self,
+        txid: String,
+        fee_rate: Option<f32>,
+    ) -> Result<String, MutinyJsError> {
+        let txid = Txid::from_str(&txid)?;
+        Ok(self
+            .inner
+            .node_manager
+            .cpfp_accelerate(txid, fee_rate)
+            .await?
+            .to_string())
+    }
+
+    /// Scans a mnemonic from another wallet and sweeps any on-chain funds found into this wallet.
+    /// The fee rate is in sat/vbyte.
+    ///
+    /// If no derivation paths are given, the common BIP44/49/84/86 paths and some
+    /// nonstandard variants will be scanned. LND aezeed seeds are not supported.
+    #[wasm_bindgen]
+    pub async fn sweep_external_mnemonic(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+        paths: JsValue, /* Option<Vec<RescuePath>> */
+        fee_rate: Option<f32>,
+    ) -> Result<JsValue /* Vec<RescueResult> */, MutinyJsError> {
+        let mnemonic = Mnemonic::from_str(&mnemonic).map_err(|_| MutinyJsError::InvalidMnemonic)?;
+        let paths: Option<Vec<RescuePath>> = paths
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let results = self
+            .inner
+            .node_manager
+            .sweep_external_mnemonic(mnemonic, passphrase, paths, fee_rate)
+            .await?;
+        Ok(JsValue::from_serde(&results)?)
+    }
+
+    /// Sweeps the on-chain funds of a key from elsewhere, like a paper wallet or a gift,
+    /// into this wallet. The fee rate is in sat/vbyte.
+    ///
+    /// The key can be a WIF private key, a master xprv or a mnemonic.
+    #[wasm_bindgen]
+    pub async fn sweep_external_key(
+        &self,
+        wif_or_seed: String,
+        fee_rate: Option<f32>,
+    ) -> Result<JsValue /* Vec<RescueResult> */, MutinyJsError> {
+        let results = self
+            .inner
+            .node_manager
+            .sweep_external_key(wif_or_seed, fee_rate)
+            .await?;
+        Ok(JsValue::from_serde(&results)?)
+    }
+
     /// Estimates the onchain fee for a transaction sending to the given address.
     /// The amount is in satoshis and the fee rate is in sat/vbyte.
     pub fn estimate_tx_fee(
@@ -263,6 +720,57 @@ impl MutinyWallet {
         )?)
     }
 
+    /// Starts tracking the payments to a third-party address, such as a cold storage address.
+    /// They show up in the activity as read-only items after the next sync.
+    #[wasm_bindgen]
+    pub fn watch_address(
+        &self,
+        address: String,
+        label: Option<String>,
+    ) -> Result<(), MutinyJsError> {
+        let address = Address::from_str(&address)?;
+        Ok(self.inner.node_manager.watch_address(address, label)?)
+    }
+
+    /// Stops tracking the address and removes its transactions from the activity.
+    #[wasm_bindgen]
+    pub fn unwatch_address(&self, address: String) -> Result<(), MutinyJsError> {
+        let address = Address::from_str(&address)?;
+        Ok(self.inner.node_manager.unwatch_address(&address)?)
+    }
+
+    /// Lists the third-party addresses being tracked.
+    #[wasm_bindgen]
+    pub fn list_watched_addresses(
+        &self,
+    ) -> Result<JsValue /* Vec<WatchedAddress> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_watched_addresses()?,
+        )?)
+    }
+
+    /// Starts tracking the confirmation status of a third-party transaction.
+    #[wasm_bindgen]
+    pub fn watch_txid(&self, txid: String) -> Result<(), MutinyJsError> {
+        let txid = Txid::from_str(&txid)?;
+        Ok(self.inner.node_manager.watch_txid(txid)?)
+    }
+
+    /// Stops tracking the transaction and removes it from the activity.
+    #[wasm_bindgen]
+    pub fn unwatch_txid(&self, txid: String) -> Result<(), MutinyJsError> {
+        let txid = Txid::from_str(&txid)?;
+        Ok(self.inner.node_manager.unwatch_txid(&txid)?)
+    }
+
+    /// Lists the third-party transactions being tracked.
+    #[wasm_bindgen]
+    pub fn list_watched_txids(&self) -> Result<JsValue /* Vec<String> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_watched_txids()?,
+        )?)
+    }
+
     /// Lists all the on-chain transactions in the wallet.
     /// These are sorted by confirmation time.
     #[wasm_bindgen]
@@ -327,22 +835,202 @@ impl MutinyWallet {
         )?)
     }
 
-    /// Attempts to connect to a peer from the selected node.
+    /// Sends an onion message of a custom type from the selected node.
+    /// The data is hex encoded, the intermediate nodes are a list of pubkeys.
     #[wasm_bindgen]
-    pub async fn connect_to_peer(
+    pub async fn send_onion_message(
         &self,
-        self_node_pubkey: String,
-        connection_string: String,
-        label: Option<String>,
+        from_node: String,
+        to_node: String,
+        intermediate_nodes: JsValue, /* Vec<String> */
+        tlv_type: u64,
+        data: String,
     ) -> Result<(), MutinyJsError> {
-        let self_node_pubkey = PublicKey::from_str(&self_node_pubkey)?;
-        Ok(self
+        let from_node = PublicKey::from_str(&from_node)?;
+        let to_node = PublicKey::from_str(&to_node)?;
+        let intermediate_nodes: Vec<String> = intermediate_nodes
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let intermediate_nodes = intermediate_nodes
+            .iter()
+            .map(|n| PublicKey::from_str(n))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data: Vec<u8> =
+            FromHex::from_hex(&data).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .send_onion_message(
+                &from_node,
+                to_node,
+                intermediate_nodes,
+                CustomOnionMessage { tlv_type, data },
+            )
+            .await?)
+    }
+
+    /// Calls the callback with the hex encoded data of each onion message
+    /// of the type our nodes receive, replacing the callback that type had.
+    #[wasm_bindgen]
+    pub fn register_onion_message_handler(&self, tlv_type: u64, callback: js_sys::Function) {
+        self.inner.node_manager.register_onion_message_handler(
+            tlv_type,
+            Arc::new(JsOnionMessageHandler::new(callback)),
+        )
+    }
+
+    /// Stops handling the onion messages of a type
+    #[wasm_bindgen]
+    pub fn unregister_onion_message_handler(&self, tlv_type: u64) {
+        self.inner
+            .node_manager
+            .unregister_onion_message_handler(tlv_type)
+    }
+
+    /// Sends a custom message from the selected node to one of its connected peers.
+    /// The data is hex encoded.
+    #[wasm_bindgen]
+    pub async fn send_peer_message(
+        &self,
+        from_node: String,
+        peer: String,
+        message_type: u16,
+        data: String,
+    ) -> Result<(), MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let peer = PublicKey::from_str(&peer)?;
+        let data: Vec<u8> =
+            FromHex::from_hex(&data).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .send_peer_message(&from_node, peer, CustomPeerMessage { message_type, data })
+            .await?)
+    }
+
+    /// Calls the callback with the peer's pubkey and the hex encoded data of each
+    /// custom message of the type our peers send, replacing the callback that type had.
+    #[wasm_bindgen]
+    pub fn register_peer_message_handler(&self, message_type: u16, callback: js_sys::Function) {
+        self.inner.node_manager.register_peer_message_handler(
+            message_type,
+            Arc::new(JsPeerMessageHandler::new(callback)),
+        )
+    }
+
+    /// Stops handling the custom messages of a type
+    #[wasm_bindgen]
+    pub fn unregister_peer_message_handler(&self, message_type: u16) {
+        self.inner
+            .node_manager
+            .unregister_peer_message_handler(message_type)
+    }
+
+    /// Signals a custom feature bit to our peers, odd bits are optional and even bits required.
+    /// The bit has to be at least 256 and is only signaled on connections made after it was added.
+    #[wasm_bindgen]
+    pub fn add_feature_bit(&self, bit: usize) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.add_feature_bit(bit)?)
+    }
+
+    /// Attempts to connect to a peer from the selected node.
+    /// The connection string is `pubkey@host:port`, or just the pubkey to look up its address.
+    #[wasm_bindgen]
+    pub async fn connect_to_peer(
+        &self,
+        self_node_pubkey: String,
+        connection_string: String,
+        label: Option<String>,
+    ) -> Result<(), MutinyJsError> {
+        let self_node_pubkey = PublicKey::from_str(&self_node_pubkey)?;
+        Ok(self
             .inner
             .node_manager
             .connect_to_peer(&self_node_pubkey, &connection_string, label)
             .await?)
     }
 
+    /// Finds the address a lightning node announced, as `host:port`,
+    /// from the network graph or a lightning explorer.
+    #[wasm_bindgen]
+    pub async fn find_peer_address(&self, pubkey: String) -> Result<Option<String>, MutinyJsError> {
+        let pubkey = PublicKey::from_str(&pubkey)?;
+        Ok(self.inner.node_manager.find_peer_address(&pubkey).await?)
+    }
+
+    /// Signs a message with the node's key to prove we control the node.
+    /// The signature is zbase32 encoded.
+    #[wasm_bindgen]
+    pub async fn sign_node_ownership(
+        &self,
+        self_node_pubkey: String,
+        message: String,
+    ) -> Result<String, MutinyJsError> {
+        let self_node_pubkey = PublicKey::from_str(&self_node_pubkey)?;
+        Ok(self
+            .inner
+            .node_manager
+            .sign_message(&self_node_pubkey, SigningScope::NodeOwnership, &message)
+            .await?)
+    }
+
+    /// Signs a message with the node's key to prove we are a party to the
+    /// channel with the given funding outpoint.
+    #[wasm_bindgen]
+    pub async fn sign_channel_ownership(
+        &self,
+        self_node_pubkey: String,
+        outpoint: String,
+        message: String,
+    ) -> Result<String, MutinyJsError> {
+        let self_node_pubkey = PublicKey::from_str(&self_node_pubkey)?;
+        let outpoint =
+            OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .sign_message(
+                &self_node_pubkey,
+                SigningScope::ChannelOwnership(outpoint),
+                &message,
+            )
+            .await?)
+    }
+
+    /// Verifies a message signed with [MutinyWallet::sign_node_ownership].
+    #[wasm_bindgen]
+    pub fn verify_node_ownership(
+        &self,
+        node_pubkey: String,
+        message: String,
+        signature: String,
+    ) -> Result<bool, MutinyJsError> {
+        let node_pubkey = PublicKey::from_str(&node_pubkey)?;
+        Ok(self.inner.node_manager.verify_message(
+            &node_pubkey,
+            SigningScope::NodeOwnership,
+            &message,
+            &signature,
+        ))
+    }
+
+    /// Creates a node announcement signed by the selected node, hex encoded.
+    /// The color should be a hex string, ie "ff0000".
+    #[wasm_bindgen]
+    pub async fn create_node_announcement(
+        &self,
+        self_node_pubkey: String,
+        alias: String,
+        color: String,
+    ) -> Result<String, MutinyJsError> {
+        let self_node_pubkey = PublicKey::from_str(&self_node_pubkey)?;
+        Ok(self
+            .inner
+            .node_manager
+            .create_node_announcement(&self_node_pubkey, alias, color)
+            .await?)
+    }
+
     /// Disconnects from a peer from the selected node.
     #[wasm_bindgen]
     pub async fn disconnect_peer(
@@ -385,74 +1073,292 @@ impl MutinyWallet {
         Ok(())
     }
 
+    /// Sets how our nodes reconnect to a peer after losing the connection.
+    /// Options that aren't given use the default policy, leaving them all out resets the peer
+    /// to the default of always reconnecting.
+    #[wasm_bindgen]
+    pub fn set_peer_reconnect_policy(
+        &self,
+        node_id: String,
+        always_reconnect: Option<bool>,
+        initial_backoff_secs: Option<u64>,
+        max_backoff_secs: Option<u64>,
+    ) -> Result<(), MutinyJsError> {
+        let node_id = NodeId::from_str(&node_id)?;
+        let policy = if always_reconnect.is_none()
+            && initial_backoff_secs.is_none()
+            && max_backoff_secs.is_none()
+        {
+            None
+        } else {
+            let default = ReconnectPolicy::default();
+            Some(ReconnectPolicy {
+                always_reconnect: always_reconnect.unwrap_or(default.always_reconnect),
+                initial_backoff_secs: initial_backoff_secs.unwrap_or(default.initial_backoff_secs),
+                max_backoff_secs: max_backoff_secs.unwrap_or(default.max_backoff_secs),
+            })
+        };
+        self.inner
+            .node_manager
+            .set_peer_reconnect_policy(&node_id, policy)?;
+        Ok(())
+    }
+
     /// Creates a lightning invoice. The amount should be in satoshis.
     /// If no amount is provided, the invoice will be created with no amount.
     /// If no description is provided, the invoice will be created with no description.
     ///
     /// If the manager has more than one node it will create a phantom invoice.
     /// If there is only one node it will create an invoice just for that node.
+    ///
+    /// An LSP from `list_lsps` can be given to wrap the invoice with,
+    /// otherwise the best one is picked.
     #[wasm_bindgen]
     pub async fn create_invoice(
         &self,
         amount: Option<u64>,
         labels: JsValue, /* Vec<String> */
+        lsp_url: Option<String>,
+    ) -> Result<MutinyInvoice, MutinyJsError> {
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .create_invoice_with_lsp(amount, labels, lsp_url)
+            .await?
+            .into())
+    }
+
+    /// Creates a lightning invoice for an amount in a fiat currency, ie "usd" or "sek",
+    /// at the current exchange rate. Any currency the price source supports can be used.
+    /// The rate is honored for `ttl` seconds,
+    /// defaulting to 10 minutes.
+    ///
+    /// If a callback is given and the invoice is not paid before the rate expires,
+    /// a fresh invoice at the new rate will be created and passed to it.
+    #[wasm_bindgen]
+    pub async fn create_fiat_invoice(
+        &self,
+        fiat_amount: f64,
+        currency: String,
+        ttl: Option<u64>,
+        labels: JsValue, /* Vec<String> */
+        on_reissue: Option<js_sys::Function>,
+    ) -> Result<FiatInvoice, MutinyJsError> {
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let fiat_invoice = self
+            .inner
+            .node_manager
+            .create_fiat_invoice(fiat_amount, currency, ttl, labels.clone())
+            .await?;
+
+        if let Some(callback) = on_reissue {
+            nodemanager::NodeManager::watch_fiat_invoice(
+                self.inner.node_manager.clone(),
+                fiat_invoice.clone(),
+                labels,
+                move |new_invoice| {
+                    let new_invoice: FiatInvoice = new_invoice.into();
+                    let _ = callback.call1(&JsValue::NULL, &new_invoice.into());
+                },
+            );
+        }
+
+        Ok(fiat_invoice.into())
+    }
+
+    /// Creates a hodl invoice for a payment hash. Payments to it are held until
+    /// they are settled with the preimage or cancelled. The amount should be in satoshis.
+    #[wasm_bindgen]
+    pub async fn create_hodl_invoice(
+        &self,
+        amount: Option<u64>,
+        payment_hash: String,
+        labels: JsValue, /* Vec<String> */
     ) -> Result<MutinyInvoice, MutinyJsError> {
+        let payment_hash = sha256::Hash::from_str(&payment_hash)?;
         let labels: Vec<String> = labels
             .into_serde()
             .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
         Ok(self
             .inner
             .node_manager
-            .create_invoice(amount, labels)
+            .create_hodl_invoice(amount, payment_hash, labels)
             .await?
             .into())
     }
 
+    /// Settles a held payment to a hodl invoice with its hex encoded preimage
+    #[wasm_bindgen]
+    pub async fn settle_invoice(&self, preimage: String) -> Result<(), MutinyJsError> {
+        let preimage: [u8; 32] = Vec::<u8>::from_hex(&preimage)?
+            .try_into()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.node_manager.settle_invoice(preimage).await?)
+    }
+
+    /// Cancels a hodl invoice, failing back any held payment
+    #[wasm_bindgen]
+    pub async fn cancel_invoice(&self, payment_hash: String) -> Result<(), MutinyJsError> {
+        let payment_hash = sha256::Hash::from_str(&payment_hash)?;
+        Ok(self
+            .inner
+            .node_manager
+            .cancel_invoice(&payment_hash)
+            .await?)
+    }
+
     /// Pays a lightning invoice from the selected node.
     /// An amount should only be provided if the invoice does not have an amount.
     /// The amount should be in satoshis.
+    ///
+    /// The options are an optional object, all of its fields are optional too:
+    /// `{ maxFeeSats, maxFeePercent, outgoingChannels, allowSplit, maxParts, minPartSats }`.
+    ///
+    /// The routing fee can be limited to an amount of sats or a percent of the amount,
+    /// if no route is within the limit the payment fails with `FeeTooHigh`.
+    ///
+    /// The payment can be forced out of specific channels by passing
+    /// a list of their funding outpoints as `outgoingChannels`.
+    ///
+    /// Without a `from_node` the node that can pay for the lowest fee is picked,
+    /// `outgoingChannels` can't be used then.
+    ///
+    /// Splitting the payment into parts can be turned off with `allowSplit`, or limited
+    /// with `maxParts` and `minPartSats`. The parts that were sent are on the result.
     #[wasm_bindgen]
     pub async fn pay_invoice(
         &self,
-        from_node: String,
+        from_node: Option<String>,
         invoice_str: String,
         amt_sats: Option<u64>,
-        labels: JsValue, /* Vec<String> */
+        labels: JsValue,  /* Vec<String> */
+        options: JsValue, /* Option<PayInvoiceOptions> */
     ) -> Result<MutinyInvoice, MutinyJsError> {
-        let from_node = PublicKey::from_str(&from_node)?;
+        let from_node = from_node.map(|n| PublicKey::from_str(&n)).transpose()?;
         let invoice = Invoice::from_str(&invoice_str)?;
         let labels: Vec<String> = labels
             .into_serde()
             .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let options = options
+            .into_serde::<Option<PayInvoiceOptions>>()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?
+            .unwrap_or_default();
+        let outgoing_channels = options
+            .outgoing_channels
+            .map(|outpoints| {
+                outpoints
+                    .iter()
+                    .map(|o| OutPoint::from_str(o))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let options = PaymentOptions {
+            max_fee: max_fee(options.max_fee_sats, options.max_fee_percent),
+            outgoing_channels,
+            mpp: mpp_options(
+                options.allow_split,
+                options.max_parts,
+                options.min_part_sats,
+            ),
+        };
+        let node_manager = &self.inner.node_manager;
+        let invoice = match from_node {
+            Some(from_node) => {
+                node_manager
+                    .pay_invoice(&from_node, &invoice, amt_sats, options, labels)
+                    .await?
+            }
+            None => {
+                node_manager
+                    .pay_invoice_from_best_node(&invoice, amt_sats, options, labels)
+                    .await?
+            }
+        };
+        Ok(invoice.into())
+    }
+
+    /// Finds a route for a lightning invoice from the selected node without paying it.
+    /// Returns the expected fee so it can be shown before the user confirms.
+    /// An amount should only be provided if the invoice does not have an amount.
+    #[wasm_bindgen]
+    pub async fn dry_run_payment(
+        &self,
+        from_node: String,
+        invoice_str: String,
+        amt_sats: Option<u64>,
+    ) -> Result<PaymentEstimate, MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let invoice = Invoice::from_str(&invoice_str)?;
+        Ok(self
+            .inner
+            .node_manager
+            .dry_run_payment(&from_node, &invoice, amt_sats)
+            .await?
+            .into())
+    }
+
+    /// Estimates the routing fee range and hop count of paying a lightning invoice,
+    /// so a fee like "≈ 12 sats" can be shown before the user confirms.
+    /// An amount should only be provided if the invoice does not have an amount.
+    #[wasm_bindgen]
+    pub async fn estimate_payment_fee(
+        &self,
+        from_node: String,
+        invoice_str: String,
+        amt_sats: Option<u64>,
+    ) -> Result<FeeEstimate, MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let invoice = Invoice::from_str(&invoice_str)?;
         Ok(self
             .inner
             .node_manager
-            .pay_invoice(&from_node, &invoice, amt_sats, labels)
+            .estimate_payment_fee(&from_node, &invoice, amt_sats)
             .await?
             .into())
     }
 
     /// Sends a spontaneous payment to a node from the selected node.
     /// The amount should be in satoshis.
+    ///
+    /// The routing fee can be limited like with [MutinyWallet::pay_invoice].
+    ///
+    /// Without a `from_node` the node with the most outbound liquidity is picked.
     #[wasm_bindgen]
     pub async fn keysend(
         &self,
-        from_node: String,
+        from_node: Option<String>,
         to_node: String,
         amt_sats: u64,
+        max_fee_sats: Option<u64>,
+        max_fee_percent: Option<f64>,
         labels: JsValue, /* Vec<String> */
     ) -> Result<MutinyInvoice, MutinyJsError> {
-        let from_node = PublicKey::from_str(&from_node)?;
+        let from_node = from_node.map(|n| PublicKey::from_str(&n)).transpose()?;
         let to_node = PublicKey::from_str(&to_node)?;
         let labels: Vec<String> = labels
             .into_serde()
             .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
-        Ok(self
-            .inner
-            .node_manager
-            .keysend(&from_node, to_node, amt_sats, labels)
-            .await?
-            .into())
+        let max_fee = max_fee(max_fee_sats, max_fee_percent);
+        let node_manager = &self.inner.node_manager;
+        let invoice = match from_node {
+            Some(from_node) => {
+                node_manager
+                    .keysend(&from_node, to_node, amt_sats, max_fee, labels)
+                    .await?
+            }
+            None => {
+                node_manager
+                    .keysend_from_best_node(to_node, amt_sats, max_fee, labels)
+                    .await?
+            }
+        };
+        Ok(invoice.into())
     }
 
     /// Decodes a lightning invoice into useful information.
@@ -468,6 +1374,19 @@ impl MutinyWallet {
             .into())
     }
 
+    /// Parses any kind of payment string, this can be a BOLT11 invoice, BIP21 URI,
+    /// on-chain address, LNURL, lightning address or node pubkey.
+    /// Will return an error if the invoice or address is for a different network.
+    #[wasm_bindgen]
+    pub fn parse_payment_request(
+        &self,
+        str: String,
+    ) -> Result<JsValue /* PaymentRequest */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.parse_payment_request(&str)?,
+        )?)
+    }
+
     /// Calls upon a LNURL to get the parameters for it.
     /// This contains what kind of LNURL it is (pay, withdrawal, auth, etc).
     #[wasm_bindgen]
@@ -478,27 +1397,55 @@ impl MutinyWallet {
 
     /// Calls upon a LNURL and pays it.
     /// This will fail if the LNURL is not a LNURL pay.
+    #[allow(clippy::too_many_arguments)]
     #[wasm_bindgen]
     pub async fn lnurl_pay(
         &self,
         from_node: String,
         lnurl: String,
         amount_sats: u64,
+        comment: Option<String>,
+        payer_data: JsValue, /* Option<PayerData> */
+        max_fee_sats: Option<u64>,
+        max_fee_percent: Option<f64>,
         labels: JsValue, /* Vec<String> */
     ) -> Result<MutinyInvoice, MutinyJsError> {
         let from_node = PublicKey::from_str(&from_node)?;
         let lnurl = LnUrl::from_str(&lnurl)?;
+        let payer_data: Option<PayerData> = payer_data
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
         let labels: Vec<String> = labels
             .into_serde()
             .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
         Ok(self
             .inner
             .node_manager
-            .lnurl_pay(&from_node, &lnurl, amount_sats, labels)
+            .lnurl_pay(
+                &from_node,
+                &lnurl,
+                amount_sats,
+                comment,
+                payer_data,
+                max_fee(max_fee_sats, max_fee_percent),
+                labels,
+            )
             .await?
             .into())
     }
 
+    /// Gets the comment and payer data that were sent with a LNURL-pay payment
+    #[wasm_bindgen]
+    pub fn get_lnurl_pay_metadata(
+        &self,
+        payment_hash: String,
+    ) -> Result<JsValue /* Option<LnUrlPayMetadata> */, MutinyJsError> {
+        let hash: sha256::Hash = sha256::Hash::from_str(&payment_hash)?;
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_lnurl_pay_metadata(&hash)?,
+        )?)
+    }
+
     /// Calls upon a LNURL and withdraws from it.
     /// This will fail if the LNURL is not a LNURL withdrawal.
     #[wasm_bindgen]
@@ -515,6 +1462,43 @@ impl MutinyWallet {
             .await?)
     }
 
+    /// Calls upon a LNURL and asks the service to open a channel to the selected node.
+    /// This will fail if the LNURL is not a LNURL channel.
+    #[wasm_bindgen]
+    pub async fn lnurl_channel(
+        &self,
+        from_node: String,
+        lnurl: String,
+    ) -> Result<(), MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let lnurl = LnUrl::from_str(&lnurl)?;
+        Ok(self
+            .inner
+            .node_manager
+            .lnurl_channel(&from_node, &lnurl)
+            .await?)
+    }
+
+    /// Withdraws everything from a LNURL-withdraw endpoint, such as a custodial wallet.
+    /// This makes as many withdrawals as needed, limited by our inbound liquidity.
+    #[wasm_bindgen]
+    pub async fn drain_lnurl_withdraw(
+        &self,
+        lnurl: String,
+        labels: JsValue, /* Vec<String> */
+    ) -> Result<JsValue /* LnUrlDrainResult */, MutinyJsError> {
+        let lnurl = LnUrl::from_str(&lnurl)?;
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let result = self
+            .inner
+            .node_manager
+            .drain_lnurl_withdraw(&lnurl, labels)
+            .await?;
+        Ok(JsValue::from_serde(&result)?)
+    }
+
     /// Creates a new LNURL-auth profile.
     #[wasm_bindgen]
     pub fn create_lnurl_auth_profile(&self, name: String) -> Result<u32, MutinyJsError> {
@@ -565,6 +1549,23 @@ impl MutinyWallet {
             .into())
     }
 
+    /// Waits until the invoice with the given payment hash is paid and returns it.
+    /// Errors if it isn't paid within `timeout_secs` or the invoice expires first.
+    #[wasm_bindgen]
+    pub async fn wait_for_payment(
+        &self,
+        hash: String,
+        timeout_secs: u64,
+    ) -> Result<MutinyInvoice, MutinyJsError> {
+        let hash: sha256::Hash = sha256::Hash::from_str(&hash)?;
+        Ok(self
+            .inner
+            .node_manager
+            .wait_for_payment(&hash, timeout_secs)
+            .await?
+            .into())
+    }
+
     /// Gets an invoice from the node manager.
     /// This includes sent and received invoices.
     #[wasm_bindgen]
@@ -574,12 +1575,88 @@ impl MutinyWallet {
         )?)
     }
 
-    /// Gets an channel closure from the node manager.
+    /// Lists the lightning payments with a status of "Pending", "Succeeded",
+    /// "Failed" or "Expired", along with why they failed.
+    /// Every payment is returned if no status is given.
     #[wasm_bindgen]
-    pub async fn get_channel_closure(
+    pub async fn list_payments(
         &self,
-        user_channel_id: String,
-    ) -> Result<ChannelClosure, MutinyJsError> {
+        status_filter: Option<String>,
+    ) -> Result<JsValue /* Vec<MutinyPayment> */, MutinyJsError> {
+        let status_filter: Option<PaymentStatus> = status_filter
+            .map(|s| serde_json::from_value(serde_json::Value::String(s)))
+            .transpose()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_payments(status_filter).await?,
+        )?)
+    }
+
+    /// Gets a summary of the fees paid by the wallet between the given unix timestamps.
+    /// If either timestamp is not provided the period is unbounded.
+    #[wasm_bindgen]
+    pub async fn get_fee_summary(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<FeeSummary, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .get_fee_summary(start, end)
+            .await?
+            .into())
+    }
+
+    /// Lists the payments our nodes routed, newest first.
+    #[wasm_bindgen]
+    pub async fn list_forwards(
+        &self,
+    ) -> Result<JsValue /* Vec<ForwardedPayment> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_forwards().await?,
+        )?)
+    }
+
+    /// Gets the routing fees our nodes earned and how much each channel routed
+    /// between the given unix timestamps. If either timestamp is not provided
+    /// the period is unbounded.
+    #[wasm_bindgen]
+    pub async fn get_routing_stats(
+        &self,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<JsValue /* RoutingStats */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self
+                .inner
+                .node_manager
+                .get_routing_stats(start, end)
+                .await?,
+        )?)
+    }
+
+    /// Turns the local usage statistics on or off, they are off by default.
+    #[wasm_bindgen]
+    pub fn set_usage_stats_enabled(&self, enabled: bool) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.set_usage_stats_enabled(enabled)?)
+    }
+
+    /// Gets statistics about the wallet's payments, such as success rates and average fees.
+    /// Returns null unless the user opted in.
+    #[wasm_bindgen]
+    pub async fn get_usage_stats(&self) -> Result<JsValue /* Option<UsageStats> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_usage_stats().await?,
+        )?)
+    }
+
+    /// Gets an channel closure from the node manager.
+    #[wasm_bindgen]
+    pub async fn get_channel_closure(
+        &self,
+        user_channel_id: String,
+    ) -> Result<ChannelClosure, MutinyJsError> {
         let user_channel_id: [u8; 16] = FromHex::from_hex(&user_channel_id)?;
         Ok(self
             .inner
@@ -601,8 +1678,103 @@ impl MutinyWallet {
         Ok(JsValue::from_serde(&channel_closures)?)
     }
 
+    /// Lists the channels bought from LSPs, newest first.
+    ///
+    /// Orders where the LSP did not deliver what was paid for are marked as
+    /// `UnderDelivered` and list the discrepancies that were found.
+    #[wasm_bindgen]
+    pub async fn get_lsp_orders(&self) -> Result<JsValue /* Vec<LspOrder> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_lsp_orders().await?,
+        )?)
+    }
+
+    /// Gets what receiving the amount will cost in LSP fees, before creating the invoice.
+    /// The amount is in satoshis. Returns undefined if no LSP is used.
+    #[wasm_bindgen]
+    pub async fn get_lsp_fee_quote(
+        &self,
+        amount: u64,
+    ) -> Result<JsValue /* Option<LspFeeQuote> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_lsp_fee_quote(amount).await?,
+        )?)
+    }
+
+    /// Summarizes how much we can receive over lightning across all our channels.
+    #[wasm_bindgen]
+    pub async fn get_inbound_liquidity(
+        &self,
+    ) -> Result<JsValue /* InboundLiquidity */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_inbound_liquidity().await?,
+        )?)
+    }
+
+    /// Gets the number of confirmations after which a transaction is considered safe.
+    #[wasm_bindgen]
+    pub fn get_safe_confirmations(&self) -> u32 {
+        self.inner.node_manager.get_safe_confirmations()
+    }
+
+    /// Sets the number of confirmations after which a transaction is considered safe,
+    /// a second transaction confirmed event is sent when it is reached.
+    #[wasm_bindgen]
+    pub fn set_safe_confirmations(&self, confirmations: u32) -> Result<(), MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .set_safe_confirmations(confirmations)?)
+    }
+
+    /// Gets the inbound liquidity, in sats, below which we alert the user.
+    #[wasm_bindgen]
+    pub fn get_inbound_liquidity_threshold(&self) -> Result<Option<u64>, MutinyJsError> {
+        Ok(self.inner.node_manager.get_inbound_liquidity_threshold()?)
+    }
+
+    /// Sets the inbound liquidity, in sats, below which we alert the user.
+    /// Leaving it undefined turns the alert off.
+    #[wasm_bindgen]
+    pub fn set_inbound_liquidity_threshold(
+        &self,
+        threshold_sats: Option<u64>,
+    ) -> Result<(), MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .set_inbound_liquidity_threshold(threshold_sats)?)
+    }
+
+    /// Lists the urls of the LSPs we use.
+    #[wasm_bindgen]
+    pub async fn list_lsps(&self) -> Result<JsValue /* Vec<String> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_lsps().await,
+        )?)
+    }
+
+    /// Adds an LSP, nodes without an LSP start using it.
+    #[wasm_bindgen]
+    pub async fn add_lsp(&self, url: String) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.add_lsp(url).await?)
+    }
+
+    /// Removes an LSP, nodes that used it switch to another one.
+    #[wasm_bindgen]
+    pub async fn remove_lsp(&self, url: String) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.remove_lsp(url).await?)
+    }
+
     /// Opens a channel from our selected node to the given pubkey.
-    /// The amount is in satoshis.
+    /// The amount is in satoshis and the fee rate is in sat/vbyte.
+    ///
+    /// If a fee rate is not provided, one will be used from the fee estimator.
+    /// The optional `push_msat` is given to the peer when the channel opens,
+    /// so the channel starts with inbound liquidity.
+    ///
+    /// Channels are private unless `announce` is set, announced channels
+    /// are gossiped to the network and can be used to route payments.
     ///
     /// The node must be online and have a connection to the peer.
     /// The wallet much have enough funds to open the channel.
@@ -612,6 +1784,9 @@ impl MutinyWallet {
         from_node: String,
         to_pubkey: Option<String>,
         amount: u64,
+        fee_rate: Option<f32>,
+        push_msat: Option<u64>,
+        announce: bool,
     ) -> Result<MutinyChannel, MutinyJsError> {
         let from_node = PublicKey::from_str(&from_node)?;
 
@@ -625,7 +1800,17 @@ impl MutinyWallet {
         Ok(self
             .inner
             .node_manager
-            .open_channel(&from_node, to_pubkey, amount, None)
+            .open_channel(
+                &from_node,
+                to_pubkey,
+                amount,
+                OpenChannelOptions {
+                    fee_rate,
+                    push_msat,
+                    user_channel_id: None,
+                    announce,
+                },
+            )
             .await?
             .into())
     }
@@ -657,11 +1842,108 @@ impl MutinyWallet {
     }
 
     /// Closes a channel with the given outpoint.
+    ///
+    /// If an address is given the funds are sent there on a cooperative close,
+    /// rather than back to the wallet.
     #[wasm_bindgen]
-    pub async fn close_channel(&self, outpoint: String) -> Result<(), MutinyJsError> {
+    pub async fn close_channel(
+        &self,
+        outpoint: String,
+        address: Option<String>,
+    ) -> Result<(), MutinyJsError> {
         let outpoint: OutPoint =
             OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
-        Ok(self.inner.node_manager.close_channel(&outpoint).await?)
+        let address = address.map(|a| Address::from_str(&a)).transpose()?;
+        Ok(self
+            .inner
+            .node_manager
+            .close_channel(&outpoint, address)
+            .await?)
+    }
+
+    /// Force closes the channel with the given outpoint, only use this if the peer is
+    /// unresponsive. Returns how long the funds are timelocked and roughly when
+    /// they will be swept back to the wallet.
+    #[wasm_bindgen]
+    pub async fn force_close_channel(
+        &self,
+        outpoint: String,
+    ) -> Result<JsValue /* ForceCloseEstimate */, MutinyJsError> {
+        let outpoint: OutPoint =
+            OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(JsValue::from_serde(
+            &self
+                .inner
+                .node_manager
+                .force_close_channel(&outpoint)
+                .await?,
+        )?)
+    }
+
+    /// Abandons a channel whose funding transaction never confirmed and can't confirm anymore,
+    /// it errors while the esplora server still knows about the transaction.
+    #[wasm_bindgen]
+    pub async fn abandon_channel(&self, outpoint: String) -> Result<(), MutinyJsError> {
+        let outpoint: OutPoint =
+            OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.node_manager.abandon_channel(&outpoint).await?)
+    }
+
+    /// Lists the balances of closed channels that are still being claimed on-chain,
+    /// with how many blocks until each can be swept back to the wallet.
+    #[wasm_bindgen]
+    pub async fn list_pending_claims(
+        &self,
+    ) -> Result<JsValue /* Vec<PendingClaim> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_pending_claims().await?,
+        )?)
+    }
+
+    /// Exports a CSV summary of the channel monitor with the given funding outpoint.
+    /// This includes the latest update id and any balances still pending claims.
+    #[wasm_bindgen]
+    pub async fn export_channel_monitor_summary(
+        &self,
+        outpoint: String,
+    ) -> Result<String, MutinyJsError> {
+        let outpoint: OutPoint =
+            OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .export_channel_monitor_summary(&outpoint)
+            .await?
+            .to_csv())
+    }
+
+    /// Lists the outgoing HTLCs that will force close their channel soon if they
+    /// aren't resolved, soonest first.
+    #[wasm_bindgen]
+    pub async fn list_expiring_htlcs(
+        &self,
+    ) -> Result<JsValue /* Vec<ExpiringHtlc> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_expiring_htlcs().await?,
+        )?)
+    }
+
+    /// Gets the forensic record of a closed channel, with the closure reason,
+    /// commitment and sweep txids, and the balances at the time of the close.
+    #[wasm_bindgen]
+    pub async fn get_channel_forensics(
+        &self,
+        channel_id: String,
+    ) -> Result<JsValue /* ChannelForensics */, MutinyJsError> {
+        let channel_id: [u8; 32] =
+            FromHex::from_hex(&channel_id).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(JsValue::from_serde(
+            &self
+                .inner
+                .node_manager
+                .get_channel_forensics(&channel_id)
+                .await?,
+        )?)
     }
 
     /// Lists all the channels for all the nodes in the node manager.
@@ -672,6 +1954,16 @@ impl MutinyWallet {
         )?)
     }
 
+    /// Lists the channels that are still being opened, with how far along each one is.
+    #[wasm_bindgen]
+    pub async fn list_pending_channels(
+        &self,
+    ) -> Result<JsValue /* Vec<PendingChannel> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.list_pending_channels().await?,
+        )?)
+    }
+
     /// Lists all the peers for all the nodes in the node manager.
     #[wasm_bindgen]
     pub async fn list_peers(&self) -> Result<JsValue /* Vec<MutinyPeer> */, MutinyJsError> {
@@ -685,32 +1977,59 @@ impl MutinyWallet {
     pub async fn get_activity(&self) -> Result<JsValue /* Vec<ActivityItem> */, MutinyJsError> {
         // get activity from the node manager
         let activity = self.inner.node_manager.get_activity().await?;
-        let mut activity: Vec<ActivityItem> = activity.into_iter().map(|a| a.into()).collect();
-
-        // add contacts to the activity
-        let contacts = self.inner.node_manager.get_contacts()?;
-        for a in activity.iter_mut() {
-            // find labels that have a contact and add them to the item
-            for label in a.labels.iter() {
-                if let Some(contact) = contacts.get(label) {
-                    a.contacts.push(Contact::from(contact.clone()));
-                }
-            }
-            // remove labels that have a contact to prevent duplicates
-            a.labels.retain(|l| !contacts.contains_key(l));
-        }
+        activity_with_contacts(&self.inner.node_manager, activity)
+    }
 
-        Ok(JsValue::from_serde(&activity)?)
+    /// Returns all the on-chain and lightning activity from the wallet, with paid lightning
+    /// payments below the threshold grouped per counterparty per day.
+    /// The payment hashes of the grouped payments are included for drilling down.
+    #[wasm_bindgen]
+    pub async fn get_aggregated_activity(
+        &self,
+        threshold_sats: u64,
+    ) -> Result<JsValue /* Vec<ActivityItem> */, MutinyJsError> {
+        let activity = self
+            .inner
+            .node_manager
+            .get_aggregated_activity(threshold_sats)
+            .await?;
+        activity_with_contacts(&self.inner.node_manager, activity)
     }
 
-    /// Initiates a redshift
+    /// Hides an item from the activity, it will no longer be returned by `get_activity`.
     #[wasm_bindgen]
+    pub fn hide_activity_item(&self, id: String) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.hide_activity_item(id)?)
+    }
+
+    /// Deletes the failed and expired lightning payments that were last updated before
+    /// `older_than`, a unix timestamp in seconds. Returns the number of payments deleted.
+    #[wasm_bindgen]
+    pub async fn purge_failed_payments(&self, older_than: u64) -> Result<usize, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .purge_failed_payments(older_than)
+            .await?)
+    }
+
+    /// Initiates a redshift.
+    ///
+    /// The fee budget limits the routing fees of all its payments, the percent is of the
+    /// redshifted amount. Failed payments are retried after the retry interval,
+    /// doubling with each failure in a row.
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
     pub async fn init_redshift(
         &self,
         outpoint: String,
         lightning_recipient_pubkey: Option<String>,
         lightning_recipient_connection_string: Option<String>,
         onchain_recipient: Option<String>,
+        max_fee_sats: Option<u64>,
+        max_fee_percent: Option<f64>,
+        retry_interval_secs: Option<u64>,
+        max_attempts: Option<u32>,
     ) -> Result<Redshift, MutinyJsError> {
         let outpoint: OutPoint =
             OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
@@ -732,6 +2051,13 @@ impl MutinyWallet {
             }
             (None, None) => RedshiftRecipient::OnChain(None),
         };
+        let defaults = RedshiftOptions::default();
+        let options = RedshiftOptions {
+            fee_budget: max_fee(max_fee_sats, max_fee_percent),
+            retry_interval_secs: retry_interval_secs.unwrap_or(defaults.retry_interval_secs),
+            max_attempts,
+            ..defaults
+        };
         Ok(self
             .inner
             .node_manager
@@ -740,11 +2066,176 @@ impl MutinyWallet {
                 redshift_recipient,
                 introduction_node,
                 lightning_recipient_connection_string.as_deref(),
+                options,
+            )
+            .await?
+            .into())
+    }
+
+    /// Sets the fees a channel charges for forwarding payments, identified by its funding outpoint.
+    /// The settings are saved and applied again whenever the node starts.
+    #[wasm_bindgen]
+    pub async fn update_channel_config(
+        &self,
+        outpoint: String,
+        base_fee_msat: u32,
+        proportional_millionths: u32,
+        cltv_expiry_delta: u16,
+    ) -> Result<(), MutinyJsError> {
+        let outpoint: OutPoint =
+            OutPoint::from_str(&outpoint).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self
+            .inner
+            .node_manager
+            .update_channel_config(
+                &outpoint,
+                base_fee_msat,
+                proportional_millionths,
+                cltv_expiry_delta,
             )
+            .await?)
+    }
+
+    /// Gets the alias, color and addresses our nodes announce, if they were set.
+    #[wasm_bindgen]
+    pub fn get_node_announcement_config(
+        &self,
+    ) -> Result<JsValue /* Option<NodeAnnouncementConfig> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_node_announcement_config()?,
+        )?)
+    }
+
+    /// Sets the alias, color and `ip:port` addresses our nodes announce to the network.
+    /// The alias is at most 32 bytes and the color is hex encoded RGB, ie `ff9900`.
+    /// Only nodes with announced channels are announced.
+    #[wasm_bindgen]
+    pub async fn set_node_announcement_config(
+        &self,
+        alias: String,
+        color: String,
+        addresses: JsValue, /* Vec<String> */
+    ) -> Result<(), MutinyJsError> {
+        let addresses: Vec<String> = addresses
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let config = nodemanager::NodeAnnouncementConfig {
+            alias,
+            color,
+            addresses,
+        };
+        Ok(self
+            .inner
+            .node_manager
+            .set_node_announcement_config(config)
+            .await?)
+    }
+
+    /// Gets which inbound channels our nodes accept.
+    #[wasm_bindgen]
+    pub fn get_channel_acceptance_policy(
+        &self,
+    ) -> Result<JsValue /* ChannelAcceptancePolicy */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_channel_acceptance_policy()?,
+        )?)
+    }
+
+    /// Sets which inbound channels our nodes accept, unset limits allow any channel.
+    /// Channels from our LSPs or requested with LNURL-channel are always accepted.
+    #[wasm_bindgen]
+    pub fn set_channel_acceptance_policy(
+        &self,
+        min_channel_size_sats: Option<u64>,
+        allowed_peers: JsValue, /* Option<Vec<String>> */
+        max_channels: Option<u32>,
+        require_zero_reserve: bool,
+    ) -> Result<(), MutinyJsError> {
+        let allowed_peers: Option<Vec<String>> = allowed_peers
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        let allowed_peers = allowed_peers
+            .map(|peers| {
+                peers
+                    .iter()
+                    .map(|p| PublicKey::from_str(p))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let policy = nodemanager::ChannelAcceptancePolicy {
+            min_channel_size_sats,
+            allowed_peers,
+            max_channels: max_channels.map(|m| m as usize),
+            require_zero_reserve,
+        };
+        Ok(self
+            .inner
+            .node_manager
+            .set_channel_acceptance_policy(policy)?)
+    }
+
+    /// Calls the callback with each inbound channel that passes the channel acceptance
+    /// policy, it returns true or a promise of true to accept it. Without a callback
+    /// the channels are accepted.
+    #[wasm_bindgen]
+    pub fn set_channel_acceptor(&self, callback: Option<js_sys::Function>) {
+        let acceptor = callback.map(|callback| {
+            Arc::new(JsChannelAcceptor::new(callback)) as Arc<dyn mutiny_core::ChannelAcceptor>
+        });
+        self.inner.node_manager.set_channel_acceptor(acceptor);
+    }
+
+    /// Gets what swapping an amount of lightning balance on-chain through Boltz would cost
+    #[wasm_bindgen]
+    pub async fn swap_out_quote(&self, amount_sats: u64) -> Result<SwapOutQuote, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .swap_out_quote(amount_sats)
             .await?
             .into())
     }
 
+    /// Starts swapping lightning balance from the node to an on-chain address through Boltz.
+    /// A new address in the wallet is used if none is given.
+    ///
+    /// The swap continues in the background, check on it with `get_swap`.
+    #[wasm_bindgen]
+    pub async fn swap_out(
+        &self,
+        from_node: String,
+        amount_sats: u64,
+        address: Option<String>,
+    ) -> Result<SwapOut, MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let address = address.map(|a| Address::from_str(&a)).transpose()?;
+        Ok(self
+            .inner
+            .node_manager
+            .init_swap_out(&from_node, amount_sats, address)
+            .await?
+            .into())
+    }
+
+    /// Gets a swap by its Boltz id
+    #[wasm_bindgen]
+    pub fn get_swap(&self, id: String) -> Result<Option<SwapOut>, MutinyJsError> {
+        Ok(self.inner.node_manager.get_swap(&id)?.map(|s| s.into()))
+    }
+
+    /// Lists every swap, newest first
+    #[wasm_bindgen]
+    pub fn list_swaps(&self) -> Result<JsValue /* Vec<SwapOut> */, MutinyJsError> {
+        let swaps: Vec<SwapOut> = self
+            .inner
+            .node_manager
+            .list_swaps()?
+            .into_iter()
+            .map(|s| s.into())
+            .collect();
+        Ok(JsValue::from_serde(&swaps)?)
+    }
+
     /// Get all redshift attempts for a given utxo
     #[wasm_bindgen]
     pub fn get_redshift(&self, id: String) -> Result<Option<Redshift>, MutinyJsError> {
@@ -753,6 +2244,27 @@ impl MutinyWallet {
         Ok(self.inner.node_manager.get_redshift(&id)?.map(|r| r.into()))
     }
 
+    /// Gets how far along a redshift is: sats moved, fees paid, attempts and an ETA
+    #[wasm_bindgen]
+    pub fn get_redshift_status(
+        &self,
+        id: String,
+    ) -> Result<JsValue /* RedshiftProgress */, MutinyJsError> {
+        let id: [u8; 16] =
+            FromHex::from_hex(&id).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_redshift_status(&id)?,
+        )?)
+    }
+
+    /// Cancels a redshift in progress, payment attempts stop and its channels are closed
+    #[wasm_bindgen]
+    pub async fn cancel_redshift(&self, id: String) -> Result<Redshift, MutinyJsError> {
+        let id: [u8; 16] =
+            FromHex::from_hex(&id).map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.node_manager.cancel_redshift(&id).await?.into())
+    }
+
     pub fn get_address_labels(
         &self,
     ) -> Result<JsValue /* Map<Address, Vec<String>> */, MutinyJsError> {
@@ -805,6 +2317,36 @@ impl MutinyWallet {
             .set_invoice_labels(invoice, labels)?)
     }
 
+    /// Gets the private memo of a transaction or payment, the id is its txid or payment hash
+    pub fn get_memo(&self, id: String) -> Result<Option<String>, MutinyJsError> {
+        Ok(self.inner.node_manager.get_memo(id)?)
+    }
+
+    /// Sets the private memo of a transaction or payment, the id is its txid or payment hash.
+    /// An empty memo removes it.
+    pub fn set_memo(&self, id: String, memo: Option<String>) -> Result<(), MutinyJsError> {
+        Ok(self.inner.node_manager.set_memo(id, memo)?)
+    }
+
+    /// Exports the address and transaction labels in the BIP329 JSONL format.
+    /// Memos are private, they are only exported when `include_memos` is set.
+    pub fn export_bip329_labels(&self, include_memos: bool) -> Result<String, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .export_bip329_labels(include_memos)?)
+    }
+
+    /// Imports labels in the BIP329 JSONL format, adding them to the existing labels.
+    /// Transaction labels are only imported as memos when `memos` is set.
+    /// Returns the number of labels imported.
+    pub fn import_bip329_labels(&self, jsonl: String, memos: bool) -> Result<usize, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .import_bip329_labels(&jsonl, memos)?)
+    }
+
     pub fn get_contacts(&self) -> Result<JsValue /* Map<String, Contact>*/, MutinyJsError> {
         Ok(JsValue::from_serde(
             &self
@@ -861,10 +2403,114 @@ impl MutinyWallet {
         )?)
     }
 
-    /// Gets the current bitcoin price in USD.
+    /// Gets the current bitcoin price in the given fiat currency, ie "eur",
+    /// or in the one from `set_fiat_currency` if none is given.
+    #[wasm_bindgen]
+    pub async fn get_bitcoin_price(&self, currency: Option<String>) -> Result<f32, MutinyJsError> {
+        let currency = currency.map(|c| FiatCurrency::from_str(&c)).transpose()?;
+        Ok(self.inner.node_manager.get_bitcoin_price(currency).await?)
+    }
+
+    /// Fills in what bitcoin was worth when each payment and transaction without
+    /// a recorded price happened, from the price history of the price api.
+    /// Returns how many were filled in.
+    #[wasm_bindgen]
+    pub async fn backfill_fiat_prices(&self) -> Result<usize, MutinyJsError> {
+        let count = self.inner.node_manager.backfill_fiat_prices().await?;
+        // Sleep to wait for indexed db to finish writing
+        sleep(500).await;
+        Ok(count)
+    }
+
+    /// Gets the lowercase code of the fiat currency prices are shown in, ie "usd".
+    #[wasm_bindgen]
+    pub fn get_fiat_currency(&self) -> Result<String, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .get_fiat_currency()?
+            .code()
+            .to_string())
+    }
+
+    /// Sets the fiat currency prices are shown in, ie "eur".
+    #[wasm_bindgen]
+    pub fn set_fiat_currency(&self, currency: String) -> Result<(), MutinyJsError> {
+        let currency = FiatCurrency::from_str(&currency)?;
+        Ok(self.inner.node_manager.set_fiat_currency(currency)?)
+    }
+
+    /// Gets where bitcoin prices are fetched from and how long they are kept.
+    #[wasm_bindgen]
+    pub fn get_price_config(&self) -> Result<JsValue /* PriceConfig */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_price_config()?,
+        )?)
+    }
+
+    /// Sets where bitcoin prices are fetched from, a coingecko compatible https api
+    /// or coingecko itself when no url is given, and how many seconds they are kept, up to a day.
+    ///
+    /// When disabled no prices are fetched at all, they have to be given with `set_bitcoin_price`.
+    #[wasm_bindgen]
+    pub async fn set_price_config(
+        &self,
+        price_api_url: Option<String>,
+        disabled: bool,
+        cache_ttl_secs: Option<u64>,
+    ) -> Result<(), MutinyJsError> {
+        let source = match (disabled, price_api_url) {
+            (true, _) => PriceSource::Disabled,
+            (false, Some(url)) => PriceSource::Custom(url),
+            (false, None) => PriceSource::Coingecko,
+        };
+        let config = PriceConfig {
+            source,
+            cache_ttl_secs: cache_ttl_secs.unwrap_or(pricing::DEFAULT_PRICE_CACHE_TTL_SECS),
+        };
+        Ok(self.inner.node_manager.set_price_config(config).await?)
+    }
+
+    /// Gives the current bitcoin price in a fiat currency, for apps that get prices themselves.
     #[wasm_bindgen]
-    pub async fn get_bitcoin_price(&self) -> Result<f32, MutinyJsError> {
-        Ok(self.inner.node_manager.get_bitcoin_price().await?)
+    pub async fn set_bitcoin_price(
+        &self,
+        currency: String,
+        price: f32,
+    ) -> Result<(), MutinyJsError> {
+        let currency = FiatCurrency::from_str(&currency)?;
+        Ok(self
+            .inner
+            .node_manager
+            .set_bitcoin_price(currency, price)
+            .await?)
+    }
+
+    /// Gets the settings for which inbound payments will be accepted.
+    #[wasm_bindgen]
+    pub fn get_inbound_payment_config(
+        &self,
+    ) -> Result<JsValue /* InboundPaymentConfig */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_inbound_payment_config()?,
+        )?)
+    }
+
+    /// Sets the settings for which inbound payments will be accepted.
+    /// The minimum HTLC is in millisatoshis and only applies to channels opened after it is set.
+    /// Payments below the minimum, or below the dust limit if dust payments are not
+    /// accepted, will be rejected when they are received.
+    #[wasm_bindgen]
+    pub fn set_inbound_payment_config(
+        &self,
+        min_htlc_msat: u64,
+        accept_dust_payments: bool,
+    ) -> Result<(), MutinyJsError> {
+        let config = nodemanager::InboundPaymentConfig {
+            min_htlc_msat,
+            accept_dust_payments,
+        };
+        Ok(self.inner.node_manager.set_inbound_payment_config(config)?)
     }
 
     /// Exports the current state of the node manager to a json object.
@@ -878,6 +2524,8 @@ impl MutinyWallet {
     pub fn get_nwc_uri(&self) -> Result<String, MutinyJsError> {
         self.inner
             .nostr
+            .as_ref()
+            .ok_or(MutinyJsError::WatchOnly)?
             .get_nwc_uri()
             .map_err(|_| MutinyJsError::JsonReadWriteError)
     }
@@ -890,6 +2538,145 @@ impl MutinyWallet {
         Ok(())
     }
 
+    /// Zaps a nostr note or profile, `target` is a `note` or `npub`.
+    #[wasm_bindgen]
+    pub async fn zap(
+        &self,
+        from_node: String,
+        target: String,
+        amount_sats: u64,
+        comment: Option<String>,
+    ) -> Result<MutinyInvoice, MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        Ok(self
+            .inner
+            .zap(&from_node, &target, amount_sats, comment)
+            .await?
+            .into())
+    }
+
+    /// Gets the zap that was made with the given payment
+    #[wasm_bindgen]
+    pub fn get_zap(
+        &self,
+        payment_hash: String,
+    ) -> Result<JsValue /* Option<ZapRecord> */, MutinyJsError> {
+        let hash: sha256::Hash = sha256::Hash::from_str(&payment_hash)?;
+        Ok(JsValue::from_serde(&self.inner.get_zap(&hash)?)?)
+    }
+
+    /// Lists all the zaps we have sent
+    #[wasm_bindgen]
+    pub fn list_zaps(&self) -> Result<JsValue /* Vec<ZapRecord> */, MutinyJsError> {
+        Ok(JsValue::from_serde(&self.inner.list_zaps()?)?)
+    }
+
+    /// Creates a credential that lets a third party create invoices for this wallet
+    /// but nothing else. Returns the Nostr Wallet Connect URI to give to them.
+    #[wasm_bindgen]
+    pub fn create_receive_credential(
+        &self,
+        name: String,
+        max_invoices_per_hour: u32,
+        max_amount_sats: Option<u64>,
+    ) -> Result<String, MutinyJsError> {
+        let (_, uri) =
+            self.inner
+                .create_receive_credential(name, max_invoices_per_hour, max_amount_sats)?;
+        Ok(uri)
+    }
+
+    /// Gets the connect URI of a receive credential that hasn't been revoked
+    #[wasm_bindgen]
+    pub fn get_receive_credential_uri(
+        &self,
+        client_pubkey: String,
+    ) -> Result<String, MutinyJsError> {
+        Ok(self.inner.get_receive_credential_uri(&client_pubkey)?)
+    }
+
+    /// Lists all receive credentials, including revoked ones
+    #[wasm_bindgen]
+    pub fn list_receive_credentials(
+        &self,
+    ) -> Result<JsValue /* Vec<ReceiveCredential> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.list_receive_credentials()?,
+        )?)
+    }
+
+    /// Returns everything connected apps have done with the wallet, newest first
+    #[wasm_bindgen]
+    pub fn get_integration_audit_log(
+        &self,
+    ) -> Result<JsValue /* Vec<IntegrationAuditRecord> */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_integration_audit_log()?,
+        )?)
+    }
+
+    /// Revokes a receive credential so it can't create invoices anymore
+    #[wasm_bindgen]
+    pub fn revoke_receive_credential(&self, client_pubkey: String) -> Result<(), MutinyJsError> {
+        Ok(self.inner.revoke_receive_credential(&client_pubkey)?)
+    }
+
+    /// Applies a rapid gossip sync snapshot that was fetched out of band.
+    /// Returns the timestamp the network graph is now synced to.
+    #[wasm_bindgen]
+    pub async fn import_rgs_snapshot(&self, snapshot: Vec<u8>) -> Result<u32, MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .import_rgs_snapshot(snapshot)
+            .await?)
+    }
+
+    /// Fetches the latest network graph updates right away, like before a big payment.
+    /// Returns the timestamp the network graph is now synced to.
+    #[wasm_bindgen]
+    pub async fn sync_gossip_now(&self) -> Result<u32, MutinyJsError> {
+        Ok(self.inner.node_manager.sync_gossip_now().await?)
+    }
+
+    /// How many channels and nodes are in the network graph and how big it is.
+    #[wasm_bindgen]
+    pub fn get_network_graph_size(&self) -> Result<JsValue /* NetworkGraphSize */, MutinyJsError> {
+        Ok(JsValue::from_serde(
+            &self.inner.node_manager.get_network_graph_size(),
+        )?)
+    }
+
+    /// Gets the size, in bytes, the network graph is pruned down to.
+    #[wasm_bindgen]
+    pub fn get_network_graph_size_limit(&self) -> Result<Option<u64>, MutinyJsError> {
+        Ok(self.inner.node_manager.get_network_graph_size_limit()?)
+    }
+
+    /// Sets the size, in bytes, the network graph is pruned down to.
+    /// Leaving it undefined lets it grow as big as the network.
+    #[wasm_bindgen]
+    pub fn set_network_graph_size_limit(
+        &self,
+        limit_bytes: Option<u64>,
+    ) -> Result<(), MutinyJsError> {
+        Ok(self
+            .inner
+            .node_manager
+            .set_network_graph_size_limit(limit_bytes)?)
+    }
+
+    /// Removes stale channels from the network graph and shrinks it to the size limit.
+    #[wasm_bindgen]
+    pub async fn prune_network_graph(
+        &self,
+    ) -> Result<JsValue /* NetworkGraphSize */, MutinyJsError> {
+        let size = self.inner.node_manager.prune_network_graph().await?;
+        // Sleep to wait for indexed db to finish writing
+        sleep(500).await;
+        Ok(JsValue::from_serde(&size)?)
+    }
+
     /// Resets the scorer and network graph. This can be useful if you get stuck in a bad state.
     #[wasm_bindgen]
     pub async fn reset_router(&self) -> Result<(), MutinyJsError> {
@@ -942,6 +2729,182 @@ impl MutinyWallet {
     pub fn convert_sats_to_btc(sats: u64) -> f64 {
         bitcoin::Amount::from_sat(sats).to_btc()
     }
+
+    /// Lists the lowercase codes of the fiat currencies bitcoin can be priced in.
+    #[wasm_bindgen]
+    pub fn get_fiat_currencies() -> Result<JsValue /* Vec<String> */, MutinyJsError> {
+        let codes: Vec<&str> = FiatCurrency::ALL.iter().map(|c| c.code()).collect();
+        Ok(JsValue::from_serde(&codes)?)
+    }
+
+    /// Formats an amount in a fiat currency with its symbol, ie "$1,234.56".
+    #[wasm_bindgen]
+    pub fn format_fiat(amount: f64, currency: String) -> Result<String, MutinyJsError> {
+        Ok(FiatCurrency::from_str(&currency)?.format(amount))
+    }
+
+    /// Formats a satoshi amount in a fiat currency at the given price of one bitcoin.
+    #[wasm_bindgen]
+    pub fn format_sats_as_fiat(
+        sats: u64,
+        price: f32,
+        currency: String,
+    ) -> Result<String, MutinyJsError> {
+        let currency = FiatCurrency::from_str(&currency)?;
+        Ok(pricing::format_sats_as_fiat(sats, price, currency))
+    }
+
+    /// Creates a WebLN provider that pays and receives with the given node,
+    /// it can be used as `window.webln` by browser extensions and embedded apps.
+    ///
+    /// The options are `{ budgetSats, maxFeeSats, maxFeePercent }`. Payments that fit the
+    /// budget are paid right away, the rest are passed to the `approve` callback which
+    /// returns a boolean, or a promise of one. One of the two is required.
+    #[wasm_bindgen]
+    pub fn webln(
+        &self,
+        from_node: String,
+        options: JsValue, /* WebLnOptions */
+        approve: Option<js_sys::Function>,
+    ) -> Result<WebLn, MutinyJsError> {
+        let from_node = PublicKey::from_str(&from_node)?;
+        let options = options
+            .into_serde::<Option<WebLnOptions>>()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?
+            .unwrap_or_default();
+        WebLn::new(self.inner.node_manager.clone(), from_node, options, approve)
+    }
+
+    /// Creates a restricted point-of-sale handle for this wallet.
+    /// It can only create invoices and check if they were paid.
+    #[wasm_bindgen]
+    pub fn pos_handle(&self) -> PosHandle {
+        PosHandle {
+            inner: mutiny_core::pos::PosHandle::new(self.inner.node_manager.clone()),
+        }
+    }
+}
+
+/// A restricted handle to the wallet for point-of-sale use.
+/// It cannot spend funds, read the seed, or see payments it did not create.
+#[wasm_bindgen]
+pub struct PosHandle {
+    inner: mutiny_core::pos::PosHandle<IndexedDbStorage>,
+}
+
+#[wasm_bindgen]
+impl PosHandle {
+    /// Returns the network of the wallet.
+    #[wasm_bindgen]
+    pub fn get_network(&self) -> String {
+        self.inner.get_network().to_string()
+    }
+
+    /// Creates a lightning invoice. The amount should be in satoshis.
+    #[wasm_bindgen]
+    pub async fn create_invoice(
+        &self,
+        amount: Option<u64>,
+        labels: JsValue, /* Vec<String> */
+    ) -> Result<MutinyInvoice, MutinyJsError> {
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.create_invoice(amount, labels).await?.into())
+    }
+
+    /// Creates a BIP 21 invoice. This creates a new address and a lightning invoice.
+    #[wasm_bindgen]
+    pub async fn create_bip21(
+        &self,
+        amount: Option<u64>,
+        labels: JsValue, /* Vec<String> */
+    ) -> Result<MutinyBip21RawMaterials, MutinyJsError> {
+        let labels: Vec<String> = labels
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        Ok(self.inner.create_bip21(amount, labels).await?.into())
+    }
+
+    /// Returns if an invoice created by this handle has been paid.
+    #[wasm_bindgen]
+    pub async fn is_invoice_paid(&self, payment_hash: String) -> Result<bool, MutinyJsError> {
+        let hash: sha256::Hash = sha256::Hash::from_str(&payment_hash)?;
+        Ok(self.inner.is_invoice_paid(&hash).await?)
+    }
+
+    /// Waits until an invoice created by this handle is paid and returns it.
+    /// Errors if it isn't paid within `timeout_secs` or the invoice expires first.
+    #[wasm_bindgen]
+    pub async fn wait_for_invoice_paid(
+        &self,
+        payment_hash: String,
+        timeout_secs: u64,
+    ) -> Result<MutinyInvoice, MutinyJsError> {
+        let hash: sha256::Hash = sha256::Hash::from_str(&payment_hash)?;
+        Ok(self
+            .inner
+            .wait_for_invoice_paid(&hash, timeout_secs)
+            .await?
+            .into())
+    }
+
+    /// Returns if an address created by this handle has received an on-chain payment.
+    #[wasm_bindgen]
+    pub async fn is_address_paid(&self, address: String) -> Result<bool, MutinyJsError> {
+        let address = Address::from_str(&address)?;
+        Ok(self.inner.is_address_paid(&address).await?)
+    }
+}
+
+/// Builds the fee limit for a payment, none if neither limit is set
+fn max_fee(max_fee_sats: Option<u64>, max_fee_percent: Option<f64>) -> Option<MaxFee> {
+    if max_fee_sats.is_none() && max_fee_percent.is_none() {
+        return None;
+    }
+    Some(MaxFee {
+        max_fee_sats,
+        max_fee_percent,
+    })
+}
+
+/// Builds the multi-path limits for a payment, none if none of them are set
+fn mpp_options(
+    allow_split: Option<bool>,
+    max_parts: Option<u8>,
+    min_part_sats: Option<u64>,
+) -> Option<MppOptions> {
+    if allow_split.is_none() && max_parts.is_none() && min_part_sats.is_none() {
+        return None;
+    }
+    Some(MppOptions {
+        allow_split: allow_split.unwrap_or(true),
+        max_parts,
+        min_part_sats,
+    })
+}
+
+/// Converts the activity for JS and attaches any contacts found in the labels
+fn activity_with_contacts(
+    node_manager: &nodemanager::NodeManager<IndexedDbStorage>,
+    activity: Vec<nodemanager::ActivityItem>,
+) -> Result<JsValue /* Vec<ActivityItem> */, MutinyJsError> {
+    let mut activity: Vec<ActivityItem> = activity.into_iter().map(|a| a.into()).collect();
+
+    // add contacts to the activity
+    let contacts = node_manager.get_contacts()?;
+    for a in activity.iter_mut() {
+        // find labels that have a contact and add them to the item
+        for label in a.labels.iter() {
+            if let Some(contact) = contacts.get(label) {
+                a.contacts.push(Contact::from(contact.clone()));
+            }
+        }
+        // remove labels that have a contact to prevent duplicates
+        a.labels.retain(|l| !contacts.contains_key(l));
+    }
+
+    Ok(JsValue::from_serde(&activity)?)
 }
 
 #[cfg(test)]
@@ -969,6 +2932,7 @@ mod tests {
             None,
             None,
             None,
+            JsValue::UNDEFINED,
         )
         .await
         .expect("mutiny wallet should initialize");
@@ -996,6 +2960,7 @@ mod tests {
             None,
             None,
             None,
+            JsValue::UNDEFINED,
         )
         .await
         .unwrap();
@@ -1003,7 +2968,7 @@ mod tests {
         log!("checking nm");
         assert!(MutinyWallet::has_node_manager(password).await);
         log!("checking seed");
-        assert_eq!(seed.to_string(), nm.show_seed());
+        assert_eq!(Some(seed.to_string()), nm.show_seed());
 
         IndexedDbStorage::clear()
             .await
@@ -1026,6 +2991,7 @@ mod tests {
             None,
             None,
             None,
+            JsValue::UNDEFINED,
         )
         .await
         .expect("mutiny wallet should initialize");
@@ -0,0 +1,52 @@
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::secp256k1::PublicKey;
+use mutiny_core::onionmessage::{CustomOnionMessage, OnionMessageHandler};
+use mutiny_core::peermessage::{CustomPeerMessage, PeerMessageHandler};
+use wasm_bindgen::prelude::*;
+
+/// Hands the custom onion messages of a type to a JS callback,
+/// it is called with the hex encoded data of each message.
+pub(crate) struct JsOnionMessageHandler {
+    callback: js_sys::Function,
+}
+
+impl JsOnionMessageHandler {
+    pub(crate) fn new(callback: js_sys::Function) -> Self {
+        Self { callback }
+    }
+}
+
+// wasm is single threaded, the callback never leaves this thread
+unsafe impl Send for JsOnionMessageHandler {}
+unsafe impl Sync for JsOnionMessageHandler {}
+
+impl OnionMessageHandler for JsOnionMessageHandler {
+    fn handle_onion_message(&self, message: CustomOnionMessage) {
+        let data = JsValue::from_str(&message.data.to_hex());
+        let _ = self.callback.call1(&JsValue::NULL, &data);
+    }
+}
+
+/// Hands the custom peer messages of a type to a JS callback,
+/// it is called with the peer's pubkey and the hex encoded data of each message.
+pub(crate) struct JsPeerMessageHandler {
+    callback: js_sys::Function,
+}
+
+impl JsPeerMessageHandler {
+    pub(crate) fn new(callback: js_sys::Function) -> Self {
+        Self { callback }
+    }
+}
+
+// wasm is single threaded, the callback never leaves this thread
+unsafe impl Send for JsPeerMessageHandler {}
+unsafe impl Sync for JsPeerMessageHandler {}
+
+impl PeerMessageHandler for JsPeerMessageHandler {
+    fn handle_peer_message(&self, from: PublicKey, message: CustomPeerMessage) {
+        let from = JsValue::from_str(&from.to_string());
+        let data = JsValue::from_str(&message.data.to_hex());
+        let _ = self.callback.call2(&JsValue::NULL, &from, &data);
+    }
+}
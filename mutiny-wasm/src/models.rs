@@ -7,6 +7,7 @@ use lnurl::lightning_address::LightningAddress;
 use lnurl::lnurl::LnUrl;
 use mutiny_core::labels::Contact as MutinyContact;
 use mutiny_core::redshift::{RedshiftRecipient, RedshiftStatus};
+use mutiny_core::swaps::SwapStatus;
 use mutiny_core::*;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
@@ -21,6 +22,8 @@ pub enum ActivityType {
     Lightning,
     ChannelOpen,
     ChannelClose,
+    AggregatedLightning,
+    External,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -33,6 +36,11 @@ pub struct ActivityItem {
     pub(crate) labels: Vec<String>,
     pub(crate) contacts: Vec<Contact>,
     pub last_updated: Option<u64>,
+    /// The payment hashes of the individual payments in an aggregated item
+    pub(crate) payment_hashes: Vec<String>,
+    memo: Option<String>,
+    /// What it was worth in fiat when it happened, if the price of bitcoin was recorded
+    fiat_value_at_time: Option<pricing::FiatValue>,
 }
 
 #[wasm_bindgen]
@@ -56,6 +64,21 @@ impl ActivityItem {
     pub fn contacts(&self) -> JsValue /* Vec<Contact> */ {
         JsValue::from_serde(&self.contacts).unwrap()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn payment_hashes(&self) -> JsValue /* Vec<String> */ {
+        JsValue::from_serde(&self.payment_hashes).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn memo(&self) -> Option<String> {
+        self.memo.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn fiat_value_at_time(&self) -> JsValue /* Option<FiatValue> */ {
+        JsValue::from_serde(&self.fiat_value_at_time).unwrap()
+    }
 }
 
 impl From<nodemanager::ActivityItem> for ActivityItem {
@@ -70,15 +93,11 @@ impl From<nodemanager::ActivityItem> for ActivityItem {
             }
             nodemanager::ActivityItem::Lightning(_) => ActivityType::Lightning,
             nodemanager::ActivityItem::ChannelClosed(_) => ActivityType::ChannelClose,
+            nodemanager::ActivityItem::AggregatedLightning(_) => ActivityType::AggregatedLightning,
+            nodemanager::ActivityItem::External(_) => ActivityType::External,
         };
 
-        let id = match a {
-            nodemanager::ActivityItem::OnChain(ref t) => t.txid.to_hex(),
-            nodemanager::ActivityItem::Lightning(ref ln) => ln.payment_hash.to_hex(),
-            nodemanager::ActivityItem::ChannelClosed(ref c) => {
-                c.user_channel_id.map(|c| c.to_hex()).unwrap_or_default()
-            }
-        };
+        let id = a.id();
 
         let (inbound, amount_sats) = match a {
             nodemanager::ActivityItem::OnChain(ref t) => {
@@ -92,6 +111,27 @@ impl From<nodemanager::ActivityItem> for ActivityItem {
             }
             nodemanager::ActivityItem::Lightning(ref ln) => (ln.inbound, ln.amount_sats),
             nodemanager::ActivityItem::ChannelClosed(_) => (false, None),
+            nodemanager::ActivityItem::AggregatedLightning(ref agg) => {
+                (agg.inbound, Some(agg.total_sats))
+            }
+            nodemanager::ActivityItem::External(ref t) => {
+                let inbound = t.amount_sats >= t.sent_sats;
+                let amount_sats = if inbound {
+                    t.amount_sats - t.sent_sats
+                } else {
+                    t.sent_sats - t.amount_sats
+                };
+                (inbound, Some(amount_sats))
+            }
+        };
+
+        let payment_hashes = match a {
+            nodemanager::ActivityItem::AggregatedLightning(ref agg) => agg
+                .payments
+                .iter()
+                .map(|p| p.payment_hash.to_hex())
+                .collect(),
+            _ => vec![],
         };
 
         ActivityItem {
@@ -102,6 +142,57 @@ impl From<nodemanager::ActivityItem> for ActivityItem {
             labels: a.labels(),
             contacts: vec![],
             last_updated: a.last_updated(),
+            payment_hashes,
+            memo: a.memo(),
+            fiat_value_at_time: a.fiat_value_at_time(),
+        }
+    }
+}
+
+/// The result of an on-chain send. Either the txid of the broadcast transaction,
+/// or with an external signer the unsigned base64 encoded PSBT it needs to sign.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[wasm_bindgen]
+pub struct OnChainSendResult {
+    txid: Option<String>,
+    psbt: Option<String>,
+}
+
+#[wasm_bindgen]
+impl OnChainSendResult {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn txid(&self) -> Option<String> {
+        self.txid.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn psbt(&self) -> Option<String> {
+        self.psbt.clone()
+    }
+
+    /// If the PSBT still needs to be signed and then broadcast with `broadcast_psbt`
+    #[wasm_bindgen(getter)]
+    pub fn needs_signature(&self) -> bool {
+        self.psbt.is_some()
+    }
+}
+
+impl From<nodemanager::OnChainSend> for OnChainSendResult {
+    fn from(s: nodemanager::OnChainSend) -> Self {
+        match s {
+            nodemanager::OnChainSend::Broadcast(txid) => OnChainSendResult {
+                txid: Some(txid.to_string()),
+                psbt: None,
+            },
+            nodemanager::OnChainSend::NeedsSignature(psbt) => OnChainSendResult {
+                txid: None,
+                psbt: Some(psbt.to_string()),
+            },
         }
     }
 }
@@ -115,12 +206,19 @@ pub struct MutinyInvoice {
     preimage: Option<String>,
     payee_pubkey: Option<String>,
     pub amount_sats: Option<u64>,
+    pub amount_msats: Option<u64>,
     pub expire: u64,
     pub paid: bool,
     pub fees_paid: Option<u64>,
+    pub fees_paid_msats: Option<u64>,
     pub inbound: bool,
     pub last_updated: u64,
     labels: Vec<String>,
+    pub degraded_hints: bool,
+    lsp_selection: Option<LspSelection>,
+    parts: Vec<PaymentPart>,
+    lnurl_verified_preimage: Option<String>,
+    memo: Option<String>,
 }
 
 #[wasm_bindgen]
@@ -159,6 +257,26 @@ impl MutinyInvoice {
     pub fn labels(&self) -> JsValue /* Vec<String> */ {
         JsValue::from_serde(&self.labels).unwrap()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn lsp_selection(&self) -> JsValue /* Option<LspSelection> */ {
+        JsValue::from_serde(&self.lsp_selection).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn parts(&self) -> JsValue /* Vec<PaymentPart> */ {
+        JsValue::from_serde(&self.parts).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lnurl_verified_preimage(&self) -> Option<String> {
+        self.lnurl_verified_preimage.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn memo(&self) -> Option<String> {
+        self.memo.clone()
+    }
 }
 
 impl From<nodemanager::MutinyInvoice> for MutinyInvoice {
@@ -170,12 +288,61 @@ impl From<nodemanager::MutinyInvoice> for MutinyInvoice {
             preimage: m.preimage,
             payee_pubkey: m.payee_pubkey.map(|p| p.to_hex()),
             amount_sats: m.amount_sats,
+            amount_msats: m.amount_msats,
             expire: m.expire,
             paid: m.paid,
             fees_paid: m.fees_paid,
+            fees_paid_msats: m.fees_paid_msats,
             inbound: m.inbound,
             last_updated: m.last_updated,
             labels: m.labels,
+            degraded_hints: m.degraded_hints,
+            lsp_selection: m.lsp_selection,
+            parts: m.parts,
+            lnurl_verified_preimage: m.lnurl_verified_preimage,
+            memo: m.memo,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[wasm_bindgen]
+pub struct FiatInvoice {
+    invoice: MutinyInvoice,
+    pub fiat_amount: f64,
+    currency: String,
+    pub rate: f32,
+    pub quoted_at: u64,
+    pub expires_at: u64,
+}
+
+#[wasm_bindgen]
+impl FiatInvoice {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn invoice(&self) -> MutinyInvoice {
+        self.invoice.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn currency(&self) -> String {
+        self.currency.clone()
+    }
+}
+
+impl From<fiat::FiatInvoice> for FiatInvoice {
+    fn from(f: fiat::FiatInvoice) -> Self {
+        FiatInvoice {
+            invoice: f.invoice.into(),
+            fiat_amount: f.quote.fiat_amount,
+            currency: f.quote.currency.clone(),
+            rate: f.quote.rate,
+            quoted_at: f.quote.quoted_at,
+            expires_at: f.quote.expires_at(),
         }
     }
 }
@@ -189,6 +356,11 @@ pub struct MutinyPeer {
     color: Option<String>,
     label: Option<String>,
     pub is_connected: bool,
+    reconnect_policy: ReconnectPolicy,
+    features: Vec<String>,
+    pub uptime_secs: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub num_channels: usize,
 }
 
 #[wasm_bindgen]
@@ -222,6 +394,16 @@ impl MutinyPeer {
     pub fn label(&self) -> Option<String> {
         self.label.clone()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn reconnect_policy(&self) -> JsValue /* ReconnectPolicy */ {
+        JsValue::from_serde(&self.reconnect_policy).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn features(&self) -> JsValue /* Vec<String> */ {
+        JsValue::from_serde(&self.features).unwrap()
+    }
 }
 
 impl From<nodemanager::MutinyPeer> for MutinyPeer {
@@ -233,6 +415,11 @@ impl From<nodemanager::MutinyPeer> for MutinyPeer {
             color: m.color,
             label: m.label,
             is_connected: m.is_connected,
+            reconnect_policy: m.reconnect_policy,
+            features: m.features,
+            uptime_secs: m.uptime_secs,
+            latency_ms: m.latency_ms,
+            num_channels: m.num_channels,
         }
     }
 }
@@ -241,6 +428,7 @@ impl From<nodemanager::MutinyPeer> for MutinyPeer {
 #[wasm_bindgen]
 pub struct MutinyChannel {
     pub balance: u64,
+    pub balance_msats: u64,
     pub size: u64,
     pub reserve: u64,
     outpoint: Option<String>,
@@ -279,6 +467,7 @@ impl From<nodemanager::MutinyChannel> for MutinyChannel {
     fn from(m: nodemanager::MutinyChannel) -> Self {
         MutinyChannel {
             balance: m.balance,
+            balance_msats: m.balance_msats,
             size: m.size,
             reserve: m.reserve,
             outpoint: m.outpoint.map(|o| o.to_string()),
@@ -295,7 +484,10 @@ impl From<nodemanager::MutinyChannel> for MutinyChannel {
 pub struct ChannelClosure {
     channel_id: Option<[u8; 32]>,
     node_id: Option<PublicKey>,
+    kind: nodemanager::ChannelClosureKind,
     reason: String,
+    closing_txid: Option<String>,
+    pub swept_sats: Option<u64>,
     pub timestamp: u64,
 }
 
@@ -316,10 +508,34 @@ impl ChannelClosure {
         self.node_id.map(|n| n.to_string())
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        match self.kind {
+            nodemanager::ChannelClosureKind::Cooperative => "Cooperative".to_string(),
+            nodemanager::ChannelClosureKind::ForceClosedByUs => "ForceClosedByUs".to_string(),
+            nodemanager::ChannelClosureKind::ForceClosedByPeer => "ForceClosedByPeer".to_string(),
+            nodemanager::ChannelClosureKind::CommitmentConfirmed => {
+                "CommitmentConfirmed".to_string()
+            }
+            nodemanager::ChannelClosureKind::FundingTimedOut => "FundingTimedOut".to_string(),
+            nodemanager::ChannelClosureKind::DisconnectedPeer => "DisconnectedPeer".to_string(),
+            nodemanager::ChannelClosureKind::ProcessingError => "ProcessingError".to_string(),
+            nodemanager::ChannelClosureKind::OutdatedChannelManager => {
+                "OutdatedChannelManager".to_string()
+            }
+            nodemanager::ChannelClosureKind::Unknown => "Unknown".to_string(),
+        }
+    }
+
     #[wasm_bindgen(getter)]
     pub fn reason(&self) -> String {
         self.reason.clone()
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn closing_txid(&self) -> Option<String> {
+        self.closing_txid.clone()
+    }
 }
 
 impl PartialOrd for ChannelClosure {
@@ -339,7 +555,10 @@ impl From<nodemanager::ChannelClosure> for ChannelClosure {
         ChannelClosure {
             channel_id: c.channel_id,
             node_id: c.node_id,
+            kind: c.kind,
             reason: c.reason,
+            closing_txid: c.closing_txid.map(|t| t.to_string()),
+            swept_sats: c.swept_sats,
             timestamp: c.timestamp,
         }
     }
@@ -351,7 +570,9 @@ pub struct MutinyBalance {
     pub confirmed: u64,
     pub unconfirmed: u64,
     pub lightning: u64,
+    pub lightning_msats: u64,
     pub force_close: u64,
+    pub retired: u64,
 }
 
 #[wasm_bindgen]
@@ -368,7 +589,93 @@ impl From<nodemanager::MutinyBalance> for MutinyBalance {
             confirmed: m.confirmed,
             unconfirmed: m.unconfirmed,
             lightning: m.lightning,
+            lightning_msats: m.lightning_msats,
             force_close: m.force_close,
+            retired: m.retired,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[wasm_bindgen]
+pub struct PaymentEstimate {
+    pub amount_msats: u64,
+    pub fee_msats: u64,
+    pub cltv_expiry_delta: u32,
+    pub num_paths: usize,
+}
+
+#[wasm_bindgen]
+impl PaymentEstimate {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+}
+
+impl From<nodemanager::PaymentEstimate> for PaymentEstimate {
+    fn from(m: nodemanager::PaymentEstimate) -> Self {
+        PaymentEstimate {
+            amount_msats: m.amount_msats,
+            fee_msats: m.fee_msats,
+            cltv_expiry_delta: m.cltv_expiry_delta,
+            num_paths: m.num_paths,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[wasm_bindgen]
+pub struct FeeEstimate {
+    pub amount_msats: u64,
+    pub min_fee_msats: u64,
+    pub max_fee_msats: u64,
+    pub num_hops: usize,
+}
+
+#[wasm_bindgen]
+impl FeeEstimate {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+}
+
+impl From<nodemanager::FeeEstimate> for FeeEstimate {
+    fn from(m: nodemanager::FeeEstimate) -> Self {
+        FeeEstimate {
+            amount_msats: m.amount_msats,
+            min_fee_msats: m.min_fee_msats,
+            max_fee_msats: m.max_fee_msats,
+            num_hops: m.num_hops,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[wasm_bindgen]
+pub struct FeeSummary {
+    pub lightning_fees: u64,
+    pub lsp_fees: u64,
+    pub onchain_fees: u64,
+    pub total: u64,
+}
+
+#[wasm_bindgen]
+impl FeeSummary {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+}
+
+impl From<nodemanager::FeeSummary> for FeeSummary {
+    fn from(m: nodemanager::FeeSummary) -> Self {
+        FeeSummary {
+            lightning_fees: m.lightning_fees,
+            lsp_fees: m.lsp_fees,
+            onchain_fees: m.onchain_fees,
+            total: m.total,
         }
     }
 }
@@ -531,6 +838,7 @@ pub struct Redshift {
     pub sats_sent: u64,
     pub change_amt: Option<u64>,
     pub fees_paid: u64,
+    pub attempts: u32,
 }
 
 #[wasm_bindgen]
@@ -559,6 +867,7 @@ impl Redshift {
             RedshiftStatus::ClosingChannels => "ClosingChannels".to_string(),
             RedshiftStatus::Completed => "Completed".to_string(),
             RedshiftStatus::Failed(_) => "Failed".to_string(),
+            RedshiftStatus::Cancelled => "Cancelled".to_string(),
         }
     }
 
@@ -620,6 +929,133 @@ impl From<redshift::Redshift> for Redshift {
             sats_sent: rs.sats_sent,
             change_amt: rs.change_amt,
             fees_paid: rs.fees_paid,
+            attempts: rs.attempts,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[wasm_bindgen]
+pub struct SwapOutQuote {
+    pub amount_sats: u64,
+    pub service_fee_sats: u64,
+    pub lockup_fee_sats: u64,
+    pub claim_fee_sats: u64,
+    pub onchain_amount_sats: u64,
+    pub min_amount_sats: u64,
+    pub max_amount_sats: u64,
+}
+
+#[wasm_bindgen]
+impl SwapOutQuote {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+}
+
+impl From<swaps::SwapOutQuote> for SwapOutQuote {
+    fn from(q: swaps::SwapOutQuote) -> Self {
+        SwapOutQuote {
+            amount_sats: q.amount_sats,
+            service_fee_sats: q.service_fee_sats,
+            lockup_fee_sats: q.lockup_fee_sats,
+            claim_fee_sats: q.claim_fee_sats,
+            onchain_amount_sats: q.onchain_amount_sats,
+            min_amount_sats: q.min_amount_sats,
+            max_amount_sats: q.max_amount_sats,
+        }
+    }
+}
+
+/// A swap of lightning balance on-chain, without the preimage and claim key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[wasm_bindgen]
+pub struct SwapOut {
+    id: String,
+    status: SwapStatus,
+    sending_node: PublicKey,
+    pub amount_sats: u64,
+    pub onchain_amount_sats: u64,
+    address: Address,
+    lockup_address: Address,
+    pub timeout_block_height: u32,
+    lockup_txid: Option<String>,
+    claim_txid: Option<String>,
+    pub created_at: u64,
+}
+
+#[wasm_bindgen]
+impl SwapOut {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> JsValue {
+        JsValue::from_serde(&serde_json::to_value(self).unwrap()).unwrap()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        match self.status {
+            SwapStatus::Created => "Created".to_string(),
+            SwapStatus::InvoicePaid => "InvoicePaid".to_string(),
+            SwapStatus::Claimed => "Claimed".to_string(),
+            SwapStatus::Completed => "Completed".to_string(),
+            SwapStatus::Failed(_) => "Failed".to_string(),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        match &self.status {
+            SwapStatus::Failed(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sending_node(&self) -> String {
+        self.sending_node.to_hex()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lockup_address(&self) -> String {
+        self.lockup_address.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lockup_txid(&self) -> Option<String> {
+        self.lockup_txid.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn claim_txid(&self) -> Option<String> {
+        self.claim_txid.clone()
+    }
+}
+
+impl From<swaps::SwapOut> for SwapOut {
+    fn from(s: swaps::SwapOut) -> Self {
+        SwapOut {
+            id: s.id,
+            status: s.status,
+            sending_node: s.sending_node,
+            amount_sats: s.amount_sats,
+            onchain_amount_sats: s.onchain_amount_sats,
+            address: s.address,
+            lockup_address: s.lockup_address,
+            timeout_block_height: s.timeout_block_height,
+            lockup_txid: s.lockup_txid.map(|t| t.to_string()),
+            claim_txid: s.claim_txid.map(|t| t.to_string()),
+            created_at: s.created_at,
         }
     }
 }
@@ -800,3 +1236,45 @@ impl From<MutinyContact> for Contact {
         }
     }
 }
+
+/// Optional settings for paying an invoice, given to `pay_invoice` as an object
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PayInvoiceOptions {
+    /// The most the routing fee can be in sats
+    pub max_fee_sats: Option<u64>,
+    /// The most the routing fee can be as a percent of the amount
+    pub max_fee_percent: Option<f64>,
+    /// Funding outpoints of the channels the payment has to go out of
+    pub outgoing_channels: Option<Vec<String>>,
+    /// Whether the payment can be split into parts, true by default
+    pub allow_split: Option<bool>,
+    /// The most parts the payment can be split into
+    pub max_parts: Option<u8>,
+    /// The smallest a part can be in sats
+    pub min_part_sats: Option<u64>,
+}
+
+/// Optional settings for creating a wallet, given to the constructor as an object
+#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WalletOptions {
+    /// An xpub or output descriptor of an external signer that holds the on-chain keys
+    pub external_signer: Option<String>,
+    /// An xpub or output descriptor to watch, no mnemonic is used and lightning is disabled
+    pub watch_only: Option<String>,
+    /// The account the wallet's keys are derived from, 0 by default
+    pub account_index: Option<u32>,
+    /// A mempool.space compatible api to get fee estimates from, like `https://mempool.space/api`
+    pub fee_source_url: Option<String>,
+    /// How many unused addresses in a row the on-chain sync looks at
+    pub stop_gap: Option<usize>,
+    /// How many addresses ahead of the last used one are watched
+    pub address_lookahead: Option<u32>,
+    /// A remote backup server to mirror channel state and payments to
+    pub vss_url: Option<String>,
+    /// Restore from the remote backup server if there are no nodes saved locally
+    pub restore_from_vss: bool,
+    /// A channel backup to recover from if there are no nodes saved locally
+    pub channel_backup: Option<String>,
+}
@@ -0,0 +1,428 @@
+use crate::error::MutinyJsError;
+use crate::indexed_db::IndexedDbStorage;
+use bitcoin::secp256k1::PublicKey;
+use gloo_utils::format::JsValueSerdeExt;
+use lightning_invoice::Invoice;
+use mutiny_core::audit::{Integration, IntegrationAuditRecord};
+use mutiny_core::error::MutinyError;
+use mutiny_core::nodemanager::{MaxFee, MutinyInvoice, NodeManager, PaymentOptions};
+use mutiny_core::signing::SigningScope;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// The routing fee cap used when the provider's options don't set one, in percent
+const DEFAULT_WEBLN_MAX_FEE_PERCENT: f64 = 1.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct WebLnNode {
+    pubkey: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct GetInfoResponse {
+    node: WebLnNode,
+    methods: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct SendPaymentResponse {
+    preimage: String,
+}
+
+/// WebLN amounts can be numbers or strings
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+enum WebLnAmount {
+    Number(u64),
+    String(String),
+}
+
+impl WebLnAmount {
+    fn sats(&self) -> Result<u64, MutinyJsError> {
+        match self {
+            WebLnAmount::Number(n) => Ok(*n),
+            WebLnAmount::String(s) => s.parse().map_err(|_| MutinyJsError::BadAmountError),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct RequestInvoiceArgs {
+    amount: Option<WebLnAmount>,
+    default_amount: Option<WebLnAmount>,
+    default_memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct RequestInvoiceResponse {
+    payment_request: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct SignMessageResponse {
+    message: String,
+    signature: String,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct KeysendArgs {
+    destination: String,
+    amount: WebLnAmount,
+    #[serde(default)]
+    custom_records: HashMap<String, String>,
+}
+
+/// Limits on what a WebLN provider may pay
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebLnOptions {
+    /// Sats the provider may spend without asking, fees included
+    budget_sats: Option<u64>,
+    /// Caps the routing fees of every payment, defaults to 1% of the amount
+    max_fee_sats: Option<u64>,
+    max_fee_percent: Option<f64>,
+}
+
+impl WebLnOptions {
+    fn max_fee(&self) -> MaxFee {
+        match (self.max_fee_sats, self.max_fee_percent) {
+            (None, None) => MaxFee {
+                max_fee_sats: None,
+                max_fee_percent: Some(DEFAULT_WEBLN_MAX_FEE_PERCENT),
+            },
+            (max_fee_sats, max_fee_percent) => MaxFee {
+                max_fee_sats,
+                max_fee_percent,
+            },
+        }
+    }
+}
+
+/// A payment the approval callback is asked about
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct PaymentApprovalRequest {
+    method: String,
+    /// The invoice for `sendPayment` or the node for `keysend`
+    destination: String,
+    amount_sats: u64,
+    max_fee_sats: u64,
+}
+
+const WEBLN_METHODS: [&str; 6] = [
+    "enable",
+    "getInfo",
+    "sendPayment",
+    "makeInvoice",
+    "signMessage",
+    "keysend",
+];
+
+/// A WebLN provider backed by one of the wallet's nodes,
+/// so browser extensions and embedded apps can use it as `window.webln`.
+///
+/// Everything but [WebLn::enable] errors until the provider is enabled.
+/// Payments and invoices are recorded in the integration audit log.
+///
+/// Payments are paid without asking while they fit the spending budget,
+/// otherwise the approval callback is asked. Without either they are refused.
+#[wasm_bindgen]
+pub struct WebLn {
+    node_manager: Arc<NodeManager<IndexedDbStorage>>,
+    from_node: PublicKey,
+    enabled: AtomicBool,
+    max_fee: MaxFee,
+    /// Sats left in the spending budget
+    budget_sats: AtomicU64,
+    approve: Option<js_sys::Function>,
+}
+
+impl WebLn {
+    /// Errors if there is neither a spending budget nor an approval callback
+    pub(crate) fn new(
+        node_manager: Arc<NodeManager<IndexedDbStorage>>,
+        from_node: PublicKey,
+        options: WebLnOptions,
+        approve: Option<js_sys::Function>,
+    ) -> Result<Self, MutinyJsError> {
+        if options.budget_sats.unwrap_or_default() == 0 && approve.is_none() {
+            return Err(MutinyJsError::InvalidArgumentsError);
+        }
+        Ok(Self {
+            node_manager,
+            from_node,
+            enabled: AtomicBool::new(false),
+            max_fee: options.max_fee(),
+            budget_sats: AtomicU64::new(options.budget_sats.unwrap_or_default()),
+            approve,
+        })
+    }
+
+    fn check_enabled(&self) -> Result<(), MutinyJsError> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return Err(MutinyJsError::NotRunning);
+        }
+        Ok(())
+    }
+
+    /// Takes the most the payment can cost out of the budget, or asks the
+    /// approval callback. Returns if the budget was used.
+    async fn authorize_payment(
+        &self,
+        method: &str,
+        destination: String,
+        amount_sats: u64,
+    ) -> Result<bool, MutinyJsError> {
+        let max_fee_sats = self.max_fee.limit_msat(amount_sats * 1_000) / 1_000;
+        let cost = amount_sats + max_fee_sats;
+        let from_budget = self
+            .budget_sats
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| {
+                b.checked_sub(cost)
+            })
+            .is_ok();
+        if from_budget {
+            return Ok(true);
+        }
+
+        let Some(approve) = self.approve.as_ref() else {
+            return Err(MutinyJsError::PaymentNotApproved);
+        };
+        let request = JsValue::from_serde(&PaymentApprovalRequest {
+            method: method.to_string(),
+            destination,
+            amount_sats,
+            max_fee_sats,
+        })?;
+        let answer = approve
+            .call1(&JsValue::NULL, &request)
+            .map_err(|_| MutinyJsError::PaymentNotApproved)?;
+        // the callback can return a promise so it can prompt the user
+        let answer = match answer.dyn_into::<js_sys::Promise>() {
+            Ok(promise) => JsFuture::from(promise)
+                .await
+                .map_err(|_| MutinyJsError::PaymentNotApproved)?,
+            Err(answer) => answer,
+        };
+        if answer.as_bool().unwrap_or(false) {
+            Ok(false)
+        } else {
+            Err(MutinyJsError::PaymentNotApproved)
+        }
+    }
+
+    /// Gives back the part of a budgeted payment that wasn't spent
+    fn settle_budget(
+        &self,
+        amount_sats: u64,
+        from_budget: bool,
+        result: &Result<MutinyInvoice, MutinyError>,
+    ) {
+        if !from_budget {
+            return;
+        }
+        let max_fee_sats = self.max_fee.limit_msat(amount_sats * 1_000) / 1_000;
+        let spent = match result {
+            Ok(invoice) => amount_sats + invoice.fees_paid.unwrap_or_default(),
+            Err(_) => 0,
+        };
+        let refund = (amount_sats + max_fee_sats).saturating_sub(spent);
+        self.budget_sats.fetch_add(refund, Ordering::Relaxed);
+    }
+
+    fn audit<T>(&self, action: &str, amount_sats: Option<u64>, result: &Result<T, MutinyError>) {
+        self.node_manager
+            .audit_integration_action(IntegrationAuditRecord::new(
+                Integration::WebLn,
+                "webln".to_string(),
+                action,
+                amount_sats,
+                result.as_ref().err().map(|e| e.to_string()),
+            ));
+    }
+}
+
+#[wasm_bindgen]
+impl WebLn {
+    #[wasm_bindgen]
+    pub async fn enable(&self) -> Result<(), MutinyJsError> {
+        self.enabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = getInfo)]
+    pub async fn get_info(&self) -> Result<JsValue /* GetInfoResponse */, MutinyJsError> {
+        self.check_enabled()?;
+        let info = GetInfoResponse {
+            node: WebLnNode {
+                pubkey: self.from_node.to_string(),
+            },
+            methods: WEBLN_METHODS.iter().map(|m| m.to_string()).collect(),
+        };
+        Ok(JsValue::from_serde(&info)?)
+    }
+
+    #[wasm_bindgen(js_name = sendPayment)]
+    pub async fn send_payment(
+        &self,
+        payment_request: String,
+    ) -> Result<JsValue /* SendPaymentResponse */, MutinyJsError> {
+        self.check_enabled()?;
+        let invoice = Invoice::from_str(&payment_request)?;
+        // the amount is needed to check it against the budget
+        let amount_sats = invoice
+            .amount_milli_satoshis()
+            .map(|msats| msats / 1_000)
+            .ok_or(MutinyJsError::BadAmountError)?;
+        let from_budget = self
+            .authorize_payment("sendPayment", payment_request, amount_sats)
+            .await?;
+
+        let result = self
+            .node_manager
+            .pay_invoice(
+                &self.from_node,
+                &invoice,
+                None,
+                PaymentOptions {
+                    max_fee: Some(self.max_fee),
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await;
+        self.settle_budget(amount_sats, from_budget, &result);
+        self.audit("sendPayment", Some(amount_sats), &result);
+
+        let preimage = result?.preimage.ok_or(MutinyJsError::RoutingFailed)?;
+        Ok(JsValue::from_serde(&SendPaymentResponse { preimage })?)
+    }
+
+    /// Takes an amount, or an object with `amount`, `defaultAmount` and `defaultMemo`
+    #[wasm_bindgen(js_name = makeInvoice)]
+    pub async fn make_invoice(
+        &self,
+        args: JsValue, /* string | number | RequestInvoiceArgs */
+    ) -> Result<JsValue /* RequestInvoiceResponse */, MutinyJsError> {
+        self.check_enabled()?;
+        let args = match args.into_serde::<WebLnAmount>() {
+            Ok(amount) => RequestInvoiceArgs {
+                amount: Some(amount),
+                ..Default::default()
+            },
+            Err(_) => args
+                .into_serde::<Option<RequestInvoiceArgs>>()
+                .map_err(|_| MutinyJsError::InvalidArgumentsError)?
+                .unwrap_or_default(),
+        };
+        let amount = args
+            .amount
+            .or(args.default_amount)
+            .map(|a| a.sats())
+            .transpose()?;
+        let labels = args.default_memo.into_iter().collect();
+
+        let result = self.node_manager.create_invoice(amount, labels).await;
+        self.audit("makeInvoice", amount, &result);
+
+        let payment_request = result?
+            .bolt11
+            .ok_or(MutinyJsError::InvoiceCreationFailed)?
+            .to_string();
+        Ok(JsValue::from_serde(&RequestInvoiceResponse {
+            payment_request,
+        })?)
+    }
+
+    /// Signs in the node ownership scope, verify with `MutinyWallet.verify_node_ownership`.
+    #[wasm_bindgen(js_name = signMessage)]
+    pub async fn sign_message(
+        &self,
+        message: String,
+    ) -> Result<JsValue /* SignMessageResponse */, MutinyJsError> {
+        self.check_enabled()?;
+        let result = self
+            .node_manager
+            .sign_message(&self.from_node, SigningScope::NodeOwnership, &message)
+            .await;
+        self.audit("signMessage", None, &result);
+
+        Ok(JsValue::from_serde(&SignMessageResponse {
+            message,
+            signature: result?,
+        })?)
+    }
+
+    /// Custom records are not supported and are rejected
+    #[wasm_bindgen]
+    pub async fn keysend(
+        &self,
+        args: JsValue, /* KeysendArgs */
+    ) -> Result<JsValue /* SendPaymentResponse */, MutinyJsError> {
+        self.check_enabled()?;
+        let args: KeysendArgs = args
+            .into_serde()
+            .map_err(|_| MutinyJsError::InvalidArgumentsError)?;
+        if !args.custom_records.is_empty() {
+            return Err(MutinyJsError::InvalidArgumentsError);
+        }
+        let to_node = PublicKey::from_str(&args.destination)?;
+        let amount = args.amount.sats()?;
+        let from_budget = self
+            .authorize_payment("keysend", args.destination, amount)
+            .await?;
+
+        let result = self
+            .node_manager
+            .keysend(&self.from_node, to_node, amount, Some(self.max_fee), vec![])
+            .await;
+        self.settle_budget(amount, from_budget, &result);
+        self.audit("keysend", Some(amount), &result);
+
+        let preimage = result?.preimage.ok_or(MutinyJsError::RoutingFailed)?;
+        Ok(JsValue::from_serde(&SendPaymentResponse { preimage })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::*;
+    use wasm_bindgen_test::{wasm_bindgen_test as test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_webln_args() {
+        log!("parsing webln args");
+
+        let args: RequestInvoiceArgs =
+            serde_json::from_str(r#"{"defaultAmount": "21", "defaultMemo": "tip"}"#).unwrap();
+        assert_eq!(args.amount, None);
+        assert_eq!(args.default_amount.unwrap().sats().unwrap(), 21);
+        assert_eq!(args.default_memo, Some("tip".to_string()));
+
+        let keysend: KeysendArgs =
+            serde_json::from_str(r#"{"destination": "02abc", "amount": 1000}"#).unwrap();
+        assert_eq!(keysend.amount.sats().unwrap(), 1_000);
+        assert!(keysend.custom_records.is_empty());
+
+        assert!(WebLnAmount::String("abc".to_string()).sats().is_err());
+
+        let options: WebLnOptions = serde_json::from_str(r#"{"budgetSats": 10000}"#).unwrap();
+        assert_eq!(options.budget_sats, Some(10_000));
+        assert_eq!(options.max_fee().limit_msat(1_000_000), 10_000);
+
+        let options: WebLnOptions = serde_json::from_str(r#"{"maxFeeSats": 5}"#).unwrap();
+        assert_eq!(options.max_fee().limit_msat(1_000_000), 5_000);
+    }
+}